@@ -15,6 +15,7 @@
  */
 
 use std::path::Path;
+use std::sync::atomic::Ordering;
 use std::time::Instant;
 use std::{cell::RefCell, rc::Rc, sync::Arc};
 
@@ -25,7 +26,8 @@ use deepflow_agent::{
     _PacketDirection as PacketDirection, _TcpPerf as TcpPerf,
     _benchmark_report as benchmark_report,
     _benchmark_session_peer_seq_no_assert as benchmark_session_peer_seq_no_assert,
-    _meta_flow_perf_update as meta_flow_perf_update,
+    _meta_flow_perf_update as meta_flow_perf_update, _pooled_string as pooled_string,
+    _recycle_string as recycle_string, _string_pool_counter as string_pool_counter,
     common::l7_protocol_log::{L7ProtocolParserInterface, ParseParam},
     utils::test::Capture,
     HttpLog,
@@ -62,6 +64,26 @@ pub(super) fn bench(c: &mut Criterion) {
             benchmark_session_peer_seq_no_assert(false);
         })
     });
+    c.bench_function("string_alloc", |b| {
+        b.iter(|| {
+            let s = black_box("SELECT id, name FROM users WHERE id = ?").to_owned();
+            drop(black_box(s));
+        })
+    });
+    c.bench_function("string_pool", |b| {
+        b.iter_custom(|iters| {
+            let miss = string_pool_counter().miss.load(Ordering::Relaxed);
+            let start = Instant::now();
+            for _ in 0..iters {
+                let s = pooled_string(black_box("SELECT id, name FROM users WHERE id = ?"));
+                recycle_string(black_box(s));
+            }
+            let elapsed = start.elapsed();
+            // only the first string is allocated, others reuse the recycled buffer
+            assert!(string_pool_counter().miss.load(Ordering::Relaxed) - miss <= 1);
+            elapsed
+        })
+    });
     c.bench_function("parse_http_v1_log", |b| {
         b.iter_custom(|iters| {
             let capture = Capture::load_pcap(
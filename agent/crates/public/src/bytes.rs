@@ -17,6 +17,7 @@
 use std::io::{self, Write};
 
 use flate2::write::ZlibEncoder;
+use thiserror::Error;
 
 pub fn read_i16_be(bs: &[u8]) -> i16 {
     assert!(bs.len() >= 2);
@@ -73,7 +74,204 @@ pub fn write_u64_be(bs: &mut [u8], v: u64) {
     bs[0..8].copy_from_slice(v.to_be_bytes().as_slice())
 }
 
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("read {len} bytes at offset {offset} of {total} bytes payload")]
+pub struct ReadError {
+    pub offset: usize,
+    pub len: usize,
+    pub total: usize,
+}
+
+// Cursor over a payload, reads return ReadError instead of panicking on truncated
+// payloads and the cursor is not moved if a read fails.
+#[derive(Debug, Clone, Copy)]
+pub struct PayloadReader<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PayloadReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, offset: 0 }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.offset
+    }
+
+    pub fn remaining_bytes(&self) -> &'a [u8] {
+        &self.buf[self.offset..]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    fn check(&self, offset: usize, len: usize) -> Result<(), ReadError> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.buf.len() => Ok(()),
+            _ => Err(ReadError {
+                offset,
+                len,
+                total: self.buf.len(),
+            }),
+        }
+    }
+
+    // moves the cursor to an absolute offset
+    pub fn seek(&mut self, offset: usize) -> Result<(), ReadError> {
+        self.check(offset, 0)?;
+        self.offset = offset;
+        Ok(())
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<(), ReadError> {
+        self.check(self.offset, n)?;
+        self.offset += n;
+        Ok(())
+    }
+
+    pub fn peek_bytes(&self, n: usize) -> Result<&'a [u8], ReadError> {
+        self.check(self.offset, n)?;
+        Ok(&self.buf[self.offset..self.offset + n])
+    }
+
+    pub fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], ReadError> {
+        let bs = self.peek_bytes(n)?;
+        self.offset += n;
+        Ok(bs)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, ReadError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub fn read_i16_be(&mut self) -> Result<i16, ReadError> {
+        Ok(i16::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u16_be(&mut self) -> Result<u16, ReadError> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u16_le(&mut self) -> Result<u16, ReadError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_i32_be(&mut self) -> Result<i32, ReadError> {
+        Ok(i32::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32_be(&mut self) -> Result<u32, ReadError> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32_le(&mut self) -> Result<u32, ReadError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64_be(&mut self) -> Result<u64, ReadError> {
+        Ok(u64::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64_le(&mut self) -> Result<u64, ReadError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    // little endian base 128 varint, at most 10 bytes
+    pub fn read_unsigned_varint(&mut self) -> Result<u64, ReadError> {
+        let mut x = 0u64;
+        for (i, b) in self.remaining_bytes().iter().take(10).enumerate() {
+            x |= ((b & 0x7F) as u64) << (7 * i);
+            if b & 0x80 == 0 {
+                self.offset += i + 1;
+                return Ok(x);
+            }
+        }
+        Err(ReadError {
+            offset: self.offset,
+            len: self.remaining().min(10) + 1,
+            total: self.buf.len(),
+        })
+    }
+
+    // zigzag encoded VARINT and VARLONG
+    pub fn read_varint(&mut self) -> Result<i64, ReadError> {
+        let x = self.read_unsigned_varint()?;
+        Ok((x >> 1) as i64 ^ -((x & 1) as i64))
+    }
+
+    // INT16 length followed by the bytes, negative length for null
+    pub fn read_string(&mut self) -> Result<&'a [u8], ReadError> {
+        let mut r = *self;
+        let len = r.read_i16_be()?;
+        let bs = r.read_bytes(len.max(0) as usize)?;
+        *self = r;
+        Ok(bs)
+    }
+
+    // UNSIGNED_VARINT length + 1 followed by the bytes, 0 for null
+    pub fn read_compact_string(&mut self) -> Result<&'a [u8], ReadError> {
+        let mut r = *self;
+        let len = r.read_unsigned_varint()?;
+        let bs = r.read_bytes(len.saturating_sub(1) as usize)?;
+        *self = r;
+        Ok(bs)
+    }
+}
+
 pub fn compress_entry(encoder: &mut ZlibEncoder<Vec<u8>>, entry: &[u8]) -> io::Result<Vec<u8>> {
     encoder.write_all(entry)?;
     encoder.reset(vec![])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_reader() {
+        let payload = [
+            0, 1, 0xff, 0xfe, 0, 0, 0, 2, 0, 3, b'a', b'b', b'c', 0x04, b'x', b'y',
+        ];
+        let mut r = PayloadReader::new(&payload);
+        assert_eq!(r.read_u16_be(), Ok(1));
+        assert_eq!(r.read_i16_be(), Ok(-2));
+        assert_eq!(r.read_i32_be(), Ok(2));
+        assert_eq!(r.read_string(), Ok(&b"abc"[..]));
+        // compact string of length 3 is truncated
+        assert_eq!(
+            r.read_compact_string(),
+            Err(ReadError {
+                offset: 14,
+                len: 3,
+                total: 16
+            })
+        );
+        assert_eq!(r.offset(), 13);
+        assert_eq!(r.read_unsigned_varint(), Ok(4));
+        assert!(r.read_u32_be().is_err());
+        assert_eq!(r.read_bytes(2), Ok(&b"xy"[..]));
+        assert!(r.is_empty());
+        assert!(r.skip(usize::MAX).is_err());
+
+        let mut r = PayloadReader::new(&[0x96, 0x01, 0x80]);
+        assert_eq!(r.read_unsigned_varint(), Ok(150));
+        assert!(r.read_unsigned_varint().is_err());
+        assert_eq!(r.remaining(), 1);
+
+        // zigzag varint
+        let mut r = PayloadReader::new(&[0x01, 0x04, 0xac, 0x02]);
+        assert_eq!(r.read_varint(), Ok(-1));
+        assert_eq!(r.read_varint(), Ok(2));
+        assert_eq!(r.read_varint(), Ok(150));
+    }
+}
@@ -139,6 +139,8 @@ pub const FIELD_LEN_ICMP_TYPE_CODE: usize = 2;
 pub const FIELD_LEN_ICMP_ID_SEQ: usize = 4;
 pub const FIELD_LEN_ICMP_REST: usize = 28;
 
+pub const ICMP_CODE_FRAGMENTATION_NEEDED: u8 = 4;
+
 pub const FIELD_LEN_SPORT: usize = 2;
 pub const FIELD_LEN_DPORT: usize = 2;
 
@@ -173,6 +175,10 @@ pub const ARP_HEADER_SIZE: usize = 28;
 pub const IPV4_HEADER_SIZE: usize = 20;
 pub const IPV6_HEADER_SIZE: usize = 40;
 pub const UDP_HEADER_SIZE: usize = 8;
+pub const ICMP_HEADER_SIZE: usize = 8;
+pub const SCTP_COMMON_HEADER_SIZE: usize = 12;
+pub const SCTP_CHUNK_HEADER_SIZE: usize = 4;
+pub const SCTP_DATA_CHUNK_HEADER_SIZE: usize = 16;
 pub const VXLAN_HEADER_SIZE: usize = 8;
 pub const GRE_HEADER_SIZE: usize = 12;
 pub const ERSPAN_HEADER_SIZE: usize = 12;
@@ -404,6 +410,12 @@ pub const GENEVE_VNI_OFFSET: usize = 4;
 
 pub const GENEVE_OPTION_LENGTH_MASK: u8 = 0x3f;
 
+pub const GENEVE_OPTION_HEADER_SIZE: usize = 4;
+pub const GENEVE_OPTION_CLASS_OFFSET: usize = 0;
+pub const GENEVE_OPTION_TYPE_OFFSET: usize = 2;
+pub const GENEVE_OPTION_LENGTH_OFFSET: usize = 3;
+pub const GENEVE_OPTION_DATA_LENGTH_MASK: u8 = 0x1f;
+
 pub const GENEVE_VERSION_SHIFT: u8 = 6;
 pub const GENEVE_VNI_SHIFT: u32 = 8;
 
@@ -416,6 +428,8 @@ pub const VXLAN_FLAGS_OFFSET_DECAP: usize = 0;
 pub const VXLAN_VNI_OFFSET_DECAP: usize = 4;
 pub const ERSPAN_ID_OFFSET: usize = 0; // erspan2和3共用，4字节取0x3ff
 pub const ERSPAN_III_FLAGS_OFFSET: usize = 11;
+pub const ERSPAN_III_TIMESTAMP_OFFSET: usize = 4;
+pub const ERSPAN_III_SGT_OFFSET: usize = 8;
 
 // IpAddr mask
 pub const IPV6_MAX_MASK_LEN: u8 = 128;
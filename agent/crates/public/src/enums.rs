@@ -36,6 +36,11 @@ impl EthernetType {
     pub const TRANSPARENT_ETHERNET_BRIDGING: Self = Self(0x6558);
     pub const QINQ: Self = Self(0x88a8);
     pub const LINK_LAYER_DISCOVERY: Self = Self(0x88cc);
+
+    // 802.1Q customer tag or 802.1ad service tag
+    pub fn is_vlan_tag(&self) -> bool {
+        *self == Self::DOT1Q || *self == Self::QINQ
+    }
 }
 
 impl Default for EthernetType {
@@ -89,6 +94,7 @@ impl IpProtocol {
     pub const NO_NEXT_HEADER: Self = Self(59);
     pub const IPV6_DESTINATION: Self = Self(60);
     pub const IPIP: Self = Self(94);
+    pub const SCTP: Self = Self(132);
 }
 
 impl Default for IpProtocol {
@@ -30,6 +30,12 @@ pub enum Error {
     CreateRawSocketError(#[from] std::io::Error),
     #[error("libpcap error {0}")]
     LibpcapError(String),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[error("pf_ring error {0}")]
+    PfRingError(String),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[error("dpdk error {0}")]
+    DpdkError(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -18,7 +18,6 @@ use num_enum::{FromPrimitive, IntoPrimitive};
 use serde::Serialize;
 
 pub const DEFAULT_DNS_PORT: u16 = 53;
-pub const DEFAULT_TLS_PORT: u16 = 443;
 
 #[derive(
     Serialize,
@@ -61,9 +60,18 @@ pub enum L7Protocol {
     Kafka = 100,
     MQTT = 101,
 
+    // VoIP
+    SIP = 110,
+    RTP = 111,
+
     // INFRA
     DNS = 120,
     Tls = 121,
+    DHCP = 122,
+    NTP = 123,
+    SSH = 124,
+    Kerberos = 125,
+    RADIUS = 126,
 
     Custom = 127,
 
@@ -91,6 +99,13 @@ impl From<String> for L7Protocol {
             "dns" => Self::DNS,
             "oracle" => Self::Oracle,
             "tls" => Self::Tls,
+            "dhcp" => Self::DHCP,
+            "ntp" => Self::NTP,
+            "ssh" => Self::SSH,
+            "kerberos" => Self::Kerberos,
+            "radius" => Self::RADIUS,
+            "sip" => Self::SIP,
+            "rtp" => Self::RTP,
             _ => Self::Unknown,
         }
     }
@@ -100,6 +115,7 @@ impl From<String> for L7Protocol {
 pub enum CustomProtocol {
     Wasm(u8, String),
     So(u8, String),
+    Simple(u8, String),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -30,6 +30,8 @@ pub struct Packet<'a> {
     pub data: &'a mut [u8],
     // Some scene packet will be copied and stored in raw, and referenced by data
     pub raw: Option<*mut u8>,
+    // One of N packets is sampled, 0 means the packet is not sampled
+    pub sampling_rate: u32,
 }
 
 unsafe impl Send for Packet<'_> {}
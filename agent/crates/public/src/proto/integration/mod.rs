@@ -31,5 +31,12 @@ pub mod opentelemetry {
                 include!("opentelemetry.proto.resource.v1.rs");
             }
         }
+        pub mod collector {
+            pub mod trace {
+                pub mod v1 {
+                    include!("opentelemetry.proto.collector.trace.v1.rs");
+                }
+            }
+        }
     }
 }
@@ -22,3 +22,49 @@ pub fn hash_to_u64<T: Hash>(data: &T) -> u64 {
     data.hash(&mut hasher);
     hasher.finish()
 }
+
+// FNV-1a with the standard offset basis, stable across releases and platforms, for hashes
+// shared between agents. Callers should write canonical bytes instead of deriving Hash,
+// whose output is not guaranteed to be stable.
+pub struct Fnv1aHasher(u64);
+
+impl Fnv1aHasher {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+}
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a() {
+        let hash = |bytes: &[u8]| {
+            let mut h = Fnv1aHasher::default();
+            h.write(bytes);
+            h.finish()
+        };
+        // test vectors from http://www.isthe.com/chongo/src/fnv/test_fnv.c
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+    }
+}
@@ -328,6 +328,12 @@ impl StashKey {
 
 struct Stash {
     sender: DebugSender<BoxedDocument>,
+    // minute metrics are also sent to the prometheus remote write, kafka and clickhouse
+    // senders
+    remote_write_sender: Option<DebugSender<BoxedDocument>>,
+    kafka_sender: Option<DebugSender<BoxedDocument>>,
+    clickhouse_sender: Option<DebugSender<BoxedDocument>>,
+    config: CollectorAccess,
     counter: Arc<CollectorCounter>,
     start_time: Duration,
     slot_interval: u64,
@@ -347,6 +353,10 @@ impl Stash {
     fn new(
         ctx: Context,
         sender: DebugSender<BoxedDocument>,
+        remote_write_sender: Option<DebugSender<BoxedDocument>>,
+        kafka_sender: Option<DebugSender<BoxedDocument>>,
+        clickhouse_sender: Option<DebugSender<BoxedDocument>>,
+        config: CollectorAccess,
         counter: Arc<CollectorCounter>,
     ) -> Self {
         let (slot_interval, doc_flag) = match ctx.metric_type {
@@ -362,6 +372,10 @@ impl Stash {
         let stash_init_capacity = inner.capacity();
         Self {
             sender,
+            remote_write_sender,
+            kafka_sender,
+            clickhouse_sender,
+            config,
             counter,
             start_time,
             global_thread_id: ctx.id as u8 + 1,
@@ -769,6 +783,21 @@ impl Stash {
         self.history_length.rotate_right(1);
         self.history_length[0] = self.inner.len();
 
+        let config = self.config.load();
+        let remote_write_sender = self
+            .remote_write_sender
+            .as_ref()
+            .filter(|_| config.prometheus_remote_write.enabled);
+        let mut remote_write_batch = vec![];
+        let kafka_sender = self.kafka_sender.as_ref().filter(|_| {
+            config.kafka_exporter.enabled && !config.kafka_exporter.metrics_topic.is_empty()
+        });
+        let mut kafka_batch = vec![];
+        let clickhouse_sender = self.clickhouse_sender.as_ref().filter(|_| {
+            config.clickhouse_exporter.enabled
+                && !config.clickhouse_exporter.metrics_table.is_empty()
+        });
+        let mut clickhouse_batch = vec![];
         let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
         for (_, mut doc) in self.inner.drain() {
             if batch.len() >= QUEUE_BATCH_SIZE {
@@ -779,6 +808,15 @@ impl Stash {
             }
             doc.timestamp = self.start_time.as_secs() as u32;
             doc.flags |= self.doc_flag;
+            if remote_write_sender.is_some() {
+                remote_write_batch.push(BoxedDocument(Box::new(doc.clone())));
+            }
+            if kafka_sender.is_some() {
+                kafka_batch.push(BoxedDocument(Box::new(doc.clone())));
+            }
+            if clickhouse_sender.is_some() {
+                clickhouse_batch.push(BoxedDocument(Box::new(doc.clone())));
+            }
             batch.push(BoxedDocument(Box::new(doc)))
         }
         if batch.len() > 0 {
@@ -786,6 +824,30 @@ impl Stash {
                 warn!("{} queue terminated", self.context.name);
             }
         }
+        if let Some(sender) = remote_write_sender {
+            if !remote_write_batch.is_empty() && sender.send_all(&mut remote_write_batch).is_err() {
+                debug!(
+                    "{} push metrics to remote write sender queue failed, maybe queue have terminated",
+                    self.context.name
+                );
+            }
+        }
+        if let Some(sender) = kafka_sender {
+            if !kafka_batch.is_empty() && sender.send_all(&mut kafka_batch).is_err() {
+                debug!(
+                    "{} push metrics to kafka sender queue failed, maybe queue have terminated",
+                    self.context.name
+                );
+            }
+        }
+        if let Some(sender) = clickhouse_sender {
+            if !clickhouse_batch.is_empty() && sender.send_all(&mut clickhouse_batch).is_err() {
+                debug!(
+                    "{} push metrics to clickhouse sender queue failed, maybe queue have terminated",
+                    self.context.name
+                );
+            }
+        }
 
         let stash_cap = self.inner.capacity();
         if stash_cap > self.stash_init_capacity {
@@ -1055,6 +1117,9 @@ pub struct Collector {
     thread: Mutex<Option<JoinHandle<()>>>,
     receiver: Arc<Receiver<Box<FlowMeterWithFlow>>>,
     sender: DebugSender<BoxedDocument>,
+    remote_write_sender: Option<DebugSender<BoxedDocument>>,
+    kafka_sender: Option<DebugSender<BoxedDocument>>,
+    clickhouse_sender: Option<DebugSender<BoxedDocument>>,
     config: CollectorAccess,
     context: Context,
 }
@@ -1064,6 +1129,9 @@ impl Collector {
         id: u32,
         receiver: Receiver<Box<FlowMeterWithFlow>>,
         sender: DebugSender<BoxedDocument>,
+        remote_write_sender: Option<DebugSender<BoxedDocument>>,
+        kafka_sender: Option<DebugSender<BoxedDocument>>,
+        clickhouse_sender: Option<DebugSender<BoxedDocument>>,
         metric_type: MetricsType,
         delay_seconds: u32,
         stats: &Arc<stats::Collector>,
@@ -1102,6 +1170,9 @@ impl Collector {
             thread: Mutex::new(None),
             receiver: Arc::new(receiver),
             sender,
+            remote_write_sender,
+            kafka_sender,
+            clickhouse_sender,
             config,
             context: Context {
                 id,
@@ -1122,13 +1193,24 @@ impl Collector {
         let counter = self.counter.clone();
         let receiver = self.receiver.clone();
         let sender = self.sender.clone();
+        let remote_write_sender = self.remote_write_sender.clone();
+        let kafka_sender = self.kafka_sender.clone();
+        let clickhouse_sender = self.clickhouse_sender.clone();
         let ctx = self.context.clone();
         let config = self.config.clone();
 
         let thread = thread::Builder::new()
             .name("collector".to_owned())
             .spawn(move || {
-                let mut stash = Stash::new(ctx, sender, counter);
+                let mut stash = Stash::new(
+                    ctx,
+                    sender,
+                    remote_write_sender,
+                    kafka_sender,
+                    clickhouse_sender,
+                    config.clone(),
+                    counter,
+                );
                 let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
                 while running.load(Ordering::Relaxed) {
                     let config = config.load();
@@ -1185,6 +1267,9 @@ pub struct L7Collector {
     thread: Mutex<Option<JoinHandle<()>>>,
     l7_receiver: Arc<Receiver<Box<AppMeterWithFlow>>>,
     sender: DebugSender<BoxedDocument>,
+    remote_write_sender: Option<DebugSender<BoxedDocument>>,
+    kafka_sender: Option<DebugSender<BoxedDocument>>,
+    clickhouse_sender: Option<DebugSender<BoxedDocument>>,
     config: CollectorAccess,
     context: Context,
 }
@@ -1194,6 +1279,9 @@ impl L7Collector {
         id: u32,
         l7_receiver: Receiver<Box<AppMeterWithFlow>>,
         sender: DebugSender<BoxedDocument>,
+        remote_write_sender: Option<DebugSender<BoxedDocument>>,
+        kafka_sender: Option<DebugSender<BoxedDocument>>,
+        clickhouse_sender: Option<DebugSender<BoxedDocument>>,
         metric_type: MetricsType,
         delay_seconds: u32,
         stats: &Arc<stats::Collector>,
@@ -1232,6 +1320,9 @@ impl L7Collector {
             thread: Mutex::new(None),
             l7_receiver: Arc::new(l7_receiver),
             sender,
+            remote_write_sender,
+            kafka_sender,
+            clickhouse_sender,
             config,
             context: Context {
                 id,
@@ -1252,13 +1343,24 @@ impl L7Collector {
         let counter = self.counter.clone();
         let l7_receiver = self.l7_receiver.clone();
         let sender = self.sender.clone();
+        let remote_write_sender = self.remote_write_sender.clone();
+        let kafka_sender = self.kafka_sender.clone();
+        let clickhouse_sender = self.clickhouse_sender.clone();
         let ctx = self.context.clone();
         let config = self.config.clone();
 
         let thread = thread::Builder::new()
             .name("l7_collector".to_owned())
             .spawn(move || {
-                let mut stash = Stash::new(ctx, sender, counter);
+                let mut stash = Stash::new(
+                    ctx,
+                    sender,
+                    remote_write_sender,
+                    kafka_sender,
+                    clickhouse_sender,
+                    config.clone(),
+                    counter,
+                );
                 let mut l7_batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
                 while running.load(Ordering::Relaxed) {
                     let config = config.load();
@@ -45,9 +45,7 @@ use public::{
     queue::{DebugSender, Error, Receiver},
 };
 
-const TIMESTAMP_SLOT_COUNT: usize = SECONDS_IN_MINUTE as usize;
-const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1); // Must be less than or equal to FLUSH_TIMEOUT
-const FLUSH_TIMEOUT: Duration = Duration::from_secs(60);
+const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1); // Must be less than or equal to aggregation interval
 
 #[derive(Debug, Default)]
 pub struct FlowAggrCounter {
@@ -63,6 +61,7 @@ pub struct FlowAggrThread {
     id: usize,
     input: Arc<Receiver<Arc<BatchedBox<TaggedFlow>>>>,
     output: DebugSender<BoxedTaggedFlow>,
+    ipfix_output: Option<DebugSender<BoxedTaggedFlow>>,
     config: CollectorAccess,
 
     thread_handle: Option<JoinHandle<()>>,
@@ -78,6 +77,7 @@ impl FlowAggrThread {
         id: usize,
         input: Receiver<Arc<BatchedBox<TaggedFlow>>>,
         output: DebugSender<BoxedTaggedFlow>,
+        ipfix_output: Option<DebugSender<BoxedTaggedFlow>>,
         config: CollectorAccess,
         ntp_diff: Arc<AtomicI64>,
     ) -> (Self, Arc<FlowAggrCounter>) {
@@ -88,6 +88,7 @@ impl FlowAggrThread {
                 id,
                 input: Arc::new(input),
                 output: output.clone(),
+                ipfix_output,
                 thread_handle: None,
                 config,
                 running,
@@ -107,6 +108,7 @@ impl FlowAggrThread {
         let mut flow_aggr = FlowAggr::new(
             self.input.clone(),
             self.output.clone(),
+            self.ipfix_output.clone(),
             self.running.clone(),
             self.config.clone(),
             self.ntp_diff.clone(),
@@ -144,10 +146,12 @@ impl FlowAggrThread {
 pub struct FlowAggr {
     input: Arc<Receiver<Arc<BatchedBox<TaggedFlow>>>>,
     output: ThrottlingQueue,
+    ipfix_output: Option<DebugSender<BoxedTaggedFlow>>,
     slot_start_time: Duration,
     flow_stashs: HashMap<u64, Box<TaggedFlow>>,
     timestamp_stashs: VecDeque<HashSet<u64>>,
     stash_init_capacity: usize,
+    // one slot for each second of the aggregation interval
     slot_count: usize,
 
     last_flush_time: Duration,
@@ -168,12 +172,13 @@ impl FlowAggr {
     pub fn new(
         input: Arc<Receiver<Arc<BatchedBox<TaggedFlow>>>>,
         output: DebugSender<BoxedTaggedFlow>,
+        ipfix_output: Option<DebugSender<BoxedTaggedFlow>>,
         running: Arc<AtomicBool>,
         config: CollectorAccess,
         ntp_diff: Arc<AtomicI64>,
         metrics: Arc<FlowAggrCounter>,
     ) -> Self {
-        let slot_count = TIMESTAMP_SLOT_COUNT;
+        let slot_count = config.load().l4_log_aggr_interval.as_secs() as usize;
         let mut timestamp_stashs = VecDeque::with_capacity(slot_count);
         for _ in 0..slot_count {
             timestamp_stashs.push_back(HashSet::with_capacity(Self::MIN_STASH_CAPACITY_SECOND));
@@ -181,6 +186,7 @@ impl FlowAggr {
         Self {
             input,
             output: ThrottlingQueue::new(output, config.clone()),
+            ipfix_output,
             flow_stashs: HashMap::with_capacity(Self::MIN_STASH_CAPACITY),
             timestamp_stashs,
             stash_init_capacity: Self::MIN_STASH_CAPACITY,
@@ -205,9 +211,9 @@ impl FlowAggr {
         self.flow_stashs.remove(flow_id)
     }
 
-    fn minute_merge(&mut self, f: Arc<BatchedBox<TaggedFlow>>) {
+    fn interval_merge(&mut self, f: Arc<BatchedBox<TaggedFlow>>) {
         let f = f.as_ref();
-        let flow_time = Timestamp::from_secs(f.flow.start_time_in_minute());
+        let flow_time = Timestamp::from_secs(f.flow.start_time_in_interval(self.slot_count as u64));
         if flow_time < self.slot_start_time {
             debug!("flow drop before slot start time. flow stat time: {:?}, slot start time is {:?}, delay is {:?}", flow_time, self.slot_start_time, self.slot_start_time - flow_time);
             self.metrics
@@ -268,17 +274,26 @@ impl FlowAggr {
         }
         f.flow.acl_gids = Vec::from(acl_gids.list());
 
+        let interval = self.slot_count as u64;
         if !f.flow.is_new_flow {
-            f.flow.start_time = Timestamp::from_secs(f.flow.start_time_in_minute());
+            f.flow.start_time = Timestamp::from_secs(f.flow.start_time_in_interval(interval));
         }
 
         if f.flow.close_type == CloseType::ForcedReport {
             // Align time to seconds
-            f.flow.end_time = Timestamp::from_secs(f.flow.start_time.as_secs() + SECONDS_IN_MINUTE);
+            f.flow.end_time = Timestamp::from_secs(f.flow.start_time.as_secs() + interval);
         }
 
         self.metrics.out.fetch_add(1, Ordering::Relaxed);
 
+        if let Some(ipfix_output) = self.ipfix_output.as_ref() {
+            if self.config.load().ipfix_exporter.enabled {
+                if let Err(_) = ipfix_output.send(BoxedTaggedFlow(f.clone())) {
+                    debug!("l4 flow aggr push flow to ipfix sender queue failed, maybe queue have terminated");
+                }
+            }
+        }
+
         let now = get_timestamp(self.ntp_diff.load(Ordering::Relaxed));
         self.output.flush_cache_with_throttling(&now);
         self.output.flush_cache_without_throttling(&now);
@@ -334,6 +349,26 @@ impl FlowAggr {
         }
     }
 
+    // flush all stashed flows and rebuild slots for the new interval
+    fn update_interval(&mut self, interval: Duration) {
+        let slot_count = interval.as_secs() as usize;
+        info!(
+            "l4 flow aggr interval change from {}s to {}s",
+            self.slot_count, slot_count
+        );
+        let mut slots = VecDeque::with_capacity(slot_count);
+        for _ in 0..slot_count {
+            slots.push_back(HashSet::with_capacity(Self::MIN_STASH_CAPACITY_SECOND));
+        }
+        let slots = std::mem::replace(&mut self.timestamp_stashs, slots);
+        for flow_id in slots.into_iter().flatten() {
+            if let Some(flow) = self.flow_stashs.remove(&flow_id) {
+                self.send_flow(flow);
+            }
+        }
+        self.slot_count = slot_count;
+    }
+
     fn calc_stash_counters(&self) {
         self.metrics
             .stash_total_len
@@ -349,6 +384,9 @@ impl FlowAggr {
             match self.input.recv_all(&mut batch, Some(QUEUE_READ_TIMEOUT)) {
                 Ok(_) => {
                     let config = self.config.load();
+                    if config.l4_log_aggr_interval.as_secs() as usize != self.slot_count {
+                        self.update_interval(config.l4_log_aggr_interval);
+                    }
                     for tagged_flow in batch.drain(..) {
                         if config.l4_log_ignore_tap_sides[tagged_flow.flow.tap_side as usize] {
                             continue;
@@ -357,7 +395,7 @@ impl FlowAggr {
                             || config.l4_log_store_tap_types
                                 [u16::from(tagged_flow.flow.flow_key.tap_type) as usize]
                         {
-                            self.minute_merge(tagged_flow);
+                            self.interval_merge(tagged_flow);
                         }
                     }
                     self.calc_stash_counters();
@@ -366,7 +404,7 @@ impl FlowAggr {
                     let now = get_timestamp(self.ntp_diff.load(Ordering::Relaxed));
                     self.output.flush_cache_with_throttling(&now);
                     self.output.flush_cache_without_throttling(&now);
-                    if now > self.last_flush_time + FLUSH_TIMEOUT {
+                    if now > self.last_flush_time + Duration::from_secs(self.slot_count as u64) {
                         self.flush_front_slot_and_rotate();
                     }
                 }
@@ -36,6 +36,7 @@ use super::{
 
 use crate::common::flow::{CloseType, L7Protocol, L7Stats, SignalSource};
 use crate::config::handler::{CollectorAccess, CollectorConfig};
+use crate::flow_generator::protocol_logs::{connection_key, L7LogDeduplicator};
 use crate::metric::meter::{AppAnomaly, AppLatency, AppMeter, AppTraffic};
 use crate::rpc::get_timestamp;
 use crate::utils::{
@@ -327,6 +328,7 @@ pub struct L7QuadrupleGeneratorThread {
     ntp_diff: Arc<AtomicI64>,
 
     stats: Arc<Collector>,
+    deduplicator: Arc<L7LogDeduplicator>,
 }
 
 impl L7QuadrupleGeneratorThread {
@@ -342,6 +344,7 @@ impl L7QuadrupleGeneratorThread {
         config: CollectorAccess,
         ntp_diff: Arc<AtomicI64>,
         stats: Arc<Collector>,
+        deduplicator: Arc<L7LogDeduplicator>,
     ) -> Self {
         let running = Arc::new(AtomicBool::new(false));
         let conf = config.load();
@@ -362,6 +365,7 @@ impl L7QuadrupleGeneratorThread {
             config,
             ntp_diff,
             stats,
+            deduplicator,
         }
     }
 
@@ -387,6 +391,7 @@ impl L7QuadrupleGeneratorThread {
             self.running.clone(),
             self.ntp_diff.clone(),
             self.stats.clone(),
+            self.deduplicator.clone(),
         );
         self.thread_handle = Some(
             thread::Builder::new()
@@ -434,6 +439,7 @@ pub struct L7QuadrupleGenerator {
     running: Arc<AtomicBool>,
     ntp_diff: Arc<AtomicI64>,
     stats: Arc<Collector>,
+    deduplicator: Arc<L7LogDeduplicator>,
 }
 
 impl L7QuadrupleGenerator {
@@ -450,6 +456,7 @@ impl L7QuadrupleGenerator {
         running: Arc<AtomicBool>,
         ntp_diff: Arc<AtomicI64>,
         stats: Arc<Collector>,
+        deduplicator: Arc<L7LogDeduplicator>,
     ) -> Self {
         let collector_config = config.load();
         info!("new l7 quadruple_generator id: {}, second_delay: {}, minute_delay: {}, l7_metrics_enabled: {}, vtap_flow_1s_enabled: {} collector_enabled: {}", id, second_delay_seconds, minute_delay_seconds, collector_config.l7_metrics_enabled, collector_config.vtap_flow_1s_enabled, collector_config.enabled);
@@ -534,9 +541,32 @@ impl L7QuadrupleGenerator {
             running,
             ntp_diff,
             stats,
+            deduplicator,
         }
     }
 
+    // perf stats from packets are dropped if logs of the connection are also captured by ebpf,
+    // which has its own perf stats
+    fn is_deduplicated(&self, l7_stats: &L7Stats) -> bool {
+        if l7_stats.signal_source != SignalSource::Packet {
+            return false;
+        }
+        let Some(tagged_flow) = &l7_stats.flow else {
+            return false;
+        };
+        let src = &tagged_flow.flow.flow_metrics_peers[0];
+        let dst = &tagged_flow.flow.flow_metrics_peers[1];
+        let key = connection_key(
+            src.nat_real_ip,
+            src.nat_real_port,
+            dst.nat_real_ip,
+            dst.nat_real_port,
+            l7_stats.l7_protocol,
+        );
+        self.deduplicator
+            .is_covered(key, get_timestamp(self.ntp_diff.load(Ordering::Relaxed)))
+    }
+
     fn handle(
         &mut self,
         config: &CollectorConfig,
@@ -564,7 +594,7 @@ impl L7QuadrupleGenerator {
             None => 0,
         };
 
-        let app_meter = if config.l7_metrics_enabled {
+        let app_meter = if config.l7_metrics_enabled && !self.is_deduplicated(&l7_stats) {
             Self::generate_app_meter(&l7_stats)
         } else {
             AppMeter::default()
@@ -592,14 +622,20 @@ impl L7QuadrupleGenerator {
     }
 
     fn generate_app_meter(l7_stats: &L7Stats) -> AppMeter {
-        let (close_type, direction_score) = if let Some(tagged_flow) = &l7_stats.flow {
-            (
-                tagged_flow.flow.close_type,
-                tagged_flow.flow.direction_score,
-            )
-        } else {
-            (CloseType::ForcedReport, 0)
-        };
+        let (close_type, direction_score, byte_tx, byte_rx) =
+            if let Some(tagged_flow) = &l7_stats.flow {
+                let peers = &tagged_flow.flow.flow_metrics_peers;
+                (
+                    tagged_flow.flow.close_type,
+                    tagged_flow.flow.direction_score,
+                    peers[0].byte_count,
+                    peers[1].byte_count,
+                )
+            } else {
+                // stats split by endpoint changes carry no flow, bytes are counted
+                // with the stats reported at the end of the statistical interval
+                (CloseType::ForcedReport, 0, 0, 0)
+            };
         let stats = &l7_stats.stats;
         match (l7_stats.l7_protocol, l7_stats.signal_source) {
             (
@@ -612,6 +648,7 @@ impl L7QuadrupleGenerator {
                         request: (close_type != CloseType::ForcedReport) as u32,
                         response: (close_type != CloseType::ForcedReport) as u32,
                         direction_score,
+                        ..Default::default()
                     },
                     ..Default::default()
                 }
@@ -621,11 +658,15 @@ impl L7QuadrupleGenerator {
                     request: stats.request_count,
                     response: stats.response_count,
                     direction_score: direction_score,
+                    byte_tx,
+                    byte_rx,
                 },
                 latency: AppLatency {
                     rrt_max: stats.rrt_max,
                     rrt_sum: stats.rrt_sum as u64,
                     rrt_count: stats.rrt_count,
+                    rrt_histogram: stats.rrt_histogram,
+                    apdex: stats.apdex,
                 },
                 anomaly: AppAnomaly {
                     client_error: stats.err_client_count,
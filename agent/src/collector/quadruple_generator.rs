@@ -991,6 +991,13 @@ impl QuadrupleGenerator {
                     art_count: stats.tcp.art_count,
                     rrt_count: 0,
                     cit_count: stats.tcp.cit_count,
+
+                    ack_rtt_client_max: stats.tcp.ack_rtt_client_max,
+                    ack_rtt_server_max: stats.tcp.ack_rtt_server_max,
+                    ack_rtt_client_sum: stats.tcp.ack_rtt_client_sum as u64,
+                    ack_rtt_server_sum: stats.tcp.ack_rtt_server_sum as u64,
+                    ack_rtt_client_count: stats.tcp.ack_rtt_client_count,
+                    ack_rtt_server_count: stats.tcp.ack_rtt_server_count,
                     ..Default::default()
                 };
 
@@ -1003,6 +1010,8 @@ impl QuadrupleGenerator {
                     zero_win_rx: dst_perf.zero_win_count as u64,
                     retrans_syn: stats.tcp.retrans_syn_count,
                     retrans_synack: stats.tcp.retrans_synack_count,
+                    dup_ack_tx: src_perf.dup_ack_count as u64,
+                    dup_ack_rx: dst_perf.dup_ack_count as u64,
                 };
             } else {
                 flow_meter.latency.art_max = stats.tcp.art_max;
@@ -1030,6 +1039,9 @@ impl QuadrupleGenerator {
                 flow_meter.latency.rrt_max = stats.l7.rrt_max;
                 flow_meter.latency.rrt_sum = stats.l7.rrt_sum;
                 flow_meter.latency.rrt_count = stats.l7.rrt_count;
+                flow_meter.latency.tls_rtt_max = stats.l7.tls_rtt_max;
+                flow_meter.latency.tls_rtt_sum = stats.l7.tls_rtt as u64;
+                flow_meter.latency.tls_rtt_count = stats.l7.tls_rtt_count;
                 flow_meter.anomaly.l7_client_error = stats.l7.err_client_count;
                 flow_meter.anomaly.l7_server_error = stats.l7.err_server_count;
                 flow_meter.anomaly.l7_timeout = stats.l7.err_timeout;
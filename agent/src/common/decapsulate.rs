@@ -15,7 +15,8 @@
  */
 
 use std::fmt;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
 
 use num_enum::TryFromPrimitive;
 
@@ -37,6 +38,8 @@ pub enum TunnelType {
     TencentGre = DecapType::Tencent as u8,
     Geneve = DecapType::Geneve as u8,
     ErspanOrTeb = DecapType::Geneve as u8 + 1,
+    GtpU = DecapType::Geneve as u8 + 2,
+    Srv6 = DecapType::Geneve as u8 + 3,
 }
 
 impl From<DecapType> for TunnelType {
@@ -60,6 +63,8 @@ impl fmt::Display for TunnelType {
             TunnelType::TencentGre => write!(f, "GRE"),
             TunnelType::Geneve => write!(f, "Geneve"),
             TunnelType::ErspanOrTeb => write!(f, "ERSPAN_TEB"),
+            TunnelType::GtpU => write!(f, "GTP-U"),
+            TunnelType::Srv6 => write!(f, "SRv6"),
         }
     }
 }
@@ -119,6 +124,14 @@ impl fmt::Display for TunnelTypeBitmap {
         }
         if self.has(TunnelType::ErspanOrTeb) {
             write!(f, "{}{}", separation, TunnelType::ErspanOrTeb)?;
+            separation = " ";
+        }
+        if self.has(TunnelType::GtpU) {
+            write!(f, "{}{}", separation, TunnelType::GtpU)?;
+            separation = " ";
+        }
+        if self.has(TunnelType::Srv6) {
+            write!(f, "{}{}", separation, TunnelType::Srv6)?;
         }
         write!(f, "")
     }
@@ -133,10 +146,154 @@ const LE_VXLAN_PROTO_UDP_DPORT2: u16 = 0x1821; // 0x2118(8472)'s LittleEndian
 const LE_VXLAN_PROTO_UDP_DPORT3: u16 = 0x801A; // 0x1A80(6784)'s LittleEndian
 const LE_TRANSPARENT_ETHERNET_BRIDGEING: u16 = 0x5865; // 0x6558(25944)'s LittleEndian
 const LE_GENEVE_PROTO_UDP_DPORT: u16 = 0xc117; // 0x17c1(6081)'s LittleEndian
+const LE_VXLAN_GPE_PROTO_UDP_DPORT: u16 = 0xB612; // 0x12B6(4790)'s LittleEndian
+const LE_GTPU_PROTO_UDP_DPORT: u16 = 0x6808; // 0x0868(2152)'s LittleEndian
+
+const ERSPAN_III_FLAG_OPTIONAL: u8 = 0x1;
+// timestamp granularity in bits 1-2 of the flags, IEEE 1588 carries nanoseconds in the
+// timestamp field and seconds in the platform specific subheader
+const ERSPAN_III_GRA_IEEE_1588: u8 = 0x2;
+const ERSPAN_III_PLATFORM_ID_IEEE_1588: u8 = 0x3;
+const ERSPAN_III_SUBHEADER_SECONDS_OFFSET: usize = 4;
+
+// https://docs.aws.amazon.com/elasticloadbalancing/latest/gateway/gateway-load-balancers.html
+const GENEVE_OPTION_CLASS_AWS_GWLB: u16 = 0x0108;
+const GENEVE_OPTION_TYPE_GWLB_ENDPOINT_ID: u8 = 1;
+const GENEVE_OPTION_TYPE_GWLB_ATTACHMENT_ID: u8 = 2;
+const GENEVE_OPTION_TYPE_GWLB_FLOW_COOKIE: u8 = 3;
 
 const VXLAN_FLAGS: u8 = 8;
+const VXLAN_GPE_FLAG_NEXT_PROTOCOL: u8 = 0x4;
+const VXLAN_GPE_NEXT_PROTOCOL_OFFSET: usize = 3;
+// next protocol of VXLAN-GPE and NSH
+const NEXT_PROTOCOL_IPV4: u8 = 1;
+const NEXT_PROTOCOL_IPV6: u8 = 2;
+const NEXT_PROTOCOL_ETHERNET: u8 = 3;
+const NEXT_PROTOCOL_NSH: u8 = 4;
+// NSH base header and service path header
+const NSH_MIN_HEADER_SIZE: usize = 8;
+const NSH_LENGTH_OFFSET: usize = 1;
+const NSH_LENGTH_MASK: u8 = 0x3f;
+const NSH_NEXT_PROTOCOL_OFFSET: usize = 3;
+const NSH_SERVICE_PATH_OFFSET: usize = 4;
+// GTPv1-U, 3GPP TS 29.281
+const GTPU_HEADER_SIZE: usize = 8;
+const GTPU_VERSION_MASK: u8 = 0xf0; // version and protocol type
+const GTPU_VERSION_1: u8 = 0x30;
+const GTPU_FLAG_OPTIONAL: u8 = 0x7; // extension header, sequence number and N-PDU number
+const GTPU_OPTIONAL_SIZE: usize = 4;
+const GTPU_MESSAGE_TYPE_OFFSET: usize = 1;
+const GTPU_MESSAGE_TYPE_G_PDU: u8 = 0xff;
+const GTPU_TEID_OFFSET: usize = 4;
+const GTPU_NEXT_EXTENSION_OFFSET: usize = 11;
+// SRv6 segment routing header, RFC 8754
+const SRH_FIXED_SIZE: usize = 8;
+const SRH_ROUTING_TYPE: u8 = 4;
+const SRH_LENGTH_OFFSET: usize = 1;
+const SRH_ROUTING_TYPE_OFFSET: usize = 2;
+const SRH_SEGMENTS_LEFT_OFFSET: usize = 3;
+const SRH_LAST_ENTRY_OFFSET: usize = 4;
+const SRV6_SEGMENT_SIZE: usize = 16;
+// segments beyond are not kept in the segment list
+const SRV6_MAX_SEGMENTS: usize = 8;
 const TUNNEL_TIER_LIMIT: u8 = 2;
 
+// Well-known Geneve option TLVs, 0 means absent
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GeneveOptions {
+    pub gwlb_endpoint_id: u64,
+    pub gwlb_attachment_id: u64,
+    pub gwlb_flow_cookie: u32,
+}
+
+impl GeneveOptions {
+    fn parse(mut options: &[u8]) -> Self {
+        let mut parsed = GeneveOptions::default();
+        while options.len() >= GENEVE_OPTION_HEADER_SIZE {
+            let class = bytes::read_u16_be(&options[GENEVE_OPTION_CLASS_OFFSET..]);
+            // the highest bit of type is the critical flag
+            let option_type = options[GENEVE_OPTION_TYPE_OFFSET] & 0x7f;
+            let data_length = ((options[GENEVE_OPTION_LENGTH_OFFSET]
+                & GENEVE_OPTION_DATA_LENGTH_MASK)
+                << 2) as usize;
+            if options.len() < GENEVE_OPTION_HEADER_SIZE + data_length {
+                break;
+            }
+            let data = &options[GENEVE_OPTION_HEADER_SIZE..GENEVE_OPTION_HEADER_SIZE + data_length];
+            if class == GENEVE_OPTION_CLASS_AWS_GWLB {
+                match option_type {
+                    GENEVE_OPTION_TYPE_GWLB_ENDPOINT_ID if data.len() >= 8 => {
+                        parsed.gwlb_endpoint_id = bytes::read_u64_be(data)
+                    }
+                    GENEVE_OPTION_TYPE_GWLB_ATTACHMENT_ID if data.len() >= 8 => {
+                        parsed.gwlb_attachment_id = bytes::read_u64_be(data)
+                    }
+                    GENEVE_OPTION_TYPE_GWLB_FLOW_COOKIE if data.len() >= 4 => {
+                        parsed.gwlb_flow_cookie = bytes::read_u32_be(data)
+                    }
+                    _ => (),
+                }
+            }
+            options = &options[GENEVE_OPTION_HEADER_SIZE + data_length..];
+        }
+        parsed
+    }
+}
+
+// Segment routing header of SRv6, segments are in the order of the header, that is the last
+// segment of the path comes first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Srv6Info {
+    pub segments_left: u8,
+    pub active_segment: Ipv6Addr,
+    segment_count: u8,
+    segments: [Ipv6Addr; SRV6_MAX_SEGMENTS],
+}
+
+impl Srv6Info {
+    // Returns the header and its size, None if it is not a valid segment routing header
+    pub fn parse(routing_header: &[u8]) -> Option<(Self, usize)> {
+        if routing_header.len() < SRH_FIXED_SIZE
+            || routing_header[SRH_ROUTING_TYPE_OFFSET] != SRH_ROUTING_TYPE
+        {
+            return None;
+        }
+        let header_size = (routing_header[SRH_LENGTH_OFFSET] as usize + 1) << 3;
+        let segments_left = routing_header[SRH_SEGMENTS_LEFT_OFFSET];
+        let segment_count = routing_header[SRH_LAST_ENTRY_OFFSET] as usize + 1;
+        if routing_header.len() < header_size
+            || header_size < SRH_FIXED_SIZE + segment_count * SRV6_SEGMENT_SIZE
+            || segments_left as usize >= segment_count
+        {
+            return None;
+        }
+
+        let read_segment = |i: usize| {
+            let offset = SRH_FIXED_SIZE + i * SRV6_SEGMENT_SIZE;
+            Ipv6Addr::from(
+                <[u8; SRV6_SEGMENT_SIZE]>::try_from(
+                    &routing_header[offset..offset + SRV6_SEGMENT_SIZE],
+                )
+                .unwrap(),
+            )
+        };
+        let mut info = Srv6Info {
+            segments_left,
+            active_segment: read_segment(segments_left as usize),
+            segment_count: segment_count.min(SRV6_MAX_SEGMENTS) as u8,
+            segments: [Ipv6Addr::UNSPECIFIED; SRV6_MAX_SEGMENTS],
+        };
+        for i in 0..info.segment_count as usize {
+            info.segments[i] = read_segment(i);
+        }
+        Some((info, header_size))
+    }
+
+    pub fn segments(&self) -> &[Ipv6Addr] {
+        &self.segments[..self.segment_count as usize]
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct TunnelInfo {
     pub src: Ipv4Addr,
@@ -147,6 +304,16 @@ pub struct TunnelInfo {
     pub tunnel_type: TunnelType,
     pub tier: u8,
     pub is_ipv6: bool,
+    // security group tag of ERSPAN III
+    pub erspan_sgt: u16,
+    // hardware timestamp of ERSPAN III in IEEE 1588 granularity
+    pub erspan_timestamp: Option<Duration>,
+    pub geneve_options: GeneveOptions,
+    // service path identifier and service index of NSH over VXLAN-GPE
+    pub nsh_spi: u32,
+    pub nsh_si: u8,
+    // segment routing header of SRv6 encapsulation
+    pub srv6: Option<Srv6Info>,
 }
 
 impl Default for TunnelInfo {
@@ -160,6 +327,12 @@ impl Default for TunnelInfo {
             tunnel_type: TunnelType::default(),
             tier: 0,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         }
     }
 }
@@ -186,7 +359,7 @@ impl TunnelInfo {
 
     pub fn decapsulate_udp(
         &mut self,
-        packet: &[u8],
+        packet: &mut [u8],
         l2_len: usize,
         tunnel_types: &TunnelTypeBitmap,
     ) -> usize {
@@ -205,6 +378,12 @@ impl TunnelInfo {
             LE_GENEVE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::Geneve) => {
                 self.decapsulate_geneve(packet, l2_len)
             }
+            LE_VXLAN_GPE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::Vxlan) => {
+                self.decapsulate_vxlan_gpe(packet, l2_len, false)
+            }
+            LE_GTPU_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::GtpU) => {
+                self.decapsulate_gtpu(packet, l2_len, false)
+            }
             _ => 0,
         }
     }
@@ -233,6 +412,161 @@ impl TunnelInfo {
         FIELD_OFFSET_VXLAN_FLAGS - ETH_HEADER_SIZE + VXLAN_HEADER_SIZE
     }
 
+    // VXLAN-GPE is decapsulated as VXLAN, the inner packet may be ethernet, ip or NSH
+    pub fn decapsulate_vxlan_gpe(
+        &mut self,
+        packet: &mut [u8],
+        l2_len: usize,
+        underlay_ipv6: bool,
+    ) -> usize {
+        let underlay_ip_header_size = if underlay_ipv6 {
+            IPV6_HEADER_SIZE
+        } else {
+            IPV4_HEADER_SIZE
+        };
+        let gpe_offset = l2_len + underlay_ip_header_size + UDP_HEADER_SIZE;
+        if packet.len() < gpe_offset + VXLAN_HEADER_SIZE {
+            return 0;
+        }
+        let flags = packet[gpe_offset + VXLAN_FLAGS_OFFSET_DECAP];
+        if flags & VXLAN_FLAGS == 0 || flags & VXLAN_GPE_FLAG_NEXT_PROTOCOL == 0 {
+            return 0;
+        }
+
+        let mut next_protocol = packet[gpe_offset + VXLAN_GPE_NEXT_PROTOCOL_OFFSET];
+        let mut overlay_offset = gpe_offset + VXLAN_HEADER_SIZE;
+        let mut service_path = None;
+        if next_protocol == NEXT_PROTOCOL_NSH {
+            if packet.len() < overlay_offset + NSH_MIN_HEADER_SIZE {
+                return 0;
+            }
+            let nsh = &packet[overlay_offset..];
+            let nsh_header_size = ((nsh[NSH_LENGTH_OFFSET] & NSH_LENGTH_MASK) as usize) << 2;
+            if nsh_header_size < NSH_MIN_HEADER_SIZE || nsh.len() < nsh_header_size {
+                return 0;
+            }
+            service_path = Some(bytes::read_u32_be(&nsh[NSH_SERVICE_PATH_OFFSET..]));
+            next_protocol = nsh[NSH_NEXT_PROTOCOL_OFFSET];
+            overlay_offset += nsh_header_size;
+        }
+        let overlay_eth_type = match next_protocol {
+            NEXT_PROTOCOL_ETHERNET => None,
+            NEXT_PROTOCOL_IPV4 => Some(EthernetType::IPV4),
+            NEXT_PROTOCOL_IPV6 => Some(EthernetType::IPV6),
+            _ => return 0,
+        };
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            if underlay_ipv6 {
+                self.decapsulate_v6_addr(&packet[l2_len..]);
+                self.is_ipv6 = true;
+            } else {
+                self.decapsulate_addr(&packet[l2_len..]);
+            }
+            self.decapsulate_mac(packet);
+            self.tunnel_type = TunnelType::Vxlan;
+            self.id = bytes::read_u32_be(&packet[gpe_offset + VXLAN_VNI_OFFSET_DECAP..]) >> 8;
+            if let Some(service_path) = service_path {
+                self.nsh_spi = service_path >> 8;
+                self.nsh_si = service_path as u8;
+            }
+        }
+        self.tier += 1;
+
+        // 内层为IP时没有L2层，将underlay的MAC作为伪造L2层放在overlay ip头前
+        if let Some(eth_type) = overlay_eth_type {
+            overlay_offset -= ETH_HEADER_SIZE;
+            packet.copy_within(0..FIELD_OFFSET_ETH_TYPE, overlay_offset);
+            bytes::write_u16_be(
+                &mut packet[overlay_offset + FIELD_OFFSET_ETH_TYPE..],
+                u16::from(eth_type),
+            );
+        }
+
+        // return offset start from L3
+        overlay_offset - l2_len
+    }
+
+    // Only G-PDU carrying user packets is decapsulated, the inner packet is always ip
+    pub fn decapsulate_gtpu(
+        &mut self,
+        packet: &mut [u8],
+        l2_len: usize,
+        underlay_ipv6: bool,
+    ) -> usize {
+        let underlay_ip_header_size = if underlay_ipv6 {
+            IPV6_HEADER_SIZE
+        } else {
+            IPV4_HEADER_SIZE
+        };
+        let gtpu_offset = l2_len + underlay_ip_header_size + UDP_HEADER_SIZE;
+        if packet.len() < gtpu_offset + GTPU_HEADER_SIZE {
+            return 0;
+        }
+        let gtpu = &packet[gtpu_offset..];
+        let flags = gtpu[0];
+        if flags & GTPU_VERSION_MASK != GTPU_VERSION_1
+            || gtpu[GTPU_MESSAGE_TYPE_OFFSET] != GTPU_MESSAGE_TYPE_G_PDU
+        {
+            return 0;
+        }
+
+        let mut overlay_offset = gtpu_offset + GTPU_HEADER_SIZE;
+        if flags & GTPU_FLAG_OPTIONAL != 0 {
+            if packet.len() < overlay_offset + GTPU_OPTIONAL_SIZE {
+                return 0;
+            }
+            let mut next_extension = packet[gtpu_offset + GTPU_NEXT_EXTENSION_OFFSET];
+            overlay_offset += GTPU_OPTIONAL_SIZE;
+            // extension headers, length in 4 octets units with the next type in the last octet
+            while next_extension != 0 {
+                if packet.len() <= overlay_offset {
+                    return 0;
+                }
+                let extension_size = (packet[overlay_offset] as usize) << 2;
+                if extension_size == 0 || packet.len() < overlay_offset + extension_size {
+                    return 0;
+                }
+                overlay_offset += extension_size;
+                next_extension = packet[overlay_offset - 1];
+            }
+        }
+        if packet.len() <= overlay_offset {
+            return 0;
+        }
+        let overlay_eth_type = match packet[overlay_offset] >> 4 {
+            4 => EthernetType::IPV4,
+            6 => EthernetType::IPV6,
+            _ => return 0,
+        };
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            if underlay_ipv6 {
+                self.decapsulate_v6_addr(&packet[l2_len..]);
+                self.is_ipv6 = true;
+            } else {
+                self.decapsulate_addr(&packet[l2_len..]);
+            }
+            self.decapsulate_mac(packet);
+            self.tunnel_type = TunnelType::GtpU;
+            self.id = bytes::read_u32_be(&packet[gtpu_offset + GTPU_TEID_OFFSET..]);
+        }
+        self.tier += 1;
+
+        // 内层为IP时没有L2层，将underlay的MAC作为伪造L2层放在overlay ip头前
+        overlay_offset -= ETH_HEADER_SIZE;
+        packet.copy_within(0..FIELD_OFFSET_ETH_TYPE, overlay_offset);
+        bytes::write_u16_be(
+            &mut packet[overlay_offset + FIELD_OFFSET_ETH_TYPE..],
+            u16::from(overlay_eth_type),
+        );
+
+        // return offset start from L3
+        overlay_offset - l2_len
+    }
+
     fn calc_gre_option_size(flags: u16) -> usize {
         let mut size = 0;
         if flags & GRE_FLAGS_KEY_MASK != 0 {
@@ -294,6 +628,7 @@ impl TunnelInfo {
                     self.id = bytes::read_u32_be(
                         &l3_packet[ip_header_size + gre_header_size + ERSPAN_ID_OFFSET..],
                     ) & 0x3ff;
+                    self.decapsulate_erspan_iii(&l3_packet[ip_header_size + gre_header_size..]);
                 }
                 self.tier += 1;
 
@@ -311,6 +646,31 @@ impl TunnelInfo {
         }
     }
 
+    fn decapsulate_erspan_iii(&mut self, erspan_header: &[u8]) {
+        if erspan_header.len() < ERSPAN_III_HEADER_SIZE {
+            return;
+        }
+        self.erspan_sgt = bytes::read_u16_be(&erspan_header[ERSPAN_III_SGT_OFFSET..]);
+
+        let flags = erspan_header[ERSPAN_III_FLAGS_OFFSET];
+        if flags & ERSPAN_III_FLAG_OPTIONAL == 0
+            || (flags >> 1) & 0x3 != ERSPAN_III_GRA_IEEE_1588
+            || erspan_header.len() < ERSPAN_III_HEADER_SIZE + ERSPAN_III_SUBHEADER_SIZE
+        {
+            return;
+        }
+        let subheader = &erspan_header[ERSPAN_III_HEADER_SIZE..];
+        if subheader[0] >> 2 != ERSPAN_III_PLATFORM_ID_IEEE_1588 {
+            return;
+        }
+        let nanos = bytes::read_u32_be(&erspan_header[ERSPAN_III_TIMESTAMP_OFFSET..]);
+        if nanos >= 1_000_000_000 {
+            return;
+        }
+        let seconds = bytes::read_u32_be(&subheader[ERSPAN_III_SUBHEADER_SECONDS_OFFSET..]);
+        self.erspan_timestamp = Some(Duration::new(seconds as u64, nanos));
+    }
+
     pub fn is_gre_pseudo_inner_mac(mac: u64) -> bool {
         mac >> 16 == 0
     }
@@ -453,6 +813,8 @@ impl TunnelInfo {
             self.decapsulate_mac(packet);
             self.tunnel_type = TunnelType::Geneve;
             self.id = tunnel_id;
+            self.geneve_options =
+                GeneveOptions::parse(&l4_payload[GENEVE_HEADER_SIZE..geneve_header_size]);
         }
         self.tier += 1;
 
@@ -541,6 +903,8 @@ impl TunnelInfo {
             self.decapsulate_mac(packet);
             self.tunnel_type = TunnelType::Geneve;
             self.id = tunnel_id;
+            self.geneve_options =
+                GeneveOptions::parse(&l4_payload[GENEVE_HEADER_SIZE..geneve_header_size]);
             self.is_ipv6 = true;
         }
         self.tier += 1;
@@ -577,7 +941,7 @@ impl TunnelInfo {
 
     pub fn decapsulate_v6_udp(
         &mut self,
-        packet: &[u8],
+        packet: &mut [u8],
         l2_len: usize,
         tunnel_types: &TunnelTypeBitmap,
     ) -> usize {
@@ -596,6 +960,12 @@ impl TunnelInfo {
             LE_GENEVE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::Geneve) => {
                 self.decapsulate_v6_geneve(packet, l2_len)
             }
+            LE_VXLAN_GPE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::Vxlan) => {
+                self.decapsulate_vxlan_gpe(packet, l2_len, true)
+            }
+            LE_GTPU_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::GtpU) => {
+                self.decapsulate_gtpu(packet, l2_len, true)
+            }
             _ => 0,
         }
     }
@@ -630,10 +1000,51 @@ impl TunnelInfo {
             IpProtocol::IPV6 if tunnel_types.has(TunnelType::Ipip) => {
                 self.decapsulate_ipip(packet, l2_len, true, true)
             }
+            IpProtocol::IPV6_ROUTING if tunnel_types.has(TunnelType::Srv6) => {
+                self.decapsulate_srv6(packet, l2_len)
+            }
             _ => 0,
         }
     }
 
+    // SRv6 encapsulation (H.Encaps), the inner ip packet follows the segment routing header
+    pub fn decapsulate_srv6(&mut self, packet: &mut [u8], l2_len: usize) -> usize {
+        let srh_offset = l2_len + IPV6_HEADER_SIZE;
+        let Some((srv6, srh_size)) = Srv6Info::parse(&packet[srh_offset..]) else {
+            return 0;
+        };
+        let overlay_eth_type = match IpProtocol::from(packet[srh_offset]) {
+            IpProtocol::IPV4 => EthernetType::IPV4,
+            IpProtocol::IPV6 => EthernetType::IPV6,
+            _ => return 0,
+        };
+        let overlay_offset = srh_offset + srh_size - ETH_HEADER_SIZE;
+        if packet.len() <= overlay_offset + ETH_HEADER_SIZE {
+            return 0;
+        }
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            self.decapsulate_v6_addr(&packet[l2_len..]);
+            self.decapsulate_mac(packet);
+            self.is_ipv6 = true;
+            self.tunnel_type = TunnelType::Srv6;
+            self.id = 0;
+            self.srv6 = Some(srv6);
+        }
+        self.tier += 1;
+
+        // 内层为IP时没有L2层，将underlay的MAC作为伪造L2层放在overlay ip头前
+        packet.copy_within(0..FIELD_OFFSET_ETH_TYPE, overlay_offset);
+        bytes::write_u16_be(
+            &mut packet[overlay_offset + FIELD_OFFSET_ETH_TYPE..],
+            u16::from(overlay_eth_type),
+        );
+
+        // return offset start from L3
+        overlay_offset - l2_len
+    }
+
     pub fn is_valid(self) -> bool {
         self.tunnel_type != TunnelType::None
     }
@@ -744,6 +1155,12 @@ mod tests {
             tunnel_type: TunnelType::ErspanOrTeb,
             tier: 1,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("decapsulate_erspan1.pcap"),
@@ -780,6 +1197,12 @@ mod tests {
             tunnel_type: TunnelType::ErspanOrTeb,
             tier: 1,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap"),
@@ -816,6 +1239,12 @@ mod tests {
             tunnel_type: TunnelType::ErspanOrTeb,
             tier: 1,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap"),
@@ -833,6 +1262,40 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_decapsulate_erspan_iii_timestamp() {
+        let mut header = [0u8; ERSPAN_III_HEADER_SIZE + ERSPAN_III_SUBHEADER_SIZE];
+        header[0] = 0x20;
+        header[ERSPAN_III_TIMESTAMP_OFFSET..ERSPAN_III_TIMESTAMP_OFFSET + 4]
+            .copy_from_slice(&123456789u32.to_be_bytes());
+        header[ERSPAN_III_SGT_OFFSET..ERSPAN_III_SGT_OFFSET + 2]
+            .copy_from_slice(&100u16.to_be_bytes());
+        header[ERSPAN_III_FLAGS_OFFSET] = ERSPAN_III_GRA_IEEE_1588 << 1 | ERSPAN_III_FLAG_OPTIONAL;
+        header[ERSPAN_III_HEADER_SIZE] = ERSPAN_III_PLATFORM_ID_IEEE_1588 << 2;
+        header[ERSPAN_III_HEADER_SIZE + ERSPAN_III_SUBHEADER_SECONDS_OFFSET..]
+            .copy_from_slice(&1700000000u32.to_be_bytes());
+
+        let mut tunnel_info = TunnelInfo::default();
+        tunnel_info.decapsulate_erspan_iii(&header);
+        assert_eq!(tunnel_info.erspan_sgt, 100);
+        assert_eq!(
+            tunnel_info.erspan_timestamp,
+            Some(Duration::new(1700000000, 123456789))
+        );
+
+        // timestamp granularity of 100 microseconds is not absolute time
+        header[ERSPAN_III_FLAGS_OFFSET] = ERSPAN_III_FLAG_OPTIONAL;
+        let mut tunnel_info = TunnelInfo::default();
+        tunnel_info.decapsulate_erspan_iii(&header);
+        assert_eq!(tunnel_info.erspan_sgt, 100);
+        assert_eq!(tunnel_info.erspan_timestamp, None);
+
+        // no platform specific subheader
+        let mut tunnel_info = TunnelInfo::default();
+        tunnel_info.decapsulate_erspan_iii(&header[..ERSPAN_III_HEADER_SIZE]);
+        assert_eq!(tunnel_info.erspan_timestamp, None);
+    }
+
     #[test]
     fn test_decapsulate_vxlan() {
         let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Vxlan]);
@@ -845,6 +1308,12 @@ mod tests {
             tunnel_type: TunnelType::Vxlan,
             tier: 1,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap"),
@@ -874,6 +1343,12 @@ mod tests {
             tunnel_type: TunnelType::TencentGre,
             tier: 1,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let expected_overlay = [
             0x00, 0x00, 0x00, 0x00, 0x02, 0x85, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00,
@@ -911,6 +1386,12 @@ mod tests {
             tunnel_type: TunnelType::ErspanOrTeb,
             tier: 1,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let mut packets: Vec<Vec<u8>> = Capture::load_pcap(
             Path::new(PCAP_PATH_PREFIX).join("vmware-gre-teb.pcap"),
@@ -940,6 +1421,12 @@ mod tests {
             tunnel_type: TunnelType::Vxlan,
             tier: 1,
             is_ipv6: true,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("ip6-vxlan.pcap"), None).into();
@@ -966,6 +1453,12 @@ mod tests {
             tunnel_type: TunnelType::Ipip,
             tier: 1,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("ipip.pcap"), None).into();
@@ -1002,6 +1495,183 @@ mod tests {
         assert!(actual_bitmap.has(TunnelType::ErspanOrTeb));
     }
 
+    #[test]
+    fn test_decapsulate_vxlan_gpe_nsh() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Vxlan]);
+        let mut packet = vec![
+            // ethernet
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x66, 0x77, 0x88, 0x99, 0xaa, 0x08, 0x00,
+            // ipv4, udp
+            0x45, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, //
+            // udp, dst port 4790
+            0x30, 0x39, 0x12, 0xb6, 0x00, 0x2c, 0x00, 0x00, //
+            // vxlan-gpe, next protocol nsh, vni 100
+            0x0c, 0x00, 0x00, 0x04, 0x00, 0x00, 0x64, 0x00, //
+            // nsh, length 2, md type 2, next protocol ipv4, spi 1000, si 254
+            0x00, 0x02, 0x02, 0x01, 0x00, 0x03, 0xe8, 0xfe, //
+            // inner ipv4
+            0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+        ];
+
+        let l2_len = 14;
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate(&mut packet, l2_len, &bitmap);
+        assert_eq!(
+            offset,
+            IPV4_HEADER_SIZE + UDP_HEADER_SIZE + VXLAN_HEADER_SIZE + NSH_MIN_HEADER_SIZE
+                - ETH_HEADER_SIZE
+        );
+        assert_eq!(actual.tunnel_type, TunnelType::Vxlan);
+        assert_eq!(actual.id, 100);
+        assert_eq!(actual.nsh_spi, 1000);
+        assert_eq!(actual.nsh_si, 254);
+        assert_eq!(actual.src, Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(actual.dst, Ipv4Addr::new(10, 0, 0, 2));
+
+        // pseudo ethernet header in front of the inner ipv4
+        let overlay = &packet[l2_len + offset..];
+        assert_eq!(&overlay[..12], &packet[..12]);
+        assert_eq!(
+            bytes::read_u16_be(&overlay[FIELD_OFFSET_ETH_TYPE..]),
+            u16::from(EthernetType::IPV4)
+        );
+        assert_eq!(overlay[ETH_HEADER_SIZE], 0x45);
+    }
+
+    #[test]
+    fn test_decapsulate_gtpu() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::GtpU]);
+        let mut packet = vec![
+            // ethernet
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x66, 0x77, 0x88, 0x99, 0xaa, 0x08, 0x00,
+            // ipv4, udp
+            0x45, 0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x01, 0x0a, 0x00, 0x00, 0x02, //
+            // udp, dst port 2152
+            0x08, 0x68, 0x08, 0x68, 0x00, 0x34, 0x00, 0x00, //
+            // gtp-u g-pdu with extension header, teid 0x12345678
+            0x34, 0xff, 0x00, 0x24, 0x12, 0x34, 0x56, 0x78, 0x00, 0x00, 0x00, 0x85, //
+            // pdu session container, qfi 9
+            0x01, 0x10, 0x09, 0x00, //
+            // inner ipv4
+            0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+        ];
+
+        let l2_len = 14;
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate(&mut packet, l2_len, &bitmap);
+        assert_eq!(
+            offset,
+            IPV4_HEADER_SIZE + UDP_HEADER_SIZE + GTPU_HEADER_SIZE + GTPU_OPTIONAL_SIZE + 4
+                - ETH_HEADER_SIZE
+        );
+        assert_eq!(actual.tunnel_type, TunnelType::GtpU);
+        assert_eq!(actual.id, 0x12345678);
+        assert_eq!(actual.tier, 1);
+        assert_eq!(actual.src, Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(actual.dst, Ipv4Addr::new(10, 0, 0, 2));
+
+        let overlay = &packet[l2_len + offset..];
+        assert_eq!(&overlay[..12], &packet[..12]);
+        assert_eq!(
+            bytes::read_u16_be(&overlay[FIELD_OFFSET_ETH_TYPE..]),
+            u16::from(EthernetType::IPV4)
+        );
+        assert_eq!(overlay[ETH_HEADER_SIZE], 0x45);
+
+        // gtp-u is decapsulated only if enabled
+        let mut actual = TunnelInfo::default();
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Vxlan]);
+        assert_eq!(actual.decapsulate(&mut packet, l2_len, &bitmap), 0);
+    }
+
+    #[test]
+    fn test_decapsulate_srv6() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Srv6]);
+        let segment = |last: u8| {
+            let mut s = [0u8; 16];
+            s[..4].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+            s[15] = last;
+            s
+        };
+        let mut packet = vec![
+            // ethernet
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x66, 0x77, 0x88, 0x99, 0xaa, 0x86, 0xdd,
+            // ipv6, next header routing
+            0x60, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x2b, 0x40,
+        ];
+        packet.extend_from_slice(&[0xfc, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        packet.extend_from_slice(&segment(1));
+        // srh, next header ipv4, 2 segments with 1 left
+        packet.extend_from_slice(&[0x04, 0x04, 0x04, 0x01, 0x01, 0x00, 0x00, 0x00]);
+        packet.extend_from_slice(&segment(2));
+        packet.extend_from_slice(&segment(1));
+        // inner ipv4
+        packet.extend_from_slice(&[
+            0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0x02,
+        ]);
+
+        let l2_len = 14;
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate_v6(&mut packet, l2_len, &bitmap);
+        assert_eq!(
+            offset,
+            IPV6_HEADER_SIZE + SRH_FIXED_SIZE + 2 * SRV6_SEGMENT_SIZE - ETH_HEADER_SIZE
+        );
+        assert_eq!(actual.tunnel_type, TunnelType::Srv6);
+        assert!(actual.is_ipv6);
+        let srv6 = actual.srv6.unwrap();
+        assert_eq!(srv6.segments_left, 1);
+        assert_eq!(srv6.active_segment, Ipv6Addr::from(segment(1)));
+        assert_eq!(
+            srv6.segments(),
+            &[Ipv6Addr::from(segment(2)), Ipv6Addr::from(segment(1))]
+        );
+
+        let overlay = &packet[l2_len + offset..];
+        assert_eq!(&overlay[..12], &packet[..12]);
+        assert_eq!(
+            bytes::read_u16_be(&overlay[FIELD_OFFSET_ETH_TYPE..]),
+            u16::from(EthernetType::IPV4)
+        );
+        assert_eq!(overlay[ETH_HEADER_SIZE], 0x45);
+    }
+
+    #[test]
+    fn test_geneve_options() {
+        let options = [
+            // unknown class
+            0x01, 0x02, 0x80, 0x01, 0x01, 0xbd, 0x01, 0xbe, //
+            // gwlb endpoint id
+            0x01, 0x08, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, //
+            // gwlb attachment id
+            0x01, 0x08, 0x02, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, //
+            // gwlb flow cookie
+            0x01, 0x08, 0x03, 0x01, 0x12, 0x34, 0x56, 0x78,
+        ];
+        assert_eq!(
+            GeneveOptions::parse(&options),
+            GeneveOptions {
+                gwlb_endpoint_id: 1,
+                gwlb_attachment_id: 2,
+                gwlb_flow_cookie: 0x12345678,
+            }
+        );
+        // truncated option is ignored
+        assert_eq!(
+            GeneveOptions::parse(&options[..options.len() - 1]),
+            GeneveOptions {
+                gwlb_endpoint_id: 1,
+                gwlb_attachment_id: 2,
+                gwlb_flow_cookie: 0,
+            }
+        );
+    }
+
     #[test]
     fn test_decapsulate_geneve() {
         let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Geneve]);
@@ -1014,6 +1684,12 @@ mod tests {
             tunnel_type: TunnelType::Geneve,
             tier: 1,
             is_ipv6: false,
+            erspan_sgt: 0,
+            erspan_timestamp: None,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            srv6: None,
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("geneve.pcap"), None).into();
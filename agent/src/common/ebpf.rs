@@ -35,6 +35,9 @@ pub const IO_EVENT: u8 = 4;
 #[allow(dead_code)]
 // hook in go http2 read/write data
 pub const GO_HTTP2_UPROBE_DATA: u8 = 5;
+#[allow(dead_code)]
+// syscall data of sockets with kernel TLS offload, it is the plaintext of the tls records
+pub const KTLS: u8 = 6;
 
 const EBPF_TYPE_TRACEPOINT: u8 = 0;
 const EBPF_TYPE_TLS_UPROBE: u8 = 1;
@@ -81,7 +84,7 @@ impl TryFrom<u8> for EbpfType {
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            GO_TLS_UPROBE | OPENSSL_UPROBE => Ok(Self::TlsUprobe),
+            GO_TLS_UPROBE | OPENSSL_UPROBE | KTLS => Ok(Self::TlsUprobe),
             GO_HTTP2_UPROBE => Ok(Self::GoHttp2Uprobe),
             GO_HTTP2_UPROBE_DATA => Ok(Self::GoHttp2UprobeData),
             SYSCALL => Ok(Self::TracePoint),
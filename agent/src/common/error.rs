@@ -27,6 +27,8 @@ pub enum Error {
     #[error("{0}")]
     ParseGpid(String),
     #[error("{0}")]
+    ParseConntrack(String),
+    #[error("{0}")]
     ParseEventData(String),
     #[error("{0}")]
     InvalidProtocol(String),
@@ -30,7 +30,7 @@ use serde::{Serialize, Serializer};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use super::super::ebpf::{MSG_REQUEST, MSG_REQUEST_END, MSG_RESPONSE, MSG_RESPONSE_END};
 use super::{
-    decapsulate::TunnelType,
+    decapsulate::{GeneveOptions, TunnelType},
     enums::{EthernetType, IpProtocol, TapType, TcpFlags},
     tap_port::TapPort,
     TaggedFlow,
@@ -242,6 +242,14 @@ pub struct TunnelField {
     pub tier: u8,
     #[serde(skip)]
     pub is_ipv6: bool,
+    #[serde(flatten)]
+    pub geneve_options: GeneveOptions,
+    #[serde(rename = "tunnel_nsh_spi")]
+    pub nsh_spi: u32,
+    #[serde(rename = "tunnel_nsh_si")]
+    pub nsh_si: u8,
+    #[serde(rename = "tunnel_erspan_sgt")]
+    pub erspan_sgt: u16,
 }
 
 pub fn mac_low32_to_string<S>(d: &u32, serializer: S) -> Result<S::Ok, S::Error>
@@ -267,6 +275,10 @@ impl Default for TunnelField {
             tunnel_type: TunnelType::default(),
             tier: 0,
             is_ipv6: false,
+            geneve_options: GeneveOptions::default(),
+            nsh_spi: 0,
+            nsh_si: 0,
+            erspan_sgt: 0,
         }
     }
 }
@@ -315,6 +327,12 @@ impl From<TunnelField> for flow_log::TunnelField {
             tunnel_type: f.tunnel_type as u32,
             tier: f.tier as u32,
             is_ipv6: 0,
+            gwlb_endpoint_id: f.geneve_options.gwlb_endpoint_id,
+            gwlb_attachment_id: f.geneve_options.gwlb_attachment_id,
+            gwlb_flow_cookie: f.geneve_options.gwlb_flow_cookie,
+            nsh_spi: f.nsh_spi,
+            nsh_si: f.nsh_si as u32,
+            erspan_sgt: f.erspan_sgt as u32,
         }
     }
 }
@@ -323,12 +341,14 @@ impl From<TunnelField> for flow_log::TunnelField {
 pub struct TcpPerfCountsPeer {
     pub retrans_count: u32,
     pub zero_win_count: u32,
+    pub dup_ack_count: u32,
 }
 
 impl TcpPerfCountsPeer {
     pub fn sequential_merge(&mut self, other: &TcpPerfCountsPeer) {
         self.retrans_count += other.retrans_count;
         self.zero_win_count += other.zero_win_count;
+        self.dup_ack_count += other.dup_ack_count;
     }
 }
 
@@ -337,6 +357,7 @@ impl From<TcpPerfCountsPeer> for flow_log::TcpPerfCountsPeer {
         flow_log::TcpPerfCountsPeer {
             retrans_count: p.retrans_count,
             zero_win_count: p.zero_win_count,
+            dup_ack_count: p.dup_ack_count,
         }
     }
 }
@@ -364,6 +385,15 @@ pub struct TcpPerfStats {
     pub art_count: u32, // UDP复用
     pub cit_count: u32,
 
+    // us, sampled from data segments and their ACKs during the whole flow, while `rtt` only
+    // comes from the handshake. client: ACKs sent by client, server: ACKs sent by server
+    pub ack_rtt_client_max: u32,
+    pub ack_rtt_server_max: u32,
+    pub ack_rtt_client_sum: u32,
+    pub ack_rtt_server_sum: u32,
+    pub ack_rtt_client_count: u32,
+    pub ack_rtt_server_count: u32,
+
     pub syn_count: u32,
     pub synack_count: u32,
 
@@ -391,12 +421,16 @@ where
         pub retrans_rx: u32,
         pub zero_win_tx: u32,
         pub zero_win_rx: u32,
+        pub dup_ack_tx: u32,
+        pub dup_ack_rx: u32,
     }
     let s = Ser {
         retrans_tx: v[0].retrans_count,
         retrans_rx: v[1].retrans_count,
         zero_win_tx: v[0].zero_win_count,
         zero_win_rx: v[1].zero_win_count,
+        dup_ack_tx: v[0].dup_ack_count,
+        dup_ack_rx: v[1].dup_ack_count,
     };
     serializer.serialize_newtype_struct("tcp_perf_counts", &s)
 }
@@ -421,12 +455,20 @@ impl TcpPerfStats {
         if self.cit_max < other.cit_max {
             self.cit_max = other.cit_max;
         }
+        if self.ack_rtt_client_max < other.ack_rtt_client_max {
+            self.ack_rtt_client_max = other.ack_rtt_client_max;
+        }
+        if self.ack_rtt_server_max < other.ack_rtt_server_max {
+            self.ack_rtt_server_max = other.ack_rtt_server_max;
+        }
 
         self.rtt_client_sum += other.rtt_client_sum;
         self.rtt_server_sum += other.rtt_server_sum;
         self.srt_sum += other.srt_sum;
         self.art_sum += other.art_sum;
         self.cit_sum += other.cit_sum;
+        self.ack_rtt_client_sum += other.ack_rtt_client_sum;
+        self.ack_rtt_server_sum += other.ack_rtt_server_sum;
 
         self.rtt_client_count += other.rtt_client_count;
         self.rtt_server_count += other.rtt_server_count;
@@ -434,6 +476,8 @@ impl TcpPerfStats {
         self.art_count += other.art_count;
         self.syn_count += other.syn_count;
         self.cit_count += other.cit_count;
+        self.ack_rtt_client_count += other.ack_rtt_client_count;
+        self.ack_rtt_server_count += other.ack_rtt_server_count;
         self.synack_count += other.synack_count;
         self.retrans_syn_count += other.retrans_syn_count;
         self.retrans_synack_count += other.retrans_synack_count;
@@ -445,6 +489,12 @@ impl TcpPerfStats {
     pub fn reverse(&mut self) {
         swap(&mut self.rtt_client_sum, &mut self.rtt_server_sum);
         swap(&mut self.rtt_client_count, &mut self.rtt_server_count);
+        swap(&mut self.ack_rtt_client_max, &mut self.ack_rtt_server_max);
+        swap(&mut self.ack_rtt_client_sum, &mut self.ack_rtt_server_sum);
+        swap(
+            &mut self.ack_rtt_client_count,
+            &mut self.ack_rtt_server_count,
+        );
         self.counts_peers.swap(0, 1);
     }
 }
@@ -469,6 +519,12 @@ impl From<TcpPerfStats> for flow_log::TcpPerfStats {
             cit_max: p.cit_max,
             syn_count: p.syn_count,
             synack_count: p.synack_count,
+            ack_rtt_client_max: p.ack_rtt_client_max,
+            ack_rtt_server_max: p.ack_rtt_server_max,
+            ack_rtt_client_sum: p.ack_rtt_client_sum,
+            ack_rtt_server_sum: p.ack_rtt_server_sum,
+            ack_rtt_client_count: p.ack_rtt_client_count,
+            ack_rtt_server_count: p.ack_rtt_server_count,
             ..Default::default()
         }
     }
@@ -480,6 +536,8 @@ pub struct FlowPerfStats {
     pub tcp: TcpPerfStats,
     #[serde(flatten)]
     pub l7: L7PerfStats,
+    #[serde(flatten)]
+    pub media: MediaPerfStats,
     pub l4_protocol: L4Protocol,
     pub l7_protocol: L7Protocol,
     pub l7_failed_count: u32,
@@ -499,6 +557,7 @@ impl FlowPerfStats {
 
         self.tcp.sequential_merge(&other.tcp);
         self.l7.sequential_merge(&other.l7);
+        self.media.sequential_merge(&other.media);
     }
 
     pub fn reverse(&mut self) {
@@ -521,6 +580,7 @@ impl From<FlowPerfStats> for flow_log::FlowPerfStats {
         flow_log::FlowPerfStats {
             tcp: Some(p.tcp.into()),
             l7: Some(p.l7.into()),
+            media: Some(p.media.into()),
             l4_protocol: p.l4_protocol as u32,
             l7_protocol: p.l7_protocol as u32,
             l7_failed_count: p.l7_failed_count,
@@ -539,6 +599,343 @@ pub struct L7Stats {
     pub time_in_second: Duration,
 }
 
+pub const RRT_HISTOGRAM_MAX_BUCKETS: usize = 16;
+
+// Exponential histogram of rrt, the upper bound of bucket i is start * factor^i microseconds,
+// and the bucket after the last bounded one counts rrt above all bounds
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RrtHistogram {
+    pub start: u32,
+    pub factor: u32,
+    // number of bounded buckets, 0 means disabled
+    pub bucket_count: u8,
+    pub counts: [u32; RRT_HISTOGRAM_MAX_BUCKETS],
+}
+
+impl RrtHistogram {
+    pub fn new(start: u32, factor: u32, bucket_count: u8) -> Self {
+        Self {
+            start,
+            factor,
+            bucket_count: bucket_count.min(RRT_HISTOGRAM_MAX_BUCKETS as u8 - 1),
+            counts: Default::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bucket_count == 0
+    }
+
+    pub fn same_layout(&self, other: &RrtHistogram) -> bool {
+        self.start == other.start
+            && self.factor == other.factor
+            && self.bucket_count == other.bucket_count
+    }
+
+    pub fn record(&mut self, rrt: u64) {
+        if self.is_empty() {
+            return;
+        }
+        let mut bound = self.start as u64;
+        let mut i = 0;
+        while i < self.bucket_count as usize && rrt > bound {
+            bound = bound.saturating_mul(self.factor as u64);
+            i += 1;
+        }
+        self.counts[i] += 1;
+    }
+
+    pub fn merge(&mut self, other: &RrtHistogram) {
+        if other.is_empty() {
+            return;
+        }
+        // the layout is changed by config, counts of the previous layout are dropped
+        if !self.same_layout(other) {
+            *self = *other;
+            return;
+        }
+        for (c, o) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *c += o;
+        }
+    }
+
+    // counts of bounded buckets and the last unbounded bucket
+    pub fn counts(&self) -> &[u32] {
+        if self.is_empty() {
+            return &[];
+        }
+        &self.counts[..=self.bucket_count as usize]
+    }
+}
+
+// rrt far below the maximum are collapsed into the lowest bin beyond this limit
+pub const RRT_SKETCH_MAX_BINS: usize = 2048;
+
+// DDSketch of rrt in microseconds with relative accuracy alpha, bin i counts rrt in
+// (gamma^(offset+i-1), gamma^(offset+i)] where gamma = (1 + alpha) / (1 - alpha)
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct RrtSketch {
+    // relative accuracy in 1/10000
+    pub alpha: u16,
+    pub offset: i32,
+    pub bins: Vec<u32>,
+}
+
+impl RrtSketch {
+    pub fn new(alpha: u16) -> Self {
+        Self {
+            alpha,
+            ..Default::default()
+        }
+    }
+
+    fn gamma(&self) -> f64 {
+        let alpha = self.alpha as f64 / 10000.0;
+        (1.0 + alpha) / (1.0 - alpha)
+    }
+
+    fn key(&self, rrt: u64) -> i32 {
+        ((rrt.max(1) as f64).ln() / self.gamma().ln()).ceil() as i32
+    }
+
+    fn max_key(&self) -> i32 {
+        self.offset + self.bins.len() as i32 - 1
+    }
+
+    // extend bins to cover keys in [low, high]
+    fn extend(&mut self, low: i32, high: i32) {
+        let (low, high) = if self.bins.is_empty() {
+            (low, high)
+        } else {
+            (low.min(self.offset), high.max(self.max_key()))
+        };
+        let low = low.max(high - RRT_SKETCH_MAX_BINS as i32 + 1);
+        if !self.bins.is_empty() && low == self.offset {
+            self.bins.resize((high - low + 1) as usize, 0);
+            return;
+        }
+        let mut bins = vec![0; (high - low + 1) as usize];
+        for (i, c) in self.bins.iter().enumerate() {
+            let key = (self.offset + i as i32).max(low);
+            bins[(key - low) as usize] += c;
+        }
+        self.bins = bins;
+        self.offset = low;
+    }
+
+    fn add(&mut self, key: i32, count: u32) {
+        let key = key.max(self.offset);
+        self.bins[(key - self.offset) as usize] += count;
+    }
+
+    pub fn record(&mut self, rrt: u64) {
+        if self.alpha == 0 {
+            return;
+        }
+        let key = self.key(rrt);
+        self.extend(key, key);
+        self.add(key, 1);
+    }
+
+    pub fn merge(&mut self, other: &RrtSketch) {
+        if other.bins.is_empty() {
+            return;
+        }
+        // the accuracy is changed by config, counts of the previous accuracy are dropped
+        if self.bins.is_empty() || self.alpha != other.alpha {
+            *self = other.clone();
+            return;
+        }
+        self.extend(other.offset, other.max_key());
+        for (i, c) in other.bins.iter().enumerate() {
+            if *c > 0 {
+                self.add(other.offset + i as i32, *c);
+            }
+        }
+    }
+
+    // estimated rrt at quantile q in [0, 1]
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        let count = self.bins.iter().map(|c| *c as u64).sum::<u64>();
+        if count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (count - 1) as f64) as u64;
+        let gamma = self.gamma();
+        let mut acc = 0;
+        for (i, c) in self.bins.iter().enumerate() {
+            acc += *c as u64;
+            if acc > rank {
+                let value = 2.0 * gamma.powi(self.offset + i as i32) / (gamma + 1.0);
+                return Some(value.round() as u64);
+            }
+        }
+        None
+    }
+}
+
+pub const RESPONSE_CODE_MAX_TOP_K: usize = 16;
+
+// Counts of the most frequent response codes, the others are counted together
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResponseCodeCounts {
+    pub codes: Vec<(String, u32)>,
+    pub others: u32,
+}
+
+impl ResponseCodeCounts {
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty() && self.others == 0
+    }
+
+    pub fn add(&mut self, code: &str, top_k: usize) {
+        if let Some((_, c)) = self.codes.iter_mut().find(|(k, _)| k == code) {
+            *c += 1;
+        } else if self.codes.len() < top_k.min(RESPONSE_CODE_MAX_TOP_K) {
+            self.codes.push((code.to_owned(), 1));
+        } else {
+            self.others += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &ResponseCodeCounts) {
+        for (code, count) in other.codes.iter() {
+            match self.codes.iter_mut().find(|(k, _)| k == code) {
+                Some((_, c)) => *c += count,
+                None => self.codes.push((code.clone(), *count)),
+            }
+        }
+        self.others += other.others;
+        if self.codes.len() > RESPONSE_CODE_MAX_TOP_K {
+            self.codes.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+            for (_, count) in self.codes.drain(RESPONSE_CODE_MAX_TOP_K..) {
+                self.others += count;
+            }
+        }
+    }
+}
+
+// Apdex samples by rrt threshold t: satisfied within t, tolerating within 4t,
+// frustrated beyond 4t or with error responses
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ApdexCounts {
+    pub satisfied: u32,
+    pub tolerating: u32,
+    pub frustrated: u32,
+}
+
+impl ApdexCounts {
+    pub fn is_empty(&self) -> bool {
+        self.satisfied == 0 && self.tolerating == 0 && self.frustrated == 0
+    }
+
+    pub fn record(&mut self, rrt: u64, threshold: u64, is_error: bool) {
+        if is_error || rrt > threshold.saturating_mul(4) {
+            self.frustrated += 1;
+        } else if rrt > threshold {
+            self.tolerating += 1;
+        } else {
+            self.satisfied += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &ApdexCounts) {
+        self.satisfied += other.satisfied;
+        self.tolerating += other.tolerating;
+        self.frustrated += other.frustrated;
+    }
+}
+
+pub const MESSAGE_SIZE_BUCKETS: usize = 10;
+
+// Sizes of l7 messages, the upper bound of histogram bucket i is 64 * 4^i bytes
+// and the last bucket is unbounded
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MessageSizeStats {
+    pub count: u32,
+    pub sum: u64,
+    pub min: u32,
+    pub max: u32,
+    pub histogram: [u32; MESSAGE_SIZE_BUCKETS],
+}
+
+impl MessageSizeStats {
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn record(&mut self, size: u32) {
+        if self.count == 0 || size < self.min {
+            self.min = size;
+        }
+        self.max = self.max.max(size);
+        self.sum += size as u64;
+        self.count += 1;
+        let mut bound = 64;
+        let mut i = 0;
+        while i < MESSAGE_SIZE_BUCKETS - 1 && size > bound {
+            bound *= 4;
+            i += 1;
+        }
+        self.histogram[i] += 1;
+    }
+
+    pub fn merge(&mut self, other: &MessageSizeStats) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+        for (h, o) in self.histogram.iter_mut().zip(other.histogram.iter()) {
+            *h += o;
+        }
+    }
+}
+
+impl From<MessageSizeStats> for flow_log::MessageSizeStats {
+    fn from(s: MessageSizeStats) -> Self {
+        flow_log::MessageSizeStats {
+            count: s.count,
+            sum: s.sum,
+            min: s.min,
+            max: s.max,
+            histogram: s.histogram.to_vec(),
+        }
+    }
+}
+
+// Requests waiting for responses in a flow, sampled on each parsed log
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyStats {
+    pub max: u32,
+    pub sum: u64,
+    pub samples: u32,
+}
+
+impl ConcurrencyStats {
+    pub fn is_empty(&self) -> bool {
+        self.samples == 0
+    }
+
+    pub fn record(&mut self, in_flight: u32) {
+        self.max = self.max.max(in_flight);
+        self.sum += in_flight as u64;
+        self.samples += 1;
+    }
+
+    pub fn merge(&mut self, other: &ConcurrencyStats) {
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.samples += other.samples;
+    }
+}
+
 #[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
 pub struct L7PerfStats {
     #[serde(rename = "l7_request")]
@@ -554,7 +951,31 @@ pub struct L7PerfStats {
     pub rrt_count: u32, // u32可记录40000M时延, 一条流在一分钟内的请求数远无法达到此数值
     pub rrt_sum: u64,   // us RRT(Request Response Time)
     pub rrt_max: u32,   // us agent保证在3600s以内
+    // us, time from ClientHello to Finished, not counted in rrt
     pub tls_rtt: u32,
+    pub tls_rtt_count: u32,
+    pub tls_rtt_max: u32,
+    // connections presenting server certificates about to expire
+    pub tls_cert_expiring: u32,
+    // dns failures by response code, and requests without response within rrt timeout
+    pub dns_nxdomain: u32,
+    pub dns_servfail: u32,
+    pub dns_refused: u32,
+    pub dns_timeout: u32,
+    #[serde(skip_serializing_if = "RrtHistogram::is_empty")]
+    pub rrt_histogram: RrtHistogram,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rrt_sketch: Option<RrtSketch>,
+    #[serde(skip_serializing_if = "ResponseCodeCounts::is_empty")]
+    pub response_codes: ResponseCodeCounts,
+    #[serde(skip_serializing_if = "ApdexCounts::is_empty")]
+    pub apdex: ApdexCounts,
+    #[serde(skip_serializing_if = "MessageSizeStats::is_empty")]
+    pub req_size: MessageSizeStats,
+    #[serde(skip_serializing_if = "MessageSizeStats::is_empty")]
+    pub resp_size: MessageSizeStats,
+    #[serde(skip_serializing_if = "ConcurrencyStats::is_empty")]
+    pub concurrency: ConcurrencyStats,
 }
 
 impl L7PerfStats {
@@ -570,6 +991,26 @@ impl L7PerfStats {
             self.rrt_max = other.rrt_max
         }
         self.tls_rtt += other.tls_rtt;
+        self.tls_rtt_count += other.tls_rtt_count;
+        if self.tls_rtt_max < other.tls_rtt_max {
+            self.tls_rtt_max = other.tls_rtt_max
+        }
+        self.tls_cert_expiring += other.tls_cert_expiring;
+        self.dns_nxdomain += other.dns_nxdomain;
+        self.dns_servfail += other.dns_servfail;
+        self.dns_refused += other.dns_refused;
+        self.dns_timeout += other.dns_timeout;
+        self.rrt_histogram.merge(&other.rrt_histogram);
+        if let Some(sketch) = other.rrt_sketch.as_ref() {
+            self.rrt_sketch
+                .get_or_insert_with(RrtSketch::default)
+                .merge(sketch);
+        }
+        self.response_codes.merge(&other.response_codes);
+        self.apdex.merge(&other.apdex);
+        self.req_size.merge(&other.req_size);
+        self.resp_size.merge(&other.resp_size);
+        self.concurrency.merge(&other.concurrency);
     }
 
     pub fn merge_perf(
@@ -592,7 +1033,9 @@ impl L7PerfStats {
             self.rrt_count += 1;
         }
         if tls_rtt != 0 {
+            self.tls_rtt_max = self.tls_rtt_max.max(tls_rtt as u32);
             self.tls_rtt += tls_rtt as u32;
+            self.tls_rtt_count += 1;
         }
     }
 
@@ -619,10 +1062,15 @@ impl L7PerfStats {
     pub fn update_tls_rtt(&mut self, tls_rtt: u64) {
         self.merge_perf(0, 0, 0, 0, 0, tls_rtt);
     }
+
+    pub fn inc_tls_cert_expiring(&mut self) {
+        self.tls_cert_expiring += 1;
+    }
 }
 
 impl From<L7PerfStats> for flow_log::L7PerfStats {
     fn from(p: L7PerfStats) -> Self {
+        let sketch = p.rrt_sketch.unwrap_or_default();
         flow_log::L7PerfStats {
             request_count: p.request_count,
             response_count: p.response_count,
@@ -633,6 +1081,97 @@ impl From<L7PerfStats> for flow_log::L7PerfStats {
             rrt_sum: p.rrt_sum,
             rrt_max: p.rrt_max,
             tls_rtt: p.tls_rtt,
+            tls_rtt_count: p.tls_rtt_count,
+            tls_rtt_max: p.tls_rtt_max,
+            tls_cert_expiring: p.tls_cert_expiring,
+            dns_nxdomain: p.dns_nxdomain,
+            dns_servfail: p.dns_servfail,
+            dns_refused: p.dns_refused,
+            dns_timeout: p.dns_timeout,
+            rrt_histogram_start: p.rrt_histogram.start,
+            rrt_histogram_factor: p.rrt_histogram.factor,
+            rrt_histogram: p.rrt_histogram.counts().to_vec(),
+            rrt_sketch_alpha: sketch.alpha as u32,
+            rrt_sketch_offset: sketch.offset,
+            rrt_sketch_bins: sketch.bins,
+            response_codes: p
+                .response_codes
+                .codes
+                .into_iter()
+                .map(|(code, count)| flow_log::ResponseCodeCount { code, count })
+                .collect(),
+            response_code_others: p.response_codes.others,
+            apdex_satisfied: p.apdex.satisfied,
+            apdex_tolerating: p.apdex.tolerating,
+            apdex_frustrated: p.apdex.frustrated,
+            req_size: (!p.req_size.is_empty()).then(|| p.req_size.into()),
+            resp_size: (!p.resp_size.is_empty()).then(|| p.resp_size.into()),
+            concurrency_max: p.concurrency.max,
+            concurrency_sum: p.concurrency.sum,
+            concurrency_samples: p.concurrency.samples,
+        }
+    }
+}
+
+// RTP stream quality of a flow, summed over all SSRCs in the flow
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct MediaPerfStats {
+    #[serde(rename = "media_ssrc")]
+    pub ssrc_count: u32,
+    #[serde(rename = "media_packet")]
+    pub packet_count: u32,
+    // packets expected according to the sequence numbers
+    #[serde(rename = "media_expected")]
+    pub expected_count: u32,
+    #[serde(rename = "media_lost")]
+    pub lost_count: u32,
+    #[serde(rename = "media_out_of_order")]
+    pub out_of_order_count: u32,
+    // us, interarrival jitter of RFC 3550, sampled on each packet
+    #[serde(rename = "media_jitter_sum")]
+    pub jitter_sum: u64,
+    #[serde(rename = "media_jitter_count")]
+    pub jitter_count: u32,
+    #[serde(rename = "media_jitter_max")]
+    pub jitter_max: u32,
+    // receiver reports, fraction lost is in units of 1/256
+    #[serde(rename = "rtcp_packet")]
+    pub rtcp_count: u32,
+    #[serde(rename = "rtcp_fraction_lost_max")]
+    pub rtcp_fraction_lost_max: u32,
+}
+
+impl MediaPerfStats {
+    pub fn sequential_merge(&mut self, other: &MediaPerfStats) {
+        // the same streams are reported in each period
+        self.ssrc_count = self.ssrc_count.max(other.ssrc_count);
+        self.packet_count += other.packet_count;
+        self.expected_count += other.expected_count;
+        self.lost_count += other.lost_count;
+        self.out_of_order_count += other.out_of_order_count;
+        self.jitter_sum += other.jitter_sum;
+        self.jitter_count += other.jitter_count;
+        self.jitter_max = self.jitter_max.max(other.jitter_max);
+        self.rtcp_count += other.rtcp_count;
+        self.rtcp_fraction_lost_max = self
+            .rtcp_fraction_lost_max
+            .max(other.rtcp_fraction_lost_max);
+    }
+}
+
+impl From<MediaPerfStats> for flow_log::MediaPerfStats {
+    fn from(p: MediaPerfStats) -> Self {
+        flow_log::MediaPerfStats {
+            ssrc_count: p.ssrc_count,
+            packet_count: p.packet_count,
+            expected_count: p.expected_count,
+            lost_count: p.lost_count,
+            out_of_order_count: p.out_of_order_count,
+            jitter_sum: p.jitter_sum,
+            jitter_count: p.jitter_count,
+            jitter_max: p.jitter_max,
+            rtcp_count: p.rtcp_count,
+            rtcp_fraction_lost_max: p.rtcp_fraction_lost_max,
         }
     }
 }
@@ -923,6 +1462,11 @@ pub struct Flow {
 
     /* L2 */
     pub vlan: u16,
+    // outer vlan tag of QinQ flows when both tags are used
+    pub outer_vlan: u16,
+    // segment list in the order of the SRv6 segment routing header and the active segment
+    pub srv6_segments: Vec<Ipv6Addr>,
+    pub srv6_active_segment: Option<Ipv6Addr>,
     pub eth_type: EthernetType,
 
     /* TCP Perf Data*/
@@ -951,6 +1495,23 @@ pub struct Flow {
     pub last_endpoint: Option<String>,
     pub direction_score: u8,
     pub pod_id: u32,
+
+    /* ICMP */
+    // type and code of the first packet of icmp flows
+    pub icmp_type: u8,
+    pub icmp_code: u8,
+    // the last icmp error message (destination unreachable, time exceeded, etc.)
+    // caused by packets of tcp/udp flows
+    pub icmp_error_type: u8,
+    pub icmp_error_code: u8,
+    pub icmp_error_count: u32,
+    // next-hop mtu of fragmentation needed or packet too big
+    pub icmp_error_mtu: u32,
+
+    /* SCTP */
+    // payload protocol identifier of DATA chunks
+    pub sctp_ppid: u32,
+    pub sctp_stream_count: u32,
 }
 
 fn tunnel_is_none(t: &TunnelField) -> bool {
@@ -959,9 +1520,14 @@ fn tunnel_is_none(t: &TunnelField) -> bool {
 
 impl Flow {
     pub fn start_time_in_minute(&self) -> u64 {
-        let second_in_minute = self.start_time.as_secs() % SECONDS_IN_MINUTE;
-        (self.flow_stat_time.as_secs() - second_in_minute) / SECONDS_IN_MINUTE * SECONDS_IN_MINUTE
-            + second_in_minute
+        self.start_time_in_interval(SECONDS_IN_MINUTE)
+    }
+
+    // intervals of a flow are aligned to its start time
+    pub fn start_time_in_interval(&self, interval: u64) -> u64 {
+        let second_in_interval = self.start_time.as_secs() % interval;
+        (self.flow_stat_time.as_secs() - second_in_interval) / interval * interval
+            + second_in_interval
     }
 
     fn swap_flow_ip_and_real_ip(&mut self) {
@@ -997,6 +1563,13 @@ impl Flow {
         if other.vlan > 0 {
             self.vlan = other.vlan
         }
+        if other.outer_vlan > 0 {
+            self.outer_vlan = other.outer_vlan
+        }
+        if self.srv6_active_segment.is_none() && other.srv6_active_segment.is_some() {
+            self.srv6_segments = other.srv6_segments.clone();
+            self.srv6_active_segment = other.srv6_active_segment;
+        }
 
         if other.last_keepalive_seq != 0 {
             self.last_keepalive_seq = other.last_keepalive_seq;
@@ -1021,6 +1594,20 @@ impl Flow {
         if nat_source > self.flow_key.tap_port.get_nat_source() {
             self.flow_key.tap_port.set_nat_source(nat_source);
         }
+
+        if other.sctp_ppid != 0 {
+            self.sctp_ppid = other.sctp_ppid;
+        }
+        self.sctp_stream_count = self.sctp_stream_count.max(other.sctp_stream_count);
+
+        if other.icmp_error_count > 0 {
+            self.icmp_error_type = other.icmp_error_type;
+            self.icmp_error_code = other.icmp_error_code;
+            self.icmp_error_count += other.icmp_error_count;
+            if other.icmp_error_mtu > 0 {
+                self.icmp_error_mtu = other.icmp_error_mtu;
+            }
+        }
     }
 
     // FIXME 注意：由于FlowGenerator中TcpPerfStats在Flow方向调整之后才获取到，
@@ -1186,6 +1773,16 @@ impl From<Flow> for flow_log::Flow {
             duration: f.duration.as_nanos() as u64,
             eth_type: u16::from(f.eth_type) as u32,
             vlan: f.vlan as u32,
+            outer_vlan: f.outer_vlan as u32,
+            srv6_segments: f
+                .srv6_segments
+                .iter()
+                .map(|s| s.octets().to_vec())
+                .collect(),
+            srv6_active_segment: f
+                .srv6_active_segment
+                .map(|s| s.octets().to_vec())
+                .unwrap_or_default(),
             has_perf_stats: f.flow_perf_stats.is_some() as u32,
             perf_stats: f.flow_perf_stats.map(|stats| stats.into()),
             close_type: f.close_type as u32,
@@ -1200,6 +1797,14 @@ impl From<Flow> for flow_log::Flow {
             last_keepalive_ack: f.last_keepalive_ack,
             acl_gids: f.acl_gids.into_iter().map(|g| g as u32).collect(),
             direction_score: f.direction_score as u32,
+            icmp_type: f.icmp_type as u32,
+            icmp_code: f.icmp_code as u32,
+            icmp_error_type: f.icmp_error_type as u32,
+            icmp_error_code: f.icmp_error_code as u32,
+            icmp_error_count: f.icmp_error_count,
+            icmp_error_mtu: f.icmp_error_mtu,
+            sctp_ppid: f.sctp_ppid,
+            sctp_stream_count: f.sctp_stream_count,
         }
     }
 }
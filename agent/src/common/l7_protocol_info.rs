@@ -25,9 +25,10 @@ use crate::{
     common::l7_protocol_log::LogCache,
     flow_generator::{
         protocol_logs::{
-            fastcgi::FastCGIInfo, pb_adapter::L7ProtocolSendLog, DnsInfo, DubboInfo, HttpInfo,
-            KafkaInfo, MongoDBInfo, MqttInfo, MysqlInfo, OracleInfo, PostgreInfo, RedisInfo,
-            SofaRpcInfo, TlsInfo,
+            fastcgi::FastCGIInfo, pb_adapter::L7ProtocolSendLog, DhcpInfo, DnsInfo, DubboInfo,
+            HttpInfo, KafkaInfo, KerberosInfo, L7ResponseStatus, MongoDBInfo, MqttInfo, MysqlInfo,
+            NtpInfo, OracleInfo, PostgreInfo, RadiusInfo, RedisInfo, SipInfo, SofaRpcInfo, SshInfo,
+            TlsInfo,
         },
         AppProtoHead, LogMessageType, Result,
     },
@@ -74,6 +75,12 @@ all_protocol_info!(
     OracleInfo(OracleInfo),
     SofaRpcInfo(SofaRpcInfo),
     TlsInfo(TlsInfo),
+    DhcpInfo(DhcpInfo),
+    NtpInfo(NtpInfo),
+    SshInfo(SshInfo),
+    KerberosInfo(KerberosInfo),
+    RadiusInfo(RadiusInfo),
+    SipInfo(SipInfo),
     CustomInfo(CustomInfo),
     // add new protocol info below
 );
@@ -98,6 +105,23 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
         None
     }
 
+    fn response_status(&self) -> L7ResponseStatus {
+        L7ResponseStatus::default()
+    }
+
+    fn request_len(&self) -> Option<u32> {
+        None
+    }
+
+    fn response_len(&self) -> Option<u32> {
+        None
+    }
+
+    // response code counted in perf stats, such as http status, kafka error code and sql error class
+    fn response_code(&self) -> Option<String> {
+        None
+    }
+
     fn skip_send(&self) -> bool {
         false
     }
@@ -155,7 +179,7 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
     fn cal_rrt(&self, param: &ParseParam, kafka_info: Option<KafkaInfoCache>) -> Option<u64> {
         let mut perf_cache = param.l7_perf_cache.borrow_mut();
         let cache_key = self.cal_cache_key(param);
-        let previous_log_info = perf_cache.rrt_cache.pop(&cache_key);
+        let previous_log_info = perf_cache.pop(&cache_key, param.direction.into());
 
         let time = param.time;
         let msg_type: LogMessageType = param.direction.into();
@@ -204,7 +228,7 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
                 // timeout, save the latest
                 if rrt > timeout {
                     *timeout_count += 1;
-                    perf_cache.rrt_cache.put(
+                    perf_cache.put(
                         cache_key,
                         LogCache {
                             msg_type: param.direction.into(),
@@ -230,7 +254,7 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
                     warn!("l7 log info disorder with long time rrt {}", rrt);
                     // timeout, save latest
                     *timeout_count += 1;
-                    perf_cache.rrt_cache.put(cache_key, previous_log_info);
+                    perf_cache.put(cache_key, previous_log_info);
                     None
                 } else {
                     Some(rrt)
@@ -249,7 +273,7 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
                     if previous_log_info.msg_type == LogMessageType::Request {
                         *in_cached_req += 1;
                     }
-                    perf_cache.rrt_cache.put(cache_key, previous_log_info);
+                    perf_cache.put(cache_key, previous_log_info);
                 } else {
                     if previous_log_info.msg_type == LogMessageType::Request {
                         *timeout_count += 1;
@@ -257,7 +281,7 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
                     if msg_type == LogMessageType::Request {
                         *in_cached_req += 1;
                     }
-                    perf_cache.rrt_cache.put(
+                    perf_cache.put(
                         cache_key,
                         LogCache {
                             msg_type: param.direction.into(),
@@ -282,6 +306,102 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
         }
     }
 
+    /*
+        calculate rrt for multiplexed protocols, whose responses may overtake each other
+        logs are kept in the pending request table of the flow keyed by session id:
+            if a request of the same session is pending and current is resp,
+                rrt = current time - request time
+            if a response of the same session is pending and current is req, likely ebpf disorder,
+                rrt = response time - current time
+            otherwise cache the current log
+
+        logs pending longer than rrt timeout are evicted and counted as timeout,
+        fallback to cal_rrt() if session id is none
+    */
+    fn cal_rrt_by_session_id(
+        &self,
+        param: &ParseParam,
+        kafka_info: Option<KafkaInfoCache>,
+    ) -> Option<u64> {
+        let Some(session_id) = self.session_id() else {
+            return self.cal_rrt(param, kafka_info);
+        };
+        let time = param.time;
+        if time == 0 {
+            error!("flow_id: {}, packet time 0", param.flow_id);
+            return None;
+        }
+        let msg_type: LogMessageType = param.direction.into();
+        let timeout = param.rrt_timeout as u64;
+
+        let mut perf_cache = param.l7_perf_cache.borrow_mut();
+        perf_cache.get_or_insert_pending(param.flow_id);
+        let perf_cache = &mut *perf_cache;
+        let (in_cached_req, timeout_count) = perf_cache
+            .timeout_cache
+            .get_or_insert_mut(param.flow_id, || (0, 0));
+        let pending = perf_cache.pending_requests.get_mut(&param.flow_id).unwrap();
+        let pending_len = pending.len();
+
+        let expired = pending.evict_expired(time, timeout);
+        *in_cached_req = in_cached_req.saturating_sub(expired);
+        *timeout_count += expired;
+
+        let rrt = match pending.remove(session_id) {
+            Some(previous)
+                if previous.msg_type == LogMessageType::Request
+                    && msg_type == LogMessageType::Response
+                    && time >= previous.time =>
+            {
+                *in_cached_req = in_cached_req.saturating_sub(1);
+                Some(time - previous.time)
+            }
+            Some(previous)
+                if previous.msg_type == LogMessageType::Response
+                    && msg_type == LogMessageType::Request
+                    && previous.time > time =>
+            {
+                Some(previous.time - time)
+            }
+            previous => {
+                if let Some(previous) = previous {
+                    debug!(
+                        "can not calculate rrt, flow_id: {}, session_id: {}, previous log type:{:?}, previous time: {}, current log type: {:?}, current time: {}",
+                        param.flow_id, session_id, previous.msg_type, previous.time, msg_type, time,
+                    );
+                    // the session id is reused before the request is responded
+                    if previous.msg_type == LogMessageType::Request {
+                        *in_cached_req = in_cached_req.saturating_sub(1);
+                        *timeout_count += 1;
+                    }
+                }
+                if msg_type == LogMessageType::Request {
+                    *in_cached_req += 1;
+                }
+                let evicted = pending.insert(
+                    session_id,
+                    LogCache {
+                        msg_type,
+                        time,
+                        kafka_info,
+                        multi_merge_info: None,
+                    },
+                );
+                if evicted.is_some() {
+                    *in_cached_req = in_cached_req.saturating_sub(1);
+                    *timeout_count += 1;
+                }
+                None
+            }
+        };
+        perf_cache.pending_count = perf_cache.pending_count + pending.len() - pending_len;
+        if pending.len() == 0 {
+            perf_cache.pending_requests.pop(&param.flow_id);
+        }
+        perf_cache.evict_pending();
+        rrt
+    }
+
     // must have request id
     // the main different with cal_rrt() is need to push back to lru when session not end
     fn cal_rrt_for_multi_merge_log(&self, param: &ParseParam) -> Option<u64> {
@@ -289,7 +409,7 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
 
         let mut perf_cache = param.l7_perf_cache.borrow_mut();
         let cache_key = self.cal_cache_key(param);
-        let previous_log_info = perf_cache.rrt_cache.pop(&cache_key);
+        let previous_log_info = perf_cache.pop(&cache_key, param.direction.into());
 
         let time = param.time;
         let msg_type: LogMessageType = param.direction.into();
@@ -389,7 +509,7 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
             };
 
             if put_back {
-                perf_cache.rrt_cache.put(cache_key, previous_log_info);
+                perf_cache.put(cache_key, previous_log_info);
             }
             r
         } else {
@@ -397,7 +517,7 @@ pub trait L7ProtocolInfoInterface: Into<L7ProtocolSendLog> {
                 *timeout_count += 1;
             }
             if put_back {
-                perf_cache.rrt_cache.put(cache_key, previous_log_info);
+                perf_cache.put(cache_key, previous_log_info);
             }
             None
         }
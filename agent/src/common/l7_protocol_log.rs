@@ -15,6 +15,7 @@
  */
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::net::IpAddr;
 use std::rc::Rc;
@@ -26,7 +27,7 @@ use log::debug;
 use lru::LruCache;
 
 use super::ebpf::EbpfType;
-use super::flow::{L7PerfStats, PacketDirection};
+use super::flow::{L7PerfStats, MediaPerfStats, PacketDirection};
 use super::l7_protocol_info::L7ProtocolInfo;
 use super::MetaPacket;
 
@@ -38,8 +39,9 @@ use crate::flow_generator::protocol_logs::plugin::custom_wrap::CustomWrapLog;
 use crate::flow_generator::protocol_logs::plugin::get_custom_log_parser;
 use crate::flow_generator::protocol_logs::sql::ObfuscateCache;
 use crate::flow_generator::protocol_logs::{
-    DnsLog, DubboLog, HttpLog, KafkaLog, MongoDBLog, MqttLog, MysqlLog, OracleLog, PostgresqlLog,
-    RedisLog, SofaRpcLog, TlsLog,
+    DhcpLog, DnsLog, DubboLog, HttpLog, KafkaLog, KerberosLog, MongoDBLog, MqttLog, MysqlLog,
+    NtpLog, OracleLog, PostgresqlLog, RadiusLog, RedisLog, RtpLog, SipLog, SofaRpcLog, SshLog,
+    TlsLog,
 };
 use crate::flow_generator::{LogMessageType, Result};
 use crate::plugin::wasm::WasmVm;
@@ -174,6 +176,13 @@ impl_protocol_parser! {
         Oracle(OracleLog),
         MQTT(MqttLog),
         Tls(TlsLog),
+        DHCP(DhcpLog),
+        NTP(NtpLog),
+        SSH(SshLog),
+        Kerberos(KerberosLog),
+        RADIUS(RadiusLog),
+        SIP(SipLog),
+        RTP(RtpLog),
         // add protocol below
     }
 }
@@ -212,6 +221,14 @@ impl L7ParseResult {
 #[enum_dispatch]
 pub trait L7ProtocolParserInterface {
     fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool;
+    // 检查是否为协议的响应，用于 agent 中途接入连接时，首个观察到的消息是响应的情况下纠正方向
+    // ==========================================================================================
+    // check whether the payload is a response of the protocol, the packet source is taken as the
+    // server. used to recover the direction when the first observed message of a flow is a
+    // response, e.g. the agent attaches to established connections
+    fn check_response(&mut self, _: &[u8], _: &ParseParam) -> bool {
+        false
+    }
     // 协议解析
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult>;
     // 返回协议号和协议名称，由于的bitmap使用u128，所以协议号不能超过128.
@@ -257,6 +274,44 @@ pub trait L7ProtocolParserInterface {
     // return perf data
     fn perf_stats(&mut self) -> Option<L7PerfStats>;
 
+    // for protocols with length prefixed messages, return the total length of the message
+    // starting at payload, messages longer than payload are reassembled from tcp segments
+    // before parse_payload() is called
+    fn message_len(&self, _: &[u8], _: PacketDirection) -> Option<usize> {
+        None
+    }
+
+    // 数据报协议（UDP）中一个数据报可能包含多个消息，例如 QUIC 的合并包，返回第一个消息的长度，
+    // 返回 None 表示整个数据报是一个消息
+    // ==========================================================================================
+    // for datagram protocols, a datagram may carry coalesced messages (e.g. QUIC packets),
+    // return the length of the first message in the datagram and the messages are parsed one
+    // by one. None means the whole datagram is one message
+    fn datagram_message_len(&self, _: &[u8]) -> Option<usize> {
+        None
+    }
+
+    // 有连接标识的数据报协议（例如 QUIC connection id）返回连接的 key，用于代替 flow_id 匹配请求和响应，
+    // 连接迁移到其他五元组后仍然可以匹配
+    // ==========================================================================================
+    // for datagram protocols with connection ids (e.g. QUIC), return a key of the connection
+    // which is used instead of the flow id to match requests and responses, so that messages
+    // are still matched after the connection migrates to another 5-tuple
+    fn datagram_connection_key(&self, _: &[u8], _: PacketDirection) -> Option<u64> {
+        None
+    }
+
+    // return media quality data, only for protocols carrying media streams
+    fn media_stats(&mut self) -> Option<MediaPerfStats> {
+        None
+    }
+
+    // return the logs kept by the parser when the flow ends, such as the last report
+    // interval of a stream, time is in microseconds
+    fn flush_logs(&mut self, _: u64) -> L7ParseResult {
+        L7ParseResult::None
+    }
+
     fn set_obfuscate_cache(&mut self, _: Option<ObfuscateCache>) {}
 }
 
@@ -288,41 +343,214 @@ pub struct LogCache {
     pub multi_merge_info: Option<(bool, bool, bool)>,
 }
 
+// Logs waiting to be paired in a flow, keyed by session id, for multiplexed protocols whose
+// responses may arrive in a different order from requests
+#[derive(Default)]
+pub struct PendingRequests {
+    logs: HashMap<u32, LogCache>,
+    // the earliest time of logs, used to skip scanning for expired logs
+    earliest: u64,
+}
+
+impl PendingRequests {
+    const MAX_PENDING: usize = 1024;
+
+    pub fn get(&self, session_id: u32) -> Option<&LogCache> {
+        self.logs.get(&session_id)
+    }
+
+    pub fn remove(&mut self, session_id: u32) -> Option<LogCache> {
+        self.logs.remove(&session_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.logs.len()
+    }
+
+    pub fn pop_oldest(&mut self) -> Option<LogCache> {
+        let oldest = self
+            .logs
+            .iter()
+            .min_by_key(|(_, l)| l.time)
+            .map(|(k, _)| *k)?;
+        self.logs.remove(&oldest)
+    }
+
+    // insert log and return the evicted request if the table is full
+    pub fn insert(&mut self, session_id: u32, log: LogCache) -> Option<LogCache> {
+        let evicted = if self.logs.len() >= Self::MAX_PENDING {
+            self.pop_oldest()
+        } else {
+            None
+        };
+        if self.logs.is_empty() || log.time < self.earliest {
+            self.earliest = log.time;
+        }
+        self.logs.insert(session_id, log);
+        evicted.filter(|l| l.msg_type == LogMessageType::Request)
+    }
+
+    // remove logs older than timeout and return the number of requests removed
+    pub fn evict_expired(&mut self, now: u64, timeout: u64) -> usize {
+        if self.logs.is_empty() || self.earliest + timeout >= now {
+            return 0;
+        }
+        let mut requests = 0;
+        let mut earliest = u64::MAX;
+        self.logs.retain(|_, l| {
+            if l.time + timeout < now {
+                if l.msg_type == LogMessageType::Request {
+                    requests += 1;
+                }
+                false
+            } else {
+                earliest = earliest.min(l.time);
+                true
+            }
+        });
+        self.earliest = earliest;
+        requests
+    }
+}
+
+// Counters of rrt_cache since last taken
+#[derive(Debug, Default, Clone, Copy)]
+pub struct L7PerfCacheCounter {
+    // responses matched or not matched with cached logs
+    pub hit: u64,
+    pub miss: u64,
+    // logs evicted by newer logs when the cache is full
+    pub evicted: u64,
+}
+
 pub struct L7PerfCache {
     // lru cache previous rrt
     pub rrt_cache: LruCache<u128, LogCache>,
+    // LruCache<flow_id, PendingRequests>, for protocols matching response by session id
+    pub pending_requests: LruCache<u64, PendingRequests>,
+    // the number of logs in pending_requests of all flows
+    pub pending_count: usize,
     // LruCache<flow_id, (in_cache_req, count)>
     pub timeout_cache: LruCache<u64, (usize, usize)>,
     // time in microseconds
     pub last_log_time: u64,
+    pub counter: L7PerfCacheCounter,
+    // the capacity of rrt_cache when created, and the lower bound of adaptive sizing
+    init_capacity: usize,
 }
 
 impl L7PerfCache {
     // 60 seconds
     const LOG_INTERVAL: u64 = 60_000_000;
+    // estimated memory of an rrt_cache entry, including the key and lru links
+    pub const ENTRY_SIZE: usize = std::mem::size_of::<(u128, LogCache)>() + 32;
 
     pub fn new(cap: usize) -> Self {
         L7PerfCache {
             rrt_cache: LruCache::new(cap.try_into().unwrap()),
+            pending_requests: LruCache::new(cap.try_into().unwrap()),
+            pending_count: 0,
             timeout_cache: LruCache::new(cap.try_into().unwrap()),
             last_log_time: 0,
+            counter: L7PerfCacheCounter::default(),
+            init_capacity: cap,
+        }
+    }
+
+    pub fn pop(&mut self, key: &u128, msg_type: LogMessageType) -> Option<LogCache> {
+        let log = self.rrt_cache.pop(key);
+        if msg_type == LogMessageType::Response {
+            if log.is_some() {
+                self.counter.hit += 1;
+            } else {
+                self.counter.miss += 1;
+            }
         }
+        log
     }
 
     pub fn put(&mut self, key: u128, value: LogCache) -> Option<LogCache> {
         let now = value.time;
         if self.rrt_cache.len() >= usize::from(self.rrt_cache.cap())
-            && self.last_log_time + Self::LOG_INTERVAL < now
+            && !self.rrt_cache.contains(&key)
         {
-            self.last_log_time = now;
-            debug!("The capacity({}) of the rrt table will be exceeded. please adjust the configuration", self.rrt_cache.cap());
+            self.counter.evicted += 1;
+            if self.last_log_time + Self::LOG_INTERVAL < now {
+                self.last_log_time = now;
+                debug!("The capacity({}) of the rrt table will be exceeded. please adjust the configuration", self.rrt_cache.cap());
+            }
         }
         self.rrt_cache.put(key, value)
     }
 
+    // Double the capacity of rrt_cache if logs are evicted, and halve it if less than a
+    // quarter is used, within [init_capacity, memory_limit / ENTRY_SIZE].
+    // Returns the new capacity if changed.
+    pub fn adapt_capacity(&mut self, evicted: u64, memory_limit: usize) -> Option<usize> {
+        let cap = usize::from(self.rrt_cache.cap());
+        let max_cap = (memory_limit / Self::ENTRY_SIZE).max(self.init_capacity);
+        let new_cap = if evicted > 0 {
+            cap.saturating_mul(2).min(max_cap)
+        } else if self.rrt_cache.len() < cap / 4 {
+            (cap / 2).max(self.init_capacity)
+        } else {
+            cap.min(max_cap)
+        };
+        if new_cap == cap {
+            return None;
+        }
+        self.rrt_cache.resize(new_cap.try_into().unwrap());
+        Some(new_cap)
+    }
+
+    // Returns the pending requests of the flow, the least recently used flow is dropped if
+    // pending_requests is full
+    pub fn get_or_insert_pending(&mut self, flow_id: u64) -> &mut PendingRequests {
+        if !self.pending_requests.contains(&flow_id)
+            && self.pending_requests.len() >= usize::from(self.pending_requests.cap())
+        {
+            if let Some((_, dropped)) = self.pending_requests.pop_lru() {
+                self.pending_count -= dropped.len();
+            }
+        }
+        self.pending_requests
+            .get_or_insert_mut(flow_id, PendingRequests::default)
+    }
+
+    // Logs pending in all flows are limited by the capacity of rrt_cache. If exceeded, the
+    // oldest log of the least recently used flow is evicted, and counted as timeout if it
+    // is a request.
+    pub fn evict_pending(&mut self) {
+        while self.pending_count > usize::from(self.rrt_cache.cap()) {
+            let Some(flow_id) = self.pending_requests.peek_lru().map(|(k, _)| *k) else {
+                return;
+            };
+            let pending = self.pending_requests.peek_mut(&flow_id).unwrap();
+            let evicted = pending.pop_oldest();
+            if pending.len() == 0 {
+                self.pending_requests.pop(&flow_id);
+            }
+            let Some(evicted) = evicted else {
+                continue;
+            };
+            self.pending_count -= 1;
+            if evicted.msg_type == LogMessageType::Request {
+                if let Some((in_cached_req, timeout_count)) = self.timeout_cache.peek_mut(&flow_id)
+                {
+                    *in_cached_req = in_cached_req.saturating_sub(1);
+                    *timeout_count += 1;
+                }
+            }
+        }
+    }
+
     pub fn pop_timeout_count(&mut self, flow_id: &u64, flow_end: bool) -> usize {
         let (in_cache, t) = self.timeout_cache.pop(flow_id).unwrap_or((0, 0));
         if flow_end {
+            // requests still pending are included in in_cache
+            if let Some(pending) = self.pending_requests.pop(flow_id) {
+                self.pending_count -= pending.len();
+            }
             in_cache + t
         } else {
             self.timeout_cache.put(*flow_id, (in_cache, 0));
@@ -34,7 +34,7 @@ use super::ebpf::EbpfType;
 use super::enums::TapType;
 use super::{
     consts::*,
-    decapsulate::TunnelInfo,
+    decapsulate::{Srv6Info, TunnelInfo},
     endpoint::EndpointDataPov,
     enums::{EthernetType, HeaderType, IpProtocol, TcpFlags},
     flow::{L7Protocol, PacketDirection, SignalSource},
@@ -53,6 +53,7 @@ use crate::{
 };
 use crate::{
     common::Timestamp,
+    config::VlanTagMode,
     utils::bytes::{read_u16_be, read_u32_be},
 };
 use npb_handler::NpbMode;
@@ -124,6 +125,8 @@ pub struct MetaPacket<'a> {
 
     pub offset_ipv6_last_option: u16,
     pub offset_ipv6_fragment_option: u16,
+    // segment routing header of SRv6 inline mode
+    pub srv6: Option<Srv6Info>,
 
     pub header_type: HeaderType,
     // 读取时不要直接用这个字段，用MetaPacket.GetPktSize()
@@ -145,11 +148,16 @@ pub struct MetaPacket<'a> {
     pub tap_port: TapPort, // packet与xflow复用
     pub signal_source: SignalSource,
     pub payload_len: u16,
+    // innermost vlan tag, or the one selected by VlanTagMode
     pub vlan: u16,
+    // outer vlan tag of double tagged frames, 0 for others
+    pub outer_vlan: u16,
     pub is_active_service: bool,
     pub queue_hash: u8,
 
     /********** for xFlow (NetFlow/sFlow/NetStream) **********/
+    // One of N packets is sampled, 0 means the packet is not sampled
+    pub sampling_rate: u32,
     // TODO support xFlow
     // pub packet_count: u64,
     // pub packet_bytes: u64,
@@ -180,6 +188,7 @@ pub struct MetaPacket<'a> {
     // for PcapAssembler
     pub flow_id: u64, // PCAP and L7 Log
     pub socket_role: u8,
+    pub unix_socket_path: String, // empty if not an AF_UNIX socket
     pub second_in_minute: u8,
 
     /********** for GPID **********/
@@ -370,6 +379,9 @@ impl<'a> MetaPacket<'a> {
                         if size_checker < 0 {
                             break;
                         }
+                        if header == IpProtocol::IPV6_ROUTING {
+                            self.srv6 = Srv6Info::parse(&packet[option_offset..]).map(|s| s.0);
+                        }
                         self.offset_ipv6_last_option = option_offset as u16;
                         next_header = packet[option_offset];
                         let length = packet[option_offset + 1] as usize;
@@ -453,6 +465,25 @@ impl<'a> MetaPacket<'a> {
         None
     }
 
+    // Segment routing header of the packet, or of the outermost SRv6 encapsulation
+    pub fn srv6(&self) -> Option<&Srv6Info> {
+        self.srv6
+            .as_ref()
+            .or_else(|| self.tunnel.as_ref().and_then(|t| t.srv6.as_ref()))
+    }
+
+    // Keeps the vlan tags used in flow keys and tags, should be called before flow generation
+    pub fn select_vlan(&mut self, mode: VlanTagMode) {
+        match mode {
+            VlanTagMode::Inner => self.outer_vlan = 0,
+            VlanTagMode::Outer if self.outer_vlan > 0 => {
+                self.vlan = self.outer_vlan;
+                self.outer_vlan = 0;
+            }
+            _ => (),
+        }
+    }
+
     pub fn update<P: AsRef<[u8]> + Into<RawPacket<'a>>>(
         &mut self,
         raw_packet: P,
@@ -498,7 +529,7 @@ impl<'a> MetaPacket<'a> {
             .map_err(|e| {
                 error::Error::ParsePacketFailed(format!("parse eth_type failed: {}", e))
             })?;
-        if eth_type == EthernetType::DOT1Q {
+        if eth_type.is_vlan_tag() {
             vlan_tag_size = VLAN_HEADER_SIZE;
             size_checker -= VLAN_HEADER_SIZE as isize;
             if size_checker < 0 {
@@ -512,12 +543,13 @@ impl<'a> MetaPacket<'a> {
             .map_err(|e| {
                 error::Error::ParsePacketFailed(format!("parse eth_type failed: {}", e))
             })?;
-            if eth_type == EthernetType::DOT1Q {
+            if eth_type.is_vlan_tag() {
                 vlan_tag_size += VLAN_HEADER_SIZE;
                 size_checker -= VLAN_HEADER_SIZE as isize;
                 if size_checker < 0 {
                     return Err(error::Error::ParsePacketFailed("packet truncated".into()));
                 }
+                self.outer_vlan = self.vlan;
                 let vlan_tag =
                     read_u16_be(&packet[FIELD_OFFSET_ETH_TYPE + VLAN_HEADER_SIZE + ETH_TYPE_LEN..]);
                 self.vlan = vlan_tag & VLAN_ID_MASK;
@@ -676,6 +708,7 @@ impl<'a> MetaPacket<'a> {
                 let icmp_type_index = FIELD_OFFSET_ICMP_TYPE_CODE + self.l2_l3_opt_size as usize;
                 let mut icmp_data = IcmpData::default();
                 icmp_data.icmp_type = packet[icmp_type_index];
+                icmp_data.icmp_code = packet[icmp_type_index + 1];
 
                 match IcmpType::new(
                     packet[FIELD_OFFSET_ICMP_TYPE_CODE + self.l2_l3_opt_size as usize],
@@ -700,6 +733,21 @@ impl<'a> MetaPacket<'a> {
                     }
                     _ => (),
                 }
+                match IcmpType::new(icmp_data.icmp_type) {
+                    IcmpTypes::DestinationUnreachable
+                    | IcmpTypes::TimeExceeded
+                    | IcmpTypes::ParameterProblem => {
+                        // next-hop mtu of fragmentation needed
+                        if icmp_data.icmp_type == IcmpTypes::DestinationUnreachable.0
+                            && icmp_data.icmp_code == ICMP_CODE_FRAGMENTATION_NEEDED
+                        {
+                            icmp_data.mtu = read_u16_be(&packet[icmp_type_index + 6..]) as u32;
+                        }
+                        icmp_data.error_key =
+                            IcmpErrorKey::parse(packet, icmp_type_index + ICMP_HEADER_SIZE, false);
+                    }
+                    _ => (),
+                }
                 self.protocol_data = ProtocolData::IcmpData(icmp_data);
                 self.payload_len =
                     (self.packet_len as usize - (packet.len() - size_checker as usize)) as u16;
@@ -817,6 +865,7 @@ impl<'a> MetaPacket<'a> {
                 if size_checker > 0 {
                     let icmpv6_type_index = ICMPV6_TYPE_OFFSET + self.l2_l3_opt_size as usize;
                     icmp_data.icmp_type = packet[icmpv6_type_index];
+                    icmp_data.icmp_code = packet.get(icmpv6_type_index + 1).copied().unwrap_or(0);
 
                     match Icmpv6Type::new(packet[icmpv6_type_index]) {
                         Icmpv6Types::NeighborAdvert => {
@@ -829,6 +878,18 @@ impl<'a> MetaPacket<'a> {
                             icmp_data.echo_id_seq = read_u32_be(&packet[icmpv6_type_index + 4..]);
                             self.lookup_key.direction = PacketDirection::ServerToClient;
                         }
+                        Icmpv6Types::DestinationUnreachable
+                        | Icmpv6Types::PacketTooBig
+                        | Icmpv6Types::TimeExceeded
+                        | Icmpv6Types::ParameterProblem => {
+                            let offset = icmpv6_type_index + ICMP_HEADER_SIZE;
+                            if icmp_data.icmp_type == Icmpv6Types::PacketTooBig.0
+                                && packet.len() >= offset
+                            {
+                                icmp_data.mtu = read_u32_be(&packet[icmpv6_type_index + 4..]);
+                            }
+                            icmp_data.error_key = IcmpErrorKey::parse(packet, offset, true);
+                        }
                         _ => {}
                     }
                     // 忽略link-local address并只考虑ND reply, i.e. neighbour advertisement
@@ -842,6 +903,20 @@ impl<'a> MetaPacket<'a> {
                     (self.packet_len - (packet.len() - size_checker as usize) as u32) as u16;
                 return Ok(());
             }
+            IpProtocol::SCTP => {
+                size_checker -= SCTP_COMMON_HEADER_SIZE as isize;
+                if size_checker < 0 {
+                    self.npb_ignore_l4 = true;
+                    return Ok(());
+                }
+                let payload_offset = packet.len() - size_checker as usize;
+                self.l4_payload_len = (self.packet_len - payload_offset as u32) as u16;
+                self.payload_len = self.l4_payload_len;
+                // header_type is kept as Ipv4/Ipv6, sctp ports are read the same as tcp/udp
+                self.protocol_data = ProtocolData::SctpData(SctpData::parse(
+                    &packet[payload_offset - SCTP_COMMON_HEADER_SIZE..],
+                ));
+            }
             _ => {
                 self.payload_len =
                     (self.packet_len - (packet.len() - size_checker as usize) as u32) as u16;
@@ -876,6 +951,36 @@ impl<'a> MetaPacket<'a> {
         self.l4_payload_len as usize
     }
 
+    /// Skip the leading bytes of tcp payload, such as the retransmitted part of a segment,
+    /// should be restored by `restore_tcp_payload` after use.
+    pub fn skip_tcp_payload(&mut self, len: u16) {
+        let ProtocolData::TcpHeader(tcp_data) = &mut self.protocol_data else {
+            return;
+        };
+        let len = len.min(self.payload_len);
+        tcp_data.seq = tcp_data.seq.wrapping_add(len as u32);
+        self.l4_opt_size += len as u32;
+        self.l4_payload_len -= len;
+        self.payload_len -= len;
+    }
+
+    pub fn restore_tcp_payload(&mut self, len: u16) {
+        let ProtocolData::TcpHeader(tcp_data) = &mut self.protocol_data else {
+            return;
+        };
+        let len = len.min(self.l4_opt_size as u16);
+        tcp_data.seq = tcp_data.seq.wrapping_sub(len as u32);
+        self.l4_opt_size -= len as u32;
+        self.l4_payload_len += len;
+        self.payload_len += len;
+    }
+
+    /// Get the number of packets the meta packet stands for, sampled packets are scaled up
+    /// by the sampling rate.
+    pub fn sampling_scale(&self) -> u64 {
+        self.sampling_rate.max(1) as u64
+    }
+
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub unsafe fn from_ebpf(data: *mut SK_BPF_DATA) -> Result<MetaPacket<'a>, Box<dyn Error>> {
         let data = &mut (*data);
@@ -890,6 +995,11 @@ impl<'a> MetaPacket<'a> {
                     IpAddr::from(Ipv4Addr::from(addr))
                 },
             )
+        } else if data.tuple.is_unix_socket() {
+            (
+                IpAddr::from(Ipv4Addr::UNSPECIFIED),
+                IpAddr::from(Ipv4Addr::UNSPECIFIED),
+            )
         } else {
             (
                 IpAddr::from(Ipv6Addr::from(data.tuple.laddr)),
@@ -911,10 +1021,10 @@ impl<'a> MetaPacket<'a> {
             dst_ip,
             src_port,
             dst_port,
-            eth_type: if data.tuple.addr_len == 4 {
-                EthernetType::IPV4
-            } else {
+            eth_type: if data.tuple.addr_len == 16 {
                 EthernetType::IPV6
+            } else {
+                EthernetType::IPV4
             },
             l2_end_0: data.direction == SOCK_DIR_SND,
             l2_end_1: data.direction == SOCK_DIR_RCV,
@@ -956,6 +1066,9 @@ impl<'a> MetaPacket<'a> {
             PACKET_KNAME_MAX_PADDING,
         );
         packet.socket_id = data.socket_id;
+        if data.tuple.is_unix_socket() {
+            packet.unix_socket_path = data.tuple.unix_socket_path();
+        }
         if let ProtocolData::TcpHeader(tcp_data) = &mut packet.protocol_data {
             tcp_data.seq = data.tcp_seq as u32;
         }
@@ -1096,13 +1209,159 @@ pub struct MetaPacketTcpHeader {
 #[derive(Clone, Debug, Default)]
 pub struct IcmpData {
     pub icmp_type: u8,
+    pub icmp_code: u8,
     pub echo_id_seq: u32,
+    // next-hop mtu of fragmentation needed or packet too big
+    pub mtu: u32,
+    // the original packet of error messages
+    pub error_key: Option<IcmpErrorKey>,
+}
+
+// 5-tuple of the original tcp/udp packet embedded in icmp error messages
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IcmpErrorKey {
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub proto: IpProtocol,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+impl IcmpErrorKey {
+    fn parse(packet: &[u8], offset: usize, is_ipv6: bool) -> Option<Self> {
+        let ip = packet.get(offset..)?;
+        let (src_ip, dst_ip, proto, l4) = if is_ipv6 {
+            if ip.len() < IPV6_HEADER_SIZE || ip[0] >> 4 != 6 {
+                return None;
+            }
+            (
+                IpAddr::from(<[u8; 16]>::try_from(&ip[8..24]).unwrap()),
+                IpAddr::from(<[u8; 16]>::try_from(&ip[24..40]).unwrap()),
+                IpProtocol::from(ip[6]),
+                &ip[IPV6_HEADER_SIZE..],
+            )
+        } else {
+            if ip.len() < IPV4_HEADER_SIZE || ip[0] >> 4 != 4 {
+                return None;
+            }
+            let ihl = ((ip[0] & 0xf) as usize) << 2;
+            if ihl < IPV4_HEADER_SIZE || ip.len() < ihl {
+                return None;
+            }
+            (
+                IpAddr::from(<[u8; 4]>::try_from(&ip[12..16]).unwrap()),
+                IpAddr::from(<[u8; 4]>::try_from(&ip[16..20]).unwrap()),
+                IpProtocol::from(ip[9]),
+                &ip[ihl..],
+            )
+        };
+        // only the first 8 bytes of l4 are guaranteed
+        if (proto != IpProtocol::TCP && proto != IpProtocol::UDP) || l4.len() < 4 {
+            return None;
+        }
+        Some(Self {
+            src_ip,
+            dst_ip,
+            proto,
+            src_port: read_u16_be(l4),
+            dst_port: read_u16_be(&l4[2..]),
+        })
+    }
+}
+
+bitflags! {
+    // chunk types of sctp packets, bit n for chunk type n
+    #[derive(Default)]
+    pub struct SctpChunks: u16 {
+        const DATA = 1 << 0;
+        const INIT = 1 << 1;
+        const INIT_ACK = 1 << 2;
+        const SACK = 1 << 3;
+        const HEARTBEAT = 1 << 4;
+        const HEARTBEAT_ACK = 1 << 5;
+        const ABORT = 1 << 6;
+        const SHUTDOWN = 1 << 7;
+        const SHUTDOWN_ACK = 1 << 8;
+        const ERROR = 1 << 9;
+        const COOKIE_ECHO = 1 << 10;
+        const COOKIE_ACK = 1 << 11;
+        const SHUTDOWN_COMPLETE = 1 << 14;
+    }
+}
+
+impl SctpChunks {
+    // INIT and INIT ACK are handled like SYN and SYN ACK of tcp
+    pub fn handshake_flags(&self) -> TcpFlags {
+        if self.contains(Self::INIT_ACK) {
+            TcpFlags::SYN_ACK
+        } else if self.contains(Self::INIT) {
+            TcpFlags::SYN
+        } else {
+            TcpFlags::empty()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SctpData {
+    pub verification_tag: u32,
+    pub chunks: SctpChunks,
+    // payload protocol identifier of the first DATA chunk, e.g. 46 for Diameter, 18 for S1AP
+    pub ppid: u32,
+    // distinct stream ids of DATA chunks, at most SctpData::MAX_STREAMS in a packet
+    pub stream_ids: [u16; SctpData::MAX_STREAMS],
+    pub stream_count: u8,
+}
+
+impl SctpData {
+    pub const MAX_STREAMS: usize = 4;
+
+    const CHUNK_TYPE_DATA: u8 = 0;
+
+    fn parse(l4: &[u8]) -> Self {
+        let mut data = Self {
+            verification_tag: read_u32_be(&l4[4..]),
+            ..Default::default()
+        };
+        let mut offset = SCTP_COMMON_HEADER_SIZE;
+        while offset + SCTP_CHUNK_HEADER_SIZE <= l4.len() {
+            let chunk_type = l4[offset];
+            let length = read_u16_be(&l4[offset + 2..]) as usize;
+            if chunk_type < u16::BITS as u8 {
+                data.chunks |= SctpChunks::from_bits_truncate(1 << chunk_type);
+            }
+            if chunk_type == Self::CHUNK_TYPE_DATA
+                && offset + SCTP_DATA_CHUNK_HEADER_SIZE <= l4.len()
+            {
+                if data.ppid == 0 {
+                    data.ppid = read_u32_be(&l4[offset + 12..]);
+                }
+                let stream_id = read_u16_be(&l4[offset + 8..]);
+                let count = data.stream_count as usize;
+                if count < Self::MAX_STREAMS && !data.stream_ids[..count].contains(&stream_id) {
+                    data.stream_ids[count] = stream_id;
+                    data.stream_count += 1;
+                }
+            }
+            if length < SCTP_CHUNK_HEADER_SIZE {
+                break;
+            }
+            // chunks are padded to multiples of 4 bytes
+            offset += (length + 3) & !3;
+        }
+        data
+    }
+
+    pub fn stream_ids(&self) -> &[u16] {
+        &self.stream_ids[..self.stream_count as usize]
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum ProtocolData {
     TcpHeader(MetaPacketTcpHeader),
     IcmpData(IcmpData),
+    SctpData(SctpData),
 }
 
 impl Default for ProtocolData {
@@ -1115,6 +1374,54 @@ impl Default for ProtocolData {
 mod tests {
     use super::*;
 
+    #[test]
+    fn sctp_data() {
+        let mut packet = vec![0u8; SCTP_COMMON_HEADER_SIZE];
+        packet[4..8].copy_from_slice(&0x12345678u32.to_be_bytes());
+        // DATA chunk of stream 3 with Diameter payload, padded to 4 bytes
+        packet.extend_from_slice(&[0, 0x03, 0, 17]);
+        packet.extend_from_slice(&1u32.to_be_bytes());
+        packet.extend_from_slice(&[0, 3, 0, 0]);
+        packet.extend_from_slice(&46u32.to_be_bytes());
+        packet.extend_from_slice(&[1, 0, 0, 0]);
+        // SACK chunk
+        packet.extend_from_slice(&[3, 0, 0, 16]);
+        packet.extend_from_slice(&[0; 12]);
+
+        let data = SctpData::parse(&packet);
+        assert_eq!(data.verification_tag, 0x12345678);
+        assert_eq!(data.chunks, SctpChunks::DATA | SctpChunks::SACK);
+        assert_eq!(data.ppid, 46);
+        assert_eq!(data.stream_ids(), &[3]);
+        assert!(data.chunks.handshake_flags().is_empty());
+    }
+
+    #[test]
+    fn icmp_error_key() {
+        // ipv4 header with udp 10.0.0.1:12345 -> 10.0.0.2:53
+        let mut packet = vec![0u8; IPV4_HEADER_SIZE + UDP_HEADER_SIZE];
+        packet[0] = 0x45;
+        packet[9] = u8::from(IpProtocol::UDP);
+        packet[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        packet[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        packet[20..22].copy_from_slice(&12345u16.to_be_bytes());
+        packet[22..24].copy_from_slice(&53u16.to_be_bytes());
+        assert_eq!(
+            IcmpErrorKey::parse(&packet, 0, false),
+            Some(IcmpErrorKey {
+                src_ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+                dst_ip: Ipv4Addr::new(10, 0, 0, 2).into(),
+                proto: IpProtocol::UDP,
+                src_port: 12345,
+                dst_port: 53,
+            })
+        );
+        // truncated l4 header
+        assert_eq!(IcmpErrorKey::parse(&packet[..22], 0, false), None);
+        // not an ipv6 header
+        assert_eq!(IcmpErrorKey::parse(&packet, 0, true), None);
+    }
+
     #[test]
     fn get_pkt_size() {
         let pkt = MetaPacket {
@@ -1138,4 +1445,32 @@ mod tests {
             pkt
         );
     }
+
+    #[test]
+    fn qinq_vlan() {
+        let mut packet =
+            vec![0u8; ETH_HEADER_SIZE + 2 * VLAN_HEADER_SIZE + IPV4_HEADER_SIZE + UDP_HEADER_SIZE];
+        // 802.1ad service tag 100 and 802.1q customer tag 200
+        packet[12..14].copy_from_slice(&0x88a8u16.to_be_bytes());
+        packet[14..16].copy_from_slice(&100u16.to_be_bytes());
+        packet[16..18].copy_from_slice(&0x8100u16.to_be_bytes());
+        packet[18..20].copy_from_slice(&200u16.to_be_bytes());
+        packet[20..22].copy_from_slice(&0x0800u16.to_be_bytes());
+        packet[22] = 0x45;
+        packet[24..26].copy_from_slice(&28u16.to_be_bytes());
+        packet[31] = u8::from(IpProtocol::UDP);
+
+        let new_packet = |mode| {
+            let mut meta = MetaPacket::empty();
+            meta.update(&packet[..], true, true, Duration::ZERO, packet.len())
+                .unwrap();
+            assert_eq!(meta.vlan_tag_size as usize, 2 * VLAN_HEADER_SIZE);
+            assert_eq!(meta.lookup_key.eth_type, EthernetType::IPV4);
+            meta.select_vlan(mode);
+            (meta.vlan, meta.outer_vlan)
+        };
+        assert_eq!(new_packet(VlanTagMode::Inner), (200, 0));
+        assert_eq!(new_packet(VlanTagMode::Outer), (100, 0));
+        assert_eq!(new_packet(VlanTagMode::Both), (200, 100));
+    }
 }
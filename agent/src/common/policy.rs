@@ -15,7 +15,7 @@
  */
 
 use std::fmt;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -987,6 +987,82 @@ impl TryFrom<&trident::GpidSyncEntry> for GpidEntry {
     }
 }
 
+// A connection in the conntrack table of the node, such as
+// ipv4     2 tcp      6 431999 ESTABLISHED src=10.1.0.2 dst=10.96.0.10 sport=43210 dport=53 src=10.1.0.9 dst=10.1.0.2 sport=53 dport=43210 [ASSURED] mark=0 zone=0 use=2
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConntrackEntry {
+    pub protocol: IpProtocol,
+    // (src, dst) of the direction which creates the connection
+    pub original: (SocketAddr, SocketAddr),
+    // (src, dst) of the reply direction
+    pub reply: (SocketAddr, SocketAddr),
+}
+
+impl ConntrackEntry {
+    // The reply tuple is not the reverse of the original one if the connection is SNATed or DNATed
+    pub fn is_nat(&self) -> bool {
+        self.original.0 != self.reply.1 || self.original.1 != self.reply.0
+    }
+
+    // The client address before SNAT and the server address after DNAT
+    pub fn real_addrs(&self) -> (SocketAddr, SocketAddr) {
+        (self.original.0, self.reply.0)
+    }
+}
+
+impl FromStr for ConntrackEntry {
+    type Err = Error;
+
+    // Only tcp and udp connections are supported
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let protocol = match fields.nth(3).and_then(|p| p.parse::<u8>().ok()) {
+            Some(p) if p == u8::from(IpProtocol::TCP) || p == u8::from(IpProtocol::UDP) => {
+                IpProtocol::from(p)
+            }
+            _ => {
+                return Err(Error::ParseConntrack(format!(
+                    "unsupported protocol: {}",
+                    s
+                )))
+            }
+        };
+
+        let (mut ips, mut ports) = (Vec::with_capacity(4), Vec::with_capacity(4));
+        for field in fields {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            match key {
+                "src" | "dst" => match value.parse::<IpAddr>() {
+                    Ok(ip) => ips.push(ip),
+                    Err(_) => return Err(Error::ParseConntrack(format!("invalid ip: {}", s))),
+                },
+                "sport" | "dport" => match value.parse::<u16>() {
+                    Ok(port) => ports.push(port),
+                    Err(_) => return Err(Error::ParseConntrack(format!("invalid port: {}", s))),
+                },
+                _ => (),
+            }
+        }
+        // src, dst of the original direction, then src, dst of the reply direction
+        if ips.len() != 4 || ports.len() != 4 {
+            return Err(Error::ParseConntrack(format!("invalid tuples: {}", s)));
+        }
+        Ok(Self {
+            protocol,
+            original: (
+                SocketAddr::new(ips[0], ports[0]),
+                SocketAddr::new(ips[1], ports[1]),
+            ),
+            reply: (
+                SocketAddr::new(ips[2], ports[2]),
+                SocketAddr::new(ips[3], ports[3]),
+            ),
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Container {
     pub pod_id: u32,
@@ -1006,6 +1082,38 @@ impl From<&trident::Container> for Container {
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_conntrack_entry() {
+        let entry: ConntrackEntry = "ipv4     2 tcp      6 431999 ESTABLISHED src=10.1.0.2 dst=10.96.0.10 sport=43210 dport=53 src=10.1.0.9 dst=10.1.0.2 sport=53 dport=43210 [ASSURED] mark=0 zone=0 use=2"
+            .parse()
+            .unwrap();
+        assert_eq!(entry.protocol, IpProtocol::TCP);
+        assert_eq!(
+            entry.original,
+            (
+                "10.1.0.2:43210".parse().unwrap(),
+                "10.96.0.10:53".parse().unwrap()
+            )
+        );
+        assert!(entry.is_nat());
+        assert_eq!(
+            entry.real_addrs(),
+            (
+                "10.1.0.2:43210".parse().unwrap(),
+                "10.1.0.9:53".parse().unwrap()
+            )
+        );
+
+        let entry: ConntrackEntry = "ipv4     2 udp      17 29 src=10.1.0.2 dst=10.1.0.3 sport=5000 dport=6000 [UNREPLIED] src=10.1.0.3 dst=10.1.0.2 sport=6000 dport=5000 mark=0 zone=0 use=2"
+            .parse()
+            .unwrap();
+        assert!(!entry.is_nat());
+
+        assert!("ipv4     2 icmp     1 29 src=10.1.0.2 dst=10.1.0.3 type=8 code=0 id=1 src=10.1.0.3 dst=10.1.0.2 type=0 code=0 id=1 mark=0 zone=0 use=2"
+            .parse::<ConntrackEntry>()
+            .is_err());
+    }
+
     #[test]
     fn test_port_segment() {
         assert_eq!(PortSegment::calc_right_zero(u16::MAX), 0);
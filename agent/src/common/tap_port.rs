@@ -42,9 +42,11 @@ impl TapPort {
 
     // The higher the nat source value, the higher the priority.
     // NAT_SOURCE_VIP and NAT_SOURCE_CONTROLLER is obtained through policy query,
+    // NAT_SOURCE_CONNTRACK is obtained through the conntrack table of the node,
     // NAT_SOURCE_TOA is obtained through TCP Options Address.
     pub const NAT_SOURCE_NONE: u8 = 0;
     pub const NAT_SOURCE_VIP: u8 = 2;
+    pub const NAT_SOURCE_CONNTRACK: u8 = 3;
     pub const NAT_SOURCE_RTOA: u8 = 4;
     pub const NAT_SOURCE_TOA: u8 = 6;
 
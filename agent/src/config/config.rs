@@ -19,12 +19,12 @@ use std::env;
 use std::fs;
 use std::io;
 use std::net::{IpAddr, ToSocketAddrs};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use log::{debug, error, info, warn};
 use md5::{Digest, Md5};
-use public::l7_protocol::{DEFAULT_DNS_PORT, DEFAULT_TLS_PORT};
+use public::l7_protocol::DEFAULT_DNS_PORT;
 use regex::Regex;
 use serde::{
     de::{self, Unexpected},
@@ -34,7 +34,9 @@ use thiserror::Error;
 use tokio::runtime::Runtime;
 
 use crate::common::l7_protocol_log::L7ProtocolParser;
-use crate::flow_generator::{DnsLog, TlsLog};
+use crate::flow_generator::{
+    DhcpLog, DnsLog, KerberosLog, NtpLog, RadiusLog, RtpLog, SipLog, TlsLog,
+};
 use crate::{
     common::{
         decapsulate::TunnelType,
@@ -352,6 +354,41 @@ impl Default for OnCpuProfile {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct OffCpuProfile {
+    pub disabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub min_block: Duration,
+}
+
+impl Default for OffCpuProfile {
+    fn default() -> Self {
+        OffCpuProfile {
+            disabled: true,
+            min_block: Duration::from_micros(50),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MemoryProfile {
+    pub disabled: bool,
+    pub regex: String,
+    pub sample_bytes: u32,
+}
+
+impl Default for MemoryProfile {
+    fn default() -> Self {
+        MemoryProfile {
+            disabled: true,
+            regex: String::new(),
+            sample_bytes: 524288,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct EbpfYamlConfig {
@@ -367,11 +404,17 @@ pub struct EbpfYamlConfig {
     pub max_socket_entries: usize,
     pub max_trace_entries: usize,
     pub socket_map_max_reclaim: usize,
+    pub map_auto_sizing_enabled: bool,
     pub go_tracing_timeout: usize,
     pub io_event_collect_mode: usize,
     #[serde(with = "humantime_serde")]
     pub io_event_minimal_duration: Duration,
+    pub unix_socket_enabled: bool,
+    pub process_socket_stats_enabled: bool,
+    pub process_socket_stats_max_processes: usize,
     pub on_cpu_profile: OnCpuProfile,
+    pub off_cpu_profile: OffCpuProfile,
+    pub memory_profile: MemoryProfile,
 }
 
 impl Default for EbpfYamlConfig {
@@ -385,13 +428,19 @@ impl Default for EbpfYamlConfig {
             max_socket_entries: 524288,
             max_trace_entries: 524288,
             socket_map_max_reclaim: 520000,
+            map_auto_sizing_enabled: false,
             kprobe_whitelist: EbpfKprobePortlist::default(),
             kprobe_blacklist: EbpfKprobePortlist::default(),
             uprobe_proc_regexp: UprobeProcRegExp::default(),
             go_tracing_timeout: 120,
             io_event_collect_mode: 1,
             io_event_minimal_duration: Duration::from_millis(1),
+            unix_socket_enabled: false,
+            process_socket_stats_enabled: false,
+            process_socket_stats_max_processes: 1024,
             on_cpu_profile: OnCpuProfile::default(),
+            off_cpu_profile: OffCpuProfile::default(),
+            memory_profile: MemoryProfile::default(),
         }
     }
 }
@@ -416,6 +465,52 @@ impl Default for PrometheusExtraConfig {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PrometheusScrapeConfig {
+    // scrape pods on the node of the agent with prometheus.io/scrape annotation
+    pub enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    // a scrape with more samples than the limit is dropped entirely
+    pub sample_limit: usize,
+}
+
+impl Default for PrometheusScrapeConfig {
+    fn default() -> Self {
+        PrometheusScrapeConfig {
+            enabled: false,
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(10),
+            sample_limit: 50000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct StatsdConfig {
+    pub enabled: bool,
+    pub listen_port: u16,
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+    // distinct series kept in a flush interval, new series are dropped when exceeded
+    pub max_series: usize,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        StatsdConfig {
+            enabled: false,
+            listen_port: 8125,
+            flush_interval: Duration::from_secs(10),
+            max_series: 100000,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct KubernetesResourceConfig {
@@ -437,6 +532,242 @@ pub struct MatchRule {
 pub struct HttpEndpointExtraction {
     pub disabled: bool,
     pub match_rules: Vec<MatchRule>,
+    pub normalization: HttpPathNormalization,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PathNormalizationRule {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpPathNormalization {
+    pub enabled: bool,
+    // builtin rules replace numeric ids, uuids and hashes in path segments
+    pub builtin_rules_disabled: bool,
+    // applied before builtin rules
+    pub rules: Vec<PathNormalizationRule>,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpErrorBodyCapture {
+    // 0 means disabled
+    pub max_length: usize,
+    pub server_error_only: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpBodyDecompression {
+    // max length of decompressed body, 0 means disabled
+    pub max_length: usize,
+}
+
+impl Default for HttpBodyDecompression {
+    fn default() -> Self {
+        Self { max_length: 16384 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TlsCertificateExpiry {
+    // server certificates expiring within warning_days are counted, 0 means disabled
+    pub warning_days: u32,
+}
+
+impl Default for TlsCertificateExpiry {
+    fn default() -> Self {
+        Self { warning_days: 30 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DnsAnswerRecords {
+    // max answer records with type and ttl logged per response, 0 means disabled
+    pub max_records: usize,
+}
+
+impl Default for DnsAnswerRecords {
+    fn default() -> Self {
+        Self { max_records: 16 }
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FieldExtractionRule {
+    pub protocol: String,
+    // the rule applies only if match_field matches match_regex, always applies if match_regex is empty
+    pub match_field: String,
+    pub match_regex: String,
+    // req_type, domain, resource, endpoint, result, exception, version or attribute.<name>
+    pub source: String,
+    // regex, json-path or bytes
+    pub extractor: String,
+    // regex (first capture group if any) or json path like $.user.id
+    pub pattern: String,
+    // byte range of bytes extractor, 0 length means to the end
+    pub offset: usize,
+    pub length: usize,
+    // attribute name
+    pub target: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7LogMasking {
+    // built-in detectors: card-number, phone-number, email
+    pub detectors: Vec<String>,
+    // user defined regexes of sensitive data
+    pub regexes: Vec<String>,
+    // resource, endpoint, attributes (all attributes) or attribute.<name>
+    pub fields: Vec<String>,
+    pub replacement: String,
+}
+
+impl Default for L7LogMasking {
+    fn default() -> Self {
+        Self {
+            detectors: vec![],
+            regexes: vec![],
+            fields: vec![
+                "resource".to_owned(),
+                "endpoint".to_owned(),
+                "attributes".to_owned(),
+            ],
+            replacement: "***".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TraceContextFormat {
+    // http header name, kafka record header key or dubbo attachment key
+    pub name: String,
+    // split value by delimiter to get ids, empty means the whole value is the id
+    pub delimiter: String,
+    // index of trace id or span id in the split value, none means not carried
+    pub trace_id_index: Option<usize>,
+    pub span_id_index: Option<usize>,
+    // ids must be hex of at most this length and are left padded with 0, 0 means any format
+    pub hex_length: usize,
+}
+
+impl TraceContextFormat {
+    fn decode(&self, value: &str, index: Option<usize>) -> Option<String> {
+        let id = if self.delimiter.is_empty() {
+            (index? == 0).then_some(value)?
+        } else {
+            value.split(self.delimiter.as_str()).nth(index?)?
+        }
+        .trim();
+        if id.is_empty() {
+            return None;
+        }
+        if self.hex_length == 0 {
+            return Some(id.to_owned());
+        }
+        if id.len() > self.hex_length || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(format!("{:0>1$}", id.to_ascii_lowercase(), self.hex_length))
+    }
+
+    pub fn decode_trace_id(&self, value: &str) -> Option<String> {
+        self.decode(value, self.trace_id_index)
+    }
+
+    pub fn decode_span_id(&self, value: &str) -> Option<String> {
+        self.decode(value, self.span_id_index)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7LogScripts {
+    // protocol name -> rhai script run on each log of the protocol before it is sent
+    pub scripts: HashMap<String, String>,
+    // budgets of a single invocation
+    pub max_operations: u64,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for L7LogScripts {
+    fn default() -> Self {
+        Self {
+            scripts: HashMap::new(),
+            max_operations: 10000,
+            timeout: Duration::from_millis(1),
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpProxyHeaderExtraction {
+    pub disabled: bool,
+    // use the first ip of X-Forwarded-For/Forwarded or X-Real-IP as http_proxy_client
+    pub first_hop_as_client: bool,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpHeaderRule {
+    // case insensitive
+    pub header_name: String,
+    // attribute name, use header_name if empty
+    pub rename: String,
+    // 0 means no truncation
+    pub max_length: usize,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpHeaderExtraction {
+    pub request_headers: Vec<HttpHeaderRule>,
+    pub response_headers: Vec<HttpHeaderRule>,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpIdentityRule {
+    // header names are case insensitive
+    pub header_names: Vec<String>,
+    pub cookie_names: Vec<String>,
+    // record md5 of the value instead of the plain text
+    pub hashed: bool,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpIdentityExtraction {
+    pub user_id: HttpIdentityRule,
+    pub session_id: HttpIdentityRule,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HttpSseTracking {
+    pub disabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub report_interval: Duration,
+}
+
+impl Default for HttpSseTracking {
+    fn default() -> Self {
+        Self {
+            disabled: false,
+            report_interval: Duration::from_secs(60),
+        }
+    }
 }
 
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
@@ -444,6 +775,184 @@ pub struct HttpEndpointExtraction {
 pub struct L7ProtocolAdvancedFeatures {
     pub http_endpoint_extraction: HttpEndpointExtraction,
     pub obfuscate_enabled_protocols: Vec<String>,
+    pub http_error_body_capture: HttpErrorBodyCapture,
+    pub graphql_endpoints: Vec<String>,
+    pub http_sse_tracking: HttpSseTracking,
+    pub http_identity_extraction: HttpIdentityExtraction,
+    pub http_header_extraction: HttpHeaderExtraction,
+    pub http_body_decompression: HttpBodyDecompression,
+    pub http_proxy_header_extraction: HttpProxyHeaderExtraction,
+    pub tls_certificate_expiry: TlsCertificateExpiry,
+    pub dns_answer_records: DnsAnswerRecords,
+    pub l7_log_scripts: L7LogScripts,
+    pub field_extraction_rules: Vec<FieldExtractionRule>,
+    pub custom_trace_contexts: Vec<TraceContextFormat>,
+    pub l7_log_masking: L7LogMasking,
+    // protocol name -> field name -> max bytes
+    pub l7_log_field_limits: HashMap<String, HashMap<String, usize>>,
+    pub simple_protocols: Vec<SimpleProtocolDefinition>,
+    pub l7_log_deduplication: L7LogDeduplication,
+    // protocol name -> rrt histogram buckets
+    pub l7_rrt_histograms: HashMap<String, RrtHistogramBuckets>,
+    pub l7_rrt_sketch: L7RrtSketch,
+    pub l7_endpoint_aggregation: L7EndpointAggregation,
+    pub l7_response_code_counts: L7ResponseCodeCounts,
+    // protocol name -> apdex threshold
+    pub l7_apdex_thresholds: HashMap<String, ApdexThreshold>,
+    pub l7_message_size_stats: L7MessageSizeStats,
+    pub l7_concurrency_stats: L7ConcurrencyStats,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7MessageSizeStats {
+    pub enabled: bool,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7ConcurrencyStats {
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ApdexThreshold {
+    // rrt within t is satisfied, and within 4t is tolerating
+    #[serde(with = "humantime_serde")]
+    pub t: Duration,
+}
+
+impl Default for ApdexThreshold {
+    fn default() -> Self {
+        Self {
+            t: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7ResponseCodeCounts {
+    pub enabled: bool,
+    pub top_k: usize,
+}
+
+impl Default for L7ResponseCodeCounts {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            top_k: 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7EndpointAggregation {
+    pub enabled: bool,
+    // perf stats of more endpoints in a flow are sent without aggregation
+    pub max_endpoints: usize,
+}
+
+impl Default for L7EndpointAggregation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_endpoints: 64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7RrtSketch {
+    pub enabled: bool,
+    // in percent
+    pub relative_accuracy: u8,
+}
+
+impl Default for L7RrtSketch {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            relative_accuracy: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RrtHistogramBuckets {
+    // upper bound of the first bucket
+    #[serde(with = "humantime_serde")]
+    pub start: Duration,
+    pub factor: u32,
+    pub count: usize,
+}
+
+impl Default for RrtHistogramBuckets {
+    fn default() -> Self {
+        Self {
+            start: Duration::from_millis(1),
+            factor: 2,
+            count: 15,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7LogDeduplication {
+    pub enabled: bool,
+    // max time difference between logs of the same request from cbpf and ebpf
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+}
+
+impl Default for L7LogDeduplication {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: Duration::from_secs(2),
+        }
+    }
+}
+
+// Binary protocol described by fixed offsets, all offsets are from the start of the message
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SimpleProtocolDefinition {
+    pub name: String,
+    // hex string, e.g. "cafe"
+    pub magic: String,
+    pub magic_offset: usize,
+    // message length is the value of the length field plus length_adjustment
+    pub length_offset: Option<usize>,
+    // 1, 2, 4 or 8 bytes
+    pub length_size: usize,
+    pub length_adjustment: i64,
+    pub request_type_offset: Option<usize>,
+    // 1, 2, 4 or 8 bytes
+    pub request_type_size: usize,
+    // applies to both length and request type fields
+    pub little_endian: bool,
+    pub resource_offset: Option<usize>,
+    // fixed length of resource, if 0 resource ends at delimiter or message end
+    pub resource_length: usize,
+    pub resource_delimiter: String,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct L7ProtocolHint {
+    // ip or cidr of servers, empty means any
+    pub cidr: String,
+    // server ports, same format as l7-protocol-ports
+    pub ports: String,
+    pub protocol: String,
+    // do not try other protocols if the hinted protocol does not match
+    pub exclusive: bool,
 }
 
 #[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
@@ -464,6 +973,8 @@ pub struct YamlConfig {
     pub af_packet_blocks_enabled: bool,
     #[serde(alias = "afpacket-blocks")]
     pub af_packet_blocks: usize,
+    #[serde(alias = "afpacket-blocks-adaptive")]
+    pub af_packet_blocks_adaptive: bool,
     pub enable_debug_stats: bool,
     pub analyzer_dedup_disabled: bool,
     pub default_tap_type: u32,
@@ -473,6 +984,8 @@ pub struct YamlConfig {
     pub first_path_level: u32,
     pub local_dispatcher_count: usize,
     pub src_interfaces: Vec<String>,
+    // capture filters of dispatchers in order, in pcap-filter syntax
+    pub dispatcher_capture_bpf: Vec<String>,
     pub mirror_traffic_pcp: u16,
     pub vtap_group_id_request: String,
     pub pcap: PcapConfig,
@@ -486,11 +999,23 @@ pub struct YamlConfig {
     pub ovs_dpdk_enabled: bool,
     pub dpdk_pmd_core_id: u32,
     pub dpdk_ring_port: String,
+    pub dpdk_file_prefix: String,
     pub libpcap_enabled: bool,
+    pub pf_ring_enabled: bool,
+    pub pf_ring_zc_enabled: bool,
     pub xflow_collector: XflowGeneratorConfig,
+    pub pcap_replay: PcapReplayConfig,
+    pub packet_dedup: PacketDedupConfig,
+    pub ipfix_exporter: IpfixExporter,
+    pub otlp_exporter: OtlpExporter,
+    pub prometheus_remote_write: PrometheusRemoteWrite,
+    pub kafka_exporter: KafkaExporter,
+    pub clickhouse_exporter: ClickhouseExporter,
     pub vxlan_flags: u8,
     pub collector_sender_queue_size: usize,
     pub collector_sender_queue_count: usize,
+    pub sender_compression: SenderCompression,
+    pub sender_spool: SenderSpool,
     pub toa_sender_queue_size: usize,
     pub toa_lru_cache_size: usize,
     pub flow_sender_queue_size: usize,
@@ -502,10 +1027,14 @@ pub struct YamlConfig {
     pub triple: TripleMapConfig,
     pub kubernetes_poller_type: KubernetesPollerType,
     pub decap_erspan: bool,
+    pub erspan_timestamp_enabled: bool,
+    pub afpacket_hardware_timestamp: bool,
     pub analyzer_ip: String,
     pub grpc_buffer_size: usize,
     #[serde(with = "humantime_serde")]
     pub l7_log_session_aggr_timeout: Duration,
+    #[serde(with = "humantime_serde")]
+    pub l4_log_aggr_interval: Duration,
     pub tap_mac_script: String,
     pub cloud_gateway_traffic: bool,
     pub kubernetes_namespace: String,
@@ -516,6 +1045,7 @@ pub struct YamlConfig {
     pub external_metrics_sender_queue_size: usize,
     pub l7_protocol_inference_max_fail_count: usize,
     pub l7_protocol_inference_ttl: usize,
+    pub l7_protocol_inference_negative_cache_retries: usize,
     pub packet_sequence_block_size: usize, // Enterprise Edition Feature: packet-sequence
     pub packet_sequence_queue_size: usize, // Enterprise Edition Feature: packet-sequence
     pub packet_sequence_queue_count: usize, // Enterprise Edition Feature: packet-sequence
@@ -530,6 +1060,7 @@ pub struct YamlConfig {
     #[serde(rename = "l7-protocol-ports")]
     // hashmap<protocolName, portRange>
     pub l7_protocol_ports: HashMap<String, String>,
+    pub l7_protocol_hints: Vec<L7ProtocolHint>,
     pub npb_port: u16,
     // process and socket scan config
     pub os_proc_root: String,
@@ -543,6 +1074,10 @@ pub struct YamlConfig {
     pub os_proc_sync_enabled: bool,
     // sync os socket and proc info only when the process has been tagged.
     pub os_proc_sync_tagged_only: bool,
+    // link flows before and after NAT with the conntrack table of the node
+    pub conntrack_nat_enabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub conntrack_sync_interval: Duration,
     #[serde(with = "humantime_serde")]
     pub guard_interval: Duration,
     pub check_core_file_disabled: bool,
@@ -557,6 +1092,8 @@ pub struct YamlConfig {
     #[serde(with = "humantime_serde")]
     pub rrt_udp_timeout: Duration,
     pub prometheus_extra_config: PrometheusExtraConfig,
+    pub prometheus_scrape_config: PrometheusScrapeConfig,
+    pub statsd_config: StatsdConfig,
     pub process_scheduling_priority: i8,
     pub cpu_affinity: String,
     pub external_profile_integration_disabled: bool,
@@ -626,6 +1163,18 @@ impl YamlConfig {
                 1 << 16
             };
         }
+        if c.sender_compression.level < 1 || c.sender_compression.level > 19 {
+            c.sender_compression.level = 3;
+        }
+        if c.sender_spool.dir.is_empty() {
+            c.sender_spool.dir = SenderSpool::default().dir;
+        }
+        if c.sender_spool.max_size == 0 {
+            c.sender_spool.max_size = 1024;
+        }
+        if c.sender_spool.max_age < Duration::from_secs(60) {
+            c.sender_spool.max_age = Duration::from_secs(3600);
+        }
         if c.flow_sender_queue_size == 0 {
             c.flow_sender_queue_size = if tap_mode == trident::TapMode::Analyzer {
                 8 << 20
@@ -636,6 +1185,37 @@ impl YamlConfig {
         if c.packet_delay < Duration::from_secs(1) || c.packet_delay > Duration::from_secs(10) {
             c.packet_delay = Duration::from_secs(1);
         }
+        if c.ipfix_exporter.template_refresh_interval < Duration::from_secs(1) {
+            c.ipfix_exporter.template_refresh_interval = Duration::from_secs(60);
+        }
+        if c.otlp_exporter.timeout < Duration::from_secs(1) {
+            c.otlp_exporter.timeout = Duration::from_secs(5);
+        }
+        if c.otlp_exporter.batch_size == 0 || c.otlp_exporter.batch_size > 8192 {
+            c.otlp_exporter.batch_size = 1024;
+        }
+        if c.prometheus_remote_write.timeout < Duration::from_secs(1) {
+            c.prometheus_remote_write.timeout = Duration::from_secs(10);
+        }
+        if c.prometheus_remote_write.batch_size == 0 || c.prometheus_remote_write.batch_size > 10000
+        {
+            c.prometheus_remote_write.batch_size = 2000;
+        }
+        if c.kafka_exporter.timeout < Duration::from_secs(1) {
+            c.kafka_exporter.timeout = Duration::from_secs(5);
+        }
+        if c.clickhouse_exporter.timeout < Duration::from_secs(1) {
+            c.clickhouse_exporter.timeout = Duration::from_secs(10);
+        }
+        if c.clickhouse_exporter.batch_size == 0 || c.clickhouse_exporter.batch_size > 100000 {
+            c.clickhouse_exporter.batch_size = 10000;
+        }
+        if c.clickhouse_exporter.flush_interval < Duration::from_secs(1) {
+            c.clickhouse_exporter.flush_interval = Duration::from_secs(10);
+        }
+        if c.clickhouse_exporter.ttl < Duration::from_secs(3600) {
+            c.clickhouse_exporter.ttl = Duration::from_secs(7 * 24 * 3600);
+        }
         if c.first_path_level < 1 || c.first_path_level > 16 {
             c.first_path_level = 8;
         }
@@ -645,6 +1225,20 @@ impl YamlConfig {
             c.l7_log_session_aggr_timeout = Duration::from_secs(10);
         }
 
+        // l4 flow log intervals must divide a minute to keep minute boundaries
+        let l4_log_aggr_interval = c.l4_log_aggr_interval.as_secs();
+        if l4_log_aggr_interval == 0
+            || l4_log_aggr_interval > 60
+            || 60 % l4_log_aggr_interval != 0
+            || c.l4_log_aggr_interval.subsec_nanos() != 0
+        {
+            warn!(
+                "invalid l4-log-aggr-interval {:?}, use 60s instead",
+                c.l4_log_aggr_interval
+            );
+            c.l4_log_aggr_interval = Duration::from_secs(60);
+        }
+
         if c.external_metrics_sender_queue_size == 0 {
             c.external_metrics_sender_queue_size = 1 << 12;
         }
@@ -657,6 +1251,10 @@ impl YamlConfig {
             c.l7_protocol_inference_ttl = L7_PROTOCOL_INFERENCE_TTL;
         }
 
+        if c.l7_protocol_inference_negative_cache_retries > u8::MAX as usize {
+            c.l7_protocol_inference_negative_cache_retries = u8::MAX as usize;
+        }
+
         // Enterprise Edition Feature: packet-sequence
         if c.packet_sequence_block_size <= 0 || c.packet_sequence_block_size >= 1024 {
             c.packet_sequence_block_size = 256;
@@ -732,6 +1330,15 @@ impl YamlConfig {
                 .on_cpu_profile
                 .java_symbol_file_refresh_defer_interval = Duration::from_secs(60)
         }
+        if c.ebpf.off_cpu_profile.min_block < Duration::from_micros(1)
+            || c.ebpf.off_cpu_profile.min_block > Duration::from_secs(1)
+        {
+            c.ebpf.off_cpu_profile.min_block = Duration::from_micros(50)
+        }
+        if c.ebpf.memory_profile.sample_bytes < 4096 || c.ebpf.memory_profile.sample_bytes > 1 << 30
+        {
+            c.ebpf.memory_profile.sample_bytes = 524288
+        }
 
         if c.guard_interval < Duration::from_secs(1) || c.guard_interval > Duration::from_secs(3600)
         {
@@ -746,6 +1353,62 @@ impl YamlConfig {
             c.kubernetes_api_list_interval = Duration::from_secs(600);
         }
 
+        if c.conntrack_sync_interval < Duration::from_secs(1) {
+            c.conntrack_sync_interval = Duration::from_secs(1);
+        }
+
+        if c.l7_protocol_advanced_features
+            .http_sse_tracking
+            .report_interval
+            < Duration::from_secs(1)
+        {
+            c.l7_protocol_advanced_features
+                .http_sse_tracking
+                .report_interval = Duration::from_secs(60);
+        }
+
+        if c.l7_protocol_advanced_features
+            .http_body_decompression
+            .max_length
+            > 1 << 20
+        {
+            c.l7_protocol_advanced_features
+                .http_body_decompression
+                .max_length = 1 << 20;
+        }
+        if c.l7_protocol_advanced_features
+            .tls_certificate_expiry
+            .warning_days
+            > 3650
+        {
+            c.l7_protocol_advanced_features
+                .tls_certificate_expiry
+                .warning_days = 3650;
+        }
+        if c.l7_protocol_advanced_features
+            .dns_answer_records
+            .max_records
+            > 256
+        {
+            c.l7_protocol_advanced_features
+                .dns_answer_records
+                .max_records = 256;
+        }
+        let scripts = &mut c.l7_protocol_advanced_features.l7_log_scripts;
+        if scripts.max_operations == 0 || scripts.max_operations > 1000000 {
+            scripts.max_operations = 1000000;
+        }
+        if scripts.timeout.is_zero() || scripts.timeout > Duration::from_millis(100) {
+            scripts.timeout = Duration::from_millis(100);
+        }
+
+        if c.flow.tcp_reassembly_max_message_size > 16 << 20 {
+            c.flow.tcp_reassembly_max_message_size = 16 << 20;
+        }
+        if c.flow.flow_sampling_permille > 1000 {
+            c.flow.flow_sampling_permille = 1000;
+        }
+
         if c.forward_capacity < 1 << 14 {
             c.forward_capacity = 1 << 14;
         }
@@ -775,6 +1438,31 @@ impl YamlConfig {
             c.prometheus_extra_config.values_limit = 4096;
         }
 
+        if c.prometheus_scrape_config.interval < Duration::from_secs(1) {
+            c.prometheus_scrape_config.interval = Duration::from_secs(15);
+        }
+        if c.prometheus_scrape_config.timeout < Duration::from_secs(1)
+            || c.prometheus_scrape_config.timeout > c.prometheus_scrape_config.interval
+        {
+            c.prometheus_scrape_config.timeout = c
+                .prometheus_scrape_config
+                .interval
+                .min(Duration::from_secs(10));
+        }
+        if c.prometheus_scrape_config.sample_limit == 0 {
+            c.prometheus_scrape_config.sample_limit = 50000;
+        }
+
+        if c.statsd_config.listen_port == 0 {
+            c.statsd_config.listen_port = 8125;
+        }
+        if c.statsd_config.flush_interval < Duration::from_secs(1) {
+            c.statsd_config.flush_interval = Duration::from_secs(10);
+        }
+        if c.statsd_config.max_series == 0 {
+            c.statsd_config.max_series = 100000;
+        }
+
         let mut valid_labels = vec![];
         let re = Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
         for label in c.prometheus_extra_config.labels {
@@ -809,9 +1497,39 @@ impl YamlConfig {
             new.insert(dns_str.to_string(), DEFAULT_DNS_PORT.to_string());
         }
         let tls_str = L7ProtocolParser::Tls(TlsLog::default()).as_str();
-        // tls default only parse 443 port. when l7_protocol_ports config without TLS, need to reserve the tls default config.
+        // tls handshake is parsed on any port, the default port of ebpf is overridden
         if !self.l7_protocol_ports.contains_key(tls_str) {
-            new.insert(tls_str.to_string(), DEFAULT_TLS_PORT.to_string());
+            new.insert(tls_str.to_string(), String::from("1-65535"));
+        }
+        let dhcp_str = L7ProtocolParser::DHCP(DhcpLog::default()).as_str();
+        // dhcp is only parsed on the server and client ports of dhcpv4 and dhcpv6
+        if !self.l7_protocol_ports.contains_key(dhcp_str) {
+            new.insert(dhcp_str.to_string(), String::from("67-68,546-547"));
+        }
+        let ntp_str = L7ProtocolParser::NTP(NtpLog::default()).as_str();
+        if !self.l7_protocol_ports.contains_key(ntp_str) {
+            new.insert(ntp_str.to_string(), String::from("123"));
+        }
+        let kerberos_str = L7ProtocolParser::Kerberos(KerberosLog::default()).as_str();
+        if !self.l7_protocol_ports.contains_key(kerberos_str) {
+            new.insert(kerberos_str.to_string(), String::from("88"));
+        }
+        let radius_str = L7ProtocolParser::RADIUS(RadiusLog::default()).as_str();
+        // authentication, accounting, their legacy ports and dynamic authorization
+        if !self.l7_protocol_ports.contains_key(radius_str) {
+            new.insert(
+                radius_str.to_string(),
+                String::from("1645-1646,1812-1813,3799"),
+            );
+        }
+        let sip_str = L7ProtocolParser::SIP(SipLog::default()).as_str();
+        if !self.l7_protocol_ports.contains_key(sip_str) {
+            new.insert(sip_str.to_string(), String::from("5060-5061"));
+        }
+        let rtp_str = L7ProtocolParser::RTP(RtpLog::default()).as_str();
+        // media ports are negotiated dynamically above the well-known range
+        if !self.l7_protocol_ports.contains_key(rtp_str) {
+            new.insert(rtp_str.to_string(), String::from("1024-65535"));
         }
 
         new
@@ -837,6 +1555,18 @@ impl YamlConfig {
         port_bitmap.sort_unstable_by_key(|p| p.0.clone());
         port_bitmap
     }
+
+    // Rings in dpdk-ring-port are distributed among dispatchers in turn
+    pub fn dispatcher_dpdk_rings(&self, id: usize, dispatcher_count: usize) -> Vec<String> {
+        self.dpdk_ring_port
+            .split(',')
+            .map(|r| r.trim())
+            .filter(|r| !r.is_empty())
+            .enumerate()
+            .filter(|(i, _)| i % dispatcher_count.max(1) == id)
+            .map(|(_, r)| r.to_owned())
+            .collect()
+    }
 }
 
 impl Default for YamlConfig {
@@ -846,6 +1576,7 @@ impl Default for YamlConfig {
             profiler: false,
             af_packet_blocks_enabled: false,
             af_packet_blocks: 128,
+            af_packet_blocks_adaptive: false,
             enable_debug_stats: false,
             analyzer_dedup_disabled: false,
             default_tap_type: 3,
@@ -854,6 +1585,7 @@ impl Default for YamlConfig {
             fast_path_map_size: 1 << 14,
             first_path_level: 0,
             src_interfaces: vec![],
+            dispatcher_capture_bpf: vec![],
             mirror_traffic_pcp: 0,
             vtap_group_id_request: "".into(),
             pcap: Default::default(),
@@ -866,15 +1598,27 @@ impl Default for YamlConfig {
             ovs_dpdk_enabled: false,
             dpdk_pmd_core_id: 0,
             dpdk_ring_port: "dpdkr0".into(),
+            dpdk_file_prefix: "rte".into(),
             #[cfg(any(target_os = "linux", target_os = "android"))]
             libpcap_enabled: false,
             #[cfg(target_os = "windows")]
             libpcap_enabled: true,
+            pf_ring_enabled: false,
+            pf_ring_zc_enabled: false,
             xflow_collector: Default::default(),
+            pcap_replay: Default::default(),
+            packet_dedup: Default::default(),
+            ipfix_exporter: Default::default(),
+            otlp_exporter: Default::default(),
+            prometheus_remote_write: Default::default(),
+            kafka_exporter: Default::default(),
+            clickhouse_exporter: Default::default(),
             vxlan_flags: 0xff,
             // default size changes according to tap_mode
             collector_sender_queue_size: 1 << 16,
             collector_sender_queue_count: 1,
+            sender_compression: Default::default(),
+            sender_spool: Default::default(),
             toa_sender_queue_size: 1 << 16,
             toa_lru_cache_size: 1 << 16,
             // default size changes according to tap_mode
@@ -885,9 +1629,12 @@ impl Default for YamlConfig {
             triple: Default::default(),
             kubernetes_poller_type: KubernetesPollerType::Adaptive,
             decap_erspan: false,
+            erspan_timestamp_enabled: false,
+            afpacket_hardware_timestamp: false,
             analyzer_ip: "".into(),
             grpc_buffer_size: 5,
             l7_log_session_aggr_timeout: Duration::from_secs(120),
+            l4_log_aggr_interval: Duration::from_secs(60),
             tap_mac_script: "".into(),
             cloud_gateway_traffic: false,
             kubernetes_namespace: "".into(),
@@ -897,6 +1644,7 @@ impl Default for YamlConfig {
             external_metrics_sender_queue_size: 1 << 12,
             l7_protocol_inference_max_fail_count: L7_PROTOCOL_INFERENCE_MAX_FAIL_COUNT,
             l7_protocol_inference_ttl: L7_PROTOCOL_INFERENCE_TTL,
+            l7_protocol_inference_negative_cache_retries: 3,
             packet_sequence_block_size: 256, // Enterprise Edition Feature: packet-sequence
             packet_sequence_queue_size: 1 << 16, // Enterprise Edition Feature: packet-sequence
             packet_sequence_queue_count: 1,  // Enterprise Edition Feature: packet-sequence
@@ -921,10 +1669,8 @@ impl Default for YamlConfig {
                 .to_string(),
 
             log_file: DEFAULT_LOG_FILE.into(),
-            l7_protocol_ports: HashMap::from([
-                (String::from("DNS"), String::from("53,5353")),
-                (String::from("TLS"), String::from("443")),
-            ]),
+            l7_protocol_ports: HashMap::from([(String::from("DNS"), String::from("53,5353,5355"))]),
+            l7_protocol_hints: vec![],
             ebpf: EbpfYamlConfig::default(),
             npb_port: NPB_DEFAULT_PORT,
             os_proc_root: "/proc".into(),
@@ -940,6 +1686,8 @@ impl Default for YamlConfig {
             os_app_tag_exec: vec![],
             os_proc_sync_enabled: false,
             os_proc_sync_tagged_only: false,
+            conntrack_nat_enabled: false,
+            conntrack_sync_interval: Duration::from_secs(10),
             guard_interval: Duration::from_secs(10),
             check_core_file_disabled: false,
             wasm_plugins: vec![],
@@ -950,6 +1698,8 @@ impl Default for YamlConfig {
             rrt_tcp_timeout: Duration::from_secs(1800),
             rrt_udp_timeout: Duration::from_secs(150),
             prometheus_extra_config: PrometheusExtraConfig::default(),
+            prometheus_scrape_config: PrometheusScrapeConfig::default(),
+            statsd_config: StatsdConfig::default(),
             process_scheduling_priority: 0,
             cpu_affinity: "".to_string(),
             external_profile_integration_disabled: false,
@@ -1056,6 +1806,43 @@ pub struct FlowGeneratorConfig {
     pub ignore_tor_mac: bool,
     pub ignore_l2_end: bool,
     pub ignore_idc_vlan: bool,
+    // flows over the same outer tunnel with different tunnel ids (GRE key, ERSPAN session id,
+    // VXLAN VNI) are generated separately
+    pub tunnel_id_in_flow_key: bool,
+    // vlan tags of double tagged frames used in flow keys and tags
+    pub vlan_tag_mode: VlanTagMode,
+
+    // 0 means tcp reassembly disabled
+    pub tcp_reassembly_max_message_size: usize,
+    pub tcp_reassembly_memory_limit: usize,
+
+    pub l7_rrt_cache_adaptive: bool,
+    pub l7_rrt_cache_memory_limit: usize,
+
+    pub protocol_timeouts: Vec<ProtocolTimeout>,
+
+    // keep only the flows whose 5-tuple hash falls in the first N of every 1000 buckets,
+    // flows matching policies are always kept, 1000 means sampling disabled
+    pub flow_sampling_permille: u16,
+}
+
+// Overrides the tcp timeouts above for flows matching the l7 protocol and server ports,
+// timeouts not specified fall back to the global ones
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ProtocolTimeout {
+    // l7 protocol name as in l7-protocol-enabled, empty for any protocol
+    pub protocol: String,
+    // server ports, e.g. "1883,8883,10000-10100", empty for any port
+    pub ports: String,
+    #[serde(with = "humantime_serde")]
+    pub established_timeout: Option<Duration>,
+    #[serde(with = "humantime_serde")]
+    pub closing_rst_timeout: Option<Duration>,
+    #[serde(with = "humantime_serde")]
+    pub others_timeout: Option<Duration>,
+    #[serde(with = "humantime_serde")]
+    pub opening_rst_timeout: Option<Duration>,
 }
 
 impl Default for FlowGeneratorConfig {
@@ -1075,6 +1862,18 @@ impl Default for FlowGeneratorConfig {
             ignore_tor_mac: false,
             ignore_l2_end: false,
             ignore_idc_vlan: false,
+            tunnel_id_in_flow_key: false,
+            vlan_tag_mode: VlanTagMode::Inner,
+
+            tcp_reassembly_max_message_size: 64 << 10,
+            tcp_reassembly_memory_limit: 64 << 20,
+
+            l7_rrt_cache_adaptive: false,
+            l7_rrt_cache_memory_limit: 64 << 20,
+
+            protocol_timeouts: vec![],
+
+            flow_sampling_permille: 1000,
         }
     }
 }
@@ -1084,6 +1883,26 @@ impl Default for FlowGeneratorConfig {
 pub struct XflowGeneratorConfig {
     pub sflow_ports: Vec<String>,
     pub netflow_ports: Vec<String>,
+    // receive sampled packet headers on sflow ports in mirror mode
+    pub sflow_ingestion_enabled: bool,
+}
+
+impl XflowGeneratorConfig {
+    pub fn sflow_ingestion_ports(&self) -> Vec<u16> {
+        if !self.sflow_ingestion_enabled {
+            return vec![];
+        }
+        self.sflow_ports
+            .iter()
+            .filter_map(|port| match port.parse() {
+                Ok(port) => Some(port),
+                Err(e) => {
+                    warn!("invalid sflow port {}: {}", port, e);
+                    None
+                }
+            })
+            .collect()
+    }
 }
 
 impl Default for XflowGeneratorConfig {
@@ -1091,10 +1910,319 @@ impl Default for XflowGeneratorConfig {
         XflowGeneratorConfig {
             sflow_ports: vec!["6343".into()],
             netflow_ports: vec!["2055".into()],
+            sflow_ingestion_enabled: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PcapReplayConfig {
+    // pcap or pcapng files replayed by dispatchers in mirror mode, empty means disabled
+    pub files: Vec<String>,
+    // 1 replays at the captured rate, 0 replays as fast as possible
+    pub speed: u32,
+    // keep the capture time of packets instead of timestamping them at replay time
+    pub preserve_timestamps: bool,
+}
+
+impl PcapReplayConfig {
+    // Files are distributed among dispatchers in turn
+    pub fn dispatcher_files(&self, id: usize, dispatcher_count: usize) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % dispatcher_count.max(1) == id)
+            .map(|(_, f)| PathBuf::from(f))
+            .collect()
+    }
+}
+
+impl Default for PcapReplayConfig {
+    fn default() -> Self {
+        PcapReplayConfig {
+            files: vec![],
+            speed: 1,
+            preserve_timestamps: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PacketDedupConfig {
+    pub enabled: bool,
+    // copies of a packet received within the window are dropped
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    // number of recent packets remembered by each dispatcher
+    pub capacity: usize,
+}
+
+impl Default for PacketDedupConfig {
+    fn default() -> Self {
+        PacketDedupConfig {
+            enabled: false,
+            window: Duration::from_millis(10),
+            capacity: 65536,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct IpfixExporter {
+    pub enabled: bool,
+    pub protocol: FlowExportProtocol,
+    // ip:port of collectors
+    pub collectors: Vec<String>,
+    pub observation_domain_id: u32,
+    #[serde(with = "humantime_serde")]
+    pub template_refresh_interval: Duration,
+    // private enterprise number of l7 information elements, 0 means not exported
+    pub enterprise_number: u32,
+}
+
+impl Default for IpfixExporter {
+    fn default() -> Self {
+        IpfixExporter {
+            enabled: false,
+            protocol: FlowExportProtocol::default(),
+            collectors: vec![],
+            observation_domain_id: 0,
+            template_refresh_interval: Duration::from_secs(60),
+            enterprise_number: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct OtlpExporter {
+    pub enabled: bool,
+    // OTLP/gRPC endpoint, e.g. http://10.1.1.1:4317
+    pub endpoint: String,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    // max spans in an export request
+    pub batch_size: usize,
+    // added to the resource attributes of all spans
+    pub resource_attributes: HashMap<String, String>,
+}
+
+impl Default for OtlpExporter {
+    fn default() -> Self {
+        OtlpExporter {
+            enabled: false,
+            endpoint: String::new(),
+            timeout: Duration::from_secs(5),
+            batch_size: 1024,
+            resource_attributes: HashMap::new(),
         }
     }
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PrometheusRemoteWrite {
+    pub enabled: bool,
+    // remote write url, e.g. http://10.1.1.1:9009/api/v1/push
+    pub endpoint: String,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    // max time series in a write request
+    pub batch_size: usize,
+    // http headers of write requests, e.g. X-Scope-OrgID for multi-tenant receivers
+    pub headers: HashMap<String, String>,
+    // added to the labels of all time series
+    pub external_labels: HashMap<String, String>,
+}
+
+impl Default for PrometheusRemoteWrite {
+    fn default() -> Self {
+        PrometheusRemoteWrite {
+            enabled: false,
+            endpoint: String::new(),
+            timeout: Duration::from_secs(10),
+            batch_size: 2000,
+            headers: HashMap::new(),
+            external_labels: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct KafkaExporter {
+    pub enabled: bool,
+    // host:port of bootstrap brokers
+    pub brokers: Vec<String>,
+    // empty topics are not exported
+    pub l7_flow_log_topic: String,
+    pub metrics_topic: String,
+    pub format: KafkaMessageFormat,
+    pub partition_key: KafkaPartitionKey,
+    // timeout of acknowledgements from brokers
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for KafkaExporter {
+    fn default() -> Self {
+        KafkaExporter {
+            enabled: false,
+            brokers: vec![],
+            l7_flow_log_topic: "deepflow_l7_flow_log".to_string(),
+            metrics_topic: "deepflow_metrics".to_string(),
+            format: KafkaMessageFormat::default(),
+            partition_key: KafkaPartitionKey::default(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KafkaMessageFormat {
+    #[default]
+    Json,
+    Protobuf,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KafkaPartitionKey {
+    // partitioned by the producer
+    #[default]
+    None,
+    FlowId,
+    ServerIp,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ClickhouseExporter {
+    pub enabled: bool,
+    // host:port of the native protocol, e.g. 10.1.1.1:9000
+    pub endpoint: String,
+    pub username: String,
+    pub password: String,
+    pub database: String,
+    // empty tables are not exported, {meter} in the metrics table is replaced by
+    // the meter of documents, e.g. app_metrics
+    pub l7_flow_log_table: String,
+    pub metrics_table: String,
+    // tables are created with the template if not exist, {database}, {table},
+    // {columns} and {ttl_hours} in the template are replaced, empty to disable
+    pub create_table_template: String,
+    #[serde(with = "humantime_serde")]
+    pub ttl: Duration,
+    // max rows in an insert
+    pub batch_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl ClickhouseExporter {
+    pub const DEFAULT_CREATE_TABLE_TEMPLATE: &'static str =
+        "CREATE TABLE IF NOT EXISTS {database}.{table} ({columns}) ENGINE = MergeTree() \
+         PARTITION BY toYYYYMMDD(time) ORDER BY time TTL time + toIntervalHour({ttl_hours})";
+}
+
+impl Default for ClickhouseExporter {
+    fn default() -> Self {
+        ClickhouseExporter {
+            enabled: false,
+            endpoint: String::new(),
+            username: "default".to_string(),
+            password: String::new(),
+            database: "deepflow".to_string(),
+            l7_flow_log_table: "l7_flow_log".to_string(),
+            metrics_table: "{meter}_metrics".to_string(),
+            create_table_template: Self::DEFAULT_CREATE_TABLE_TEMPLATE.to_string(),
+            ttl: Duration::from_secs(7 * 24 * 3600),
+            batch_size: 10000,
+            flush_interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompressionAlgorithm {
+    #[default]
+    None,
+    Zstd,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SenderCompression {
+    // only takes effect when the server supports it, otherwise data is sent uncompressed
+    pub algorithm: CompressionAlgorithm,
+    // zstd level, 1 to 19
+    pub level: i32,
+}
+
+impl Default for SenderCompression {
+    fn default() -> Self {
+        SenderCompression {
+            algorithm: CompressionAlgorithm::None,
+            level: 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct SenderSpool {
+    // buffer data on disk when the server is unreachable and send it after reconnection
+    pub enabled: bool,
+    // each sender spools in its own sub directory
+    pub dir: String,
+    // max size of each sender in MB, oldest data is evicted first
+    pub max_size: u32,
+    #[serde(with = "humantime_serde")]
+    pub max_age: Duration,
+}
+
+impl Default for SenderSpool {
+    fn default() -> Self {
+        SenderSpool {
+            enabled: false,
+            dir: Path::new(DEFAULT_LOG_FILE)
+                .parent()
+                .unwrap()
+                .join("spool")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            max_size: 1024,
+            max_age: Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FlowExportProtocol {
+    #[default]
+    Ipfix,
+    NetflowV9,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum VlanTagMode {
+    Outer,
+    #[default]
+    Inner,
+    Both,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct TripleMapConfig {
@@ -1188,6 +2316,8 @@ pub struct RuntimeConfig {
     pub vtap_id: u16,
     #[serde(deserialize_with = "to_socket_type")]
     pub collector_socket_type: trident::SocketType,
+    #[serde(skip)]
+    pub collector_zstd_supported: bool,
     #[serde(deserialize_with = "to_socket_type")]
     pub npb_socket_type: trident::SocketType,
     #[serde(skip)]
@@ -1300,6 +2430,7 @@ impl RuntimeConfig {
             epc_id: 3302,
             vtap_id: 3302,
             collector_socket_type: trident::SocketType::File,
+            collector_zstd_supported: false,
             npb_socket_type: trident::SocketType::RawUdp,
             trident_type: common::TridentType::TtProcess,
             capture_packet_size: 65535,
@@ -1486,6 +2617,7 @@ impl TryFrom<trident::Config> for RuntimeConfig {
             epc_id: conf.epc_id(),
             vtap_id: (conf.vtap_id() & 0xFFFFFFFF) as u16,
             collector_socket_type: conf.collector_socket_type(),
+            collector_zstd_supported: conf.collector_zstd_supported(),
             npb_socket_type: conf.npb_socket_type(),
             trident_type: conf.trident_type(),
             capture_packet_size: conf.capture_packet_size(),
@@ -1596,7 +2728,7 @@ where
             TunnelType::try_from(t).map_err(|_| {
                 de::Error::invalid_value(
                     Unexpected::Unsigned(t as u64),
-                    &"None|Vxlan|Ipip|TencentGre|Geneve|ErspanOrTeb",
+                    &"None|Vxlan|Ipip|TencentGre|Geneve|ErspanOrTeb|GtpU|Srv6",
                 )
             })
         })
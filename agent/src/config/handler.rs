@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::borrow::Cow;
 use std::cmp::{max, min};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
@@ -34,6 +35,7 @@ use nix::{
     sched::{sched_setaffinity, CpuSet},
     unistd::Pid,
 };
+use regex::Regex;
 use sysinfo::SystemExt;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use sysinfo::{CpuRefreshKind, RefreshKind, System};
@@ -47,8 +49,15 @@ use super::{
 };
 use super::{
     config::{
-        Config, HttpEndpointExtraction, KubernetesResourceConfig, MatchRule, PcapConfig,
-        PortConfig, YamlConfig,
+        ApdexThreshold, ClickhouseExporter, CompressionAlgorithm, Config, DnsAnswerRecords,
+        FieldExtractionRule, HttpBodyDecompression, HttpEndpointExtraction, HttpErrorBodyCapture,
+        HttpHeaderExtraction, HttpIdentityExtraction, HttpPathNormalization,
+        HttpProxyHeaderExtraction, HttpSseTracking, IpfixExporter, KafkaExporter,
+        KubernetesResourceConfig, L7ConcurrencyStats, L7EndpointAggregation, L7LogDeduplication,
+        L7LogMasking, L7LogScripts, L7MessageSizeStats, L7ProtocolHint, L7ResponseCodeCounts,
+        L7RrtSketch, MatchRule, OtlpExporter, PathNormalizationRule, PcapConfig, PortConfig,
+        PrometheusRemoteWrite, PrometheusScrapeConfig, RrtHistogramBuckets, SenderSpool,
+        TlsCertificateExpiry, TraceContextFormat, VlanTagMode, YamlConfig,
     },
     ConfigError, KubernetesPollerType, RuntimeConfig,
 };
@@ -57,10 +66,21 @@ use crate::plugin::c_ffi::SoPluginFunc;
 use crate::plugin::shared_obj::load_plugin;
 use crate::rpc::Session;
 use crate::{
-    common::{decapsulate::TunnelTypeBitmap, enums::TapType, l7_protocol_log::L7ProtocolBitmap},
+    common::{
+        decapsulate::TunnelTypeBitmap,
+        enums::TapType,
+        flow::{L7Protocol, RrtHistogram, RESPONSE_CODE_MAX_TOP_K, RRT_HISTOGRAM_MAX_BUCKETS},
+        l7_protocol_log::{L7ProtocolBitmap, L7ProtocolParser, L7ProtocolParserInterface},
+    },
     dispatcher::recv_engine,
     exception::ExceptionHandler,
-    flow_generator::{protocol_logs::SOFA_NEW_RPC_TRACE_CTX_KEY, FlowTimeout, TcpTimeout},
+    flow_generator::{
+        protocol_logs::{
+            plugin::simple::{compile_simple_protocols, SimpleProtocol},
+            SOFA_NEW_RPC_TRACE_CTX_KEY,
+        },
+        FlowTimeout, FlowTimeoutRule, TcpTimeout,
+    },
     handler::PacketHandlerBuilder,
     metric::document::TapSide,
     trident::{AgentComponents, RunningMode},
@@ -86,7 +106,7 @@ use public::proto::{
 };
 
 use crate::{trident::AgentId, utils::cgroups::is_kernel_available_for_cgroups};
-use public::utils::net::MacAddr;
+use public::utils::{bitmap::parse_u16_range_list_to_bitmap, net::MacAddr};
 
 const MB: u64 = 1048576;
 const MINUTE: Duration = Duration::from_secs(60);
@@ -142,6 +162,11 @@ pub struct CollectorConfig {
     pub vtap_id: u16,
     pub cloud_gateway_traffic: bool,
     pub packet_delay: Duration,
+    pub l4_log_aggr_interval: Duration,
+    pub ipfix_exporter: IpfixExporter,
+    pub prometheus_remote_write: PrometheusRemoteWrite,
+    pub kafka_exporter: KafkaExporter,
+    pub clickhouse_exporter: ClickhouseExporter,
 }
 
 impl fmt::Debug for CollectorConfig {
@@ -187,6 +212,11 @@ impl fmt::Debug for CollectorConfig {
             .field("vtap_id", &self.vtap_id)
             .field("cloud_gateway_traffic", &self.cloud_gateway_traffic)
             .field("packet_delay", &self.packet_delay)
+            .field("l4_log_aggr_interval", &self.l4_log_aggr_interval)
+            .field("ipfix_exporter", &self.ipfix_exporter)
+            .field("prometheus_remote_write", &self.prometheus_remote_write)
+            .field("kafka_exporter", &self.kafka_exporter)
+            .field("clickhouse_exporter", &self.clickhouse_exporter)
             .finish()
     }
 }
@@ -217,6 +247,9 @@ pub struct SenderConfig {
     pub npb_bps_threshold: u64,
     pub npb_socket_type: trident::SocketType,
     pub collector_socket_type: trident::SocketType,
+    // compress data sent to the server, only when the server supports it
+    pub zstd_level: Option<i32>,
+    pub sender_spool: SenderSpool,
     pub standalone_data_file_size: u32,
     pub standalone_data_file_dir: String,
     pub server_tx_bandwidth_threshold: u64,
@@ -290,6 +323,9 @@ pub struct PlatformConfig {
     pub thread_threshold: u32,
     pub tap_mode: TapMode,
     pub os_proc_scan_conf: OsProcScanConfig,
+    pub conntrack_nat_enabled: bool,
+    pub conntrack_sync_interval: Duration,
+    pub prometheus_scrape_config: PrometheusScrapeConfig,
 }
 
 #[derive(Clone, PartialEq, Debug, Eq)]
@@ -318,6 +354,8 @@ pub struct DispatcherConfig {
     pub capture_bpf: String,
     pub max_memory: u64,
     pub af_packet_blocks: usize,
+    // total of all dispatchers, 0 means the number of blocks is fixed
+    pub af_packet_max_blocks: usize,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub af_packet_version: OptTpacketVersion,
     pub tap_mode: TapMode,
@@ -349,12 +387,23 @@ pub struct FlowConfig {
     pub packet_delay: Duration,
     pub flush_interval: Duration,
     pub flow_timeout: FlowTimeout,
+    pub flow_timeout_rules: Vec<FlowTimeoutRule>,
     pub ignore_tor_mac: bool,
     pub ignore_l2_end: bool,
     pub ignore_idc_vlan: bool,
+    pub tunnel_id_in_flow_key: bool,
+    pub vlan_tag_mode: VlanTagMode,
 
     pub memory_pool_size: usize,
 
+    pub tcp_reassembly_max_message_size: usize,
+    pub tcp_reassembly_memory_limit: usize,
+
+    pub l7_rrt_cache_adaptive: bool,
+    pub l7_rrt_cache_memory_limit: usize,
+
+    pub flow_sampling_permille: u16,
+
     pub l7_metrics_enabled: bool,
     pub app_proto_log_enabled: bool,
     pub l4_performance_enabled: bool,
@@ -362,6 +411,7 @@ pub struct FlowConfig {
 
     pub l7_protocol_inference_max_fail_count: usize,
     pub l7_protocol_inference_ttl: usize,
+    pub l7_protocol_inference_negative_cache_retries: usize,
 
     // Enterprise Edition Feature: packet-sequence
     pub packet_sequence_flag: u8,
@@ -371,6 +421,7 @@ pub struct FlowConfig {
 
     // vec<protocolName, port bitmap>
     pub l7_protocol_parse_port_bitmap: Arc<Vec<(String, Bitmap)>>,
+    pub l7_protocol_hints: Vec<L7ProtocolHint>,
 
     // name, data
     pub wasm_plugins: Vec<(String, Vec<u8>)>,
@@ -415,10 +466,75 @@ impl From<&RuntimeConfig> for FlowConfig {
                 others: flow_config.others_timeout.into(),
                 opening_rst: flow_config.opening_rst_timeout.into(),
             }),
+            flow_timeout_rules: flow_config
+                .protocol_timeouts
+                .iter()
+                .filter_map(|t| {
+                    if t.protocol.is_empty() && t.ports.is_empty() {
+                        warn!("protocol timeout {:?} matches nothing, ignored", t);
+                        return None;
+                    }
+                    let l7_protocol = if t.protocol.is_empty() {
+                        None
+                    } else {
+                        match L7ProtocolParser::try_from(t.protocol.as_str()) {
+                            Ok(p) => Some(p.protocol()),
+                            Err(_) => {
+                                warn!(
+                                    "invalid protocol {} in protocol timeout, ignored",
+                                    t.protocol
+                                );
+                                return None;
+                            }
+                        }
+                    };
+                    let port_bitmap = if t.ports.is_empty() {
+                        None
+                    } else {
+                        match parse_u16_range_list_to_bitmap(&t.ports, true) {
+                            Some(b) => Some(b),
+                            None => {
+                                warn!("invalid ports {} in protocol timeout, ignored", t.ports);
+                                return None;
+                            }
+                        }
+                    };
+                    Some(FlowTimeoutRule {
+                        l7_protocol,
+                        ports: t.ports.clone(),
+                        port_bitmap,
+                        flow_timeout: FlowTimeout::from(TcpTimeout {
+                            established: t
+                                .established_timeout
+                                .unwrap_or(flow_config.established_timeout)
+                                .into(),
+                            closing_rst: t
+                                .closing_rst_timeout
+                                .unwrap_or(flow_config.closing_rst_timeout)
+                                .into(),
+                            others: t
+                                .others_timeout
+                                .unwrap_or(flow_config.others_timeout)
+                                .into(),
+                            opening_rst: t
+                                .opening_rst_timeout
+                                .unwrap_or(flow_config.opening_rst_timeout)
+                                .into(),
+                        }),
+                    })
+                })
+                .collect(),
             ignore_tor_mac: flow_config.ignore_tor_mac,
             ignore_l2_end: flow_config.ignore_l2_end,
             ignore_idc_vlan: flow_config.ignore_idc_vlan,
+            tunnel_id_in_flow_key: flow_config.tunnel_id_in_flow_key,
+            vlan_tag_mode: flow_config.vlan_tag_mode,
             memory_pool_size: flow_config.memory_pool_size,
+            tcp_reassembly_max_message_size: flow_config.tcp_reassembly_max_message_size,
+            tcp_reassembly_memory_limit: flow_config.tcp_reassembly_memory_limit,
+            l7_rrt_cache_adaptive: flow_config.l7_rrt_cache_adaptive,
+            l7_rrt_cache_memory_limit: flow_config.l7_rrt_cache_memory_limit,
+            flow_sampling_permille: flow_config.flow_sampling_permille,
             l7_metrics_enabled: conf.l7_metrics_enabled,
             app_proto_log_enabled: conf.app_proto_log_enabled,
             l4_performance_enabled: conf.l4_performance_enabled,
@@ -427,6 +543,9 @@ impl From<&RuntimeConfig> for FlowConfig {
                 .yaml_config
                 .l7_protocol_inference_max_fail_count,
             l7_protocol_inference_ttl: conf.yaml_config.l7_protocol_inference_ttl,
+            l7_protocol_inference_negative_cache_retries: conf
+                .yaml_config
+                .l7_protocol_inference_negative_cache_retries,
             packet_sequence_flag: conf.yaml_config.packet_sequence_flag, // Enterprise Edition Feature: packet-sequence
             packet_sequence_block_size: conf.yaml_config.packet_sequence_block_size, // Enterprise Edition Feature: packet-sequence
             l7_protocol_enabled_bitmap: L7ProtocolBitmap::from(
@@ -435,6 +554,7 @@ impl From<&RuntimeConfig> for FlowConfig {
             l7_protocol_parse_port_bitmap: Arc::new(
                 (&conf.yaml_config).get_protocol_port_parse_bitmap(),
             ),
+            l7_protocol_hints: conf.yaml_config.l7_protocol_hints.clone(),
             wasm_plugins: vec![],
             so_plugins: vec![],
             rrt_tcp_timeout: conf.yaml_config.rrt_tcp_timeout.as_micros() as usize,
@@ -472,8 +592,22 @@ impl fmt::Debug for FlowConfig {
             .field("packet_delay", &self.packet_delay)
             .field("flush_interval", &self.flush_interval)
             .field("flow_timeout", &self.flow_timeout)
+            .field("flow_timeout_rules", &self.flow_timeout_rules)
             .field("ignore_tor_mac", &self.ignore_tor_mac)
             .field("ignore_l2_end", &self.ignore_l2_end)
+            .field("tunnel_id_in_flow_key", &self.tunnel_id_in_flow_key)
+            .field("vlan_tag_mode", &self.vlan_tag_mode)
+            .field(
+                "tcp_reassembly_max_message_size",
+                &self.tcp_reassembly_max_message_size,
+            )
+            .field(
+                "tcp_reassembly_memory_limit",
+                &self.tcp_reassembly_memory_limit,
+            )
+            .field("l7_rrt_cache_adaptive", &self.l7_rrt_cache_adaptive)
+            .field("l7_rrt_cache_memory_limit", &self.l7_rrt_cache_memory_limit)
+            .field("flow_sampling_permille", &self.flow_sampling_permille)
             .field("l7_metrics_enabled", &self.l7_metrics_enabled)
             .field("app_proto_log_enabled", &self.app_proto_log_enabled)
             .field("l4_performance_enabled", &self.l4_performance_enabled)
@@ -483,6 +617,10 @@ impl fmt::Debug for FlowConfig {
                 &self.l7_protocol_inference_max_fail_count,
             )
             .field("l7_protocol_inference_ttl", &self.l7_protocol_inference_ttl)
+            .field(
+                "l7_protocol_inference_negative_cache_retries",
+                &self.l7_protocol_inference_negative_cache_retries,
+            )
             .field("packet_sequence_flag", &self.packet_sequence_flag)
             .field(
                 "packet_sequence_block_size",
@@ -492,6 +630,7 @@ impl fmt::Debug for FlowConfig {
                 "l7_protocol_enabled_bitmap",
                 &self.l7_protocol_enabled_bitmap,
             )
+            .field("l7_protocol_hints", &self.l7_protocol_hints)
             // FIXME: this field is too long to log
             // .field("l7_protocol_parse_port_bitmap", &self.l7_protocol_parse_port_bitmap)
             .finish()
@@ -576,6 +715,112 @@ impl From<&HttpEndpointExtraction> for HttpEndpointTrie {
     }
 }
 
+#[derive(Clone)]
+pub struct HttpPathNormalizer {
+    enabled: bool,
+    builtin_rules: bool,
+    rules: Vec<(Regex, String)>,
+}
+
+impl HttpPathNormalizer {
+    const UUID_SEGMENT_LENGTH: usize = 36;
+    const MIN_HASH_SEGMENT_LENGTH: usize = 16;
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            builtin_rules: false,
+            rules: vec![],
+        }
+    }
+
+    fn is_uuid(segment: &str) -> bool {
+        segment.len() == Self::UUID_SEGMENT_LENGTH
+            && segment.char_indices().all(|(i, c)| match i {
+                8 | 13 | 18 | 23 => c == '-',
+                _ => c.is_ascii_hexdigit(),
+            })
+    }
+
+    // such as md5, sha1 or object id, at least one digit is required to keep words like 'deadbeefcafebabe'
+    fn is_hash(segment: &str) -> bool {
+        segment.len() >= Self::MIN_HASH_SEGMENT_LENGTH
+            && segment.chars().all(|c| c.is_ascii_hexdigit())
+            && segment.chars().any(|c| c.is_ascii_digit())
+    }
+
+    fn normalize_segment(segment: &str) -> &str {
+        if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+            "{id}"
+        } else if Self::is_uuid(segment) {
+            "{uuid}"
+        } else if Self::is_hash(segment) {
+            "{hash}"
+        } else {
+            segment
+        }
+    }
+
+    // the query string should be removed from the path before normalization
+    pub fn normalize<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        if !self.enabled {
+            return Cow::Borrowed(path);
+        }
+        let mut output = Cow::Borrowed(path);
+        for (re, replacement) in self.rules.iter() {
+            if let Cow::Owned(s) = re.replace_all(&output, replacement.as_str()) {
+                output = Cow::Owned(s);
+            }
+        }
+        if !self.builtin_rules {
+            return output;
+        }
+        Cow::Owned(
+            output
+                .split('/')
+                .map(Self::normalize_segment)
+                .collect::<Vec<_>>()
+                .join("/"),
+        )
+    }
+}
+
+impl PartialEq for HttpPathNormalizer {
+    fn eq(&self, other: &Self) -> bool {
+        self.enabled == other.enabled
+            && self.builtin_rules == other.builtin_rules
+            && self.rules.len() == other.rules.len()
+            && self
+                .rules
+                .iter()
+                .zip(other.rules.iter())
+                .all(|(a, b)| a.0.as_str() == b.0.as_str() && a.1 == b.1)
+    }
+}
+
+impl Eq for HttpPathNormalizer {}
+
+impl From<&HttpPathNormalization> for HttpPathNormalizer {
+    fn from(v: &HttpPathNormalization) -> Self {
+        let rules = v
+            .rules
+            .iter()
+            .filter_map(|r| match Regex::new(&r.pattern) {
+                Ok(re) => Some((re, r.replacement.clone())),
+                Err(e) => {
+                    warn!("invalid path normalization pattern {}: {}", r.pattern, e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            enabled: v.enabled,
+            builtin_rules: !v.builtin_rules_disabled,
+            rules,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct LogParserConfig {
     pub l7_log_collect_nps_threshold: u64,
@@ -584,7 +829,36 @@ pub struct LogParserConfig {
     pub l7_log_ignore_tap_sides: [bool; TapSide::MAX as usize + 1],
     pub http_endpoint_disabled: bool,
     pub http_endpoint_trie: HttpEndpointTrie,
+    pub http_path_normalizer: HttpPathNormalizer,
     pub obfuscate_enabled_protocols: L7ProtocolBitmap,
+    pub http_error_body_capture: HttpErrorBodyCapture,
+    pub graphql_endpoints: Vec<String>,
+    pub http_sse_tracking: HttpSseTracking,
+    pub http_identity_extraction: HttpIdentityExtraction,
+    pub http_header_extraction: HttpHeaderExtraction,
+    pub http_body_decompression: HttpBodyDecompression,
+    pub http_proxy_header_extraction: HttpProxyHeaderExtraction,
+    pub tls_certificate_expiry: TlsCertificateExpiry,
+    pub dns_answer_records: DnsAnswerRecords,
+    pub l7_log_scripts: L7LogScripts,
+    pub field_extraction_rules: Vec<FieldExtractionRule>,
+    pub l7_log_masking: L7LogMasking,
+    pub l7_log_field_limits: HashMap<String, HashMap<String, usize>>,
+    pub simple_protocols: Vec<SimpleProtocol>,
+    pub l7_log_deduplication: L7LogDeduplication,
+    pub rrt_histograms: HashMap<L7Protocol, RrtHistogram>,
+    // relative accuracy of rrt sketches in 1/10000, 0 means disabled
+    pub rrt_sketch_alpha: u16,
+    pub l7_endpoint_aggregation: L7EndpointAggregation,
+    // number of response codes counted in perf stats, 0 means disabled
+    pub response_code_top_k: usize,
+    // apdex thresholds in microseconds
+    pub apdex_thresholds: HashMap<L7Protocol, u64>,
+    pub l7_message_size_stats: L7MessageSizeStats,
+    pub l7_concurrency_stats: L7ConcurrencyStats,
+    pub otlp_exporter: OtlpExporter,
+    pub kafka_exporter: KafkaExporter,
+    pub clickhouse_exporter: ClickhouseExporter,
 }
 
 impl Default for LogParserConfig {
@@ -596,9 +870,136 @@ impl Default for LogParserConfig {
             l7_log_ignore_tap_sides: [false; TapSide::MAX as usize + 1],
             http_endpoint_disabled: false,
             http_endpoint_trie: HttpEndpointTrie::new(),
+            http_path_normalizer: HttpPathNormalizer::new(),
             obfuscate_enabled_protocols: L7ProtocolBitmap::default(),
+            http_error_body_capture: HttpErrorBodyCapture::default(),
+            graphql_endpoints: vec![],
+            http_sse_tracking: HttpSseTracking::default(),
+            http_identity_extraction: HttpIdentityExtraction::default(),
+            http_header_extraction: HttpHeaderExtraction::default(),
+            http_body_decompression: HttpBodyDecompression::default(),
+            http_proxy_header_extraction: HttpProxyHeaderExtraction::default(),
+            tls_certificate_expiry: TlsCertificateExpiry::default(),
+            dns_answer_records: DnsAnswerRecords::default(),
+            l7_log_scripts: L7LogScripts::default(),
+            field_extraction_rules: vec![],
+            l7_log_masking: L7LogMasking::default(),
+            l7_log_field_limits: HashMap::new(),
+            simple_protocols: vec![],
+            l7_log_deduplication: L7LogDeduplication::default(),
+            rrt_histograms: HashMap::new(),
+            rrt_sketch_alpha: 0,
+            l7_endpoint_aggregation: L7EndpointAggregation::default(),
+            response_code_top_k: 0,
+            apdex_thresholds: HashMap::new(),
+            l7_message_size_stats: L7MessageSizeStats::default(),
+            l7_concurrency_stats: L7ConcurrencyStats::default(),
+            otlp_exporter: OtlpExporter::default(),
+            kafka_exporter: KafkaExporter::default(),
+            clickhouse_exporter: ClickhouseExporter::default(),
+        }
+    }
+}
+
+fn compile_rrt_histograms(
+    config: &HashMap<String, RrtHistogramBuckets>,
+) -> HashMap<L7Protocol, RrtHistogram> {
+    let mut histograms = HashMap::new();
+    for (protocol_name, buckets) in config.iter() {
+        let protocol = L7Protocol::from(protocol_name.clone());
+        if protocol == L7Protocol::Unknown {
+            warn!(
+                "rrt histogram of unknown protocol {} ignored",
+                protocol_name
+            );
+            continue;
+        }
+        let start = buckets.start.as_micros();
+        if start == 0 || start > u32::MAX as u128 {
+            warn!(
+                "rrt histogram of protocol {} with invalid start {:?} ignored",
+                protocol_name, buckets.start
+            );
+            continue;
+        }
+        if buckets.factor < 2 {
+            warn!(
+                "rrt histogram of protocol {} with invalid factor {} ignored",
+                protocol_name, buckets.factor
+            );
+            continue;
+        }
+        if buckets.count == 0 || buckets.count >= RRT_HISTOGRAM_MAX_BUCKETS {
+            warn!(
+                "rrt histogram of protocol {} with invalid count {} ignored, should be in [1, {}]",
+                protocol_name,
+                buckets.count,
+                RRT_HISTOGRAM_MAX_BUCKETS - 1
+            );
+            continue;
+        }
+        histograms.insert(
+            protocol,
+            RrtHistogram::new(start as u32, buckets.factor, buckets.count as u8),
+        );
+    }
+    histograms
+}
+
+fn rrt_sketch_alpha(config: &L7RrtSketch) -> u16 {
+    if !config.enabled {
+        return 0;
+    }
+    let default_accuracy = L7RrtSketch::default().relative_accuracy;
+    let accuracy = if (1..=10).contains(&config.relative_accuracy) {
+        config.relative_accuracy
+    } else {
+        warn!(
+            "invalid rrt sketch relative accuracy {}%, use default {}%",
+            config.relative_accuracy, default_accuracy
+        );
+        default_accuracy
+    };
+    accuracy as u16 * 100
+}
+
+fn response_code_top_k(config: &L7ResponseCodeCounts) -> usize {
+    if !config.enabled {
+        return 0;
+    }
+    if config.top_k == 0 || config.top_k > RESPONSE_CODE_MAX_TOP_K {
+        let default_top_k = L7ResponseCodeCounts::default().top_k;
+        warn!(
+            "invalid response code top k {}, use default {}",
+            config.top_k, default_top_k
+        );
+        return default_top_k;
+    }
+    config.top_k
+}
+
+fn compile_apdex_thresholds(config: &HashMap<String, ApdexThreshold>) -> HashMap<L7Protocol, u64> {
+    let mut thresholds = HashMap::new();
+    for (protocol_name, threshold) in config.iter() {
+        let protocol = L7Protocol::from(protocol_name.clone());
+        if protocol == L7Protocol::Unknown {
+            warn!(
+                "apdex threshold of unknown protocol {} ignored",
+                protocol_name
+            );
+            continue;
+        }
+        let t = threshold.t.as_micros() as u64;
+        if t == 0 {
+            warn!(
+                "apdex threshold of protocol {} with invalid t {:?} ignored",
+                protocol_name, threshold.t
+            );
+            continue;
         }
+        thresholds.insert(protocol, t);
     }
+    thresholds
 }
 
 impl fmt::Debug for LogParserConfig {
@@ -867,6 +1268,9 @@ pub struct L7LogDynamicConfig {
 
     trace_set: HashSet<String>,
     span_set: HashSet<String>,
+
+    // lowercase name -> format
+    custom_trace_contexts: HashMap<String, TraceContextFormat>,
 }
 
 impl PartialEq for L7LogDynamicConfig {
@@ -875,6 +1279,7 @@ impl PartialEq for L7LogDynamicConfig {
             && self.x_request_id == other.x_request_id
             && self.trace_types == other.trace_types
             && self.span_types == other.span_types
+            && self.custom_trace_contexts == other.custom_trace_contexts
     }
 }
 
@@ -884,11 +1289,32 @@ impl L7LogDynamicConfig {
     pub fn new(
         mut proxy_client: String,
         x_request_id: Vec<String>,
-        trace_types: Vec<TraceType>,
-        span_types: Vec<TraceType>,
+        mut trace_types: Vec<TraceType>,
+        mut span_types: Vec<TraceType>,
+        custom_trace_contexts: Vec<TraceContextFormat>,
     ) -> Self {
         proxy_client.make_ascii_lowercase();
 
+        // custom trace contexts are carriers of trace id or span id in addition to the configured
+        let mut custom_trace_context_map = HashMap::new();
+        for c in custom_trace_contexts.into_iter() {
+            if c.name.is_empty() {
+                continue;
+            }
+            let name = c.name.to_ascii_lowercase();
+            if c.trace_id_index.is_some()
+                && !trace_types.iter().any(|t| t.to_checker_string() == name)
+            {
+                trace_types.push(TraceType::Customize(c.name.clone()));
+            }
+            if c.span_id_index.is_some()
+                && !span_types.iter().any(|t| t.to_checker_string() == name)
+            {
+                span_types.push(TraceType::Customize(c.name.clone()));
+            }
+            custom_trace_context_map.insert(name, c);
+        }
+
         let mut x_request_id_set = HashSet::new();
         for t in x_request_id.iter() {
             x_request_id_set.insert(t.trim().to_string());
@@ -911,7 +1337,15 @@ impl L7LogDynamicConfig {
             span_types,
             trace_set,
             span_set,
+            custom_trace_contexts: custom_trace_context_map,
+        }
+    }
+
+    pub fn custom_trace_context(&self, name: &str) -> Option<&TraceContextFormat> {
+        if self.custom_trace_contexts.is_empty() {
+            return None;
         }
+        self.custom_trace_contexts.get(&name.to_ascii_lowercase())
     }
 
     pub fn is_trace_id(&self, context: &str) -> bool {
@@ -1044,6 +1478,9 @@ impl TryFrom<(Config, RuntimeConfig)> for ModuleConfig {
                 af_packet_blocks: conf
                     .yaml_config
                     .get_af_packet_blocks(conf.tap_mode, conf.max_memory),
+                af_packet_max_blocks: conf
+                    .yaml_config
+                    .get_af_packet_max_blocks(conf.tap_mode, conf.max_memory),
                 #[cfg(any(target_os = "linux", target_os = "android"))]
                 af_packet_version: conf.capture_socket_type.into(),
                 tap_mode: conf.tap_mode,
@@ -1068,6 +1505,13 @@ impl TryFrom<(Config, RuntimeConfig)> for ModuleConfig {
                 server_tx_bandwidth_threshold: conf.server_tx_bandwidth_threshold,
                 bandwidth_probe_interval: conf.bandwidth_probe_interval,
                 collector_socket_type: conf.collector_socket_type,
+                zstd_level: match conf.yaml_config.sender_compression.algorithm {
+                    CompressionAlgorithm::Zstd if conf.collector_zstd_supported => {
+                        Some(conf.yaml_config.sender_compression.level)
+                    }
+                    _ => None,
+                },
+                sender_spool: conf.yaml_config.sender_spool.clone(),
                 standalone_data_file_size: conf.yaml_config.standalone_data_file_size,
                 standalone_data_file_dir: conf.yaml_config.standalone_data_file_dir.clone(),
                 enabled: conf.collector_enabled,
@@ -1113,6 +1557,11 @@ impl TryFrom<(Config, RuntimeConfig)> for ModuleConfig {
                 },
                 cloud_gateway_traffic: conf.yaml_config.cloud_gateway_traffic,
                 packet_delay: conf.yaml_config.packet_delay,
+                l4_log_aggr_interval: conf.yaml_config.l4_log_aggr_interval,
+                ipfix_exporter: conf.yaml_config.ipfix_exporter.clone(),
+                prometheus_remote_write: conf.yaml_config.prometheus_remote_write.clone(),
+                kafka_exporter: conf.yaml_config.kafka_exporter.clone(),
+                clickhouse_exporter: conf.yaml_config.clickhouse_exporter.clone(),
             },
             handler: HandlerConfig {
                 npb_dedup_enabled: conf.npb_dedup_enabled,
@@ -1172,6 +1621,9 @@ impl TryFrom<(Config, RuntimeConfig)> for ModuleConfig {
                 },
                 #[cfg(target_os = "windows")]
                 os_proc_scan_conf: OsProcScanConfig {},
+                conntrack_nat_enabled: conf.yaml_config.conntrack_nat_enabled,
+                conntrack_sync_interval: conf.yaml_config.conntrack_sync_interval,
+                prometheus_scrape_config: conf.yaml_config.prometheus_scrape_config.clone(),
                 prometheus_http_api_addresses: conf.prometheus_http_api_addresses.clone(),
             },
             flow: (&conf).into(),
@@ -1192,6 +1644,10 @@ impl TryFrom<(Config, RuntimeConfig)> for ModuleConfig {
                         .split(',')
                         .map(|item| TraceType::from(item))
                         .collect(),
+                    conf.yaml_config
+                        .l7_protocol_advanced_features
+                        .custom_trace_contexts
+                        .clone(),
                 ),
                 l7_log_ignore_tap_sides: {
                     let mut tap_sides = [false; TapSide::MAX as usize + 1];
@@ -1212,12 +1668,134 @@ impl TryFrom<(Config, RuntimeConfig)> for ModuleConfig {
                         .l7_protocol_advanced_features
                         .http_endpoint_extraction,
                 ),
+                http_path_normalizer: HttpPathNormalizer::from(
+                    &conf
+                        .yaml_config
+                        .l7_protocol_advanced_features
+                        .http_endpoint_extraction
+                        .normalization,
+                ),
                 obfuscate_enabled_protocols: L7ProtocolBitmap::from(
                     &conf
                         .yaml_config
                         .l7_protocol_advanced_features
                         .obfuscate_enabled_protocols,
                 ),
+                http_error_body_capture: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .http_error_body_capture
+                    .clone(),
+                graphql_endpoints: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .graphql_endpoints
+                    .clone(),
+                http_sse_tracking: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .http_sse_tracking
+                    .clone(),
+                http_identity_extraction: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .http_identity_extraction
+                    .clone(),
+                http_header_extraction: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .http_header_extraction
+                    .clone(),
+                http_body_decompression: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .http_body_decompression
+                    .clone(),
+                http_proxy_header_extraction: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .http_proxy_header_extraction
+                    .clone(),
+                tls_certificate_expiry: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .tls_certificate_expiry
+                    .clone(),
+                dns_answer_records: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .dns_answer_records
+                    .clone(),
+                l7_log_scripts: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .l7_log_scripts
+                    .clone(),
+                field_extraction_rules: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .field_extraction_rules
+                    .clone(),
+                l7_log_masking: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .l7_log_masking
+                    .clone(),
+                l7_log_field_limits: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .l7_log_field_limits
+                    .clone(),
+                simple_protocols: compile_simple_protocols(
+                    &conf
+                        .yaml_config
+                        .l7_protocol_advanced_features
+                        .simple_protocols,
+                ),
+                l7_log_deduplication: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .l7_log_deduplication
+                    .clone(),
+                rrt_histograms: compile_rrt_histograms(
+                    &conf
+                        .yaml_config
+                        .l7_protocol_advanced_features
+                        .l7_rrt_histograms,
+                ),
+                rrt_sketch_alpha: rrt_sketch_alpha(
+                    &conf.yaml_config.l7_protocol_advanced_features.l7_rrt_sketch,
+                ),
+                l7_endpoint_aggregation: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .l7_endpoint_aggregation
+                    .clone(),
+                response_code_top_k: response_code_top_k(
+                    &conf
+                        .yaml_config
+                        .l7_protocol_advanced_features
+                        .l7_response_code_counts,
+                ),
+                apdex_thresholds: compile_apdex_thresholds(
+                    &conf
+                        .yaml_config
+                        .l7_protocol_advanced_features
+                        .l7_apdex_thresholds,
+                ),
+                l7_message_size_stats: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .l7_message_size_stats
+                    .clone(),
+                l7_concurrency_stats: conf
+                    .yaml_config
+                    .l7_protocol_advanced_features
+                    .l7_concurrency_stats
+                    .clone(),
+                otlp_exporter: conf.yaml_config.otlp_exporter.clone(),
+                kafka_exporter: conf.yaml_config.kafka_exporter.clone(),
+                clickhouse_exporter: conf.yaml_config.clickhouse_exporter.clone(),
             },
             debug: DebugConfig {
                 vtap_id: conf.vtap_id as u16,
@@ -1779,6 +2357,13 @@ impl ConfigHandler {
                         candidate_config.tap_mode,
                         candidate_config.dispatcher.max_memory,
                     )
+                    || yaml_config.get_af_packet_max_blocks(
+                        new_config.tap_mode,
+                        new_config.dispatcher.max_memory,
+                    ) != yaml_config.get_af_packet_max_blocks(
+                        candidate_config.tap_mode,
+                        candidate_config.dispatcher.max_memory,
+                    )
                     || yaml_config.get_fast_path_map_size(new_config.dispatcher.max_memory)
                         != yaml_config
                             .get_fast_path_map_size(candidate_config.dispatcher.max_memory)
@@ -2590,6 +3175,16 @@ impl YamlConfig {
             (mem_size as usize / recv_engine::DEFAULT_BLOCK_SIZE / 16).min(128)
         }
     }
+
+    // Rings grow on kernel drops up to a quarter of max_memory in total, which is
+    // divided among dispatchers when they are built
+    fn get_af_packet_max_blocks(&self, tap_mode: TapMode, mem_size: u64) -> usize {
+        if !self.af_packet_blocks_adaptive {
+            return 0;
+        }
+        (mem_size as usize / recv_engine::DEFAULT_BLOCK_SIZE / 4)
+            .max(self.get_af_packet_blocks(tap_mode, mem_size))
+    }
 }
 
 #[cfg(test)]
@@ -2649,4 +3244,54 @@ mod tests {
         assert_eq!(trie.find_matching_rule("/a/b/c/d"), 3);
         assert_eq!(trie.find_matching_rule("/x/y/z"), 2);
     }
+
+    #[test]
+    fn test_path_normalizer() {
+        let mut config = HttpPathNormalization::default();
+        let normalizer = HttpPathNormalizer::from(&config);
+        assert_eq!(normalizer.normalize("/users/123"), "/users/123");
+
+        config.enabled = true;
+        let normalizer = HttpPathNormalizer::from(&config);
+        assert_eq!(
+            normalizer.normalize("/users/123/orders"),
+            "/users/{id}/orders"
+        );
+        assert_eq!(
+            normalizer.normalize("/files/3f2504e0-4f89-11d3-9a0c-0305e82c3301/"),
+            "/files/{uuid}/"
+        );
+        assert_eq!(
+            normalizer.normalize("/blobs/d41d8cd98f00b204e9800998ecf8427e"),
+            "/blobs/{hash}"
+        );
+        assert_eq!(
+            normalizer.normalize("/api/v2/deadbeefcafebabe"),
+            "/api/v2/deadbeefcafebabe"
+        );
+
+        config.rules = vec![
+            PathNormalizationRule {
+                pattern: "^/shop/[^/]+/".to_string(),
+                replacement: "/shop/{name}/".to_string(),
+            },
+            PathNormalizationRule {
+                pattern: "(".to_string(),
+                replacement: "".to_string(),
+            },
+        ];
+        let normalizer = HttpPathNormalizer::from(&config);
+        assert_eq!(normalizer.rules.len(), 1);
+        assert_eq!(
+            normalizer.normalize("/shop/abc/items/42"),
+            "/shop/{name}/items/{id}"
+        );
+
+        config.builtin_rules_disabled = true;
+        let normalizer = HttpPathNormalizer::from(&config);
+        assert_eq!(
+            normalizer.normalize("/shop/abc/items/42"),
+            "/shop/{name}/items/42"
+        );
+    }
 }
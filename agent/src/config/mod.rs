@@ -18,14 +18,23 @@ mod config;
 pub mod handler;
 
 pub use config::{
-    AgentIdType, Config, ConfigError, FlowGeneratorConfig, HttpEndpointExtraction,
-    KubernetesPollerType, KubernetesResourceConfig, MatchRule, OracleParseConfig, OsProcRegexp,
-    PcapConfig, PrometheusExtraConfig, RuntimeConfig, TripleMapConfig, UprobeProcRegExp,
-    XflowGeneratorConfig, YamlConfig, OS_PROC_REGEXP_MATCH_ACTION_ACCEPT,
-    OS_PROC_REGEXP_MATCH_ACTION_DROP, OS_PROC_REGEXP_MATCH_TYPE_CMD,
-    OS_PROC_REGEXP_MATCH_TYPE_PARENT_PROC_NAME, OS_PROC_REGEXP_MATCH_TYPE_PROC_NAME,
-    OS_PROC_REGEXP_MATCH_TYPE_TAG,
+    AgentIdType, ApdexThreshold, ClickhouseExporter, CompressionAlgorithm, Config, ConfigError,
+    DnsAnswerRecords, FieldExtractionRule, FlowExportProtocol, FlowGeneratorConfig,
+    HttpBodyDecompression, HttpEndpointExtraction, HttpErrorBodyCapture, HttpHeaderExtraction,
+    HttpHeaderRule, HttpIdentityExtraction, HttpIdentityRule, HttpPathNormalization,
+    HttpProxyHeaderExtraction, HttpSseTracking, IpfixExporter, KafkaExporter, KafkaMessageFormat,
+    KafkaPartitionKey, KubernetesPollerType, KubernetesResourceConfig, L7ConcurrencyStats,
+    L7EndpointAggregation, L7LogDeduplication, L7LogMasking, L7LogScripts, L7MessageSizeStats,
+    L7ProtocolHint, L7ResponseCodeCounts, L7RrtSketch, MatchRule, OracleParseConfig, OsProcRegexp,
+    OtlpExporter, PcapConfig, PrometheusExtraConfig, PrometheusRemoteWrite, PrometheusScrapeConfig,
+    ProtocolTimeout, RrtHistogramBuckets, RuntimeConfig, SenderCompression, SenderSpool,
+    SimpleProtocolDefinition, StatsdConfig, TlsCertificateExpiry, TraceContextFormat,
+    TripleMapConfig, UprobeProcRegExp, VlanTagMode, XflowGeneratorConfig, YamlConfig,
+    OS_PROC_REGEXP_MATCH_ACTION_ACCEPT, OS_PROC_REGEXP_MATCH_ACTION_DROP,
+    OS_PROC_REGEXP_MATCH_TYPE_CMD, OS_PROC_REGEXP_MATCH_TYPE_PARENT_PROC_NAME,
+    OS_PROC_REGEXP_MATCH_TYPE_PROC_NAME, OS_PROC_REGEXP_MATCH_TYPE_TAG,
 };
 pub use handler::{
-    DispatcherConfig, FlowAccess, FlowConfig, HttpEndpointTrie, ModuleConfig, NpbConfig,
+    DispatcherConfig, FlowAccess, FlowConfig, HttpEndpointTrie, HttpPathNormalizer, ModuleConfig,
+    NpbConfig,
 };
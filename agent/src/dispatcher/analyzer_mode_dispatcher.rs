@@ -37,6 +37,7 @@ use crate::{
     config::DispatcherConfig,
     dispatcher::{
         base_dispatcher::{BaseDispatcherListener, TapTypeHandler},
+        dedup::PacketDeduplicator,
         error::Result,
     },
     flow_generator::{flow_map::Config, FlowMap},
@@ -63,6 +64,7 @@ const BILD_FLAGS_OFFSET: usize = 6;
 const BILD_OVERLAY_OFFSET: usize = 7;
 
 const HANDLER_BATCH_SIZE: usize = 64;
+const ERSPAN_TIMESTAMP_MAX_OFFSET: Duration = Duration::from_secs(60);
 
 #[derive(Clone)]
 pub struct AnalyzerModeDispatcherListener {
@@ -257,8 +259,16 @@ impl AnalyzerModeDispatcher {
         let analyzer_dedup_disabled = base.analyzer_dedup_disabled;
         let vm_mac_addrs = self.vm_mac_addrs.clone();
         let mut dedup = PacketDedupMap::new();
+        let mut packet_deduplicator = base
+            .options
+            .lock()
+            .unwrap()
+            .packet_dedup
+            .as_ref()
+            .map(PacketDeduplicator::new);
         let id = base.id;
         let pool_raw_size = self.pool_raw_size;
+        let erspan_timestamp_enabled = base.options.lock().unwrap().erspan_timestamp_enabled;
 
         let npb_dedup_enabled = base.npb_dedup_enabled.clone();
         let flow_output_queue = base.flow_output_queue.clone();
@@ -333,6 +343,8 @@ impl AnalyzerModeDispatcher {
                                 }
                             };
 
+                            let erspan_timestamp = tunnel_info.erspan_timestamp;
+
                             if decap_length >= raw_length {
                                 counter.invalid_packets.fetch_add(1, Ordering::Relaxed);
                                 warn!(
@@ -351,10 +363,21 @@ impl AnalyzerModeDispatcher {
                                 0
                             };
                             let original_length = packet.raw.len() - decap_length;
-                            let timestamp = packet.timestamp;
+                            let timestamp = match erspan_timestamp {
+                                Some(t) if erspan_timestamp_enabled => {
+                                    Self::erspan_timestamp(t, packet.timestamp)
+                                }
+                                _ => packet.timestamp,
+                            };
 
                             let mut overlay_packet = packet.raw;
                             overlay_packet.truncate(decap_length..raw_length);
+                            if let Some(deduplicator) = packet_deduplicator.as_mut() {
+                                if deduplicator.duplicate(overlay_packet.as_ref(), timestamp) {
+                                    counter.duplicate_packets.fetch_add(1, Ordering::Relaxed);
+                                    continue;
+                                }
+                            }
                             // Only cloud traffic goes to de-duplication
                             if tap_type == TapType::Cloud
                                 && !analyzer_dedup_disabled
@@ -619,6 +642,21 @@ impl AnalyzerModeDispatcher {
         BaseDispatcher::decap_tunnel(packet, tap_type_handler, tunnel_info, bitmap)
     }
 
+    // Hardware timestamps far from the capture time are ignored, as the clock of the
+    // switch is not synchronized with the agent
+    fn erspan_timestamp(erspan_timestamp: Duration, timestamp: Duration) -> Duration {
+        let diff = if erspan_timestamp > timestamp {
+            erspan_timestamp - timestamp
+        } else {
+            timestamp - erspan_timestamp
+        };
+        if diff > ERSPAN_TIMESTAMP_MAX_OFFSET {
+            timestamp
+        } else {
+            erspan_timestamp
+        }
+    }
+
     pub(super) fn prepare_flow(
         meta_packet: &mut MetaPacket,
         tap_type: TapType,
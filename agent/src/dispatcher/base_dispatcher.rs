@@ -362,7 +362,12 @@ impl BaseDispatcher {
                 // vxlan-erspan：隧道信息为空
                 // erspan-vxlan；隧道信息为vxlan，隧道层数为1
                 // erspan-vxlan-erspan；隧道信息为空
-                *tunnel_info = Default::default();
+                // ERSPAN III header fields are kept for the packet
+                *tunnel_info = TunnelInfo {
+                    erspan_sgt: tunnel_info.erspan_sgt,
+                    erspan_timestamp: tunnel_info.erspan_timestamp,
+                    ..Default::default()
+                };
             }
             if decap_len + offset > packet.len() {
                 break;
@@ -388,10 +393,11 @@ impl BaseDispatcher {
         }
 
         let bpf_options = self.bpf_options.lock().unwrap();
-        if let Err(e) = self
-            .engine
-            .set_bpf(vec![], &CString::new(bpf_options.get_bpf_syntax()).unwrap())
-        {
+        let options = self.options.lock().unwrap();
+        if let Err(e) = self.engine.set_bpf(
+            vec![],
+            &CString::new(bpf_options.get_bpf_syntax(&options.capture_bpf)).unwrap(),
+        ) {
             warn!("set_bpf failed: {}", e);
         }
     }
@@ -485,7 +491,12 @@ impl BaseDispatcher {
                 // vxlan-erspan：隧道信息为空
                 // erspan-vxlan；隧道信息为vxlan，隧道层数为1
                 // erspan-vxlan-erspan；隧道信息为空
-                *tunnel_info = Default::default();
+                // ERSPAN III header fields are kept for the packet
+                *tunnel_info = TunnelInfo {
+                    erspan_sgt: tunnel_info.erspan_sgt,
+                    erspan_timestamp: tunnel_info.erspan_timestamp,
+                    ..Default::default()
+                };
             }
             decap_len += offset;
         }
@@ -513,13 +524,15 @@ impl BaseDispatcher {
         }
 
         let bpf_options = self.bpf_options.lock().unwrap();
+        let options = self.options.lock().unwrap();
         if let Err(e) = self.engine.set_bpf(
             bpf_options.get_bpf_instructions(
                 &tap_interfaces,
                 &self.tap_interface_whitelist,
-                self.options.lock().unwrap().snap_len,
+                options.snap_len,
+                &options.capture_bpf,
             ),
-            &CString::new(bpf_options.get_bpf_syntax()).unwrap(),
+            &CString::new(bpf_options.get_bpf_syntax(&options.capture_bpf)).unwrap(),
         ) {
             warn!(
                 "set_bpf failed with tap_interfaces count {}: {}",
@@ -544,7 +557,9 @@ impl TapTypeHandler {
         let mut eth_type = read_u16_be(&packet[FIELD_OFFSET_ETH_TYPE..]);
         let mut tap_type = self.default_tap_type;
         let mut l2_len = ETH_HEADER_SIZE;
-        if eth_type == EthernetType::DOT1Q && packet.len() >= ETH_HEADER_SIZE + VLAN_HEADER_SIZE {
+        if EthernetType::from(eth_type).is_vlan_tag()
+            && packet.len() >= ETH_HEADER_SIZE + VLAN_HEADER_SIZE
+        {
             let vlan_tag = read_u16_be(&packet[ETH_HEADER_SIZE..]);
             eth_type = read_u16_be(&packet[FIELD_OFFSET_ETH_TYPE + VLAN_HEADER_SIZE..]);
             // tap_type从qinq外层的vlan获取
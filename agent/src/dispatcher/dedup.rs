@@ -0,0 +1,165 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::time::Duration;
+
+use public::{
+    consts::{
+        ETH_HEADER_SIZE, FIELD_OFFSET_ETH_TYPE, IPV4_HEADER_SIZE, IPV6_HEADER_SIZE,
+        VLAN_HEADER_SIZE,
+    },
+    enums::{EthernetType, IpProtocol},
+};
+
+use crate::utils::bytes::read_u16_be;
+
+#[derive(Clone, Debug)]
+pub struct PacketDedupOptions {
+    // copies of a packet received within the window are dropped
+    pub window: Duration,
+    pub capacity: usize,
+}
+
+// Drops copies of mirrored packets, e.g. received from multiple SPAN sessions or from both
+// directions of a tap. Packets are identified by the fields kept unchanged along the path:
+// ip addresses, ip id and length, tcp seq/ack and l4 checksum, while ttl, ip checksum and
+// l2 headers are ignored.
+pub struct PacketDeduplicator {
+    window: Duration,
+    // direct mapped table of (packet hash, timestamp), a slot is overwritten by later packets
+    slots: Vec<(u64, Duration)>,
+}
+
+impl PacketDeduplicator {
+    pub fn new(options: &PacketDedupOptions) -> Self {
+        Self {
+            window: options.window,
+            slots: vec![(0, Duration::ZERO); options.capacity.max(1).next_power_of_two()],
+        }
+    }
+
+    pub fn duplicate(&mut self, packet: &[u8], timestamp: Duration) -> bool {
+        let Some(hash) = Self::packet_hash(packet) else {
+            return false;
+        };
+        let index = hash as usize & (self.slots.len() - 1);
+        let (last_hash, last_timestamp) = self.slots[index];
+        let interval = if timestamp > last_timestamp {
+            timestamp - last_timestamp
+        } else {
+            last_timestamp - timestamp
+        };
+        if last_hash == hash && interval <= self.window {
+            return true;
+        }
+        self.slots[index] = (hash, timestamp);
+        false
+    }
+
+    fn packet_hash(packet: &[u8]) -> Option<u64> {
+        let mut l3_offset = ETH_HEADER_SIZE;
+        let mut eth_type = EthernetType::from(read_u16_be(
+            packet.get(FIELD_OFFSET_ETH_TYPE..FIELD_OFFSET_ETH_TYPE + 2)?,
+        ));
+        while eth_type.is_vlan_tag() {
+            l3_offset += VLAN_HEADER_SIZE;
+            eth_type = EthernetType::from(read_u16_be(packet.get(l3_offset - 2..l3_offset)?));
+        }
+        let l3_packet = packet.get(l3_offset..)?;
+
+        let mut hasher = DefaultHasher::new();
+        let (protocol, l4_packet) = match eth_type {
+            EthernetType::IPV4 if l3_packet.len() >= IPV4_HEADER_SIZE => {
+                // total length, id, flags and fragment offset
+                hasher.write(&l3_packet[2..8]);
+                // protocol and addresses without ttl and checksum
+                hasher.write(&l3_packet[9..10]);
+                hasher.write(&l3_packet[12..20]);
+                let ihl = ((l3_packet[0] & 0xf) as usize) << 2;
+                (l3_packet[9], l3_packet.get(ihl..).unwrap_or_default())
+            }
+            EthernetType::IPV6 if l3_packet.len() >= IPV6_HEADER_SIZE => {
+                // flow label, payload length, next header and addresses without hop limit
+                hasher.write(&l3_packet[1..7]);
+                hasher.write(&l3_packet[8..IPV6_HEADER_SIZE]);
+                (l3_packet[6], &l3_packet[IPV6_HEADER_SIZE..])
+            }
+            _ => return None,
+        };
+        match IpProtocol::from(protocol) {
+            // seq, ack and checksum
+            IpProtocol::TCP if l4_packet.len() >= 18 => {
+                hasher.write(&l4_packet[4..12]);
+                hasher.write(&l4_packet[16..18]);
+            }
+            // checksum
+            IpProtocol::UDP if l4_packet.len() >= 8 => hasher.write(&l4_packet[6..8]),
+            _ => (),
+        }
+        Some(hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tcp_packet(ttl: u8, vlan: Option<u16>) -> Vec<u8> {
+        let mut packet = vec![0u8; 12];
+        if let Some(vlan) = vlan {
+            packet.extend_from_slice(&0x8100u16.to_be_bytes());
+            packet.extend_from_slice(&vlan.to_be_bytes());
+        }
+        packet.extend_from_slice(&0x0800u16.to_be_bytes());
+        // ipv4, total length 40, id 0x1234
+        packet.extend_from_slice(&[0x45, 0, 0, 40, 0x12, 0x34, 0x40, 0, ttl, 6]);
+        // header checksum changes with ttl
+        packet.extend_from_slice(&[ttl, ttl, 10, 0, 0, 1, 10, 0, 0, 2]);
+        // tcp, seq 1000, ack 2000, checksum 0xabcd
+        packet.extend_from_slice(&[0x30, 0x39, 0, 80]);
+        packet.extend_from_slice(&1000u32.to_be_bytes());
+        packet.extend_from_slice(&2000u32.to_be_bytes());
+        packet.extend_from_slice(&[0x50, 0x18, 0xff, 0xff, 0xab, 0xcd, 0, 0]);
+        packet
+    }
+
+    #[test]
+    fn dedup_mirrored_copies() {
+        let mut dedup = PacketDeduplicator::new(&PacketDedupOptions {
+            window: Duration::from_millis(10),
+            capacity: 1024,
+        });
+        let t = Duration::from_secs(1);
+        assert!(!dedup.duplicate(&tcp_packet(64, None), t));
+        // copy from another span session, one hop later with a different vlan
+        assert!(dedup.duplicate(&tcp_packet(63, Some(100)), t + Duration::from_millis(1)));
+        // retransmission out of the window
+        assert!(!dedup.duplicate(&tcp_packet(64, None), t + Duration::from_millis(20)));
+
+        let mut other = tcp_packet(64, None);
+        // different seq
+        other[14 + 20 + 7] = 1;
+        assert!(!dedup.duplicate(&other, t + Duration::from_millis(21)));
+
+        // non-ip packets are never duplicate
+        let mut arp = tcp_packet(64, None);
+        arp[12..14].copy_from_slice(&0x0806u16.to_be_bytes());
+        assert!(!dedup.duplicate(&arp, t));
+        assert!(!dedup.duplicate(&arp, t));
+    }
+}
@@ -45,6 +45,7 @@ use crate::{
     config::DispatcherConfig,
     dispatcher::{
         base_dispatcher::{BaseDispatcher, BaseDispatcherListener},
+        dedup::PacketDeduplicator,
         error::{Error, Result},
         PacketCounter,
     },
@@ -204,6 +205,7 @@ pub(super) struct LastTimestamps {
 pub(super) struct MirrorModeDispatcher {
     pub(super) base: BaseDispatcher,
     pub(super) dedup: PacketDedupMap,
+    pub(super) packet_deduplicator: Option<PacketDeduplicator>,
     pub(super) local_vm_mac_set: Arc<Mutex<HashMap<u32, bool>>>,
     pub(super) local_segment_macs: Vec<MacAddr>,
     pub(super) tap_bridge_macs: Vec<MacAddr>,
@@ -331,6 +333,7 @@ impl MirrorModeDispatcher {
         overlay_packet: &[u8],
         timestamp: Duration,
         original_length: usize,
+        sampling_rate: u32,
         updated: &Arc<AtomicBool>,
         pipelines: &mut HashMap<u32, MirrorPipeline>,
         handler_builder: &Arc<Mutex<Vec<PacketHandlerBuilder>>>,
@@ -353,6 +356,7 @@ impl MirrorModeDispatcher {
             dst_local,
             original_length,
         )?;
+        meta_packet.sampling_rate = sampling_rate;
 
         Self::prepare_flow(
             &mut meta_packet,
@@ -390,6 +394,9 @@ impl MirrorModeDispatcher {
             &self.base.stats,
             false, // !from_ebpf
         );
+        if self.base.engine.preserves_timestamps() {
+            flow_map.follow_packet_clock();
+        }
 
         while !self.base.terminated.load(Ordering::Relaxed) {
             let config = Config {
@@ -469,6 +476,16 @@ impl MirrorModeDispatcher {
             let original_length = packet.data.len() - decap_length;
             let overlay_packet = &mut packet.data[decap_length..decap_length + original_length];
 
+            if let Some(deduplicator) = self.packet_deduplicator.as_mut() {
+                if deduplicator.duplicate(overlay_packet, timestamp) {
+                    self.base
+                        .counter
+                        .duplicate_packets
+                        .fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            }
+
             // Only virtual network traffic goes to remove duplicates
             #[cfg(any(target_os = "linux", target_os = "android"))]
             if self.dedup.duplicate(overlay_packet, timestamp) {
@@ -491,6 +508,7 @@ impl MirrorModeDispatcher {
                     overlay_packet,
                     timestamp,
                     original_length,
+                    packet.sampling_rate,
                     &self.updated,
                     &mut self.pipelines,
                     &self.base.handler_builder,
@@ -514,6 +532,7 @@ impl MirrorModeDispatcher {
                     overlay_packet,
                     timestamp,
                     original_length,
+                    packet.sampling_rate,
                     &self.updated,
                     &mut self.pipelines,
                     &self.base.handler_builder,
@@ -535,6 +554,7 @@ impl MirrorModeDispatcher {
                     overlay_packet,
                     timestamp,
                     original_length,
+                    packet.sampling_rate,
                     &self.updated,
                     &mut self.pipelines,
                     &self.base.handler_builder,
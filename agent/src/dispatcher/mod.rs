@@ -20,9 +20,12 @@ pub(crate) mod recv_engine;
 mod base_dispatcher;
 
 mod analyzer_mode_dispatcher;
+mod dedup;
 mod local_mode_dispatcher;
 mod mirror_mode_dispatcher;
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::ffi::CString;
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 use std::{
@@ -42,15 +45,21 @@ use special_recv_engine::Libpcap;
 
 use analyzer_mode_dispatcher::{AnalyzerModeDispatcher, AnalyzerModeDispatcherListener}; // Enterprise Edition Feature: analyzer_mode
 use base_dispatcher::{BaseDispatcher, TapTypeHandler};
+pub use dedup::PacketDedupOptions;
+use dedup::PacketDeduplicator;
 use error::{Error, Result};
 use local_mode_dispatcher::{LocalModeDispatcher, LocalModeDispatcherListener};
 use mirror_mode_dispatcher::{MirrorModeDispatcher, MirrorModeDispatcherListener};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use recv_engine::pcap_replay::PcapReplayOptions;
 pub use recv_engine::RecvEngine;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use recv_engine::{
     af_packet::{self, bpf::*, BpfSyntax, OptTpacketVersion, RawInstruction, Tpacket},
     DEFAULT_BLOCK_SIZE, FRAME_SIZE_MAX, FRAME_SIZE_MIN, POLL_TIMEOUT,
 };
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use recv_engine::{pcap_replay::PcapReplayer, pf_ring::PfRing, sflow::SflowReceiver};
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use self::base_dispatcher::TapInterfaceWhitelist;
@@ -312,8 +321,12 @@ impl DispatcherListener {
 #[derive(Default)]
 pub struct DpdkRingPortConf {
     pub enabled: bool,
+    // dedicated core of the dispatcher, 0 means disabled
     pub core_id: u32,
-    pub port_name: String,
+    // rings of the primary process consumed by this dispatcher
+    pub ring_names: Vec<String>,
+    // --file-prefix of the primary process
+    pub file_prefix: String,
 }
 
 pub struct BpfOptions {
@@ -416,8 +429,18 @@ impl BpfOptions {
         return bpf_syntax;
     }
 
+    // Filters of the dispatcher are applied together with the global capture bpf
+    fn combined_capture_bpf(&self, dispatcher_bpf: &str) -> String {
+        match (self.capture_bpf.is_empty(), dispatcher_bpf.is_empty()) {
+            (_, true) => self.capture_bpf.clone(),
+            (true, false) => dispatcher_bpf.to_string(),
+            (false, false) => format!("({}) and ({})", self.capture_bpf, dispatcher_bpf),
+        }
+    }
+
     #[cfg(any(target_os = "linux", target_os = "android"))]
-    fn to_pcap_bpf_prog(&self) -> Option<pcap_sys::bpf_program> {
+    fn to_pcap_bpf_prog(capture_bpf: &str) -> Option<pcap_sys::bpf_program> {
+        let capture_bpf = CString::new(capture_bpf).ok()?;
         let mut prog = pcap_sys::bpf_program {
             bf_len: 0,
             bf_insns: std::ptr::null_mut(),
@@ -428,7 +451,7 @@ impl BpfOptions {
                 0xffff as libc::c_int,
                 1,
                 &mut prog,
-                self.capture_bpf.as_ptr() as *const i8,
+                capture_bpf.as_ptr() as *const i8,
                 1,
                 0xffffffff,
             );
@@ -437,7 +460,7 @@ impl BpfOptions {
                 0xffff as libc::c_int,
                 1,
                 &mut prog,
-                self.capture_bpf.as_ptr() as *const u8,
+                capture_bpf.as_ptr() as *const u8,
                 1,
                 0xffffffff,
             );
@@ -455,11 +478,13 @@ impl BpfOptions {
         tap_interfaces: &Vec<Link>,
         white_list: &TapInterfaceWhitelist,
         snap_len: usize,
+        dispatcher_bpf: &str,
     ) -> Vec<RawInstruction> {
         let mut syntaxs = vec![];
         debug!("Capture bpf set to:");
-        if self.capture_bpf.len() != 0 {
-            let prog = self.to_pcap_bpf_prog();
+        let capture_bpf = self.combined_capture_bpf(dispatcher_bpf);
+        if capture_bpf.len() != 0 {
+            let prog = Self::to_pcap_bpf_prog(&capture_bpf);
             if !prog.is_none() && prog.unwrap().bf_len > 0 {
                 let prog = prog.unwrap();
                 unsafe {
@@ -493,20 +518,20 @@ impl BpfOptions {
                         } else {
                             error!(
                                 "Capture customized bpf({}) error, use default only.",
-                                self.capture_bpf
+                                capture_bpf
                             );
                         }
                     } else {
                         error!(
                             "Capture customized bpf({}) error, use default only.",
-                            self.capture_bpf
+                            capture_bpf
                         );
                     }
                 }
             } else {
                 error!(
                     "Capture customized bpf({}) error, use default only.",
-                    self.capture_bpf
+                    capture_bpf
                 );
             }
         }
@@ -519,9 +544,10 @@ impl BpfOptions {
         return syntaxs;
     }
 
-    pub fn get_bpf_syntax(&self) -> String {
-        if self.capture_bpf.len() > 0 {
-            let syntax = format!("({}) and ({})", self.capture_bpf, self.bpf_syntax_str);
+    pub fn get_bpf_syntax(&self, dispatcher_bpf: &str) -> String {
+        let capture_bpf = self.combined_capture_bpf(dispatcher_bpf);
+        if capture_bpf.len() > 0 {
+            let syntax = format!("({}) and ({})", capture_bpf, self.bpf_syntax_str);
             debug!("Capture bpf set to: {}", syntax);
             return syntax;
         }
@@ -533,18 +559,37 @@ impl BpfOptions {
 #[derive(Default)]
 pub struct Options {
     pub packet_blocks: usize,
+    // share of the dispatcher in the total, 0 means the number of blocks is fixed
+    pub packet_max_blocks: usize,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub af_packet_version: OptTpacketVersion,
     pub snap_len: usize,
     pub tap_mode: TapMode,
     pub dpdk_conf: DpdkRingPortConf,
     pub libpcap_enabled: bool,
+    // use hardware timestamps of ERSPAN III as packet timestamps in analyzer mode
+    pub erspan_timestamp_enabled: bool,
+    // use rx timestamps of the NIC hardware clock as packet timestamps with af_packet
+    pub hardware_timestamp_enabled: bool,
     pub tap_mac_script: String,
     pub is_ipv6: bool,
     pub vxlan_flags: u8,
     pub npb_port: u16,
     pub controller_port: u16,
     pub controller_tls_port: u16,
+    // udp ports receiving sflow datagrams in mirror mode, empty means disabled
+    pub sflow_ports: Vec<u16>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub pf_ring_enabled: bool,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub pf_ring_zc_enabled: bool,
+    // pcap files replayed in mirror mode instead of capturing, None means disabled
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub pcap_replay: Option<PcapReplayOptions>,
+    // capture filter of this dispatcher in pcap-filter syntax, empty means disabled
+    pub capture_bpf: String,
+    // drops copies of mirrored packets in analyzer and mirror mode, None means disabled
+    pub packet_dedup: Option<PacketDedupOptions>,
 }
 
 pub struct Pipeline {
@@ -564,6 +609,7 @@ struct PacketCounter {
 
     invalid_packets: AtomicU64,
     get_token_failed: AtomicU64,
+    duplicate_packets: AtomicU64,
 
     retired: AtomicU64,
     kernel_counter: Arc<dyn stats::RefCountable>,
@@ -582,6 +628,7 @@ impl PacketCounter {
 
             invalid_packets: AtomicU64::new(0),
             get_token_failed: AtomicU64::new(0),
+            duplicate_packets: AtomicU64::new(0),
 
             retired: AtomicU64::new(0),
             kernel_counter,
@@ -632,6 +679,11 @@ impl stats::RefCountable for PacketCounter {
                 stats::CounterType::Counted,
                 stats::CounterValue::Unsigned(get_token_failed),
             ),
+            (
+                "duplicate_packets",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.duplicate_packets.swap(0, Ordering::Relaxed)),
+            ),
             (
                 "retired",
                 stats::CounterType::Counted,
@@ -1010,6 +1062,13 @@ impl DispatcherBuilder {
             TapMode::Mirror => DispatcherFlavor::Mirror(MirrorModeDispatcher {
                 base,
                 dedup: PacketDedupMap::new(),
+                packet_deduplicator: base
+                    .options
+                    .lock()
+                    .unwrap()
+                    .packet_dedup
+                    .as_ref()
+                    .map(PacketDeduplicator::new),
                 local_vm_mac_set: Arc::new(Mutex::new(HashMap::new())),
                 local_segment_macs: vec![],
                 tap_bridge_macs: vec![],
@@ -1100,6 +1159,37 @@ impl DispatcherBuilder {
     ) -> Result<RecvEngine> {
         let options = options.lock().unwrap();
         match tap_mode {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            TapMode::Mirror if options.pcap_replay.is_some() => {
+                let replay_options = options.pcap_replay.as_ref().unwrap();
+                info!(
+                    "Pcap replayer init with files {:?} speed {} preserve timestamps {}",
+                    replay_options.files, replay_options.speed, replay_options.preserve_timestamps
+                );
+                Ok(RecvEngine::PcapReplay(PcapReplayer::new(replay_options)))
+            }
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            TapMode::Mirror if !options.sflow_ports.is_empty() => {
+                info!("Sflow receiver init with ports {:?}", options.sflow_ports);
+                Ok(RecvEngine::Sflow(SflowReceiver::new(&options.sflow_ports)?))
+            }
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            TapMode::Mirror | TapMode::Local if options.pf_ring_enabled => {
+                let src_ifaces = pcap_interfaces
+                    .iter()
+                    .flatten()
+                    .map(|src_iface| (src_iface.name.as_str(), src_iface.if_index as isize))
+                    .collect();
+                info!(
+                    "PF_RING init with: {:?} {} zc: {}",
+                    &src_ifaces, options.snap_len, options.pf_ring_zc_enabled
+                );
+                Ok(RecvEngine::PfRing(PfRing::new(
+                    src_ifaces,
+                    options.snap_len,
+                    options.pf_ring_zc_enabled,
+                )?))
+            }
             TapMode::Mirror | TapMode::Local if options.libpcap_enabled => {
                 if pcap_interfaces.is_none() || pcap_interfaces.as_ref().unwrap().is_empty() {
                     return Err(error::Error::Libpcap(
@@ -1141,7 +1231,15 @@ impl DispatcherBuilder {
                 ));
                 #[cfg(not(target_arch = "s390x"))]
                 {
-                    Ok(RecvEngine::Dpdk())
+                    info!(
+                        "DPDK init with rings {:?} file prefix {} core {}",
+                        options.dpdk_conf.ring_names,
+                        options.dpdk_conf.file_prefix,
+                        options.dpdk_conf.core_id
+                    );
+                    Ok(RecvEngine::Dpdk(recv_engine::dpdk::Dpdk::new(
+                        &options.dpdk_conf,
+                    )?))
                 }
             }
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -1154,9 +1252,11 @@ impl DispatcherBuilder {
                     },
                     block_size: DEFAULT_BLOCK_SIZE as u32,
                     num_blocks: options.packet_blocks as u32,
+                    max_num_blocks: options.packet_max_blocks as u32,
                     poll_timeout: POLL_TIMEOUT.as_nanos() as isize,
                     version: options.af_packet_version,
                     iface: src_interface.as_ref().unwrap_or(&"".to_string()).clone(),
+                    hardware_timestamp: options.hardware_timestamp_enabled,
                     ..Default::default()
                 };
                 info!("Afpacket init with {:?}", afp);
@@ -22,6 +22,8 @@ use libc::{c_uint, sockaddr_ll};
 const TP_STATUS_KERNEL: u32 = 0;
 const TPACKET_ALIGNMENT: usize = 0x10;
 pub const TP_STATUS_USER: isize = 1;
+// the packet is timestamped by the NIC hardware clock
+const TP_STATUS_TS_RAW_HARDWARE: u32 = 1 << 31;
 
 fn to_align(n: usize) -> usize {
     return (n + TPACKET_ALIGNMENT - 1) & !(TPACKET_ALIGNMENT - 1);
@@ -31,6 +33,7 @@ pub trait Header {
     fn get_status(&self) -> isize;
     fn clear_status(&mut self);
     fn get_time(&self) -> Duration;
+    fn is_hardware_timestamp(&self) -> bool;
     fn get_data(&self) -> &mut [u8];
     fn get_length(&self) -> isize;
     fn get_iface_index(&self) -> isize;
@@ -186,6 +189,10 @@ impl Header for *mut Tpacket2Hdr {
         }
     }
 
+    fn is_hardware_timestamp(&self) -> bool {
+        unsafe { (*(*self)).tp_status & TP_STATUS_TS_RAW_HARDWARE != 0 }
+    }
+
     fn get_data(&self) -> &mut [u8] {
         unsafe {
             let ptr =
@@ -279,6 +286,10 @@ impl Header for V3Wrapper {
         }
     }
 
+    fn is_hardware_timestamp(&self) -> bool {
+        unsafe { (*self.v3_header).tp_status & TP_STATUS_TS_RAW_HARDWARE != 0 }
+    }
+
     fn get_data(&self) -> &mut [u8] {
         unsafe {
             let ptr = self.v3_header as *const u8 as usize;
@@ -358,6 +369,11 @@ mod tests {
             (*v2).tp_snaplen = 60;
 
             assert_eq!(v2.get_data(), [10; 60]);
+
+            (*v2).tp_status = TP_STATUS_USER as u32;
+            assert!(!v2.is_hardware_timestamp());
+            (*v2).tp_status |= TP_STATUS_TS_RAW_HARDWARE;
+            assert!(v2.is_hardware_timestamp());
         }
 
         assert_ne!(raw, [10; 1000])
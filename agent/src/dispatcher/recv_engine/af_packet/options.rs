@@ -74,12 +74,16 @@ pub struct Options {
     pub frame_size: u32,
     pub block_size: u32,
     pub num_blocks: u32,
+    // upper limit of num_blocks when the ring grows on kernel drops, 0 means fixed
+    pub max_num_blocks: u32,
     pub add_vlan_header: bool,
     pub block_timeout: u32,
     pub poll_timeout: isize,
     pub version: OptTpacketVersion,
     pub socket_type: OptSocketType,
     pub iface: String,
+    // use rx timestamps of the NIC hardware clock when supported
+    pub hardware_timestamp: bool,
 }
 
 impl Default for Options {
@@ -88,12 +92,14 @@ impl Default for Options {
             frame_size: 4096,
             block_size: 4096 * 128,
             num_blocks: 128,
+            max_num_blocks: 0,
             add_vlan_header: false,
             block_timeout: 64 * 1000000,
             poll_timeout: -1 * 1000000,
             version: OptTpacketVersion::TpacketVersionHighestavailablet,
             socket_type: OptSocketType::SocketTypeRaw,
             iface: "".to_string(),
+            hardware_timestamp: false,
         }
     }
 }
@@ -114,6 +120,11 @@ impl Options {
         if self.num_blocks < 1 {
             return Err(Error::InvalidOption("num blocks must be >=1."));
         }
+        if self.max_num_blocks != 0 && self.max_num_blocks < self.num_blocks {
+            return Err(Error::InvalidOption(
+                "max num blocks must be >= num blocks.",
+            ));
+        }
         if self.block_timeout < 1000000 {
             return Err(Error::InvalidOption("block time must be >=1,000,000"));
         }
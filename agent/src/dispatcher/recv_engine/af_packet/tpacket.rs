@@ -19,14 +19,18 @@ use std::io;
 use std::mem;
 use std::net::Shutdown;
 use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::{
+    atomic::{AtomicU32, AtomicU64, Ordering},
+    Arc,
+};
 
 use libc::{
-    c_int, c_uint, c_void, getsockopt, mmap, munmap, off_t, poll, pollfd, setsockopt, size_t,
-    sockaddr, sockaddr_ll, socket, socklen_t, write, AF_PACKET, ETH_P_ALL, MAP_LOCKED,
+    c_int, c_uint, c_void, getsockopt, ioctl, mmap, munmap, off_t, poll, pollfd, setsockopt,
+    size_t, sockaddr, sockaddr_ll, socket, socklen_t, write, AF_PACKET, ETH_P_ALL, MAP_LOCKED,
     MAP_NORESERVE, MAP_SHARED, POLLERR, POLLIN, PROT_READ, PROT_WRITE, SOL_PACKET, SOL_SOCKET,
     SO_ATTACH_FILTER,
 };
-use log::warn;
+use log::{error, info, warn};
 use public::error::*;
 use public::packet::Packet;
 use socket2::Socket;
@@ -39,7 +43,22 @@ use public::utils::net::{self, link_by_name};
 const PACKET_VERSION: c_int = 10;
 const PACKET_RX_RING: c_int = 5;
 const PACKET_STATISTICS: c_int = 6;
+const PACKET_TIMESTAMP: c_int = 17;
+// linux/net_tstamp.h
+const SOF_TIMESTAMPING_RAW_HARDWARE: c_int = 1 << 6;
+const HWTSTAMP_TX_OFF: c_int = 0;
+const HWTSTAMP_FILTER_NONE: c_int = 0;
+const HWTSTAMP_FILTER_ALL: c_int = 1;
+#[cfg(target_env = "gnu")]
+const SIOCSHWTSTAMP: u64 = 0x89b0;
+#[cfg(any(target_env = "musl", target_env = ""))]
+const SIOCSHWTSTAMP: i32 = 0x89b0;
+const IFNAMSIZ: usize = 16;
 const MILLI_SECONDS: u32 = 1000000;
+// ring grows when the ratio of kernel drops in a stats interval exceeds this
+const RING_GROW_DROP_RATIO: f64 = 0.001;
+// grown ring halves after this many stats intervals without kernel drops
+const RING_SHRINK_QUIET_INTERVALS: u32 = 60;
 
 // https://www.ietf.org/archive/id/draft-gharris-opsawg-pcap-01.html
 const LINKTYPE_ETHERNET: c_int = 1;
@@ -66,8 +85,37 @@ pub struct TpacketStatsV3 {
     pub tp_freeze_q_cnt: c_uint,
 }
 
+#[repr(C)]
+struct HwtstampConfig {
+    flags: c_int,
+    tx_type: c_int,
+    rx_filter: c_int,
+}
+
+#[repr(C)]
+struct IfReq {
+    ifr_name: [u8; IFNAMSIZ],
+    ifr_data: *mut c_void,
+    // struct ifreq is padded to 40 bytes by the union
+    _padding: [u8; 16],
+}
+
+// Shared between Tpacket and TpacketCounter, the counter requests a larger ring on
+// kernel drops and a smaller one when drops stop, the ring is resized by the next read
+#[derive(Debug)]
+struct RingSize {
+    blocks: AtomicU32,
+    target_blocks: AtomicU32,
+    min_blocks: u32,
+    max_blocks: u32,
+    // stats intervals without kernel drops
+    quiet_intervals: AtomicU32,
+}
+
 pub struct Tpacket {
     _stats: Stats,
+    ring_size: Arc<RingSize>,
+    hardware_timestamp_packets: Arc<AtomicU64>,
 
     raw_socket: Socket,
     ring: *mut u8,
@@ -161,6 +209,43 @@ impl Tpacket {
         }
     }
 
+    // Enables hardware timestamping of all received packets on the NIC, see
+    // https://www.kernel.org/doc/Documentation/networking/timestamping.txt
+    fn enable_nic_timestamp(&self) -> af_packet::Result<()> {
+        let mut config = HwtstampConfig {
+            flags: 0,
+            tx_type: HWTSTAMP_TX_OFF,
+            rx_filter: HWTSTAMP_FILTER_ALL,
+        };
+        let mut ifr = IfReq {
+            ifr_name: [0; IFNAMSIZ],
+            ifr_data: &mut config as *mut HwtstampConfig as *mut c_void,
+            _padding: [0; 16],
+        };
+        let name = self.opts.iface.as_bytes();
+        let len = name.len().min(IFNAMSIZ - 1);
+        ifr.ifr_name[..len].copy_from_slice(&name[..len]);
+        unsafe {
+            if ioctl(self.raw_socket.as_raw_fd(), SIOCSHWTSTAMP, &mut ifr) == -1 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        // the driver may downgrade the filter to what it supports
+        if config.rx_filter == HWTSTAMP_FILTER_NONE {
+            return Err(io::Error::from(io::ErrorKind::Unsupported).into());
+        }
+        Ok(())
+    }
+
+    // Packets not timestamped by the NIC keep software timestamps
+    fn set_hardware_timestamp(&self) -> af_packet::Result<()> {
+        // timestamping of all NICs is left as configured when capturing on any interface
+        if self.opts.iface != "" {
+            self.enable_nic_timestamp()?;
+        }
+        self.setsockopt(SOL_PACKET, PACKET_TIMESTAMP, SOF_TIMESTAMPING_RAW_HARDWARE)
+    }
+
     fn set_version_internal(&mut self, tp_version: options::OptTpacketVersion) -> bool {
         // 设置af packet版本
         self.setsockopt(SOL_PACKET, PACKET_VERSION, tp_version as c_int)
@@ -294,6 +379,59 @@ impl Tpacket {
         return false;
     }
 
+    fn resize_ring(&mut self, num_blocks: u32) -> af_packet::Result<()> {
+        unsafe {
+            munmap(
+                self.ring as *mut c_void,
+                (self.opts.block_size * self.opts.num_blocks) as size_t,
+            );
+        }
+        self.ring = std::ptr::null_mut();
+        self.current = None;
+        self.header_next_needed = false;
+        self.offset = 0;
+
+        // the kernel refuses a new ring before the old one is freed with an empty request
+        if self.tp_version == options::OptTpacketVersion::TpacketVersion2 {
+            let req = header::TpacketReq {
+                tp_block_size: 0,
+                tp_block_nr: 0,
+                tp_frame_size: 0,
+                tp_frame_nr: 0,
+            };
+            self.setsockopt(SOL_PACKET, PACKET_RX_RING, req)?;
+        } else {
+            let req = header::TpacketReq3 {
+                tp_block_size: 0,
+                tp_block_nr: 0,
+                tp_frame_size: 0,
+                tp_frame_nr: 0,
+                tp_retire_blk_tov: 0,
+                tp_sizeof_priv: 0,
+                tp_feature_req_word: 0,
+            };
+            self.setsockopt(SOL_PACKET, PACKET_RX_RING, req)?;
+        }
+        let old_blocks = self.opts.num_blocks;
+        self.opts.num_blocks = num_blocks;
+        if let Err(e) = self.set_ring().and_then(|_| self.mmap_ring()) {
+            warn!(
+                "Afpacket resize ring to {} blocks failed: {}, restore {} blocks",
+                num_blocks, e, old_blocks
+            );
+            self.opts.num_blocks = old_blocks;
+            self.set_ring()?;
+            self.mmap_ring()?;
+        }
+        self.ring_size
+            .blocks
+            .store(self.opts.num_blocks, Ordering::Relaxed);
+        self.ring_size
+            .target_blocks
+            .store(self.opts.num_blocks, Ordering::Relaxed);
+        Ok(())
+    }
+
     // The data referenced in the packet points to Shared memory. The life cycle
     // of the packet cannot exceed the next call to the read function.
     pub unsafe fn read(&mut self) -> Option<Packet> {
@@ -308,6 +446,22 @@ impl Tpacket {
                 self.offset += 1;
                 self.should_release_packet = false;
             }
+            let target_blocks = self.ring_size.target_blocks.load(Ordering::Relaxed);
+            if target_blocks != self.opts.num_blocks {
+                info!(
+                    "Afpacket resize ring from {} to {} blocks",
+                    self.opts.num_blocks, target_blocks
+                );
+                if let Err(e) = self.resize_ring(target_blocks) {
+                    error!("Afpacket resize ring failed: {}", e);
+                    self.ring_size
+                        .target_blocks
+                        .store(self.opts.num_blocks, Ordering::Relaxed);
+                }
+            }
+            if self.ring.is_null() {
+                return None;
+            }
             self.current = Some(self.get_packet_header());
             let ok = self.poll_for_first_packet();
             if !ok {
@@ -322,6 +476,10 @@ impl Tpacket {
             }
         }
         if let Some(x) = self.current.as_ref() {
+            if self.opts.hardware_timestamp && x.is_hardware_timestamp() {
+                self.hardware_timestamp_packets
+                    .fetch_add(1, Ordering::Relaxed);
+            }
             let packet = Packet {
                 timestamp: x.get_time(),
                 if_index: x.get_iface_index(),
@@ -354,6 +512,8 @@ impl Tpacket {
         TpacketCounter {
             tp_version: self.tp_version,
             fd: self.raw_socket.as_raw_fd(),
+            ring_size: self.ring_size.clone(),
+            hardware_timestamp_packets: self.hardware_timestamp_packets.clone(),
         }
     }
 
@@ -376,6 +536,14 @@ impl Tpacket {
                 packets: 0,
                 polls: 0,
             },
+            ring_size: Arc::new(RingSize {
+                blocks: AtomicU32::new(opts.num_blocks),
+                target_blocks: AtomicU32::new(opts.num_blocks),
+                min_blocks: opts.num_blocks,
+                max_blocks: opts.max_num_blocks,
+                quiet_intervals: AtomicU32::new(0),
+            }),
+            hardware_timestamp_packets: Arc::new(AtomicU64::new(0)),
             raw_socket,
             ring: std::ptr::null_mut(),
             opts: opts.clone(),
@@ -390,6 +558,15 @@ impl Tpacket {
         tpacket.set_version()?;
         tpacket.set_ring()?;
         tpacket.mmap_ring()?;
+        if opts.hardware_timestamp {
+            match tpacket.set_hardware_timestamp() {
+                Ok(_) => info!("Afpacket hardware timestamp enabled on {:?}", opts.iface),
+                Err(e) => warn!(
+                    "Afpacket hardware timestamp not supported on {:?}, use software timestamp: {}",
+                    opts.iface, e
+                ),
+            }
+        }
         tpacket.set_bpf(vec![bpf::BpfSyntax::RetConstant(bpf::RetConstant {
             val: 0,
         })
@@ -416,6 +593,82 @@ impl Drop for Tpacket {
 pub struct TpacketCounter {
     tp_version: options::OptTpacketVersion,
     fd: i32,
+    ring_size: Arc<RingSize>,
+    hardware_timestamp_packets: Arc<AtomicU64>,
+}
+
+impl TpacketCounter {
+    // Returns the number of blocks the ring should grow to, or None if it should be kept
+    fn grown_blocks(blocks: u32, max_blocks: u32, packets: u32, drops: u32) -> Option<u32> {
+        if blocks >= max_blocks || packets == 0 {
+            return None;
+        }
+        if (drops as f64 / packets as f64) < RING_GROW_DROP_RATIO {
+            return None;
+        }
+        Some((blocks * 2).min(max_blocks))
+    }
+
+    // Returns the number of blocks the grown ring should shrink to, or None if it should be kept
+    fn shrunk_blocks(blocks: u32, min_blocks: u32, quiet_intervals: u32) -> Option<u32> {
+        if blocks <= min_blocks || quiet_intervals < RING_SHRINK_QUIET_INTERVALS {
+            return None;
+        }
+        Some((blocks / 2).max(min_blocks))
+    }
+
+    // kernel_packets includes kernel_drops
+    fn ring_counters(&self, packets: u32, drops: u32) -> Vec<stats::Counter> {
+        let blocks = self.ring_size.blocks.load(Ordering::Relaxed);
+        let drop_ratio = if packets > 0 {
+            drops as f64 / packets as f64
+        } else {
+            0.0
+        };
+        if drops > 0 {
+            warn!(
+                "Afpacket kernel dropped {} of {} packets ({:.2}%) with {} ring blocks",
+                drops,
+                packets,
+                drop_ratio * 100.0,
+                blocks
+            );
+        }
+        let ring_size = &self.ring_size;
+        if drops > 0 {
+            ring_size.quiet_intervals.store(0, Ordering::Relaxed);
+            if let Some(target) = Self::grown_blocks(blocks, ring_size.max_blocks, packets, drops) {
+                ring_size.target_blocks.store(target, Ordering::Relaxed);
+            }
+        } else {
+            let quiet_intervals = ring_size.quiet_intervals.fetch_add(1, Ordering::Relaxed) + 1;
+            if let Some(target) = Self::shrunk_blocks(blocks, ring_size.min_blocks, quiet_intervals)
+            {
+                ring_size.quiet_intervals.store(0, Ordering::Relaxed);
+                ring_size.target_blocks.store(target, Ordering::Relaxed);
+            }
+        }
+        vec![
+            (
+                "kernel_drop_ratio",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Float(drop_ratio),
+            ),
+            (
+                "ring_blocks",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(blocks as u64),
+            ),
+            // packets timestamped by the NIC clock instead of the kernel
+            (
+                "hardware_timestamp_packets",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(
+                    self.hardware_timestamp_packets.swap(0, Ordering::Relaxed),
+                ),
+            ),
+        ]
+    }
 }
 
 impl stats::RefCountable for TpacketCounter {
@@ -440,7 +693,7 @@ impl stats::RefCountable for TpacketCounter {
                     return vec![];
                 }
             }
-            vec![
+            let mut counters = vec![
                 (
                     "kernel_packets",
                     stats::CounterType::Counted,
@@ -456,7 +709,9 @@ impl stats::RefCountable for TpacketCounter {
                     stats::CounterType::Counted,
                     stats::CounterValue::Unsigned(stats_v3.tp_freeze_q_cnt as u64),
                 ),
-            ]
+            ];
+            counters.extend(self.ring_counters(stats_v3.tp_packets, stats_v3.tp_drops));
+            counters
         } else if self.tp_version == options::OptTpacketVersion::TpacketVersion2 {
             let mut stats = TpacketStats {
                 tp_packets: 0,
@@ -476,7 +731,7 @@ impl stats::RefCountable for TpacketCounter {
                     return vec![];
                 }
             }
-            vec![
+            let mut counters = vec![
                 (
                     "kernel_packets",
                     stats::CounterType::Counted,
@@ -487,10 +742,51 @@ impl stats::RefCountable for TpacketCounter {
                     stats::CounterType::Counted,
                     stats::CounterValue::Unsigned(stats.tp_drops as u64),
                 ),
-            ]
+            ];
+            counters.extend(self.ring_counters(stats.tp_packets, stats.tp_drops));
+            counters
         } else {
             warn!("invalid tp version");
             vec![]
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_grows_on_drops() {
+        // fixed ring
+        assert_eq!(TpacketCounter::grown_blocks(16, 0, 1000, 100), None);
+        // no drops or too few drops
+        assert_eq!(TpacketCounter::grown_blocks(16, 64, 1000, 0), None);
+        assert_eq!(TpacketCounter::grown_blocks(16, 64, 100000, 10), None);
+        assert_eq!(TpacketCounter::grown_blocks(16, 64, 0, 0), None);
+
+        assert_eq!(TpacketCounter::grown_blocks(16, 64, 1000, 100), Some(32));
+        assert_eq!(TpacketCounter::grown_blocks(48, 64, 1000, 100), Some(64));
+        assert_eq!(TpacketCounter::grown_blocks(64, 64, 1000, 100), None);
+    }
+
+    #[test]
+    fn ring_shrinks_when_quiet() {
+        // not grown
+        assert_eq!(TpacketCounter::shrunk_blocks(16, 16, 1000), None);
+        // drops stopped recently
+        assert_eq!(
+            TpacketCounter::shrunk_blocks(64, 16, RING_SHRINK_QUIET_INTERVALS - 1),
+            None
+        );
+
+        assert_eq!(
+            TpacketCounter::shrunk_blocks(64, 16, RING_SHRINK_QUIET_INTERVALS),
+            Some(32)
+        );
+        assert_eq!(
+            TpacketCounter::shrunk_blocks(24, 16, RING_SHRINK_QUIET_INTERVALS),
+            Some(16)
+        );
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+/*
+ * Ring and mbuf functions of DPDK used by the DPDK recv engine of deepflow-agent.
+ *
+ * Most of them are inline functions in DPDK headers which can not be loaded with
+ * dlsym, so they are wrapped in this library. It must be built against the same
+ * DPDK version as the primary process, and is loaded by the agent at runtime.
+ */
+
+#include <errno.h>
+#include <stdint.h>
+
+#include <rte_eal.h>
+#include <rte_errno.h>
+#include <rte_mbuf.h>
+#include <rte_ring.h>
+
+/* Returns 0 on success, or a negative errno */
+int deepflow_dpdk_init(int argc, char **argv)
+{
+	if (rte_eal_init(argc, argv) < 0)
+		return -rte_errno;
+	if (rte_eal_process_type() != RTE_PROC_SECONDARY) {
+		rte_eal_cleanup();
+		return -EINVAL;
+	}
+	return 0;
+}
+
+void *deepflow_dpdk_ring_lookup(const char *name)
+{
+	return rte_ring_lookup(name);
+}
+
+unsigned int deepflow_dpdk_dequeue(void *ring, void **mbufs, unsigned int n)
+{
+	return rte_ring_dequeue_burst((struct rte_ring *)ring, mbufs, n, NULL);
+}
+
+unsigned int deepflow_dpdk_ring_count(void *ring)
+{
+	return rte_ring_count((struct rte_ring *)ring);
+}
+
+/* Only the first segment of chained mbufs is captured */
+void deepflow_dpdk_mbuf(void *m, uint8_t ** data, uint32_t * data_len,
+			uint32_t * pkt_len)
+{
+	struct rte_mbuf *mbuf = (struct rte_mbuf *)m;
+
+	*data = rte_pktmbuf_mtod(mbuf, uint8_t *);
+	*data_len = rte_pktmbuf_data_len(mbuf);
+	*pkt_len = rte_pktmbuf_pkt_len(mbuf);
+}
+
+void deepflow_dpdk_free(void **mbufs, unsigned int n)
+{
+	unsigned int i;
+
+	for (i = 0; i < n; i++)
+		rte_pktmbuf_free((struct rte_mbuf *)mbufs[i]);
+}
@@ -0,0 +1,348 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// The agent runs as a DPDK secondary process of the primary owning the NIC, and
+// consumes mbufs the primary enqueues into shared rings. Ring and mbuf functions are
+// inline in DPDK headers, so they are wrapped by libdeepflow_dpdk.so (deepflow_dpdk.c
+// in this directory), which is loaded at runtime so that agents built without DPDK
+// still start when ovs-dpdk-enable is false.
+
+use std::ffi::{CStr, CString};
+use std::hint;
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, OnceLock,
+};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use libc::{c_char, c_int, c_uint, c_void};
+use log::{info, warn};
+use public::error::{Error, Result};
+use public::packet::Packet;
+
+use super::POLL_TIMEOUT;
+
+use crate::dispatcher::DpdkRingPortConf;
+use crate::utils::stats;
+
+const LIBRARY_NAME: &str = "libdeepflow_dpdk.so";
+
+const BURST_SIZE: usize = 32;
+// rings are polled, sleep between polls when they are empty unless the core is dedicated
+const IDLE_SLEEP: Duration = Duration::from_micros(100);
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+type InitFn = unsafe extern "C" fn(c_int, *mut *mut c_char) -> c_int;
+type LookupFn = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type DequeueFn = unsafe extern "C" fn(*mut c_void, *mut *mut c_void, c_uint) -> c_uint;
+type CountFn = unsafe extern "C" fn(*mut c_void) -> c_uint;
+type MbufFn = unsafe extern "C" fn(*mut c_void, *mut *mut u8, *mut u32, *mut u32);
+type FreeFn = unsafe extern "C" fn(*mut *mut c_void, c_uint);
+
+struct DpdkLib {
+    ring_lookup: LookupFn,
+    dequeue: DequeueFn,
+    ring_count: CountFn,
+    mbuf: MbufFn,
+    free: FreeFn,
+}
+
+// Functions of the library are thread safe, and the library is never unloaded
+unsafe impl Send for DpdkLib {}
+unsafe impl Sync for DpdkLib {}
+
+// EAL can be initialized only once in a process, so the library is shared by
+// dispatchers and kept until exit
+static DPDK_LIB: OnceLock<std::result::Result<DpdkLib, String>> = OnceLock::new();
+
+impl DpdkLib {
+    fn get(conf: &DpdkRingPortConf) -> Result<&'static Self> {
+        DPDK_LIB
+            .get_or_init(|| unsafe { Self::load(conf) })
+            .as_ref()
+            .map_err(|e| Error::DpdkError(e.clone()))
+    }
+
+    unsafe fn load(conf: &DpdkRingPortConf) -> std::result::Result<Self, String> {
+        let name = CString::new(LIBRARY_NAME).unwrap();
+        let handle = libc::dlopen(name.as_ptr(), libc::RTLD_LOCAL | libc::RTLD_NOW);
+        if handle.is_null() {
+            return Err(Self::dl_error());
+        }
+        let get_func = |sym: &str| {
+            let func_sym = CString::new(sym).unwrap();
+            let func = libc::dlsym(handle, func_sym.as_ptr());
+            if func.is_null() {
+                Err(format!("{}: {}", sym, Self::dl_error()))
+            } else {
+                Ok(func)
+            }
+        };
+        let funcs = (|| {
+            Ok::<_, String>((
+                get_func("deepflow_dpdk_init")?,
+                get_func("deepflow_dpdk_ring_lookup")?,
+                get_func("deepflow_dpdk_dequeue")?,
+                get_func("deepflow_dpdk_ring_count")?,
+                get_func("deepflow_dpdk_mbuf")?,
+                get_func("deepflow_dpdk_free")?,
+            ))
+        })();
+        let (init, ring_lookup, dequeue, ring_count, mbuf, free) = match funcs {
+            Ok(funcs) => funcs,
+            Err(e) => {
+                libc::dlclose(handle);
+                return Err(e);
+            }
+        };
+        let lib = Self {
+            ring_lookup: mem::transmute::<*mut c_void, LookupFn>(ring_lookup),
+            dequeue: mem::transmute::<*mut c_void, DequeueFn>(dequeue),
+            ring_count: mem::transmute::<*mut c_void, CountFn>(ring_count),
+            mbuf: mem::transmute::<*mut c_void, MbufFn>(mbuf),
+            free: mem::transmute::<*mut c_void, FreeFn>(free),
+        };
+        Self::eal_init(mem::transmute::<*mut c_void, InitFn>(init), conf)?;
+        Ok(lib)
+    }
+
+    unsafe fn eal_init(init: InitFn, conf: &DpdkRingPortConf) -> std::result::Result<(), String> {
+        let mut args = vec![
+            "deepflow-agent".to_owned(),
+            "--proc-type=secondary".to_owned(),
+            format!("--file-prefix={}", conf.file_prefix),
+        ];
+        if conf.core_id > 0 {
+            args.push(format!("-l{}", conf.core_id));
+        }
+        info!("DPDK EAL init with {:?}", args);
+        let args = args
+            .into_iter()
+            .map(|a| CString::new(a).unwrap())
+            .collect::<Vec<_>>();
+        let mut argv = args
+            .iter()
+            .map(|a| a.as_ptr() as *mut c_char)
+            .collect::<Vec<_>>();
+        // EAL binds the calling thread to the main lcore, which is not a dispatcher thread
+        let mut cpu_set: libc::cpu_set_t = mem::zeroed();
+        let saved = libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut cpu_set);
+        let ret = init(argv.len() as c_int, argv.as_mut_ptr());
+        if saved == 0 {
+            libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &cpu_set);
+        }
+        if ret < 0 {
+            return Err(format!(
+                "EAL init as secondary process failed: {}",
+                std::io::Error::from_raw_os_error(-ret)
+            ));
+        }
+        Ok(())
+    }
+
+    unsafe fn dl_error() -> String {
+        let err = libc::dlerror();
+        if err.is_null() {
+            format!("{} not found", LIBRARY_NAME)
+        } else {
+            CStr::from_ptr(err).to_string_lossy().into_owned()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DpdkCounter {
+    packets: AtomicU64,
+    // mbufs pending in the rings
+    ring_entries: AtomicU64,
+}
+
+impl stats::RefCountable for DpdkCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "kernel_packets",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.packets.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "ring_entries",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(self.ring_entries.load(Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+pub struct Dpdk {
+    lib: &'static DpdkLib,
+    rings: Vec<*mut c_void>,
+    core_id: u32,
+    pinned: bool,
+    next: usize,
+    // mbufs dequeued from rings, mbufs before burst_next are read
+    burst: [*mut c_void; BURST_SIZE],
+    burst_len: usize,
+    burst_next: usize,
+    last_stats: Instant,
+    counter: Arc<DpdkCounter>,
+}
+
+// Rings are only used by the dispatcher thread owning the recv engine
+unsafe impl Send for Dpdk {}
+
+impl Dpdk {
+    pub fn new(conf: &DpdkRingPortConf) -> Result<Self> {
+        if conf.ring_names.is_empty() {
+            return Err(Error::DpdkError(
+                "dpdk capture must give ring to capture packet".to_owned(),
+            ));
+        }
+        let lib = DpdkLib::get(conf)?;
+        let mut rings = Vec::with_capacity(conf.ring_names.len());
+        for name in conf.ring_names.iter() {
+            let c_name = CString::new(name.as_str()).unwrap();
+            let ring = unsafe { (lib.ring_lookup)(c_name.as_ptr()) };
+            if ring.is_null() {
+                return Err(Error::DpdkError(format!(
+                    "ring {} not found in the primary process",
+                    name
+                )));
+            }
+            info!("DPDK attached to ring {}", name);
+            rings.push(ring);
+        }
+        Ok(Self {
+            lib,
+            rings,
+            core_id: conf.core_id,
+            pinned: false,
+            next: 0,
+            burst: [ptr::null_mut(); BURST_SIZE],
+            burst_len: 0,
+            burst_next: 0,
+            last_stats: Instant::now(),
+            counter: Default::default(),
+        })
+    }
+
+    // the dispatcher thread runs on the dedicated core
+    fn pin(&mut self) {
+        self.pinned = true;
+        if self.core_id == 0 {
+            return;
+        }
+        unsafe {
+            let mut cpu_set: libc::cpu_set_t = mem::zeroed();
+            libc::CPU_SET(self.core_id as usize, &mut cpu_set);
+            if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &cpu_set) != 0 {
+                warn!(
+                    "bind dispatcher to core {} failed: {}",
+                    self.core_id,
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+    }
+
+    fn update_stats(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.last_stats) < STATS_INTERVAL {
+            return;
+        }
+        self.last_stats = now;
+        let entries = self
+            .rings
+            .iter()
+            .map(|r| unsafe { (self.lib.ring_count)(*r) } as u64)
+            .sum();
+        self.counter.ring_entries.store(entries, Ordering::Relaxed);
+    }
+
+    fn free_burst(&mut self) {
+        if self.burst_len > 0 {
+            unsafe { (self.lib.free)(self.burst.as_mut_ptr(), self.burst_len as c_uint) };
+        }
+        self.burst_len = 0;
+        self.burst_next = 0;
+    }
+
+    // The data referenced in the packet is in the mbuf and kept until the burst
+    // is read through, which happens at a later call to the read function.
+    pub fn read(&mut self) -> Result<Packet> {
+        if !self.pinned {
+            self.pin();
+        }
+        self.update_stats();
+        let start = Instant::now();
+        while self.burst_next >= self.burst_len {
+            self.free_burst();
+            // round robin between rings
+            for _ in 0..self.rings.len() {
+                let i = self.next;
+                self.next = (self.next + 1) % self.rings.len();
+                let n = unsafe {
+                    (self.lib.dequeue)(self.rings[i], self.burst.as_mut_ptr(), BURST_SIZE as c_uint)
+                };
+                if n > 0 {
+                    self.burst_len = n as usize;
+                    self.counter.packets.fetch_add(n as u64, Ordering::Relaxed);
+                    break;
+                }
+            }
+            if self.burst_len > 0 {
+                break;
+            }
+            if start.elapsed() >= POLL_TIMEOUT {
+                return Err(Error::Timeout);
+            }
+            if self.core_id > 0 {
+                hint::spin_loop();
+            } else {
+                thread::sleep(IDLE_SLEEP);
+            }
+        }
+
+        let mbuf = self.burst[self.burst_next];
+        self.burst_next += 1;
+        let (mut data, mut data_len, mut pkt_len) = (ptr::null_mut(), 0, 0);
+        unsafe { (self.lib.mbuf)(mbuf, &mut data, &mut data_len, &mut pkt_len) };
+        Ok(Packet {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            // mbufs from rings have no interface
+            if_index: 0,
+            capture_length: pkt_len as isize,
+            data: unsafe { slice::from_raw_parts_mut(data, data_len as usize) },
+            ..Default::default()
+        })
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<DpdkCounter> {
+        self.counter.clone()
+    }
+}
+
+impl Drop for Dpdk {
+    fn drop(&mut self) {
+        self.free_burst();
+    }
+}
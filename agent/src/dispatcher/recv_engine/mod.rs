@@ -16,6 +16,14 @@
 
 pub mod af_packet;
 pub(crate) mod bpf;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod dpdk;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod pcap_replay;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod pf_ring;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod sflow;
 
 use std::ffi::CStr;
 use std::sync::{atomic::AtomicU64, Arc};
@@ -23,8 +31,16 @@ use std::time::Duration;
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use af_packet::{options::Options, tpacket::Tpacket};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use dpdk::Dpdk;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use pcap_replay::PcapReplayer;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use pf_ring::PfRing;
 pub use public::error::{Error, Result};
 use public::packet;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use sflow::SflowReceiver;
 
 use crate::utils::stats;
 
@@ -38,8 +54,15 @@ pub const POLL_TIMEOUT: Duration = Duration::from_millis(100);
 pub enum RecvEngine {
     #[cfg(any(target_os = "linux", target_os = "android"))]
     AfPacket(Tpacket),
-    Dpdk(),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    Dpdk(Dpdk),
     Libpcap(Option<Libpcap>),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    Sflow(SflowReceiver),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    PfRing(PfRing),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    PcapReplay(PcapReplayer),
 }
 
 impl RecvEngine {
@@ -49,8 +72,15 @@ impl RecvEngine {
         match self {
             #[cfg(any(target_os = "linux", target_os = "android"))]
             Self::AfPacket(_) => Ok(()),
-            Self::Dpdk() => todo!(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Dpdk(_) => Ok(()),
             Self::Libpcap(_) => Ok(()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Sflow(_) => Ok(()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PfRing(_) => Ok(()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(_) => Ok(()),
         }
     }
 
@@ -70,11 +100,18 @@ impl RecvEngine {
                 Some(p) => Ok(p),
                 None => Err(Error::Timeout),
             },
-            Self::Dpdk() => todo!(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Dpdk(e) => e.read(),
             Self::Libpcap(w) => w
                 .as_mut()
                 .ok_or(Error::LibpcapError(Self::LIBPCAP_NONE.to_string()))
                 .and_then(|e| e.read()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Sflow(e) => e.read(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PfRing(e) => e.read(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(e) => e.read(),
         }
     }
 
@@ -87,7 +124,26 @@ impl RecvEngine {
                 .as_mut()
                 .ok_or(Error::LibpcapError(Self::LIBPCAP_NONE.to_string()))
                 .and_then(|e| e.set_bpf(syntax.to_str().unwrap())),
-            Self::Dpdk() => todo!(),
+            // mbufs enqueued by the primary process are filtered by itself
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Dpdk(_) => Ok(()),
+            // sampled headers are not filtered
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Sflow(_) => Ok(()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PfRing(e) => e.set_bpf(syntax.to_str().unwrap()),
+            // replayed packets are not filtered either
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(_) => Ok(()),
+        }
+    }
+
+    // packets keep timestamps of the capture, which do not follow the system time
+    pub fn preserves_timestamps(&self) -> bool {
+        match self {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(e) => e.preserves_timestamps(),
+            _ => false,
         }
     }
 
@@ -95,11 +151,18 @@ impl RecvEngine {
         match self {
             #[cfg(any(target_os = "linux", target_os = "android"))]
             Self::AfPacket(e) => Arc::new(e.get_counter_handle()),
-            Self::Dpdk() => todo!(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Dpdk(e) => e.get_counter_handle(),
             Self::Libpcap(w) => match w {
                 Some(w) => w.get_counter_handle(),
                 None => Arc::new(LibpcapCounter::default()),
             },
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Sflow(e) => e.get_counter_handle(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PfRing(e) => e.get_counter_handle(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::PcapReplay(e) => e.get_counter_handle(),
         }
     }
 }
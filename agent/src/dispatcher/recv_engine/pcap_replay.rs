@@ -0,0 +1,253 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use public::error::{Error, Result};
+use public::packet::Packet;
+
+use super::POLL_TIMEOUT;
+
+use crate::utils::stats;
+
+#[derive(Clone, Debug, Default)]
+pub struct PcapReplayOptions {
+    pub files: Vec<PathBuf>,
+    // replay speed relative to the capture, 0 means as fast as possible
+    pub speed: u32,
+    // timestamp packets with the capture time instead of the replay time
+    pub preserve_timestamps: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct PcapReplayCounter {
+    files: AtomicU64,
+    invalid_files: AtomicU64,
+    packets: AtomicU64,
+}
+
+impl stats::RefCountable for PcapReplayCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "pcap_replay_files",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.files.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "pcap_replay_invalid_files",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.invalid_files.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "pcap_replay_packets",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.packets.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+// Reads packets of pcap files one by one. Packets are timestamped with the time they are
+// replayed, as flows are generated in a time window following the system time, intervals
+// between packets in the capture are kept when speed is not 0. With preserve_timestamps,
+// packets keep the capture time and the flow map follows packet timestamps instead.
+pub struct PcapReplayer {
+    files: VecDeque<PathBuf>,
+    speed: u32,
+    preserve_timestamps: bool,
+    capture: Option<pcap::Capture<pcap::Offline>>,
+    // (timestamp of the first packet in the capture, time when it is replayed)
+    base_time: Option<(Duration, Duration)>,
+    // packet read from the capture but not due yet
+    pending: Option<(Duration, Vec<u8>, u32)>,
+    current: Vec<u8>,
+    counter: Arc<PcapReplayCounter>,
+}
+
+impl PcapReplayer {
+    pub fn new(options: &PcapReplayOptions) -> Self {
+        Self {
+            files: options.files.iter().cloned().collect(),
+            speed: options.speed,
+            preserve_timestamps: options.preserve_timestamps,
+            capture: None,
+            base_time: None,
+            pending: None,
+            current: vec![],
+            counter: Default::default(),
+        }
+    }
+
+    // Returns (timestamp in capture, data, original length) of the next packet
+    fn next_packet(&mut self) -> Option<(Duration, Vec<u8>, u32)> {
+        loop {
+            if self.capture.is_none() {
+                let path = self.files.pop_front()?;
+                match pcap::Capture::from_file(&path) {
+                    Ok(capture) => {
+                        info!("pcap replay start reading {}", path.display());
+                        self.counter.files.fetch_add(1, Ordering::Relaxed);
+                        self.capture = Some(capture);
+                        self.base_time = None;
+                    }
+                    Err(e) => {
+                        warn!("pcap replay open {} failed: {}", path.display(), e);
+                        self.counter.invalid_files.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                }
+            }
+
+            let next = self.capture.as_mut().unwrap().next().map(|p| {
+                (
+                    Duration::new(p.header.ts.tv_sec as u64, p.header.ts.tv_usec as u32 * 1000),
+                    p.data.to_vec(),
+                    p.header.len,
+                )
+            });
+            match next {
+                Ok(packet) => return Some(packet),
+                Err(pcap::Error::NoMorePackets) => info!("pcap replay finished a file"),
+                Err(e) => warn!("pcap replay read failed: {}", e),
+            }
+            self.capture = None;
+        }
+    }
+
+    pub fn read(&mut self) -> Result<Packet> {
+        if self.pending.is_none() {
+            self.pending = self.next_packet();
+        }
+        let Some((capture_time, _, _)) = self.pending.as_ref() else {
+            // all files are replayed
+            thread::sleep(POLL_TIMEOUT);
+            return Err(Error::Timeout);
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let (first_capture_time, replay_start) =
+            *self.base_time.get_or_insert((*capture_time, now));
+        let due = if self.speed == 0 {
+            now
+        } else {
+            replay_start + capture_time.saturating_sub(first_capture_time) / self.speed
+        };
+        // wait for the packet in short sleeps, so that the dispatcher can be stopped in time
+        if due > now {
+            thread::sleep((due - now).min(POLL_TIMEOUT));
+            if due - now > POLL_TIMEOUT {
+                return Err(Error::Timeout);
+            }
+        }
+
+        let (capture_time, data, length) = self.pending.take().unwrap();
+        let timestamp = if self.preserve_timestamps {
+            capture_time
+        } else {
+            due
+        };
+        self.current = data;
+        self.counter.packets.fetch_add(1, Ordering::Relaxed);
+        Ok(Packet {
+            timestamp,
+            capture_length: length as isize,
+            data: &mut self.current,
+            ..Default::default()
+        })
+    }
+
+    pub fn preserves_timestamps(&self) -> bool {
+        self.preserve_timestamps
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<PcapReplayCounter> {
+        self.counter.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_files() {
+        let files = vec![
+            "resources/test/flow_generator/syn-1.pcap".into(),
+            "resources/test/flow_generator/not-exist.pcap".into(),
+            "resources/test/flow_generator/server-reset.pcap".into(),
+        ];
+        let mut replayer = PcapReplayer::new(&PcapReplayOptions {
+            files,
+            speed: 0,
+            ..Default::default()
+        });
+        let mut count = 0;
+        loop {
+            match replayer.read() {
+                Ok(packet) => {
+                    assert!(!packet.data.is_empty());
+                    count += 1;
+                }
+                Err(Error::Timeout) => break,
+                Err(e) => panic!("{}", e),
+            }
+        }
+        let expected = ["syn-1.pcap", "server-reset.pcap"]
+            .iter()
+            .map(|f| {
+                let mut capture =
+                    pcap::Capture::from_file(format!("resources/test/flow_generator/{}", f))
+                        .unwrap();
+                let mut n = 0;
+                while capture.next().is_ok() {
+                    n += 1;
+                }
+                n
+            })
+            .sum::<usize>();
+        assert_eq!(count, expected);
+        let counter = replayer.get_counter_handle();
+        assert_eq!(counter.files.load(Ordering::Relaxed), 2);
+        assert_eq!(counter.invalid_files.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn preserve_timestamps() {
+        let file = "resources/test/flow_generator/syn-1.pcap";
+        let mut replayer = PcapReplayer::new(&PcapReplayOptions {
+            files: vec![file.into()],
+            speed: 0,
+            preserve_timestamps: true,
+        });
+        let mut capture = pcap::Capture::from_file(file).unwrap();
+        while let Ok(p) = capture.next() {
+            let expected =
+                Duration::new(p.header.ts.tv_sec as u64, p.header.ts.tv_usec as u32 * 1000);
+            assert_eq!(replayer.read().unwrap().timestamp, expected);
+        }
+    }
+}
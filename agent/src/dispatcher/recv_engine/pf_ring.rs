@@ -0,0 +1,368 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::ffi::{CStr, CString};
+use std::io;
+use std::ptr;
+use std::slice;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use libc::{c_char, c_int, c_uchar, c_uint, c_void, poll, pollfd, timeval, POLLIN};
+use log::{info, warn};
+use public::error::{Error, Result};
+use public::packet::Packet;
+
+use super::POLL_TIMEOUT;
+
+use crate::utils::stats;
+
+// libpfring is loaded at runtime, so that agents built without PF_RING still start
+// when pf-ring-enabled is false and the library is not installed.
+const LIBRARY_NAMES: [&str; 2] = ["libpfring.so", "libpfring.so.1"];
+// ZC devices are opened by prefixing the interface name, e.g. zc:eth0
+const ZC_DEVICE_PREFIX: &str = "zc:";
+
+// flags of pfring_open in pfring.h
+const PF_RING_PROMISC: u32 = 1 << 3;
+
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[repr(C)]
+struct PfRingStat {
+    recv: u64,
+    drop: u64,
+    shunt: u64,
+}
+
+// struct pfring_pkthdr, extended_hdr is only filled with PF_RING_LONG_HEADER and
+// is reserved here with a size larger than any version of pfring_extended_pkthdr
+#[repr(C)]
+struct PfRingPkthdr {
+    ts: timeval,
+    caplen: u32,
+    len: u32,
+    extended_hdr: [u8; 512],
+}
+
+type OpenFn = unsafe extern "C" fn(*const c_char, u32, u32) -> *mut c_void;
+type RingFn = unsafe extern "C" fn(*mut c_void) -> c_int;
+type CloseFn = unsafe extern "C" fn(*mut c_void);
+type RecvFn =
+    unsafe extern "C" fn(*mut c_void, *mut *mut c_uchar, c_uint, *mut PfRingPkthdr, u8) -> c_int;
+type SetBpfFn = unsafe extern "C" fn(*mut c_void, *mut c_char) -> c_int;
+type StatsFn = unsafe extern "C" fn(*mut c_void, *mut PfRingStat) -> c_int;
+
+struct PfRingLib {
+    handle: *mut c_void,
+    open: OpenFn,
+    enable_ring: RingFn,
+    close: CloseFn,
+    recv: RecvFn,
+    set_bpf_filter: SetBpfFn,
+    stats: StatsFn,
+    get_selectable_fd: RingFn,
+}
+
+impl PfRingLib {
+    fn load() -> Result<Self> {
+        unsafe {
+            let handle = LIBRARY_NAMES
+                .iter()
+                .map(|name| {
+                    let name = CString::new(*name).unwrap();
+                    libc::dlopen(name.as_ptr(), libc::RTLD_LOCAL | libc::RTLD_NOW)
+                })
+                .find(|h| !h.is_null())
+                .ok_or_else(|| Error::PfRingError(Self::dl_error()))?;
+            let get_func = |sym: &str| {
+                let func_sym = CString::new(sym).unwrap();
+                let func = libc::dlsym(handle, func_sym.as_ptr());
+                if func.is_null() {
+                    Err(Error::PfRingError(format!("{}: {}", sym, Self::dl_error())))
+                } else {
+                    Ok(func)
+                }
+            };
+            let funcs = (|| {
+                Ok::<_, Error>((
+                    get_func("pfring_open")?,
+                    get_func("pfring_enable_ring")?,
+                    get_func("pfring_close")?,
+                    get_func("pfring_recv")?,
+                    get_func("pfring_set_bpf_filter")?,
+                    get_func("pfring_stats")?,
+                    get_func("pfring_get_selectable_fd")?,
+                ))
+            })();
+            let (open, enable_ring, close, recv, set_bpf_filter, stats, get_selectable_fd) =
+                match funcs {
+                    Ok(funcs) => funcs,
+                    Err(e) => {
+                        libc::dlclose(handle);
+                        return Err(e);
+                    }
+                };
+            Ok(Self {
+                handle,
+                open: std::mem::transmute::<*mut c_void, OpenFn>(open),
+                enable_ring: std::mem::transmute::<*mut c_void, RingFn>(enable_ring),
+                close: std::mem::transmute::<*mut c_void, CloseFn>(close),
+                recv: std::mem::transmute::<*mut c_void, RecvFn>(recv),
+                set_bpf_filter: std::mem::transmute::<*mut c_void, SetBpfFn>(set_bpf_filter),
+                stats: std::mem::transmute::<*mut c_void, StatsFn>(stats),
+                get_selectable_fd: std::mem::transmute::<*mut c_void, RingFn>(get_selectable_fd),
+            })
+        }
+    }
+
+    unsafe fn dl_error() -> String {
+        let err = libc::dlerror();
+        if err.is_null() {
+            "libpfring not found".to_owned()
+        } else {
+            CStr::from_ptr(err).to_string_lossy().into_owned()
+        }
+    }
+}
+
+impl Drop for PfRingLib {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dlclose(self.handle);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PfRingCounter {
+    packets: AtomicU64,
+    drops: AtomicU64,
+}
+
+impl stats::RefCountable for PfRingCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "kernel_packets",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.packets.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "kernel_drops",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.drops.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct Ring {
+    ring: *mut c_void,
+    if_index: isize,
+    // cumulative pfring_stats of the last read
+    last_stat: (u64, u64),
+}
+
+pub struct PfRing {
+    // rings must be closed before the library is unloaded
+    rings: Vec<Ring>,
+    lib: PfRingLib,
+    poll_fds: Vec<pollfd>,
+    next: usize,
+    last_stats: Instant,
+    counter: Arc<PfRingCounter>,
+}
+
+// Rings are only used by the dispatcher thread owning the recv engine
+unsafe impl Send for PfRing {}
+
+impl PfRing {
+    pub fn new(interfaces: Vec<(&str, isize)>, snap_len: usize, zc: bool) -> Result<Self> {
+        let lib = PfRingLib::load()?;
+        let mut rings = Vec::with_capacity(interfaces.len());
+        let mut poll_fds = Vec::with_capacity(interfaces.len());
+        for (name, if_index) in interfaces {
+            let device = if zc && !name.starts_with(ZC_DEVICE_PREFIX) {
+                format!("{}{}", ZC_DEVICE_PREFIX, name)
+            } else {
+                name.to_owned()
+            };
+            let c_device = CString::new(device.as_str()).unwrap();
+            let ring = unsafe { (lib.open)(c_device.as_ptr(), snap_len as u32, PF_RING_PROMISC) };
+            if ring.is_null() {
+                let e = io::Error::last_os_error();
+                Self::close_rings(&lib, &mut rings);
+                return Err(Error::PfRingError(format!(
+                    "pfring_open {} failed: {}",
+                    device, e
+                )));
+            }
+            rings.push(Ring {
+                ring,
+                if_index,
+                last_stat: (0, 0),
+            });
+            if unsafe { (lib.enable_ring)(ring) } != 0 {
+                Self::close_rings(&lib, &mut rings);
+                return Err(Error::PfRingError(format!(
+                    "pfring_enable_ring {} failed",
+                    device
+                )));
+            }
+            poll_fds.push(pollfd {
+                fd: unsafe { (lib.get_selectable_fd)(ring) },
+                events: POLLIN,
+                revents: 0,
+            });
+            info!("PF_RING opened {} with snap_len {}", device, snap_len);
+        }
+        if rings.is_empty() {
+            return Err(Error::PfRingError(
+                "pf_ring capture must give interface to capture packet".to_owned(),
+            ));
+        }
+        Ok(Self {
+            rings,
+            lib,
+            poll_fds,
+            next: 0,
+            last_stats: Instant::now(),
+            counter: Default::default(),
+        })
+    }
+
+    fn close_rings(lib: &PfRingLib, rings: &mut Vec<Ring>) {
+        for r in rings.drain(..) {
+            unsafe { (lib.close)(r.ring) };
+        }
+    }
+
+    fn update_stats(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.last_stats) < STATS_INTERVAL {
+            return;
+        }
+        self.last_stats = now;
+        for r in self.rings.iter_mut() {
+            let mut stat = PfRingStat {
+                recv: 0,
+                drop: 0,
+                shunt: 0,
+            };
+            if unsafe { (self.lib.stats)(r.ring, &mut stat) } != 0 {
+                continue;
+            }
+            self.counter
+                .packets
+                .fetch_add(stat.recv.saturating_sub(r.last_stat.0), Ordering::Relaxed);
+            self.counter
+                .drops
+                .fetch_add(stat.drop.saturating_sub(r.last_stat.1), Ordering::Relaxed);
+            r.last_stat = (stat.recv, stat.drop);
+        }
+    }
+
+    // The data referenced in the packet is in the ring buffer and kept until the next
+    // call to the read function.
+    pub fn read(&mut self) -> Result<Packet> {
+        self.update_stats();
+        let mut buffer: *mut c_uchar = ptr::null_mut();
+        let mut hdr: PfRingPkthdr = unsafe { std::mem::zeroed() };
+        let mut polled = false;
+        loop {
+            // round robin between rings without waiting
+            for _ in 0..self.rings.len() {
+                let i = self.next;
+                self.next = (self.next + 1) % self.rings.len();
+                // zero copy recv with buffer_len 0
+                let ret =
+                    unsafe { (self.lib.recv)(self.rings[i].ring, &mut buffer, 0, &mut hdr, 0) };
+                if ret < 0 {
+                    return Err(Error::PfRingError(format!("pfring_recv returned {}", ret)));
+                }
+                if ret == 0 || buffer.is_null() {
+                    continue;
+                }
+                let timestamp = if hdr.ts.tv_sec == 0 && hdr.ts.tv_usec == 0 {
+                    // hardware timestamps are disabled on some ZC devices
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                } else {
+                    Duration::new(hdr.ts.tv_sec as u64, hdr.ts.tv_usec as u32 * 1000)
+                };
+                return Ok(Packet {
+                    timestamp,
+                    if_index: self.rings[i].if_index,
+                    capture_length: hdr.len as isize,
+                    data: unsafe { slice::from_raw_parts_mut(buffer, hdr.caplen as usize) },
+                    ..Default::default()
+                });
+            }
+            if polled {
+                return Err(Error::Timeout);
+            }
+            let n = unsafe {
+                poll(
+                    self.poll_fds.as_mut_ptr(),
+                    self.poll_fds.len() as _,
+                    POLL_TIMEOUT.as_millis() as c_int,
+                )
+            };
+            if n < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    return Err(Error::Timeout);
+                }
+                return Err(e.into());
+            }
+            if n == 0 {
+                return Err(Error::Timeout);
+            }
+            polled = true;
+        }
+    }
+
+    pub fn set_bpf(&mut self, syntax: &str) -> Result<()> {
+        let syntax = CString::new(syntax).unwrap();
+        for r in self.rings.iter() {
+            // pfring_set_bpf_filter takes a mutable buffer but does not modify it
+            let ret = unsafe { (self.lib.set_bpf_filter)(r.ring, syntax.as_ptr() as *mut c_char) };
+            if ret != 0 {
+                warn!("pfring_set_bpf_filter {:?} failed: {}", syntax, ret);
+                return Err(Error::PfRingError(format!(
+                    "pfring_set_bpf_filter returned {}",
+                    ret
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<PfRingCounter> {
+        self.counter.clone()
+    }
+}
+
+impl Drop for PfRing {
+    fn drop(&mut self) {
+        Self::close_rings(&self.lib, &mut self.rings);
+    }
+}
@@ -0,0 +1,386 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::VecDeque;
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::os::unix::io::AsRawFd;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc::{c_int, c_void, poll, pollfd, setsockopt, socklen_t, POLLIN, SOL_SOCKET, SO_REUSEPORT};
+use log::info;
+use public::error::{Error, Result};
+use public::packet::Packet;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::POLL_TIMEOUT;
+
+use crate::utils::stats;
+
+// sFlow version 5, https://sflow.org/sflow_version_5.txt
+const SFLOW_VERSION_5: u32 = 5;
+const ADDRESS_TYPE_IPV4: u32 = 1;
+const ADDRESS_TYPE_IPV6: u32 = 2;
+// sample and record formats of enterprise 0
+const FLOW_SAMPLE: u32 = 1;
+const EXPANDED_FLOW_SAMPLE: u32 = 3;
+const RAW_PACKET_HEADER: u32 = 1;
+const HEADER_PROTOCOL_ETHERNET: u32 = 1;
+
+const MAX_DATAGRAM_SIZE: usize = 1 << 16;
+
+#[derive(Debug, Default)]
+pub struct SflowCounter {
+    datagrams: AtomicU64,
+    invalid_datagrams: AtomicU64,
+    samples: AtomicU64,
+    unsupported_records: AtomicU64,
+}
+
+impl stats::RefCountable for SflowCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "sflow_datagrams",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.datagrams.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "sflow_invalid_datagrams",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.invalid_datagrams.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "sflow_samples",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.samples.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "sflow_unsupported_records",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.unsupported_records.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SflowSample {
+    // the sampled ethernet header, which may be truncated
+    pub header: Vec<u8>,
+    // length of the original frame, stripped bytes such as FCS not included
+    pub frame_length: u32,
+    pub sampling_rate: u32,
+}
+
+// XDR decoder, all fields are 4 bytes aligned big endian
+struct XdrReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> XdrReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        let bytes = self.read_bytes(4)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    // opaque data is padded to 4 bytes
+    fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let padded = (len + 3) & !3;
+        if self.offset + padded > self.data.len() {
+            return None;
+        }
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += padded;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.read_bytes(len).map(|_| ())
+    }
+}
+
+// Returns None if the datagram is invalid, samples parsed before are kept
+pub fn parse_datagram(
+    data: &[u8],
+    samples: &mut VecDeque<SflowSample>,
+    counter: &SflowCounter,
+) -> Option<()> {
+    let mut reader = XdrReader::new(data);
+    if reader.read_u32()? != SFLOW_VERSION_5 {
+        return None;
+    }
+    match reader.read_u32()? {
+        ADDRESS_TYPE_IPV4 => reader.skip(4)?,
+        ADDRESS_TYPE_IPV6 => reader.skip(16)?,
+        _ => return None,
+    }
+    // sub agent id, sequence number and uptime
+    reader.skip(12)?;
+    let sample_count = reader.read_u32()?;
+    for _ in 0..sample_count {
+        let format = reader.read_u32()?;
+        let length = reader.read_u32()? as usize;
+        let mut sample = XdrReader::new(reader.read_bytes(length)?);
+        let sampling_rate = match format {
+            FLOW_SAMPLE => {
+                // sequence number and source id
+                sample.skip(8)?;
+                let sampling_rate = sample.read_u32()?;
+                // sample pool, drops, input and output
+                sample.skip(16)?;
+                sampling_rate
+            }
+            EXPANDED_FLOW_SAMPLE => {
+                // sequence number, source id type and index
+                sample.skip(12)?;
+                let sampling_rate = sample.read_u32()?;
+                // sample pool, drops, input and output in format and value
+                sample.skip(24)?;
+                sampling_rate
+            }
+            // counter samples and samples of other enterprises
+            _ => continue,
+        };
+        let record_count = sample.read_u32()?;
+        for _ in 0..record_count {
+            let format = sample.read_u32()?;
+            let length = sample.read_u32()? as usize;
+            let mut record = XdrReader::new(sample.read_bytes(length)?);
+            if format != RAW_PACKET_HEADER {
+                continue;
+            }
+            if record.read_u32()? != HEADER_PROTOCOL_ETHERNET {
+                counter.unsupported_records.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let frame_length = record.read_u32()?;
+            let stripped = record.read_u32()?;
+            let header_length = record.read_u32()? as usize;
+            let header = record.read_bytes(header_length)?;
+            samples.push_back(SflowSample {
+                header: header.to_vec(),
+                frame_length: frame_length.saturating_sub(stripped),
+                sampling_rate,
+            });
+            counter.samples.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    Some(())
+}
+
+// Receives sFlow datagrams on udp ports, sampled packet headers are read as packets
+pub struct SflowReceiver {
+    sockets: Vec<UdpSocket>,
+    poll_fds: Vec<pollfd>,
+    buffer: Vec<u8>,
+    samples: VecDeque<SflowSample>,
+    current: Vec<u8>,
+    counter: Arc<SflowCounter>,
+}
+
+impl SflowReceiver {
+    pub fn new(ports: &[u16]) -> Result<Self> {
+        let mut sockets = vec![];
+        for port in ports {
+            let socket = Self::bind(*port)?;
+            info!("sflow receiver listen on {:?}", socket.local_addr());
+            sockets.push(socket);
+        }
+        let poll_fds = sockets
+            .iter()
+            .map(|s| pollfd {
+                fd: s.as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+            })
+            .collect();
+        Ok(Self {
+            sockets,
+            poll_fds,
+            buffer: vec![0; MAX_DATAGRAM_SIZE],
+            samples: VecDeque::new(),
+            current: vec![],
+            counter: Default::default(),
+        })
+    }
+
+    // SO_REUSEPORT lets the kernel balance datagrams between dispatchers by source address
+    fn bind(port: u16) -> Result<UdpSocket> {
+        // prefer dual stack sockets and fallback to ipv4 if ipv6 is disabled
+        let (socket, addr) = match Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP)) {
+            Ok(socket) if socket.set_only_v6(false).is_ok() => {
+                (socket, SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port))
+            }
+            _ => (
+                Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?,
+                SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port),
+            ),
+        };
+        let enable: c_int = 1;
+        let ret = unsafe {
+            setsockopt(
+                socket.as_raw_fd(),
+                SOL_SOCKET,
+                SO_REUSEPORT,
+                &enable as *const c_int as *const c_void,
+                mem::size_of::<c_int>() as socklen_t,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        socket.bind(&addr.into())?;
+        socket.set_nonblocking(true)?;
+        Ok(socket.into())
+    }
+
+    fn recv_datagrams(&mut self) -> Result<()> {
+        let n = unsafe {
+            poll(
+                self.poll_fds.as_mut_ptr(),
+                self.poll_fds.len() as _,
+                POLL_TIMEOUT.as_millis() as c_int,
+            )
+        };
+        if n < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        if n == 0 {
+            return Err(Error::Timeout);
+        }
+        for (socket, poll_fd) in self.sockets.iter().zip(self.poll_fds.iter()) {
+            if poll_fd.revents & POLLIN == 0 {
+                continue;
+            }
+            let len = match socket.recv(&mut self.buffer) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.counter.datagrams.fetch_add(1, Ordering::Relaxed);
+            if parse_datagram(&self.buffer[..len], &mut self.samples, &self.counter).is_none() {
+                self.counter
+                    .invalid_datagrams
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    // The data referenced in the packet is kept until the next call to the read function.
+    pub fn read(&mut self) -> Result<Packet> {
+        if self.samples.is_empty() {
+            self.recv_datagrams()?;
+        }
+        let Some(sample) = self.samples.pop_front() else {
+            return Err(Error::Timeout);
+        };
+        self.current = sample.header;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Packet {
+            timestamp,
+            capture_length: sample.frame_length as isize,
+            data: &mut self.current,
+            sampling_rate: sample.sampling_rate,
+            ..Default::default()
+        })
+    }
+
+    pub fn get_counter_handle(&self) -> Arc<SflowCounter> {
+        self.counter.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_u32(buf: &mut Vec<u8>, v: u32) {
+        buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    #[test]
+    fn parse_flow_samples() {
+        let header = vec![0xaau8; 42];
+        let mut record = vec![];
+        push_u32(&mut record, HEADER_PROTOCOL_ETHERNET);
+        push_u32(&mut record, 1518);
+        push_u32(&mut record, 4);
+        push_u32(&mut record, header.len() as u32);
+        record.extend_from_slice(&header);
+        record.extend_from_slice(&[0, 0]);
+
+        let mut sample = vec![];
+        // sequence number and source id
+        push_u32(&mut sample, 1);
+        push_u32(&mut sample, 3);
+        push_u32(&mut sample, 1024);
+        // sample pool, drops, input and output
+        sample.extend_from_slice(&[0; 16]);
+        push_u32(&mut sample, 2);
+        // extended switch record is skipped
+        push_u32(&mut sample, 1001);
+        push_u32(&mut sample, 16);
+        sample.extend_from_slice(&[0; 16]);
+        push_u32(&mut sample, RAW_PACKET_HEADER);
+        push_u32(&mut sample, record.len() as u32);
+        sample.extend_from_slice(&record);
+
+        let mut datagram = vec![];
+        push_u32(&mut datagram, SFLOW_VERSION_5);
+        push_u32(&mut datagram, ADDRESS_TYPE_IPV4);
+        datagram.extend_from_slice(&[10, 0, 0, 1]);
+        // sub agent id, sequence number and uptime
+        datagram.extend_from_slice(&[0; 12]);
+        push_u32(&mut datagram, 2);
+        // counter sample is skipped
+        push_u32(&mut datagram, 2);
+        push_u32(&mut datagram, 8);
+        datagram.extend_from_slice(&[0; 8]);
+        push_u32(&mut datagram, FLOW_SAMPLE);
+        push_u32(&mut datagram, sample.len() as u32);
+        datagram.extend_from_slice(&sample);
+
+        let counter = SflowCounter::default();
+        let mut samples = VecDeque::new();
+        assert!(parse_datagram(&datagram, &mut samples, &counter).is_some());
+        assert_eq!(
+            samples.pop_front(),
+            Some(SflowSample {
+                header,
+                frame_length: 1514,
+                sampling_rate: 1024,
+            })
+        );
+        assert!(samples.is_empty());
+
+        // truncated datagram
+        assert!(parse_datagram(&datagram[..datagram.len() - 4], &mut samples, &counter).is_none());
+    }
+}
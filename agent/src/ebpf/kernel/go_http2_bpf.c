@@ -347,6 +347,9 @@ http2_fill_common_socket_2(struct http2_header_data *data,
 		if (!socket_info_map__update(&conn_key, &sk_info)) {
 			__sync_fetch_and_add(&trace_stats->
 					     socket_map_count, 1);
+		} else {
+			__sync_fetch_and_add(&trace_stats->
+					     socket_map_overflow, 1);
 		}
 	}
 
@@ -1049,6 +1052,9 @@ static __inline int fill_http2_dataframe_base(struct __http2_stack *stack,
 
 		if (!socket_info_map__update(&conn_key, &sk_info)) {
 			__sync_fetch_and_add(&trace_stats->socket_map_count, 1);
+		} else {
+			__sync_fetch_and_add(&trace_stats->socket_map_overflow,
+					     1);
 		}
 	}
 
@@ -81,6 +81,7 @@ enum process_data_extra_source {
 	DATA_SOURCE_OPENSSL_UPROBE,
 	DATA_SOURCE_IO_EVENT,
 	DATA_SOURCE_GO_HTTP2_DATAFRAME_UPROBE,
+	DATA_SOURCE_KTLS,
 };
 
 struct protocol_message_t {
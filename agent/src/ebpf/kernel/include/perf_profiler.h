@@ -24,6 +24,14 @@
 
 #define STACK_MAP_ENTRIES 65536
 
+/*
+ * Sampled allocations in progress (one per thread) and not freed yet,
+ * with the default sampling interval of 512KB, 65536 in-use samples
+ * represent 32GB of memory.
+ */
+#define MEM_PENDING_MAP_ENTRIES 16384
+#define MEM_INUSE_MAP_ENTRIES 65536
+
 /*
  * The meaning of the "__profiler_state_map" index.
  */
@@ -37,6 +45,12 @@ typedef enum {
 	ERROR_IDX,		/* Count the number of failed push notifications. */
 	ENABLE_IDX,		/* Enable profiler sampling flag.
 				   0: disable sampling; 1: enable sampling. */
+	OFFCPU_MIN_BLOCK_IDX,	/* Minimum off-CPU time (nanoseconds) of a
+				   blocking to be recorded, 0: off-CPU
+				   profiling is disabled. */
+	MEM_SAMPLE_BYTES_IDX,	/* Average bytes allocated between two
+				   memory allocation samples, 0: memory
+				   profiling is disabled. */
 
 	PROFILER_CNT
 } profiler_idx;
@@ -49,6 +63,61 @@ struct stack_trace_key_t {
 	int kernstack;
 	int userstack;
 	__u64 timestamp;
+	__u64 off_cpu_ns;	// off-CPU time, 0 for on-CPU samples
+	__u64 alloc_bytes;	// allocated bytes, 0 for CPU samples
+	__u64 inuse_bytes;	// in-use bytes, only set by user space
+};
+
+/*
+ * A sampled allocation of a thread, waiting for the allocation function
+ * to return the address.
+ */
+struct mem_alloc_pending_t {
+	__u64 bytes;		// allocated bytes represented by the sample
+	int stackid;		// stack ID in mem_stack_map
+};
+
+/* A sampled allocation not freed yet. */
+struct mem_inuse_key_t {
+	__u32 tgid;
+	__u32 reserved;
+	__u64 addr;		// address returned by the allocation function
+};
+
+struct mem_inuse_value_t {
+	__u64 bytes;
+	int stackid;
+};
+
+/* The in-use bytes of a stack of a process. */
+struct mem_stack_key_t {
+	__u32 tgid;
+	int stackid;
+};
+
+// /sys/kernel/debug/tracing/events/sched/sched_switch/format
+struct sched_switch_ctx {
+	__u64 __pad_0;
+	char prev_comm[TASK_COMM_LEN];
+	__s32 prev_pid;
+	__s32 prev_prio;
+	long prev_state;
+	char next_comm[TASK_COMM_LEN];
+	__s32 next_pid;
+	__s32 next_prio;
+};
+
+/*
+ * The task state and stack trace recorded when a task is switched
+ * out, used to compute the off-CPU time when it is switched back in.
+ */
+struct off_cpu_start_t {
+	__u64 timestamp;
+	__u64 transfer_count;
+	__u32 tgid;
+	int kernstack;
+	int userstack;
+	char comm[TASK_COMM_LEN];
 };
 
 #endif /* DF_BPF_PERF_PROFILER_H */
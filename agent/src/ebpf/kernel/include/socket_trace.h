@@ -108,6 +108,9 @@ struct member_fields_offset {
 	__u32 struct_sock_sport_offset;	// offsetof(struct sock_common, skc_num)
 	__u32 struct_sock_skc_state_offset;	// offsetof(struct sock_common, skc_state)
 	__u32 struct_sock_common_ipv6only_offset;	// offsetof(struct sock_common, skc_flags)
+	__u32 struct_unix_sock_addr_offset;	// offsetof(struct unix_sock, addr)
+	__u32 struct_unix_sock_peer_offset;	// offsetof(struct unix_sock, peer)
+	__u32 struct_unix_address_name_offset;	// offsetof(struct unix_address, name)
 
 };
 
@@ -293,6 +296,32 @@ struct syscall_comm_enter_ctx {
 	unsigned int flags;
 };
 
+#ifndef SOL_TLS
+#define SOL_TLS 282
+#endif
+#define TLS_TX 1
+#define TLS_RX 2
+
+// Directions with kernel TLS offload enabled on a socket
+#define KTLS_TX (1 << 0)
+#define KTLS_RX (1 << 1)
+
+struct syscall_setsockopt_enter_ctx {
+	__u64 __pad_0;		/*     0     8 */
+	int __syscall_nr;	/*    offset:8     4 */
+	__u32 __pad_1;		/*    12     4 */
+	__u64 fd;		/*    offset:16    8 */
+	__u64 level;		/*    offset:24    8 */
+	__u64 optname;		/*    offset:32    8 */
+	char *optval;		/*    offset:40    8 */
+	__u64 optlen;		/*    offset:48    8 */
+};
+
+struct ktls_args_t {
+	__u64 conn_key;
+	__u32 flag;
+} __attribute__ ((packed));
+
 struct sched_comm_exit_ctx {
 	__u64 __pad_0;		/*     0     8 */
 	char comm[16];		/*     offset:8;       size:16 */
@@ -30,6 +30,10 @@ enum endpoint_role {
 	ROLE_SERVER
 };
 
+/*
+ * For AF_UNIX sockets 'addr_len' is 0, and daddr and rcv_saddr hold the
+ * socket path (truncated to 32 bytes, NUL terminated if shorter).
+ */
 struct __tuple_t {
 	__u8 daddr[16];
 	__u8 rcv_saddr[16];
@@ -93,11 +97,28 @@ struct trace_conf_t {
 	__u32 go_tracing_timeout;
 	__u32 io_event_collect_mode;
 	__u64 io_event_minimal_duration;
+	__u32 unix_socket_enabled; // Capture data on AF_UNIX sockets
+	__u32 proc_sock_stats_enabled; // Count socket statistics per process
+};
+
+/*
+ * Per-process socket statistics, the counters are cumulative and
+ * read periodically by the user space.
+ */
+struct proc_sock_stats {
+	__u64 conn_count;	// Connections established by connect()/accept()
+	__u64 conn_failed;	// Failed connect() calls
+	__u64 tx_bytes;
+	__u64 rx_bytes;
+	__u64 retransmits;	// TCP retransmitted segments
 };
 
 struct trace_stats {
 	__u64 socket_map_count;     // 对socket 链接表进行统计
 	__u64 trace_map_count;     // 对同一进程/线程的多次转发表进行统计
+	// Number of new entries dropped because the map is full
+	__u64 socket_map_overflow;
+	__u64 trace_map_overflow;
 };
 
 struct socket_info_t {
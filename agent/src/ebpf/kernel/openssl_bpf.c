@@ -19,22 +19,22 @@
  * SPDX-License-Identifier: GPL-2.0
  */
 
-struct ssl_ctx_struct {
-	void *buf;
-	int num;
+/*
+ * Uprobes of TLS libraries: OpenSSL, BoringSSL and LibreSSL (SSL_read() and
+ * SSL_write()), NSS (PR_Read(), PR_Write(), PR_Recv() and PR_Send()) and
+ * rustls (Reader::read() and Writer::write()). The plaintext arguments are
+ * saved in ssl_ctx_map on entry and submitted on return.
+ */
 
-	int fd;
-	// Since the length of the plaintext is not equal to the length of the
-	// ciphertext, the TCP sequence number at the beginning cannot be
-	// calculated based on the TCP seq at the end and the length of the
-	// message.
-	__u32 tcp_seq;
-} __attribute__((packed));
-
-// Save function arguments and use them when the function returns
-// key: pid_tgid
-// value: SSL_* arguments
-BPF_HASH(ssl_ctx_map, __u64, struct ssl_ctx_struct)
+// The second word of the return value, e.g. the usize of Rust io::Result<usize>
+#if defined(__x86_64__)
+#define PT_REGS_RC2(x) PT_REGS_PARM3(x)
+#elif defined(__aarch64__)
+#define PT_REGS_RC2(x) PT_REGS_PARM2(x)
+#endif
+
+// PRFileDesc.identity of NSPR, the bottom layer of plain sockets and files
+#define PR_NSPR_IO_LAYER 0
 
 static int get_fd_from_openssl_ssl(void *ssl)
 {
@@ -55,39 +55,67 @@ static int get_fd_from_openssl_ssl(void *ssl)
 	return fd;
 }
 
-// int SSL_write(SSL *ssl, const void *buf, int num);
-SEC("uprobe/openssl_write_enter")
-int uprobe_openssl_write_enter(struct pt_regs *ctx)
+// Only the top layer of an SSL socket is hooked, or the ciphertext written to
+// the socket layer by NSS would be submitted as well
+static __inline bool is_nss_ssl_layer(void *pr_fd)
+{
+	struct {
+		void *methods;
+		void *secret;
+		void *lower;
+		void *higher;
+		void *dtor;
+		int identity;
+	} desc;
+
+	if (bpf_probe_read(&desc, sizeof(desc), pr_fd))
+		return false;
+	return desc.identity != PR_NSPR_IO_LAYER && desc.higher == NULL;
+}
+
+static __inline void ssl_ctx_save(struct pt_regs *ctx, int fd, __u32 tcp_seq)
 {
-	void *ssl = (void *)PT_REGS_PARM1(ctx);
-	int fd = get_fd_from_openssl_ssl(ssl);
 	__u64 id = bpf_get_current_pid_tgid();
 	struct ssl_ctx_struct ssl_ctx = {
 		.fd = fd,
 		.buf = (void *)PT_REGS_PARM2(ctx),
 		.num = (int)PT_REGS_PARM3(ctx),
-		.tcp_seq = get_tcp_write_seq_from_fd(fd),
+		.tcp_seq = tcp_seq,
 	};
 	ssl_ctx_map__update(&id, &ssl_ctx);
-	return 0;
 }
 
-// int SSL_write(SSL *ssl, const void *buf, int num);
-SEC("uretprobe/openssl_write_exit")
-int uprobe_openssl_write_exit(struct pt_regs *ctx)
+// The fd of rustls is the socket last used by the thread, the first call of
+// each thread is skipped as the thread is not tracked yet.
+static __inline int rustls_thread_fd(void)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	int *fd = tls_thread_fd_map__lookup(&id);
+	if (fd == NULL) {
+		int unknown = -1;
+		tls_thread_fd_map__update(&id, &unknown);
+		return -1;
+	}
+	return *fd;
+}
+
+static __inline int ssl_ctx_submit(struct pt_regs *ctx,
+				   const enum traffic_direction direction,
+				   int size)
 {
 	__u64 id = bpf_get_current_pid_tgid();
 	struct ssl_ctx_struct *ssl_ctx = ssl_ctx_map__lookup(&id);
 	if (!ssl_ctx)
 		return 0;
 
-	int size = (int)PT_REGS_RC(ctx);
-	if (size <= 0) {
+	// The fd is still unknown if no socket syscall is issued, e.g. the data
+	// is read from the buffer of the TLS library
+	if (size <= 0 || ssl_ctx->fd < 0) {
 		ssl_ctx_map__delete(&id);
 		return 0;
 	}
 
-	struct data_args_t write_args = {
+	struct data_args_t args = {
 		.buf = ssl_ctx->buf,
 		.fd = ssl_ctx->fd,
 		.enter_ts = bpf_ktime_get_ns(),
@@ -101,68 +129,132 @@ int uprobe_openssl_write_exit(struct pt_regs *ctx)
 	};
 
 	ssl_ctx_map__delete(&id);
-        active_write_args_map__update(&id, &write_args);
-	if (!process_data((struct pt_regs *)ctx, id, T_EGRESS, &write_args,
-			  size, &extra)) {
-		bpf_tail_call(ctx, &NAME(progs_jmp_kp_map),
-			      PROG_DATA_SUBMIT_KP_IDX);
+	if (direction == T_EGRESS) {
+		active_write_args_map__update(&id, &args);
+		if (!process_data((struct pt_regs *)ctx, id, T_EGRESS, &args,
+				  size, &extra)) {
+			bpf_tail_call(ctx, &NAME(progs_jmp_kp_map),
+				      PROG_DATA_SUBMIT_KP_IDX);
+		}
+		active_write_args_map__delete(&id);
+	} else {
+		active_read_args_map__update(&id, &args);
+		if (!process_data((struct pt_regs *)ctx, id, T_INGRESS, &args,
+				  size, &extra)) {
+			bpf_tail_call(ctx, &NAME(progs_jmp_kp_map),
+				      PROG_DATA_SUBMIT_KP_IDX);
+		}
+		active_read_args_map__delete(&id);
 	}
-	active_write_args_map__delete(&id);
 	return 0;
 }
 
+// int SSL_write(SSL *ssl, const void *buf, int num);
+SEC("uprobe/openssl_write_enter")
+int uprobe_openssl_write_enter(struct pt_regs *ctx)
+{
+	int fd = get_fd_from_openssl_ssl((void *)PT_REGS_PARM1(ctx));
+	ssl_ctx_save(ctx, fd, get_tcp_write_seq_from_fd(fd));
+	return 0;
+}
+
+// int SSL_write(SSL *ssl, const void *buf, int num);
+// PRInt32 PR_Write(PRFileDesc *fd, const void *buf, PRInt32 amount);
+SEC("uretprobe/openssl_write_exit")
+int uprobe_openssl_write_exit(struct pt_regs *ctx)
+{
+	return ssl_ctx_submit(ctx, T_EGRESS, (int)PT_REGS_RC(ctx));
+}
+
 // int SSL_read(SSL *ssl, void *buf, int num);
 SEC("uprobe/openssl_read_enter")
 int uprobe_openssl_read_enter(struct pt_regs *ctx)
 {
-	void *ssl = (void *)PT_REGS_PARM1(ctx);
-	int fd = get_fd_from_openssl_ssl(ssl);
-	__u64 id = bpf_get_current_pid_tgid();
-	struct ssl_ctx_struct ssl_ctx = {
-		.fd = fd,
-		.buf = (void *)PT_REGS_PARM2(ctx),
-		.num = (int)PT_REGS_PARM3(ctx),
-		.tcp_seq = get_tcp_read_seq_from_fd(fd),
-	};
-	ssl_ctx_map__update(&id, &ssl_ctx);
+	int fd = get_fd_from_openssl_ssl((void *)PT_REGS_PARM1(ctx));
+	ssl_ctx_save(ctx, fd, get_tcp_read_seq_from_fd(fd));
 	return 0;
 }
 
 // int SSL_read(SSL *ssl, void *buf, int num);
+// PRInt32 PR_Read(PRFileDesc *fd, void *buf, PRInt32 amount);
 SEC("uretprobe/openssl_read_exit")
 int uprobe_openssl_read_exit(struct pt_regs *ctx)
 {
-	__u64 id = bpf_get_current_pid_tgid();
-	struct ssl_ctx_struct *ssl_ctx = ssl_ctx_map__lookup(&id);
-	if (!ssl_ctx)
-		return 0;
+	return ssl_ctx_submit(ctx, T_INGRESS, (int)PT_REGS_RC(ctx));
+}
 
-	int size = (int)PT_REGS_RC(ctx);
-	if (size <= 0) {
-		ssl_ctx_map__delete(&id);
-		return 0;
-	}
+// SSL_write() of BoringSSL and LibreSSL, fd is set by tls_track_fd()
+SEC("uprobe/tls_write_enter")
+int uprobe_tls_write_enter(struct pt_regs *ctx)
+{
+	ssl_ctx_save(ctx, -1, 0);
+	return 0;
+}
 
-	struct data_args_t read_args = {
-		.buf = ssl_ctx->buf,
-		.fd = ssl_ctx->fd,
-		.enter_ts = bpf_ktime_get_ns(),
-		.tcp_seq = ssl_ctx->tcp_seq,
-	};
+// SSL_read() of BoringSSL and LibreSSL, fd is set by tls_track_fd()
+SEC("uprobe/tls_read_enter")
+int uprobe_tls_read_enter(struct pt_regs *ctx)
+{
+	ssl_ctx_save(ctx, -1, 0);
+	return 0;
+}
 
-	struct process_data_extra extra = {
-		.vecs = false,
-		.source = DATA_SOURCE_OPENSSL_UPROBE,
-		.is_go_process = false,
-	};
+// PRInt32 PR_Write(PRFileDesc *fd, const void *buf, PRInt32 amount);
+// PRInt32 PR_Send(PRFileDesc *fd, const void *buf, PRInt32 amount,
+//                 PRIntn flags, PRIntervalTime timeout);
+SEC("uprobe/nss_write_enter")
+int uprobe_nss_write_enter(struct pt_regs *ctx)
+{
+	if (is_nss_ssl_layer((void *)PT_REGS_PARM1(ctx)))
+		ssl_ctx_save(ctx, -1, 0);
+	return 0;
+}
 
-	ssl_ctx_map__delete(&id);
-        active_read_args_map__update(&id, &read_args);
-	if (!process_data((struct pt_regs *)ctx, id, T_INGRESS, &read_args,
-			  size, &extra)) {
-		bpf_tail_call(ctx, &NAME(progs_jmp_kp_map),
-			      PROG_DATA_SUBMIT_KP_IDX);
-	}
-	active_read_args_map__delete(&id);
+// PRInt32 PR_Read(PRFileDesc *fd, void *buf, PRInt32 amount);
+// PRInt32 PR_Recv(PRFileDesc *fd, void *buf, PRInt32 amount,
+//                 PRIntn flags, PRIntervalTime timeout);
+SEC("uprobe/nss_read_enter")
+int uprobe_nss_read_enter(struct pt_regs *ctx)
+{
+	if (is_nss_ssl_layer((void *)PT_REGS_PARM1(ctx)))
+		ssl_ctx_save(ctx, -1, 0);
 	return 0;
 }
+
+// fn <rustls::conn::Writer as std::io::Write>::write(&mut self, buf: &[u8])
+//     -> std::io::Result<usize>
+SEC("uprobe/rustls_write_enter")
+int uprobe_rustls_write_enter(struct pt_regs *ctx)
+{
+	int fd = rustls_thread_fd();
+	if (fd > 2)
+		ssl_ctx_save(ctx, fd, get_tcp_write_seq_from_fd(fd));
+	return 0;
+}
+
+// io::Result<usize> is returned in two registers, Ok is 0
+SEC("uretprobe/rustls_write_exit")
+int uprobe_rustls_write_exit(struct pt_regs *ctx)
+{
+	int size = PT_REGS_RC(ctx) == 0 ? (int)PT_REGS_RC2(ctx) : -1;
+	return ssl_ctx_submit(ctx, T_EGRESS, size);
+}
+
+// fn <rustls::conn::Reader as std::io::Read>::read(&mut self, buf: &mut [u8])
+//     -> std::io::Result<usize>
+SEC("uprobe/rustls_read_enter")
+int uprobe_rustls_read_enter(struct pt_regs *ctx)
+{
+	int fd = rustls_thread_fd();
+	if (fd > 2)
+		ssl_ctx_save(ctx, fd, get_tcp_read_seq_from_fd(fd));
+	return 0;
+}
+
+// io::Result<usize> is returned in two registers, Ok is 0
+SEC("uretprobe/rustls_read_exit")
+int uprobe_rustls_read_exit(struct pt_regs *ctx)
+{
+	int size = PT_REGS_RC(ctx) == 0 ? (int)PT_REGS_RC2(ctx) : -1;
+	return ssl_ctx_submit(ctx, T_INGRESS, size);
+}
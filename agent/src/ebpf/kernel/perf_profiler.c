@@ -213,3 +213,410 @@ int bpf_perf_event(struct bpf_perf_event_data *ctx)
 
 	return 0;
 }
+
+/*
+ * Off-CPU profiling
+ *
+ * When a task is switched out, its stack trace and the switch time are
+ * saved in off_cpu_start_map. When the task is switched back in, the
+ * time it spent off-CPU (blocked on locks, I/O, sleeping ...) is
+ * computed and, if not less than the minimum blocking time, output
+ * through the same buffers as the on-CPU samples, with 'off_cpu_ns' set.
+ *
+ * A blocking that spans a buffer switch is dropped, as its stack ids
+ * belong to the stack map being cleaned up by user space.
+ */
+MAP_HASH(off_cpu_start_map, __u32, struct off_cpu_start_t, 65536)
+
+SEC("tracepoint/sched/sched_switch")
+int bpf_func_sched_switch(struct sched_switch_ctx *ctx)
+{
+	__u32 count_idx;
+
+	count_idx = TRANSFER_CNT_IDX;
+	__u64 *transfer_count_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = SAMPLE_CNT_A_IDX;
+	__u64 *sample_count_a_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = SAMPLE_CNT_B_IDX;
+	__u64 *sample_count_b_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = OUTPUT_CNT_IDX;
+	__u64 *output_count_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = ENABLE_IDX;
+	__u64 *enable_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = ERROR_IDX;
+	__u64 *error_count_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = OFFCPU_MIN_BLOCK_IDX;
+	__u64 *min_block_ptr = profiler_state_map__lookup(&count_idx);
+
+	if (transfer_count_ptr == NULL || sample_count_a_ptr == NULL ||
+	    sample_count_b_ptr == NULL || output_count_ptr == NULL ||
+	    enable_ptr == NULL || error_count_ptr == NULL ||
+	    min_block_ptr == NULL)
+		return 0;
+
+	if (unlikely(*enable_ptr == 0 || *min_block_ptr == 0))
+		return 0;
+
+	__u64 ts = bpf_ktime_get_ns();
+	__u64 transfer_count = *transfer_count_ptr;
+
+	/*
+	 * The task being switched out is the current task, the idle
+	 * task is ignored.
+	 */
+	__u64 id = bpf_get_current_pid_tgid();
+	__u32 prev_pid = (__u32) id;
+	if (prev_pid != 0) {
+		struct off_cpu_start_t start = { 0 };
+		start.timestamp = ts;
+		start.transfer_count = transfer_count;
+		start.tgid = id >> 32;
+		bpf_get_current_comm(&start.comm, sizeof(start.comm));
+		if (!(transfer_count & 0x1ULL)) {
+			start.kernstack =
+			    bpf_get_stackid(ctx, &NAME(stack_map_a),
+					    KERN_STACKID_FLAGS);
+			start.userstack =
+			    bpf_get_stackid(ctx, &NAME(stack_map_a),
+					    USER_STACKID_FLAGS);
+		} else {
+			start.kernstack =
+			    bpf_get_stackid(ctx, &NAME(stack_map_b),
+					    KERN_STACKID_FLAGS);
+			start.userstack =
+			    bpf_get_stackid(ctx, &NAME(stack_map_b),
+					    USER_STACKID_FLAGS);
+		}
+
+		if (start.userstack >= 0 || start.kernstack >= 0)
+			off_cpu_start_map__update(&prev_pid, &start);
+	}
+
+	__u32 next_pid = (__u32) ctx->next_pid;
+	struct off_cpu_start_t *start_ptr =
+	    off_cpu_start_map__lookup(&next_pid);
+	if (start_ptr == NULL)
+		return 0;
+
+	struct off_cpu_start_t start = *start_ptr;
+	off_cpu_start_map__delete(&next_pid);
+
+	if (start.transfer_count != transfer_count || ts < start.timestamp)
+		return 0;
+
+	__u64 delta = ts - start.timestamp;
+	if (delta < *min_block_ptr)
+		return 0;
+
+	struct stack_trace_key_t key = { 0 };
+	key.pid = next_pid;
+	key.tgid = start.tgid;
+	key.cpu = bpf_get_smp_processor_id();
+	__builtin_memcpy(key.comm, start.comm, sizeof(key.comm));
+	key.kernstack = start.kernstack;
+	key.userstack = start.userstack;
+	key.timestamp = ts;
+	key.off_cpu_ns = delta;
+
+	int ret;
+	if (!(transfer_count & 0x1ULL)) {
+		__sync_fetch_and_add(sample_count_a_ptr, 1);
+		ret = bpf_perf_event_output(ctx, &NAME(profiler_output_a),
+					    BPF_F_CURRENT_CPU, &key,
+					    sizeof(key));
+	} else {
+		__sync_fetch_and_add(sample_count_b_ptr, 1);
+		ret = bpf_perf_event_output(ctx, &NAME(profiler_output_b),
+					    BPF_F_CURRENT_CPU, &key,
+					    sizeof(key));
+	}
+
+	if (ret)
+		__sync_fetch_and_add(error_count_ptr, 1);
+	else
+		__sync_fetch_and_add(output_count_ptr, 1);
+
+	return 0;
+}
+
+/*
+ * Memory allocation profiling
+ *
+ * The allocation functions (malloc(), calloc() and realloc() of libc or
+ * jemalloc) of the profiled processes are hooked with uprobes. Allocated
+ * bytes are accumulated per CPU and a sample is output every time the
+ * accumulated bytes exceed the sampling interval, with the stack trace of
+ * the allocation crossing the interval and 'alloc_bytes' set to the
+ * accumulated bytes. Stacks allocating more memory are more likely to be
+ * sampled, this is enough to find out where the memory churn comes from,
+ * at the cost of a hook for every allocation.
+ *
+ * In-use memory is tracked for the sampled allocations only:
+ *
+ *   1 The allocation entry records the bytes and the stack ID (in
+ *     mem_stack_map) of the sample in mem_pending_map.
+ *   2 The allocation return moves them into mem_inuse_map, keyed by the
+ *     returned address, and adds the bytes to the stack in
+ *     mem_inuse_stack_map.
+ *   3 free() and realloc() look up the address in mem_inuse_map and
+ *     subtract the bytes from the stack.
+ *
+ * User space reads mem_inuse_stack_map periodically to output mem-inuse
+ * profiles, stack IDs in mem_stack_map stay valid as long as the stack
+ * is in mem_inuse_stack_map, unlike those of stack_map_a and stack_map_b
+ * which are cleared every iteration.
+ *
+ * Java processes with the agent loaded also sample allocations in TLABs
+ * and outside TLABs with JVMTI, the agent calls df_java_alloc_sample()
+ * for each sample, which is hooked to output the sample with the stack
+ * trace of the Java thread.
+ */
+MAP_PERARRAY(alloc_bytes_map, __u32, __u64, 1)
+
+MAP_STACK_TRACE(mem_stack_map, STACK_MAP_ENTRIES)
+
+MAP_HASH(mem_pending_map, __u64, struct mem_alloc_pending_t,
+	 MEM_PENDING_MAP_ENTRIES)
+MAP_HASH(mem_inuse_map, struct mem_inuse_key_t, struct mem_inuse_value_t,
+	 MEM_INUSE_MAP_ENTRIES)
+MAP_HASH(mem_inuse_stack_map, struct mem_stack_key_t, __u64,
+	 MEM_INUSE_MAP_ENTRIES)
+
+static inline __attribute__ ((always_inline))
+int mem_sample_output(struct pt_regs *ctx, __u64 bytes)
+{
+	__u32 count_idx;
+
+	count_idx = TRANSFER_CNT_IDX;
+	__u64 *transfer_count_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = SAMPLE_CNT_A_IDX;
+	__u64 *sample_count_a_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = SAMPLE_CNT_B_IDX;
+	__u64 *sample_count_b_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = OUTPUT_CNT_IDX;
+	__u64 *output_count_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = ERROR_IDX;
+	__u64 *error_count_ptr = profiler_state_map__lookup(&count_idx);
+
+	if (transfer_count_ptr == NULL || sample_count_a_ptr == NULL ||
+	    sample_count_b_ptr == NULL || output_count_ptr == NULL ||
+	    error_count_ptr == NULL)
+		return 0;
+
+	__u64 id = bpf_get_current_pid_tgid();
+	struct stack_trace_key_t key = { 0 };
+	key.tgid = id >> 32;
+	key.pid = (__u32) id;
+	key.cpu = bpf_get_smp_processor_id();
+	bpf_get_current_comm(&key.comm, sizeof(key.comm));
+	key.timestamp = bpf_ktime_get_ns();
+	key.alloc_bytes = bytes;
+
+	int ret;
+	if (!((*transfer_count_ptr) & 0x1ULL)) {
+		key.kernstack = -1;
+		key.userstack = bpf_get_stackid(ctx, &NAME(stack_map_a),
+						USER_STACKID_FLAGS);
+		if (key.userstack < 0)
+			return 0;
+
+		__sync_fetch_and_add(sample_count_a_ptr, 1);
+		ret = bpf_perf_event_output(ctx, &NAME(profiler_output_a),
+					    BPF_F_CURRENT_CPU, &key,
+					    sizeof(key));
+	} else {
+		key.kernstack = -1;
+		key.userstack = bpf_get_stackid(ctx, &NAME(stack_map_b),
+						USER_STACKID_FLAGS);
+		if (key.userstack < 0)
+			return 0;
+
+		__sync_fetch_and_add(sample_count_b_ptr, 1);
+		ret = bpf_perf_event_output(ctx, &NAME(profiler_output_b),
+					    BPF_F_CURRENT_CPU, &key,
+					    sizeof(key));
+	}
+
+	if (ret)
+		__sync_fetch_and_add(error_count_ptr, 1);
+	else
+		__sync_fetch_and_add(output_count_ptr, 1);
+
+	return 0;
+}
+
+static inline __attribute__ ((always_inline))
+__u64 *mem_profiler_enabled(void)
+{
+	__u32 count_idx;
+
+	count_idx = ENABLE_IDX;
+	__u64 *enable_ptr = profiler_state_map__lookup(&count_idx);
+
+	count_idx = MEM_SAMPLE_BYTES_IDX;
+	__u64 *sample_bytes_ptr = profiler_state_map__lookup(&count_idx);
+
+	if (enable_ptr == NULL || sample_bytes_ptr == NULL)
+		return NULL;
+
+	if (unlikely(*enable_ptr == 0 || *sample_bytes_ptr == 0))
+		return NULL;
+
+	return sample_bytes_ptr;
+}
+
+static inline __attribute__ ((always_inline))
+int mem_alloc_sample(struct pt_regs *ctx, __u64 size)
+{
+	__u64 *sample_bytes_ptr = mem_profiler_enabled();
+	if (sample_bytes_ptr == NULL)
+		return 0;
+
+	__u32 zero = 0;
+	__u64 *alloc_bytes_ptr = alloc_bytes_map__lookup(&zero);
+	if (alloc_bytes_ptr == NULL)
+		return 0;
+
+	*alloc_bytes_ptr += size;
+	if (*alloc_bytes_ptr < *sample_bytes_ptr)
+		return 0;
+
+	__u64 bytes = *alloc_bytes_ptr;
+	*alloc_bytes_ptr = 0;
+
+	struct mem_alloc_pending_t pending = { 0 };
+	pending.bytes = bytes;
+	pending.stackid = bpf_get_stackid(ctx, &NAME(mem_stack_map),
+					  USER_STACKID_FLAGS);
+	if (pending.stackid >= 0) {
+		__u64 id = bpf_get_current_pid_tgid();
+		mem_pending_map__update(&id, &pending);
+	}
+
+	return mem_sample_output(ctx, bytes);
+}
+
+static inline __attribute__ ((always_inline))
+int mem_alloc_return(struct pt_regs *ctx)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	struct mem_alloc_pending_t *pending = mem_pending_map__lookup(&id);
+	if (pending == NULL)
+		return 0;
+
+	struct mem_inuse_key_t key = { 0 };
+	struct mem_inuse_value_t value = { 0 };
+	key.tgid = id >> 32;
+	key.addr = (__u64) PT_REGS_RC(ctx);
+	value.bytes = pending->bytes;
+	value.stackid = pending->stackid;
+	mem_pending_map__delete(&id);
+
+	if (key.addr == 0)
+		return 0;
+
+	if (mem_inuse_map__update(&key, &value))
+		return 0;
+
+	struct mem_stack_key_t stack_key = { 0 };
+	stack_key.tgid = key.tgid;
+	stack_key.stackid = value.stackid;
+	__u64 *inuse_bytes_ptr = mem_inuse_stack_map__lookup(&stack_key);
+	if (inuse_bytes_ptr == NULL) {
+		if (bpf_map_update_elem(&NAME(mem_inuse_stack_map), &stack_key,
+					&value.bytes, BPF_NOEXIST) == 0)
+			return 0;
+		// Added by another thread of the process
+		inuse_bytes_ptr = mem_inuse_stack_map__lookup(&stack_key);
+		if (inuse_bytes_ptr == NULL)
+			return 0;
+	}
+	__sync_fetch_and_add(inuse_bytes_ptr, value.bytes);
+
+	return 0;
+}
+
+static inline __attribute__ ((always_inline))
+int mem_free(__u64 addr)
+{
+	if (addr == 0)
+		return 0;
+
+	struct mem_inuse_key_t key = { 0 };
+	key.tgid = bpf_get_current_pid_tgid() >> 32;
+	key.addr = addr;
+	struct mem_inuse_value_t *value = mem_inuse_map__lookup(&key);
+	if (value == NULL)
+		return 0;
+
+	struct mem_stack_key_t stack_key = { 0 };
+	stack_key.tgid = key.tgid;
+	stack_key.stackid = value->stackid;
+	__u64 *inuse_bytes_ptr = mem_inuse_stack_map__lookup(&stack_key);
+	/*
+	 * Stacks with no bytes in use are removed by user space, removing
+	 * them here may lose the bytes added by other threads meanwhile.
+	 */
+	if (inuse_bytes_ptr != NULL)
+		__sync_fetch_and_add(inuse_bytes_ptr, -value->bytes);
+
+	mem_inuse_map__delete(&key);
+	return 0;
+}
+
+// void *malloc(size_t size);
+SEC("uprobe/malloc_enter")
+int uprobe_malloc_enter(struct pt_regs *ctx)
+{
+	return mem_alloc_sample(ctx, (__u64) PT_REGS_PARM1(ctx));
+}
+
+// void *calloc(size_t nmemb, size_t size);
+SEC("uprobe/calloc_enter")
+int uprobe_calloc_enter(struct pt_regs *ctx)
+{
+	return mem_alloc_sample(ctx, (__u64) PT_REGS_PARM1(ctx) *
+				(__u64) PT_REGS_PARM2(ctx));
+}
+
+// void *realloc(void *ptr, size_t size);
+SEC("uprobe/realloc_enter")
+int uprobe_realloc_enter(struct pt_regs *ctx)
+{
+	mem_free((__u64) PT_REGS_PARM1(ctx));
+	return mem_alloc_sample(ctx, (__u64) PT_REGS_PARM2(ctx));
+}
+
+// The address returned by malloc(), calloc() and realloc()
+SEC("uretprobe/mem_alloc_exit")
+int uprobe_mem_alloc_exit(struct pt_regs *ctx)
+{
+	return mem_alloc_return(ctx);
+}
+
+// void free(void *ptr);
+SEC("uprobe/free_enter")
+int uprobe_free_enter(struct pt_regs *ctx)
+{
+	return mem_free((__u64) PT_REGS_PARM1(ctx));
+}
+
+// void df_java_alloc_sample(uint64_t bytes);
+SEC("uprobe/java_alloc_sample")
+int uprobe_java_alloc_sample(struct pt_regs *ctx)
+{
+	if (mem_profiler_enabled() == NULL)
+		return 0;
+
+	return mem_sample_output(ctx, (__u64) PT_REGS_PARM1(ctx));
+}
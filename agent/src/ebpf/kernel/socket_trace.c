@@ -133,6 +133,62 @@ BPF_HASH(socket_role_map, __u64, __u32);
 // Key is struct trace_key_t. value is trace_info_t
 BPF_HASH(trace_map, struct trace_key_t, struct trace_info_t)
 
+// Key is tgid. value is struct proc_sock_stats
+BPF_HASH(proc_sock_stats_map, __u32, struct proc_sock_stats, 16384)
+
+/*
+ * Retransmissions happen mostly in softirq context, the owner process
+ * is found by the socket address.
+ * Key is socket address. value is tgid
+ */
+BPF_HASH(sk_tgid_map, __u64, __u32)
+
+// Key is {tgid, pid}. value is the fd of the connect() call in progress
+BPF_HASH(connect_fd_map, __u64, int)
+
+/*
+ * Non-blocking connect() returning -EINPROGRESS, counted when the
+ * handshake finishes or the socket is closed.
+ * Key is socket address. value is tgid
+ */
+BPF_HASH(connect_pending_map, __u64, __u32)
+
+struct ssl_ctx_struct {
+	void *buf;
+	int num;
+
+	// -1 if the TLS library is hooked without knowing its connection
+	// struct, the fd is then taken from socket syscalls of the thread.
+	int fd;
+	// Since the length of the plaintext is not equal to the length of the
+	// ciphertext, the TCP sequence number at the beginning cannot be
+	// calculated based on the TCP seq at the end and the length of the
+	// message.
+	__u32 tcp_seq;
+} __attribute__((packed));
+
+// Save TLS library function arguments and use them when the function returns
+// key: pid_tgid
+// value: SSL_* arguments
+BPF_HASH(ssl_ctx_map, __u64, struct ssl_ctx_struct)
+
+// rustls does no I/O in Reader::read() and Writer::write(), the connection
+// is the socket last read or written by the thread. Threads are added by
+// rustls uprobes.
+// key: pid_tgid
+// value: fd
+BPF_HASH(tls_thread_fd_map, __u64, int)
+
+// Sockets with kernel TLS offload, the application reads and writes plaintext
+// through syscalls and the kernel encrypts and decrypts the TLS records.
+// key: {tgid, fd}
+// value: KTLS_TX | KTLS_RX
+BPF_HASH(ktls_conn_map, __u64, __u32)
+
+// Save setsockopt(SOL_TLS) arguments and use them when the syscall returns
+// key: pid_tgid
+BPF_HASH(ktls_args_map, __u64, struct ktls_args_t)
+
 // Stores the identity used to fit the kernel, key: 0, vlaue:{tgid, pid}
 MAP_ARRAY(adapt_kern_uid_map, __u32, __u64, 1)
 
@@ -472,6 +528,45 @@ do {									\
 		f = PF_INET;						\
 } while(0)
 
+static __inline bool is_unix_socket_enabled(struct member_fields_offset *offset)
+{
+	__u32 k0 = 0;
+	struct trace_conf_t *trace_conf = trace_conf_map__lookup(&k0);
+	if (trace_conf == NULL || !trace_conf->unix_socket_enabled)
+		return false;
+
+	return offset->struct_unix_address_name_offset != 0;
+}
+
+/*
+ * The path of an unnamed socket, e.g. the client side of a connection,
+ * is taken from its peer.
+ */
+static __inline void get_unix_socket_path(void *sk,
+					  struct member_fields_offset *offset,
+					  __u8 *path, int size)
+{
+	void *addr = NULL;
+	bpf_probe_read(&addr, sizeof(addr),
+		       sk + offset->struct_unix_sock_addr_offset);
+	if (addr == NULL) {
+		void *peer = NULL;
+		bpf_probe_read(&peer, sizeof(peer),
+			       sk + offset->struct_unix_sock_peer_offset);
+		if (peer == NULL)
+			return;
+		bpf_probe_read(&addr, sizeof(addr),
+			       peer + offset->struct_unix_sock_addr_offset);
+		if (addr == NULL)
+			return;
+	}
+
+	// struct sockaddr_un { sa_family_t sun_family; char sun_path[108]; }
+	bpf_probe_read(path, size, addr +
+		       offset->struct_unix_address_name_offset +
+		       sizeof(__u16));
+}
+
 static __inline int is_tcp_udp_data(void *sk,
 				    struct member_fields_offset *offset,
 				    struct conn_info_t *conn_info)
@@ -489,9 +584,7 @@ static __inline int is_tcp_udp_data(void *sk,
 	conn_info->skc_ipv6only = skc_flags.skc_ipv6only;
 	bpf_probe_read(&conn_info->skc_family, sizeof(conn_info->skc_family),
 		       sk + offset->struct_sock_family_offset);
-	/*
-	 * Without thinking about PF_UNIX.
-	 */
+
 	switch (conn_info->skc_family) {
 	case PF_INET:
 		break;
@@ -500,6 +593,14 @@ static __inline int is_tcp_udp_data(void *sk,
 			ipv4_mapped_on_ipv6_confirm(sk, conn_info->skc_family, offset);
 		}
 		break;
+	case PF_UNIX:
+		/*
+		 * The offsets of struct unix_sock are only known from BTF,
+		 * stream and datagram sockets are handled as TCP and UDP.
+		 */
+		if (!is_unix_socket_enabled(offset))
+			return SOCK_CHECK_TYPE_ERROR;
+		break;
 	default:
 		return SOCK_CHECK_TYPE_ERROR;
 	}
@@ -559,9 +660,7 @@ static __inline bool get_socket_info(struct __socket_data *v, void *sk,
 	struct member_fields_offset *offset = members_offset__lookup(&k0);
 	if (!offset)
 		return false;
-	/*
-	 * Without thinking about PF_UNIX.
-	 */
+
 	switch (conn_info->skc_family) {
 	case PF_INET:
 		bpf_probe_read(v->tuple.rcv_saddr, 4,
@@ -583,6 +682,17 @@ static __inline bool get_socket_info(struct __socket_data *v, void *sk,
 		}
 		v->tuple.addr_len = 16;
 		break;
+	case PF_UNIX:
+		/*
+		 * daddr and rcv_saddr are contiguous and hold the path.
+		 */
+		__builtin_memset(v->tuple.daddr, 0, sizeof(v->tuple.daddr) +
+				 sizeof(v->tuple.rcv_saddr));
+		get_unix_socket_path(sk, offset, v->tuple.daddr,
+				     sizeof(v->tuple.daddr) +
+				     sizeof(v->tuple.rcv_saddr));
+		v->tuple.addr_len = 0;
+		break;
 	default:
 		return false;
 	}
@@ -997,6 +1107,9 @@ static __inline void trace_process(struct socket_info_t *socket_info_ptr,
 			if (ret == 0) {
 				__sync_fetch_and_add(&trace_stats->
 						     trace_map_count, 1);
+			} else {
+				__sync_fetch_and_add(&trace_stats->
+						     trace_map_overflow, 1);
 			}
 		}
 	} else { /* direction == T_EGRESS */
@@ -1033,9 +1146,13 @@ static __inline void trace_process(struct socket_info_t *socket_info_ptr,
 				trace_info.is_trace_id_zero = true;
 				trace_info.update_time =
 				    time_stamp / NS_PER_SEC;
-				trace_map__update(trace_key, &trace_info);
-				__sync_fetch_and_add(&trace_stats->
-						     trace_map_count, 1);
+				if (trace_map__update(trace_key, &trace_info) == 0) {
+					__sync_fetch_and_add(&trace_stats->
+							     trace_map_count, 1);
+				} else {
+					__sync_fetch_and_add(&trace_stats->
+							     trace_map_overflow, 1);
+				}
 			}
 		}
 	}
@@ -1138,9 +1255,13 @@ __data_submit(struct pt_regs *ctx, struct conn_info_t *conn_info,
 		}
 
 		int ret = socket_info_map__update(&conn_key, &sk_info);
-		if (socket_info_ptr == NULL && ret == 0) {
-			__sync_fetch_and_add(&trace_stats->
-                        	             socket_map_count, 1);
+		if (socket_info_ptr == NULL) {
+			if (ret == 0)
+				__sync_fetch_and_add(&trace_stats->
+						     socket_map_count, 1);
+			else
+				__sync_fetch_and_add(&trace_stats->
+						     socket_map_overflow, 1);
 		}
 	}
 
@@ -1222,9 +1343,11 @@ __data_submit(struct pt_regs *ctx, struct conn_info_t *conn_info,
 	v->msg_type = conn_info->message_type;
 	v->tcp_seq = 0;
 
-	if ((extra->source == DATA_SOURCE_GO_TLS_UPROBE ||
-	     extra->source == DATA_SOURCE_OPENSSL_UPROBE) ||
-	    (conn_info->tuple.l4_protocol == IPPROTO_TCP)) {
+	// There is no TCP sequence on AF_UNIX sockets.
+	if (conn_info->skc_family != PF_UNIX &&
+	    ((extra->source == DATA_SOURCE_GO_TLS_UPROBE ||
+	      extra->source == DATA_SOURCE_OPENSSL_UPROBE) ||
+	     (conn_info->tuple.l4_protocol == IPPROTO_TCP))) {
 		/*
 		 * If the current state is TCPF_CLOSE_WAIT, the FIN frame already has been received.
 		 * However, it cannot be confirmed that it has been processed by the syscall,
@@ -1294,6 +1417,61 @@ __data_submit(struct pt_regs *ctx, struct conn_info_t *conn_info,
 	return SUBMIT_OK;
 }
 
+static __inline bool is_proc_sock_stats_enabled(void)
+{
+	__u32 k0 = 0;
+	struct trace_conf_t *trace_conf = trace_conf_map__lookup(&k0);
+	return trace_conf != NULL && trace_conf->proc_sock_stats_enabled;
+}
+
+static __inline struct proc_sock_stats *get_proc_sock_stats(__u32 tgid)
+{
+	struct proc_sock_stats *stats = proc_sock_stats_map__lookup(&tgid);
+	if (stats == NULL) {
+		struct proc_sock_stats __stats = { 0 };
+		proc_sock_stats_map__update(&tgid, &__stats);
+		stats = proc_sock_stats_map__lookup(&tgid);
+	}
+
+	return stats;
+}
+
+static __inline void proc_sock_stats_add_bytes(__u32 tgid, void *sk,
+					       enum traffic_direction direction,
+					       ssize_t bytes_count)
+{
+	if (!is_proc_sock_stats_enabled())
+		return;
+
+	struct proc_sock_stats *stats = get_proc_sock_stats(tgid);
+	if (stats == NULL)
+		return;
+
+	if (direction == T_EGRESS)
+		__sync_fetch_and_add(&stats->tx_bytes, bytes_count);
+	else
+		__sync_fetch_and_add(&stats->rx_bytes, bytes_count);
+
+	__u64 sk_addr = (__u64)sk;
+	if (sk_tgid_map__lookup(&sk_addr) == NULL)
+		sk_tgid_map__update(&sk_addr, &tgid);
+}
+
+static __inline void proc_sock_stats_add_conn(__u32 tgid, bool failed)
+{
+	if (!is_proc_sock_stats_enabled())
+		return;
+
+	struct proc_sock_stats *stats = get_proc_sock_stats(tgid);
+	if (stats == NULL)
+		return;
+
+	if (failed)
+		__sync_fetch_and_add(&stats->conn_failed, 1);
+	else
+		__sync_fetch_and_add(&stats->conn_count, 1);
+}
+
 static __inline int process_data(struct pt_regs *ctx, __u64 id,
 				 const enum traffic_direction direction,
 				 const struct data_args_t *args,
@@ -1332,6 +1510,9 @@ static __inline int process_data(struct pt_regs *ctx, __u64 id,
 		return -1;
 	}
 
+	proc_sock_stats_add_bytes((__u32)(id >> 32), sk, direction,
+				  bytes_count);
+
 	init_conn_info(id >> 32, args->fd, &__conn_info, sk, offset);
 
 	conn_info->direction = direction;
@@ -1371,7 +1552,8 @@ static __inline int process_data(struct pt_regs *ctx, __u64 id,
 			ctx_map->tail_call.offset = offset;
 			ctx_map->tail_call.dir = direction;
 			/* Enter the protocol inference tail call program. */
-			if (extra->source == DATA_SOURCE_SYSCALL)
+			if (extra->source == DATA_SOURCE_SYSCALL ||
+			    extra->source == DATA_SOURCE_KTLS)
 				bpf_tail_call(ctx, &NAME(progs_jmp_tp_map),
 					      PROG_PROTO_INFER_TP_IDX);
 			else
@@ -1398,15 +1580,36 @@ static __inline int process_data(struct pt_regs *ctx, __u64 id,
 	return -1;
 }
 
+/*
+ * The syscall data of kTLS sockets is the plaintext of the TLS records, it is
+ * not inferred as TLS and is reported as decrypted data. Applications using
+ * kTLS bypass the SSL_write()/SSL_read() uprobes if they read and write the
+ * socket directly after the handshake.
+ */
+static __inline enum process_data_extra_source
+syscall_data_source(__u64 id, int fd, const enum traffic_direction direction)
+{
+	__u64 conn_key = gen_conn_key_id(id >> 32, (__u64)fd);
+	__u32 *ktls = ktls_conn_map__lookup(&conn_key);
+	if (ktls && (*ktls & (direction == T_EGRESS ? KTLS_TX : KTLS_RX)))
+		return DATA_SOURCE_KTLS;
+
+	return DATA_SOURCE_SYSCALL;
+}
+
 static __inline void process_syscall_data(struct pt_regs* ctx, __u64 id,
 					  const enum traffic_direction direction,
 					  const struct data_args_t* args, ssize_t bytes_count) {
 	struct process_data_extra extra = {
 		.vecs = false,
-		.source = DATA_SOURCE_SYSCALL,
+		.source = syscall_data_source(id, args->fd, direction),
 		.is_go_process = is_current_go_process(),
 	};
 
+	// The plaintext is reported by the TLS library uprobes
+	if (extra.source == DATA_SOURCE_KTLS && ssl_ctx_map__lookup(&id))
+		return;
+
 	if (!process_data(ctx, id, direction, args, bytes_count, &extra)) {
 		bpf_tail_call(ctx, &NAME(progs_jmp_tp_map),
 			      PROG_DATA_SUBMIT_TP_IDX);
@@ -1422,10 +1625,14 @@ static __inline void process_syscall_data_vecs(struct pt_regs* ctx, __u64 id,
 					       ssize_t bytes_count) {
 	struct process_data_extra extra = {
 		.vecs = true,
-		.source = DATA_SOURCE_SYSCALL,
+		.source = syscall_data_source(id, args->fd, direction),
 		.is_go_process = is_current_go_process(),
 	};
 
+	// The plaintext is reported by the TLS library uprobes
+	if (extra.source == DATA_SOURCE_KTLS && ssl_ctx_map__lookup(&id))
+		return;
+
 	if (!process_data(ctx, id, direction, args, bytes_count, &extra)) {
 		bpf_tail_call(ctx, &NAME(progs_jmp_tp_map),
 			      PROG_DATA_SUBMIT_TP_IDX);
@@ -1435,6 +1642,28 @@ static __inline void process_syscall_data_vecs(struct pt_regs* ctx, __u64 id,
 	}
 }
 
+/*
+ * BoringSSL, LibreSSL and NSS are hooked without reading fd from their
+ * connection structs, the fd of SSL_read()/SSL_write() is the socket read or
+ * written by the syscall issued inside.
+ */
+static __inline void tls_track_fd(__u64 id, int fd, __u32 tcp_seq)
+{
+	// TLS runs over TCP, other files and sockets have no tcp seq
+	if (tcp_seq == 0)
+		return;
+
+	struct ssl_ctx_struct *ssl_ctx = ssl_ctx_map__lookup(&id);
+	if (ssl_ctx != NULL && ssl_ctx->fd < 0) {
+		ssl_ctx->fd = fd;
+		ssl_ctx->tcp_seq = tcp_seq;
+	}
+
+	int *thread_fd = tls_thread_fd_map__lookup(&id);
+	if (thread_fd != NULL)
+		*thread_fd = fd;
+}
+
 /***********************************************************
  * BPF syscall probe/tracepoint function entry-points
  ***********************************************************/
@@ -1450,6 +1679,7 @@ TPPROG(sys_enter_write) (struct syscall_comm_enter_ctx *ctx) {
 	write_args.enter_ts = bpf_ktime_get_ns();
 	write_args.tcp_seq = get_tcp_write_seq_from_fd(fd);
 	active_write_args_map__update(&id, &write_args);
+	tls_track_fd(id, fd, write_args.tcp_seq);
 
 	return 0;
 }
@@ -1483,6 +1713,7 @@ TPPROG(sys_enter_read) (struct syscall_comm_enter_ctx *ctx) {
 	read_args.enter_ts = bpf_ktime_get_ns();
 	read_args.tcp_seq = get_tcp_read_seq_from_fd(fd);
 	active_read_args_map__update(&id, &read_args);
+	tls_track_fd(id, fd, read_args.tcp_seq);
 
 	return 0;
 }
@@ -1517,6 +1748,7 @@ TPPROG(sys_enter_sendto) (struct syscall_comm_enter_ctx *ctx) {
 	write_args.enter_ts = bpf_ktime_get_ns();
 	write_args.tcp_seq = get_tcp_write_seq_from_fd(sockfd);
 	active_write_args_map__update(&id, &write_args);
+	tls_track_fd(id, sockfd, write_args.tcp_seq);
 
 	return 0;
 }
@@ -1560,6 +1792,7 @@ TPPROG(sys_enter_recvfrom) (struct syscall_comm_enter_ctx *ctx) {
 	read_args.enter_ts = bpf_ktime_get_ns();
 	read_args.tcp_seq = get_tcp_read_seq_from_fd(sockfd);
 	active_read_args_map__update(&id, &read_args);
+	tls_track_fd(id, sockfd, read_args.tcp_seq);
 
 	return 0;
 }
@@ -1600,6 +1833,7 @@ KPROG(__sys_sendmsg) (struct pt_regs* ctx) {
 		write_args.enter_ts = bpf_ktime_get_ns();
 		write_args.tcp_seq = get_tcp_write_seq_from_fd(sockfd);
 		active_write_args_map__update(&id, &write_args);
+		tls_track_fd(id, sockfd, write_args.tcp_seq);
 	}
 
 	return 0;
@@ -1690,6 +1924,7 @@ KPROG(__sys_recvmsg) (struct pt_regs* ctx) {
 		read_args.enter_ts = bpf_ktime_get_ns();
 		read_args.tcp_seq = get_tcp_read_seq_from_fd(sockfd);
 		active_read_args_map__update(&id, &read_args);
+		tls_track_fd(id, sockfd, read_args.tcp_seq);
 	}
 
 	return 0;
@@ -1782,6 +2017,7 @@ KPROG(do_writev) (struct pt_regs* ctx) {
 	write_args.enter_ts = bpf_ktime_get_ns();
 	write_args.tcp_seq = get_tcp_write_seq_from_fd(fd);
 	active_write_args_map__update(&id, &write_args);
+	tls_track_fd(id, fd, write_args.tcp_seq);
 	return 0;
 }
 
@@ -1817,6 +2053,7 @@ KPROG(do_readv) (struct pt_regs* ctx) {
 	read_args.enter_ts = bpf_ktime_get_ns();
 	read_args.tcp_seq = get_tcp_read_seq_from_fd(fd);
 	active_read_args_map__update(&id, &read_args);
+	tls_track_fd(id, fd, read_args.tcp_seq);
 
 	return 0;
 }
@@ -1858,6 +2095,15 @@ TPPROG(sys_enter_close) (struct syscall_comm_enter_ctx *ctx) {
 			delete_socket_info(conn_key, socket_info_ptr);
 		
 		socket_role_map__delete(&conn_key);
+		ktls_conn_map__delete(&conn_key);
+		sk_tgid_map__delete(&sock_addr);
+
+		// Closed before the non-blocking connect() finished
+		if (connect_pending_map__lookup(&sock_addr)) {
+			proc_sock_stats_add_conn(bpf_get_current_pid_tgid() >> 32,
+						 true);
+			connect_pending_map__delete(&sock_addr);
+		}
 	}
 
 	return 0;
@@ -1932,6 +2178,9 @@ TPPROG(sys_exit_socket) (struct syscall_comm_exit_ctx *ctx) {
 		if (ret == 0) {
 			__sync_fetch_and_add(&trace_stats->
 					     socket_map_count, 1);
+		} else {
+			__sync_fetch_and_add(&trace_stats->
+					     socket_map_overflow, 1);
 		}
 	}
 
@@ -1943,6 +2192,9 @@ TPPROG(sys_exit_accept)(struct syscall_comm_exit_ctx *ctx)
 	int sockfd = ctx->ret;
 	__u64 pid_tgid = bpf_get_current_pid_tgid();
 	__u32 tgid = (__u32)(pid_tgid >> 32);
+	if (sockfd < 0)
+		return 0;
+	proc_sock_stats_add_conn(tgid, false);
 	__u64 conn_key = gen_conn_key_id((__u64)tgid, (__u64)sockfd);
 	__u32 role = ROLE_SERVER;
 	socket_role_map__update(&conn_key, &role);
@@ -1954,6 +2206,9 @@ TPPROG(sys_exit_accept4)(struct syscall_comm_exit_ctx *ctx)
 	int sockfd = ctx->ret;
 	__u64 pid_tgid = bpf_get_current_pid_tgid();
 	__u32 tgid = (__u32)(pid_tgid >> 32);
+	if (sockfd < 0)
+		return 0;
+	proc_sock_stats_add_conn(tgid, false);
 	__u64 conn_key = gen_conn_key_id((__u64)tgid, (__u64)sockfd);
 	__u32 role = ROLE_SERVER;
 	socket_role_map__update(&conn_key, &role);
@@ -1968,6 +2223,108 @@ TPPROG(sys_enter_connect)(struct syscall_comm_enter_ctx *ctx)
 	__u64 conn_key = gen_conn_key_id((__u64)tgid, (__u64)sockfd);
 	__u32 role = ROLE_CLIENT;
 	socket_role_map__update(&conn_key, &role);
+	if (is_proc_sock_stats_enabled())
+		connect_fd_map__update(&pid_tgid, &sockfd);
+	return 0;
+}
+
+// /sys/kernel/debug/tracing/events/syscalls/sys_enter_setsockopt/format
+TPPROG(sys_enter_setsockopt) (struct syscall_setsockopt_enter_ctx *ctx) {
+	int optname = (int)ctx->optname;
+	if ((int)ctx->level != SOL_TLS ||
+	    (optname != TLS_TX && optname != TLS_RX))
+		return 0;
+
+	__u64 id = bpf_get_current_pid_tgid();
+	struct ktls_args_t args = {
+		.conn_key = gen_conn_key_id(id >> 32, ctx->fd),
+		.flag = optname == TLS_TX ? KTLS_TX : KTLS_RX,
+	};
+	ktls_args_map__update(&id, &args);
+	return 0;
+}
+
+// /sys/kernel/debug/tracing/events/syscalls/sys_exit_setsockopt/format
+TPPROG(sys_exit_setsockopt) (struct syscall_comm_exit_ctx *ctx) {
+	__u64 id = bpf_get_current_pid_tgid();
+	struct ktls_args_t *args = ktls_args_map__lookup(&id);
+	if (args == NULL)
+		return 0;
+
+	if ((int)ctx->ret == 0) {
+		__u64 conn_key = args->conn_key;
+		__u32 flags = args->flag;
+		__u32 *ktls = ktls_conn_map__lookup(&conn_key);
+		if (ktls)
+			flags |= *ktls;
+		ktls_conn_map__update(&conn_key, &flags);
+		/*
+		 * The protocol inferred from the TLS handshake does not apply to
+		 * the plaintext, the protocol of the socket is inferred again.
+		 */
+		delete_socket_info(conn_key, socket_info_map__lookup(&conn_key));
+	}
+
+	ktls_args_map__delete(&id);
+	return 0;
+}
+
+#ifndef EINPROGRESS
+#define EINPROGRESS 115
+#endif
+
+// /sys/kernel/debug/tracing/events/syscalls/sys_exit_connect/format
+TPPROG(sys_exit_connect)(struct syscall_comm_exit_ctx *ctx)
+{
+	int ret = ctx->ret;
+	__u64 id = bpf_get_current_pid_tgid();
+	__u32 tgid = (__u32)(id >> 32);
+	int *fd = connect_fd_map__lookup(&id);
+	if (fd == NULL)
+		return 0;
+
+	int sockfd = *fd;
+	connect_fd_map__delete(&id);
+	if (ret != -EINPROGRESS) {
+		proc_sock_stats_add_conn(tgid, ret < 0);
+		return 0;
+	}
+
+	// The result of a non-blocking connect() is known later
+	__u32 k0 = 0;
+	struct member_fields_offset *offset = members_offset__lookup(&k0);
+	if (!offset)
+		return 0;
+
+	CHECK_OFFSET_READY(sockfd);
+
+	__u64 sock_addr = (__u64)get_socket_from_fd(sockfd, offset);
+	if (sock_addr)
+		connect_pending_map__update(&sock_addr, &tgid);
+	return 0;
+}
+
+KPROG(tcp_finish_connect) (struct pt_regs* ctx) {
+	__u64 sk_addr = (__u64)PT_REGS_PARM1(ctx);
+	__u32 *tgid = connect_pending_map__lookup(&sk_addr);
+	if (tgid == NULL)
+		return 0;
+
+	proc_sock_stats_add_conn(*tgid, false);
+	connect_pending_map__delete(&sk_addr);
+	return 0;
+}
+
+KPROG(tcp_retransmit_skb) (struct pt_regs* ctx) {
+	__u64 sk_addr = (__u64)PT_REGS_PARM1(ctx);
+	__u32 *tgid = sk_tgid_map__lookup(&sk_addr);
+	if (tgid == NULL)
+		return 0;
+
+	struct proc_sock_stats *stats = proc_sock_stats_map__lookup(tgid);
+	if (stats)
+		__sync_fetch_and_add(&stats->retransmits, 1);
+
 	return 0;
 }
 
@@ -296,13 +296,9 @@ static __inline bool is_tcp_conn_interface(void *conn,
 	return info ? i.type == info->net_TCPConn_itab : false;
 }
 
-static __inline int get_fd_from_tcp_conn_interface(void *conn,
-						   struct ebpf_proc_info *info)
+static __inline int get_fd_from_tcp_conn(void *conn,
+					 struct ebpf_proc_info *info)
 {
-	if (!is_tcp_conn_interface(conn, info)) {
-		return -1;
-	}
-
 	int offset_fd_sysfd = info->offsets[OFFSET_IDX_SYSFD_POLL_FD];
 	if (offset_fd_sysfd < 0)
 		return -1;
@@ -317,6 +313,16 @@ static __inline int get_fd_from_tcp_conn_interface(void *conn,
 	return fd;
 }
 
+static __inline int get_fd_from_tcp_conn_interface(void *conn,
+						   struct ebpf_proc_info *info)
+{
+	if (!is_tcp_conn_interface(conn, info)) {
+		return -1;
+	}
+
+	return get_fd_from_tcp_conn(conn, info);
+}
+
 static __inline int get_fd_from_tls_conn_struct(void *conn,
 						struct ebpf_proc_info *info)
 {
@@ -324,6 +330,11 @@ static __inline int get_fd_from_tls_conn_struct(void *conn,
 	if (offset_conn_conn < 0)
 		return -1;
 
+	// Stripped binaries have no itab symbols, crypto/tls.Conn.conn
+	// is taken as a *net.TCPConn then.
+	if (!info->net_TCPConn_itab)
+		return get_fd_from_tcp_conn(conn + offset_conn_conn, info);
+
 	return get_fd_from_tcp_conn_interface(conn + offset_conn_conn, info);
 }
 
@@ -82,6 +82,8 @@ pub const FEATURE_UPROBE_GOLANG_SYMBOL: c_int = 0;
 pub const FEATURE_UPROBE_OPENSSL: c_int = 1;
 #[allow(dead_code)]
 pub const FEATURE_UPROBE_GOLANG: c_int = 2;
+#[allow(dead_code)]
+pub const FEATURE_PROFILE_MEMORY: c_int = 3;
 
 //L7层协议是否需要重新核实
 #[allow(dead_code)]
@@ -116,6 +118,18 @@ pub const DATA_SOURCE_GO_HTTP2_UPROBE: u8 = 2;
 pub const DATA_SOURCE_OPENSSL_UPROBE: u8 = 3;
 #[allow(dead_code)]
 pub const DATA_SOURCE_IO_EVENT: u8 = 4;
+#[allow(dead_code)]
+pub const DATA_SOURCE_KTLS: u8 = 6;
+
+// Stack trace message event types of the profiler
+#[allow(dead_code)]
+pub const PROFILER_EVENT_ON_CPU: u32 = 1;
+#[allow(dead_code)]
+pub const PROFILER_EVENT_OFF_CPU: u32 = 2;
+#[allow(dead_code)]
+pub const PROFILER_EVENT_MEM_ALLOC: u32 = 3;
+#[allow(dead_code)]
+pub const PROFILER_EVENT_MEM_INUSE: u32 = 4;
 
 // 消息类型
 // 目前除了 source=EBPF_TYPE_GO_HTTP2_UPROBE 以外,都不能保证这个方向的正确性.
@@ -226,8 +240,49 @@ pub struct SK_BPF_DATA {
     pub cap_data: *mut c_char, // 内核送到用户空间的数据地址
 }
 
+impl tuple_t {
+    // AF_UNIX sockets have addr_len 0, raddr and laddr hold the socket path
+    pub fn is_unix_socket(&self) -> bool {
+        self.addr_len == 0
+    }
+
+    pub fn unix_socket_path(&self) -> String {
+        let mut path = [0u8; 32];
+        path[..16].copy_from_slice(&self.raddr);
+        path[16..].copy_from_slice(&self.laddr);
+        // abstract socket names start with NUL and are shown as '@name'
+        let (prefix, name) = if path[0] == 0 {
+            ("@", &path[1..])
+        } else {
+            ("", &path[..])
+        };
+        let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        if end == 0 {
+            return String::new();
+        }
+        format!("{}{}", prefix, String::from_utf8_lossy(&name[..end]))
+    }
+}
+
 impl fmt::Display for SK_BPF_DATA {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tuple.is_unix_socket() {
+            return write!(
+                f,
+                "Timestamp: {} Socket: {} CapSeq: {} Process: {} Thread: {} MsgType: {} Direction: {} \n \
+                \tunix:{} L7: {} Len: {}",
+                self.timestamp,
+                self.socket_id,
+                self.cap_seq,
+                self.process_id,
+                self.thread_id,
+                self.msg_type,
+                self.direction,
+                self.tuple.unix_socket_path(),
+                self.l7_protocol_hint,
+                self.cap_len,
+            );
+        }
         let (local_ip, remote_ip) = if self.tuple.addr_len == 4 {
             (
                 {
@@ -317,6 +372,10 @@ pub struct SK_TRACE_STATS {
     pub kern_trace_map_used: u32, // 线程/协程追踪会话的hash表项当前值
     pub socket_map_max_reclaim: u32, // socket map表项进行清理的最大阈值，
     // 当前map的表项数量超过这个值进行map清理操作。
+    pub kern_socket_map_overflow: u64, // socket追踪的hash表满导致新表项插入失败的次数
+    pub kern_trace_map_overflow: u64,  // 线程/协程追踪会话的hash表满导致新表项插入失败的次数
+    pub socket_map_reclaim_count: u64, // socket追踪的hash表清理回收的表项数量
+    pub trace_map_reclaim_count: u64,  // 线程/协程追踪会话的hash表清理回收的表项数量
 
     /*
      * 数据处理统计
@@ -348,6 +407,33 @@ pub struct SK_TRACE_STATS {
     pub data_limit_max: u32,
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PROCESS_SOCKET_STATS {
+    pub pid: u32,
+    pub container_id: [u8; CONTAINER_ID_SIZE], // container id
+    // counters are cumulative since the process was first seen
+    pub conn_count: u64,  // connections established by connect()/accept()
+    pub conn_failed: u64, // failed connect() calls
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    pub retransmits: u64, // tcp retransmitted segments
+}
+
+impl Default for PROCESS_SOCKET_STATS {
+    fn default() -> Self {
+        Self {
+            pid: 0,
+            container_id: [0; CONTAINER_ID_SIZE],
+            conn_count: 0,
+            conn_failed: 0,
+            tx_bytes: 0,
+            rx_bytes: 0,
+            retransmits: 0,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct stack_profile_data {
@@ -368,8 +454,12 @@ pub struct stack_profile_data {
      * The profiler captures the number of occurrences of the same
      * data by querying with the quadruple
      * "<pid + stime + u_stack_id + k_stack_id + tid + cpu>" as the key.
+     * For off-CPU stack traces, it is the total off-CPU time in microseconds.
+     * For memory allocation stack traces, it is the allocated bytes.
+     * For in-use memory stack traces, it is the bytes not freed yet.
      */
     pub count: u32,
+    pub event_type: u32, // PROFILER_EVENT_ON_CPU, PROFILER_EVENT_OFF_CPU, PROFILER_EVENT_MEM_ALLOC or PROFILER_EVENT_MEM_INUSE
     /*
      * comm in task_struct(linux kernel), always 16 bytes
      * If the capture is a process, fill in the process name here.
@@ -404,6 +494,17 @@ extern "C" {
     pub fn set_data_limit_max(limit_size: c_int) -> c_int;
     pub fn set_go_tracing_timeout(timeout: c_int) -> c_int;
     pub fn set_io_event_collect_mode(mode: c_int) -> c_int;
+    pub fn set_unix_socket_enabled(enabled: bool) -> c_int;
+    pub fn set_proc_sock_stats_enabled(enabled: bool) -> c_int;
+    /*
+     * Copy per-process socket statistics into @stats, entries of exited
+     * processes are removed after being copied for the last time.
+     * @stats : Array to store the statistics
+     * @count : The size of the @stats array
+     *
+     * @return the number of entries copied, or a negative value on failure.
+     */
+    pub fn collect_process_socket_stats(stats: *mut PROCESS_SOCKET_STATS, count: c_int) -> c_int;
     pub fn set_io_event_minimal_duration(duration: c_ulonglong) -> c_int;
     pub fn set_allow_port_bitmap(bitmap: *const c_uchar) -> c_int;
     pub fn set_bypass_port_bitmap(bitmap: *const c_uchar) -> c_int;
@@ -436,7 +537,7 @@ extern "C" {
      * performed on the port numbers and range "443, 4467-5678." If the inference
      * fails, data will be discarded.
      *
-     * Note: that the default value for 'TLS' in 'l7-protocol-ports' is "443".
+     * Note: that the default value for 'TLS' in 'l7-protocol-ports' is "1-65535".
      */
     pub fn set_protocol_ports_bitmap(proto_type: c_int, ports: *const c_char) -> c_int;
 
@@ -582,6 +683,35 @@ extern "C" {
      */
     pub fn set_profiler_cpu_aggregation(flag: c_int) -> c_int;
 
+    /*
+     * Enable off-CPU profiling, the time tasks spend blocked (on locks,
+     * I/O, sleeping ...) is recorded with the stack traces where they
+     * are switched out. It needs to be called before the profiler starts.
+     *
+     * @min_block_us:
+     *   Minimum off-CPU time (microseconds) of a blocking to be recorded,
+     *   0 disables off-CPU profiling.
+     *
+     * @returns 0 on success, < 0 on error
+     */
+    pub fn set_profiler_offcpu(min_block_us: c_int) -> c_int;
+
+    /*
+     * Enable memory allocation profiling, malloc(), calloc(), realloc()
+     * and free() of the processes matching the regex of
+     * FEATURE_PROFILE_MEMORY are hooked, allocations are sampled by size
+     * and the sampled allocations not freed yet are output as in-use
+     * memory. Java allocations are sampled by the Java agent. It needs to
+     * be called before the profiler starts.
+     *
+     * @sample_bytes:
+     *   Average bytes allocated between two samples, 0 disables memory
+     *   profiling.
+     *
+     * @returns 0 on success, < 0 on error
+     */
+    pub fn set_profiler_memory(sample_bytes: c_int) -> c_int;
+
     /*
      * test flame graph
      */
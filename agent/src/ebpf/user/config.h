@@ -32,6 +32,8 @@
 #define MAP_KPROBE_PORT_BITMAP_NAME	"__kprobe_port_bitmap"
 #define MAP_ADAPT_KERN_UID_NAME		"__adapt_kern_uid_map"
 #define MAP_PROTO_PORTS_BITMAPS_NAME	"__proto_ports_bitmap"
+#define MAP_PROC_SOCK_STATS_NAME	"__proc_sock_stats_map"
+#define MAP_KTLS_CONN_NAME		"__ktls_conn_map"
 
 //Program jmp tables
 #define MAP_PROGS_JMP_KP_NAME		"__progs_jmp_kp_map"
@@ -133,6 +135,9 @@ enum {
 #define MAP_STACK_A_NAME	"__stack_map_a"
 #define MAP_STACK_B_NAME	"__stack_map_b"
 #define MAP_PROFILER_STATE_MAP	"__profiler_state_map"
+#define MAP_MEM_STACK_NAME	"__mem_stack_map"
+#define MAP_MEM_INUSE_NAME	"__mem_inuse_map"
+#define MAP_MEM_INUSE_STACK_NAME	"__mem_inuse_stack_map"
 
 #define STRINGIFIER_STACK_STR_HASH_BUCKETS_NUM	8192
 #define STRINGIFIER_STACK_STR_HASH_MEM_SZ	(1ULL << 30) // 1Gbytes
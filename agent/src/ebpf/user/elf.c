@@ -539,3 +539,121 @@ int elf_info_collect(struct elf_info *elf_info, const void *buf, size_t buf_sz)
 
 	return ret;
 }
+
+/*
+ * .gopclntab header magic, see debug/gosym/pclntab.go
+ */
+#define GO_PCLNTAB_MAGIC_12	0xfffffffb	// go1.2 ~ go1.15
+#define GO_PCLNTAB_MAGIC_116	0xfffffffa	// go1.16, go1.17
+#define GO_PCLNTAB_MAGIC_118	0xfffffff0	// go1.18, go1.19
+#define GO_PCLNTAB_MAGIC_120	0xfffffff1	// go1.20+
+
+static inline uint64_t pclntab_read(const char *p, int size)
+{
+	return size == 8 ? *(uint64_t *) p : *(uint32_t *) p;
+}
+
+/*
+ * go_pclntab_find_func -- Resolve a function from the Go function table.
+ * @path: Go executable file path
+ * @name: function name, e.g. "crypto/tls.(*Conn).Write"
+ * @entry: function entry address (virtual address)
+ * @size: function size
+ *
+ * Stripped Go binaries lose .symtab, but .gopclntab is needed by the
+ * runtime for stack unwinding and is always kept.
+ *
+ * Return 0 if found, otherwise -1.
+ */
+int go_pclntab_find_func(const char *path, const char *name,
+			 uint64_t * entry, uint64_t * size)
+{
+	Elf *e;
+	int fd;
+	int ret = -1;
+
+	if (openelf(path, &e, &fd) < 0)
+		return -1;
+
+	Elf_Data *data = get_sec_elf_data(e, ".gopclntab");
+	if (data == NULL || data->d_buf == NULL || data->d_size < 16)
+		goto exit;
+
+	const char *tab = (const char *)data->d_buf;
+	size_t tab_sz = data->d_size;
+	uint32_t magic = *(uint32_t *) tab;
+	int ptr_sz = tab[7];
+	if (tab[4] != 0 || tab[5] != 0 || (ptr_sz != 4 && ptr_sz != 8))
+		goto exit;
+
+	/*
+	 * functab is a list of (entry, funcoff) pairs followed by the end
+	 * address of the last function. Each func starts with its entry
+	 * followed by the int32 offset of the name.
+	 */
+	uint64_t nfunc = pclntab_read(tab + 8, ptr_sz);
+	uint64_t text_start = 0, funcname_off, funcdata_off, functab_off;
+	int field_sz;
+	switch (magic) {
+	case GO_PCLNTAB_MAGIC_12:
+		funcname_off = 0;
+		funcdata_off = 0;
+		functab_off = 8 + ptr_sz;
+		field_sz = ptr_sz;
+		break;
+	case GO_PCLNTAB_MAGIC_116:
+		funcname_off = pclntab_read(tab + 8 + 2 * ptr_sz, ptr_sz);
+		funcdata_off = pclntab_read(tab + 8 + 6 * ptr_sz, ptr_sz);
+		functab_off = funcdata_off;
+		field_sz = ptr_sz;
+		break;
+	case GO_PCLNTAB_MAGIC_118:
+	case GO_PCLNTAB_MAGIC_120:
+		/* Since go1.18 entries are 32-bit offsets from runtime.text. */
+		text_start = pclntab_read(tab + 8 + 2 * ptr_sz, ptr_sz);
+		funcname_off = pclntab_read(tab + 8 + 3 * ptr_sz, ptr_sz);
+		funcdata_off = pclntab_read(tab + 8 + 7 * ptr_sz, ptr_sz);
+		functab_off = funcdata_off;
+		field_sz = 4;
+		if (text_start == 0) {
+			/* PIE binaries fill it in with a relocation. */
+			GElf_Shdr shdr;
+			Elf_Scn *scn = get_scn_by_sec_name(e, ".text");
+			if (scn == NULL || !gelf_getshdr(scn, &shdr))
+				goto exit;
+			text_start = shdr.sh_addr;
+		}
+		break;
+	default:
+		goto exit;
+	}
+
+	size_t name_len = strlen(name);
+	for (uint64_t i = 0; i < nfunc; i++) {
+		uint64_t p = functab_off + i * 2 * field_sz;
+		if (p + 3 * field_sz > tab_sz)
+			break;
+
+		uint64_t f = funcdata_off + pclntab_read(tab + p + field_sz,
+							 field_sz);
+		if (f + field_sz + 4 > tab_sz)
+			continue;
+
+		uint64_t n = funcname_off + *(int32_t *) (tab + f + field_sz);
+		if (n + name_len + 1 > tab_sz ||
+		    memcmp(tab + n, name, name_len + 1))
+			continue;
+
+		uint64_t start = pclntab_read(tab + p, field_sz);
+		uint64_t end = pclntab_read(tab + p + 2 * field_sz, field_sz);
+		*entry = text_start + start;
+		*size = end > start ? end - start : 0;
+		ret = 0;
+		break;
+	}
+
+exit:
+	elf_end(e);
+	close(fd);
+	return ret;
+}
@@ -62,4 +62,6 @@ int elf_info_collect(struct elf_info *elf_info, const void *buf, size_t buf_sz);
 int find_sym_by_idx(Elf * e, Elf_Scn * syms_scn, int sym_idx, GElf_Sym * sym);
 int find_prog_func_sym(Elf * e, Elf_Scn * syms_scn, size_t prog_shndx,
 		       GElf_Sym * sym);
+int go_pclntab_find_func(const char *path, const char *name,
+			 uint64_t * entry, uint64_t * size);
 #endif /*DF_TRACE_ELF_H */
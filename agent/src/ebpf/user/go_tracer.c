@@ -72,6 +72,9 @@ static struct data_members offsets[] = {
 		.field_name = "goid",
 		.idx = OFFSET_IDX_GOID_RUNTIME_G,
 		.default_offset = 152,
+		// go1.23 added runtime.g.syscallbp before goid
+		.new_version = GO_VERSION(1, 23, 0),
+		.new_default_offset = 160,
 	},
 	{
 		.structure = "crypto/tls.Conn",
@@ -416,9 +419,10 @@ bool fetch_go_elf_version(const char *path, struct version_info * go_ver)
 	static const int go_version_offset = 0x21;
 	if (data->d_size > go_version_offset) {
 		buf = info + go_version_offset;
-		num = sscanf(buf, "go%d.%d", &go_ver->major, &go_ver->minor);
 		go_ver->revision = 0;
-		if (num == 2) {
+		num = sscanf(buf, "go%d.%d.%d", &go_ver->major, &go_ver->minor,
+			     &go_ver->revision);
+		if (num >= 2) {
 			res = true;
 			goto exit;
 		}
@@ -443,9 +447,10 @@ bool fetch_go_elf_version(const char *path, struct version_info * go_ver)
 	if (buf == NULL || len <= 0)
 		goto exit;
 
-	num = sscanf(buf, "go%d.%d", &go_ver->major, &go_ver->minor);
 	go_ver->revision = 0;
-	if (num != 2)
+	num = sscanf(buf, "go%d.%d.%d", &go_ver->major, &go_ver->minor,
+		     &go_ver->revision);
+	if (num < 2)
 		ebpf_warning("sscanf() go version failed. num = %d\n", num);
 	else
 		res = true;
@@ -592,8 +597,13 @@ static int resolve_bin_file(const char *path, int pid,
 			    struct_member_offset_analyze(binary_path,
 							 off->structure,
 							 off->field_name);
-			if (offset == ETR_INVAL)
-				offset = off->default_offset;
+			if (offset == ETR_INVAL) {
+				if (off->new_version &&
+				    p_info->info.version >= off->new_version)
+					offset = off->new_default_offset;
+				else
+					offset = off->default_offset;
+			}
 
 			p_info->info.offsets[off->idx] = offset;
 		}
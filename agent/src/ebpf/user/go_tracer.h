@@ -26,6 +26,10 @@ struct data_members {
 	const char *field_name;
 	enum offsets_index idx;
 	int default_offset;
+	// The layout changed since this Go version (GO_VERSION), and
+	// new_default_offset is used for binaries without DWARF info.
+	int new_version;
+	int new_default_offset;
 };
 
 // Pid correspond to offsets.
@@ -59,6 +59,19 @@ FILE *perf_map_log_file_ptr = NULL;
 int g_perf_map_file_size_limit;
 int g_perf_map_file_size;
 
+/*
+ * Sampling interval (bytes) of Java allocations for memory profiling,
+ * 0: allocations are not sampled.
+ */
+jint g_alloc_sample_interval;
+
+/*
+ * Every attach gets a new JVMTI environment, allocations are sampled in
+ * the environment of the first attach, so that later attaches change the
+ * sampling instead of adding another.
+ */
+jvmtiEnv *g_alloc_jvmti;
+
 #define _(e)                                                                \
 	if (e != JNI_OK) {                                                  \
 		df_log("DF java agent failed, %s, error code: %d.", #e, e); \
@@ -136,8 +149,16 @@ jint df_agent_config(char *opts)
 	start = ++p;
 	if (start == NULL)
 		return JNI_ERR;
+	p = strchr(start, ',');
+	if (p != NULL)
+		*p = '\0';
 	snprintf(perf_log_file_path, sizeof(perf_log_file_path), "%s", start);
 
+	/* g_alloc_sample_interval, not given by earlier deepflow-agent */
+	g_alloc_sample_interval = 0;
+	if (p != NULL)
+		g_alloc_sample_interval = atoi(++p);
+
 	return JNI_OK;
 }
 
@@ -308,6 +329,31 @@ cbCompiledMethodUnload(jvmtiEnv * jvmti, jmethodID method, const void *address)
 	df_write_symbol(address, 0, "");
 }
 
+JNIEXPORT __attribute__ ((noinline))
+void df_java_alloc_sample(uint64_t bytes)
+{
+	/*
+	 * The memory profiler of deepflow-agent hooks this function with
+	 * a uprobe to get the allocated bytes and the stack trace of the
+	 * Java thread, it does nothing else.
+	 */
+	__asm__ __volatile__("":::"memory");
+}
+
+void JNICALL
+cbSampledObjectAlloc(jvmtiEnv * jvmti, JNIEnv * jni, jthread thread,
+		     jobject object, jclass object_klass, jlong size)
+{
+	/*
+	 * A sample is taken about every g_alloc_sample_interval bytes
+	 * allocated in TLABs or outside TLABs, it represents the bytes
+	 * of the interval unless the object is larger.
+	 */
+	uint64_t bytes = size > g_alloc_sample_interval ?
+	    size : g_alloc_sample_interval;
+	df_java_alloc_sample(bytes);
+}
+
 jvmtiError set_callback_funs(jvmtiEnv * jvmti)
 {
 	jvmtiEventCallbacks callbacks;
@@ -316,6 +362,7 @@ jvmtiError set_callback_funs(jvmtiEnv * jvmti)
 	callbacks.CompiledMethodLoad = &cbCompiledMethodLoad;
 	callbacks.CompiledMethodUnload = &cbCompiledMethodUnload;
 	callbacks.DynamicCodeGenerated = &cbDynamicCodeGenerated;
+	callbacks.SampledObjectAlloc = &cbSampledObjectAlloc;
 
 	jvmtiError err = (*jvmti)->SetEventCallbacks(jvmti, &callbacks,
 						     (jint) sizeof(callbacks));
@@ -365,6 +412,57 @@ jint set_notification_modes(jvmtiEnv * jvmti, jvmtiEventMode mode)
 	return JNI_OK;
 }
 
+/*
+ * Sample Java allocations with JVMTI (JDK 11+), sampling stays enabled
+ * after the agent returns, until a later attach disables it.
+ */
+jint set_alloc_sampling(jvmtiEnv * jvmti)
+{
+	jvmtiError error;
+
+	if (g_alloc_jvmti == NULL)
+		g_alloc_jvmti = jvmti;
+	jvmti = g_alloc_jvmti;
+
+	if (g_alloc_sample_interval > 0) {
+		jvmtiCapabilities capabilities;
+		memset(&capabilities, 0, sizeof(jvmtiCapabilities));
+		capabilities.can_generate_sampled_object_alloc_events = 1;
+		error = (*jvmti)->AddCapabilities(jvmti, &capabilities);
+		if (error != JVMTI_ERROR_NONE) {
+			jvmti_err_log(jvmti, error,
+				      "Unable to get capability of sampling "
+				      "allocations.");
+			return JNI_ERR;
+		}
+
+		error = (*jvmti)->SetHeapSamplingInterval(jvmti,
+							  g_alloc_sample_interval);
+		if (error != JVMTI_ERROR_NONE) {
+			jvmti_err_log(jvmti, error,
+				      "Unable to set heap sampling interval.");
+			return JNI_ERR;
+		}
+
+		error = (*jvmti)->SetEventNotificationMode(jvmti, JVMTI_ENABLE,
+							   JVMTI_EVENT_SAMPLED_OBJECT_ALLOC,
+							   NULL);
+		if (error != JVMTI_ERROR_NONE) {
+			jvmti_err_log(jvmti, error,
+				      "Unable to set notification mode for SampledObjectAlloc.");
+			return JNI_ERR;
+		}
+
+		return JNI_OK;
+	}
+
+	/* Fails without the capability, sampling was never enabled then. */
+	(*jvmti)->SetEventNotificationMode(jvmti, JVMTI_DISABLE,
+					   JVMTI_EVENT_SAMPLED_OBJECT_ALLOC,
+					   NULL);
+	return JNI_OK;
+}
+
 jint replay_callbacks(jvmtiEnv * jvmti)
 {
 	jvmtiError error;
@@ -422,6 +520,11 @@ Agent_OnAttach(JavaVM * vm, char *options, void *reserved)
 
 	df_log("- JVMTI symbolization agent startup sequence complete.");
 
+	/* Symbolization still works if allocations can't be sampled. */
+	if (set_alloc_sampling(jvmti) == JNI_OK && g_alloc_sample_interval > 0)
+		df_log("- JVMTI allocation sampling interval: %d",
+		       g_alloc_sample_interval);
+
 	close_files();
 	return 0;
 }
@@ -24,6 +24,7 @@
 #include "../../vec.h"
 #include "../../tracer.h"
 #include "../../socket.h"
+#include "../mem_profiler.h"
 #include "gen_syms_file.h"
 #include "config.h"
 #include "df_jattach.h"
@@ -43,16 +44,22 @@ void gen_java_symbols_file(int pid, bool * need_update)
 		return;
 	}
 
+	/*
+	 * The agent samples Java allocations for memory profiling if the
+	 * sampling interval is not 0.
+	 */
+	u64 alloc_sample_bytes = java_alloc_sample_bytes(pid);
 	char args[PERF_PATH_SZ * 2];
 	if (!is_same_mntns(pid)) {
-		snprintf(args, sizeof(args), "%d %d,%s,%s", pid,
+		snprintf(args, sizeof(args), "%d %d,%s,%s,%lu", pid,
 			 g_java_syms_write_bytes_max,
-			 PERF_MAP_FILE_FMT, PERF_MAP_LOG_FILE_FMT);
+			 PERF_MAP_FILE_FMT, PERF_MAP_LOG_FILE_FMT,
+			 alloc_sample_bytes);
 	} else {
-		snprintf(args, sizeof(args), "%d %d,%s,%s", pid,
+		snprintf(args, sizeof(args), "%d %d,%s,%s,%lu", pid,
 			 g_java_syms_write_bytes_max,
 			 DF_AGENT_LOCAL_PATH_FMT ".map",
-			 DF_AGENT_LOCAL_PATH_FMT ".log");
+			 DF_AGENT_LOCAL_PATH_FMT ".log", alloc_sample_bytes);
 	}
 
 	exec_command(DF_JAVA_ATTACH_CMD, args);
@@ -0,0 +1,356 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+/*
+ * Memory allocation profiling
+ *
+ * The allocation functions of the processes matching the regular expression
+ * of FEATURE_PROFILE_MEMORY are hooked with uprobes of the continuous profiler,
+ * the BPF programs sample the allocations by size (see 'mem_alloc_sample()' in
+ * 'kernel/perf_profiler.c'). The returns of the allocation functions and free()
+ * are also hooked to track the sampled allocations not freed yet, which are
+ * output as in-use memory.
+ *
+ * The allocator of a process is looked up in the following order:
+ *   1 The executable defining malloc(), e.g. statically linked or linked
+ *     with a static allocator.
+ *   2 libjemalloc.so
+ *   3 libc.so
+ *
+ * Java objects are not allocated with malloc(), the Java agent (loaded by the
+ * Java symbols update through jattach) samples allocations in TLABs and outside
+ * TLABs with JVMTI when the process matches, and calls df_java_alloc_sample()
+ * for each sample, which is hooked in the agent library mapped by the process.
+ *
+ * The processes are scanned periodically, new processes are hooked and the
+ * uprobes and the in-use allocations of exited processes are removed.
+ */
+
+#ifndef AARCH64_MUSL
+#include <dirent.h>
+#include <bcc/bcc_elf.h>
+#include "../config.h"
+#include "../common.h"
+#include "../log.h"
+#include "../tracer.h"
+#include "../load.h"
+#include "perf_profiler.h"
+#include "mem_profiler.h"
+#include "java/config.h"
+
+extern u64 mem_sample_bytes;
+
+static struct symbol mem_alloc_syms[] = {
+	{
+		.type = MEMORY_UPROBE,
+		.symbol = "malloc",
+		.probe_func = "uprobe_malloc_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = MEMORY_UPROBE,
+		.symbol = "calloc",
+		.probe_func = "uprobe_calloc_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = MEMORY_UPROBE,
+		.symbol = "realloc",
+		.probe_func = "uprobe_realloc_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = MEMORY_UPROBE,
+		.symbol = "malloc",
+		.probe_func = "uprobe_mem_alloc_exit",
+		.is_probe_ret = true,
+	},
+	{
+		.type = MEMORY_UPROBE,
+		.symbol = "calloc",
+		.probe_func = "uprobe_mem_alloc_exit",
+		.is_probe_ret = true,
+	},
+	{
+		.type = MEMORY_UPROBE,
+		.symbol = "realloc",
+		.probe_func = "uprobe_mem_alloc_exit",
+		.is_probe_ret = true,
+	},
+	{
+		.type = MEMORY_UPROBE,
+		.symbol = "free",
+		.probe_func = "uprobe_free_enter",
+		.is_probe_ret = false,
+	},
+};
+
+static struct symbol java_alloc_sym = {
+	.type = MEMORY_UPROBE,
+	.symbol = "df_java_alloc_sample",
+	.probe_func = "uprobe_java_alloc_sample",
+	.is_probe_ret = false,
+};
+
+static const char *alloc_libs[] = { "jemalloc", "c" };
+
+static struct bcc_symbol_option elf_sym_option = {
+	.use_debug_file = 0,
+	.check_debug_file_crc = 0,
+	.lazy_symbolize = 1,
+	.use_symbol_type = (1 << STT_FUNC) | (1 << STT_GNU_IFUNC),
+};
+
+static int find_malloc_callback(const char *name, uint64_t addr,
+				uint64_t size, void *payload)
+{
+	bool *found = payload;
+	// Undefined symbols of dynamically linked binaries
+	if (addr && !strcmp(name, "malloc")) {
+		*found = true;
+		return -1;
+	}
+	return 0;
+}
+
+static bool elf_defines_malloc(const char *path)
+{
+	bool found = false;
+	if (bcc_elf_foreach_sym(path, find_malloc_callback, &elf_sym_option,
+				&found))
+		return false;
+	return found;
+}
+
+static char *alloc_binary_of(int pid)
+{
+	char *path = get_elf_path_by_pid(pid);
+	if (path && elf_defines_malloc(path))
+		return path;
+	free(path);
+
+	int i;
+	for (i = 0; i < NELEMS(alloc_libs); i++) {
+		path = get_so_path_by_pid(pid, alloc_libs[i]);
+		if (path)
+			return path;
+	}
+
+	return NULL;
+}
+
+/*
+ * The agent library may have been removed from the file system of the
+ * process after it is loaded, it is still reachable through map_files.
+ */
+static char *java_agent_path_of(int pid)
+{
+	char maps_path[64], line[PATH_MAX + 128], *path = NULL;
+	char map_files_path[64];
+	snprintf(maps_path, sizeof(maps_path), "/proc/%d/maps", pid);
+	FILE *fp = fopen(maps_path, "r");
+	if (fp == NULL)
+		return NULL;
+
+	unsigned long start, end, offset;
+	char name[PATH_MAX];
+	while (fgets(line, sizeof(line), fp)) {
+		name[0] = '\0';
+		if (sscanf(line, "%lx-%lx %*s %lx %*s %*d %s", &start, &end,
+			   &offset, name) != 4 || offset != 0)
+			continue;
+		if (strstr(name, "/" AGENT_LIB_NAME) == NULL &&
+		    strstr(name, "/" AGENT_MUSL_LIB_NAME) == NULL)
+			continue;
+		snprintf(map_files_path, sizeof(map_files_path),
+			 "/proc/%d/map_files/%lx-%lx", pid, start, end);
+		path = strdup(map_files_path);
+		break;
+	}
+
+	fclose(fp);
+	return path;
+}
+
+static bool is_hooked(struct tracer_probes_conf *conf, int pid,
+		      const char *probe_func)
+{
+	struct symbol_uprobe *usym;
+	list_for_each_entry(usym, &conf->uprobe_syms_head, list) {
+		if (usym->type == MEMORY_UPROBE && usym->pid == pid &&
+		    !strcmp(usym->probe_func, probe_func))
+			return true;
+	}
+	return false;
+}
+
+static void mem_profiler_register(int pid, struct tracer_probes_conf *conf)
+{
+	char *path = alloc_binary_of(pid);
+	if (path == NULL)
+		return;
+
+	int i;
+	struct symbol_uprobe *usym;
+	for (i = 0; i < NELEMS(mem_alloc_syms); i++) {
+		usym = resolve_and_gen_uprobe_symbol(path, &mem_alloc_syms[i],
+						     0, pid);
+		if (usym == NULL)
+			continue;
+		add_uprobe_symbol(pid, usym, conf);
+	}
+
+	ebpf_info(LOG_CP_TAG "memory profile uprobe, pid:%d, path:%s\n", pid,
+		  path);
+	free(path);
+}
+
+static void java_alloc_register(int pid, struct tracer_probes_conf *conf)
+{
+	char *path = java_agent_path_of(pid);
+	if (path == NULL)
+		return;
+
+	struct symbol_uprobe *usym;
+	usym = resolve_and_gen_uprobe_symbol(path, &java_alloc_sym, 0, pid);
+	if (usym != NULL) {
+		add_uprobe_symbol(pid, usym, conf);
+		ebpf_info(LOG_CP_TAG
+			  "memory profile java uprobe, pid:%d, path:%s\n",
+			  pid, path);
+	}
+	free(path);
+}
+
+/*
+ * The sampling interval of Java allocations passed to the Java agent of
+ * the process, 0 if the allocations of the process are not sampled.
+ */
+u64 java_alloc_sample_bytes(int pid)
+{
+	if (mem_sample_bytes == 0 ||
+	    !is_feature_enabled(FEATURE_PROFILE_MEMORY))
+		return 0;
+
+	char *path = get_elf_path_by_pid(pid);
+	bool matched = is_feature_matched(FEATURE_PROFILE_MEMORY, path);
+	free(path);
+	return matched ? mem_sample_bytes : 0;
+}
+
+static void clear_exited_probes(struct bpf_tracer *tracer)
+{
+	struct probe *probe;
+	struct list_head *p, *n;
+	struct symbol_uprobe *usym;
+
+	list_for_each_safe(p, n, &tracer->probes_head) {
+		probe = container_of(p, struct probe, list);
+		if (!(probe->type == UPROBE && probe->private_data != NULL))
+			continue;
+		usym = probe->private_data;
+		if (usym->type != MEMORY_UPROBE)
+			continue;
+
+		// The pid may be reused by a new process
+		if (is_process(usym->pid) &&
+		    get_process_starttime(usym->pid) == usym->starttime)
+			continue;
+
+		if (probe_detach(probe)) {
+			ebpf_warning("probe_detach failed, path:%s, name:%s\n",
+				     usym->binary_path, usym->name);
+		}
+		free_probe_from_tracer(probe);
+	}
+}
+
+/* Remove the in-use allocations of exited processes. */
+static void clear_exited_allocs(struct bpf_tracer *tracer)
+{
+	struct ebpf_map *map =
+	    ebpf_obj__get_map_by_name(tracer->obj, MAP_MEM_INUSE_NAME);
+	if (map == NULL)
+		return;
+
+	struct mem_inuse_key_t key = {}, next_key;
+	struct list_head clear_elem_head;
+	init_list_head(&clear_elem_head);
+
+	while (bpf_get_next_key(map->fd, &key, &next_key) == 0) {
+		key = next_key;
+		if (!is_process(key.tgid))
+			insert_list(&key, sizeof(key), &clear_elem_head);
+	}
+
+	__reclaim_map(map->fd, &clear_elem_head);
+}
+
+void mem_profiler_update(struct bpf_tracer *tracer)
+{
+	struct dirent *entry = NULL;
+	DIR *fddir = NULL;
+	char *path = NULL;
+	int pid, count = 0;
+
+	if (!is_feature_enabled(FEATURE_PROFILE_MEMORY))
+		return;
+
+	fddir = opendir("/proc/");
+	if (!fddir) {
+		ebpf_warning(LOG_CP_TAG "Failed to open /proc.\n");
+		return;
+	}
+
+	pthread_mutex_lock(&tracer->mutex_probes_lock);
+	clear_exited_probes(tracer);
+	clear_exited_allocs(tracer);
+
+	while ((entry = readdir(fddir))) {
+		if (entry->d_type != DT_DIR)
+			continue;
+
+		pid = atoi(entry->d_name);
+		if (pid <= 1 || !is_user_process(pid))
+			continue;
+
+		path = get_elf_path_by_pid(pid);
+		if (is_feature_matched(FEATURE_PROFILE_MEMORY, path)) {
+			if (!is_hooked(tracer->tps, pid, "uprobe_malloc_enter"))
+				mem_profiler_register(pid, tracer->tps);
+			// The Java agent is loaded by the Java symbols update
+			if (!is_hooked(tracer->tps, pid,
+				       "uprobe_java_alloc_sample"))
+				java_alloc_register(pid, tracer->tps);
+		}
+		free(path);
+	}
+
+	tracer_uprobes_update(tracer);
+	tracer_hooks_process(tracer, HOOK_ATTACH, &count);
+	pthread_mutex_unlock(&tracer->mutex_probes_lock);
+
+	closedir(fddir);
+}
+#else /* defined AARCH64_MUSL */
+#include "../tracer.h"
+#include "mem_profiler.h"
+
+u64 java_alloc_sample_bytes(int pid)
+{
+	return 0;
+}
+#endif /* AARCH64_MUSL */
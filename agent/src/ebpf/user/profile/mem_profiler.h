@@ -0,0 +1,27 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#ifndef DF_USER_MEM_PROFILER_H
+#define DF_USER_MEM_PROFILER_H
+
+// Interval (seconds) of hooking new processes and unhooking exited ones
+#define MEM_PROFILER_UPDATE_INTERVAL 10
+
+#ifndef AARCH64_MUSL
+void mem_profiler_update(struct bpf_tracer *tracer);
+#endif /* AARCH64_MUSL */
+u64 java_alloc_sample_bytes(int pid);
+#endif /* DF_USER_MEM_PROFILER_H */
@@ -40,6 +40,7 @@
 #include "../perf_reader.h"
 #include "../bihash_8_8.h"
 #include "stringifier.h"
+#include "mem_profiler.h"
 #include "../table.h"
 #include <regex.h>
 #include "java/config.h"
@@ -63,7 +64,6 @@
 /* use for java symbols generate */
 #include "deepflow_jattach_bin.c"
 
-#define LOG_CP_TAG	"[CP] "
 #define CP_TRACER_NAME	"continuous_profiler"
 #define CP_PERF_PG_NUM	16
 
@@ -124,6 +124,25 @@ static bool regex_existed = false;
  */
 static volatile u64 cpu_aggregation_flag;
 
+/*
+ * Minimum off-CPU time (nanoseconds) of a blocking to be recorded,
+ * set by 'set_profiler_offcpu()' before the profiler starts. If it
+ * is 0, off-CPU profiling is disabled and the sched_switch tracepoint
+ * is not attached.
+ */
+static u64 offcpu_min_block_ns;
+
+/*
+ * Average bytes allocated between two memory allocation samples, set
+ * by 'set_profiler_memory()' before the profiler starts. If it is 0,
+ * memory profiling is disabled.
+ */
+u64 mem_sample_bytes;
+static u64 mem_profiler_update_time;
+
+/* Tracepoints and uprobes of off-CPU and memory profiling. */
+static struct tracer_probes_conf profiler_tps;
+
 /*
  * Cache hash: obtain folded stack trace string from stack ID.
  */
@@ -144,6 +163,8 @@ static void print_profiler_status(struct bpf_tracer *t, u64 iter_count,
 				  stack_str_hash_t * h,
 				  stack_trace_msg_hash_t * msg_h);
 static void print_cp_tracer_status(struct bpf_tracer *t);
+static void aggregate_mem_inuse(struct bpf_tracer *t,
+				stack_trace_msg_hash_t * msg_hash);
 
 /*
  * During the parsing process, it is possible for processes in procfs
@@ -161,6 +182,8 @@ static uint32_t cpdbg_timeout;
 /* Record all stack IDs in each iteration for quick retrieval. */
 struct stack_ids_bitmap stack_ids_a;
 struct stack_ids_bitmap stack_ids_b;
+/* Stack IDs in mem_stack_map referenced by in-use memory. */
+static struct stack_ids_bitmap mem_stack_ids;
 /* This vector table is used to remove a stack from the stack map. */
 static int *clear_stack_ids_a;
 static int *clear_stack_ids_b;
@@ -191,6 +214,17 @@ static inline stack_trace_msg_t *alloc_stack_trace_msg(int len)
 	return NULL;
 }
 
+static inline u32 stack_trace_event_type(struct stack_trace_key_t *v)
+{
+	if (v->off_cpu_ns > 0)
+		return PROFILER_EVENT_OFF_CPU;
+	if (v->alloc_bytes > 0)
+		return PROFILER_EVENT_MEM_ALLOC;
+	if (v->inuse_bytes > 0)
+		return PROFILER_EVENT_MEM_INUSE;
+	return PROFILER_EVENT_ON_CPU;
+}
+
 /* 
  * The invocation of this interface is always when the process name does
  * not match.
@@ -203,6 +237,7 @@ static void set_msg_kvp_by_comm(stack_trace_msg_kv_t * kvp,
 			v->comm, strlen(v->comm));
 	kvp->c_k.cpu = v->cpu;
 	kvp->c_k.pid = v->tgid;
+	kvp->c_k.event_type = stack_trace_event_type(v);
 	kvp->c_k.reserved = 0;
 	kvp->msg_ptr = pointer_to_uword(msg_value);
 }
@@ -213,12 +248,31 @@ static void set_msg_kvp(stack_trace_msg_kv_t * kvp,
 	kvp->k.tgid = v->tgid;
 	kvp->k.pid = v->pid;
 	kvp->k.stime = stime;
+	kvp->k.event_type = stack_trace_event_type(v);
 	kvp->k.cpu = v->cpu;
 	kvp->k.u_stack_id = (u32) v->userstack;
 	kvp->k.k_stack_id = (u32) v->kernstack;
 	kvp->msg_ptr = pointer_to_uword(msg_value);
 }
 
+/*
+ * The value of the stack trace added to the count of the message:
+ * the number of samples for on-CPU, the off-CPU time in microseconds
+ * for off-CPU, the allocated bytes for memory allocation, and the bytes
+ * not freed yet for in-use memory.
+ */
+static inline u32 stack_trace_value(struct stack_trace_key_t *v)
+{
+	u64 value = 1;
+	if (v->off_cpu_ns > 0)
+		value = v->off_cpu_ns / 1000;
+	else if (v->alloc_bytes > 0)
+		value = v->alloc_bytes;
+	else if (v->inuse_bytes > 0)
+		value = v->inuse_bytes;
+	return value > UINT32_MAX ? UINT32_MAX : (u32) value;
+}
+
 static void set_stack_trace_msg(stack_trace_msg_t * msg,
 				struct stack_trace_key_t *v,
 				bool matched,
@@ -283,7 +337,8 @@ static void set_stack_trace_msg(stack_trace_msg_t * msg,
 	}
 
 	msg->time_stamp = gettime(CLOCK_REALTIME, TIME_TYPE_NAN);
-	msg->count = 1;
+	msg->event_type = stack_trace_event_type(v);
+	msg->count = stack_trace_value(v);
 	msg->data_ptr = pointer_to_uword(&msg->data[0]);
 
 	/* Only use for test flame graph. */
@@ -470,6 +525,13 @@ static void push_and_release_stack_trace_msg(stack_trace_msg_hash_t * h,
 	last_push_time = curr_time;
 	push_count++;
 
+	/*
+	 * In-use memory is a snapshot taken once in each push interval,
+	 * it is skipped by the final release when the profiler exits.
+	 */
+	if (mem_sample_bytes > 0 && !is_force)
+		aggregate_mem_inuse(profiler_tracer, h);
+
 	stack_trace_msg_hash_foreach_key_value_pair(h, push_and_free_msg_kvp_cb,
 						    NULL);
 	/*
@@ -565,8 +627,11 @@ static void aggregate_stack_traces(struct bpf_tracer *t,
 		if (v->userstack == -EEXIST)
 			stack_trace_lost++;
 
-		add_stack_id_to_bitmap(v->kernstack, use_a_map);
-		add_stack_id_to_bitmap(v->userstack, use_a_map);
+		/* Stack IDs of in-use memory are in mem_stack_map. */
+		if (v->inuse_bytes == 0) {
+			add_stack_id_to_bitmap(v->kernstack, use_a_map);
+			add_stack_id_to_bitmap(v->userstack, use_a_map);
+		}
 
 		/* Total iteration count for this iteration. */
 		(*count)++;
@@ -624,7 +689,10 @@ static void aggregate_stack_traces(struct bpf_tracer *t,
 		    (msg_hash, (stack_trace_msg_hash_kv *) & kv,
 		     (stack_trace_msg_hash_kv *) & kv) == 0) {
 			__sync_fetch_and_add(&msg_hash->hit_hash_count, 1);
-			((stack_trace_msg_t *) kv.msg_ptr)->count++;
+			stack_trace_msg_t *msg = (stack_trace_msg_t *) kv.msg_ptr;
+			u32 value = stack_trace_value(v);
+			msg->count = msg->count > UINT32_MAX - value ?
+			    UINT32_MAX : msg->count + value;
 			continue;
 		}
 
@@ -697,6 +765,90 @@ static void aggregate_stack_traces(struct bpf_tracer *t,
 	vec_free(raw_stack_data);
 }
 
+/*
+ * Add the in-use bytes of the stacks in mem_inuse_stack_map into the
+ * messages as in-use memory stack traces. Stacks with no bytes in use
+ * or of exited processes are removed, and then the stack IDs no longer
+ * referenced are removed from mem_stack_map.
+ */
+static void aggregate_mem_inuse(struct bpf_tracer *t,
+				stack_trace_msg_hash_t * msg_hash)
+{
+	struct ebpf_map *stack_map =
+	    ebpf_obj__get_map_by_name(t->obj, MAP_MEM_INUSE_STACK_NAME);
+	struct ebpf_map *ids_map =
+	    ebpf_obj__get_map_by_name(t->obj, MAP_MEM_STACK_NAME);
+	if (stack_map == NULL || ids_map == NULL) {
+		ebpf_warning("[%s] map(name:%s or %s) is NULL.\n", __func__,
+			     MAP_MEM_INUSE_STACK_NAME, MAP_MEM_STACK_NAME);
+		return;
+	}
+
+	struct mem_stack_key_t key = {}, next_key;
+	struct list_head clear_elem_head;
+	init_list_head(&clear_elem_head);
+	memset(&mem_stack_ids, 0, sizeof(mem_stack_ids));
+	u64 bytes;
+
+	while (bpf_get_next_key(stack_map->fd, &key, &next_key) == 0) {
+		key = next_key;
+		if (bpf_lookup_elem(stack_map->fd, &key, &bytes))
+			continue;
+
+		char name[TASK_COMM_LEN];
+		memset(name, 0, sizeof(name));
+		u64 stime, netns_id;
+		void *info_p = NULL;
+		get_process_info_by_pid(key.tgid, &stime, &netns_id,
+					(char *)name, &info_p);
+		/* The bytes may be negative if frees race with allocations. */
+		if ((i64) bytes <= 0 || stime == 0) {
+			insert_list(&key, sizeof(key), &clear_elem_head);
+			continue;
+		}
+
+		if (key.stackid < 0 || key.stackid >= STACK_MAP_ENTRIES)
+			continue;
+		set_bitmap(mem_stack_ids.bitmap, key.stackid);
+
+		struct stack_trace_key_t v = { 0 };
+		v.tgid = v.pid = key.tgid;
+		v.cpu = CPU_INVALID;
+		strcpy_s_inline(v.comm, sizeof(v.comm), name, strlen(name));
+		v.kernstack = -1;
+		v.userstack = key.stackid;
+		v.inuse_bytes = bytes;
+
+		int ret = VEC_OK;
+		vec_add1(raw_stack_data, v, ret);
+		if (ret != VEC_OK) {
+			ebpf_warning("vec add failed\n");
+		}
+	}
+
+	/*
+	 * Stack trace strings are cached by stack ID, and the stack IDs of
+	 * mem_stack_map overlap those of stack_map_a and stack_map_b, so
+	 * the cache is cleaned before and after.
+	 */
+	u32 count = 0;
+	clean_stack_strs(&g_stack_str_hash);
+	aggregate_stack_traces(t, MAP_MEM_STACK_NAME, &g_stack_str_hash,
+			       msg_hash, &count, false);
+	clean_stack_strs(&g_stack_str_hash);
+
+	__reclaim_map(stack_map->fd, &clear_elem_head);
+
+	u32 id = 0, next_id;
+	while (bpf_get_next_key(ids_map->fd, &id, &next_id) == 0) {
+		id = next_id;
+		if (id >= STACK_MAP_ENTRIES
+		    || !is_set_bitmap(mem_stack_ids.bitmap, id))
+			insert_list(&id, sizeof(id), &clear_elem_head);
+	}
+	__reclaim_map(ids_map->fd, &clear_elem_head);
+}
+
 void set_enable_perf_sample(struct bpf_tracer *t, u64 enable_flag)
 {
 	if (bpf_table_set_value(t, MAP_PROFILER_STATE_MAP,
@@ -806,8 +958,13 @@ static void cleanup_stackmap(struct bpf_tracer *t,
 		 * excessive remnants of stack data from affecting the acquisition
 		 * of new stack data (i.e., eBPF using the bpf_get_stackid()
 		 * interface will return -EEXIST).
+		 *
+		 * Off-CPU profiling fetches the stack trace whenever a task is
+		 * switched out, and the stack IDs of blockings shorter than the
+		 * minimum blocking time are never output, so the stack map is
+		 * also cleaned to remove them.
 		 */
-		if (*perf_buf_lost_p > 0) {
+		if (*perf_buf_lost_p > 0 || offcpu_min_block_ns > 0) {
 			delete_all_stackmap_elems(t, stack_map_name);
 			*perf_buf_lost_p = 0;
 		}
@@ -985,6 +1142,15 @@ static void cp_reader_work(void *arg)
 		if (unlikely(!g_enable_perf_sample))
 			set_enable_perf_sample(t, 1);
 
+		if (mem_sample_bytes > 0) {
+			u64 now = gettime(CLOCK_MONOTONIC, TIME_TYPE_SEC);
+			if (now - mem_profiler_update_time >=
+			    MEM_PROFILER_UPDATE_INTERVAL) {
+				mem_profiler_update(t);
+				mem_profiler_update_time = now;
+			}
+		}
+
 		tracer_reader_lock(t);
 		process_bpf_stacktraces(t, reader_a, reader_b);
 		tracer_reader_unlock(t);
@@ -1029,6 +1195,26 @@ static int create_profiler(struct bpf_tracer *tracer)
 
 	set_enable_perf_sample(tracer, 0);
 
+	if (bpf_table_set_value(tracer, MAP_PROFILER_STATE_MAP,
+				OFFCPU_MIN_BLOCK_IDX,
+				&offcpu_min_block_ns) == false) {
+		ebpf_warning("profiler state map update error."
+			     "(%s offcpu_min_block_ns %lu) - %s\n",
+			     MAP_PROFILER_STATE_MAP,
+			     offcpu_min_block_ns, strerror(errno));
+		return ETR_INVAL;
+	}
+
+	if (bpf_table_set_value(tracer, MAP_PROFILER_STATE_MAP,
+				MEM_SAMPLE_BYTES_IDX,
+				&mem_sample_bytes) == false) {
+		ebpf_warning("profiler state map update error."
+			     "(%s mem_sample_bytes %lu) - %s\n",
+			     MAP_PROFILER_STATE_MAP,
+			     mem_sample_bytes, strerror(errno));
+		return ETR_INVAL;
+	}
+
 	/*
 	 * create reader for read eBPF-profiler data.
 	 * To implement eBPF perf-profiler double buffering output,
@@ -1403,9 +1589,26 @@ int start_continuous_profiler(int freq, int java_syms_space_limit,
 	bpf_bin_buffer = (void *)perf_profiler_common_ebpf_data;
 	buffer_sz = sizeof(perf_profiler_common_ebpf_data);
 
+	/*
+	 * Off-CPU profiling attaches the sched_switch tracepoint and memory
+	 * profiling attaches the uprobes of processes later, the on-CPU
+	 * sampling perf event is always attached.
+	 */
+	struct tracer_probes_conf *tps = NULL;
+	if (offcpu_min_block_ns > 0 || mem_sample_bytes > 0) {
+		tps = &profiler_tps;
+		if (tps->uprobe_syms_head.next == NULL)
+			init_list_head(&tps->uprobe_syms_head);
+		if (offcpu_min_block_ns > 0 && tps->tps_nr == 0) {
+			int index = 0, curr_idx;
+			tps_set_symbol(tps, "tracepoint/sched/sched_switch");
+			tps->tps_nr = index;
+		}
+	}
+
 	struct bpf_tracer *tracer =
 	    setup_bpf_tracer(CP_TRACER_NAME, bpf_load_buffer_name,
-			     bpf_bin_buffer, buffer_sz, NULL, 0,
+			     bpf_bin_buffer, buffer_sz, tps, 0,
 			     relase_profiler, create_profiler,
 			     (void *)callback, freq);
 	if (tracer == NULL)
@@ -1547,6 +1750,57 @@ int set_profiler_cpu_aggregation(int flag)
 	return (0);
 }
 
+/*
+ * Enable off-CPU profiling, it needs to be called before the profiler
+ * starts.
+ *
+ * @min_block_us
+ *   Minimum off-CPU time (microseconds) of a blocking to be recorded,
+ *   0 disables off-CPU profiling.
+ * @returns 0 on success, < 0 on error
+ */
+int set_profiler_offcpu(int min_block_us)
+{
+	if (min_block_us < 0) {
+		ebpf_info(LOG_CP_TAG
+			  "Set 'offcpu_min_block' parameter invalid.\n");
+		return (-1);
+	}
+
+	offcpu_min_block_ns = (u64) min_block_us * 1000;
+
+	ebpf_info(LOG_CP_TAG
+		  "Set 'offcpu_min_block' successful, value %dus\n",
+		  min_block_us);
+	return (0);
+}
+
+/*
+ * Enable memory allocation profiling of the processes matching the
+ * regular expression of FEATURE_PROFILE_MEMORY, it needs to be called
+ * before the profiler starts.
+ *
+ * @sample_bytes
+ *   Average bytes allocated between two samples, 0 disables memory
+ *   profiling.
+ * @returns 0 on success, < 0 on error
+ */
+int set_profiler_memory(int sample_bytes)
+{
+	if (sample_bytes < 0) {
+		ebpf_info(LOG_CP_TAG
+			  "Set 'mem_sample_bytes' parameter invalid.\n");
+		return (-1);
+	}
+
+	mem_sample_bytes = (u64) sample_bytes;
+
+	ebpf_info(LOG_CP_TAG
+		  "Set 'mem_sample_bytes' successful, value %d\n",
+		  sample_bytes);
+	return (0);
+}
+
 struct bpf_tracer *get_profiler_tracer(void)
 {
 	return profiler_tracer;
@@ -1589,6 +1843,16 @@ int set_profiler_cpu_aggregation(int flag)
 	return (-1);
 }
 
+int set_profiler_offcpu(int min_block_us)
+{
+	return (-1);
+}
+
+int set_profiler_memory(int sample_bytes)
+{
+	return (-1);
+}
+
 struct bpf_tracer *get_profiler_tracer(void)
 {
 	return NULL;
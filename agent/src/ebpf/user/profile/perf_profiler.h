@@ -37,6 +37,8 @@
 
 #define JAVA_ATTACH_TOOL_PATH DF_JAVA_ATTACH_CMD
 
+#define LOG_CP_TAG	"[CP] "
+
 /*
  * stack trace messages for push-hash kvp.
  */
@@ -64,8 +66,11 @@ typedef struct {
 			/*
 			 * process start time(the number of millisecond
 			 * elapsed since January 1, 1970 00:00:00).
+			 * event_type: stack traces of different event
+			 *   types are aggregated separately.
  			 */
-			u64 stime;
+			u64 stime: 61,
+			    event_type: 3;
 			u32 u_stack_id;
 			u32 k_stack_id;
 		} k;
@@ -74,7 +79,8 @@ typedef struct {
 		struct {
 			u8 comm[TASK_COMM_LEN];
 			u64 pid: 26,
-			    reserved: 26,
+			    event_type: 3,
+			    reserved: 23,
 			    cpu: 12;
 		} c_k;
 	};
@@ -106,6 +112,13 @@ typedef struct {
  *   The profiler captures the number of occurrences of the same
  *   data by querying with the quadruple
  *   "<pid + stime + u_stack_id + k_stack_id + tid + cpu>" as the key.
+ *   For off-CPU stack traces, it is the total off-CPU time in
+ *   microseconds. For memory allocation stack traces, it is the
+ *   allocated bytes, and for in-use memory stack traces, it is the
+ *   bytes allocated and not freed yet.
+ * @event_type
+ *   PROFILER_EVENT_ON_CPU, PROFILER_EVENT_OFF_CPU,
+ *   PROFILER_EVENT_MEM_ALLOC or PROFILER_EVENT_MEM_INUSE.
  * @comm
  *   comm in task_struct(linux kernel), always 16 bytes
  *   If the capture is a process, fill in the process name here.
@@ -124,6 +137,12 @@ typedef struct {
  *   The merged folded stack trace string style for user space and kernel space would be:
  *   <user space folded stack trace string> + ";" + <kernel space folded stack trace string>
  */
+/* stack trace message event types, same as the ProfileEventType of agent */
+#define PROFILER_EVENT_ON_CPU	1
+#define PROFILER_EVENT_OFF_CPU	2
+#define PROFILER_EVENT_MEM_ALLOC	3
+#define PROFILER_EVENT_MEM_INUSE	4
+
 typedef struct {
 	u64 time_stamp;
 	u32 pid;
@@ -134,6 +153,7 @@ typedef struct {
 	u32 k_stack_id;
 	u32 cpu;
 	u32 count;
+	u32 event_type;
 	u8 comm[TASK_COMM_LEN];
 	u8 process_name[TASK_COMM_LEN];
 	u8 container_id[CONTAINER_ID_SIZE];
@@ -155,6 +175,8 @@ void process_stack_trace_data_for_flame_graph(stack_trace_msg_t *val);
 void release_flame_graph_hash(void);
 int set_profiler_regex(const char *pattern);
 int set_profiler_cpu_aggregation(int flag);
+int set_profiler_offcpu(int min_block_us);
+int set_profiler_memory(int sample_bytes);
 struct bpf_tracer *get_profiler_tracer(void);
 void set_enable_perf_sample(struct bpf_tracer *t, u64 enable_flag);
 #endif /* DF_USER_PERF_PROFILER_H */
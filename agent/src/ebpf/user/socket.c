@@ -86,6 +86,8 @@ static uint32_t go_tracing_timeout = GO_TRACING_TIMEOUT_DEFAULT;
 
 // 0: disable 1: during request 2: all
 static uint32_t io_event_collect_mode = 1;
+static uint32_t unix_socket_enabled;
+static uint32_t proc_sock_stats_enabled;
 static uint64_t io_event_minimal_duration = 1000000;
 
 /*
@@ -94,6 +96,15 @@ static uint64_t io_event_minimal_duration = 1000000;
  */
 static uint32_t conf_socket_map_max_reclaim;
 
+/*
+ * Number of entries evicted by map reclamation, and the kernel map
+ * overflow counters at the last statistics collection.
+ */
+static atomic64_t socket_map_reclaim_count;
+static atomic64_t trace_map_reclaim_count;
+static uint64_t last_socket_map_overflow;
+static uint64_t last_trace_map_overflow;
+
 /*
  * The table for L7 protocol filtering ports.
  */
@@ -125,6 +136,8 @@ static void socket_tracer_set_probes(struct tracer_probes_conf *tps)
 	probes_set_enter_symbol(tps, "__sys_recvmmsg");
 	probes_set_enter_symbol(tps, "do_writev");
 	probes_set_enter_symbol(tps, "do_readv");
+	probes_set_enter_symbol(tps, "tcp_retransmit_skb");
+	probes_set_enter_symbol(tps, "tcp_finish_connect");
 	tps->kprobes_nr = index;
 
 	/* tracepoints */
@@ -154,6 +167,10 @@ static void socket_tracer_set_probes(struct tracer_probes_conf *tps)
 	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_readv");
 	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_accept");
 	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_accept4");
+	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_connect");
+	// kernel TLS offload
+	tps_set_symbol(tps, "tracepoint/syscalls/sys_enter_setsockopt");
+	tps_set_symbol(tps, "tracepoint/syscalls/sys_exit_setsockopt");
 	// process execute
 	tps_set_symbol(tps, "tracepoint/sched/sched_process_fork");
 
@@ -863,6 +880,7 @@ static void reclaim_trace_map(struct bpf_tracer *tracer, uint32_t timeout)
 	}
 
 	reclaim_count = __reclaim_map(map_fd, &clear_elem_head);
+	atomic64_add(&trace_map_reclaim_count, reclaim_count);
 	// The trace statistics map needs to be updated to reflect the count.   
 	curr_trace_count -= reclaim_count;
 	if (!bpf_stats_map_update(tracer, -1, curr_trace_count)) {
@@ -910,6 +928,7 @@ static void reclaim_socket_map(struct bpf_tracer *tracer, uint32_t timeout)
 	}
 
 	sockets_reclaim_count = __reclaim_map(map_fd, &clear_elem_head);
+	atomic64_add(&socket_map_reclaim_count, sockets_reclaim_count);
 	curr_socket_count -= sockets_reclaim_count;
 	if (!bpf_stats_map_update(tracer, curr_socket_count, -1)) {
 		ebpf_warning("Update trace statistics failed.\n");
@@ -919,6 +938,35 @@ static void reclaim_socket_map(struct bpf_tracer *tracer, uint32_t timeout)
 		  __func__, curr_socket_count, sockets_reclaim_count);
 }
 
+/*
+ * Sockets closed by process exit are not removed from the kTLS connection
+ * map by the close() tracepoint, remove the entries of exited processes.
+ */
+static void reclaim_ktls_conn_map(struct bpf_tracer *tracer)
+{
+	struct ebpf_map *map =
+	    ebpf_obj__get_map_by_name(tracer->obj, MAP_KTLS_CONN_NAME);
+	if (map == NULL) {
+		ebpf_warning("[%s] map(name:%s) is NULL.\n", __func__,
+			     MAP_KTLS_CONN_NAME);
+		return;
+	}
+	int map_fd = map->fd;
+
+	uint64_t conn_key = 0, next_conn_key;
+	struct list_head clear_elem_head;
+	init_list_head(&clear_elem_head);
+
+	while (bpf_get_next_key(map_fd, &conn_key, &next_conn_key) == 0) {
+		if (!is_process(next_conn_key >> 32))
+			insert_list(&next_conn_key, sizeof(next_conn_key),
+				    &clear_elem_head);
+		conn_key = next_conn_key;
+	}
+
+	__reclaim_map(map_fd, &clear_elem_head);
+}
+
 static int check_map_exceeded(void)
 {
 	struct bpf_tracer *t = find_bpf_tracer(SK_TRACER_NAME);
@@ -951,6 +999,8 @@ static int check_map_exceeded(void)
 		reclaim_trace_map(t, TRACE_RECLAIM_TIMEOUT_DEF);
 	}
 
+	reclaim_ktls_conn_map(t);
+
 	return 0;
 }
 
@@ -1269,6 +1319,37 @@ static int update_offset_map_from_btf_vmlinux(struct bpf_tracer *t)
 	offset.struct_sock_common_ipv6only_offset =
 	    struct_sock_common_ipv6only_offset;
 
+	/*
+	 * AF_UNIX socket tracing is optional, it stays disabled if any of
+	 * these offsets is unavailable.
+	 */
+	int struct_unix_sock_addr_offset =
+	    kernel_struct_field_offset(obj, "unix_sock", "addr");
+	int struct_unix_sock_peer_offset =
+	    kernel_struct_field_offset(obj, "unix_sock", "peer");
+	int struct_unix_address_name_offset =
+	    kernel_struct_field_offset(obj, "unix_address", "name");
+	if (struct_unix_sock_addr_offset > 0 &&
+	    struct_unix_sock_peer_offset > 0 &&
+	    struct_unix_address_name_offset > 0) {
+		ebpf_info("    struct_unix_sock_addr_offset: 0x%x\n",
+			  struct_unix_sock_addr_offset);
+		ebpf_info("    struct_unix_sock_peer_offset: 0x%x\n",
+			  struct_unix_sock_peer_offset);
+		ebpf_info("    struct_unix_address_name_offset: 0x%x\n",
+			  struct_unix_address_name_offset);
+		offset.struct_unix_sock_addr_offset =
+		    struct_unix_sock_addr_offset;
+		offset.struct_unix_sock_peer_offset =
+		    struct_unix_sock_peer_offset;
+		offset.struct_unix_address_name_offset =
+		    struct_unix_address_name_offset;
+	} else {
+		offset.struct_unix_sock_addr_offset = 0;
+		offset.struct_unix_sock_peer_offset = 0;
+		offset.struct_unix_address_name_offset = 0;
+	}
+
 	if (update_offsets_table(t, &offset) != ETR_OK) {
 		ebpf_warning("Update offsets map failed.\n");
 		return ETR_UPDATE_MAP_FAILD;
@@ -1455,6 +1536,128 @@ int set_io_event_collect_mode(uint32_t mode)
 	return 0;
 }
 
+int set_unix_socket_enabled(bool enabled)
+{
+	unix_socket_enabled = enabled;
+
+	struct bpf_tracer *tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL) {
+		return 0;
+	}
+
+	int cpu;
+	int nr_cpus = get_num_possible_cpus();
+	struct trace_conf_t values[nr_cpus];
+	memset(values, 0, sizeof(values));
+
+	if (!bpf_table_get_value(tracer, MAP_TRACE_CONF_NAME, 0, values)) {
+		ebpf_warning("Get map '%s' failed.\n", MAP_TRACE_CONF_NAME);
+		return ETR_NOTEXIST;
+	}
+
+	for (cpu = 0; cpu < nr_cpus; cpu++) {
+		values[cpu].unix_socket_enabled = unix_socket_enabled;
+	}
+
+	if (!bpf_table_set_value
+	    (tracer, MAP_TRACE_CONF_NAME, 0, (void *)&values)) {
+		ebpf_warning("Set '%s' failed\n", MAP_TRACE_CONF_NAME);
+		return ETR_UPDATE_MAP_FAILD;
+	}
+
+	return 0;
+}
+
+int set_proc_sock_stats_enabled(bool enabled)
+{
+	proc_sock_stats_enabled = enabled;
+
+	struct bpf_tracer *tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL) {
+		return 0;
+	}
+
+	int cpu;
+	int nr_cpus = get_num_possible_cpus();
+	struct trace_conf_t values[nr_cpus];
+	memset(values, 0, sizeof(values));
+
+	if (!bpf_table_get_value(tracer, MAP_TRACE_CONF_NAME, 0, values)) {
+		ebpf_warning("Get map '%s' failed.\n", MAP_TRACE_CONF_NAME);
+		return ETR_NOTEXIST;
+	}
+
+	for (cpu = 0; cpu < nr_cpus; cpu++) {
+		values[cpu].proc_sock_stats_enabled = proc_sock_stats_enabled;
+	}
+
+	if (!bpf_table_set_value
+	    (tracer, MAP_TRACE_CONF_NAME, 0, (void *)&values)) {
+		ebpf_warning("Set '%s' failed\n", MAP_TRACE_CONF_NAME);
+		return ETR_UPDATE_MAP_FAILD;
+	}
+
+	return 0;
+}
+
+/*
+ * Copy per-process socket statistics into @stats, entries of exited
+ * processes are removed from the map after being copied for the last
+ * time.
+ *
+ * @stats : Array to store the statistics
+ * @count : The size of the @stats array
+ *
+ * @return the number of entries copied, or a negative value on failure.
+ */
+int collect_process_socket_stats(struct process_socket_stats *stats,
+				 int count)
+{
+	struct bpf_tracer *tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL)
+		return -1;
+
+	struct ebpf_map *map =
+	    ebpf_obj__get_map_by_name(tracer->obj, MAP_PROC_SOCK_STATS_NAME);
+	if (map == NULL) {
+		ebpf_warning("[%s] map(name:%s) is NULL.\n", __func__,
+			     MAP_PROC_SOCK_STATS_NAME);
+		return -1;
+	}
+	int map_fd = map->fd;
+
+	uint32_t pid = 0, next_pid;
+	struct proc_sock_stats value;
+	struct list_head clear_elem_head;
+	init_list_head(&clear_elem_head);
+	int n = 0;
+
+	while (bpf_get_next_key(map_fd, &pid, &next_pid) == 0) {
+		if (n < count && bpf_lookup_elem(map_fd, &next_pid, &value) == 0) {
+			struct process_socket_stats *s = &stats[n++];
+			memset(s, 0, sizeof(*s));
+			s->pid = next_pid;
+			get_container_id_from_procs_cache(next_pid,
+							  s->container_id,
+							  sizeof(s->container_id));
+			s->conn_count = value.conn_count;
+			s->conn_failed = value.conn_failed;
+			s->tx_bytes = value.tx_bytes;
+			s->rx_bytes = value.rx_bytes;
+			s->retransmits = value.retransmits;
+		}
+
+		if (!is_process(next_pid))
+			insert_list(&next_pid, sizeof(next_pid),
+				    &clear_elem_head);
+		pid = next_pid;
+	}
+
+	__reclaim_map(map_fd, &clear_elem_head);
+
+	return n;
+}
+
 int set_io_event_minimal_duration(uint64_t duration)
 {
 	io_event_minimal_duration = duration;
@@ -1877,6 +2080,8 @@ int running_socket_tracer(tracer_callback_t handle,
 		t_conf[cpu].io_event_collect_mode = io_event_collect_mode;
 		t_conf[cpu].io_event_minimal_duration =
 		    io_event_minimal_duration;
+		t_conf[cpu].unix_socket_enabled = unix_socket_enabled;
+		t_conf[cpu].proc_sock_stats_enabled = proc_sock_stats_enabled;
 	}
 
 	if (!bpf_table_set_value
@@ -2024,6 +2229,8 @@ static bool bpf_stats_map_collect(struct bpf_tracer *tracer,
 	memset(stats_total, 0, sizeof(*stats_total));
 	stats_total->socket_map_count += value.socket_map_count;
 	stats_total->trace_map_count += value.trace_map_count;
+	stats_total->socket_map_overflow += value.socket_map_overflow;
+	stats_total->trace_map_overflow += value.trace_map_overflow;
 
 	return true;
 }
@@ -2137,7 +2344,19 @@ struct socket_trace_stats socket_tracer_stats(void)
 	if (bpf_stats_map_collect(t, &stats_total)) {
 		stats.kern_socket_map_used = stats_total.socket_map_count;
 		stats.kern_trace_map_used = stats_total.trace_map_count;
-	}
+		stats.kern_socket_map_overflow =
+		    stats_total.socket_map_overflow - last_socket_map_overflow;
+		stats.kern_trace_map_overflow =
+		    stats_total.trace_map_overflow - last_trace_map_overflow;
+		last_socket_map_overflow = stats_total.socket_map_overflow;
+		last_trace_map_overflow = stats_total.trace_map_overflow;
+	}
+
+	stats.socket_map_reclaim_count =
+	    atomic64_read(&socket_map_reclaim_count);
+	stats.trace_map_reclaim_count = atomic64_read(&trace_map_reclaim_count);
+	atomic64_init(&socket_map_reclaim_count);
+	atomic64_init(&trace_map_reclaim_count);
 
 	int i;
 	for (i = 0; i < t->dispatch_workers_nr; i++) {
@@ -35,6 +35,20 @@ enum probes_act_type {
 	ACT_DETACH
 };
 
+/*
+ * Per-process socket statistics, counters are cumulative since the
+ * process was first seen.
+ */
+struct process_socket_stats {
+	uint32_t pid;
+	uint8_t container_id[CONTAINER_ID_SIZE];
+	uint64_t conn_count;
+	uint64_t conn_failed;
+	uint64_t tx_bytes;
+	uint64_t rx_bytes;
+	uint64_t retransmits;
+};
+
 struct socket_bpf_data {
 	/* session info */
 	uint32_t process_id;	// tgid in kernel struct task_struct
@@ -111,6 +125,12 @@ struct socket_trace_stats {
 	uint32_t kern_trace_map_max;
 	uint32_t kern_trace_map_used;
 	uint32_t socket_map_max_reclaim;
+	// New entries dropped because the map is full
+	uint64_t kern_socket_map_overflow;
+	uint64_t kern_trace_map_overflow;
+	// Entries evicted by map reclamation
+	uint64_t socket_map_reclaim_count;
+	uint64_t trace_map_reclaim_count;
 
 	/*
 	 * 数据处理统计
@@ -159,6 +179,9 @@ struct bpf_offset_param {
 	uint32_t struct_sock_sport_offset;	// offsetof(struct sock_common, skc_num)
 	uint32_t struct_sock_skc_state_offset;	// offsetof(struct sock_common, skc_state)
 	uint32_t struct_sock_common_ipv6only_offset;	// offsetof(struct sock_common, skc_flags)
+	uint32_t struct_unix_sock_addr_offset;	// offsetof(struct unix_sock, addr)
+	uint32_t struct_unix_sock_peer_offset;	// offsetof(struct unix_sock, peer)
+	uint32_t struct_unix_address_name_offset;	// offsetof(struct unix_address, name)
 };
 
 struct bpf_offset_param_array {
@@ -313,6 +336,10 @@ prefetch_and_process_data(struct bpf_tracer *t, int nb_rx, void **datas_burst)
 int set_data_limit_max(int limit_size);
 int set_go_tracing_timeout(int timeout);
 int set_io_event_collect_mode(uint32_t mode);
+int set_unix_socket_enabled(bool enabled);
+int set_proc_sock_stats_enabled(bool enabled);
+int collect_process_socket_stats(struct process_socket_stats *stats,
+				 int count);
 int set_io_event_minimal_duration(uint64_t duration);
 struct socket_trace_stats socket_tracer_stats(void);
 int running_socket_tracer(tracer_callback_t handle,
@@ -67,6 +67,155 @@ static struct symbol openssl_syms[] = {
 	},
 };
 
+// BoringSSL and LibreSSL share the API of OpenSSL but not the layout of
+// struct ssl_st, the fd is taken from the socket syscalls instead.
+static struct symbol boringssl_syms[] = {
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "SSL_write",
+		.probe_func = "uprobe_tls_write_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "SSL_write",
+		.probe_func = "uprobe_openssl_write_exit",
+		.is_probe_ret = true,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "SSL_read",
+		.probe_func = "uprobe_tls_read_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "SSL_read",
+		.probe_func = "uprobe_openssl_read_exit",
+		.is_probe_ret = true,
+	},
+};
+
+// NSS encrypts in the SSL layer of NSPR file descriptors
+static struct symbol nss_syms[] = {
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "PR_Write",
+		.probe_func = "uprobe_nss_write_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "PR_Write",
+		.probe_func = "uprobe_openssl_write_exit",
+		.is_probe_ret = true,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "PR_Send",
+		.probe_func = "uprobe_nss_write_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "PR_Send",
+		.probe_func = "uprobe_openssl_write_exit",
+		.is_probe_ret = true,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "PR_Read",
+		.probe_func = "uprobe_nss_read_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "PR_Read",
+		.probe_func = "uprobe_openssl_read_exit",
+		.is_probe_ret = true,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "PR_Recv",
+		.probe_func = "uprobe_nss_read_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "PR_Recv",
+		.probe_func = "uprobe_openssl_read_exit",
+		.is_probe_ret = true,
+	},
+};
+
+// Legacy mangled names of rustls methods without the path of the module, which
+// changes between versions, and the hash suffix, which changes between builds.
+static struct symbol rustls_syms[] = {
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "Writer$u20$as$u20$std..io..Write$GT$5write17h",
+		.probe_func = "uprobe_rustls_write_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "Writer$u20$as$u20$std..io..Write$GT$5write17h",
+		.probe_func = "uprobe_rustls_write_exit",
+		.is_probe_ret = true,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "Reader$u20$as$u20$std..io..Read$GT$4read17h",
+		.probe_func = "uprobe_rustls_read_enter",
+		.is_probe_ret = false,
+	},
+	{
+		.type = OPENSSL_UPROBE,
+		.symbol = "Reader$u20$as$u20$std..io..Read$GT$4read17h",
+		.probe_func = "uprobe_rustls_read_exit",
+		.is_probe_ret = true,
+	},
+};
+
+struct tls_library {
+	const char *name;
+	struct symbol *syms;
+	int syms_count;
+	// Symbols are substrings of mangled names in rustls crates
+	bool is_rustls;
+};
+
+static const struct tls_library openssl_lib = {
+	.name = "openssl",
+	.syms = openssl_syms,
+	.syms_count = NELEMS(openssl_syms),
+};
+
+static const struct tls_library boringssl_lib = {
+	.name = "boringssl",
+	.syms = boringssl_syms,
+	.syms_count = NELEMS(boringssl_syms),
+};
+
+static const struct tls_library libressl_lib = {
+	.name = "libressl",
+	.syms = boringssl_syms,
+	.syms_count = NELEMS(boringssl_syms),
+};
+
+static const struct tls_library nss_lib = {
+	.name = "nss",
+	.syms = nss_syms,
+	.syms_count = NELEMS(nss_syms),
+};
+
+static const struct tls_library rustls_lib = {
+	.name = "rustls",
+	.syms = rustls_syms,
+	.syms_count = NELEMS(rustls_syms),
+	.is_rustls = true,
+};
+
 #if defined(__powerpc64__) && defined(_CALL_ELF) && _CALL_ELF == 2
 #define bcc_use_symbol_type (65535 | (1 << STT_PPC64_ELFV2_SYM_LEP))
 #else
@@ -84,6 +233,7 @@ struct bcc_elf_foreach_sym_payload {
 	uint64_t addr;
 	uint64_t size;
 	const char *name;
+	bool is_rustls;
 };
 
 // Lower version kernels do not support hooking so files in containers
@@ -100,6 +250,17 @@ static int bcc_elf_foreach_sym_callback(const char *name, uint64_t addr,
 {
 	struct bcc_elf_foreach_sym_payload *p = payload;
 	char *pos;
+	// Undefined symbols of dynamically linked binaries
+	if (!addr)
+		return 0;
+	if (p->is_rustls) {
+		if (strstr(name, "rustls..conn..") && strstr(name, p->name)) {
+			p->addr = addr;
+			p->size = size;
+			return -1;
+		}
+		return 0;
+	}
 	if ((pos = strstr(name, p->name))) {
 		if (pos[strlen(p->name)] == '\0') {
 			p->addr = addr;
@@ -110,7 +271,21 @@ static int bcc_elf_foreach_sym_callback(const char *name, uint64_t addr,
 	return 0;
 }
 
+static bool elf_has_symbol(const char *path, const char *name, bool is_rustls)
+{
+	struct bcc_elf_foreach_sym_payload payload;
+
+	memset(&payload, 0, sizeof(payload));
+	payload.name = name;
+	payload.is_rustls = is_rustls;
+	if (bcc_elf_foreach_sym(path, bcc_elf_foreach_sym_callback,
+				&bcc_elf_foreach_sym_option, &payload))
+		return false;
+	return payload.addr != 0;
+}
+
 static int add_probe_sym_to_tracer_probes(int pid, const char *path,
+					  const struct tls_library *lib,
 					  struct tracer_probes_conf *conf)
 {
 	int ret = 0;
@@ -119,20 +294,20 @@ static int add_probe_sym_to_tracer_probes(int pid, const char *path,
 	struct symbol *cur = NULL;
 	struct bcc_elf_foreach_sym_payload payload;
 
-	for (idx = 0; idx < NELEMS(openssl_syms); ++idx) {
+	for (idx = 0; idx < lib->syms_count; ++idx) {
 		memset(&payload, 0, sizeof(payload));
-		cur = &openssl_syms[idx];
+		cur = &lib->syms[idx];
 
 		// Use memory on the stack, no need to allocate on the heap
 		payload.name = cur->symbol;
+		payload.is_rustls = lib->is_rustls;
 		ret = bcc_elf_foreach_sym(path, bcc_elf_foreach_sym_callback,
 					  &bcc_elf_foreach_sym_option,
 					  &payload);
 		if (ret)
 			break;
 
-		// It has been confirmed earlier that the incoming binary file
-		// must be libssl.so and should not be hit here
+		// Symbols missing in this version of the library are skipped
 		if (!payload.addr || !payload.size)
 			continue;
 
@@ -167,68 +342,32 @@ static int add_probe_sym_to_tracer_probes(int pid, const char *path,
 	return 0;
 }
 
-// https://github.com/iovisor/bcc/blob/15fccdb9a4dbdc3d41e669a7ad5be73d2ac44b00/src/cc/bcc_proc.c#L419
-static int which_so_in_process(const char *libname, int pid, char *libpath)
+/*
+ * BoringSSL and LibreSSL are also built as libssl.so, and BoringSSL is
+ * statically linked into Envoy and gRPC C++. They are told apart from OpenSSL
+ * by symbols only exported by them.
+ */
+static const struct tls_library *ssl_library_of(const char *path)
 {
-	int ret, found = 0;
-	char endline[4096], *mapname = NULL, *newline;
-	char mappings_file[128];
-	const size_t search_len = strlen(libname) + strlen("/lib.");
-	char search1[search_len + 1];
-	char search2[search_len + 1];
-
-	snprintf(mappings_file, sizeof(mappings_file), "/proc/%ld/maps",
-		 (long)pid);
-	FILE *fp = fopen(mappings_file, "r");
-	if (!fp)
-		return found;
-
-	snprintf(search1, search_len + 1, "/lib%s.", libname);
-	snprintf(search2, search_len + 1, "/lib%s-", libname);
-
-	do {
-		ret = fscanf(fp, "%*x-%*x %*s %*x %*s %*d");
-		if (!fgets(endline, sizeof(endline), fp))
-			break;
-
-		mapname = endline;
-		newline = strchr(endline, '\n');
-		if (newline)
-			newline[0] = '\0';
-
-		while (isspace(mapname[0]))
-			mapname++;
-
-		if (strstr(mapname, ".so") &&
-		    (strstr(mapname, search1) || strstr(mapname, search2))) {
-			found = 1;
-			memcpy(libpath, mapname, strlen(mapname) + 1);
-			break;
-		}
-	} while (ret != EOF);
-
-	fclose(fp);
-	return found;
+	if (elf_has_symbol(path, "SSL_CTX_set_select_certificate_cb", false))
+		return &boringssl_lib;
+	if (elf_has_symbol(path, "SSL_CTX_use_certificate_chain_mem", false))
+		return &libressl_lib;
+	return &openssl_lib;
 }
 
-static char *get_openssl_so_path_by_pid(int pid)
+static void tls_register(int pid, const char *path,
+			 const struct tls_library *lib,
+			 struct tracer_probes_conf *conf)
 {
-	int ret = 0;
-	char so_path[PATH_MAX] = { 0 };
-
-	int offset = snprintf(so_path, sizeof(so_path), "/proc/%d/root", pid);
-	if (offset < 0 || offset >= sizeof(so_path))
-		return NULL;
-
-	ret = which_so_in_process("ssl", pid, so_path + offset);
-	if (!ret)
-		return NULL;
-	return strdup(so_path);
+	ebpf_info("%s uprobe, pid:%d, path:%s\n", lib->name, pid, path);
+	add_probe_sym_to_tracer_probes(pid, path, lib, conf);
 }
 
 static void openssl_parse_and_register(int pid, struct tracer_probes_conf *conf)
 {
 	char *path = NULL;
+	char *exe_path = NULL;
 
 	if (pid <= 1)
 		goto out;
@@ -236,15 +375,36 @@ static void openssl_parse_and_register(int pid, struct tracer_probes_conf *conf)
 	if (!is_user_process(pid))
 		goto out;
 
-	path = get_openssl_so_path_by_pid(pid);
-	if (!path)
-		goto out;
+	path = get_so_path_by_pid(pid, "ssl");
+	if (path) {
+		tls_register(pid, path, ssl_library_of(path), conf);
+		free(path);
+	}
 
-	ebpf_info("openssl uprobe, pid:%d, path:%s\n", pid, path);
-	add_probe_sym_to_tracer_probes(pid, path, conf);
+	// NSPR of NSS, only for processes loading the SSL library of NSS
+	path = get_so_path_by_pid(pid, "ssl3");
+	if (path) {
+		free(path);
+		path = get_so_path_by_pid(pid, "nspr4");
+		if (path) {
+			tls_register(pid, path, &nss_lib, conf);
+			free(path);
+		}
+	}
+	path = NULL;
+
+	// Statically linked TLS libraries
+	exe_path = get_elf_path_by_pid(pid);
+	if (!exe_path)
+		goto out;
+	if (elf_has_symbol(exe_path, "SSL_write", false))
+		tls_register(pid, exe_path, ssl_library_of(exe_path), conf);
+	if (elf_has_symbol(exe_path, rustls_syms[0].symbol, true))
+		tls_register(pid, exe_path, &rustls_lib, conf);
 
 out:
 	free(path);
+	free(exe_path);
 	return;
 }
 
@@ -35,11 +35,13 @@
 #include <bcc/bcc_elf.h>
 #include <bcc/bcc_syms.h>
 #include <dirent.h>		// for opendir()
+#include <ctype.h>
 #include "config.h"
 #include "log.h"
 #include "common.h"
 #include "symbol.h"
 #include "tracer.h"
+#include "elf.h"
 #if defined __x86_64__
 #include "bddisasm/bddisasm.h"
 #include "bddisasm/disasmtypes.h"
@@ -358,6 +360,15 @@ struct symbol_uprobe *resolve_and_gen_uprobe_symbol(const char *bin_file,
 		error = bcc_elf_foreach_sym(uprobe_sym->binary_path, find_sym,
 					    &default_option, uprobe_sym);
 
+		// Stripped Go binaries, resolve from the Go function table
+		if (uprobe_sym->entry == 0x0 && uprobe_sym->type == GO_UPROBE) {
+			uint64_t entry = 0;
+			error = go_pclntab_find_func(uprobe_sym->binary_path,
+						     uprobe_sym->name, &entry,
+						     &uprobe_sym->size);
+			uprobe_sym->entry = entry;
+		}
+
 		if (!is_feature_enabled(FEATURE_UPROBE_GOLANG_SYMBOL) && error) {
 			goto invalid;
 		}
@@ -454,6 +465,65 @@ char *get_elf_path_by_pid(int pid)
 	return path;
 }
 
+// https://github.com/iovisor/bcc/blob/15fccdb9a4dbdc3d41e669a7ad5be73d2ac44b00/src/cc/bcc_proc.c#L419
+static int which_so_in_process(const char *libname, int pid, char *libpath)
+{
+	int ret, found = 0;
+	char endline[4096], *mapname = NULL, *newline;
+	char mappings_file[128];
+	const size_t search_len = strlen(libname) + strlen("/lib.");
+	char search1[search_len + 1];
+	char search2[search_len + 1];
+
+	snprintf(mappings_file, sizeof(mappings_file), "/proc/%ld/maps",
+		 (long)pid);
+	FILE *fp = fopen(mappings_file, "r");
+	if (!fp)
+		return found;
+
+	snprintf(search1, search_len + 1, "/lib%s.", libname);
+	snprintf(search2, search_len + 1, "/lib%s-", libname);
+
+	do {
+		ret = fscanf(fp, "%*x-%*x %*s %*x %*s %*d");
+		if (!fgets(endline, sizeof(endline), fp))
+			break;
+
+		mapname = endline;
+		newline = strchr(endline, '\n');
+		if (newline)
+			newline[0] = '\0';
+
+		while (isspace(mapname[0]))
+			mapname++;
+
+		if (strstr(mapname, ".so") &&
+		    (strstr(mapname, search1) || strstr(mapname, search2))) {
+			found = 1;
+			memcpy(libpath, mapname, strlen(mapname) + 1);
+			break;
+		}
+	} while (ret != EOF);
+
+	fclose(fp);
+	return found;
+}
+
+char *get_so_path_by_pid(int pid, const char *libname)
+{
+	int ret = 0;
+	char so_path[PATH_MAX] = { 0 };
+
+	int offset = snprintf(so_path, sizeof(so_path), "/proc/%d/root", pid);
+	if (offset < 0 || offset >= sizeof(so_path))
+		return NULL;
+
+	ret = which_so_in_process(libname, pid, so_path + offset);
+	if (!ret)
+		return NULL;
+	return strdup(so_path);
+}
+
 #if defined(__x86_64__)
 // The bddisasm library requires defined functions
 void *nd_memset(void *s, int c, ND_SIZET n)
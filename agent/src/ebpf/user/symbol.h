@@ -100,6 +100,7 @@ struct tracer_probes_conf;
 enum uprobe_type {
 	GO_UPROBE = 0,
 	OPENSSL_UPROBE,
+	MEMORY_UPROBE,
 	OTHER_UPROBE
 };
 
@@ -182,6 +183,7 @@ void add_uprobe_symbol(int pid, struct symbol_uprobe *u_sym,
 		       struct tracer_probes_conf *conf);
 int copy_uprobe_symbol(struct symbol_uprobe *src, struct symbol_uprobe *dst);
 char *get_elf_path_by_pid(int pid);
+char *get_so_path_by_pid(int pid, const char *libname);
 struct symbol_uprobe *resolve_and_gen_uprobe_symbol(const char *bin_file,
 						    struct symbol *sym,
 						    const uint64_t addr,
@@ -932,6 +932,15 @@ perf_event:
 	 * perf event
 	 */
 	if (type == HOOK_ATTACH) {
+		/*
+		 * Perf events are attached only once, the tracer may be
+		 * attached again to install the uprobes of new processes.
+		 */
+		for (i = 0; i < ARRAY_SIZE(tracer->per_cpu_fds); i++) {
+			if (tracer->per_cpu_fds[i] > 0)
+				return ETR_OK;
+		}
+
 		struct ebpf_object *obj = tracer->obj;
 		for (i = 0; i < obj->progs_cnt; i++) {
 			if (obj->progs[i].type == BPF_PROG_TYPE_PERF_EVENT) {
@@ -108,6 +108,8 @@ enum cfg_feature_idx {
 	FEATURE_UPROBE_OPENSSL,
 	// golang uprobe
 	FEATURE_UPROBE_GOLANG,
+	// memory allocation profiling
+	FEATURE_PROFILE_MEMORY,
 	FEATURE_MAX,
 };
 
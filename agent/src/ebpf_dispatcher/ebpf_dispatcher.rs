@@ -14,11 +14,13 @@
  * limitations under the License.
  */
 
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
+use std::fs;
 use std::ptr::null_mut;
 use std::slice;
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
@@ -26,6 +28,7 @@ use ahash::HashSet;
 use arc_swap::access::Access;
 use libc::{c_int, c_ulonglong};
 use log::{debug, error, info, warn};
+use procfs::process::FDTarget;
 
 use super::{Error, Result};
 use crate::common::flow::L7Stats;
@@ -39,12 +42,13 @@ use crate::config::handler::{CollectorAccess, EbpfAccess, EbpfConfig, LogParserA
 use crate::config::FlowAccess;
 use crate::ebpf::{
     self, set_allow_port_bitmap, set_bypass_port_bitmap, set_profiler_cpu_aggregation,
-    set_profiler_regex, set_protocol_ports_bitmap, start_continuous_profiler,
+    set_profiler_memory, set_profiler_offcpu, set_profiler_regex, set_protocol_ports_bitmap,
+    start_continuous_profiler,
 };
 use crate::flow_generator::{flow_map::Config, FlowMap, MetaAppProto};
 use crate::integration_collector::Profile;
 use crate::policy::PolicyGetter;
-use crate::utils::stats;
+use crate::utils::stats::{self, Countable, RefCountable, StatsOption};
 use public::{
     buffer::BatchedBox,
     counter::{Counter, CounterType, CounterValue, OwnedCountable},
@@ -115,6 +119,26 @@ impl OwnedCountable for SyncEbpfCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(ebpf_counter.socket_map_max_reclaim as u64),
             ),
+            (
+                "kern_socket_map_overflow",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.kern_socket_map_overflow),
+            ),
+            (
+                "kern_trace_map_overflow",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.kern_trace_map_overflow),
+            ),
+            (
+                "socket_map_reclaim_count",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaim_count),
+            ),
+            (
+                "trace_map_reclaim_count",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.trace_map_reclaim_count),
+            ),
             (
                 "worker_num",
                 CounterType::Counted,
@@ -173,6 +197,157 @@ impl OwnedCountable for SyncEbpfCounter {
     }
 }
 
+#[derive(Default)]
+struct ProcessSocketMetric {
+    conn_count: AtomicU64,
+    conn_failed: AtomicU64,
+    tx_bytes: AtomicU64,
+    rx_bytes: AtomicU64,
+    retransmits: AtomicU64,
+}
+
+// Socket statistics of a process counted by the eBPF socket tracer
+#[derive(Default)]
+struct ProcessSocketBroker {
+    old: ProcessSocketMetric,
+    new: ProcessSocketMetric,
+}
+
+impl ProcessSocketBroker {
+    fn update(&self, stats: &ebpf::PROCESS_SOCKET_STATS) {
+        self.new
+            .conn_count
+            .store(stats.conn_count, Ordering::Relaxed);
+        self.new
+            .conn_failed
+            .store(stats.conn_failed, Ordering::Relaxed);
+        self.new.tx_bytes.store(stats.tx_bytes, Ordering::Relaxed);
+        self.new.rx_bytes.store(stats.rx_bytes, Ordering::Relaxed);
+        self.new
+            .retransmits
+            .store(stats.retransmits, Ordering::Relaxed);
+    }
+}
+
+impl RefCountable for ProcessSocketBroker {
+    fn get_counters(&self) -> Vec<Counter> {
+        [
+            ("conn_count", &self.old.conn_count, &self.new.conn_count),
+            ("conn_failed", &self.old.conn_failed, &self.new.conn_failed),
+            ("tx_bytes", &self.old.tx_bytes, &self.new.tx_bytes),
+            ("rx_bytes", &self.old.rx_bytes, &self.new.rx_bytes),
+            ("retransmits", &self.old.retransmits, &self.new.retransmits),
+        ]
+        .into_iter()
+        .map(|(name, old, new)| {
+            let new = new.load(Ordering::Relaxed);
+            let old = old.swap(new, Ordering::Relaxed);
+            (
+                name,
+                CounterType::Counted,
+                CounterValue::Unsigned(new.overflowing_sub(old).0),
+            )
+        })
+        .collect()
+    }
+}
+
+// Registers a broker for each process with socket statistics before every stats
+// collection, brokers of exited processes are dropped. The number of brokers is
+// bounded by max_processes, the size of the buffer statistics are collected into.
+fn register_process_socket_stats(stats_collector: &Arc<stats::Collector>, max_processes: usize) {
+    let mut brokers: HashMap<u32, Arc<ProcessSocketBroker>> = HashMap::new();
+    let mut pids: HashSet<u32> = HashSet::default();
+    let mut buffer = vec![ebpf::PROCESS_SOCKET_STATS::default(); max_processes];
+    let collector = stats_collector.clone();
+    stats_collector.register_pre_hook(Box::new(move || {
+        let n = unsafe {
+            ebpf::collect_process_socket_stats(buffer.as_mut_ptr(), buffer.len() as c_int)
+        };
+        if n < 0 {
+            return;
+        }
+        let entries = &buffer[..n as usize];
+        pids.clear();
+        pids.extend(entries.iter().map(|e| e.pid));
+        brokers.retain(|pid, _| pids.contains(pid));
+        for entry in entries {
+            if let Some(broker) = brokers.get(&entry.pid) {
+                broker.update(entry);
+                continue;
+            }
+            let broker = Arc::new(ProcessSocketBroker::default());
+            // the first update is the baseline, counters before it are not reported
+            broker.update(entry);
+            broker.get_counters();
+            let process_name = fs::read_to_string(format!("/proc/{}/comm", entry.pid))
+                .map(|s| s.trim_end().to_owned())
+                .unwrap_or_default();
+            let container_id = CStr::from_bytes_until_nul(&entry.container_id)
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            collector.register_countable(
+                "process_socket",
+                Countable::Ref(Arc::downgrade(&broker) as Weak<dyn RefCountable>),
+                vec![
+                    StatsOption::Tag("pid", entry.pid.to_string()),
+                    StatsOption::Tag("process_name", process_name),
+                    StatsOption::Tag("container_id", container_id),
+                ],
+            );
+            brokers.insert(entry.pid, broker);
+        }
+    }));
+}
+
+// Upper bound of max-socket-entries and max-trace-entries
+const MAX_MAP_ENTRIES: usize = 2000000;
+// Room left in the auto sized maps for connections opened after startup
+const MAP_SIZE_FACTOR: usize = 4;
+
+// Counts the sockets opened by all processes on the node
+fn count_sockets() -> usize {
+    let Ok(processes) = procfs::process::all_processes() else {
+        return 0;
+    };
+    processes
+        .filter_map(|p| p.ok()?.fd().ok())
+        .map(|fds| {
+            fds.filter(|fd| matches!(fd, Ok(f) if matches!(f.target, FDTarget::Socket(_))))
+                .count()
+        })
+        .sum()
+}
+
+// Returns the socket map entries, trace map entries and socket map reclaim threshold,
+// the maps can not be resized after they are created, so they are sized from the sockets
+// on the node at startup if auto sizing is enabled.
+fn map_entries(config: &EbpfConfig) -> (usize, usize, usize) {
+    let ebpf = &config.ebpf;
+    if !ebpf.map_auto_sizing_enabled {
+        return (
+            ebpf.max_socket_entries,
+            ebpf.max_trace_entries,
+            ebpf.socket_map_max_reclaim,
+        );
+    }
+    let sockets = count_sockets();
+    let entries = (sockets * MAP_SIZE_FACTOR).next_power_of_two();
+    let socket_entries = entries.clamp(ebpf.max_socket_entries, MAX_MAP_ENTRIES);
+    let trace_entries = entries.clamp(ebpf.max_trace_entries, MAX_MAP_ENTRIES);
+    let socket_reclaim = if socket_entries > ebpf.max_socket_entries {
+        // keep the ratio of the default reclaim threshold to the default map size
+        socket_entries - socket_entries / 128
+    } else {
+        ebpf.socket_map_max_reclaim
+    };
+    info!(
+        "ebpf map auto sizing: {} sockets, max socket entries {}, max trace entries {}, socket map max reclaim {}",
+        sockets, socket_entries, trace_entries, socket_reclaim
+    );
+    (socket_entries, trace_entries, socket_reclaim)
+}
+
 #[derive(Clone)]
 struct EbpfDispatcher {
     dispatcher_id: usize,
@@ -346,9 +521,25 @@ impl EbpfCollector {
             }
             let mut profile = metric::Profile::default();
             let data = &mut *data;
-            profile.sample_rate = ON_CPU_PROFILE_FREQUENCY;
             profile.timestamp = data.timestamp;
-            profile.event_type = metric::ProfileEventType::EbpfOnCpu.into();
+            match data.event_type {
+                // count is the off-CPU time in microseconds
+                ebpf::PROFILER_EVENT_OFF_CPU => {
+                    profile.event_type = metric::ProfileEventType::EbpfOffCpu.into();
+                }
+                // count is the allocated bytes
+                ebpf::PROFILER_EVENT_MEM_ALLOC => {
+                    profile.event_type = metric::ProfileEventType::EbpfMemAlloc.into();
+                }
+                // count is the bytes allocated and not freed yet
+                ebpf::PROFILER_EVENT_MEM_INUSE => {
+                    profile.event_type = metric::ProfileEventType::EbpfMemInUse.into();
+                }
+                _ => {
+                    profile.sample_rate = ON_CPU_PROFILE_FREQUENCY;
+                    profile.event_type = metric::ProfileEventType::EbpfOnCpu.into();
+                }
+            }
             profile.stime = data.stime;
             profile.pid = data.pid;
             profile.tid = data.tid;
@@ -487,6 +678,22 @@ impl EbpfCollector {
                 return Err(Error::EbpfInitError);
             }
 
+            if ebpf::set_unix_socket_enabled(config.ebpf.unix_socket_enabled) != 0 {
+                info!(
+                    "ebpf set_unix_socket_enabled error: {}",
+                    config.ebpf.unix_socket_enabled
+                );
+                return Err(Error::EbpfInitError);
+            }
+
+            if ebpf::set_proc_sock_stats_enabled(config.ebpf.process_socket_stats_enabled) != 0 {
+                info!(
+                    "ebpf set_proc_sock_stats_enabled error: {}",
+                    config.ebpf.process_socket_stats_enabled
+                );
+                return Err(Error::EbpfInitError);
+            }
+
             let mut all_proto_map = get_all_protocol()
                 .iter()
                 .map(|p| p.as_str().to_lowercase())
@@ -516,14 +723,16 @@ impl EbpfCollector {
                 }
             }
 
+            let (max_socket_entries, max_trace_entries, socket_map_max_reclaim) =
+                map_entries(config);
             if ebpf::running_socket_tracer(
-                Self::ebpf_l7_callback,                    /* 回调接口 rust -> C */
+                Self::ebpf_l7_callback,              /* 回调接口 rust -> C */
                 config.ebpf.thread_num as i32, /* 工作线程数，是指用户态有多少线程参与数据处理 */
                 config.ebpf.perf_pages_count as u32, /* 内核共享内存占用的页框数量, 值为2的次幂。用于perf数据传递 */
                 config.ebpf.ring_size as u32, /* 环形缓存队列大小，值为2的次幂。e.g: 2,4,8,16,32,64,128 */
-                config.ebpf.max_socket_entries as u32, /* 设置用于socket追踪的hash表项最大值，取决于实际场景中并发请求数量 */
-                config.ebpf.max_trace_entries as u32, /* 设置用于线程追踪会话的hash表项最大值，SK_BPF_DATA结构的syscall_trace_id_session关联这个哈希表 */
-                config.ebpf.socket_map_max_reclaim as u32, /* socket map表项进行清理的最大阈值，当前map的表项数量超过这个值进行map清理操作 */
+                max_socket_entries as u32, /* 设置用于socket追踪的hash表项最大值，取决于实际场景中并发请求数量 */
+                max_trace_entries as u32, /* 设置用于线程追踪会话的hash表项最大值，SK_BPF_DATA结构的syscall_trace_id_session关联这个哈希表 */
+                socket_map_max_reclaim as u32, /* socket map表项进行清理的最大阈值，当前map的表项数量超过这个值进行map清理操作 */
             ) != 0
             {
                 return Err(Error::EbpfRunningError);
@@ -531,6 +740,24 @@ impl EbpfCollector {
 
             let on_cpu_profile_config = &config.ebpf.on_cpu_profile;
             if !on_cpu_profile_config.disabled {
+                // off-CPU profiling shares the stack trace aggregation and output
+                // of the on-CPU profiler
+                let off_cpu_profile_config = &config.ebpf.off_cpu_profile;
+                if !off_cpu_profile_config.disabled {
+                    set_profiler_offcpu(off_cpu_profile_config.min_block.as_micros() as i32);
+                }
+                let memory_profile_config = &config.ebpf.memory_profile;
+                if !memory_profile_config.disabled && !memory_profile_config.regex.is_empty() {
+                    ebpf::set_feature_regex(
+                        ebpf::FEATURE_PROFILE_MEMORY,
+                        CString::new(memory_profile_config.regex.as_bytes())
+                            .unwrap()
+                            .as_c_str()
+                            .as_ptr(),
+                    );
+                    set_profiler_memory(memory_profile_config.sample_bytes as i32);
+                }
+
                 if start_continuous_profiler(
                     on_cpu_profile_config.frequency as i32,
                     on_cpu_profile_config.java_symbol_file_max_space_limit as i32,
@@ -661,6 +888,12 @@ impl EbpfCollector {
             time_diff.clone(),
         )?;
         Self::ebpf_on_config_change(ebpf::CAP_LEN_MAX);
+        if ebpf_config.ebpf.process_socket_stats_enabled {
+            register_process_socket_stats(
+                &stats_collector,
+                ebpf_config.ebpf.process_socket_stats_max_processes,
+            );
+        }
 
         info!("ebpf collector initialized.");
         Ok(Box::new(EbpfCollector {
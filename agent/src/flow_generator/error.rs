@@ -16,6 +16,7 @@
 
 use std::str::Utf8Error;
 
+use public::bytes::ReadError;
 use thiserror::Error;
 
 use super::MetaAppProto;
@@ -65,6 +66,20 @@ pub enum Error {
     TlsLogParseFailed(String),
     #[error("{0}")]
     TlsPerfParseFailed(&'static str),
+    #[error("{0}")]
+    DhcpLogParseFailed(String),
+    #[error("{0}")]
+    NtpLogParseFailed(String),
+    #[error("{0}")]
+    SshLogParseFailed(String),
+    #[error("{0}")]
+    KerberosLogParseFailed(String),
+    #[error("{0}")]
+    RadiusLogParseFailed(String),
+    #[error("{0}")]
+    SipLogParseFailed(String),
+    #[error("{0}")]
+    RtpLogParseFailed(String),
     #[error("l7 protocol unknown")]
     L7ProtocolUnknown,
     #[error("l7 protocol check limit")]
@@ -89,6 +104,10 @@ pub enum Error {
     SoReturnUnexpectVal,
     #[error("so plugin parse fail")]
     SoParseFail,
+    #[error("simple protocol parse fail")]
+    SimpleProtocolParseFailed,
+    #[error("{0}")]
+    PayloadReadFailed(#[from] ReadError),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -15,12 +15,13 @@
  */
 
 use std::{
+    fmt,
     sync::atomic::{AtomicBool, AtomicU32},
     sync::Arc,
 };
 
 use crate::common::Timestamp;
-use public::proto::common::TridentType;
+use public::{bitmap::Bitmap, l7_protocol::L7Protocol, proto::common::TridentType};
 
 pub const TIMEOUT_OTHERS: Timestamp = Timestamp::from_secs(5);
 pub const TIMEOUT_ESTABLISHED: Timestamp = Timestamp::from_secs(300);
@@ -102,6 +103,37 @@ impl FlowTimeout {
     }
 }
 
+// Flow timeouts for flows of specific l7 protocol or server ports
+#[derive(Clone, PartialEq, Eq)]
+pub struct FlowTimeoutRule {
+    pub l7_protocol: Option<L7Protocol>,
+    pub ports: String,
+    pub port_bitmap: Option<Bitmap>,
+    pub flow_timeout: FlowTimeout,
+}
+
+impl FlowTimeoutRule {
+    pub fn matches(&self, l7_protocol: L7Protocol, server_port: u16) -> bool {
+        if self.l7_protocol.is_some() && self.l7_protocol != Some(l7_protocol) {
+            return false;
+        }
+        match self.port_bitmap.as_ref() {
+            Some(bitmap) => bitmap.get(server_port as usize).unwrap_or(false),
+            None => true,
+        }
+    }
+}
+
+impl fmt::Debug for FlowTimeoutRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlowTimeoutRule")
+            .field("l7_protocol", &self.l7_protocol)
+            .field("ports", &self.ports)
+            .field("flow_timeout", &self.flow_timeout)
+            .finish()
+    }
+}
+
 #[derive(Default)]
 pub struct FlowMapRuntimeConfig {
     pub l7_metrics_enabled: AtomicBool,
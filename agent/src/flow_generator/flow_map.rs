@@ -19,7 +19,8 @@ use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
     mem,
-    net::Ipv4Addr,
+    hash::Hasher,
+    net::{IpAddr, Ipv4Addr},
     num::NonZeroUsize,
     rc::Rc,
     str::FromStr,
@@ -39,7 +40,7 @@ use super::{
     error::Error,
     flow_state::{StateMachine, StateValue},
     perf::{tcp::TcpPerf, FlowLog, FlowPerfCounter, L7ProtocolChecker},
-    pool::MemoryPool,
+    pool::{shrink_string_pool, MemoryPool},
     protocol_logs::{
         sql::{ObfuscateCache, OBFUSCATE_CACHE_SIZE},
         MetaAppProto,
@@ -53,6 +54,7 @@ use super::{
 
 use crate::{
     common::{
+        decapsulate::{GeneveOptions, TunnelType},
         endpoint::{
             EndpointData, EndpointDataPov, EndpointInfo, EPC_FROM_DEEPFLOW, EPC_FROM_INTERNET,
         },
@@ -66,7 +68,7 @@ use crate::{
             L7PerfCache, L7ProtocolBitmap, L7ProtocolParser, L7ProtocolParserInterface,
         },
         lookup_key::LookupKey,
-        meta_packet::{MetaPacket, MetaPacketTcpHeader, ProtocolData},
+        meta_packet::{MetaPacket, MetaPacketTcpHeader, ProtocolData, SctpChunks},
         tagged_flow::TaggedFlow,
         tap_port::TapPort,
         Timestamp,
@@ -104,7 +106,7 @@ use public::{
     packet::SECONDS_IN_MINUTE,
     proto::common::TridentType,
     queue::{self, DebugSender, Receiver},
-    utils::net::MacAddr,
+    utils::{hash::Fnv1aHasher, net::MacAddr},
 };
 
 use packet_sequence_block::PacketSequenceBlock;
@@ -132,6 +134,8 @@ pub struct FlowMap {
     id: u32,
     state_machine_master: StateMachine,
     state_machine_slave: StateMachine,
+    // state machines of FlowConfig.flow_timeout_rules, in the same order
+    rule_state_machines: Vec<(StateMachine, StateMachine)>,
     service_table: ServiceTable,
     app_table: AppTable,
     policy_getter: PolicyGetter,
@@ -158,6 +162,9 @@ pub struct FlowMap {
     packet_sequence_enabled: bool,
     stats_counter: Arc<FlowMapCounter>,
     system_time: Duration,
+    // (timestamp of the latest packet, system time when it is injected) when the time
+    // window follows packet timestamps instead of the system time
+    packet_clock: Option<(Duration, Duration)>,
 
     l7_protocol_checker: L7ProtocolChecker,
 
@@ -192,7 +199,12 @@ impl FlowMap {
         let stats_counter = Arc::new(FlowMapCounter::default());
         let packet_sequence_enabled = config.packet_sequence_flag > 0 && !from_ebpf;
         let time_window_size = {
-            let max_timeout = config.flow_timeout.max;
+            let max_timeout = config
+                .flow_timeout_rules
+                .iter()
+                .fold(config.flow_timeout.max, |max, rule| {
+                    max.max(rule.flow_timeout.max)
+                });
             let size = config.packet_delay.as_secs() + max_timeout.as_secs() + 1;
             size.next_power_of_two() as usize
         };
@@ -279,6 +291,16 @@ impl FlowMap {
             id,
             state_machine_master: StateMachine::new_master(&config.flow_timeout),
             state_machine_slave: StateMachine::new_slave(&config.flow_timeout),
+            rule_state_machines: config
+                .flow_timeout_rules
+                .iter()
+                .map(|rule| {
+                    (
+                        StateMachine::new_master(&rule.flow_timeout),
+                        StateMachine::new_slave(&rule.flow_timeout),
+                    )
+                })
+                .collect(),
             service_table: ServiceTable::new(
                 SERVICE_TABLE_IPV4_CAPACITY,
                 SERVICE_TABLE_IPV6_CAPACITY,
@@ -335,6 +357,7 @@ impl FlowMap {
             packet_sequence_enabled,
             stats_counter,
             system_time,
+            packet_clock: None,
             l7_protocol_checker: L7ProtocolChecker::new(
                 &config.l7_protocol_enabled_bitmap,
                 &config
@@ -346,6 +369,7 @@ impl FlowMap {
                             .map(|p| (p.protocol(), bitmap.clone()))
                     })
                     .collect(),
+                &config.l7_protocol_hints,
             ),
             time_key_buffer: None,
             wasm_vm: {
@@ -429,11 +453,50 @@ impl FlowMap {
         left as usize
     }
 
+    // Used for pcap files replayed with original timestamps, which are far before the
+    // system time. The time window starts from the first packet and is moved by packets,
+    // ticks move it by the system time elapsed since the latest packet.
+    pub fn follow_packet_clock(&mut self) {
+        self.packet_clock = Some((Duration::ZERO, Duration::ZERO));
+    }
+
+    // Returns the time of the packet clock, or None before the first packet
+    fn tick_packet_clock(
+        &mut self,
+        config: &FlowConfig,
+        timestamp: Duration,
+    ) -> Option<Duration> {
+        let (latest, injected) = self.packet_clock.as_mut().unwrap();
+        let now = get_timestamp(self.ntp_diff.load(Ordering::Relaxed));
+        if timestamp.is_zero() {
+            if latest.is_zero() {
+                return None;
+            }
+            return Some(*latest + now.saturating_sub(*injected));
+        }
+        if latest.is_zero() {
+            self.start_time =
+                timestamp.saturating_sub(config.packet_delay + Duration::from_secs(1));
+            self.start_time_in_unit = (self.start_time.as_nanos() / TIME_UNIT.as_nanos()) as u64;
+        }
+        if timestamp > *latest {
+            *latest = timestamp;
+            *injected = now;
+        }
+        Some(timestamp)
+    }
+
     pub fn inject_flush_ticker(&mut self, config: &Config, mut timestamp: Duration) -> bool {
         let is_tick = timestamp.is_zero();
-        if is_tick {
+        if self.packet_clock.is_some() {
+            match self.tick_packet_clock(config.flow, timestamp) {
+                Some(t) => timestamp = t,
+                None => return true,
+            }
+        } else if is_tick {
             timestamp = get_timestamp(self.ntp_diff.load(Ordering::Relaxed));
-        } else if timestamp < self.start_time {
+        }
+        if !is_tick && timestamp < self.start_time {
             self.stats_counter
                 .drop_by_window
                 .fetch_add(1, Ordering::Relaxed);
@@ -447,7 +510,7 @@ impl FlowMap {
             return true;
         }
 
-        self.system_time = if is_tick {
+        self.system_time = if is_tick || self.packet_clock.is_some() {
             timestamp
         } else {
             // calculate packet delay only when window will be pushed forward
@@ -478,6 +541,7 @@ impl FlowMap {
         self.start_time =
             Duration::from_nanos(next_start_time_in_unit * TIME_UNIT.as_nanos() as u64);
         timestamp = self.start_time - Duration::from_nanos(1);
+        shrink_string_pool(self.system_time);
 
         let Some((mut node_map, mut time_set)) = self.node_map.take() else {
             warn!("cannot get node map and time set");
@@ -565,6 +629,7 @@ impl FlowMap {
 
         self.start_time_in_unit = next_start_time_in_unit;
         self.flush_queue(&config, timestamp);
+        self.update_perf_cache(&config);
 
         self.flush_app_protolog();
 
@@ -586,6 +651,7 @@ impl FlowMap {
         }
 
         let flow_config = &config.flow;
+        meta_packet.select_vlan(flow_config.vlan_tag_mode);
 
         let pkt_key = FlowMapKey::new(&meta_packet.lookup_key, meta_packet.tap_port);
 
@@ -594,6 +660,12 @@ impl FlowMap {
             return;
         };
 
+        if meta_packet.lookup_key.proto == IpProtocol::ICMPV4
+            || meta_packet.lookup_key.proto == IpProtocol::ICMPV6
+        {
+            Self::annotate_icmp_error(&mut node_map, meta_packet);
+        }
+
         let pkt_timestamp = meta_packet.lookup_key.timestamp;
         let mut max_depth = 1;
         match node_map.get_mut(&pkt_key) {
@@ -603,6 +675,7 @@ impl FlowMap {
                 let ignore_l2_end = flow_config.ignore_l2_end;
                 let ignore_tor_mac = flow_config.ignore_tor_mac;
                 let ignore_idc_vlan = flow_config.ignore_idc_vlan;
+                let tunnel_id_in_flow_key = flow_config.tunnel_id_in_flow_key;
                 let trident_type = flow_config.trident_type;
                 let index = nodes.iter().position(|node| {
                     node.match_node(
@@ -610,6 +683,7 @@ impl FlowMap {
                         ignore_l2_end,
                         ignore_tor_mac,
                         ignore_idc_vlan,
+                        tunnel_id_in_flow_key,
                         trident_type,
                     )
                 });
@@ -638,6 +712,7 @@ impl FlowMap {
                     .fetch_add(1 + index as u64, Ordering::Relaxed);
 
                 let node = &mut nodes[index];
+                node.learn_gtpu_teid(meta_packet);
                 // 1. 输出上一个统计周期的统计信息
                 self.node_updated_aftercare(
                     &flow_config,
@@ -657,6 +732,7 @@ impl FlowMap {
                 let flow_closed = match meta_packet.lookup_key.proto {
                     IpProtocol::TCP => self.update_tcp_node(config, node, meta_packet),
                     IpProtocol::UDP => self.update_udp_node(config, node, meta_packet),
+                    IpProtocol::SCTP => self.update_sctp_node(config, node, meta_packet),
                     _ => self.update_other_node(config, node, meta_packet),
                 };
 
@@ -704,6 +780,52 @@ impl FlowMap {
         // rust 版本用了std的hashmap自动处理扩容，所以无需执行policy_gettelr
     }
 
+    // Correlate icmp error messages to the original tcp/udp flow embedded in them,
+    // surfacing mtu and routing problems of the flow
+    fn annotate_icmp_error(
+        node_map: &mut AHashMap<FlowMapKey, Vec<Box<FlowNode>>>,
+        meta_packet: &MetaPacket,
+    ) {
+        let ProtocolData::IcmpData(icmp_data) = &meta_packet.protocol_data else {
+            return;
+        };
+        let Some(error_key) = icmp_data.error_key.as_ref() else {
+            return;
+        };
+        let mut lookup_key = meta_packet.lookup_key.clone();
+        lookup_key.src_ip = error_key.src_ip;
+        lookup_key.dst_ip = error_key.dst_ip;
+        lookup_key.src_port = error_key.src_port;
+        lookup_key.dst_port = error_key.dst_port;
+        lookup_key.proto = error_key.proto;
+        let Some(nodes) = node_map.get_mut(&FlowMapKey::new(&lookup_key, meta_packet.tap_port))
+        else {
+            return;
+        };
+        let node = nodes.iter_mut().find(|node| {
+            let key = &node.tagged_flow.flow.flow_key;
+            key.proto == error_key.proto
+                && key.tap_type == lookup_key.tap_type
+                && ((key.ip_src == error_key.src_ip
+                    && key.port_src == error_key.src_port
+                    && key.ip_dst == error_key.dst_ip
+                    && key.port_dst == error_key.dst_port)
+                    || (key.ip_src == error_key.dst_ip
+                        && key.port_src == error_key.dst_port
+                        && key.ip_dst == error_key.src_ip
+                        && key.port_dst == error_key.src_port))
+        });
+        if let Some(node) = node {
+            let flow = &mut node.tagged_flow.flow;
+            flow.icmp_error_type = icmp_data.icmp_type;
+            flow.icmp_error_code = icmp_data.icmp_code;
+            flow.icmp_error_count += 1;
+            if icmp_data.mtu > 0 {
+                flow.icmp_error_mtu = icmp_data.mtu;
+            }
+        }
+    }
+
     fn append_to_block(&self, config: &FlowConfig, node: &mut FlowNode, meta_packet: &MetaPacket) {
         const MINUTE: u64 = 60;
         let packet_sequence_start_time = node.tagged_flow.flow.start_time_in_minute();
@@ -807,7 +929,8 @@ impl FlowMap {
             && peers[FLOW_METRICS_PEER_DST].packet_count > 0
         {
             // For udp, eBPF and Packet data use the same timeout
-            node.timeout = flow_config.flow_timeout.closing;
+            Self::update_flow_timeout_rule(flow_config, node);
+            node.timeout = Self::flow_timeout(flow_config, node).closing;
         }
         meta_packet.is_active_service = node.tagged_flow.flow.is_active_service;
         if flow_config.collector_enabled {
@@ -831,6 +954,74 @@ impl FlowMap {
         false
     }
 
+    fn update_sctp_node(
+        &mut self,
+        config: &Config,
+        node: &mut FlowNode,
+        meta_packet: &mut MetaPacket,
+    ) -> bool {
+        self.update_flow(config, node, meta_packet);
+        meta_packet.is_active_service = node.tagged_flow.flow.is_active_service;
+        Self::update_sctp_association(config.flow, node, meta_packet)
+    }
+
+    // SCTP association tracking, INIT and INIT ACK are handled like SYN and SYN ACK,
+    // ABORT like RST and SHUTDOWN COMPLETE like the last ACK of tcp
+    fn update_sctp_association(
+        flow_config: &FlowConfig,
+        node: &mut FlowNode,
+        meta_packet: &MetaPacket,
+    ) -> bool {
+        const MAX_SCTP_STREAMS: usize = 64;
+
+        let ProtocolData::SctpData(sctp_data) = &meta_packet.protocol_data else {
+            return false;
+        };
+        if sctp_data.ppid != 0 {
+            node.tagged_flow.flow.sctp_ppid = sctp_data.ppid;
+        }
+        for stream_id in sctp_data.stream_ids() {
+            if node.sctp_stream_ids.len() < MAX_SCTP_STREAMS
+                && !node.sctp_stream_ids.contains(stream_id)
+            {
+                node.sctp_stream_ids.push(*stream_id);
+            }
+        }
+        node.tagged_flow.flow.sctp_stream_count = node.sctp_stream_ids.len() as u32;
+
+        let chunks = sctp_data.chunks;
+        if chunks.contains(SctpChunks::ABORT) {
+            node.flow_state = if meta_packet.lookup_key.direction == PacketDirection::ClientToServer
+            {
+                FlowState::Reset
+            } else {
+                FlowState::ServerReset
+            };
+            return true;
+        }
+        if chunks.contains(SctpChunks::SHUTDOWN_COMPLETE) {
+            node.flow_state = FlowState::Closed;
+            return true;
+        }
+
+        let flow_timeout = Self::flow_timeout(flow_config, node);
+        let (flow_state, timeout) =
+            if chunks.intersects(SctpChunks::SHUTDOWN | SctpChunks::SHUTDOWN_ACK) {
+                (FlowState::ClosingTx1, flow_timeout.closing)
+            } else if chunks.contains(SctpChunks::INIT_ACK) {
+                (FlowState::Opening2, flow_timeout.opening)
+            } else if chunks.contains(SctpChunks::INIT) {
+                (FlowState::Opening1, flow_timeout.opening)
+            } else if node.flow_state == FlowState::ClosingTx1 {
+                return false;
+            } else {
+                (FlowState::Established, flow_timeout.established)
+            };
+        node.flow_state = flow_state;
+        node.timeout = timeout;
+        false
+    }
+
     fn update_other_node(
         &mut self,
         config: &Config,
@@ -890,9 +1081,10 @@ impl FlowMap {
         self.update_tcp_keepalive_seq(node, meta_packet);
         meta_packet.is_active_service = node.tagged_flow.flow.is_active_service;
 
+        Self::update_flow_timeout_rule(flow_config, node);
         if pkt_tcp_flags.is_invalid() {
             // exception timeout
-            node.timeout = flow_config.flow_timeout.exception;
+            node.timeout = Self::flow_timeout(flow_config, node).exception;
             node.flow_state = FlowState::Exception;
             return false;
         }
@@ -962,22 +1154,29 @@ impl FlowMap {
         flags: TcpFlags,
         direction: PacketDirection,
     ) -> bool {
+        let flow_timeout = Self::flow_timeout(config, node);
+        let (state_machine_master, state_machine_slave) = match node
+            .flow_timeout_rule
+            .and_then(|i| self.rule_state_machines.get(i))
+        {
+            Some((master, slave)) => (master, slave),
+            None => (&self.state_machine_master, &self.state_machine_slave),
+        };
         let &StateValue {
             mut timeout,
             mut state,
             mut closed,
-        } = self
-            .state_machine_master
+        } = state_machine_master
             .get(node.flow_state, flags)
             .unwrap_or(&StateValue::new(
                 // exception timeout,
-                config.flow_timeout.exception,
+                flow_timeout.exception,
                 FlowState::Exception,
                 false,
             ));
 
         if direction == PacketDirection::ServerToClient {
-            if let Some(v) = self.state_machine_slave.get(node.flow_state, flags) {
+            if let Some(v) = state_machine_slave.get(node.flow_state, flags) {
                 timeout = v.timeout;
                 state = v.state;
                 closed = v.closed;
@@ -990,7 +1189,7 @@ impl FlowMap {
         let peer_dst = &flow.flow_metrics_peers[FLOW_METRICS_PEER_DST];
         if peer_src.total_packet_count == 0 || peer_dst.total_packet_count == 0 {
             //single direction timeout
-            node.timeout = config.flow_timeout.single_direction;
+            node.timeout = flow_timeout.single_direction;
         } else {
             node.timeout = timeout;
         }
@@ -998,6 +1197,31 @@ impl FlowMap {
         closed
     }
 
+    // Flows matching a rule use its timeouts instead of the global ones, the l7 protocol of
+    // the flow is unknown until its payload is parsed, so the rule may change afterwards
+    fn update_flow_timeout_rule(config: &FlowConfig, node: &mut FlowNode) {
+        if config.flow_timeout_rules.is_empty() {
+            return;
+        }
+        let l7_protocol = node
+            .meta_flow_log
+            .as_ref()
+            .map(|log| log.l7_protocol())
+            .unwrap_or(L7Protocol::Unknown);
+        let server_port = node.tagged_flow.flow.flow_key.port_dst;
+        node.flow_timeout_rule = config
+            .flow_timeout_rules
+            .iter()
+            .position(|rule| rule.matches(l7_protocol, server_port));
+    }
+
+    fn flow_timeout<'a>(config: &'a FlowConfig, node: &FlowNode) -> &'a FlowTimeout {
+        node.flow_timeout_rule
+            .and_then(|i| config.flow_timeout_rules.get(i))
+            .map(|rule| &rule.flow_timeout)
+            .unwrap_or(&config.flow_timeout)
+    }
+
     fn l7_metrics_enabled(config: &FlowConfig) -> bool {
         config.l7_metrics_enabled
     }
@@ -1054,6 +1278,13 @@ impl FlowMap {
         } else {
             TcpFlags::default()
         };
+        let (icmp_type, icmp_code) =
+            if let ProtocolData::IcmpData(icmp_data) = &meta_packet.protocol_data {
+                (icmp_data.icmp_type, icmp_data.icmp_code)
+            } else {
+                (0, 0)
+            };
+        let scale = meta_packet.sampling_scale();
         let flow = Flow {
             flow_key: FlowKey {
                 vtap_id: flow_config.vtap_id,
@@ -1077,6 +1308,10 @@ impl FlowMap {
                     tier: tunnel.tier,
                     tunnel_type: tunnel.tunnel_type,
                     is_ipv6: tunnel.is_ipv6,
+                    geneve_options: tunnel.geneve_options,
+                    nsh_spi: tunnel.nsh_spi,
+                    nsh_si: tunnel.nsh_si,
+                    erspan_sgt: tunnel.erspan_sgt,
                     ..Default::default()
                 }
             } else {
@@ -1090,18 +1325,24 @@ impl FlowMap {
             start_time: lookup_key.timestamp.into(),
             flow_stat_time: lookup_key.timestamp.round_to(TIME_UNIT.into()),
             vlan: meta_packet.vlan,
+            outer_vlan: meta_packet.outer_vlan,
+            srv6_segments: meta_packet
+                .srv6()
+                .map(|s| s.segments().to_vec())
+                .unwrap_or_default(),
+            srv6_active_segment: meta_packet.srv6().map(|s| s.active_segment),
             eth_type: lookup_key.eth_type,
             queue_hash: meta_packet.queue_hash,
             is_new_flow: true,
             // 统计量
             flow_metrics_peers: [
                 FlowMetricsPeer {
-                    total_packet_count: 1,
-                    packet_count: 1,
-                    total_byte_count: meta_packet.packet_len as u64,
-                    byte_count: meta_packet.packet_len as u64,
-                    l3_byte_count: meta_packet.l3_payload_len() as u64,
-                    l4_byte_count: meta_packet.l4_payload_len() as u64,
+                    total_packet_count: scale,
+                    packet_count: scale,
+                    total_byte_count: meta_packet.packet_len as u64 * scale,
+                    byte_count: meta_packet.packet_len as u64 * scale,
+                    l3_byte_count: meta_packet.l3_payload_len() as u64 * scale,
+                    l4_byte_count: meta_packet.l4_payload_len() as u64 * scale,
                     first: lookup_key.timestamp.into(),
                     last: lookup_key.timestamp.into(),
                     tcp_flags: flags,
@@ -1112,6 +1353,8 @@ impl FlowMap {
             ],
             signal_source: meta_packet.signal_source,
             is_active_service,
+            icmp_type,
+            icmp_code,
             ..Default::default()
         };
         tagged_flow.flow = flow;
@@ -1129,9 +1372,12 @@ impl FlowMap {
         node.min_arrived_time = lookup_key.timestamp;
         node.recent_time = lookup_key.timestamp;
         node.timeout = Timestamp::ZERO;
+        node.flow_timeout_rule = None;
         node.packet_in_tick = true;
         node.policy_in_tick = policy_in_tick;
         node.flow_state = FlowState::Raw;
+        node.tunnel_id = meta_packet.tunnel.map(|t| t.id).unwrap_or_default();
+        node.reverse_tunnel_id = 0;
         node.meta_flow_log = None;
         node.next_tcp_seq0 = 0;
         node.next_tcp_seq1 = 0;
@@ -1139,6 +1385,8 @@ impl FlowMap {
         node.endpoint_data_cache = Default::default();
         node.packet_sequence_block = None; // Enterprise Edition Feature: packet-sequence
         node.residual_request = 0;
+        node.endpoint_stats.clear();
+        node.sctp_stream_ids.clear();
         #[cfg(any(target_os = "linux", target_os = "android"))]
         let local_epc_id = match config.ebpf.as_ref() {
             Some(c) => c.epc_id as i32,
@@ -1235,10 +1483,15 @@ impl FlowMap {
                 (L7ProtocolEnum::default(), 0, false, 0, None)
             };
 
+        // Sampled packets (e.g. sFlow) have neither continuous tcp sequences nor complete
+        // l7 messages, so tcp perf and l7 parsing are skipped for them
+        let is_sampled = meta_packet.sampling_rate > 0;
         let l4_enabled = node.tagged_flow.flow.signal_source == SignalSource::Packet
+            && !is_sampled
             && Self::l4_metrics_enabled(flow_config);
-        let l7_enabled = Self::l7_metrics_enabled(flow_config)
-            || Self::l7_log_parse_enabled(flow_config, &meta_packet.lookup_key);
+        let l7_enabled = !is_sampled
+            && (Self::l7_metrics_enabled(flow_config)
+                || Self::l7_log_parse_enabled(flow_config, &meta_packet.lookup_key));
         if l4_enabled || l7_enabled {
             node.tagged_flow.flow.flow_perf_stats = Some(FlowPerfStats {
                 l7_failed_count,
@@ -1384,14 +1637,15 @@ impl FlowMap {
             return;
         }
 
+        let scale = meta_packet.sampling_scale();
         let flow_metrics_peer =
             &mut flow.flow_metrics_peers[meta_packet.lookup_key.direction as usize];
-        flow_metrics_peer.packet_count += 1;
-        flow_metrics_peer.total_packet_count += 1;
-        flow_metrics_peer.byte_count += meta_packet.packet_len as u64;
-        flow_metrics_peer.l3_byte_count += meta_packet.l3_payload_len() as u64;
-        flow_metrics_peer.l4_byte_count += meta_packet.l4_payload_len() as u64;
-        flow_metrics_peer.total_byte_count += meta_packet.packet_len as u64;
+        flow_metrics_peer.packet_count += scale;
+        flow_metrics_peer.total_packet_count += scale;
+        flow_metrics_peer.byte_count += meta_packet.packet_len as u64 * scale;
+        flow_metrics_peer.l3_byte_count += meta_packet.l3_payload_len() as u64 * scale;
+        flow_metrics_peer.l4_byte_count += meta_packet.l4_payload_len() as u64 * scale;
+        flow_metrics_peer.total_byte_count += meta_packet.packet_len as u64 * scale;
         flow_metrics_peer.last = pkt_timestamp.into();
         if flow_metrics_peer.first.is_zero() {
             flow_metrics_peer.first = pkt_timestamp.into();
@@ -1400,6 +1654,15 @@ impl FlowMap {
         if meta_packet.vlan > 0 {
             flow.vlan = meta_packet.vlan;
         }
+        if meta_packet.outer_vlan > 0 {
+            flow.outer_vlan = meta_packet.outer_vlan;
+        }
+        if let Some(srv6) = meta_packet.srv6() {
+            if flow.srv6_active_segment.is_none() {
+                flow.srv6_segments = srv6.segments().to_vec();
+                flow.srv6_active_segment = Some(srv6.active_segment);
+            }
+        }
         if let Some(tunnel) = meta_packet.tunnel {
             match meta_packet.lookup_key.direction {
                 PacketDirection::ClientToServer => {
@@ -1420,6 +1683,16 @@ impl FlowMap {
             flow.tunnel.tier = tunnel.tier;
             flow.tunnel.tunnel_type = tunnel.tunnel_type;
             flow.tunnel.is_ipv6 = tunnel.is_ipv6;
+            if tunnel.geneve_options != GeneveOptions::default() {
+                flow.tunnel.geneve_options = tunnel.geneve_options;
+            }
+            if tunnel.nsh_spi != 0 {
+                flow.tunnel.nsh_spi = tunnel.nsh_spi;
+                flow.tunnel.nsh_si = tunnel.nsh_si;
+            }
+            if tunnel.erspan_sgt != 0 {
+                flow.tunnel.erspan_sgt = tunnel.erspan_sgt;
+            }
         }
         // 这里需要查询策略，建立ARP表
         if meta_packet.is_ndp_response() {
@@ -1437,6 +1710,7 @@ impl FlowMap {
 
     fn collect_l7_stats(
         &mut self,
+        log_parser_config: &LogParserConfig,
         node: &mut FlowNode,
         meta_packet: &MetaPacket,
         new_endpoint: Option<String>,
@@ -1470,18 +1744,37 @@ impl FlowMap {
 
                 flow_perf_stats.l7_protocol = l7_protocol;
 
-                let l7_stats = L7Stats {
-                    flow: None,
-                    stats: l7_perf_stats,
-                    endpoint: Some(last_endpoint.clone()),
-                    flow_id: *flow_id,
-                    time_in_second: node.tagged_flow.flow.flow_stat_time.into(),
-                    signal_source: node.tagged_flow.flow.signal_source,
-                    l7_protocol,
-                };
+                let aggregation = &log_parser_config.l7_endpoint_aggregation;
+                let aggregated = aggregation.enabled
+                    && match node.endpoint_stats.iter_mut().find(|s| {
+                        s.l7_protocol == l7_protocol && s.endpoint.as_ref() == Some(last_endpoint)
+                    }) {
+                        Some(s) => {
+                            s.stats.sequential_merge(&l7_perf_stats);
+                            true
+                        }
+                        None => false,
+                    };
 
-                self.l7_stats_buffer
-                    .push(self.l7_stats_allocator.allocate_one_with(l7_stats));
+                if !aggregated {
+                    let l7_stats = L7Stats {
+                        flow: None,
+                        stats: l7_perf_stats,
+                        endpoint: Some(last_endpoint.clone()),
+                        flow_id: *flow_id,
+                        time_in_second: node.tagged_flow.flow.flow_stat_time.into(),
+                        signal_source: node.tagged_flow.flow.signal_source,
+                        l7_protocol,
+                    };
+                    // sent at the end of the statistical interval
+                    if aggregation.enabled && node.endpoint_stats.len() < aggregation.max_endpoints
+                    {
+                        node.endpoint_stats.push(l7_stats);
+                    } else {
+                        self.l7_stats_buffer
+                            .push(self.l7_stats_allocator.allocate_one_with(l7_stats));
+                    }
+                }
             }
         }
         // FIXME: the endpoint may be None after parsed
@@ -1490,6 +1783,13 @@ impl FlowMap {
         }
     }
 
+    fn flush_endpoint_stats(&mut self, node: &mut FlowNode) {
+        for l7_stats in node.endpoint_stats.drain(..) {
+            self.l7_stats_buffer
+                .push(self.l7_stats_allocator.allocate_one_with(l7_stats));
+        }
+    }
+
     fn collect_metric(
         &mut self,
         config: &Config,
@@ -1534,12 +1834,22 @@ impl FlowMap {
                     }
                     match info {
                         crate::common::l7_protocol_log::L7ParseResult::Single(s) => {
-                            self.collect_l7_stats(node, &meta_packet, s.get_endpoint());
+                            self.collect_l7_stats(
+                                log_parser_config,
+                                node,
+                                &meta_packet,
+                                s.get_endpoint(),
+                            );
                             self.write_to_app_proto_log(flow_config, node, &meta_packet, s);
                         }
                         crate::common::l7_protocol_log::L7ParseResult::Multi(m) => {
                             for i in m.into_iter() {
-                                self.collect_l7_stats(node, &meta_packet, i.get_endpoint());
+                                self.collect_l7_stats(
+                                    log_parser_config,
+                                    node,
+                                    &meta_packet,
+                                    i.get_endpoint(),
+                                );
                                 self.write_to_app_proto_log(flow_config, node, &meta_packet, i);
                             }
                         }
@@ -1570,6 +1880,7 @@ impl FlowMap {
             node.timeout = config.log_parser.l7_log_session_aggr_timeout.into();
         } else {
             reverse = self.update_l4_direction(meta_packet, &mut node, true);
+            Self::update_flow_timeout_rule(flow_config, &mut node);
 
             let tcp_data = if let ProtocolData::TcpHeader(tcp_data) = &meta_packet.protocol_data {
                 tcp_data
@@ -1578,7 +1889,7 @@ impl FlowMap {
             };
             if tcp_data.flags.is_invalid() {
                 // exception timeout
-                node.timeout = flow_config.flow_timeout.exception;
+                node.timeout = Self::flow_timeout(flow_config, &node).exception;
                 node.flow_state = FlowState::Exception;
             }
             self.update_flow_state_machine(
@@ -1620,19 +1931,33 @@ impl FlowMap {
             (node.tagged_flow.flow.start_time.as_secs() % SECONDS_IN_MINUTE) as u8;
         meta_packet.is_active_service = node.tagged_flow.flow.is_active_service;
         node.flow_state = FlowState::Established;
-        // For eBPF UDP Flow, there is no special treatment for timeout.
-        node.timeout = flow_config.flow_timeout.opening; // use opening timeout
         let mut reverse = false;
         if node.tagged_flow.flow.signal_source != SignalSource::EBPF {
             // eBPF Flow only use server_port to correct the direction.
             reverse = self.update_l4_direction(meta_packet, &mut node, true);
         }
+        Self::update_flow_timeout_rule(flow_config, &mut node);
+        // For eBPF UDP Flow, there is no special treatment for timeout.
+        node.timeout = Self::flow_timeout(flow_config, &node).opening; // use opening timeout
         if flow_config.collector_enabled {
             self.collect_metric(config, &mut node, meta_packet, !reverse, true);
         }
         node
     }
 
+    fn new_sctp_node(&mut self, config: &Config, meta_packet: &mut MetaPacket) -> Box<FlowNode> {
+        let mut node = self.init_flow(config, meta_packet);
+        meta_packet.flow_id = node.tagged_flow.flow.flow_id;
+        meta_packet.second_in_minute =
+            (node.tagged_flow.flow.start_time.as_secs() % SECONDS_IN_MINUTE) as u8;
+        self.update_l4_direction(meta_packet, &mut node, true);
+        meta_packet.is_active_service = node.tagged_flow.flow.is_active_service;
+        Self::update_flow_timeout_rule(config.flow, &mut node);
+        node.timeout = Self::flow_timeout(config.flow, &node).opening;
+        let _ = Self::update_sctp_association(config.flow, &mut node, meta_packet);
+        node
+    }
+
     fn new_other_node(&mut self, config: &Config, meta_packet: &mut MetaPacket) -> Box<FlowNode> {
         let mut node = self.init_flow(config, meta_packet);
         meta_packet.flow_id = node.tagged_flow.flow.flow_id;
@@ -1652,12 +1977,19 @@ impl FlowMap {
         config: &Config,
         meta_packet: &mut MetaPacket,
     ) -> Option<Box<FlowNode>> {
+        if self.is_sampled_out(config, meta_packet) {
+            self.stats_counter
+                .sampled_out
+                .fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
         // To avoid using each package to query policies that may lead to CPU increase and performance decrease,
         // there will not be use config.capacity to limit the addition of FlowNode
         self.stats_counter.new.fetch_add(1, Ordering::Relaxed);
         let mut node = match meta_packet.lookup_key.proto {
             IpProtocol::TCP => self.new_tcp_node(config, meta_packet),
             IpProtocol::UDP => self.new_udp_node(config, meta_packet),
+            IpProtocol::SCTP => self.new_sctp_node(config, meta_packet),
             _ => self.new_other_node(config, meta_packet),
         };
 
@@ -1677,6 +2009,71 @@ impl FlowMap {
         Some(node)
     }
 
+    // The hash of the 5-tuple is symmetric and independent of the agent and its version, so packets
+    // of both directions and all agents make the same decision. Flows matching policies are always kept.
+    fn is_sampled_out(&mut self, config: &Config, meta_packet: &mut MetaPacket) -> bool {
+        const SAMPLING_BUCKETS: u64 = 1000;
+        let permille = config.flow.flow_sampling_permille as u64;
+        if permille >= SAMPLING_BUCKETS || meta_packet.signal_source == SignalSource::EBPF {
+            return false;
+        }
+        let key = &meta_packet.lookup_key;
+        let (src, dst) = ((key.src_ip, key.src_port), (key.dst_ip, key.dst_port));
+        let (first, second) = if src <= dst { (src, dst) } else { (dst, src) };
+        let mut hasher = Fnv1aHasher::default();
+        for (ip, port) in [first, second] {
+            match ip {
+                IpAddr::V4(ip) => hasher.write(&ip.octets()),
+                IpAddr::V6(ip) => hasher.write(&ip.octets()),
+            }
+            hasher.write(&port.to_be_bytes());
+        }
+        hasher.write(&[u8::from(key.proto)]);
+        if hasher.finish() % SAMPLING_BUCKETS < permille {
+            return false;
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let local_epc_id = match config.ebpf.as_ref() {
+            Some(c) => c.epc_id as i32,
+            _ => 0,
+        };
+        #[cfg(target_os = "windows")]
+        let local_epc_id = 0;
+        (self.policy_getter).lookup(meta_packet, self.id as usize, local_epc_id);
+        meta_packet
+            .policy_data
+            .as_ref()
+            .map(|p| p.acl_id == 0)
+            .unwrap_or(true)
+    }
+
+    // report counters of rrt cache and resize it by eviction pressure
+    fn update_perf_cache(&mut self, config: &FlowConfig) {
+        let mut perf_cache = self.perf_cache.borrow_mut();
+        let counter = mem::take(&mut perf_cache.counter);
+        self.stats_counter
+            .l7_perf_cache_hit
+            .fetch_add(counter.hit, Ordering::Relaxed);
+        self.stats_counter
+            .l7_perf_cache_miss
+            .fetch_add(counter.miss, Ordering::Relaxed);
+        self.stats_counter
+            .l7_perf_cache_evicted
+            .fetch_add(counter.evicted, Ordering::Relaxed);
+        if config.l7_rrt_cache_adaptive {
+            if let Some(cap) =
+                perf_cache.adapt_capacity(counter.evicted, config.l7_rrt_cache_memory_limit)
+            {
+                debug!("flow_map#{} resize l7 rrt cache to {}", self.id, cap);
+            }
+        }
+        self.stats_counter.l7_perf_cache_capacity.store(
+            usize::from(perf_cache.rrt_cache.cap()) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
     fn flush_queue(&mut self, config: &FlowConfig, now: Duration) {
         if now > config.flush_interval + self.last_queue_flush {
             if self.l7_stats_buffer.len() > 0 {
@@ -1735,6 +2132,7 @@ impl FlowMap {
     ) {
         // 统计数据输出前矫正流方向
         self.update_flow_direction(&mut node, meta_packet);
+        self.flush_endpoint_stats(&mut node);
 
         let mut flow = &mut node.tagged_flow.flow;
         if flow.signal_source == SignalSource::EBPF {
@@ -1769,6 +2167,7 @@ impl FlowMap {
             if let Some(perf) = node.meta_flow_log.as_mut() {
                 collect_stats = true;
                 perf.copy_and_reset_l4_perf_data(flow.reversed, &mut flow);
+                perf.copy_and_reset_media_perf_data(&mut flow);
                 let l7_timeout_count = self
                     .perf_cache
                     .borrow_mut()
@@ -1793,6 +2192,8 @@ impl FlowMap {
             .fetch_sub(1, Ordering::Relaxed);
         self.stats_counter.closed.fetch_add(1, Ordering::Relaxed);
 
+        self.flush_l7_logs(config, &mut node, timeout);
+
         let tagged_flow = Arc::new(
             self.tagged_flow_allocator
                 .allocate_one_with(node.tagged_flow.clone()),
@@ -1827,6 +2228,7 @@ impl FlowMap {
         {
             self.update_flow_direction(node, meta_packet); // 每个流统计数据输出前矫正流方向
             node.tagged_flow.flow.close_type = CloseType::ForcedReport;
+            self.flush_endpoint_stats(node);
             let flow = &mut node.tagged_flow.flow;
             if !config.collector_enabled {
                 return;
@@ -1840,6 +2242,7 @@ impl FlowMap {
             {
                 if let Some(perf) = node.meta_flow_log.as_mut() {
                     perf.copy_and_reset_l4_perf_data(flow.reversed, flow);
+                    perf.copy_and_reset_media_perf_data(flow);
                     let l7_timeout_count = self
                         .perf_cache
                         .borrow_mut()
@@ -1897,6 +2300,31 @@ impl FlowMap {
         }
     }
 
+    // logs kept by the l7 parser, such as the last interval of a stream, are sent before the flow is removed
+    fn flush_l7_logs(&mut self, config: &FlowConfig, node: &mut FlowNode, timeout: Duration) {
+        let Some(perf) = node.meta_flow_log.as_mut() else {
+            return;
+        };
+        let infos = match perf.flush_l7_logs(timeout.as_micros() as u64) {
+            crate::common::l7_protocol_log::L7ParseResult::Single(s) => vec![s],
+            crate::common::l7_protocol_log::L7ParseResult::Multi(m) => m,
+            crate::common::l7_protocol_log::L7ParseResult::None => return,
+        };
+        let flow = &node.tagged_flow.flow;
+        let mut meta_packet = MetaPacket::default();
+        meta_packet.lookup_key.timestamp = timeout.into();
+        meta_packet.lookup_key.proto = flow.flow_key.proto;
+        meta_packet.lookup_key.tap_type = flow.flow_key.tap_type;
+        meta_packet.lookup_key.direction = PacketDirection::ServerToClient;
+        meta_packet.signal_source = flow.signal_source;
+        if !Self::l7_log_parse_enabled(config, &meta_packet.lookup_key) {
+            return;
+        }
+        for info in infos {
+            self.write_to_app_proto_log(config, node, &meta_packet, info);
+        }
+    }
+
     fn flush_app_protolog(&mut self) {
         if self.protolog_buffer.len() > 0 {
             if let Err(_) = self.out_log_queue.send_all(&mut self.protolog_buffer) {
@@ -1950,6 +2378,23 @@ impl FlowMap {
                 flow_src_key,
                 flow_dst_key,
             ),
+            IpProtocol::SCTP => {
+                let flags = if let ProtocolData::SctpData(sctp_data) = &meta_packet.protocol_data {
+                    sctp_data.chunks.handshake_flags()
+                } else {
+                    TcpFlags::empty()
+                };
+                self.service_table.get_tcp_score(
+                    is_first_packet,
+                    meta_packet.need_reverse_flow,
+                    lookup_key.direction,
+                    flags,
+                    false,
+                    false,
+                    flow_src_key,
+                    flow_dst_key,
+                )
+            }
             _ => unimplemented!(),
         };
 
@@ -2025,7 +2470,7 @@ impl FlowMap {
                     flow_dst_key,
                 )
             }
-            IpProtocol::UDP => self.service_table.get_udp_score(
+            IpProtocol::UDP | IpProtocol::SCTP => self.service_table.get_udp_score(
                 false,
                 false,
                 PacketDirection::ClientToServer,
@@ -2049,6 +2494,9 @@ impl FlowMap {
 
     fn reverse_flow(node: &mut FlowNode, is_first_packet: bool) {
         node.policy_in_tick.swap(0, 1);
+        if node.tagged_flow.flow.tunnel.tunnel_type == TunnelType::GtpU {
+            mem::swap(&mut node.tunnel_id, &mut node.reverse_tunnel_id);
+        }
         node.policy_data_cache.swap(0, 1);
         if let Some(ep) = node.endpoint_data_cache.as_mut() {
             ep.reverse();
@@ -2084,18 +2532,21 @@ impl FlowMap {
 
         if need_reverse {
             Self::reverse_flow(node, is_first_packet);
-            // After modifying the flow direction, it is necessary to synchronize the service table to
-            // avoid incorrect directions in subsequent queries
-            let flow = &node.tagged_flow.flow;
-            if flow.signal_source != SignalSource::EBPF {
-                let src_epc_id = flow.flow_metrics_peers[0].l3_epc_id as i16;
-                let dst_epc_id = flow.flow_metrics_peers[1].l3_epc_id as i16;
-                let flow_key = &flow.flow_key;
-                self.service_table.reset_score(
-                    ServiceKey::new(flow_key.ip_src, src_epc_id, flow_key.port_src),
-                    ServiceKey::new(flow_key.ip_dst, dst_epc_id, flow_key.port_dst),
-                )
-            }
+        }
+        // After modifying the flow direction, it is necessary to synchronize the service table to
+        // avoid incorrect directions in subsequent queries. The direction confirmed by the L7 parser
+        // is also learned, so that later flows of the same server seen mid-life are corrected.
+        let flow = &mut node.tagged_flow.flow;
+        if flow.signal_source != SignalSource::EBPF
+            && (need_reverse || ServiceTable::is_weak_score(flow.direction_score))
+        {
+            let src_epc_id = flow.flow_metrics_peers[0].l3_epc_id as i16;
+            let dst_epc_id = flow.flow_metrics_peers[1].l3_epc_id as i16;
+            let flow_key = &flow.flow_key;
+            flow.direction_score = self.service_table.reset_score(
+                ServiceKey::new(flow_key.ip_src, src_epc_id, flow_key.port_src),
+                ServiceKey::new(flow_key.ip_dst, dst_epc_id, flow_key.port_dst),
+            );
         }
     }
 
@@ -2191,6 +2642,19 @@ pub struct FlowMapCounter {
     time_set_shrinks: AtomicU64,         // the total number of time_set HashSet shrinks
     pub l7_perf_cache_len: AtomicU64,    // the number of struct L7PerfCache::rrt_cache length
     pub l7_timeout_cache_len: AtomicU64, // the number of struct L7PerfCache::timeout_cache length
+    l7_perf_cache_hit: AtomicU64,        // the number of responses matched in L7PerfCache::rrt_cache
+    l7_perf_cache_miss: AtomicU64,       // the number of responses not matched in L7PerfCache::rrt_cache
+    l7_perf_cache_evicted: AtomicU64,    // the number of logs evicted from full L7PerfCache::rrt_cache
+    l7_perf_cache_capacity: AtomicU64,   // the capacity of L7PerfCache::rrt_cache
+    pub http_pipeline_mismatch: AtomicU64, // the number of http1 responses without request or requests without response
+    pub tcp_reassembly_memory: AtomicU64, // the bytes reserved by tcp reassembly buffers
+    pub tcp_reassembly_complete: AtomicU64, // the number of messages reassembled
+    pub tcp_reassembly_evicted: AtomicU64, // the number of messages given up for memory, lost segments or timeout
+    pub l7_tcp_retrans_skip: AtomicU64, // the number of retransmitted or overlapping tcp segments fully or partially skipped by l7 parsers
+    pub l7_inference_negative_cache_hit: AtomicU64, // the number of protocol checks skipped by negative inference cache
+    pub l7_inference_negative_cache_size: AtomicU64, // the number of protocols cached as failed in all flows
+    pub l7_direction_recovered: AtomicU64, // the number of flows whose protocol is recognized by a response
+    sampled_out: AtomicU64, // the number of packets ignored by flow sampling
 }
 
 impl RefCountable for FlowMapCounter {
@@ -2260,6 +2724,77 @@ impl RefCountable for FlowMapCounter {
                 CounterType::Gauged,
                 CounterValue::Unsigned(self.l7_timeout_cache_len.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "l7_perf_cache_hit",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.l7_perf_cache_hit.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "l7_perf_cache_miss",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.l7_perf_cache_miss.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "l7_perf_cache_evicted",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.l7_perf_cache_evicted.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "l7_perf_cache_capacity",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.l7_perf_cache_capacity.load(Ordering::Relaxed)),
+            ),
+            (
+                "http_pipeline_mismatch",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.http_pipeline_mismatch.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tcp_reassembly_memory",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.tcp_reassembly_memory.load(Ordering::Relaxed)),
+            ),
+            (
+                "tcp_reassembly_complete",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tcp_reassembly_complete.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tcp_reassembly_evicted",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tcp_reassembly_evicted.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "l7_tcp_retrans_skip",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.l7_tcp_retrans_skip.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "l7_inference_negative_cache_hit",
+                CounterType::Counted,
+                CounterValue::Unsigned(
+                    self.l7_inference_negative_cache_hit
+                        .swap(0, Ordering::Relaxed),
+                ),
+            ),
+            (
+                "l7_inference_negative_cache_size",
+                CounterType::Gauged,
+                CounterValue::Unsigned(
+                    self.l7_inference_negative_cache_size
+                        .load(Ordering::Relaxed),
+                ),
+            ),
+            (
+                "l7_direction_recovered",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.l7_direction_recovered.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "sampled_out",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.sampled_out.swap(0, Ordering::Relaxed)),
+            ),
         ]
     }
 }
@@ -2387,11 +2922,18 @@ mod tests {
     use super::*;
 
     use crate::{
-        common::{enums::EthernetType, flow::CloseType, tap_port::TapPort},
+        common::{
+            decapsulate::{TunnelInfo, TunnelType},
+            enums::EthernetType,
+            flow::CloseType,
+            meta_packet::{IcmpData, IcmpErrorKey, SctpData},
+            tap_port::TapPort,
+        },
+        flow_generator::{FlowTimeoutRule, TcpTimeout},
         utils::test::Capture,
     };
     use npb_pcap_policy::{NpbAction, NpbTunnelType, PolicyData, TapSide};
-    use public::utils::net::MacAddr;
+    use public::utils::{bitmap::parse_u16_range_list_to_bitmap, net::MacAddr};
 
     const DEFAULT_DURATION: Duration = Duration::from_millis(10);
 
@@ -2440,6 +2982,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn packet_clock() {
+        let (module_config, mut flow_map, output_queue_receiver) =
+            _new_flow_map_and_receiver(TridentType::TtProcess, None, false);
+        flow_map.follow_packet_clock();
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        // ticks before the first packet do not move the time window
+        flow_map.inject_flush_ticker(&config, Duration::ZERO);
+
+        // packets captured long before the system time
+        let capture_time = Duration::from_secs(1_600_000_000);
+        let mut packet0 = _new_meta_packet();
+        packet0.lookup_key.timestamp = capture_time.into();
+        flow_map.inject_meta_packet(&config, &mut packet0);
+        let mut packet1 = _new_meta_packet();
+        if let ProtocolData::TcpHeader(tcp_data) = &mut packet1.protocol_data {
+            tcp_data.flags = TcpFlags::RST;
+        }
+        _reverse_meta_packet(&mut packet1);
+        packet1.lookup_key.timestamp = (capture_time + DEFAULT_DURATION).into();
+        flow_map.inject_meta_packet(&config, &mut packet1);
+        assert_eq!(
+            flow_map.stats_counter.drop_by_window.load(Ordering::Relaxed),
+            0
+        );
+
+        flow_map.inject_flush_ticker(&config, capture_time + Duration::from_secs(10));
+        let tagged_flow = output_queue_receiver.recv(Some(TIME_UNIT)).unwrap();
+        assert_eq!(tagged_flow.flow.close_type, CloseType::ServerReset);
+        assert_eq!(Duration::from(tagged_flow.flow.start_time), capture_time);
+    }
+
     #[test]
     fn syn_fin() {
         let (module_config, mut flow_map, output_queue_receiver) =
@@ -3111,6 +3691,302 @@ mod tests {
         assert_eq!(perf_stats.counts_peers[1].zero_win_count, 1);
     }
 
+    #[test]
+    fn flow_timeout_rule() {
+        let (mut module_config, _, _) =
+            _new_flow_map_and_receiver(TridentType::TtProcess, None, false);
+        let established = Timestamp::from_secs(1800);
+        module_config.flow.flow_timeout_rules = vec![FlowTimeoutRule {
+            l7_protocol: None,
+            ports: "1883,8883".to_owned(),
+            port_bitmap: parse_u16_range_list_to_bitmap("1883,8883", true),
+            flow_timeout: TcpTimeout {
+                established,
+                ..Default::default()
+            }
+            .into(),
+        }];
+
+        let mut node = FlowNode::default();
+        node.tagged_flow.flow.flow_key.port_dst = 8883;
+        FlowMap::update_flow_timeout_rule(&module_config.flow, &mut node);
+        assert_eq!(node.flow_timeout_rule, Some(0));
+        assert_eq!(
+            FlowMap::flow_timeout(&module_config.flow, &node).established,
+            established
+        );
+
+        node.tagged_flow.flow.flow_key.port_dst = 53;
+        FlowMap::update_flow_timeout_rule(&module_config.flow, &mut node);
+        assert_eq!(node.flow_timeout_rule, None);
+        assert_eq!(
+            FlowMap::flow_timeout(&module_config.flow, &node),
+            &module_config.flow.flow_timeout
+        );
+    }
+
+    #[test]
+    fn flow_sampling() {
+        let (mut module_config, mut flow_map, _) =
+            _new_flow_map_and_receiver(TridentType::TtProcess, None, false);
+        module_config.flow.flow_sampling_permille = 0;
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let mut packet0 = _new_meta_packet();
+        flow_map.inject_meta_packet(&config, &mut packet0);
+        let mut packet1 = _new_meta_packet();
+        _reverse_meta_packet(&mut packet1);
+        flow_map.inject_meta_packet(&config, &mut packet1);
+        assert!(flow_map.node_map.as_ref().unwrap().0.is_empty());
+        assert_eq!(
+            flow_map.stats_counter.sampled_out.load(Ordering::Relaxed),
+            2
+        );
+
+        module_config.flow.flow_sampling_permille = 1000;
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let mut packet0 = _new_meta_packet();
+        flow_map.inject_meta_packet(&config, &mut packet0);
+        assert_eq!(flow_map.node_map.as_ref().unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn sampled_packets_skip_perf() {
+        let (module_config, mut flow_map, output_queue_receiver) =
+            _new_flow_map_and_receiver(TridentType::TtProcess, None, false);
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let mut packet0 = _new_meta_packet();
+        packet0.sampling_rate = 100;
+        flow_map.inject_meta_packet(&config, &mut packet0);
+        let mut packet1 = _new_meta_packet();
+        if let ProtocolData::TcpHeader(tcp_data) = &mut packet1.protocol_data {
+            tcp_data.flags = TcpFlags::RST;
+        }
+        _reverse_meta_packet(&mut packet1);
+        packet1.sampling_rate = 100;
+        packet1.lookup_key.timestamp += DEFAULT_DURATION.into();
+        let flush_timestamp = packet1.lookup_key.timestamp.into();
+        flow_map.inject_meta_packet(&config, &mut packet1);
+
+        flow_map.inject_flush_ticker(&config, flush_timestamp + Duration::from_secs(10));
+
+        let tagged_flow = output_queue_receiver.recv(Some(TIME_UNIT)).unwrap();
+        assert!(tagged_flow.flow.flow_perf_stats.is_none());
+        let peer_src = &tagged_flow.flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC];
+        assert_eq!(peer_src.packet_count, 100);
+    }
+
+    #[test]
+    fn tunnel_id_in_flow_key() {
+        let (mut module_config, mut flow_map, _) =
+            _new_flow_map_and_receiver(TridentType::TtProcess, None, false);
+        module_config.flow.tunnel_id_in_flow_key = true;
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let new_tunnel_packet = |id: u32, reverse: bool| {
+            let mut packet = _new_meta_packet();
+            if reverse {
+                _reverse_meta_packet(&mut packet);
+            }
+            packet.tunnel = Some(TunnelInfo {
+                id,
+                tunnel_type: TunnelType::TencentGre,
+                tier: 1,
+                ..Default::default()
+            });
+            packet
+        };
+        for (id, reverse) in [(10, false), (20, false), (10, true), (20, true)] {
+            let mut packet = new_tunnel_packet(id, reverse);
+            flow_map.inject_meta_packet(&config, &mut packet);
+        }
+
+        let node_map = &flow_map.node_map.as_ref().unwrap().0;
+        let nodes = node_map.values().flatten().collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 2);
+        for node in nodes {
+            let peers = &node.tagged_flow.flow.flow_metrics_peers;
+            assert_eq!(peers[FLOW_METRICS_PEER_SRC].packet_count, 1);
+            assert_eq!(peers[FLOW_METRICS_PEER_DST].packet_count, 1);
+        }
+    }
+
+    #[test]
+    fn gtpu_teid_in_flow_key() {
+        let (module_config, mut flow_map, _) =
+            _new_flow_map_and_receiver(TridentType::TtProcess, None, false);
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let new_gtpu_packet = |teid: u32, reverse: bool| {
+            let mut packet = _new_meta_packet();
+            if reverse {
+                _reverse_meta_packet(&mut packet);
+            }
+            packet.tunnel = Some(TunnelInfo {
+                id: teid,
+                tunnel_type: TunnelType::GtpU,
+                tier: 1,
+                ..Default::default()
+            });
+            packet
+        };
+        // two sessions with the same inner 5-tuple, uplink and downlink TEIDs differ
+        for (teid, reverse) in [(10, false), (30, false), (20, true), (40, true)] {
+            let mut packet = new_gtpu_packet(teid, reverse);
+            flow_map.inject_meta_packet(&config, &mut packet);
+        }
+
+        let node_map = &flow_map.node_map.as_ref().unwrap().0;
+        let mut nodes = node_map.values().flatten().collect::<Vec<_>>();
+        nodes.sort_by_key(|node| node.tunnel_id);
+        assert_eq!(nodes.len(), 2);
+        for (node, (teid, reverse_teid)) in nodes.into_iter().zip([(10, 20), (30, 40)]) {
+            assert_eq!(node.tunnel_id, teid);
+            assert_eq!(node.reverse_tunnel_id, reverse_teid);
+            let peers = &node.tagged_flow.flow.flow_metrics_peers;
+            assert_eq!(peers[FLOW_METRICS_PEER_SRC].packet_count, 1);
+            assert_eq!(peers[FLOW_METRICS_PEER_DST].packet_count, 1);
+        }
+    }
+
+    #[test]
+    fn icmp_error() {
+        let (module_config, mut flow_map, _) =
+            _new_flow_map_and_receiver(TridentType::TtProcess, None, false);
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let mut packet0 = _new_meta_packet();
+        packet0.lookup_key.proto = IpProtocol::UDP;
+        packet0.header_type = HeaderType::Ipv4Udp;
+        flow_map.inject_meta_packet(&config, &mut packet0);
+
+        // port unreachable sent by the server for the udp packet
+        let mut packet1 = _new_meta_packet();
+        _reverse_meta_packet(&mut packet1);
+        packet1.lookup_key.proto = IpProtocol::ICMPV4;
+        packet1.lookup_key.src_port = 0;
+        packet1.lookup_key.dst_port = 0;
+        packet1.header_type = HeaderType::Ipv4Icmp;
+        packet1.protocol_data = ProtocolData::IcmpData(IcmpData {
+            icmp_type: 3,
+            icmp_code: 3,
+            error_key: Some(IcmpErrorKey {
+                src_ip: packet0.lookup_key.src_ip,
+                dst_ip: packet0.lookup_key.dst_ip,
+                proto: IpProtocol::UDP,
+                src_port: packet0.lookup_key.src_port,
+                dst_port: packet0.lookup_key.dst_port,
+            }),
+            ..Default::default()
+        });
+        flow_map.inject_meta_packet(&config, &mut packet1);
+
+        let node_map = &flow_map.node_map.as_ref().unwrap().0;
+        let flows = node_map
+            .values()
+            .flatten()
+            .map(|node| &node.tagged_flow.flow)
+            .collect::<Vec<_>>();
+        let udp_flow = flows
+            .iter()
+            .find(|f| f.flow_key.proto == IpProtocol::UDP)
+            .unwrap();
+        assert_eq!(udp_flow.icmp_error_type, 3);
+        assert_eq!(udp_flow.icmp_error_code, 3);
+        assert_eq!(udp_flow.icmp_error_count, 1);
+        let icmp_flow = flows
+            .iter()
+            .find(|f| f.flow_key.proto == IpProtocol::ICMPV4)
+            .unwrap();
+        assert_eq!((icmp_flow.icmp_type, icmp_flow.icmp_code), (3, 3));
+    }
+
+    #[test]
+    fn sctp_association() {
+        let (module_config, mut flow_map, output_queue_receiver) =
+            _new_flow_map_and_receiver(TridentType::TtProcess, None, false);
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let new_sctp_packet = |chunks: SctpChunks, stream_id: u16, reverse: bool| {
+            let mut packet = _new_meta_packet();
+            if reverse {
+                _reverse_meta_packet(&mut packet);
+            }
+            packet.lookup_key.proto = IpProtocol::SCTP;
+            packet.header_type = HeaderType::Ipv4;
+            packet.protocol_data = ProtocolData::SctpData(SctpData {
+                chunks,
+                ppid: if chunks.contains(SctpChunks::DATA) {
+                    46
+                } else {
+                    0
+                },
+                stream_ids: [stream_id, 0, 0, 0],
+                stream_count: chunks.contains(SctpChunks::DATA) as u8,
+                ..Default::default()
+            });
+            packet
+        };
+
+        let mut packet0 = new_sctp_packet(SctpChunks::INIT, 0, false);
+        flow_map.inject_meta_packet(&config, &mut packet0);
+        let mut packet1 = new_sctp_packet(SctpChunks::INIT_ACK, 0, true);
+        flow_map.inject_meta_packet(&config, &mut packet1);
+        for stream_id in [1, 2, 1] {
+            let mut packet = new_sctp_packet(SctpChunks::DATA, stream_id, false);
+            flow_map.inject_meta_packet(&config, &mut packet);
+        }
+        let mut packet2 = new_sctp_packet(SctpChunks::ABORT, 0, true);
+        packet2.lookup_key.timestamp += DEFAULT_DURATION.into();
+        let flush_timestamp = packet2.lookup_key.timestamp.into();
+        flow_map.inject_meta_packet(&config, &mut packet2);
+
+        flow_map.inject_flush_ticker(&config, flush_timestamp + Duration::from_secs(10));
+
+        let tagged_flow = output_queue_receiver.recv(Some(TIME_UNIT)).unwrap();
+        assert_eq!(tagged_flow.flow.flow_key.proto, IpProtocol::SCTP);
+        assert_eq!(tagged_flow.flow.close_type, CloseType::ServerReset);
+        assert_eq!(tagged_flow.flow.sctp_ppid, 46);
+        assert_eq!(tagged_flow.flow.sctp_stream_count, 2);
+    }
+
     #[test]
     fn sort_nodes_by_timeout() {
         let mut nodes = vec![
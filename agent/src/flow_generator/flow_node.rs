@@ -21,7 +21,10 @@ use crate::common::{
     decapsulate::TunnelType,
     endpoint::EndpointDataPov,
     enums::{EthernetType, TapType, TcpFlags},
-    flow::{FlowMetricsPeer, L7PerfStats, PacketDirection, SignalSource, TcpPerfStats},
+    flow::{
+        FlowMetricsPeer, L7PerfStats, L7Stats, MediaPerfStats, PacketDirection, SignalSource,
+        TcpPerfStats,
+    },
     lookup_key::LookupKey,
     meta_packet::MetaPacket,
     tagged_flow::TaggedFlow,
@@ -122,6 +125,8 @@ pub struct FlowNode {
     pub recent_time: Timestamp,
     // 相对超时时间
     pub timeout: Timestamp,
+    // index of the matched rule in FlowConfig.flow_timeout_rules
+    pub flow_timeout_rule: Option<usize>,
     // 用作time_set比对的标识，等于FlowTimeKey的timestamp_key, 只有创建FlowNode和刷新更新流节点的超时才会更新
     pub timestamp_key: u64,
 
@@ -137,6 +142,15 @@ pub struct FlowNode {
     pub policy_in_tick: [bool; 2],
     pub packet_in_tick: bool, // 当前统计周期（目前是自然秒）是否有包
     pub flow_state: FlowState,
+    // id of the outermost tunnel when the flow is created, 0 for untunneled flows
+    pub tunnel_id: u32,
+    // GTP-U TEIDs differ in each direction, this is the TEID of server to client packets
+    // learned from the first of them, 0 if not seen yet
+    pub reverse_tunnel_id: u32,
+    // perf stats of endpoints switched from in the current statistical interval
+    pub endpoint_stats: Vec<L7Stats>,
+    // distinct stream ids of sctp associations
+    pub sctp_stream_ids: Vec<u16>,
 
     // Enterprise Edition Feature: packet-sequence
     pub packet_sequence_block: Option<Box<PacketSequenceBlock>>,
@@ -149,6 +163,7 @@ impl FlowNode {
         let flow = &mut self.tagged_flow.flow;
         flow.flow_stat_time = Default::default();
         flow.is_new_flow = false;
+        flow.icmp_error_count = 0;
         let flow_metrics_peer_src = &mut flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC];
         flow_metrics_peer_src.packet_count = 0;
         flow_metrics_peer_src.byte_count = 0;
@@ -166,6 +181,7 @@ impl FlowNode {
         if let Some(ref mut flow_perf_stats) = &mut flow.flow_perf_stats {
             flow_perf_stats.tcp = TcpPerfStats::default();
             flow_perf_stats.l7 = L7PerfStats::default();
+            flow_perf_stats.media = MediaPerfStats::default();
         }
     }
 
@@ -175,6 +191,7 @@ impl FlowNode {
         ignore_l2_end: bool,
         ignore_tor_mac: bool,
         ignore_idc_vlan: bool,
+        tunnel_id_in_flow_key: bool,
         trident_type: TridentType,
     ) -> bool {
         if meta_packet.signal_source == SignalSource::EBPF {
@@ -207,7 +224,7 @@ impl FlowNode {
             return false;
         }
 
-        if flow.vlan != meta_packet.vlan
+        if (flow.vlan != meta_packet.vlan || flow.outer_vlan != meta_packet.outer_vlan)
             && meta_lookup_key.tap_type != TapType::Cloud
             && !ignore_idc_vlan
         {
@@ -252,6 +269,18 @@ impl FlowNode {
             }
         }
 
+        // GTP-U flows are always keyed by TEID, which is checked after the direction is known
+        let gtpu_teid = meta_packet
+            .tunnel
+            .filter(|t| t.tunnel_type == TunnelType::GtpU)
+            .map(|t| t.id);
+        if gtpu_teid.is_none()
+            && tunnel_id_in_flow_key
+            && self.tunnel_id != meta_packet.tunnel.map(|t| t.id).unwrap_or_default()
+        {
+            return false;
+        }
+
         // Ipv4/Ipv6 solve
         let mac_match = Self::mac_match(meta_packet, ignore_l2_end, ignore_tor_mac, trident_type);
         if flow_key.ip_src == meta_lookup_key.src_ip
@@ -275,6 +304,11 @@ impl FlowNode {
         } else {
             return false;
         }
+        if let Some(teid) = gtpu_teid {
+            if !self.gtpu_teid_match(meta_packet.lookup_key.direction, teid) {
+                return false;
+            }
+        }
         Self::endpoint_match_with_direction(&flow.flow_metrics_peers, meta_packet)
             && Self::mac_match_with_direction(
                 meta_packet,
@@ -284,6 +318,33 @@ impl FlowNode {
             )
     }
 
+    fn gtpu_teid_match(&self, direction: PacketDirection, teid: u32) -> bool {
+        let expected = if direction == PacketDirection::ClientToServer {
+            self.tunnel_id
+        } else {
+            self.reverse_tunnel_id
+        };
+        expected == 0 || expected == teid
+    }
+
+    // Records the TEID of the direction on its first packet, must be called after match_node
+    pub fn learn_gtpu_teid(&mut self, meta_packet: &MetaPacket) {
+        let Some(tunnel) = meta_packet.tunnel else {
+            return;
+        };
+        if tunnel.tunnel_type != TunnelType::GtpU {
+            return;
+        }
+        let teid = if meta_packet.lookup_key.direction == PacketDirection::ClientToServer {
+            &mut self.tunnel_id
+        } else {
+            &mut self.reverse_tunnel_id
+        };
+        if *teid == 0 {
+            *teid = tunnel.id;
+        }
+    }
+
     fn is_hyper_v(trident_type: TridentType) -> bool {
         trident_type == TridentType::TtHyperVCompute || trident_type == TridentType::TtHyperVNetwork
     }
@@ -786,7 +786,10 @@ mod tests {
             min_arrived_time: Timestamp::ZERO,
             recent_time: Timestamp::ZERO,
             timeout: Timestamp::ZERO,
+            flow_timeout_rule: None,
             flow_state: FlowState::Raw,
+            tunnel_id: 0,
+            reverse_tunnel_id: 0,
             meta_flow_log: None,
             policy_data_cache: Default::default(),
             endpoint_data_cache: {
@@ -823,6 +826,8 @@ mod tests {
             next_tcp_seq1: 0,
             packet_in_tick: false,
             policy_in_tick: [false; 2],
+            endpoint_stats: vec![],
+            sctp_stream_ids: vec![],
             packet_sequence_block: Some(Box::new(PacketSequenceBlock::default())), // Enterprise Edition Feature: packet-sequence
         };
 
@@ -854,7 +859,10 @@ mod tests {
             min_arrived_time: Timestamp::ZERO,
             recent_time: Timestamp::ZERO,
             timeout: Timestamp::ZERO,
+            flow_timeout_rule: None,
             flow_state: FlowState::Raw,
+            tunnel_id: 0,
+            reverse_tunnel_id: 0,
             meta_flow_log: None,
             policy_data_cache: Default::default(),
             endpoint_data_cache: {
@@ -891,6 +899,8 @@ mod tests {
             next_tcp_seq1: 0,
             packet_in_tick: false,
             policy_in_tick: [false; 2],
+            endpoint_stats: vec![],
+            sctp_stream_ids: vec![],
             packet_sequence_block: Some(Box::new(PacketSequenceBlock::default())), // Enterprise Edition Feature: packet-sequence
         };
 
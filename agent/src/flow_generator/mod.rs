@@ -22,20 +22,24 @@ pub(crate) mod flow_node;
 pub(crate) mod flow_state;
 mod packet_sequence; // Enterprise Edition Feature: packet-sequence
 pub mod perf;
-mod pool;
+pub(crate) mod pool;
 pub mod protocol_logs;
 mod service_table;
+mod tcp_reassembly;
 
 pub use app_table::AppTable;
 pub use error::{Error, Result};
-pub use flow_config::{FlowMapConfig, FlowMapRuntimeConfig, FlowTimeout, TcpTimeout};
+pub use flow_config::{
+    FlowMapConfig, FlowMapRuntimeConfig, FlowTimeout, FlowTimeoutRule, TcpTimeout,
+};
 pub use flow_map::FlowMap;
 use flow_node::{FlowMapKey, FlowNode};
 pub use flow_state::FlowState;
 pub use packet_sequence::PacketSequenceParser; // Enterprise Edition Feature: packet-sequence
 pub use protocol_logs::{
-    AppProtoHead, AppProtoLogsBaseInfo, DnsLog, DubboLog, HttpLog, KafkaLog, L7ProtoRawDataType,
-    LogMessageType, MetaAppProto, MqttLog, MysqlLog, RedisLog, TlsLog,
+    AppProtoHead, AppProtoLogsBaseInfo, DhcpLog, DnsLog, DubboLog, HttpLog, KafkaLog, KerberosLog,
+    L7ProtoRawDataType, LogMessageType, MetaAppProto, MqttLog, MysqlLog, NtpLog, RadiusLog,
+    RedisLog, RtpLog, SipLog, TlsLog,
 };
 
 use std::time::Duration;
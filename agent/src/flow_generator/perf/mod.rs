@@ -21,14 +21,19 @@ pub(crate) mod udp;
 
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::mem;
+use std::net::IpAddr;
 use std::rc::Rc;
 use std::slice;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
 
 use enum_dispatch::enum_dispatch;
+use ipnet::IpNet;
+use log::warn;
 use public::bitmap::Bitmap;
 use public::l7_protocol::L7ProtocolEnum;
+use public::utils::bitmap::parse_u16_range_list_to_bitmap;
 
 use super::protocol_logs::sql::ObfuscateCache;
 use super::{
@@ -36,12 +41,17 @@ use super::{
     error::{Error, Result},
     flow_map::FlowMapCounter,
     pool::MemoryPool,
-    protocol_logs::AppProtoHead,
+    protocol_logs::{AppProtoHead, L7ResponseStatus, LogMessageType},
+    tcp_reassembly::{Reassembled, TcpReassembly},
 };
 
 use crate::common::l7_protocol_log::L7PerfCache;
 use crate::common::{
-    flow::{Flow, L7PerfStats},
+    flow::{
+        ApdexCounts, ConcurrencyStats, Flow, L7PerfStats, MessageSizeStats, ResponseCodeCounts,
+        RrtHistogram, RrtSketch,
+    },
+    l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
     l7_protocol_log::L7ParseResult,
 };
 use crate::plugin::wasm::WasmVm;
@@ -50,15 +60,16 @@ use crate::plugin::{c_ffi::SoPluginFunc, shared_obj::SoPluginCounterMap};
 use crate::rpc::get_timestamp;
 use crate::{
     common::{
+        enums::IpProtocol,
         flow::{FlowPerfStats, L4Protocol, L7Protocol, PacketDirection, SignalSource},
         l7_protocol_log::{
             get_all_protocol, get_parser, L7ProtocolBitmap, L7ProtocolParser,
             L7ProtocolParserInterface, ParseParam,
         },
-        meta_packet::MetaPacket,
+        meta_packet::{MetaPacket, ProtocolData},
         Timestamp,
     },
-    config::{handler::LogParserConfig, FlowConfig},
+    config::{handler::LogParserConfig, FlowConfig, L7ProtocolHint},
 };
 
 use {icmp::IcmpPerf, tcp::TcpPerf, udp::UdpPerf};
@@ -121,16 +132,70 @@ impl L4FlowPerf for L4FlowPerfTable {
 
 pub type L7ProtocolTuple = (L7Protocol, Option<Bitmap>);
 
+struct L7ProtocolHintRule {
+    // None means any server
+    net: Option<IpNet>,
+    ports: Bitmap,
+    protocol: L7Protocol,
+    exclusive: bool,
+}
+
+impl L7ProtocolHintRule {
+    fn new(hint: &L7ProtocolHint) -> Option<Self> {
+        let protocol = L7Protocol::from(hint.protocol.clone());
+        if protocol == L7Protocol::Unknown {
+            warn!(
+                "l7 protocol hint of unknown protocol {} ignored",
+                hint.protocol
+            );
+            return None;
+        }
+        let net = if hint.cidr.is_empty() {
+            None
+        } else {
+            // plain ip address is treated as a host route
+            let net = hint.cidr.parse::<IpNet>().ok().or_else(|| {
+                let ip = hint.cidr.parse::<IpAddr>().ok()?;
+                IpNet::new(ip, if ip.is_ipv4() { 32 } else { 128 }).ok()
+            });
+            match net {
+                Some(net) => Some(net),
+                None => {
+                    warn!("l7 protocol hint with invalid cidr {} ignored", hint.cidr);
+                    return None;
+                }
+            }
+        };
+        let Some(ports) = parse_u16_range_list_to_bitmap(&hint.ports, false) else {
+            warn!("l7 protocol hint with invalid ports {} ignored", hint.ports);
+            return None;
+        };
+        Some(Self {
+            net,
+            ports,
+            protocol,
+            exclusive: hint.exclusive,
+        })
+    }
+
+    fn matches(&self, ip: IpAddr, port: u16) -> bool {
+        self.ports.get(port as usize).unwrap_or_default()
+            && self.net.map(|n| n.contains(&ip)).unwrap_or(true)
+    }
+}
+
 // None in Vec means all ports
 pub struct L7ProtocolChecker {
     tcp: Vec<L7ProtocolTuple>,
     udp: Vec<L7ProtocolTuple>,
+    hints: Vec<L7ProtocolHintRule>,
 }
 
 impl L7ProtocolChecker {
     pub fn new(
         protocol_bitmap: &L7ProtocolBitmap,
         port_bitmap: &HashMap<L7Protocol, Bitmap>,
+        hints: &[L7ProtocolHint],
     ) -> Self {
         let mut tcp = vec![];
         let mut udp = vec![];
@@ -147,20 +212,43 @@ impl L7ProtocolChecker {
             }
         }
 
-        L7ProtocolChecker { tcp, udp }
+        L7ProtocolChecker {
+            tcp,
+            udp,
+            hints: hints.iter().filter_map(L7ProtocolHintRule::new).collect(),
+        }
     }
 
+    // the hinted protocol of the server is checked first regardless of its port bitmap,
+    // and other protocols are not checked if the hint is exclusive
     pub fn possible_protocols(
         &self,
         l4_protocol: L4Protocol,
+        server_ip: IpAddr,
         port: u16,
     ) -> L7ProtocolCheckerIterator {
+        let protocols = match l4_protocol {
+            L4Protocol::Tcp => &self.tcp[..],
+            L4Protocol::Udp => &self.udp[..],
+            _ => &[],
+        };
+        // hints of disabled protocols or protocols not parsable on l4_protocol are ignored
+        let hint = self.hints.iter().find_map(|h| {
+            if !h.matches(server_ip, port) {
+                return None;
+            }
+            protocols
+                .iter()
+                .find(|(p, _)| *p == h.protocol)
+                .map(|(p, _)| (p, h.exclusive))
+        });
         L7ProtocolCheckerIterator {
-            iter: match l4_protocol {
-                L4Protocol::Tcp => self.tcp.iter(),
-                L4Protocol::Udp => self.udp.iter(),
-                _ => [].iter(),
+            iter: match hint {
+                Some((_, true)) => [].iter(),
+                _ => protocols.iter(),
             },
+            hint: hint.map(|(p, _)| p),
+            hint_checked: false,
             port,
         }
     }
@@ -168,6 +256,8 @@ impl L7ProtocolChecker {
 
 pub struct L7ProtocolCheckerIterator<'a> {
     iter: slice::Iter<'a, L7ProtocolTuple>,
+    hint: Option<&'a L7Protocol>,
+    hint_checked: bool,
     port: u16,
 }
 
@@ -175,7 +265,16 @@ impl<'a> Iterator for L7ProtocolCheckerIterator<'a> {
     type Item = &'a L7Protocol;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if !self.hint_checked {
+            self.hint_checked = true;
+            if self.hint.is_some() {
+                return self.hint;
+            }
+        }
         while let Some((proto, bitmap)) = self.iter.next() {
+            if Some(proto) == self.hint {
+                continue;
+            }
             match bitmap {
                 // if bitmap is not None and does not has port in it, check next protocol
                 Some(b) if !b.get(self.port as usize).unwrap_or_default() => continue,
@@ -186,6 +285,246 @@ impl<'a> Iterator for L7ProtocolCheckerIterator<'a> {
     }
 }
 
+// Caches protocols failed to check on a flow, so that long-lived unknown flows do not run
+// check_payload of every protocol on every packet. A protocol is skipped after it fails
+// `retries` times, and the cache is cleared when the flow recovers from inference failure.
+struct NegativeProtocolCache {
+    misses: Vec<(L7Protocol, u8)>,
+    // the number of protocols reached the retry budget, reported as cache size
+    cached: u64,
+    counter: Arc<FlowMapCounter>,
+}
+
+impl NegativeProtocolCache {
+    fn new(counter: Arc<FlowMapCounter>) -> Self {
+        Self {
+            misses: vec![],
+            cached: 0,
+            counter,
+        }
+    }
+
+    fn contains(&self, protocol: L7Protocol, retries: u8) -> bool {
+        let hit = self
+            .misses
+            .iter()
+            .any(|(p, count)| *p == protocol && *count >= retries);
+        if hit {
+            self.counter
+                .l7_inference_negative_cache_hit
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        hit
+    }
+
+    fn miss(&mut self, protocol: L7Protocol, retries: u8) {
+        let count = match self.misses.iter_mut().position(|(p, _)| *p == protocol) {
+            Some(i) => &mut self.misses[i].1,
+            None => {
+                self.misses.push((protocol, 0));
+                &mut self.misses.last_mut().unwrap().1
+            }
+        };
+        if *count < retries {
+            *count += 1;
+            if *count == retries {
+                self.cached += 1;
+                self.counter
+                    .l7_inference_negative_cache_size
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.misses.clear();
+        if self.cached > 0 {
+            self.counter
+                .l7_inference_negative_cache_size
+                .fetch_sub(self.cached, Ordering::Relaxed);
+            self.cached = 0;
+        }
+    }
+}
+
+impl Drop for NegativeProtocolCache {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+const PARSED_SEGMENTS_LEN: usize = 8;
+
+// Byte ranges of recently parsed tcp segments of one direction, adjacent or
+// overlapping ranges are merged. Segments inside the ranges are retransmissions,
+// and only the new tail of a segment partially overlapping the ranges is parsed.
+#[derive(Default)]
+struct ParsedSegments {
+    // [start, end) in sequence space, from the oldest to the newest
+    ranges: [(u32, u32); PARSED_SEGMENTS_LEN],
+    len: usize,
+}
+
+impl ParsedSegments {
+    // whether seq is in [start, end], sequence numbers may wrap
+    fn within(seq: u32, start: u32, end: u32) -> bool {
+        seq.wrapping_sub(start) <= end.wrapping_sub(start)
+    }
+
+    // returns the length of leading bytes of the segment parsed already, and records the segment
+    fn check_and_insert(&mut self, seq: u32, len: u32) -> u32 {
+        let parsed = self.ranges[..self.len]
+            .iter()
+            .find(|(start, end)| seq.wrapping_sub(*start) < end.wrapping_sub(*start))
+            .map(|(_, end)| end.wrapping_sub(seq).min(len))
+            .unwrap_or(0);
+        if parsed == len {
+            return parsed;
+        }
+
+        let (mut start, mut end) = (seq, seq.wrapping_add(len));
+        let mut i = 0;
+        while i < self.len {
+            let (s, e) = self.ranges[i];
+            if !Self::within(s, start, end) && !Self::within(start, s, e) {
+                i += 1;
+                continue;
+            }
+            if Self::within(start, s, e) {
+                start = s;
+            }
+            if e.wrapping_sub(start) > end.wrapping_sub(start) {
+                end = e;
+            }
+            self.ranges.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+        if self.len == PARSED_SEGMENTS_LEN {
+            // forget the oldest range
+            self.ranges.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.ranges[self.len] = (start, end);
+        self.len += 1;
+        parsed
+    }
+}
+
+// Perf details recorded from parsed logs regardless of protocol parsers
+#[derive(Default)]
+struct L7PerfDetails {
+    rrt_histogram: RrtHistogram,
+    rrt_sketch: Option<RrtSketch>,
+    response_codes: ResponseCodeCounts,
+    apdex: ApdexCounts,
+    req_size: MessageSizeStats,
+    resp_size: MessageSizeStats,
+    concurrency: ConcurrencyStats,
+}
+
+impl L7PerfDetails {
+    fn record(
+        &mut self,
+        log_parser_config: &LogParserConfig,
+        protocol: L7Protocol,
+        result: &L7ParseResult,
+    ) {
+        let template = log_parser_config.rrt_histograms.get(&protocol);
+        let alpha = log_parser_config.rrt_sketch_alpha;
+        let top_k = log_parser_config.response_code_top_k;
+        let apdex_threshold = log_parser_config.apdex_thresholds.get(&protocol).copied();
+        let size_enabled = log_parser_config.l7_message_size_stats.enabled;
+        if template.is_none()
+            && alpha == 0
+            && top_k == 0
+            && apdex_threshold.is_none()
+            && !size_enabled
+        {
+            return;
+        }
+        if let Some(t) = template {
+            if !self.rrt_histogram.same_layout(t) {
+                self.rrt_histogram = *t;
+            }
+        }
+        let mut sketch = if alpha > 0 {
+            let s = self.rrt_sketch.get_or_insert_with(|| RrtSketch::new(alpha));
+            if s.alpha != alpha {
+                *s = RrtSketch::new(alpha);
+            }
+            Some(s)
+        } else {
+            None
+        };
+        let histogram = &mut self.rrt_histogram;
+        let response_codes = &mut self.response_codes;
+        let apdex = &mut self.apdex;
+        let req_size = &mut self.req_size;
+        let resp_size = &mut self.resp_size;
+        let mut record = |info: &L7ProtocolInfo| {
+            let Some(head) = info.app_proto_head() else {
+                return;
+            };
+            if size_enabled {
+                if head.msg_type != LogMessageType::Response {
+                    if let Some(len) = info.request_len() {
+                        req_size.record(len);
+                    }
+                }
+                if head.msg_type != LogMessageType::Request {
+                    if let Some(len) = info.response_len() {
+                        resp_size.record(len);
+                    }
+                }
+            }
+            if head.msg_type != LogMessageType::Request {
+                if top_k > 0 {
+                    if let Some(code) = info.response_code() {
+                        response_codes.add(&code, top_k);
+                    }
+                }
+                // responses without rrt are counted only if they are errors
+                if let Some(t) = apdex_threshold {
+                    let is_error = matches!(
+                        info.response_status(),
+                        L7ResponseStatus::ClientError
+                            | L7ResponseStatus::ServerError
+                            | L7ResponseStatus::Error
+                    );
+                    if head.rrt > 0 || is_error {
+                        apdex.record(head.rrt, t, is_error);
+                    }
+                }
+            }
+            if head.rrt == 0 {
+                return;
+            }
+            if template.is_some() {
+                histogram.record(head.rrt);
+            }
+            if let Some(s) = sketch.as_mut() {
+                s.record(head.rrt);
+            }
+        };
+        match result {
+            L7ParseResult::Single(info) => record(info),
+            L7ParseResult::Multi(infos) => infos.iter().for_each(record),
+            L7ParseResult::None => (),
+        }
+    }
+
+    // requests of the flow waiting for responses, maintained by rrt calculation
+    fn record_concurrency(&mut self, param: &ParseParam) {
+        let perf_cache = param.l7_perf_cache.borrow();
+        let in_flight = perf_cache
+            .timeout_cache
+            .peek(&param.flow_id)
+            .map(|(in_cached_req, _)| *in_cached_req)
+            .unwrap_or_default();
+        self.concurrency.record(in_flight as u32);
+    }
+}
+
 pub struct FlowLog {
     l4: Option<Box<L4FlowPerfTable>>,
     l7_protocol_log_parser: Option<Box<L7ProtocolParser>>,
@@ -215,10 +554,21 @@ pub struct FlowLog {
 
     ntp_diff: Arc<AtomicI64>,
     obfuscate_cache: Option<ObfuscateCache>,
+
+    tcp_reassembly: Option<Box<TcpReassembly>>,
+    // parsed tcp segments in the first packet direction and the reversed
+    parsed_segments: [ParsedSegments; 2],
+
+    negative_protocol_cache: NegativeProtocolCache,
+
+    // recorded since the last perf stats report
+    perf_details: L7PerfDetails,
 }
 
 impl FlowLog {
     const PROTOCOL_CHECK_LIMIT: usize = 5;
+    // keeps connection keys of datagram protocols apart from flow ids in the perf cache
+    const DATAGRAM_CONNECTION_KEY_FLAG: u64 = 1 << 63;
 
     // if flow parse fail exceed l7_protocol_inference_max_fail_count and time exceed l7_protocol_inference_ttl,
     // recover the flow check and parse
@@ -228,10 +578,56 @@ impl FlowLog {
             if now.as_secs() > self.last_fail.unwrap() + self.l7_protocol_inference_ttl {
                 self.last_fail = None;
                 self.is_skip = false;
+                self.negative_protocol_cache.clear();
             }
         }
     }
 
+    fn set_datagram_connection(
+        parser: &L7ProtocolParser,
+        packet: &MetaPacket,
+        param: &mut ParseParam,
+    ) {
+        if packet.lookup_key.proto != IpProtocol::UDP {
+            return;
+        }
+        if let Some(key) = packet
+            .get_l4_payload()
+            .and_then(|p| parser.datagram_connection_key(p, packet.lookup_key.direction))
+        {
+            param.flow_id = key | Self::DATAGRAM_CONNECTION_KEY_FLAG;
+        }
+    }
+
+    // parse coalesced messages in a datagram one by one
+    fn parse_datagram<P: L7ProtocolParserInterface>(
+        parser: &mut P,
+        payload: &[u8],
+        param: &ParseParam,
+    ) -> Result<L7ParseResult> {
+        let mut infos = vec![];
+        let mut error = None;
+        let mut rest = payload;
+        while !rest.is_empty() {
+            let len = match parser.datagram_message_len(rest) {
+                Some(len) if len > 0 && len <= rest.len() => len,
+                _ => rest.len(),
+            };
+            match parser.parse_payload(&rest[..len], param) {
+                Ok(L7ParseResult::Single(info)) => infos.push(info),
+                Ok(L7ParseResult::Multi(m)) => infos.extend(m),
+                Ok(L7ParseResult::None) => (),
+                Err(e) => error = Some(e),
+            }
+            rest = &rest[len..];
+        }
+        match infos.len() {
+            0 => error.map_or(Ok(L7ParseResult::None), Err),
+            1 => Ok(L7ParseResult::Single(infos.pop().unwrap())),
+            _ => Ok(L7ParseResult::Multi(infos)),
+        }
+    }
+
     fn l7_parse_log(
         &mut self,
         flow_config: &FlowConfig,
@@ -241,8 +637,9 @@ impl FlowLog {
         parse_param: &ParseParam,
         local_epc: i32,
         remote_epc: i32,
+        reassembled: Option<&[u8]>,
     ) -> Result<L7ParseResult> {
-        if let Some(payload) = packet.get_l4_payload() {
+        if let Some(payload) = reassembled.or_else(|| packet.get_l4_payload()) {
             let parser = self.l7_protocol_log_parser.as_mut().unwrap();
 
             if log_parser_config
@@ -252,17 +649,32 @@ impl FlowLog {
                 parser.set_obfuscate_cache(self.obfuscate_cache.as_ref().map(|o| o.clone()));
             }
 
-            let ret = parser.parse_payload(
-                {
-                    let pkt_size = flow_config.l7_log_packet_size as usize;
-                    if pkt_size > payload.len() {
-                        payload
-                    } else {
-                        &payload[..pkt_size]
+            let payload = {
+                let pkt_size = flow_config.l7_log_packet_size as usize;
+                // reassembled messages are not truncated
+                if pkt_size > payload.len() || reassembled.is_some() {
+                    payload
+                } else {
+                    &payload[..pkt_size]
+                }
+            };
+            let ret = match parser.datagram_message_len(payload) {
+                Some(_) if packet.lookup_key.proto == IpProtocol::UDP => {
+                    Self::parse_datagram(parser.as_mut(), payload, parse_param)
+                }
+                _ => parser.parse_payload(payload, parse_param),
+            };
+            if parse_param.parse_perf {
+                if let Ok(r) = ret.as_ref() {
+                    self.perf_details
+                        .record(log_parser_config, parser.protocol(), r);
+                    if log_parser_config.l7_concurrency_stats.enabled
+                        && !matches!(r, L7ParseResult::None)
+                    {
+                        self.perf_details.record_concurrency(parse_param);
                     }
-                },
-                parse_param,
-            );
+                }
+            }
 
             let mut cache_proto = |proto: L7ProtocolEnum| match packet.signal_source {
                 SignalSource::EBPF => {
@@ -342,13 +754,21 @@ impl FlowLog {
             }
             param.set_oracle_conf(flow_config.oracle_parse_conf);
 
-            for protocol in checker.possible_protocols(
-                packet.lookup_key.proto.into(),
-                match packet.lookup_key.direction {
-                    PacketDirection::ClientToServer => packet.lookup_key.dst_port,
-                    PacketDirection::ServerToClient => packet.lookup_key.src_port,
-                },
-            ) {
+            let (server_ip, server_port) = match packet.lookup_key.direction {
+                PacketDirection::ClientToServer => {
+                    (packet.lookup_key.dst_ip, packet.lookup_key.dst_port)
+                }
+                PacketDirection::ServerToClient => {
+                    (packet.lookup_key.src_ip, packet.lookup_key.src_port)
+                }
+            };
+            let retries = flow_config.l7_protocol_inference_negative_cache_retries as u8;
+            for protocol in
+                checker.possible_protocols(packet.lookup_key.proto.into(), server_ip, server_port)
+            {
+                if retries > 0 && self.negative_protocol_cache.contains(*protocol, retries) {
+                    continue;
+                }
                 let Some(mut parser) = get_parser(L7ProtocolEnum::L7Protocol(*protocol)) else {
                     continue;
                 };
@@ -359,6 +779,7 @@ impl FlowLog {
                     parser.set_obfuscate_cache(self.obfuscate_cache.as_ref().map(|o| o.clone()));
                 }
                 if parser.check_payload(cut_payload, &param) {
+                    self.negative_protocol_cache.clear();
                     self.l7_protocol_enum = parser.l7_protocol_enum();
 
                     // redis can not determine dirction by RESP protocol when pakcet is from ebpf, special treatment
@@ -378,6 +799,7 @@ impl FlowLog {
                         packet.lookup_key.direction = PacketDirection::ClientToServer;
                     }
                     param.direction = packet.lookup_key.direction;
+                    Self::set_datagram_connection(&parser, packet, &mut param);
 
                     self.l7_protocol_log_parser = Some(Box::new(parser));
                     return self.l7_parse_log(
@@ -388,8 +810,49 @@ impl FlowLog {
                         &param,
                         local_epc,
                         remote_epc,
+                        None,
                     );
                 }
+                if retries > 0 {
+                    self.negative_protocol_cache.miss(*protocol, retries);
+                }
+            }
+
+            // the first observed message may be a response if the agent attaches to established
+            // connections, check responses with the packet source as the server instead of
+            // waiting for a request or misparsing the response as a request
+            for protocol in checker.possible_protocols(
+                packet.lookup_key.proto.into(),
+                packet.lookup_key.src_ip,
+                packet.lookup_key.src_port,
+            ) {
+                let Some(mut parser) = get_parser(L7ProtocolEnum::L7Protocol(*protocol)) else {
+                    continue;
+                };
+                if !parser.check_response(cut_payload, &param) {
+                    continue;
+                }
+                self.stats_counter
+                    .l7_direction_recovered
+                    .fetch_add(1, Ordering::Relaxed);
+                self.negative_protocol_cache.clear();
+                self.l7_protocol_enum = parser.l7_protocol_enum();
+                self.server_port = packet.lookup_key.src_port;
+                packet.lookup_key.direction = PacketDirection::ServerToClient;
+                param.direction = packet.lookup_key.direction;
+                Self::set_datagram_connection(&parser, packet, &mut param);
+
+                self.l7_protocol_log_parser = Some(Box::new(parser));
+                return self.l7_parse_log(
+                    flow_config,
+                    log_parser_config,
+                    packet,
+                    app_table,
+                    &param,
+                    local_epc,
+                    remote_epc,
+                    None,
+                );
             }
 
             self.is_skip = match packet.signal_source {
@@ -409,6 +872,52 @@ impl FlowLog {
         return Err(Error::L7ProtocolUnknown);
     }
 
+    fn tcp_reassemble(&mut self, flow_config: &FlowConfig, packet: &MetaPacket) -> Reassembled {
+        if flow_config.tcp_reassembly_max_message_size == 0
+            || packet.signal_source != SignalSource::Packet
+            || packet.lookup_key.proto != IpProtocol::TCP
+        {
+            return Reassembled::Bypass;
+        }
+        let (Some(payload), ProtocolData::TcpHeader(tcp_data)) =
+            (packet.get_l4_payload(), &packet.protocol_data)
+        else {
+            return Reassembled::Bypass;
+        };
+        let parser = self.l7_protocol_log_parser.as_ref();
+        let direction = packet.lookup_key.direction;
+        let message_len = |payload: &[u8]| parser.and_then(|p| p.message_len(payload, direction));
+        if self.tcp_reassembly.is_none() && message_len(payload).is_none() {
+            return Reassembled::Bypass;
+        }
+        let stats_counter = &self.stats_counter;
+        self.tcp_reassembly
+            .get_or_insert_with(|| Box::new(TcpReassembly::new(stats_counter.clone())))
+            .reassemble(
+                direction,
+                tcp_data.seq,
+                payload,
+                message_len,
+                packet.lookup_key.timestamp.into(),
+                flow_config.tcp_reassembly_max_message_size,
+                flow_config.tcp_reassembly_memory_limit,
+            )
+    }
+
+    // retransmitted bytes are parsed already, skip them to avoid double counting
+    // in perf stats and mismatching sessions
+    // returns the length of leading payload parsed already
+    fn tcp_parsed_len(&mut self, packet: &MetaPacket, is_first_packet_direction: bool) -> u16 {
+        if packet.signal_source != SignalSource::Packet || packet.payload_len == 0 {
+            return 0;
+        }
+        let ProtocolData::TcpHeader(tcp_data) = &packet.protocol_data else {
+            return 0;
+        };
+        self.parsed_segments[!is_first_packet_direction as usize]
+            .check_and_insert(tcp_data.seq, packet.payload_len as u32) as u16
+    }
+
     fn l7_parse(
         &mut self,
         flow_config: &FlowConfig,
@@ -438,6 +947,11 @@ impl FlowLog {
         }
 
         if self.l7_protocol_log_parser.is_some() {
+            let reassembled = match self.tcp_reassemble(flow_config, packet) {
+                Reassembled::Bypass => None,
+                Reassembled::Pending => return Ok(L7ParseResult::None),
+                Reassembled::Complete(buffer) => Some(buffer),
+            };
             let param = &mut ParseParam::new(
                 &*packet,
                 self.perf_cache.clone(),
@@ -457,6 +971,11 @@ impl FlowLog {
             if let Some(vm) = self.wasm_vm.as_ref() {
                 param.set_wasm_vm(vm.clone());
             }
+            Self::set_datagram_connection(
+                self.l7_protocol_log_parser.as_ref().unwrap(),
+                packet,
+                param,
+            );
             return self.l7_parse_log(
                 flow_config,
                 log_parser_config,
@@ -465,6 +984,7 @@ impl FlowLog {
                 param,
                 local_epc,
                 remote_epc,
+                reassembled.as_deref(),
             );
         }
 
@@ -512,6 +1032,7 @@ impl FlowLog {
         if !l4_enabled && !l7_enabled {
             return None;
         }
+        let negative_protocol_cache = NegativeProtocolCache::new(stats_counter.clone());
         let l4 = if l4_enabled {
             match l4_proto {
                 L4Protocol::Tcp => Some(L4FlowPerfTable::Tcp(
@@ -546,6 +1067,10 @@ impl FlowLog {
             l7_protocol_inference_ttl,
             ntp_diff,
             obfuscate_cache,
+            tcp_reassembly: None,
+            parsed_segments: Default::default(),
+            perf_details: L7PerfDetails::default(),
+            negative_protocol_cache,
         })
     }
 
@@ -575,8 +1100,19 @@ impl FlowLog {
         }
 
         if l7_performance_enabled || l7_log_parse_enabled {
+            let parsed_len = self.tcp_parsed_len(packet, is_first_packet_direction);
+            if parsed_len > 0 {
+                self.stats_counter
+                    .l7_tcp_retrans_skip
+                    .fetch_add(1, Ordering::Relaxed);
+                if parsed_len == packet.payload_len {
+                    return Err(Error::RetransPacket);
+                }
+                // only the new tail of the segment is parsed
+                packet.skip_tcp_payload(parsed_len);
+            }
             // 抛出错误由flowMap.FlowPerfCounter处理
-            return self.l7_parse(
+            let result = self.l7_parse(
                 flow_config,
                 log_parser_config,
                 packet,
@@ -587,10 +1123,18 @@ impl FlowLog {
                 remote_epc,
                 checker,
             );
+            if parsed_len > 0 {
+                packet.restore_tcp_payload(parsed_len);
+            }
+            return result;
         }
         Ok(L7ParseResult::None)
     }
 
+    pub fn l7_protocol(&self) -> L7Protocol {
+        self.l7_protocol_enum.get_l7_protocol()
+    }
+
     pub fn parse_l3(&mut self, packet: &mut MetaPacket) -> Result<()> {
         if let Some(l4) = self.l4.as_mut() {
             l4.parse(packet, false)?;
@@ -608,12 +1152,40 @@ impl FlowLog {
         }
     }
 
+    pub fn copy_and_reset_media_perf_data(&mut self, flow: &mut Flow) {
+        if let Some(media) = self
+            .l7_protocol_log_parser
+            .as_mut()
+            .and_then(|l| l.media_stats())
+        {
+            flow.flow_perf_stats
+                .as_mut()
+                .unwrap()
+                .media
+                .sequential_merge(&media);
+        }
+    }
+
+    pub fn flush_l7_logs(&mut self, time: u64) -> L7ParseResult {
+        self.l7_protocol_log_parser
+            .as_mut()
+            .map_or(L7ParseResult::None, |l| l.flush_logs(time))
+    }
+
     pub fn copy_and_reset_l7_perf_data(
         &mut self,
         l7_timeout_count: u32,
     ) -> (L7PerfStats, L7Protocol) {
+        let l7_protocol = self.l7_protocol_enum.get_l7_protocol();
+        // requests of dns without response are counted as client side timeout
+        let dns_timeout = if l7_protocol == L7Protocol::DNS {
+            l7_timeout_count
+        } else {
+            0
+        };
         let default_l7_perf = L7PerfStats {
             err_timeout: l7_timeout_count,
+            dns_timeout,
             ..Default::default()
         };
 
@@ -623,10 +1195,212 @@ impl FlowLog {
             .map_or(default_l7_perf.clone(), |l| {
                 l.perf_stats().map_or(default_l7_perf, |mut p| {
                     p.err_timeout = l7_timeout_count;
+                    p.dns_timeout = dns_timeout;
                     p
                 })
             });
+        let details = mem::take(&mut self.perf_details);
+        let l7_perf = L7PerfStats {
+            rrt_histogram: details.rrt_histogram,
+            rrt_sketch: details.rrt_sketch,
+            response_codes: details.response_codes,
+            apdex: details.apdex,
+            req_size: details.req_size,
+            resp_size: details.resp_size,
+            concurrency: details.concurrency,
+            ..l7_perf
+        };
+
+        (l7_perf, l7_protocol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        common::l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        flow_generator::protocol_logs::DnsInfo,
+    };
+
+    #[test]
+    fn test_protocol_hints() {
+        let bitmap = L7ProtocolBitmap::from(&vec![
+            "HTTP".to_owned(),
+            "Redis".to_owned(),
+            "Kafka".to_owned(),
+        ]);
+        let mut ports = HashMap::new();
+        ports.insert(
+            L7Protocol::Kafka,
+            parse_u16_range_list_to_bitmap("9092", false).unwrap(),
+        );
+        let hint = |cidr: &str, ports: &str, protocol: &str, exclusive| L7ProtocolHint {
+            cidr: cidr.to_owned(),
+            ports: ports.to_owned(),
+            protocol: protocol.to_owned(),
+            exclusive,
+        };
+        let checker = L7ProtocolChecker::new(
+            &bitmap,
+            &ports,
+            &[
+                hint("10.0.0.0/8", "9093", "Kafka", true),
+                hint("", "6380", "Redis", false),
+                hint("invalid", "80", "HTTP", false),
+                hint("", "80", "MySQL", true),
+            ],
+        );
+        let protocols = |ip: &str, port| {
+            checker
+                .possible_protocols(L4Protocol::Tcp, ip.parse().unwrap(), port)
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(protocols("10.1.1.1", 9093), vec![L7Protocol::Kafka]);
+        let other = protocols("192.168.1.1", 9093);
+        assert!(!other.contains(&L7Protocol::Kafka));
+        assert!(other.contains(&L7Protocol::Http1));
+
+        let hinted = protocols("192.168.1.1", 6380);
+        assert_eq!(hinted[0], L7Protocol::Redis);
+        assert_eq!(
+            hinted.iter().filter(|p| **p == L7Protocol::Redis).count(),
+            1
+        );
+        assert!(hinted.contains(&L7Protocol::Http1));
+
+        // hint of disabled protocol is ignored
+        assert!(protocols("192.168.1.1", 80).contains(&L7Protocol::Http1));
+    }
+
+    #[test]
+    fn test_negative_protocol_cache() {
+        let counter = Arc::new(FlowMapCounter::default());
+        let mut cache = NegativeProtocolCache::new(counter.clone());
+        for _ in 0..2 {
+            assert!(!cache.contains(L7Protocol::Kafka, 2));
+            cache.miss(L7Protocol::Kafka, 2);
+        }
+        cache.miss(L7Protocol::MySQL, 2);
+        assert!(cache.contains(L7Protocol::Kafka, 2));
+        assert!(!cache.contains(L7Protocol::MySQL, 2));
+        cache.miss(L7Protocol::Kafka, 2);
+        assert_eq!(
+            counter
+                .l7_inference_negative_cache_size
+                .load(Ordering::Relaxed),
+            1
+        );
+        assert_eq!(
+            counter
+                .l7_inference_negative_cache_hit
+                .load(Ordering::Relaxed),
+            1
+        );
+
+        cache.miss(L7Protocol::MySQL, 2);
+        drop(cache);
+        assert_eq!(
+            counter
+                .l7_inference_negative_cache_size
+                .load(Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[test]
+    fn test_parsed_segments() {
+        let mut segments = ParsedSegments::default();
+        assert_eq!(segments.check_and_insert(1000, 100), 0);
+        assert_eq!(segments.check_and_insert(1100, 100), 0);
+        // exact retransmission
+        assert_eq!(segments.check_and_insert(1000, 100), 100);
+        // out of order segments carry new data
+        assert_eq!(segments.check_and_insert(1300, 100), 0);
+        assert_eq!(segments.check_and_insert(1200, 100), 0);
+        // segments inside parsed data are retransmissions whatever their length
+        assert_eq!(segments.check_and_insert(1100, 200), 200);
+        assert_eq!(segments.check_and_insert(1150, 100), 100);
+        assert_eq!(segments.check_and_insert(1200, 100), 100);
+        // only the new tail of partially overlapping segments is parsed
+        assert_eq!(segments.check_and_insert(1350, 100), 50);
+        assert_eq!(segments.check_and_insert(1000, 450), 450);
+        // new data before parsed data is parsed
+        assert_eq!(segments.check_and_insert(900, 200), 0);
+        assert_eq!(segments.check_and_insert(900, 550), 550);
+        // wrapping sequence numbers
+        assert_eq!(segments.check_and_insert(u32::MAX - 50, 100), 0);
+        assert_eq!(segments.check_and_insert(u32::MAX - 50, 100), 100);
+        assert_eq!(segments.check_and_insert(u32::MAX - 20, 100), 70);
+        assert_eq!(segments.check_and_insert(10, 50), 50);
+
+        // the oldest ranges are forgotten
+        for i in 0..PARSED_SEGMENTS_LEN as u32 {
+            assert_eq!(segments.check_and_insert(2000 + i * 200, 100), 0);
+        }
+        assert_eq!(segments.check_and_insert(1000, 100), 0);
+    }
+
+    // datagrams of messages with length and id
+    struct CoalescedLog;
+
+    impl L7ProtocolParserInterface for CoalescedLog {
+        fn check_payload(&mut self, _: &[u8], _: &ParseParam) -> bool {
+            true
+        }
+
+        fn parse_payload(&mut self, payload: &[u8], _: &ParseParam) -> Result<L7ParseResult> {
+            match payload.get(1) {
+                Some(id) if *id > 0 => {
+                    Ok(L7ParseResult::Single(L7ProtocolInfo::DnsInfo(DnsInfo {
+                        trans_id: *id as u16,
+                        ..Default::default()
+                    })))
+                }
+                _ => Err(Error::L7ProtocolUnknown),
+            }
+        }
+
+        fn protocol(&self) -> L7Protocol {
+            L7Protocol::Unknown
+        }
+
+        fn perf_stats(&mut self) -> Option<L7PerfStats> {
+            None
+        }
+
+        fn datagram_message_len(&self, payload: &[u8]) -> Option<usize> {
+            payload.first().map(|len| *len as usize)
+        }
+    }
+
+    #[test]
+    fn test_parse_datagram() {
+        let packet = MetaPacket::default();
+        let param = ParseParam::new(
+            &packet,
+            Rc::new(RefCell::new(L7PerfCache::new(8))),
+            false,
+            false,
+        );
+        let mut parser = CoalescedLog;
+        let session_ids = |payload: &[u8], parser: &mut CoalescedLog| match FlowLog::parse_datagram(
+            parser, payload, &param,
+        ) {
+            Ok(L7ParseResult::Single(info)) => vec![info.session_id().unwrap()],
+            Ok(L7ParseResult::Multi(infos)) => {
+                infos.iter().map(|i| i.session_id().unwrap()).collect()
+            }
+            _ => vec![],
+        };
 
-        (l7_perf, self.l7_protocol_enum.get_l7_protocol())
+        assert_eq!(session_ids(&[2, 1, 3, 2, 0], &mut parser), vec![1, 2]);
+        // invalid messages are skipped, the last one is truncated
+        assert_eq!(session_ids(&[2, 0, 2, 3, 4, 4], &mut parser), vec![3, 4]);
+        assert_eq!(session_ids(&[0, 5], &mut parser), vec![5]);
+        assert!(FlowLog::parse_datagram(&mut parser, &[2, 0], &param).is_err());
     }
 }
@@ -89,6 +89,7 @@ pub(crate) struct SessionPeer {
 
     seq_threshold: u32, // fast syn_retrans check
     seq: u32,
+    ack: u32,
     payload_len: u32,
     win_size: u16,
     win_scale: u8,
@@ -99,6 +100,7 @@ pub(crate) struct SessionPeer {
     srt_calculable: bool,
     rtt_calculable: bool,
     art_calculable: bool,
+    ack_rtt_calculable: bool, // the last packet carries new data, its ACK can be used to sample rtt
 
     rtt_full_precondition: bool, // rtt计算前置条件，SYN包在SYN/ACK包之前到达
     rtt_full_calculable: bool,   // rtt计算触发标志，完成计算后需reset
@@ -350,12 +352,15 @@ impl SessionPeer {
         } else {
             unreachable!();
         };
+        // retransmitted segments are excluded from rtt sampling
+        self.ack_rtt_calculable = p.has_valid_payload() && self.is_next_packet(p);
         self.timestamp = p.lookup_key.timestamp.into();
         self.payload_len = p.payload_len as u32;
         if tcp_data.flags.contains(TcpFlags::SYN) {
             self.payload_len = 1;
         }
         self.seq = tcp_data.seq;
+        self.ack = tcp_data.ack;
         self.win_size = tcp_data.win_size;
         // winScale不能在这里更新p.winScale = tcpHeader.WinScale
     }
@@ -397,6 +402,8 @@ impl TimeStats {
 pub(crate) struct PerfData {
     rtt_0: TimeStats, // The time difference between the first SYN and the last SYN_ACK
     rtt_1: TimeStats, // The time difference between the first SYN_ACK and the last ACK
+    ack_rtt_0: TimeStats, // The time difference between the data from server and the ACK of client
+    ack_rtt_1: TimeStats, // The time difference between the data from client and the ACK of server
     art_0: TimeStats,
     art_1: TimeStats,
     srt_0: TimeStats,
@@ -420,6 +427,9 @@ pub(crate) struct PerfData {
     zero_win_count_0: u32,
     zero_win_count_1: u32,
 
+    dup_ack_count_0: u32,
+    dup_ack_count_1: u32,
+
     // SYN SYN_ACK count
     syn: u32,
     synack: u32,
@@ -469,6 +479,15 @@ impl PerfData {
         self.updated = true;
     }
 
+    fn calc_ack_rtt(&mut self, d: Timestamp, fpd: bool) {
+        if fpd {
+            self.ack_rtt_0.update(d);
+        } else {
+            self.ack_rtt_1.update(d);
+        }
+        self.updated = true;
+    }
+
     fn calc_retrans_syn(&mut self, fpd: bool) {
         if fpd {
             self.retrans_syn_0 += 1;
@@ -498,6 +517,15 @@ impl PerfData {
         self.updated = true;
     }
 
+    fn calc_dup_ack(&mut self, fpd: bool) {
+        if fpd {
+            self.dup_ack_count_0 += 1;
+        } else {
+            self.dup_ack_count_1 += 1;
+        }
+        self.updated = true;
+    }
+
     fn calc_psh_urg(&mut self, fpd: bool) {
         if fpd {
             self.psh_urg_count_0 += 1;
@@ -544,6 +572,8 @@ impl PerfData {
         stats.total_retrans_count = self.retrans_sum;
         stats.counts_peers[0].zero_win_count = self.zero_win_count_0;
         stats.counts_peers[1].zero_win_count = self.zero_win_count_1;
+        stats.counts_peers[0].dup_ack_count = self.dup_ack_count_0;
+        stats.counts_peers[1].dup_ack_count = self.dup_ack_count_1;
 
         stats.syn_count = self.syn;
         stats.synack_count = self.synack;
@@ -594,6 +624,22 @@ impl PerfData {
             stats.cit_sum = self.cit.sum.as_micros() as u32;
             stats.cit_count = self.cit.count;
         }
+
+        let (ack_rtt_client, ack_rtt_server) = if !flow_reversed {
+            (&self.ack_rtt_0, &self.ack_rtt_1)
+        } else {
+            (&self.ack_rtt_1, &self.ack_rtt_0)
+        };
+        if ack_rtt_client.updated {
+            stats.ack_rtt_client_max = ack_rtt_client.max.as_micros() as u32;
+            stats.ack_rtt_client_sum = ack_rtt_client.sum.as_micros() as u32;
+            stats.ack_rtt_client_count = ack_rtt_client.count;
+        }
+        if ack_rtt_server.updated {
+            stats.ack_rtt_server_max = ack_rtt_server.max.as_micros() as u32;
+            stats.ack_rtt_server_sum = ack_rtt_server.sum.as_micros() as u32;
+            stats.ack_rtt_server_count = ack_rtt_server.count;
+        }
     }
 }
 
@@ -693,6 +739,57 @@ impl TcpPerf {
         }
     }
 
+    // A duplicate ACK is a pure ACK repeating the ack number and window of the last packet in
+    // the same direction, while the opposite direction has sent data beyond the ack number
+    fn is_dup_ack_packet(&self, p: &MetaPacket, fpd: bool) -> bool {
+        let tcp_data = if let ProtocolData::TcpHeader(tcp_data) = &p.protocol_data {
+            tcp_data
+        } else {
+            unreachable!();
+        };
+        let (same_dir, oppo_dir) = if fpd {
+            (&self.ctrl_info.0, &self.ctrl_info.1)
+        } else {
+            (&self.ctrl_info.1, &self.ctrl_info.0)
+        };
+        p.is_ack()
+            && !same_dir.timestamp.is_zero()
+            && !oppo_dir.timestamp.is_zero()
+            && tcp_data.win_size != 0
+            && tcp_data.ack == same_dir.ack
+            && tcp_data.win_size == same_dir.win_size
+            && tcp_data.ack != oppo_dir.seq.wrapping_add(oppo_dir.payload_len)
+    }
+
+    // Unlike the handshake rtt, the ack rtt is sampled during the whole flow: the time between
+    // a segment carrying new data and the ACK acknowledging it in the opposite direction
+    fn calc_ack_rtt(&mut self, p: &MetaPacket, fpd: bool) {
+        let oppo_dir = if fpd {
+            &mut self.ctrl_info.1
+        } else {
+            &mut self.ctrl_info.0
+        };
+        if !oppo_dir.ack_rtt_calculable {
+            return;
+        }
+        let tcp_data = if let ProtocolData::TcpHeader(tcp_data) = &p.protocol_data {
+            tcp_data
+        } else {
+            unreachable!();
+        };
+        if !tcp_data.flags.contains(TcpFlags::ACK) || !oppo_dir.is_reply_packet(p) {
+            return;
+        }
+        oppo_dir.ack_rtt_calculable = false;
+        let rtt = adjust_rtt(
+            (p.lookup_key.timestamp - oppo_dir.timestamp).into(),
+            RTT_MAX,
+        );
+        if !rtt.is_zero() {
+            self.perf_data.calc_ack_rtt(rtt, fpd);
+        }
+    }
+
     fn is_interested_tcp_flags(flags: TcpFlags) -> bool {
         if flags.contains(TcpFlags::SYN) {
             if flags.intersects(TcpFlags::FIN | TcpFlags::RST) {
@@ -1010,6 +1107,10 @@ impl L4FlowPerf for TcpPerf {
             return Err(Error::InvalidPacketTimestamp);
         }
 
+        if self.is_dup_ack_packet(p, fpd) {
+            self.perf_data.calc_dup_ack(fpd);
+        }
+        self.calc_ack_rtt(p, fpd);
         let is_retrans = self.calculate(p, fpd);
         if fpd {
             self.ctrl_info.0.update_data(p);
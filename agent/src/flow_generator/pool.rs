@@ -14,6 +14,19 @@
  * limitations under the License.
  */
 
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    mem,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use public::counter::{Counter, CounterType, CounterValue, OwnedCountable};
+
 use super::{flow_node::FlowNode, perf::tcp::TcpPerf};
 
 pub trait Recyclable {
@@ -58,3 +71,321 @@ impl<T: Recyclable> MemoryPool<T> {
         self.objs.push(obj);
     }
 }
+
+// Strings of l7 logs (url, topic, sql, ...) are allocated by parsers in flow map threads
+// and freed after encoded in sender threads. Recycled strings are collected in thread
+// local batches, full batches are exchanged between threads through a shared stack so
+// that the lock is taken once per STRING_BATCH_SIZE strings.
+// Buffers are kept in buckets by capacity, bucket i holds buffers with capacity in
+// [STRING_BUCKETS[i], STRING_BUCKETS[i + 1]), and a string is copied into a buffer from
+// the smallest bucket fitting it, so that short strings do not hold large buffers.
+// Capacity of buffers in the shared stacks is limited by bytes, and batches not taken
+// by any thread during STRING_POOL_IDLE_TIMEOUT are freed.
+const STRING_BUCKETS: [usize; 4] = [64, 256, 1024, 4096];
+const STRING_BATCH_SIZE: usize = 64;
+// larger buffers are freed instead of being kept in the pool
+const MAX_STRING_CAPACITY: usize = 16384;
+const DEFAULT_STRING_POOL_MAX_BYTES: usize = 8 << 20;
+const STRING_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct SharedBucket {
+    batches: Vec<Vec<String>>,
+    // the least number of batches in the stack since last shrink, these batches are idle
+    low_water: usize,
+}
+
+struct SharedStrings {
+    buckets: [SharedBucket; STRING_BUCKETS.len()],
+    last_shrink: Duration,
+}
+
+static STRING_BATCHES: Mutex<SharedStrings> = Mutex::new(SharedStrings {
+    buckets: [
+        SharedBucket::new(),
+        SharedBucket::new(),
+        SharedBucket::new(),
+        SharedBucket::new(),
+    ],
+    last_shrink: Duration::ZERO,
+});
+static STRING_POOL_MAX_BYTES: AtomicUsize = AtomicUsize::new(DEFAULT_STRING_POOL_MAX_BYTES);
+static STRING_POOL_COUNTER: StringPoolCounter = StringPoolCounter {
+    hit: AtomicU64::new(0),
+    miss: AtomicU64::new(0),
+    dropped: AtomicU64::new(0),
+    released: AtomicU64::new(0),
+    bytes: AtomicUsize::new(0),
+};
+
+thread_local! {
+    static LOCAL_STRINGS: RefCell<LocalStrings> = RefCell::new(LocalStrings::default());
+}
+
+#[derive(Debug)]
+pub struct StringPoolCounter {
+    pub hit: AtomicU64,
+    pub miss: AtomicU64,
+    pub dropped: AtomicU64,
+    // buffers freed after idle
+    pub released: AtomicU64,
+    // capacity of buffers in the shared stacks
+    pub bytes: AtomicUsize,
+}
+
+pub fn string_pool_counter() -> &'static StringPoolCounter {
+    &STRING_POOL_COUNTER
+}
+
+pub struct StringPoolCountable;
+
+impl OwnedCountable for StringPoolCountable {
+    fn get_counters(&self) -> Vec<Counter> {
+        let c = &STRING_POOL_COUNTER;
+        vec![
+            (
+                "hit",
+                CounterType::Counted,
+                CounterValue::Unsigned(c.hit.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "miss",
+                CounterType::Counted,
+                CounterValue::Unsigned(c.miss.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(c.dropped.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "released",
+                CounterType::Counted,
+                CounterValue::Unsigned(c.released.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "bytes",
+                CounterType::Gauged,
+                CounterValue::Unsigned(c.bytes.load(Ordering::Relaxed) as u64),
+            ),
+        ]
+    }
+
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+// Limits the capacity of buffers kept in the shared stacks, usually derived from max_memory
+pub fn set_string_pool_limit(bytes: usize) {
+    STRING_POOL_MAX_BYTES.store(bytes, Ordering::Relaxed);
+}
+
+// Frees the batches not taken by any thread since last shrink, it is called periodically
+// by flow map threads and takes effect once in STRING_POOL_IDLE_TIMEOUT
+pub fn shrink_string_pool(now: Duration) {
+    let mut shared = STRING_BATCHES.lock().unwrap();
+    if now < shared.last_shrink + STRING_POOL_IDLE_TIMEOUT {
+        return;
+    }
+    shared.last_shrink = now;
+    for bucket in shared.buckets.iter_mut() {
+        // the oldest batches are at the bottom of the stack
+        for batch in bucket.batches.drain(..bucket.low_water) {
+            STRING_POOL_COUNTER
+                .bytes
+                .fetch_sub(batch_bytes(&batch), Ordering::Relaxed);
+            STRING_POOL_COUNTER
+                .released
+                .fetch_add(batch.len() as u64, Ordering::Relaxed);
+        }
+        bucket.low_water = bucket.batches.len();
+    }
+}
+
+fn batch_bytes(batch: &[String]) -> usize {
+    batch.iter().map(|s| s.capacity()).sum()
+}
+
+impl SharedBucket {
+    const fn new() -> Self {
+        Self {
+            batches: Vec::new(),
+            low_water: 0,
+        }
+    }
+
+    fn pop(&mut self) -> Option<Vec<String>> {
+        let batch = self.batches.pop()?;
+        self.low_water = self.low_water.min(self.batches.len());
+        STRING_POOL_COUNTER
+            .bytes
+            .fetch_sub(batch_bytes(&batch), Ordering::Relaxed);
+        Some(batch)
+    }
+
+    fn push(&mut self, batch: Vec<String>) {
+        let bytes = batch_bytes(&batch);
+        if STRING_POOL_COUNTER.bytes.load(Ordering::Relaxed) + bytes
+            > STRING_POOL_MAX_BYTES.load(Ordering::Relaxed)
+        {
+            STRING_POOL_COUNTER
+                .dropped
+                .fetch_add(batch.len() as u64, Ordering::Relaxed);
+            return;
+        }
+        STRING_POOL_COUNTER
+            .bytes
+            .fetch_add(bytes, Ordering::Relaxed);
+        self.batches.push(batch);
+    }
+}
+
+#[derive(Default)]
+struct LocalBucket {
+    free: Vec<String>,
+    recycled: Vec<String>,
+}
+
+#[derive(Default)]
+struct LocalStrings {
+    buckets: [LocalBucket; STRING_BUCKETS.len()],
+}
+
+impl LocalStrings {
+    // the smallest bucket with buffers large enough for len
+    fn bucket_to_get(len: usize) -> Option<usize> {
+        STRING_BUCKETS.iter().position(|size| *size >= len)
+    }
+
+    fn bucket_to_put(capacity: usize) -> Option<usize> {
+        if capacity > MAX_STRING_CAPACITY {
+            return None;
+        }
+        STRING_BUCKETS.iter().rposition(|size| *size <= capacity)
+    }
+
+    fn get(&mut self, len: usize) -> Option<String> {
+        let index = Self::bucket_to_get(len)?;
+        let bucket = &mut self.buckets[index];
+        if bucket.free.is_empty() {
+            if !bucket.recycled.is_empty() {
+                mem::swap(&mut bucket.free, &mut bucket.recycled);
+            } else if let Some(batch) = STRING_BATCHES.lock().unwrap().buckets[index].pop() {
+                bucket.free = batch;
+            }
+        }
+        bucket.free.pop()
+    }
+
+    fn put(&mut self, s: String) {
+        let Some(index) = Self::bucket_to_put(s.capacity()) else {
+            return;
+        };
+        let bucket = &mut self.buckets[index];
+        bucket.recycled.push(s);
+        if bucket.recycled.len() < STRING_BATCH_SIZE {
+            return;
+        }
+        let batch = mem::replace(&mut bucket.recycled, Vec::with_capacity(STRING_BATCH_SIZE));
+        STRING_BATCHES.lock().unwrap().buckets[index].push(batch);
+    }
+}
+
+// Returns a copy of s in a recycled buffer if there is any
+pub fn pooled_string(s: &str) -> String {
+    if s.is_empty() {
+        return String::new();
+    }
+    // the thread local is not accessible when the thread is exiting
+    match LOCAL_STRINGS
+        .try_with(|l| l.borrow_mut().get(s.len()))
+        .ok()
+        .flatten()
+    {
+        Some(mut buf) => {
+            STRING_POOL_COUNTER.hit.fetch_add(1, Ordering::Relaxed);
+            buf.clear();
+            buf.push_str(s);
+            buf
+        }
+        None => {
+            STRING_POOL_COUNTER.miss.fetch_add(1, Ordering::Relaxed);
+            s.to_owned()
+        }
+    }
+}
+
+// Same as String::from_utf8_lossy(bytes).into_owned() with a recycled buffer
+pub fn pooled_string_lossy(bytes: &[u8]) -> String {
+    match String::from_utf8_lossy(bytes) {
+        Cow::Borrowed(s) => pooled_string(s),
+        Cow::Owned(s) => s,
+    }
+}
+
+pub fn recycle_string(s: String) {
+    if s.capacity() < STRING_BUCKETS[0] || s.capacity() > MAX_STRING_CAPACITY {
+        return;
+    }
+    let _ = LOCAL_STRINGS.try_with(|l| l.borrow_mut().put(s));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_pool() {
+        let mut s = pooled_string("SELECT * FROM users");
+        s.reserve(STRING_BUCKETS[0]);
+        let ptr = s.as_ptr();
+        recycle_string(s);
+        let s = pooled_string("topic");
+        assert_eq!(s, "topic");
+        assert_eq!(s.as_ptr(), ptr);
+
+        // short strings do not take large buffers
+        let large = String::with_capacity(STRING_BUCKETS[2]);
+        let ptr = large.as_ptr();
+        recycle_string(large);
+        assert_ne!(pooled_string("topic").as_ptr(), ptr);
+        let s = pooled_string(&"a".repeat(STRING_BUCKETS[1] + 1));
+        assert_eq!(s.as_ptr(), ptr);
+        // buffers too small to be pooled are freed
+        recycle_string(String::from("topic"));
+        assert_eq!(LocalStrings::bucket_to_put(5), None);
+        assert_eq!(LocalStrings::bucket_to_get(STRING_BUCKETS[3] + 1), None);
+
+        assert_eq!(pooled_string_lossy(b"/api/\xff"), "/api/\u{fffd}");
+        assert_eq!(pooled_string("").capacity(), 0);
+    }
+
+    #[test]
+    fn shared_bucket() {
+        let batch = || -> Vec<String> {
+            (0..STRING_BATCH_SIZE)
+                .map(|_| String::with_capacity(STRING_BUCKETS[3]))
+                .collect()
+        };
+        let batch_bytes = STRING_BUCKETS[3] * STRING_BATCH_SIZE;
+        let mut bucket = SharedBucket::new();
+        bucket.push(batch());
+        bucket.push(batch());
+        bucket.low_water = bucket.batches.len();
+        assert!(bucket.pop().is_some());
+        assert_eq!(bucket.low_water, 1);
+
+        // limited by bytes
+        let dropped = STRING_POOL_COUNTER.dropped.load(Ordering::Relaxed);
+        set_string_pool_limit(STRING_POOL_COUNTER.bytes.load(Ordering::Relaxed) + batch_bytes);
+        bucket.push(batch());
+        bucket.push(batch());
+        assert_eq!(bucket.batches.len(), 2);
+        assert!(
+            STRING_POOL_COUNTER.dropped.load(Ordering::Relaxed)
+                >= dropped + STRING_BATCH_SIZE as u64
+        );
+        while bucket.pop().is_some() {}
+        set_string_pool_limit(DEFAULT_STRING_POOL_MAX_BYTES);
+    }
+}
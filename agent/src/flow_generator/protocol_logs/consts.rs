@@ -165,16 +165,35 @@ pub const PORT: u16 = 53;
 
 pub const DNS_TCP_PAYLOAD_OFFSET: usize = 2;
 
+// DNS over TLS, reference: https://www.rfc-editor.org/rfc/rfc7858
+pub const DNS_OVER_TLS_PORT: u16 = 853;
+pub const DNS_OVER_TLS_ALPN: &str = "dot";
+// values of the `encrypted_dns` attribute
+pub const ENCRYPTED_DNS_DOT: &str = "DoT";
+pub const ENCRYPTED_DNS_DOH: &str = "DoH";
+
+// multicast dns, reference: https://www.rfc-editor.org/rfc/rfc6762
+// and https://www.rfc-editor.org/rfc/rfc4795
+pub const MDNS_PORT: u16 = 5353;
+pub const LLMNR_PORT: u16 = 5355;
+// values of the `multicast_dns` attribute
+pub const MULTICAST_DNS_MDNS: &str = "mDNS";
+pub const MULTICAST_DNS_LLMNR: &str = "LLMNR";
+
 pub const DNS_HEADER_SIZE: usize = 12;
 pub const DNS_HEADER_FLAGS_OFFSET: usize = 2;
 pub const DNS_HEADER_QR_MASK: u8 = 0x80;
+pub const DNS_HEADER_AA_MASK: u8 = 0x04;
+pub const DNS_HEADER_TC_MASK: u8 = 0x02;
 pub const DNS_HEADER_RESPCODE_MASK: u8 = 0x0f;
 pub const DNS_OPCODE_REQUEST: u8 = 0x00;
 pub const DNS_OPCODE_RESPONSE: u8 = 0x80;
 
 pub const DNS_RESPCODE_SUCCESS: u8 = 0x00;
 pub const DNS_RESPCODE_FORMAT: u8 = 0x01;
+pub const DNS_RESPCODE_SERVFAIL: u8 = 0x02;
 pub const DNS_RESPCODE_NXDOMAIN: u8 = 0x03;
+pub const DNS_RESPCODE_REFUSED: u8 = 0x05;
 
 // Linux和Windows环境默认DNS超时时间均为10s，Linux最大可设置为30s*5=150s
 // https://man7.org/linux/man-pages/man5/resolv.conf.5.html
@@ -195,6 +214,7 @@ pub const DNS_HEADER_NSCOUNT_OFFSET: usize = 8;
 pub const QUESTION_CLASS_OFFSET: usize = 2;
 pub const QUESTION_CLASS_TYPE_SIZE: usize = 4;
 pub const RR_CLASS_OFFSET: usize = 2;
+pub const RR_TTL_OFFSET: usize = 4;
 pub const RR_DATALENGTH_OFFSET: usize = 8;
 pub const RR_RDATA_OFFSET: usize = 10;
 pub const DNS_TYPE_A: u16 = 1;
@@ -203,8 +223,12 @@ pub const DNS_TYPE_CNAME: u16 = 5;
 pub const DNS_TYPE_SOA: u16 = 6;
 pub const DNS_TYPE_WKS: u16 = 11;
 pub const DNS_TYPE_PTR: u16 = 12;
+pub const DNS_TYPE_MX: u16 = 15;
+pub const DNS_TYPE_TXT: u16 = 16;
 pub const DNS_TYPE_AAAA: u16 = 28;
+pub const DNS_TYPE_SRV: u16 = 33;
 pub const DNS_TYPE_DNAME: u16 = 39;
+pub const DNS_TYPE_HTTPS: u16 = 65;
 pub const DNS_TYPE_WKS_LENGTH: usize = 5;
 pub const DNS_TYPE_PTR_LENGTH: usize = 2;
 pub const DOMAIN_NAME_SPLIT: char = ';';
@@ -0,0 +1,382 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::{hash_map::DefaultHasher, hash_map::Entry, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use super::{parser::MetaAppProto, value_is_default, AppProtoLogsBaseInfo, LogMessageType};
+use crate::common::{
+    flow::{L7Protocol, SignalSource},
+    l7_protocol_info::L7ProtocolInfoInterface,
+};
+
+// logs are sharded by connection and session, so that dispatchers do not contend on one lock
+const SHARDS: usize = 16;
+// logs waiting for their counterparts are sent directly beyond this limit
+const MAX_PENDING_LOGS: usize = 1 << 16;
+const MAX_SHARD_PENDING_LOGS: usize = MAX_PENDING_LOGS / SHARDS;
+// packet l7 perf stats of connections with deduplicated logs are dropped within this period
+const COVERED_TIMEOUT: Duration = Duration::from_secs(120);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+
+pub enum DedupResult {
+    // held until the counterpart from the other signal source arrives or the window expires
+    Held,
+    Send(Box<MetaAppProto>),
+    // merged with the counterpart from the other signal source
+    Merged(Box<MetaAppProto>),
+}
+
+// grpc is parsed as http2 first and may be changed during parsing
+fn normalize_protocol(protocol: L7Protocol) -> L7Protocol {
+    if protocol == L7Protocol::Grpc {
+        L7Protocol::Http2
+    } else {
+        protocol
+    }
+}
+
+// key of the connection regardless of direction
+pub fn connection_key(
+    ip_0: IpAddr,
+    port_0: u16,
+    ip_1: IpAddr,
+    port_1: u16,
+    protocol: L7Protocol,
+) -> u64 {
+    let (a, b) = if (ip_0, port_0) <= (ip_1, port_1) {
+        ((ip_0, port_0), (ip_1, port_1))
+    } else {
+        ((ip_1, port_1), (ip_0, port_0))
+    };
+    let mut hasher = DefaultHasher::new();
+    a.hash(&mut hasher);
+    b.hash(&mut hasher);
+    normalize_protocol(protocol).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn log_connection_key(item: &MetaAppProto) -> u64 {
+    let b = &item.base_info;
+    connection_key(b.ip_src, b.port_src, b.ip_dst, b.port_dst, b.head.proto)
+}
+
+// sessions with both request and response are preferred, then ebpf logs which carry
+// process and syscall info and are not cut by packet boundaries
+fn richness(item: &MetaAppProto) -> (bool, bool) {
+    (
+        item.base_info.head.msg_type == LogMessageType::Session,
+        item.base_info.signal_source == SignalSource::EBPF,
+    )
+}
+
+macro_rules! fill_if_default {
+    ($kept:expr, $other:expr, $($field:ident),+) => {
+        $(
+            if value_is_default(&$kept.$field) {
+                $kept.$field = $other.$field.clone();
+            }
+        )+
+    };
+}
+
+fn fill_missing(kept: &mut AppProtoLogsBaseInfo, other: &AppProtoLogsBaseInfo) {
+    fill_if_default!(
+        kept,
+        other,
+        req_tcp_seq,
+        resp_tcp_seq,
+        gpid_0,
+        gpid_1,
+        process_id_0,
+        process_id_1,
+        process_kname_0,
+        process_kname_1,
+        syscall_trace_id_request,
+        syscall_trace_id_response,
+        syscall_trace_id_thread_0,
+        syscall_trace_id_thread_1,
+        pod_id_0,
+        pod_id_1
+    );
+}
+
+fn merge(a: Box<MetaAppProto>, b: Box<MetaAppProto>) -> Box<MetaAppProto> {
+    let (mut kept, other) = if richness(&b) > richness(&a) {
+        (b, a)
+    } else {
+        (a, b)
+    };
+    fill_missing(&mut kept.base_info, &other.base_info);
+    kept
+}
+
+struct Pending {
+    item: Box<MetaAppProto>,
+    deadline: Duration,
+}
+
+#[derive(Default)]
+struct Shard {
+    // connection and session id -> logs
+    pending: HashMap<u64, Vec<Pending>>,
+    pending_count: usize,
+    // (deadline, key) in arrival order
+    deadlines: VecDeque<(Duration, u64)>,
+    // connection key -> expiry time
+    covered: HashMap<u64, Duration>,
+    last_cleanup: Duration,
+}
+
+// Deduplicates l7 logs of the same request captured by both cbpf and ebpf,
+// shared by session aggregators of all dispatchers and l7 quadruple generators
+#[derive(Default)]
+pub struct L7LogDeduplicator {
+    // pending logs are in the shard of their key, and covered connections in the shard
+    // of the connection key
+    shards: [Mutex<Shard>; SHARDS],
+    // the number of covered connections in all shards
+    covered_count: AtomicUsize,
+}
+
+impl L7LogDeduplicator {
+    fn shard(&self, key: u64) -> &Mutex<Shard> {
+        &self.shards[key as usize % SHARDS]
+    }
+
+    fn cover(&self, conn_key: u64, now: Duration) {
+        let mut shard = self.shard(conn_key).lock().unwrap();
+        if shard
+            .covered
+            .insert(conn_key, now + COVERED_TIMEOUT)
+            .is_none()
+        {
+            self.covered_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn submit(&self, item: Box<MetaAppProto>, window: Duration, now: Duration) -> DedupResult {
+        let source = item.base_info.signal_source;
+        if source != SignalSource::Packet && source != SignalSource::EBPF {
+            return DedupResult::Send(item);
+        }
+        let conn_key = log_connection_key(&item);
+        let mut hasher = DefaultHasher::new();
+        conn_key.hash(&mut hasher);
+        item.l7_info.session_id().hash(&mut hasher);
+        let key = hasher.finish();
+
+        let mut guard = self.shard(key).lock().unwrap();
+        let inner = &mut *guard;
+        if let Entry::Occupied(mut e) = inner.pending.entry(key) {
+            let start_time = item.base_info.start_time.as_nanos();
+            let window = window.as_nanos() as u64;
+            let found = e
+                .get()
+                .iter()
+                .enumerate()
+                .filter_map(|(i, p)| {
+                    if p.item.base_info.signal_source == source {
+                        return None;
+                    }
+                    let diff = p.item.base_info.start_time.as_nanos().abs_diff(start_time);
+                    (diff <= window).then_some((i, diff))
+                })
+                .min_by_key(|(_, diff)| *diff);
+            if let Some((i, _)) = found {
+                let p = e.get_mut().swap_remove(i);
+                if e.get().is_empty() {
+                    e.remove();
+                }
+                inner.pending_count -= 1;
+                drop(guard);
+                self.cover(conn_key, now);
+                return DedupResult::Merged(merge(p.item, item));
+            }
+        }
+        if inner.pending_count >= MAX_SHARD_PENDING_LOGS {
+            return DedupResult::Send(item);
+        }
+        let deadline = now + window;
+        inner
+            .pending
+            .entry(key)
+            .or_default()
+            .push(Pending { item, deadline });
+        inner.pending_count += 1;
+        inner.deadlines.push_back((deadline, key));
+        DedupResult::Held
+    }
+
+    // returns logs without counterparts before their deadlines
+    pub fn expire(&self, now: Duration) -> Vec<Box<MetaAppProto>> {
+        let mut expired = vec![];
+        for shard in self.shards.iter() {
+            let mut guard = shard.lock().unwrap();
+            let inner = &mut *guard;
+            let count = expired.len();
+            while let Some(&(deadline, key)) = inner.deadlines.front() {
+                if deadline > now {
+                    break;
+                }
+                inner.deadlines.pop_front();
+                // logs may have been merged, or a later log with the same key is pending
+                let Entry::Occupied(mut e) = inner.pending.entry(key) else {
+                    continue;
+                };
+                let logs = e.get_mut();
+                let mut i = 0;
+                while i < logs.len() {
+                    if logs[i].deadline <= now {
+                        expired.push(logs.swap_remove(i).item);
+                    } else {
+                        i += 1;
+                    }
+                }
+                if logs.is_empty() {
+                    e.remove();
+                }
+            }
+            inner.pending_count -= expired.len() - count;
+            if now >= inner.last_cleanup + CLEANUP_INTERVAL {
+                let covered = inner.covered.len();
+                inner.covered.retain(|_, t| *t > now);
+                self.covered_count
+                    .fetch_sub(covered - inner.covered.len(), Ordering::Relaxed);
+                inner.last_cleanup = now;
+            }
+        }
+        expired
+    }
+
+    // whether logs of the connection are captured by both cbpf and ebpf
+    pub fn is_covered(&self, conn_key: u64, now: Duration) -> bool {
+        if self.covered_count.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        let shard = self.shard(conn_key).lock().unwrap();
+        shard
+            .covered
+            .get(&conn_key)
+            .map(|t| *t > now)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    use crate::common::{l7_protocol_info::L7ProtocolInfo, MetaPacket, TaggedFlow};
+    use crate::flow_generator::protocol_logs::{AppProtoHead, DnsInfo};
+
+    fn new_log(
+        source: SignalSource,
+        msg_type: LogMessageType,
+        start_time: u64,
+    ) -> Box<MetaAppProto> {
+        let mut item = MetaAppProto::new(
+            &TaggedFlow::default(),
+            &MetaPacket::default(),
+            L7ProtocolInfo::DnsInfo(DnsInfo::default()),
+            AppProtoHead {
+                proto: L7Protocol::DNS,
+                msg_type,
+                rrt: 0,
+            },
+        )
+        .unwrap();
+        let b = &mut item.base_info;
+        b.signal_source = source;
+        b.start_time = Duration::from_millis(start_time).into();
+        b.ip_src = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        b.ip_dst = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        b.port_src = 34567;
+        b.port_dst = 53;
+        Box::new(item)
+    }
+
+    #[test]
+    fn deduplicate() {
+        let dedup = L7LogDeduplicator::default();
+        let window = Duration::from_secs(2);
+        let now = Duration::from_secs(100);
+
+        let packet = new_log(SignalSource::Packet, LogMessageType::Session, 1000);
+        assert!(matches!(
+            dedup.submit(packet, window, now),
+            DedupResult::Held
+        ));
+        // same source is not deduplicated
+        let packet = new_log(SignalSource::Packet, LogMessageType::Session, 1001);
+        assert!(matches!(
+            dedup.submit(packet, window, now),
+            DedupResult::Held
+        ));
+        let mut ebpf = new_log(SignalSource::EBPF, LogMessageType::Session, 1001);
+        ebpf.base_info.process_id_0 = 42;
+        match dedup.submit(ebpf, window, now) {
+            DedupResult::Merged(m) => {
+                assert_eq!(m.base_info.signal_source, SignalSource::EBPF);
+                assert_eq!(m.base_info.process_id_0, 42);
+            }
+            _ => panic!("ebpf log not merged"),
+        }
+        let key = connection_key(
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            53,
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            34567,
+            L7Protocol::DNS,
+        );
+        assert!(dedup.is_covered(key, now));
+
+        // session from packet is preferred over a single request from ebpf
+        let ebpf = new_log(SignalSource::EBPF, LogMessageType::Request, 5000);
+        assert!(matches!(dedup.submit(ebpf, window, now), DedupResult::Held));
+        let packet = new_log(SignalSource::Packet, LogMessageType::Session, 5001);
+        match dedup.submit(packet, window, now) {
+            DedupResult::Merged(m) => {
+                assert_eq!(m.base_info.signal_source, SignalSource::Packet)
+            }
+            _ => panic!("packet log not merged"),
+        }
+
+        // out of window
+        let ebpf = new_log(SignalSource::EBPF, LogMessageType::Session, 9000);
+        assert!(matches!(dedup.submit(ebpf, window, now), DedupResult::Held));
+
+        assert!(dedup
+            .expire(now + window - Duration::from_secs(1))
+            .is_empty());
+        assert_eq!(dedup.expire(now + window).len(), 2);
+        assert!(!dedup.is_covered(key, now + COVERED_TIMEOUT));
+        assert!(dedup
+            .expire(now + COVERED_TIMEOUT + CLEANUP_INTERVAL)
+            .is_empty());
+        assert_eq!(dedup.covered_count.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,796 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// DHCP for IPv4, reference: https://www.rfc-editor.org/rfc/rfc2131
+// and https://www.rfc-editor.org/rfc/rfc2132
+// DHCP for IPv6, reference: https://www.rfc-editor.org/rfc/rfc8415
+
+use std::net::IpAddr;
+
+use serde::Serialize;
+
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    flow_generator::error::{Error, Result},
+    utils::bytes::{read_u16_be, read_u32_be},
+};
+use public::{l7_protocol::L7Protocol, utils::net::parse_ip_slice};
+
+pub const DHCP_V4_SERVER_PORT: u16 = 67;
+pub const DHCP_V4_CLIENT_PORT: u16 = 68;
+pub const DHCP_V6_CLIENT_PORT: u16 = 546;
+pub const DHCP_V6_SERVER_PORT: u16 = 547;
+
+// op, htype, hlen, hops, xid, secs, flags, ciaddr, yiaddr, siaddr, giaddr, chaddr, sname, file
+const V4_HEADER_SIZE: usize = 236;
+const V4_MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+const V4_XID_OFFSET: usize = 4;
+const V4_CIADDR_OFFSET: usize = 12;
+const V4_YIADDR_OFFSET: usize = 16;
+const V4_GIADDR_OFFSET: usize = 24;
+const V4_CHADDR_OFFSET: usize = 28;
+const V4_CHADDR_SIZE: usize = 16;
+const V4_HTYPE_ETHERNET: u8 = 1;
+
+const V4_OPTION_PAD: u8 = 0;
+const V4_OPTION_HOSTNAME: u8 = 12;
+const V4_OPTION_REQUESTED_IP: u8 = 50;
+const V4_OPTION_LEASE_TIME: u8 = 51;
+const V4_OPTION_MESSAGE_TYPE: u8 = 53;
+const V4_OPTION_SERVER_ID: u8 = 54;
+const V4_OPTION_MESSAGE: u8 = 56;
+const V4_OPTION_END: u8 = 255;
+
+const V4_OFFER: u8 = 2;
+const V4_ACK: u8 = 5;
+const V4_NAK: u8 = 6;
+
+// msg-type and transaction-id
+const V6_HEADER_SIZE: usize = 4;
+// msg-type, hop-count, link-address and peer-address
+const V6_RELAY_HEADER_SIZE: usize = 34;
+const V6_RELAY_MAX_HOPS: usize = 32;
+
+const V6_OPTION_CLIENT_ID: u16 = 1;
+const V6_OPTION_SERVER_ID: u16 = 2;
+const V6_OPTION_IA_NA: u16 = 3;
+const V6_OPTION_IA_TA: u16 = 4;
+const V6_OPTION_IA_ADDR: u16 = 5;
+const V6_OPTION_RELAY_MSG: u16 = 9;
+const V6_OPTION_STATUS_CODE: u16 = 13;
+const V6_OPTION_FQDN: u16 = 39;
+
+// IAID, T1 and T2 of IA_NA
+const V6_IA_NA_HEADER_SIZE: usize = 12;
+// IAID of IA_TA
+const V6_IA_TA_HEADER_SIZE: usize = 4;
+// address, preferred-lifetime and valid-lifetime
+const V6_IA_ADDR_HEADER_SIZE: usize = 24;
+
+const V6_SOLICIT: u8 = 1;
+const V6_ADVERTISE: u8 = 2;
+const V6_REPLY: u8 = 7;
+const V6_RELAY_FORW: u8 = 12;
+const V6_RELAY_REPL: u8 = 13;
+
+const V6_STATUS_SUCCESS: u16 = 0;
+const V6_STATUS_NO_BINDING: u16 = 3;
+const V6_STATUS_NOT_ON_LINK: u16 = 4;
+const V6_STATUS_USE_MULTICAST: u16 = 5;
+
+fn v4_message_type(t: u8) -> Option<&'static str> {
+    let types = [
+        "DISCOVER", "OFFER", "REQUEST", "DECLINE", "ACK", "NAK", "RELEASE", "INFORM",
+    ];
+    types.get((t as usize).checked_sub(1)?).copied()
+}
+
+fn v6_message_type(t: u8) -> Option<&'static str> {
+    let types = [
+        "SOLICIT",
+        "ADVERTISE",
+        "REQUEST",
+        "CONFIRM",
+        "RENEW",
+        "REBIND",
+        "REPLY",
+        "RELEASE",
+        "DECLINE",
+        "RECONFIGURE",
+        "INFORMATION-REQUEST",
+    ];
+    types.get((t as usize).checked_sub(1)?).copied()
+}
+
+fn v4_options(mut data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || loop {
+        let (&code, rest) = data.split_first()?;
+        match code {
+            V4_OPTION_PAD => data = rest,
+            V4_OPTION_END => return None,
+            _ => {
+                let (&len, rest) = rest.split_first()?;
+                let value = rest.get(..len as usize)?;
+                data = &rest[len as usize..];
+                return Some((code, value));
+            }
+        }
+    })
+}
+
+fn v6_options(mut data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 4 {
+            return None;
+        }
+        let code = read_u16_be(data);
+        let len = read_u16_be(&data[2..]) as usize;
+        let value = data.get(4..4 + len)?;
+        data = &data[4 + len..];
+        Some((code, value))
+    })
+}
+
+fn to_hex(data: &[u8], separator: &str) -> String {
+    data.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct DhcpInfo {
+    #[serde(rename = "request_id")]
+    pub transaction_id: u32,
+    // 4 or 6
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub version: u8,
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    pub request_type: &'static str,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub response_type: &'static str,
+
+    #[serde(rename = "request_resource", skip_serializing_if = "Option::is_none")]
+    pub requested_ip: Option<IpAddr>,
+    #[serde(rename = "response_result", skip_serializing_if = "Option::is_none")]
+    pub assigned_ip: Option<IpAddr>,
+    // seconds, valid-lifetime of the address for dhcpv6
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_time: Option<u32>,
+    // ip of dhcpv4 server, or DUID of dhcpv6 server
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub server_id: String,
+    // hardware address of dhcpv4 client, or DUID of dhcpv6 client
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relay_ip: Option<IpAddr>,
+
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<i32>,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub exception: String,
+
+    msg_type: LogMessageType,
+    rrt: u64,
+}
+
+impl L7ProtocolInfoInterface for DhcpInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.transaction_id)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::DhcpInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::DHCP,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+}
+
+impl DhcpInfo {
+    pub fn merge(&mut self, other: &mut Self) {
+        self.response_type = other.response_type;
+        self.assigned_ip = other.assigned_ip;
+        self.status = other.status;
+        self.status_code = other.status_code;
+        std::mem::swap(&mut self.exception, &mut other.exception);
+        if other.lease_time.is_some() {
+            self.lease_time = other.lease_time;
+        }
+        if self.server_id.is_empty() {
+            std::mem::swap(&mut self.server_id, &mut other.server_id);
+        }
+        if self.hostname.is_none() {
+            self.hostname = other.hostname.take();
+        }
+    }
+}
+
+impl From<DhcpInfo> for L7ProtocolSendLog {
+    fn from(f: DhcpInfo) -> Self {
+        let mut attributes = vec![];
+        if !f.response_type.is_empty() {
+            attributes.push(KeyVal {
+                key: "dhcp_response_type".to_string(),
+                val: f.response_type.to_string(),
+            });
+        }
+        if let Some(lease_time) = f.lease_time {
+            attributes.push(KeyVal {
+                key: "dhcp_lease_time".to_string(),
+                val: lease_time.to_string(),
+            });
+        }
+        for (key, val) in [
+            (
+                "dhcp_server_id",
+                Some(f.server_id).filter(|s| !s.is_empty()),
+            ),
+            (
+                "dhcp_client_id",
+                Some(f.client_id).filter(|s| !s.is_empty()),
+            ),
+            ("dhcp_hostname", f.hostname),
+            ("dhcp_relay_ip", f.relay_ip.map(|ip| ip.to_string())),
+        ] {
+            if let Some(val) = val {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        L7ProtocolSendLog {
+            req: L7Request {
+                req_type: f.request_type.to_string(),
+                resource: f.requested_ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                ..Default::default()
+            },
+            resp: L7Response {
+                result: f.assigned_ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                code: f.status_code,
+                status: f.status,
+                exception: f.exception,
+            },
+            version: Some(f.version.to_string()),
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(f.transaction_id),
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DhcpLog {
+    perf_stats: Option<L7PerfStats>,
+}
+
+impl L7ProtocolParserInterface for DhcpLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::UDP {
+            return false;
+        }
+        // responses are checked too, since offers are usually sent in another flow
+        match Self::parse(payload, param) {
+            Ok(info) => !info.client_id.is_empty() || !info.server_id.is_empty(),
+            Err(_) => false,
+        }
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+        let mut info = Self::parse(payload, param)?;
+        if let Some(perf_stats) = self.perf_stats.as_mut() {
+            match info.msg_type {
+                LogMessageType::Request => perf_stats.inc_req(),
+                _ => perf_stats.inc_resp(),
+            }
+            match info.status {
+                L7ResponseStatus::ClientError => perf_stats.inc_req_err(),
+                L7ResponseStatus::ServerError => perf_stats.inc_resp_err(),
+                _ => {}
+            }
+        }
+        info.cal_rrt(param, None).map(|rrt| {
+            info.rrt = rrt;
+            self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+        });
+        if param.parse_log {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::DhcpInfo(info)))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::DHCP
+    }
+
+    fn parsable_on_tcp(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl DhcpLog {
+    fn parse(payload: &[u8], param: &ParseParam) -> Result<DhcpInfo> {
+        if payload.len() >= V4_HEADER_SIZE + V4_MAGIC_COOKIE.len()
+            && payload[V4_HEADER_SIZE..V4_HEADER_SIZE + V4_MAGIC_COOKIE.len()] == V4_MAGIC_COOKIE
+        {
+            return Self::parse_v4(payload);
+        }
+        // dhcpv6 has no magic, ports are required
+        let ports = [param.port_src, param.port_dst];
+        if ports.contains(&DHCP_V6_CLIENT_PORT) || ports.contains(&DHCP_V6_SERVER_PORT) {
+            return Self::parse_v6(payload);
+        }
+        Err(Error::DhcpLogParseFailed("not dhcp message".to_string()))
+    }
+
+    fn parse_v4(payload: &[u8]) -> Result<DhcpInfo> {
+        let mut info = DhcpInfo {
+            version: 4,
+            transaction_id: read_u32_be(&payload[V4_XID_OFFSET..]),
+            ..Default::default()
+        };
+        let (htype, hlen) = (payload[1], payload[2] as usize);
+        if hlen > 0 && hlen <= V4_CHADDR_SIZE {
+            let chaddr = &payload[V4_CHADDR_OFFSET..V4_CHADDR_OFFSET + hlen];
+            info.client_id = if htype == V4_HTYPE_ETHERNET {
+                to_hex(chaddr, ":")
+            } else {
+                to_hex(chaddr, "")
+            };
+        }
+        let ip_at = |offset: usize| {
+            parse_ip_slice(&payload[offset..offset + 4]).filter(|ip| !ip.is_unspecified())
+        };
+        info.relay_ip = ip_at(V4_GIADDR_OFFSET);
+
+        let mut message_type = None;
+        let mut message = None;
+        for (code, value) in v4_options(&payload[V4_HEADER_SIZE + V4_MAGIC_COOKIE.len()..]) {
+            match code {
+                V4_OPTION_MESSAGE_TYPE if value.len() == 1 => message_type = Some(value[0]),
+                V4_OPTION_REQUESTED_IP => info.requested_ip = parse_ip_slice(value),
+                V4_OPTION_LEASE_TIME if value.len() == 4 => {
+                    info.lease_time = Some(read_u32_be(value))
+                }
+                V4_OPTION_SERVER_ID => {
+                    if let Some(ip) = parse_ip_slice(value) {
+                        info.server_id = ip.to_string();
+                    }
+                }
+                V4_OPTION_HOSTNAME => {
+                    info.hostname = Some(String::from_utf8_lossy(value).into_owned())
+                }
+                V4_OPTION_MESSAGE => message = Some(String::from_utf8_lossy(value).into_owned()),
+                _ => {}
+            }
+        }
+        let Some(message_type) = message_type else {
+            return Err(Error::DhcpLogParseFailed(
+                "dhcp message type not found".to_string(),
+            ));
+        };
+        let Some(name) = v4_message_type(message_type) else {
+            let err_msg = format!("dhcp message type {} invalid", message_type);
+            return Err(Error::DhcpLogParseFailed(err_msg));
+        };
+        match message_type {
+            V4_OFFER | V4_ACK | V4_NAK => {
+                info.msg_type = LogMessageType::Response;
+                info.response_type = name;
+                info.assigned_ip = ip_at(V4_YIADDR_OFFSET);
+                if message_type == V4_NAK {
+                    info.status = L7ResponseStatus::ClientError;
+                    info.exception = message.unwrap_or_else(|| name.to_string());
+                }
+            }
+            _ => {
+                info.msg_type = LogMessageType::Request;
+                info.request_type = name;
+                // the address of renewing or rebinding client
+                if info.requested_ip.is_none() {
+                    info.requested_ip = ip_at(V4_CIADDR_OFFSET);
+                }
+            }
+        }
+        Ok(info)
+    }
+
+    fn parse_v6(payload: &[u8]) -> Result<DhcpInfo> {
+        let mut payload = payload;
+        let mut relay_ip = None;
+        // unwrap the message of relay agents
+        for _ in 0..V6_RELAY_MAX_HOPS {
+            match payload.first() {
+                Some(&V6_RELAY_FORW) | Some(&V6_RELAY_REPL) => (),
+                _ => break,
+            }
+            if payload.len() < V6_RELAY_HEADER_SIZE {
+                return Err(Error::L7ProtocolUnknown);
+            }
+            // link-address of the first relay agent
+            relay_ip = parse_ip_slice(&payload[2..18]).filter(|ip| !ip.is_unspecified());
+            let Some((_, message)) = v6_options(&payload[V6_RELAY_HEADER_SIZE..])
+                .find(|(code, _)| *code == V6_OPTION_RELAY_MSG)
+            else {
+                return Err(Error::L7ProtocolUnknown);
+            };
+            payload = message;
+        }
+        // the innermost message may be truncated or nested beyond the hop limit
+        if payload.len() < V6_HEADER_SIZE
+            || payload[0] == V6_RELAY_FORW
+            || payload[0] == V6_RELAY_REPL
+        {
+            return Err(Error::L7ProtocolUnknown);
+        }
+
+        let message_type = payload[0];
+        let Some(name) = v6_message_type(message_type) else {
+            let err_msg = format!("dhcpv6 message type {} invalid", message_type);
+            return Err(Error::DhcpLogParseFailed(err_msg));
+        };
+        let mut info = DhcpInfo {
+            version: 6,
+            transaction_id: read_u32_be(payload) & 0xffffff,
+            relay_ip,
+            ..Default::default()
+        };
+        let is_response = message_type == V6_ADVERTISE || message_type == V6_REPLY;
+        if is_response {
+            info.msg_type = LogMessageType::Response;
+            info.response_type = name;
+        } else {
+            info.msg_type = LogMessageType::Request;
+            info.request_type = name;
+        }
+
+        let mut address = None;
+        let mut status = None;
+        for (code, value) in v6_options(&payload[V6_HEADER_SIZE..]) {
+            match code {
+                V6_OPTION_CLIENT_ID => info.client_id = to_hex(value, ""),
+                V6_OPTION_SERVER_ID => info.server_id = to_hex(value, ""),
+                V6_OPTION_IA_NA | V6_OPTION_IA_TA => {
+                    let header_size = if code == V6_OPTION_IA_NA {
+                        V6_IA_NA_HEADER_SIZE
+                    } else {
+                        V6_IA_TA_HEADER_SIZE
+                    };
+                    for (code, value) in v6_options(value.get(header_size..).unwrap_or_default()) {
+                        match code {
+                            V6_OPTION_IA_ADDR if value.len() >= V6_IA_ADDR_HEADER_SIZE => {
+                                if address.is_none() {
+                                    address = Some((
+                                        parse_ip_slice(&value[..16]),
+                                        read_u32_be(&value[20..]),
+                                    ));
+                                }
+                                // status of the address
+                                if let Some((_, s)) = v6_options(&value[V6_IA_ADDR_HEADER_SIZE..])
+                                    .find(|(code, _)| *code == V6_OPTION_STATUS_CODE)
+                                {
+                                    status = status.or(Some(s));
+                                }
+                            }
+                            V6_OPTION_STATUS_CODE => status = status.or(Some(value)),
+                            _ => {}
+                        }
+                    }
+                }
+                V6_OPTION_STATUS_CODE => status = Some(value),
+                V6_OPTION_FQDN if value.len() > 1 => {
+                    info.hostname = Some(Self::decode_fqdn(&value[1..]))
+                }
+                _ => {}
+            }
+        }
+        if let Some((ip, valid_lifetime)) = address {
+            if is_response {
+                info.assigned_ip = ip;
+                info.lease_time = Some(valid_lifetime);
+            } else {
+                info.requested_ip = ip;
+            }
+        }
+        if let Some(status) = status.filter(|s| s.len() >= 2) {
+            let code = read_u16_be(status);
+            info.status_code = Some(code as i32);
+            info.status = match code {
+                V6_STATUS_SUCCESS => L7ResponseStatus::Ok,
+                V6_STATUS_NO_BINDING | V6_STATUS_NOT_ON_LINK | V6_STATUS_USE_MULTICAST => {
+                    L7ResponseStatus::ClientError
+                }
+                _ => L7ResponseStatus::ServerError,
+            };
+            info.exception = String::from_utf8_lossy(&status[2..]).into_owned();
+        }
+        if message_type == V6_SOLICIT && info.client_id.is_empty() {
+            return Err(Error::DhcpLogParseFailed(
+                "dhcpv6 solicit without client id".to_string(),
+            ));
+        }
+        Ok(info)
+    }
+
+    // domain name in dns wire format without compression
+    fn decode_fqdn(mut data: &[u8]) -> String {
+        let mut labels = vec![];
+        while let Some((&len, rest)) = data.split_first() {
+            if len == 0 || len as usize > rest.len() {
+                break;
+            }
+            labels.push(String::from_utf8_lossy(&rest[..len as usize]));
+            data = &rest[len as usize..];
+        }
+        labels.join(".")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    use crate::{
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn dhcpv4(op: u8, xid: u32, yiaddr: [u8; 4], options: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut m = vec![op, V4_HTYPE_ETHERNET, 6, 0];
+        m.extend_from_slice(&xid.to_be_bytes());
+        // secs, flags and ciaddr
+        m.extend_from_slice(&[0; 8]);
+        m.extend_from_slice(&yiaddr);
+        // siaddr and giaddr
+        m.extend_from_slice(&[0; 8]);
+        m.extend_from_slice(&[0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+        m.resize(V4_HEADER_SIZE, 0);
+        m.extend_from_slice(&V4_MAGIC_COOKIE);
+        for (code, value) in options {
+            m.push(*code);
+            m.push(value.len() as u8);
+            m.extend_from_slice(value);
+        }
+        m.push(V4_OPTION_END);
+        m
+    }
+
+    fn parse(payload: &[u8], src_port: u16, dst_port: u16) -> Option<DhcpInfo> {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        packet.lookup_key.direction = PacketDirection::ClientToServer;
+        packet.lookup_key.src_port = src_port;
+        packet.lookup_key.dst_port = dst_port;
+        let param = ParseParam::new(&packet, log_cache, true, true);
+        let mut dhcp = DhcpLog::default();
+        if !dhcp.check_payload(payload, &param) {
+            return None;
+        }
+        match dhcp.parse_payload(payload, &param).ok()?.unwrap_single() {
+            L7ProtocolInfo::DhcpInfo(i) => Some(i),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_dhcpv4() {
+        let discover = dhcpv4(
+            1,
+            0x3903f326,
+            [0; 4],
+            &[
+                (V4_OPTION_MESSAGE_TYPE, &[1]),
+                (V4_OPTION_REQUESTED_IP, &[192, 168, 1, 100]),
+                (V4_OPTION_HOSTNAME, b"host-1"),
+            ],
+        );
+        let info = parse(&discover, DHCP_V4_CLIENT_PORT, DHCP_V4_SERVER_PORT).unwrap();
+        assert_eq!(info.msg_type, LogMessageType::Request);
+        assert_eq!(info.transaction_id, 0x3903f326);
+        assert_eq!(info.request_type, "DISCOVER");
+        assert_eq!(info.requested_ip, Some("192.168.1.100".parse().unwrap()));
+        assert_eq!(info.client_id, "52:54:00:12:34:56");
+        assert_eq!(info.hostname.as_deref(), Some("host-1"));
+
+        let ack = dhcpv4(
+            2,
+            0x3903f326,
+            [192, 168, 1, 100],
+            &[
+                (V4_OPTION_MESSAGE_TYPE, &[V4_ACK]),
+                (V4_OPTION_SERVER_ID, &[192, 168, 1, 1]),
+                (V4_OPTION_LEASE_TIME, &[0, 0, 0x0e, 0x10]),
+            ],
+        );
+        let info = parse(&ack, DHCP_V4_SERVER_PORT, DHCP_V4_CLIENT_PORT).unwrap();
+        assert_eq!(info.msg_type, LogMessageType::Response);
+        assert_eq!(info.response_type, "ACK");
+        assert_eq!(info.assigned_ip, Some("192.168.1.100".parse().unwrap()));
+        assert_eq!(info.server_id, "192.168.1.1");
+        assert_eq!(info.lease_time, Some(3600));
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+
+        let nak = dhcpv4(
+            2,
+            0x3903f326,
+            [0; 4],
+            &[
+                (V4_OPTION_MESSAGE_TYPE, &[V4_NAK]),
+                (V4_OPTION_MESSAGE, b"wrong network"),
+            ],
+        );
+        let info = parse(&nak, DHCP_V4_SERVER_PORT, DHCP_V4_CLIENT_PORT).unwrap();
+        assert_eq!(info.response_type, "NAK");
+        assert_eq!(info.assigned_ip, None);
+        assert_eq!(info.status, L7ResponseStatus::ClientError);
+        assert_eq!(info.exception, "wrong network");
+
+        // without message type
+        let bootp = dhcpv4(1, 1, [0; 4], &[]);
+        assert!(parse(&bootp, DHCP_V4_CLIENT_PORT, DHCP_V4_SERVER_PORT).is_none());
+    }
+
+    #[test]
+    fn test_dhcpv6() {
+        let client_id = [0, 3, 0, 1, 0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+        let mut solicit = vec![V6_SOLICIT, 0x12, 0x34, 0x56];
+        solicit.extend_from_slice(&[0, 1, 0, client_id.len() as u8]);
+        solicit.extend_from_slice(&client_id);
+        let info = parse(&solicit, DHCP_V6_CLIENT_PORT, DHCP_V6_SERVER_PORT).unwrap();
+        assert_eq!(info.version, 6);
+        assert_eq!(info.transaction_id, 0x123456);
+        assert_eq!(info.request_type, "SOLICIT");
+        assert_eq!(info.client_id, "00030001525400123456");
+
+        // reply with address and status NoAddrsAvail of IA_NA
+        let mut reply = vec![V6_REPLY, 0x12, 0x34, 0x56];
+        reply.extend_from_slice(&[0, 2, 0, 4, 0, 3, 0, 1]);
+        let mut ia_addr = "2001:db8::100"
+            .parse::<std::net::Ipv6Addr>()
+            .unwrap()
+            .octets()
+            .to_vec();
+        ia_addr.extend_from_slice(&[0, 0, 0x0e, 0x10, 0, 0, 0x1c, 0x20]);
+        let mut ia_na = vec![0; V6_IA_NA_HEADER_SIZE];
+        ia_na.extend_from_slice(&[0, 5, 0, ia_addr.len() as u8]);
+        ia_na.extend_from_slice(&ia_addr);
+        ia_na.extend_from_slice(&[0, 13, 0, 6, 0, 2]);
+        ia_na.extend_from_slice(b"full");
+        reply.extend_from_slice(&[0, 3, 0, ia_na.len() as u8]);
+        reply.extend_from_slice(&ia_na);
+
+        // forwarded by relay agent
+        let mut relay = vec![V6_RELAY_REPL, 0];
+        relay.extend_from_slice(
+            &"2001:db8::1"
+                .parse::<std::net::Ipv6Addr>()
+                .unwrap()
+                .octets(),
+        );
+        relay.extend_from_slice(&[0; 16]);
+        relay.extend_from_slice(&[0, 9, 0, reply.len() as u8]);
+        relay.extend_from_slice(&reply);
+        let info = parse(&relay, DHCP_V6_SERVER_PORT, DHCP_V6_SERVER_PORT).unwrap();
+        assert_eq!(info.msg_type, LogMessageType::Response);
+        assert_eq!(info.response_type, "REPLY");
+        assert_eq!(info.assigned_ip, Some("2001:db8::100".parse().unwrap()));
+        assert_eq!(info.lease_time, Some(7200));
+        assert_eq!(info.server_id, "00030001");
+        assert_eq!(info.relay_ip, Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(info.status_code, Some(2));
+        assert_eq!(info.status, L7ResponseStatus::ServerError);
+        assert_eq!(info.exception, "full");
+
+        // dhcpv6 is only parsed on its ports
+        assert!(parse(&solicit, 50000, 53).is_none());
+    }
+
+    #[test]
+    fn test_dhcpv6_truncated_relay() {
+        let relay = |message: &[u8]| {
+            let mut relay = vec![V6_RELAY_FORW, 0];
+            relay.extend_from_slice(&[0; 32]);
+            relay.extend_from_slice(&V6_OPTION_RELAY_MSG.to_be_bytes());
+            relay.extend_from_slice(&(message.len() as u16).to_be_bytes());
+            relay.extend_from_slice(message);
+            relay
+        };
+        let solicit = [V6_SOLICIT, 0x12, 0x34, 0x56, 0, 1, 0, 2, 0, 3];
+        assert!(DhcpLog::parse_v6(&relay(&solicit)).is_ok());
+
+        // relayed message shorter than the dhcpv6 header
+        for message in [&[][..], &[V6_SOLICIT][..], &solicit[..3]] {
+            assert!(matches!(
+                DhcpLog::parse_v6(&relay(message)),
+                Err(Error::L7ProtocolUnknown)
+            ));
+        }
+        // relay header truncated in the nested relay
+        let nested = relay(&relay(&solicit)[..V6_RELAY_HEADER_SIZE - 1]);
+        assert!(matches!(
+            DhcpLog::parse_v6(&nested),
+            Err(Error::L7ProtocolUnknown)
+        ));
+        // relay message option truncated
+        let mut truncated = relay(&solicit);
+        truncated.truncate(truncated.len() - 1);
+        assert!(matches!(
+            DhcpLog::parse_v6(&truncated),
+            Err(Error::L7ProtocolUnknown)
+        ));
+        // nested beyond the hop limit
+        let mut deep = solicit.to_vec();
+        for _ in 0..=V6_RELAY_MAX_HOPS {
+            deep = relay(&deep);
+        }
+        assert!(matches!(
+            DhcpLog::parse_v6(&deep),
+            Err(Error::L7ProtocolUnknown)
+        ));
+        assert!(parse(&relay(&[]), DHCP_V6_SERVER_PORT, DHCP_V6_SERVER_PORT).is_none());
+    }
+}
@@ -14,9 +14,13 @@
  * limitations under the License.
  */
 
+pub mod doh;
+
+use std::fmt;
+
 use serde::Serialize;
 
-use super::pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response};
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
 use super::{consts::*, value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
 use crate::common::flow::L7PerfStats;
 use crate::common::l7_protocol_log::L7ParseResult;
@@ -28,8 +32,9 @@ use crate::{
         meta_packet::EbpfFlags,
         IPV4_ADDR_LEN, IPV6_ADDR_LEN,
     },
+    config::DnsAnswerRecords,
     flow_generator::error::{Error, Result},
-    utils::bytes::read_u16_be,
+    utils::bytes::{read_u16_be, read_u32_be},
 };
 use public::{l7_protocol::L7Protocol, utils::net::parse_ip_slice};
 
@@ -56,6 +61,24 @@ pub struct DnsInfo {
     #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
     pub status_code: Option<i32>,
 
+    // DoT or DoH
+    #[serde(skip)]
+    pub encrypted_dns: Option<&'static str>,
+    // mDNS or LLMNR
+    #[serde(skip)]
+    pub multicast_dns: Option<&'static str>,
+    // DNS-SD service type in the names of mDNS, e.g. `_http._tcp`
+    #[serde(skip)]
+    pub service_type: Option<String>,
+
+    // answer records of the response, at most `dns-answer-records.max-records`
+    #[serde(skip)]
+    pub records: Vec<DnsRecord>,
+    #[serde(skip)]
+    pub authoritative: bool,
+    #[serde(skip)]
+    pub truncated: bool,
+
     msg_type: LogMessageType,
     #[serde(skip)]
     is_tls: bool,
@@ -88,11 +111,21 @@ impl L7ProtocolInfoInterface for DnsInfo {
     fn is_tls(&self) -> bool {
         self.is_tls
     }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
 }
 
 impl DnsInfo {
     pub fn merge(&mut self, other: &mut Self) {
         std::mem::swap(&mut self.answers, &mut other.answers);
+        std::mem::swap(&mut self.records, &mut other.records);
+        self.authoritative = other.authoritative;
+        self.truncated = other.truncated;
+        if self.service_type.is_none() {
+            self.service_type = other.service_type.take();
+        }
         if other.status != L7ResponseStatus::default() {
             self.status = other.status;
         }
@@ -105,20 +138,68 @@ impl DnsInfo {
     }
 
     pub fn get_domain_str(&self) -> &'static str {
-        let typ = [
-            "", "A", "NS", "MD", "MF", "CNAME", "SOA", "MB", "MG", "MR", "NULL", "WKS", "PTR",
-            "HINFO", "MINFO", "MX", "TXT",
-        ];
+        domain_type_str(self.domain_type)
+    }
+}
 
-        match self.domain_type {
-            1..=16 => typ[self.domain_type as usize],
-            28 => "AAAA",
-            252 => "AXFR",
-            253 => "MAILB",
-            254 => "MAILA",
-            255 => "ANY",
-            _ => "",
+// service type of DNS-SD is the `_service._tcp` or `_service._udp` labels in the name,
+// e.g. `_ipp._tcp` in `My Printer._ipp._tcp.local`
+fn service_type(name: &str) -> Option<&str> {
+    let mut start = 0;
+    let mut prev = None;
+    for label in name.split('.') {
+        if label == "_tcp" || label == "_udp" {
+            if let Some(p) = prev.filter(|&p| name[p..].starts_with('_')) {
+                return Some(&name[p..start + label.len()]);
+            }
         }
+        prev = Some(start);
+        start += label.len() + 1;
+    }
+    None
+}
+
+fn domain_type_str(domain_type: u16) -> &'static str {
+    let typ = [
+        "", "A", "NS", "MD", "MF", "CNAME", "SOA", "MB", "MG", "MR", "NULL", "WKS", "PTR", "HINFO",
+        "MINFO", "MX", "TXT",
+    ];
+
+    match domain_type {
+        1..=16 => typ[domain_type as usize],
+        28 => "AAAA",
+        33 => "SRV",
+        39 => "DNAME",
+        65 => "HTTPS",
+        252 => "AXFR",
+        253 => "MAILB",
+        254 => "MAILA",
+        255 => "ANY",
+        _ => "",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub rr_type: u16,
+    pub ttl: u32,
+    // presentation of rdata, empty if the type is not supported
+    pub data: String,
+}
+
+// zone file like, e.g. `www.example.com 300 CNAME example.com`
+impl fmt::Display for DnsRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ", self.name, self.ttl)?;
+        match domain_type_str(self.rr_type) {
+            "" => write!(f, "TYPE{}", self.rr_type)?,
+            t => write!(f, "{}", t)?,
+        }
+        if !self.data.is_empty() {
+            write!(f, " {}", self.data)?;
+        }
+        Ok(())
     }
 }
 
@@ -130,6 +211,48 @@ impl From<DnsInfo> for L7ProtocolSendLog {
         } else {
             EbpfFlags::NONE.bits()
         };
+        let mut attributes = vec![];
+        if let Some(e) = f.encrypted_dns {
+            attributes.push(KeyVal {
+                key: "encrypted_dns".to_string(),
+                val: e.to_string(),
+            });
+        }
+        if let Some(m) = f.multicast_dns {
+            attributes.push(KeyVal {
+                key: "multicast_dns".to_string(),
+                val: m.to_string(),
+            });
+        }
+        if let Some(service_type) = f.service_type {
+            attributes.push(KeyVal {
+                key: "dns_service_type".to_string(),
+                val: service_type,
+            });
+        }
+        if !f.records.is_empty() {
+            attributes.push(KeyVal {
+                key: "dns_answers".to_string(),
+                val: f
+                    .records
+                    .iter()
+                    .map(|r| r.to_string())
+                    .collect::<Vec<_>>()
+                    .join(&DOMAIN_NAME_SPLIT.to_string()),
+            });
+        }
+        if f.authoritative {
+            attributes.push(KeyVal {
+                key: "dns_authoritative".to_string(),
+                val: "true".to_string(),
+            });
+        }
+        if f.truncated {
+            attributes.push(KeyVal {
+                key: "dns_truncated".to_string(),
+                val: "true".to_string(),
+            });
+        }
         let log = L7ProtocolSendLog {
             req: L7Request {
                 req_type,
@@ -144,6 +267,11 @@ impl From<DnsInfo> for L7ProtocolSendLog {
             },
             ext_info: Some(ExtendedInfo {
                 request_id: Some(f.trans_id as u32),
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
                 ..Default::default()
             }),
             flags,
@@ -154,9 +282,15 @@ impl From<DnsInfo> for L7ProtocolSendLog {
     }
 }
 
+// a message over tcp is at most 65535 bytes after the 2 bytes length
+const DNS_TCP_MAX_MESSAGE_SIZE: usize = DNS_TCP_PAYLOAD_OFFSET + u16::MAX as usize;
+
 #[derive(Default)]
 pub struct DnsLog {
     perf_stats: Option<L7PerfStats>,
+    // incomplete tcp message of each direction, which continues in the next segment
+    tcp_buffers: [Vec<u8>; 2],
+    max_answer_records: usize,
 }
 
 //解析器接口实现
@@ -165,24 +299,43 @@ impl L7ProtocolParserInterface for DnsLog {
         if !param.ebpf_type.is_raw_protocol() {
             return false;
         }
-        let mut info = DnsInfo::default();
-        self.parse(payload, &mut info, param).is_ok()
-            && info.msg_type == LogMessageType::Request
-            && !info.query_name.is_empty()
+        match self.parse(payload, param, false) {
+            // announcements of mDNS are sent without query
+            Ok(infos) => infos
+                .first()
+                .map(|i| {
+                    (i.msg_type == LogMessageType::Request || i.multicast_dns.is_some())
+                        && !i.query_name.is_empty()
+                })
+                .unwrap_or(false),
+            Err(_) => false,
+        }
     }
 
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
-        let mut info = DnsInfo::default();
-        self.parse(payload, &mut info, param)?;
-        info.cal_rrt(param, None).map(|rrt| {
-            info.rrt = rrt;
-            self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
-        });
-        info.is_tls = param.is_tls();
-        if param.parse_log {
-            Ok(L7ParseResult::Single(L7ProtocolInfo::DnsInfo(info)))
+        let mut infos = self.parse(payload, param, true)?;
+        for info in infos.iter_mut() {
+            info.cal_rrt(param, None).map(|rrt| {
+                info.rrt = rrt;
+                self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+            });
+            info.is_tls = param.is_tls();
+            // plaintext of tls from ebpf uprobe
+            if info.is_tls && param.l4_protocol == IpProtocol::TCP && info.encrypted_dns.is_none() {
+                info.encrypted_dns = Some(ENCRYPTED_DNS_DOT);
+            }
+        }
+        if !param.parse_log {
+            return Ok(L7ParseResult::None);
+        }
+        if infos.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::DnsInfo(
+                infos.pop().unwrap(),
+            )))
         } else {
-            Ok(L7ParseResult::None)
+            Ok(L7ParseResult::Multi(
+                infos.into_iter().map(L7ProtocolInfo::DnsInfo).collect(),
+            ))
         }
     }
 
@@ -289,6 +442,9 @@ impl DnsLog {
             info.query_name.push(DOMAIN_NAME_SPLIT);
         }
         info.query_name.push_str(&name);
+        if info.multicast_dns.is_some() && info.service_type.is_none() {
+            info.service_type = service_type(&name).map(str::to_owned);
+        }
         if info.query_type == DNS_REQUEST {
             info.domain_type = read_u16_be(&payload[offset..]);
             info.msg_type = LogMessageType::Request;
@@ -302,8 +458,9 @@ impl DnsLog {
         payload: &[u8],
         g_offset: usize,
         info: &mut DnsInfo,
+        record: bool,
     ) -> Result<usize> {
-        let (_, offset) = self.decode_name(payload, g_offset)?;
+        let (name, offset) = self.decode_name(payload, g_offset)?;
 
         if payload.len() <= offset {
             let err_msg = format!("payload length error: {}", payload.len());
@@ -321,10 +478,78 @@ impl DnsLog {
         if data_length != 0 {
             self.decode_rdata(payload, offset + RR_RDATA_OFFSET, data_length, info)?;
         }
+        if info.multicast_dns.is_some() {
+            if info.service_type.is_none() {
+                info.service_type = service_type(&name).map(str::to_owned);
+            }
+            // responses of mDNS usually have no question
+            if info.query_name.is_empty() {
+                info.query_name = name.clone();
+            }
+        }
+        if record {
+            info.records.push(DnsRecord {
+                name,
+                rr_type: info.domain_type,
+                ttl: read_u32_be(&payload[offset + RR_TTL_OFFSET..]),
+                data: self
+                    .decode_record_data(
+                        payload,
+                        offset + RR_RDATA_OFFSET,
+                        data_length,
+                        info.domain_type,
+                    )
+                    .unwrap_or_default(),
+            });
+        }
 
         Ok(offset + RR_RDATA_OFFSET + data_length)
     }
 
+    fn decode_record_data(
+        &self,
+        payload: &[u8],
+        g_offset: usize,
+        data_length: usize,
+        rr_type: u16,
+    ) -> Option<String> {
+        let data = payload.get(g_offset..g_offset + data_length)?;
+        let name_at = |offset: usize| {
+            self.decode_name(payload, g_offset + offset)
+                .ok()
+                .map(|(name, _)| name)
+        };
+        match rr_type {
+            DNS_TYPE_A | DNS_TYPE_AAAA => parse_ip_slice(data).map(|ip| ip.to_string()),
+            DNS_TYPE_NS | DNS_TYPE_CNAME | DNS_TYPE_PTR | DNS_TYPE_DNAME => name_at(0),
+            DNS_TYPE_MX if data.len() > 2 => Some(format!("{} {}", read_u16_be(data), name_at(2)?)),
+            DNS_TYPE_SRV if data.len() > 6 => Some(format!(
+                "{} {} {} {}",
+                read_u16_be(data),
+                read_u16_be(&data[2..]),
+                read_u16_be(&data[4..]),
+                name_at(6)?
+            )),
+            DNS_TYPE_SOA => {
+                let (mname, offset) = self.decode_name(payload, g_offset).ok()?;
+                let (rname, offset) = self.decode_name(payload, offset).ok()?;
+                let serial = payload.get(offset..offset + 4).map(read_u32_be)?;
+                Some(format!("{} {} {}", mname, rname, serial))
+            }
+            DNS_TYPE_TXT => {
+                let mut texts = vec![];
+                let mut rest = data;
+                while let Some((&len, s)) = rest.split_first() {
+                    let len = (len as usize).min(s.len());
+                    texts.push(String::from_utf8_lossy(&s[..len]));
+                    rest = &s[len..];
+                }
+                Some(texts.join(" "))
+            }
+            _ => None,
+        }
+    }
+
     fn decode_rdata(
         &mut self,
         payload: &[u8],
@@ -385,6 +610,11 @@ impl DnsLog {
                     info.answers.push_str(&ipaddr.to_string());
                 }
             }
+            DNS_TYPE_PTR if info.multicast_dns.is_some() => {
+                // instance name of DNS-SD service
+                let (name, _) = self.decode_name(payload, g_offset)?;
+                info.answers.push_str(&name);
+            }
             DNS_TYPE_PTR => {
                 if data_length != DNS_TYPE_PTR_LENGTH {
                     let err_msg = format!(
@@ -394,9 +624,11 @@ impl DnsLog {
                     return Err(Error::DNSLogParseFailed(err_msg));
                 }
             }
-            DNS_TYPE_CNAME => {
-                // doing nothing
+            DNS_TYPE_CNAME | DNS_TYPE_MX | DNS_TYPE_TXT | DNS_TYPE_SRV | DNS_TYPE_HTTPS => {
+                // doing nothing, logged in answer records only
             }
+            // NSEC, HINFO and so on in mDNS
+            _ if info.multicast_dns.is_some() => {}
             _ => {
                 let err_msg = format!(
                     "other domain type {} data length {} invalid",
@@ -418,15 +650,29 @@ impl DnsLog {
             self.perf_stats.as_mut().map(|p| p.inc_resp_err());
             info.status = L7ResponseStatus::ServerError;
         }
+        self.perf_stats.as_mut().map(|p| match status_code {
+            DNS_RESPCODE_NXDOMAIN => p.dns_nxdomain += 1,
+            DNS_RESPCODE_SERVFAIL => p.dns_servfail += 1,
+            DNS_RESPCODE_REFUSED => p.dns_refused += 1,
+            _ => {}
+        });
     }
 
-    fn decode_payload(&mut self, payload: &[u8], info: &mut DnsInfo) -> Result<()> {
+    // Resource records after the end of a truncated payload are ignored
+    fn decode_payload(
+        &mut self,
+        payload: &[u8],
+        info: &mut DnsInfo,
+        truncated: bool,
+    ) -> Result<()> {
         if payload.len() <= DNS_HEADER_SIZE {
             let err_msg = format!("dns payload length too short:{}", payload.len());
             return Err(Error::DNSLogParseFailed(err_msg));
         }
         info.trans_id = read_u16_be(&payload[..DNS_HEADER_FLAGS_OFFSET]);
         info.query_type = payload[DNS_HEADER_FLAGS_OFFSET] & 0x80;
+        info.authoritative = payload[DNS_HEADER_FLAGS_OFFSET] & DNS_HEADER_AA_MASK != 0;
+        info.truncated = payload[DNS_HEADER_FLAGS_OFFSET] & DNS_HEADER_TC_MASK != 0;
         let code = payload[DNS_HEADER_FLAGS_OFFSET + 1] & 0xf;
         info.status_code = Some(code as i32);
 
@@ -442,12 +688,13 @@ impl DnsLog {
         if info.query_type == DNS_RESPONSE {
             info.query_type = 1;
 
-            for _i in 0..an_count {
-                g_offset = self.decode_resource_record(payload, g_offset, info)?;
-            }
-
-            for _i in 0..ns_count {
-                g_offset = self.decode_resource_record(payload, g_offset, info)?;
+            for i in 0..an_count as u32 + ns_count as u32 {
+                let record = i < an_count as u32 && info.records.len() < self.max_answer_records;
+                match self.decode_resource_record(payload, g_offset, info, record) {
+                    Ok(offset) => g_offset = offset,
+                    Err(_) if truncated => break,
+                    Err(e) => return Err(e),
+                }
             }
 
             self.perf_stats.as_mut().map(|p| p.inc_resp());
@@ -460,30 +707,127 @@ impl DnsLog {
         Ok(())
     }
 
-    fn parse(&mut self, payload: &[u8], info: &mut DnsInfo, param: &ParseParam) -> Result<()> {
+    // Messages over tcp are prefixed with a 2 bytes length, a segment may carry
+    // several messages or part of one, reference: https://www.rfc-editor.org/rfc/rfc7766#section-8
+    //
+    // The incomplete message at the end of segment is kept until the next segment of
+    // the same direction if reassemble is set, unless the segment is cut off by
+    // `l7_log_packet_size`, in which case the message is decoded as far as possible.
+    fn parse_tcp(
+        &mut self,
+        payload: &[u8],
+        param: &ParseParam,
+        reassemble: bool,
+    ) -> Result<Vec<DnsInfo>> {
+        let truncated = param.buf_size > 0 && payload.len() >= param.buf_size as usize;
+        let index = param.direction as usize;
+        let mut buffer = if reassemble {
+            std::mem::take(&mut self.tcp_buffers[index])
+        } else {
+            vec![]
+        };
+        let continued = !buffer.is_empty();
+        let data = if continued {
+            buffer.extend_from_slice(payload);
+            &buffer[..]
+        } else {
+            payload
+        };
+
+        let mut infos = vec![];
+        let mut error = None;
+        let mut offset = 0;
+        while offset + DNS_TCP_PAYLOAD_OFFSET < data.len() {
+            let start = offset + DNS_TCP_PAYLOAD_OFFSET;
+            let end = start + read_u16_be(&data[offset..]) as usize;
+            if end > data.len() {
+                break;
+            }
+            let mut info = DnsInfo::default();
+            if let Err(e) = self.decode_payload(&data[start..end], &mut info, false) {
+                error = Some(e);
+                break;
+            }
+            infos.push(info);
+            offset = end;
+        }
+        let rest = &data[offset..];
+        if rest.is_empty() && !infos.is_empty() {
+            return Ok(infos);
+        }
+
+        if truncated && error.is_none() && rest.len() > DNS_TCP_PAYLOAD_OFFSET {
+            let mut info = DnsInfo::default();
+            if self
+                .decode_payload(&rest[DNS_TCP_PAYLOAD_OFFSET..], &mut info, true)
+                .is_ok()
+            {
+                infos.push(info);
+                return Ok(infos);
+            }
+        }
+        if infos.is_empty() && !continued {
+            // the length may be written by another syscall and not captured by ebpf
+            let mut info = DnsInfo::default();
+            if self.decode_payload(data, &mut info, truncated).is_ok() {
+                return Ok(vec![info]);
+            }
+        }
+        if let Some(e) = error {
+            return Err(e);
+        }
+        if !truncated && reassemble && rest.len() < DNS_TCP_MAX_MESSAGE_SIZE {
+            self.tcp_buffers[index] = rest.to_vec();
+        }
+        if infos.is_empty() {
+            let err_msg = format!("dns tcp message incomplete:{}", rest.len());
+            return Err(Error::DNSLogParseFailed(err_msg));
+        }
+        Ok(infos)
+    }
+
+    fn parse(
+        &mut self,
+        payload: &[u8],
+        param: &ParseParam,
+        reassemble: bool,
+    ) -> Result<Vec<DnsInfo>> {
         let proto = param.l4_protocol;
         if self.perf_stats.is_none() && param.parse_perf {
             self.perf_stats = Some(L7PerfStats::default())
         };
+        self.max_answer_records = param
+            .parse_config
+            .map(|c| c.dns_answer_records.max_records)
+            .unwrap_or(DnsAnswerRecords::default().max_records);
         match proto {
-            IpProtocol::UDP => self.decode_payload(payload, info),
-            IpProtocol::TCP => {
-                if payload.len() <= DNS_TCP_PAYLOAD_OFFSET {
-                    let err_msg = format!("dns payload length error:{}", payload.len());
-                    return Err(Error::DNSLogParseFailed(err_msg));
-                }
-
-                let size = read_u16_be(payload) as usize;
-                if size != payload[DNS_TCP_PAYLOAD_OFFSET..].len() {
-                    self.decode_payload(payload, info)
-                } else {
-                    self.decode_payload(&payload[DNS_TCP_PAYLOAD_OFFSET..], info)
-                        .or_else(|_| {
-                            self.reset();
-                            self.decode_payload(payload, info)
-                        })
-                }
+            IpProtocol::UDP => {
+                let ports = [param.port_src, param.port_dst];
+                let mut info = DnsInfo {
+                    multicast_dns: if ports.contains(&MDNS_PORT) {
+                        Some(MULTICAST_DNS_MDNS)
+                    } else if ports.contains(&LLMNR_PORT) {
+                        Some(MULTICAST_DNS_LLMNR)
+                    } else {
+                        None
+                    },
+                    ..Default::default()
+                };
+                self.decode_payload(payload, &mut info, false)?;
+                Ok(vec![info])
             }
+            IpProtocol::TCP => match param.is_tls().then(|| doh::dns_message(payload)).flatten() {
+                Some(message) => {
+                    let mut info = DnsInfo {
+                        encrypted_dns: Some(ENCRYPTED_DNS_DOH),
+                        ..Default::default()
+                    };
+                    let truncated = param.buf_size > 0 && payload.len() >= param.buf_size as usize;
+                    self.decode_payload(&message, &mut info, truncated)?;
+                    Ok(vec![info])
+                }
+                None => self.parse_tcp(payload, param, reassemble),
+            },
             _ => {
                 let err_msg = format!("dns payload length error:{}", payload.len());
                 Err(Error::DNSLogParseFailed(err_msg))
@@ -502,7 +846,13 @@ mod tests {
     use super::*;
 
     use crate::{
-        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        common::{
+            ebpf::EbpfType,
+            flow::PacketDirection,
+            l7_protocol_log::{EbpfParam, L7PerfCache},
+            MetaPacket,
+        },
+        config::handler::LogParserConfig,
         flow_generator::L7_RRT_CACHE_CAPACITY,
         utils::test::Capture,
     };
@@ -573,6 +923,263 @@ mod tests {
         }
     }
 
+    fn dns_message(id: u16, response: bool) -> Vec<u8> {
+        let mut m = id.to_be_bytes().to_vec();
+        if response {
+            m.extend_from_slice(&[0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0]);
+        } else {
+            m.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        }
+        m.extend_from_slice(b"\x07example\x03com\x00\x00\x01\x00\x01");
+        if response {
+            m.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 0, 0, 1]);
+        }
+        let mut prefixed = (m.len() as u16).to_be_bytes().to_vec();
+        prefixed.extend(m);
+        prefixed
+    }
+
+    fn parse_tcp(dns: &mut DnsLog, payload: &[u8], param: &ParseParam) -> Vec<DnsInfo> {
+        match dns.parse_payload(payload, param) {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::DnsInfo(i))) => vec![i],
+            Ok(L7ParseResult::Multi(m)) => m
+                .into_iter()
+                .map(|i| match i {
+                    L7ProtocolInfo::DnsInfo(i) => i,
+                    _ => unreachable!(),
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn test_tcp_reassembly() {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        packet.lookup_key.direction = PacketDirection::ClientToServer;
+        let mut dns = DnsLog::default();
+
+        // multiple messages in one segment
+        let mut payload = dns_message(1, false);
+        payload.extend(dns_message(2, false));
+        let param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        assert!(dns.check_payload(&payload, &param));
+        let infos = parse_tcp(&mut dns, &payload, &param);
+        assert_eq!(
+            infos.iter().map(|i| i.trans_id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(infos[1].query_name, "example.com");
+
+        // message split across segments
+        packet.lookup_key.direction = PacketDirection::ServerToClient;
+        let param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        let payload = dns_message(1, true);
+        assert!(parse_tcp(&mut dns, &payload[..20], &param).is_empty());
+        let infos = parse_tcp(&mut dns, &payload[20..], &param);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].trans_id, 1);
+        assert_eq!(infos[0].answers, "10.0.0.1");
+        assert_eq!(infos[0].msg_type, LogMessageType::Response);
+
+        // length written alone, and the rest of message followed by the next one
+        let mut payload = dns_message(2, true);
+        payload.extend(dns_message(3, true));
+        assert!(parse_tcp(&mut dns, &payload[..2], &param).is_empty());
+        let infos = parse_tcp(&mut dns, &payload[2..], &param);
+        assert_eq!(
+            infos.iter().map(|i| i.trans_id).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        // segment cut off by l7_log_packet_size
+        let mut param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        let payload = dns_message(4, true);
+        param.buf_size = payload.len() as u16 - 4;
+        let infos = parse_tcp(&mut dns, &payload[..param.buf_size as usize], &param);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].trans_id, 4);
+        assert_eq!(infos[0].query_name, "example.com");
+        assert!(infos[0].answers.is_empty());
+        assert!(dns.tcp_buffers.iter().all(|b| b.is_empty()));
+    }
+
+    #[test]
+    fn test_doh() {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::TCP;
+        packet.lookup_key.direction = PacketDirection::ClientToServer;
+        let mut dns = DnsLog::default();
+
+        let mut payload =
+            b"POST /dns-query HTTP/1.1\r\nContent-Type: application/dns-message\r\n\r\n".to_vec();
+        payload.extend_from_slice(&dns_message(5, false)[DNS_TCP_PAYLOAD_OFFSET..]);
+        let mut param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        assert!(!dns.check_payload(&payload, &param));
+
+        param.ebpf_type = EbpfType::TlsUprobe;
+        param.ebpf_param = Some(EbpfParam {
+            is_tls: true,
+            is_req_end: false,
+            is_resp_end: false,
+            process_kname: String::new(),
+        });
+        assert!(dns.check_payload(&payload, &param));
+        let infos = parse_tcp(&mut dns, &payload, &param);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].trans_id, 5);
+        assert_eq!(infos[0].query_name, "example.com");
+        assert_eq!(infos[0].encrypted_dns, Some(ENCRYPTED_DNS_DOH));
+
+        // dns over tcp in the plaintext of tls
+        let infos = parse_tcp(&mut dns, &dns_message(6, false), &param);
+        assert_eq!(infos[0].encrypted_dns, Some(ENCRYPTED_DNS_DOT));
+    }
+
+    #[test]
+    fn test_answer_records() {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        packet.lookup_key.direction = PacketDirection::ServerToClient;
+        let mut dns = DnsLog::default();
+
+        // www.example.com CNAME cdn.example.net CNAME edge.example.net A 93.184.216.34
+        let mut payload = vec![0, 7, 0x87, 0x80, 0, 1, 0, 3, 0, 0, 0, 0];
+        payload.extend_from_slice(b"\x03www\x07example\x03com\x00\x00\x01\x00\x01");
+        payload.extend_from_slice(&[0xc0, 0x0c, 0, 5, 0, 1, 0, 0, 1, 44, 0, 17]);
+        payload.extend_from_slice(b"\x03cdn\x07example\x03net\x00");
+        payload.extend_from_slice(&[0xc0, 45, 0, 5, 0, 1, 0, 0, 0, 60, 0, 7]);
+        payload.extend_from_slice(b"\x04edge\xc0\x31");
+        payload.extend_from_slice(&[0xc0, 74, 0, 1, 0, 1, 0, 0, 0, 5, 0, 4, 93, 184, 216, 34]);
+
+        let param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        let info = match dns.parse_payload(&payload, &param).unwrap().unwrap_single() {
+            L7ProtocolInfo::DnsInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(info.answers, "93.184.216.34");
+        assert!(info.authoritative);
+        assert!(info.truncated);
+        assert_eq!(
+            info.records
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "www.example.com 300 CNAME cdn.example.net",
+                "cdn.example.net 60 CNAME edge.example.net",
+                "edge.example.net 5 A 93.184.216.34",
+            ]
+        );
+
+        let config = LogParserConfig {
+            dns_answer_records: DnsAnswerRecords { max_records: 2 },
+            ..Default::default()
+        };
+        let mut param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        param.set_log_parse_config(&config);
+        let info = match dns.parse_payload(&payload, &param).unwrap().unwrap_single() {
+            L7ProtocolInfo::DnsInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(info.records.len(), 2);
+        assert_eq!(info.answers, "93.184.216.34");
+
+        let mx = b"\x00\x0a\x04mail\x00";
+        assert_eq!(
+            dns.decode_record_data(mx, 0, mx.len(), DNS_TYPE_MX)
+                .as_deref(),
+            Some("10 mail")
+        );
+        let txt = b"\x03abc\x02de";
+        assert_eq!(
+            dns.decode_record_data(txt, 0, txt.len(), DNS_TYPE_TXT)
+                .as_deref(),
+            Some("abc de")
+        );
+    }
+
+    #[test]
+    fn test_multicast_dns() {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        packet.lookup_key.direction = PacketDirection::ClientToServer;
+        packet.lookup_key.src_port = MDNS_PORT;
+        packet.lookup_key.dst_port = MDNS_PORT;
+        let mut dns = DnsLog::default();
+
+        // announcement of `My Web._http._tcp.local` without question
+        let mut payload = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        payload.extend_from_slice(b"\x05_http\x04_tcp\x05local\x00");
+        payload.extend_from_slice(&[0, 12, 0x80, 1, 0, 0, 0x11, 0x94, 0, 9]);
+        payload.extend_from_slice(b"\x06My Web\xc0\x0c");
+        let param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        assert!(dns.check_payload(&payload, &param));
+        let info = match dns.parse_payload(&payload, &param).unwrap().unwrap_single() {
+            L7ProtocolInfo::DnsInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(info.msg_type, LogMessageType::Response);
+        assert_eq!(info.multicast_dns, Some(MULTICAST_DNS_MDNS));
+        assert_eq!(info.query_name, "_http._tcp.local");
+        assert_eq!(info.answers, "My Web._http._tcp.local");
+        assert_eq!(info.service_type.as_deref(), Some("_http._tcp"));
+
+        packet.lookup_key.src_port = 50000;
+        packet.lookup_key.dst_port = LLMNR_PORT;
+        let mut payload = vec![0x12, 0x34, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        payload.extend_from_slice(b"\x04host\x00\x00\x01\x00\x01");
+        let param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        assert!(dns.check_payload(&payload, &param));
+        let info = match dns.parse_payload(&payload, &param).unwrap().unwrap_single() {
+            L7ProtocolInfo::DnsInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(info.multicast_dns, Some(MULTICAST_DNS_LLMNR));
+        assert_eq!(info.query_name, "host");
+        assert_eq!(info.service_type, None);
+
+        assert_eq!(
+            service_type("My Printer._ipp._tcp.local"),
+            Some("_ipp._tcp")
+        );
+        assert_eq!(
+            service_type("_printer._sub._http._tcp.local"),
+            Some("_http._tcp")
+        );
+        assert_eq!(service_type("www.example.com"), None);
+    }
+
+    #[test]
+    fn test_failure_counters() {
+        let mut dns = DnsLog {
+            perf_stats: Some(L7PerfStats::default()),
+            ..Default::default()
+        };
+        let mut info = DnsInfo::default();
+        for code in [
+            DNS_RESPCODE_SUCCESS,
+            DNS_RESPCODE_FORMAT,
+            DNS_RESPCODE_SERVFAIL,
+            DNS_RESPCODE_NXDOMAIN,
+            DNS_RESPCODE_NXDOMAIN,
+            DNS_RESPCODE_REFUSED,
+        ] {
+            dns.set_status(code, &mut info);
+        }
+        let stats = dns.perf_stats.unwrap();
+        assert_eq!(stats.err_client_count, 3);
+        assert_eq!(stats.err_server_count, 2);
+        assert_eq!(stats.dns_nxdomain, 2);
+        assert_eq!(stats.dns_servfail, 1);
+        assert_eq!(stats.dns_refused, 1);
+    }
+
     #[test]
     fn check_perf() {
         let expected = vec![(
@@ -586,6 +1193,7 @@ mod tests {
                 rrt_count: 2,
                 rrt_sum: 181558,
                 rrt_max: 176754,
+                dns_nxdomain: 1,
                 ..Default::default()
             },
         )];
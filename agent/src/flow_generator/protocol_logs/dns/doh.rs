@@ -0,0 +1,96 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// DNS over HTTPS, reference: https://www.rfc-editor.org/rfc/rfc8484
+//
+// POST /dns-query HTTP/1.1            GET /dns-query?dns=AAABAAAB... HTTP/1.1
+// Content-Type: application/dns-message
+//
+// The dns message is the body of POST request and response, or the base64url encoded
+// `dns` parameter of GET request. Only HTTP/1.x is supported, which is usually seen
+// in the plaintext data of ebpf uprobe.
+
+use std::borrow::Cow;
+
+use super::super::http::{get_http_v1_body, is_http_v1_payload, parse_v1_headers};
+
+const CONTENT_TYPE: &str = "application/dns-message";
+const QUERY_PARAM: &str = "dns=";
+
+fn query_message(path: &str) -> Option<Vec<u8>> {
+    let (_, query) = path.split_once('?')?;
+    let value = query
+        .split('&')
+        .find_map(|param| param.strip_prefix(QUERY_PARAM))?;
+    base64::decode_config(value.trim_end_matches('='), base64::URL_SAFE_NO_PAD).ok()
+}
+
+// returns the dns message of a DoH request or response
+pub fn dns_message(payload: &[u8]) -> Option<Cow<'_, [u8]>> {
+    if !is_http_v1_payload(payload) {
+        return None;
+    }
+    let mut headers = parse_v1_headers(payload);
+    let first_line = headers.next()?;
+    if let Some(path) = first_line
+        .strip_prefix("GET ")
+        .and_then(|l| l.split_ascii_whitespace().next())
+    {
+        return query_message(path).map(Cow::Owned);
+    }
+    let is_dns_message = headers.any(|h| {
+        h.split_once(':').map_or(false, |(key, val)| {
+            key.trim().eq_ignore_ascii_case("content-type")
+                && val.trim().to_ascii_lowercase().starts_with(CONTENT_TYPE)
+        })
+    });
+    if !is_dns_message {
+        return None;
+    }
+    get_http_v1_body(payload)
+        .filter(|b| !b.is_empty())
+        .map(Cow::Borrowed)
+}
+
+pub fn is_doh(payload: &[u8]) -> bool {
+    dns_message(payload).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dns_message() {
+        let post = b"POST /dns-query HTTP/1.1\r\nHost: dns.example.com\r\nContent-Type: application/dns-message\r\nContent-Length: 4\r\n\r\n\xab\xcd\x01\x00";
+        assert_eq!(dns_message(post).as_deref(), Some(&b"\xab\xcd\x01\x00"[..]));
+
+        let get = b"GET /dns-query?ct&dns=q80BAA HTTP/1.1\r\nHost: dns.example.com\r\n\r\n";
+        assert_eq!(dns_message(get).as_deref(), Some(&b"\xab\xcd\x01\x00"[..]));
+
+        let resp =
+            b"HTTP/1.1 200 OK\r\ncontent-type: Application/DNS-Message\r\n\r\n\xab\xcd\x81\x80";
+        assert_eq!(dns_message(resp).as_deref(), Some(&b"\xab\xcd\x81\x80"[..]));
+
+        assert!(!is_doh(
+            b"GET /index.html HTTP/1.1\r\nHost: www.example.com\r\n\r\n"
+        ));
+        assert!(!is_doh(
+            b"POST /api HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{}"
+        ));
+        assert!(!is_doh(b"\xab\xcd\x01\x00\x00\x01"));
+    }
+}
@@ -128,6 +128,10 @@ impl L7ProtocolInfoInterface for FastCGIInfo {
         self.is_tls
     }
 
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+
     fn tcp_seq_offset(&self) -> u32 {
         self.seq_off
     }
@@ -202,7 +206,9 @@ impl FastCGIInfo {
 
                 config.map(|c| {
                     if c.is_trace_id(key) {
-                        if let Some(id) = HttpLog::decode_id(val, key, HttpLog::TRACE_ID) {
+                        if let Some(id) =
+                            HttpLog::decode_trace_context(c, val, key, HttpLog::TRACE_ID)
+                        {
                             self.trace_id = id;
                         }
                     }
@@ -210,7 +216,9 @@ impl FastCGIInfo {
 
                 config.map(|c| {
                     if c.is_span_id(key) {
-                        if let Some(id) = HttpLog::decode_id(val, key, HttpLog::SPAN_ID) {
+                        if let Some(id) =
+                            HttpLog::decode_trace_context(c, val, key, HttpLog::SPAN_ID)
+                        {
                             self.span_id = id;
                         }
                     }
@@ -0,0 +1,375 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use log::warn;
+use public::l7_protocol::L7Protocol;
+use regex::Regex;
+use serde_json::Value;
+
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog};
+use crate::config::FieldExtractionRule;
+
+const ATTRIBUTE_PREFIX: &str = "attribute.";
+
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+enum Extractor {
+    Regex(Regex),
+    JsonPath(Vec<PathSegment>),
+    Bytes { offset: usize, length: usize },
+}
+
+struct Rule {
+    condition: Option<(String, Regex)>,
+    source: String,
+    extractor: Extractor,
+    target: String,
+}
+
+// parse json path like $.user.id, $.items[0].name or $['user']['id']
+fn parse_json_path(path: &str) -> Option<Vec<PathSegment>> {
+    let mut path = path.strip_prefix('$')?;
+    let mut segments = vec![];
+    while !path.is_empty() {
+        if let Some(p) = path.strip_prefix('.') {
+            let end = p.find(|c| c == '.' || c == '[').unwrap_or(p.len());
+            if end == 0 {
+                return None;
+            }
+            segments.push(PathSegment::Key(p[..end].to_owned()));
+            path = &p[end..];
+        } else if let Some(p) = path.strip_prefix('[') {
+            let end = p.find(']')?;
+            let index = p[..end].trim();
+            if let Some(key) = index
+                .strip_prefix('\'')
+                .and_then(|k| k.strip_suffix('\''))
+                .or_else(|| index.strip_prefix('"').and_then(|k| k.strip_suffix('"')))
+            {
+                segments.push(PathSegment::Key(key.to_owned()));
+            } else {
+                segments.push(PathSegment::Index(index.parse().ok()?));
+            }
+            path = &p[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    Some(segments)
+}
+
+fn field<'a>(log: &'a L7ProtocolSendLog, name: &str) -> Option<&'a str> {
+    let s = match name {
+        "req_type" => &log.req.req_type,
+        "domain" => &log.req.domain,
+        "resource" => &log.req.resource,
+        "endpoint" => &log.req.endpoint,
+        "result" => &log.resp.result,
+        "exception" => &log.resp.exception,
+        "version" => log.version.as_ref()?,
+        _ => {
+            let key = name.strip_prefix(ATTRIBUTE_PREFIX)?;
+            &log.ext_info
+                .as_ref()?
+                .attributes
+                .as_ref()?
+                .iter()
+                .find(|kv| kv.key == key)?
+                .val
+        }
+    };
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.as_str())
+    }
+}
+
+fn is_valid_field(name: &str) -> bool {
+    match name {
+        "req_type" | "domain" | "resource" | "endpoint" | "result" | "exception" | "version" => {
+            true
+        }
+        _ => name.len() > ATTRIBUTE_PREFIX.len() && name.starts_with(ATTRIBUTE_PREFIX),
+    }
+}
+
+impl Rule {
+    fn new(rule: &FieldExtractionRule) -> Result<Self, String> {
+        if rule.target.is_empty() {
+            return Err("target is empty".to_owned());
+        }
+        if !is_valid_field(&rule.source) {
+            return Err(format!("invalid source {}", rule.source));
+        }
+        let condition = if rule.match_regex.is_empty() {
+            None
+        } else {
+            if !is_valid_field(&rule.match_field) {
+                return Err(format!("invalid match-field {}", rule.match_field));
+            }
+            let re = Regex::new(&rule.match_regex).map_err(|e| e.to_string())?;
+            Some((rule.match_field.clone(), re))
+        };
+        let extractor = match rule.extractor.as_str() {
+            "regex" => Extractor::Regex(Regex::new(&rule.pattern).map_err(|e| e.to_string())?),
+            "json-path" => Extractor::JsonPath(
+                parse_json_path(&rule.pattern)
+                    .ok_or_else(|| format!("invalid json path {}", rule.pattern))?,
+            ),
+            "bytes" => Extractor::Bytes {
+                offset: rule.offset,
+                length: rule.length,
+            },
+            e => return Err(format!("unknown extractor {}", e)),
+        };
+        Ok(Self {
+            condition,
+            source: rule.source.clone(),
+            extractor,
+            target: rule.target.clone(),
+        })
+    }
+
+    fn extract(&self, log: &L7ProtocolSendLog) -> Option<String> {
+        if let Some((name, re)) = self.condition.as_ref() {
+            if !re.is_match(field(log, name)?) {
+                return None;
+            }
+        }
+        let source = field(log, &self.source)?;
+        let value = match &self.extractor {
+            Extractor::Regex(re) => {
+                let caps = re.captures(source)?;
+                caps.get(1).or_else(|| caps.get(0))?.as_str().to_owned()
+            }
+            Extractor::JsonPath(path) => {
+                let json: Value = serde_json::from_str(source).ok()?;
+                let mut value = &json;
+                for segment in path {
+                    value = match segment {
+                        PathSegment::Key(k) => value.get(k)?,
+                        PathSegment::Index(i) => value.get(*i)?,
+                    };
+                }
+                match value {
+                    Value::Null => return None,
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                }
+            }
+            Extractor::Bytes { offset, length } => {
+                let bytes = source.as_bytes().get(*offset..)?;
+                let bytes = if *length > 0 {
+                    &bytes[..bytes.len().min(*length)]
+                } else {
+                    bytes
+                };
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+        };
+        if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+// Extracts attributes from fields of l7 logs by configured rules
+#[derive(Default)]
+pub struct FieldExtractor {
+    config: Vec<FieldExtractionRule>,
+    rules: HashMap<L7Protocol, Vec<Rule>>,
+}
+
+impl FieldExtractor {
+    pub fn new(config: &[FieldExtractionRule]) -> Self {
+        let mut rules: HashMap<L7Protocol, Vec<Rule>> = HashMap::new();
+        for r in config.iter() {
+            let protocol = L7Protocol::from(r.protocol.clone());
+            if protocol == L7Protocol::Unknown {
+                warn!(
+                    "field extraction rule of unknown protocol {} ignored",
+                    r.protocol
+                );
+                continue;
+            }
+            match Rule::new(r) {
+                Ok(rule) => rules.entry(protocol).or_default().push(rule),
+                Err(e) => warn!("invalid field extraction rule {:?}: {}", r, e),
+            }
+        }
+        Self {
+            config: config.to_vec(),
+            rules,
+        }
+    }
+
+    pub fn update(&mut self, config: &[FieldExtractionRule]) {
+        if self.config != config {
+            *self = Self::new(config);
+        }
+    }
+
+    pub fn contains(&self, protocol: L7Protocol) -> bool {
+        self.rules.contains_key(&protocol)
+    }
+
+    pub fn extract(&self, protocol: L7Protocol, log: &mut L7ProtocolSendLog) {
+        let Some(rules) = self.rules.get(&protocol) else {
+            return;
+        };
+        let attributes = rules
+            .iter()
+            .filter_map(|r| {
+                r.extract(log).map(|val| KeyVal {
+                    key: r.target.clone(),
+                    val,
+                })
+            })
+            .collect::<Vec<_>>();
+        if attributes.is_empty() {
+            return;
+        }
+        log.ext_info
+            .get_or_insert_with(ExtendedInfo::default)
+            .attributes
+            .get_or_insert_with(Vec::new)
+            .extend(attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::flow_generator::protocol_logs::pb_adapter::{L7Request, L7Response};
+
+    fn rule(
+        protocol: &str,
+        source: &str,
+        extractor: &str,
+        pattern: &str,
+        target: &str,
+    ) -> FieldExtractionRule {
+        FieldExtractionRule {
+            protocol: protocol.to_owned(),
+            source: source.to_owned(),
+            extractor: extractor.to_owned(),
+            pattern: pattern.to_owned(),
+            target: target.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn attributes(log: &L7ProtocolSendLog) -> Vec<(&str, &str)> {
+        log.ext_info
+            .as_ref()
+            .and_then(|e| e.attributes.as_ref())
+            .map(|a| {
+                a.iter()
+                    .map(|kv| (kv.key.as_str(), kv.val.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_extract() {
+        let mut bytes_rule = rule("HTTP", "attribute.x-trace", "bytes", "", "shard");
+        bytes_rule.offset = 2;
+        bytes_rule.length = 4;
+        let mut conditional = rule("HTTP", "resource", "regex", r"/v(\d+)/", "api_version");
+        conditional.match_field = "req_type".to_owned();
+        conditional.match_regex = "^POST$".to_owned();
+        let extractor = FieldExtractor::new(&[
+            rule("Kafka", "resource", "regex", r"^([a-z]+)\.", "tenant"),
+            rule(
+                "HTTP",
+                "result",
+                "json-path",
+                "$.data.user['id']",
+                "user_id",
+            ),
+            rule("HTTP", "result", "json-path", "$.items[1]", "second_item"),
+            bytes_rule,
+            conditional,
+            rule("HTTP", "resource", "json-path", "user.id", "invalid"),
+            rule("Unknown", "resource", "regex", ".*", "invalid"),
+        ]);
+        assert!(extractor.contains(L7Protocol::Kafka));
+        assert!(extractor.contains(L7Protocol::Http1));
+        assert!(!extractor.contains(L7Protocol::MySQL));
+
+        let mut log = L7ProtocolSendLog {
+            req: L7Request {
+                resource: "acme.orders".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        extractor.extract(L7Protocol::Kafka, &mut log);
+        assert_eq!(attributes(&log), vec![("tenant", "acme")]);
+
+        let mut log = L7ProtocolSendLog {
+            req: L7Request {
+                req_type: "GET".to_owned(),
+                resource: "/api/v2/users".to_owned(),
+                ..Default::default()
+            },
+            resp: L7Response {
+                result: r#"{"data":{"user":{"id":1024}},"items":["a","b"]}"#.to_owned(),
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                attributes: Some(vec![KeyVal {
+                    key: "x-trace".to_owned(),
+                    val: "0a12345678".to_owned(),
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        extractor.extract(L7Protocol::Http1, &mut log);
+        assert_eq!(
+            attributes(&log),
+            vec![
+                ("x-trace", "0a12345678"),
+                ("user_id", "1024"),
+                ("second_item", "b"),
+                ("shard", "1234"),
+            ]
+        );
+
+        log.req.req_type = "POST".to_owned();
+        log.ext_info = None;
+        extractor.extract(L7Protocol::Http1, &mut log);
+        assert_eq!(
+            attributes(&log),
+            vec![
+                ("user_id", "1024"),
+                ("second_item", "b"),
+                ("api_version", "2")
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use log::warn;
+use public::l7_protocol::L7Protocol;
+
+use super::pb_adapter::L7ProtocolSendLog;
+
+const ATTRIBUTE_PREFIX: &str = "attribute.";
+
+#[derive(Default)]
+struct Limits {
+    req_type: Option<usize>,
+    domain: Option<usize>,
+    resource: Option<usize>,
+    endpoint: Option<usize>,
+    result: Option<usize>,
+    exception: Option<usize>,
+    // attribute name -> limit, empty name for all attributes
+    attributes: HashMap<String, usize>,
+}
+
+// truncate at char boundary, returns true if truncated
+fn truncate(s: &mut String, limit: Option<usize>) -> bool {
+    let Some(mut limit) = limit else {
+        return false;
+    };
+    if s.len() <= limit {
+        return false;
+    }
+    while !s.is_char_boundary(limit) {
+        limit -= 1;
+    }
+    s.truncate(limit);
+    true
+}
+
+// Truncates fields of l7 logs by per-protocol limits
+#[derive(Default)]
+pub struct FieldLimiter {
+    config: HashMap<String, HashMap<String, usize>>,
+    limits: HashMap<L7Protocol, Limits>,
+}
+
+impl FieldLimiter {
+    pub fn new(config: &HashMap<String, HashMap<String, usize>>) -> Self {
+        let mut limits = HashMap::new();
+        for (protocol_name, fields) in config.iter() {
+            let protocol = L7Protocol::from(protocol_name.clone());
+            if protocol == L7Protocol::Unknown {
+                warn!(
+                    "l7 log field limits of unknown protocol {} ignored",
+                    protocol_name
+                );
+                continue;
+            }
+            let mut l = Limits::default();
+            for (field, limit) in fields.iter() {
+                let limit = *limit;
+                match field.as_str() {
+                    "req_type" => l.req_type = Some(limit),
+                    "domain" => l.domain = Some(limit),
+                    "resource" => l.resource = Some(limit),
+                    "endpoint" => l.endpoint = Some(limit),
+                    "result" => l.result = Some(limit),
+                    "exception" => l.exception = Some(limit),
+                    "attributes" => {
+                        l.attributes.insert(String::new(), limit);
+                    }
+                    _ => match field.strip_prefix(ATTRIBUTE_PREFIX) {
+                        Some(name) if !name.is_empty() => {
+                            l.attributes.insert(name.to_owned(), limit);
+                        }
+                        _ => warn!(
+                            "l7 log field limit of unknown field {} of {} ignored",
+                            field, protocol_name
+                        ),
+                    },
+                }
+            }
+            limits.insert(protocol, l);
+        }
+        Self {
+            config: config.clone(),
+            limits,
+        }
+    }
+
+    pub fn update(&mut self, config: &HashMap<String, HashMap<String, usize>>) {
+        if &self.config != config {
+            *self = Self::new(config);
+        }
+    }
+
+    pub fn contains(&self, protocol: L7Protocol) -> bool {
+        self.limits.contains_key(&protocol)
+    }
+
+    // returns true if any field is truncated
+    pub fn truncate(&self, protocol: L7Protocol, log: &mut L7ProtocolSendLog) -> bool {
+        let Some(l) = self.limits.get(&protocol) else {
+            return false;
+        };
+        let mut truncated = truncate(&mut log.req.req_type, l.req_type);
+        truncated |= truncate(&mut log.req.domain, l.domain);
+        truncated |= truncate(&mut log.req.resource, l.resource);
+        truncated |= truncate(&mut log.req.endpoint, l.endpoint);
+        truncated |= truncate(&mut log.resp.result, l.result);
+        truncated |= truncate(&mut log.resp.exception, l.exception);
+        if l.attributes.is_empty() {
+            return truncated;
+        }
+        let Some(attributes) = log.ext_info.as_mut().and_then(|e| e.attributes.as_mut()) else {
+            return truncated;
+        };
+        let all = l.attributes.get("").copied();
+        for kv in attributes.iter_mut() {
+            let limit = l.attributes.get(&kv.key).copied().or(all);
+            truncated |= truncate(&mut kv.val, limit);
+        }
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::flow_generator::protocol_logs::pb_adapter::{ExtendedInfo, KeyVal, L7Request};
+
+    #[test]
+    fn test_truncate() {
+        let mut config: HashMap<String, HashMap<String, usize>> = HashMap::new();
+        config.insert(
+            "MySQL".to_owned(),
+            [("resource".to_owned(), 16)].into_iter().collect(),
+        );
+        config.insert(
+            "HTTP".to_owned(),
+            [
+                ("resource".to_owned(), 7),
+                ("attributes".to_owned(), 4),
+                ("attribute.body".to_owned(), 6),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let limiter = FieldLimiter::new(&config);
+        assert!(limiter.contains(L7Protocol::MySQL));
+        assert!(!limiter.contains(L7Protocol::Kafka));
+
+        let mut log = L7ProtocolSendLog {
+            req: L7Request {
+                resource: "SELECT * FROM users WHERE id = 1".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(limiter.truncate(L7Protocol::MySQL, &mut log));
+        assert_eq!(log.req.resource, "SELECT * FROM us");
+        assert!(!limiter.truncate(L7Protocol::MySQL, &mut log));
+
+        let mut log = L7ProtocolSendLog {
+            req: L7Request {
+                // truncated at char boundary
+                resource: "/api/用户".to_owned(),
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                attributes: Some(vec![
+                    KeyVal {
+                        key: "body".to_owned(),
+                        val: "0123456789".to_owned(),
+                    },
+                    KeyVal {
+                        key: "other".to_owned(),
+                        val: "0123456789".to_owned(),
+                    },
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(limiter.truncate(L7Protocol::Http1, &mut log));
+        assert_eq!(log.req.resource, "/api/");
+        let attributes = log.ext_info.unwrap().attributes.unwrap();
+        assert_eq!(attributes[0].val, "012345");
+        assert_eq!(attributes[1].val, "0123");
+    }
+}
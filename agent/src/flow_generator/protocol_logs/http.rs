@@ -14,17 +14,26 @@
  * limitations under the License.
  */
 
+mod compression;
+mod graphql;
+mod multipart;
+mod proxy;
+
+use std::borrow::Cow;
 use std::str;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use hpack::Decoder;
+use md5::{Digest, Md5};
 use nom::AsBytes;
 use serde::Serialize;
 
 use super::pb_adapter::{
-    ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, TraceInfo,
+    ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, MetricKeyVal, TraceInfo,
 };
 use super::value_is_default;
-use super::{consts::*, AppProtoHead, L7ResponseStatus};
+use super::{consts::*, dns, AppProtoHead, L7ResponseStatus};
 use super::{decode_new_rpc_trace_context_with_type, LogMessageType};
 
 use crate::plugin::CustomInfo;
@@ -38,12 +47,17 @@ use crate::{
         l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
         meta_packet::EbpfFlags,
     },
-    config::handler::{L7LogDynamicConfig, LogParserConfig, TraceType},
+    config::{
+        handler::{L7LogDynamicConfig, LogParserConfig, TraceType},
+        HttpBodyDecompression, HttpErrorBodyCapture, HttpIdentityRule, HttpProxyHeaderExtraction,
+    },
     flow_generator::error::{Error, Result},
+    flow_generator::pool::{pooled_string, pooled_string_lossy},
     flow_generator::protocol_logs::{decode_base64_to_string, L7ProtoRawDataType},
     utils::bytes::{read_u32_be, read_u32_le},
 };
 use cloud_platform::tingyun;
+use compression::ContentEncoding;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum Version {
@@ -160,6 +174,9 @@ pub struct HttpInfo {
 
     #[serde(rename = "request_id", skip_serializing_if = "value_is_default")]
     pub stream_id: Option<u32>,
+    // sequence of the http1 request or response in the flow, used to match pipelined requests
+    #[serde(skip)]
+    pipeline_seq: Option<u32>,
     #[serde(skip_serializing_if = "value_is_default")]
     pub version: Version,
     #[serde(skip_serializing_if = "value_is_default")]
@@ -179,10 +196,21 @@ pub struct HttpInfo {
     pub referer: Option<String>,
     #[serde(rename = "http_proxy_client", skip_serializing_if = "Option::is_none")]
     pub client_ip: Option<String>,
+    // hops of X-Forwarded-For and Forwarded, the client is the first
+    #[serde(rename = "http_forwarded_for", skip_serializing_if = "Vec::is_empty")]
+    pub forwarded_for: Vec<String>,
+    #[serde(rename = "http_real_ip", skip_serializing_if = "Option::is_none")]
+    pub real_ip: Option<String>,
+    #[serde(rename = "http_via", skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
     #[serde(skip_serializing_if = "value_is_default")]
     pub x_request_id_0: String,
     #[serde(skip_serializing_if = "value_is_default")]
     pub x_request_id_1: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
 
     #[serde(rename = "request_length", skip_serializing_if = "Option::is_none")]
     pub req_content_length: Option<u32>,
@@ -194,6 +222,9 @@ pub struct HttpInfo {
     pub status_code: u16,
     #[serde(rename = "response_status")]
     pub status: L7ResponseStatus,
+    // leading bytes of the response body, captured only for error responses
+    #[serde(rename = "response_body", skip_serializing_if = "Option::is_none")]
+    pub resp_body: Option<String>,
 
     endpoint: Option<String>,
     // set by wasm plugin
@@ -202,6 +233,15 @@ pub struct HttpInfo {
 
     #[serde(skip)]
     attributes: Vec<KeyVal>,
+    #[serde(skip)]
+    metrics: Vec<MetricKeyVal>,
+
+    #[serde(skip)]
+    is_event_stream: bool,
+    #[serde(skip)]
+    multipart_boundary: Option<Vec<u8>>,
+    #[serde(skip)]
+    content_encoding: Option<ContentEncoding>,
 }
 
 impl HttpInfo {
@@ -257,7 +297,7 @@ impl HttpInfo {
 
 impl L7ProtocolInfoInterface for HttpInfo {
     fn session_id(&self) -> Option<u32> {
-        self.stream_id
+        self.stream_id.or(self.pipeline_seq)
     }
 
     fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
@@ -279,6 +319,22 @@ impl L7ProtocolInfoInterface for HttpInfo {
         self.is_tls
     }
 
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+
+    fn request_len(&self) -> Option<u32> {
+        self.req_content_length
+    }
+
+    fn response_len(&self) -> Option<u32> {
+        self.resp_content_length
+    }
+
+    fn response_code(&self) -> Option<String> {
+        (self.status_code != 0).then(|| self.status_code.to_string())
+    }
+
     fn skip_send(&self) -> bool {
         // filter the empty data from go http uprobe.
         self.raw_data_type == L7ProtoRawDataType::GoHttp2Uprobe && self.is_empty()
@@ -321,6 +377,10 @@ impl HttpInfo {
                 super::swap_if!(self, user_agent, is_none, other);
                 super::swap_if!(self, referer, is_none, other);
                 super::swap_if!(self, endpoint, is_none, other);
+                super::swap_if!(self, client_ip, is_none, other);
+                super::swap_if!(self, forwarded_for, is_empty, other);
+                super::swap_if!(self, real_ip, is_none, other);
+                super::swap_if!(self, via, is_none, other);
                 // 下面用于判断是否结束
                 // ================
                 // determine whether request is end
@@ -342,6 +402,7 @@ impl HttpInfo {
 
                 super::swap_if!(self, custom_exception, is_none, other);
                 super::swap_if!(self, custom_result, is_none, other);
+                super::swap_if!(self, resp_body, is_none, other);
 
                 if self.resp_content_length.is_none() {
                     self.resp_content_length = other.resp_content_length;
@@ -361,7 +422,10 @@ impl HttpInfo {
         super::swap_if!(self, span_id, is_empty, other);
         super::swap_if!(self, x_request_id_0, is_empty, other);
         super::swap_if!(self, x_request_id_1, is_empty, other);
+        super::swap_if!(self, user_id, is_none, other);
+        super::swap_if!(self, session_id, is_none, other);
         self.attributes.append(&mut other.attributes);
+        self.metrics.append(&mut other.metrics);
         Ok(())
     }
 
@@ -435,6 +499,43 @@ impl From<HttpInfo> for L7ProtocolSendLog {
         } else {
             EbpfFlags::NONE.bits()
         };
+        let mut attributes = f.attributes;
+        if let Some(user_id) = f.user_id {
+            attributes.push(KeyVal {
+                key: "user_id".to_string(),
+                val: user_id,
+            });
+        }
+        if let Some(session_id) = f.session_id {
+            attributes.push(KeyVal {
+                key: "session_id".to_string(),
+                val: session_id,
+            });
+        }
+        if !f.forwarded_for.is_empty() {
+            attributes.push(KeyVal {
+                key: "http_forwarded_for".to_string(),
+                val: f.forwarded_for.join(", "),
+            });
+        }
+        if let Some(real_ip) = f.real_ip {
+            attributes.push(KeyVal {
+                key: "http_real_ip".to_string(),
+                val: real_ip,
+            });
+        }
+        if let Some(via) = f.via {
+            attributes.push(KeyVal {
+                key: "http_via".to_string(),
+                val: via,
+            });
+        }
+        if let Some(body) = f.resp_body {
+            attributes.push(KeyVal {
+                key: "response_body".to_string(),
+                val: body,
+            });
+        }
 
         L7ProtocolSendLog {
             req_len: f.req_content_length,
@@ -470,10 +571,17 @@ impl From<HttpInfo> for L7ProtocolSendLog {
                 referer: f.referer,
                 rpc_service: service_name,
                 attributes: {
-                    if f.attributes.is_empty() {
+                    if attributes.is_empty() {
+                        None
+                    } else {
+                        Some(attributes)
+                    }
+                },
+                metrics: {
+                    if f.metrics.is_empty() {
                         None
                     } else {
-                        Some(f.attributes)
+                        Some(f.metrics)
                     }
                 },
                 ..Default::default()
@@ -484,6 +592,90 @@ impl From<HttpInfo> for L7ProtocolSendLog {
     }
 }
 
+// Server-Sent Events stream of http1, which responds with `Content-Type: text/event-stream`
+// and keeps sending events until the connection is closed.
+#[derive(Default)]
+struct SseStream {
+    method: Method,
+    path: String,
+    host: String,
+    endpoint: Option<String>,
+    is_tls: bool,
+    // micro second, approximate time of the request
+    start_time: u64,
+    last_report_time: u64,
+    first_event_time: Option<u64>,
+    event_count: u32,
+    bytes: u64,
+    ends_with_newline: bool,
+}
+
+impl SseStream {
+    // events are separated by a blank line
+    fn on_data(&mut self, data: &[u8], time: u64) {
+        if data.is_empty() {
+            return;
+        }
+        self.bytes += data.len() as u64;
+        let mut count = data.windows(2).filter(|w| w == b"\n\n").count()
+            + data.windows(4).filter(|w| w == b"\r\n\r\n").count();
+        // the blank line may be split into two segments
+        if self.ends_with_newline && (data[0] == b'\n' || data.starts_with(b"\r\n")) {
+            count += 1;
+        }
+        self.ends_with_newline = data.ends_with(b"\n");
+        if count > 0 {
+            self.event_count += count as u32;
+            if self.first_event_time.is_none() {
+                self.first_event_time = Some(time);
+            }
+        }
+    }
+
+    fn report(&mut self, time: u64) -> HttpInfo {
+        let mut info = HttpInfo {
+            proto: L7Protocol::Http1,
+            msg_type: LogMessageType::Session,
+            version: Version::V1_1,
+            method: self.method,
+            path: self.path.clone(),
+            host: self.host.clone(),
+            endpoint: self.endpoint.clone(),
+            is_tls: self.is_tls,
+            status_code: 200,
+            status: L7ResponseStatus::Ok,
+            is_event_stream: true,
+            ..Default::default()
+        };
+        info.metrics.push(MetricKeyVal {
+            key: "sse_event_count".to_string(),
+            val: self.event_count as f32,
+        });
+        info.metrics.push(MetricKeyVal {
+            key: "sse_bytes".to_string(),
+            val: self.bytes as f32,
+        });
+        if let Some(t) = self.first_event_time {
+            info.metrics.push(MetricKeyVal {
+                key: "sse_time_to_first_event".to_string(),
+                val: t.saturating_sub(self.start_time) as f32,
+            });
+        }
+        self.event_count = 0;
+        self.bytes = 0;
+        self.last_report_time = time;
+        info
+    }
+
+    // reports the events and bytes received since the last report when the stream ends
+    fn close(mut self, time: u64) -> Option<HttpInfo> {
+        if self.event_count == 0 && self.bytes == 0 {
+            return None;
+        }
+        Some(self.report(time))
+    }
+}
+
 #[derive(Default)]
 pub struct HttpLog {
     proto: L7Protocol,
@@ -491,6 +683,20 @@ pub struct HttpLog {
     perf_stats: Option<L7PerfStats>,
     http2_req_decoder: Option<Decoder<'static>>,
     http2_resp_decoder: Option<Decoder<'static>>,
+    // the last request in the flow is a graphql request, its response body will be checked for errors
+    graphql_pending: bool,
+    // method, path and host of the last http1 request, used by sse stream
+    last_request: Option<(Method, String, String)>,
+    sse_stream: Option<Box<SseStream>>,
+    // the next sequence of http1 request and response in the flow
+    req_seq: u32,
+    resp_seq: u32,
+    // the client has been seen pipelining requests in the flow
+    pipelined: bool,
+    // the sequences were resynchronized because a request arrived before the previous response
+    unanswered_dropped: bool,
+    // time of the last request or response that made progress in the pipeline, in microseconds
+    pipeline_progress_time: u64,
 }
 
 impl L7ProtocolParserInterface for HttpLog {
@@ -506,7 +712,10 @@ impl L7ProtocolParserInterface for HttpLog {
         };
         // http2 有两个版本, 现在可以直接通过proto区分解析哪个版本的协议.
         match self.proto {
-            L7Protocol::Http1 => self.http1_check_protocol(payload),
+            // DoH in the plaintext of tls is parsed by the dns parser
+            L7Protocol::Http1 => {
+                self.http1_check_protocol(payload) && !(param.is_tls() && dns::doh::is_doh(payload))
+            }
             L7Protocol::Http2 | L7Protocol::Grpc => {
                 let Some(config) = param.parse_config else {
                     return false;
@@ -531,6 +740,17 @@ impl L7ProtocolParserInterface for HttpLog {
         }
     }
 
+    fn check_response(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if param.l4_protocol != IpProtocol::TCP || self.proto != L7Protocol::Http1 {
+            return false;
+        }
+        // HTTP response line: HTTP/1.1 200 OK
+        parse_v1_headers(payload)
+            .next()
+            .map(|line| get_http_resp_info(line).is_ok())
+            .unwrap_or_default()
+    }
+
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
         let Some(config) = param.parse_config else {
             return Err(Error::NoParseConfig);
@@ -545,12 +765,26 @@ impl L7ProtocolParserInterface for HttpLog {
             self.perf_stats = Some(L7PerfStats::default())
         };
 
+        let mut stream_end = None;
         match self.proto {
             L7Protocol::Http1 => {
+                if self.sse_stream.is_some()
+                    && param.direction == PacketDirection::ServerToClient
+                    && !is_http_v1_payload(payload)
+                {
+                    return Ok(self.on_sse_data(config, payload, param));
+                }
                 self.parse_http_v1(payload, param, &mut info)?;
                 if param.parse_log {
                     self.wasm_hook(param, payload, &mut info);
                 }
+                // a new request on the connection ends the event stream of the last response
+                if info.msg_type == LogMessageType::Request {
+                    stream_end = self
+                        .sse_stream
+                        .take()
+                        .and_then(|stream| stream.close(param.time));
+                }
             }
             L7Protocol::Http2 | L7Protocol::Grpc => match param.ebpf_type {
                 EbpfType::GoHttp2Uprobe => {
@@ -573,14 +807,21 @@ impl L7ProtocolParserInterface for HttpLog {
         }
         match self.proto {
             L7Protocol::Http1 | L7Protocol::Http2 => {
-                if !config.http_endpoint_disabled && info.path.len() > 0 {
+                if !config.http_endpoint_disabled && info.path.len() > 0 && info.endpoint.is_none()
+                {
                     info.endpoint = Some(handle_endpoint(config, &info.path));
                 }
             }
             _ => {}
         }
         if param.parse_log {
-            Ok(L7ParseResult::Single(L7ProtocolInfo::HttpInfo(info)))
+            match stream_end {
+                Some(stream_end) => Ok(L7ParseResult::Multi(vec![
+                    L7ProtocolInfo::HttpInfo(stream_end),
+                    L7ProtocolInfo::HttpInfo(info),
+                ])),
+                None => Ok(L7ParseResult::Single(L7ProtocolInfo::HttpInfo(info))),
+            }
         } else {
             Ok(L7ParseResult::None)
         }
@@ -610,9 +851,24 @@ impl L7ProtocolParserInterface for HttpLog {
         new_log.perf_stats = self.perf_stats.take();
         new_log.http2_req_decoder = self.http2_req_decoder.take();
         new_log.http2_resp_decoder = self.http2_resp_decoder.take();
+        new_log.graphql_pending = self.graphql_pending;
+        new_log.last_request = self.last_request.take();
+        new_log.sse_stream = self.sse_stream.take();
+        new_log.req_seq = self.req_seq;
+        new_log.resp_seq = self.resp_seq;
+        new_log.pipelined = self.pipelined;
+        new_log.unanswered_dropped = self.unanswered_dropped;
+        new_log.pipeline_progress_time = self.pipeline_progress_time;
         *self = new_log;
     }
 
+    fn flush_logs(&mut self, time: u64) -> L7ParseResult {
+        match self.sse_stream.take().and_then(|stream| stream.close(time)) {
+            Some(info) => L7ParseResult::Single(L7ProtocolInfo::HttpInfo(info)),
+            None => L7ParseResult::None,
+        }
+    }
+
     fn perf_stats(&mut self) -> Option<L7PerfStats> {
         self.perf_stats.take()
     }
@@ -622,6 +878,9 @@ impl HttpLog {
     pub const TRACE_ID: u8 = 0;
     pub const SPAN_ID: u8 = 1;
 
+    const MAX_PIPELINED_REQUESTS: u32 = 64;
+    const PIPELINE_TIMEOUT: u64 = Duration::from_secs(30).as_micros() as u64;
+
     pub fn new_v1() -> Self {
         Self {
             proto: L7Protocol::Http1,
@@ -721,7 +980,8 @@ impl HttpLog {
         let val_offset = HTTPV2_CUSTOM_DATA_MIN_LENGTH + key_len;
         let key = &payload[HTTPV2_CUSTOM_DATA_MIN_LENGTH..val_offset];
         let val = &payload[val_offset..val_offset + val_len];
-        self.on_header(config, key, val, direction, info)?;
+        let parse_config = param.parse_config;
+        self.on_header(config, parse_config, key, val, direction, info)?;
         if key == b"content-length" {
             info.req_content_length = Some(
                 str::from_utf8(val)
@@ -771,17 +1031,12 @@ impl HttpLog {
         };
 
         if direction == PacketDirection::ServerToClient {
-            const HTTP_STATUS_CODE_CONTINUE: u16 = 100;
-            const HTTP_STATUS_CODE_PROCESSING: u16 = 102;
-            const HTTP_STATUS_CODE_EARLY_HINTS: u16 = 103;
             // HTTP响应行：HTTP/1.1 404 Not Found.
             let (version, status_code) = get_http_resp_info(first_line)?;
-            // reference https://developer.mozilla.org/en-US/docs/Web/HTTP/Status the 100 102 103 status code should be ignore
-            // because it will have the actually response after the send the full request
-            if status_code == HTTP_STATUS_CODE_CONTINUE
-                || status_code == HTTP_STATUS_CODE_PROCESSING
-                || status_code == HTTP_STATUS_CODE_EARLY_HINTS
-            {
+            // reference https://developer.mozilla.org/en-US/docs/Web/HTTP/Status the interim 1xx status code
+            // (except 101) should be ignore because it will have the actually response after the send the full
+            // request, and it must not consume a pipeline sequence
+            if Self::is_interim_status(status_code) {
                 return Err(Error::HttpHeaderParseFailed);
             }
             info.version = version;
@@ -798,13 +1053,22 @@ impl HttpLog {
             };
 
             info.method = method;
-            info.path = path.to_owned();
+            info.path = pooled_string(path);
             info.version = get_http_request_version(version)?;
 
             info.msg_type = LogMessageType::Request;
             self.perf_stats.as_mut().map(|p| p.inc_req());
         }
 
+        // must be set before cal_rrt, because the rrt cache key is calculated by session_id
+        let (seq, mismatched) = self.next_pipeline_seq(info.msg_type, param.time);
+        info.pipeline_seq = Some(seq);
+        if mismatched {
+            param.stats_counter.as_ref().map(|c| {
+                c.http_pipeline_mismatch.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+
         info.cal_rrt(param, None).map(|rrt| {
             info.rrt = rrt;
             self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
@@ -830,6 +1094,7 @@ impl HttpLog {
             let lower_key = key.to_ascii_lowercase();
             self.on_header(
                 config,
+                param.parse_config,
                 lower_key.as_bytes(),
                 value.trim().as_bytes(),
                 direction,
@@ -840,15 +1105,218 @@ impl HttpLog {
             }
         }
 
+        let parse_config = param.parse_config.as_ref().unwrap();
+        let decompression = &parse_config.http_body_decompression;
         // 当解析完所有Header仍未找到Content-Length，则认为该字段值为0
         if direction == PacketDirection::ServerToClient {
             info.resp_content_length = content_length;
+            let capture = &parse_config.http_error_body_capture;
+            let error_body_captured = Self::is_error_body_captured(capture, info.status_code);
+            if error_body_captured || self.graphql_pending {
+                let body = get_decoded_body(decompression, info.content_encoding, payload);
+                if let Some(body) = body.as_deref() {
+                    if error_body_captured {
+                        info.resp_body = Self::capture_error_body(capture, body);
+                    }
+                    if self.graphql_pending {
+                        self.on_graphql_response(body, info);
+                    }
+                }
+                self.graphql_pending = false;
+            }
+            if info.is_event_stream && !parse_config.http_sse_tracking.disabled {
+                let (method, path, host) = self.last_request.take().unwrap_or_default();
+                let endpoint = if !parse_config.http_endpoint_disabled && path.len() > 0 {
+                    Some(handle_endpoint(parse_config, &path))
+                } else {
+                    None
+                };
+                let mut stream = SseStream {
+                    method,
+                    path,
+                    host,
+                    endpoint,
+                    is_tls: param.is_tls(),
+                    start_time: param.time.saturating_sub(info.rrt),
+                    last_report_time: param.time,
+                    ..Default::default()
+                };
+                if let Some(body) = get_http_v1_body(payload) {
+                    stream.on_data(body, param.time);
+                }
+                self.sse_stream = Some(Box::new(stream));
+            }
         } else {
             info.req_content_length = content_length;
+            if !parse_config.http_sse_tracking.disabled {
+                self.last_request = Some((info.method, info.path.clone(), info.host.clone()));
+            }
+            let boundary = info.multipart_boundary.take();
+            let path = info.path.split('?').next().unwrap_or_default();
+            self.graphql_pending = info.method == Method::Post
+                && parse_config.graphql_endpoints.iter().any(|e| e == path);
+            if boundary.is_some() || self.graphql_pending {
+                let body = get_decoded_body(decompression, info.content_encoding, payload);
+                if let Some(body) = body.as_deref() {
+                    if let Some(boundary) = boundary {
+                        Self::on_multipart_request(body, &boundary, info);
+                    }
+                    if self.graphql_pending {
+                        Self::on_graphql_request(body, info);
+                    }
+                }
+            }
         }
         Ok(())
     }
 
+    fn on_sse_data(
+        &mut self,
+        config: &LogParserConfig,
+        payload: &[u8],
+        param: &ParseParam,
+    ) -> L7ParseResult {
+        let stream = self.sse_stream.as_mut().unwrap();
+        stream.on_data(payload, param.time);
+        let interval = config.http_sse_tracking.report_interval.as_micros() as u64;
+        if !param.parse_log || param.time < stream.last_report_time + interval {
+            return L7ParseResult::None;
+        }
+        L7ParseResult::Single(L7ProtocolInfo::HttpInfo(stream.report(param.time)))
+    }
+
+    // only metadata of the parts is recorded, the content is never captured
+    fn on_multipart_request(body: &[u8], boundary: &[u8], info: &mut HttpInfo) {
+        let parts = multipart::parse_parts(body, boundary);
+        if parts.is_empty() {
+            return;
+        }
+        info.metrics.push(MetricKeyVal {
+            key: "multipart_part_count".to_string(),
+            val: parts.len() as f32,
+        });
+        let filenames = parts
+            .iter()
+            .filter_map(|p| p.filename.as_deref())
+            .collect::<Vec<_>>();
+        if !filenames.is_empty() {
+            info.attributes.push(KeyVal {
+                key: "multipart_filenames".to_string(),
+                val: filenames.join(","),
+            });
+        }
+        // the size of a truncated part is suffixed with '+'
+        info.attributes.push(KeyVal {
+            key: "multipart_part_sizes".to_string(),
+            val: parts
+                .iter()
+                .map(|p| {
+                    if p.truncated {
+                        format!("{}+", p.size)
+                    } else {
+                        p.size.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+        });
+    }
+
+    fn on_graphql_request(body: &[u8], info: &mut HttpInfo) {
+        let Some(req) = graphql::parse_request(body) else {
+            return;
+        };
+        info.endpoint = Some(req.endpoint());
+        if let Some(name) = req.operation_name {
+            info.attributes.push(KeyVal {
+                key: "graphql_operation_name".to_string(),
+                val: name,
+            });
+        }
+    }
+
+    // graphql responds errors with status code 200, such as query validation failures
+    fn on_graphql_response(&mut self, body: &[u8], info: &mut HttpInfo) {
+        if info.status != L7ResponseStatus::Ok {
+            return;
+        }
+        if graphql::response_has_errors(body) {
+            self.perf_stats.as_mut().map(|p| p.inc_req_err());
+            info.status = L7ResponseStatus::ClientError;
+        }
+    }
+
+    fn is_interim_status(status_code: u16) -> bool {
+        (100..200).contains(&status_code) && status_code != 101
+    }
+
+    // Requests pipelined by http1 client are responded in the same order, so the
+    // n-th response of the flow is matched with the n-th request.
+    // Most clients do not pipeline, so until pipelining is observed, a request sent
+    // before the previous response is treated as a lost response and the sequences
+    // are resynchronized. Pipelining is observed when the response of such a request
+    // shows up after all.
+    // Returns the sequence and whether the request and response counts are mismatched,
+    // in which case the sequences are resynchronized.
+    fn next_pipeline_seq(&mut self, msg_type: LogMessageType, time: u64) -> (u32, bool) {
+        let mut mismatched = false;
+        let pending = self.req_seq.wrapping_sub(self.resp_seq);
+        let seq = if msg_type == LogMessageType::Request {
+            if pending == 0 {
+                self.unanswered_dropped = false;
+                self.pipeline_progress_time = time;
+            } else if !self.pipelined
+                || pending >= Self::MAX_PIPELINED_REQUESTS
+                || time > self.pipeline_progress_time + Self::PIPELINE_TIMEOUT
+            {
+                // the responses of the previous requests may be lost
+                self.resp_seq = self.req_seq;
+                self.unanswered_dropped = true;
+                self.pipeline_progress_time = time;
+                mismatched = true;
+            }
+            let seq = self.req_seq;
+            self.req_seq = self.req_seq.wrapping_add(1);
+            seq
+        } else {
+            if pending == 0 {
+                // response without request, the request may be lost, or the response
+                // of a request resynchronized before if the client is pipelining
+                if self.unanswered_dropped {
+                    self.pipelined = true;
+                    self.unanswered_dropped = false;
+                }
+                self.req_seq = self.req_seq.wrapping_add(1);
+                mismatched = true;
+            }
+            self.pipeline_progress_time = time;
+            let seq = self.resp_seq;
+            self.resp_seq = self.resp_seq.wrapping_add(1);
+            seq
+        };
+        (seq, mismatched)
+    }
+
+    fn is_error_body_captured(capture: &HttpErrorBodyCapture, status_code: u16) -> bool {
+        if capture.max_length == 0 {
+            return false;
+        }
+        let min_status_code = if capture.server_error_only {
+            HTTP_STATUS_SERVER_ERROR_MIN
+        } else {
+            HTTP_STATUS_CLIENT_ERROR_MIN
+        };
+        status_code >= min_status_code && status_code <= HTTP_STATUS_SERVER_ERROR_MAX
+    }
+
+    fn capture_error_body(capture: &HttpErrorBodyCapture, body: &[u8]) -> Option<String> {
+        if body.is_empty() {
+            return None;
+        }
+        let body = &body[..body.len().min(capture.max_length)];
+        Some(String::from_utf8_lossy(body).into_owned())
+    }
+
     fn has_magic(payload: &[u8]) -> bool {
         if payload.len() < HTTPV2_MAGIC_LENGTH {
             return false;
@@ -932,8 +1400,9 @@ impl HttpLog {
                 }
                 let header_list = parse_rst.unwrap();
 
+                let parse_config = param.parse_config;
                 for (key, val) in header_list.iter() {
-                    self.on_header(config, key, val, direction, info)?;
+                    self.on_header(config, parse_config, key, val, direction, info)?;
                     if key == b"content-length" {
                         content_length = Some(
                             str::from_utf8(val)
@@ -1004,7 +1473,7 @@ impl HttpLog {
             if info.stream_id.is_none() {
                 info.stream_id = Some(httpv2_header.stream_id);
             }
-            info.cal_rrt(param, None).map(|rrt| {
+            info.cal_rrt_by_session_id(param, None).map(|rrt| {
                 info.rrt = rrt;
                 self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
             });
@@ -1016,6 +1485,7 @@ impl HttpLog {
     fn on_header(
         &mut self,
         config: &L7LogDynamicConfig,
+        parse_config: Option<&LogParserConfig>,
         key: &[u8],
         val: &[u8],
         direction: PacketDirection,
@@ -1040,15 +1510,20 @@ impl HttpLog {
                 info.status_code = code;
                 self.set_status(code, info);
             }
-            "host" | ":authority" => info.host = String::from_utf8_lossy(val).into_owned(),
-            ":path" => info.path = String::from_utf8_lossy(val).into_owned(),
+            "host" | ":authority" => info.host = pooled_string_lossy(val),
+            ":path" => info.path = pooled_string_lossy(val),
             "content-type" => {
                 // change to grpc protocol
                 if val.starts_with(b"application/grpc") {
                     self.proto = L7Protocol::Grpc;
                     info.proto = L7Protocol::Grpc;
+                } else if val.starts_with(b"text/event-stream") {
+                    info.is_event_stream = true;
+                } else if val.starts_with(b"multipart/form-data") {
+                    info.multipart_boundary = multipart::get_boundary(val).map(|b| b.to_vec());
                 }
             }
+            "content-encoding" => info.content_encoding = ContentEncoding::from_header(val),
             "user-agent" => info.user_agent = Some(String::from_utf8_lossy(val).into_owned()),
             "referer" => info.referer = Some(String::from_utf8_lossy(val).into_owned()),
             _ => {}
@@ -1064,12 +1539,12 @@ impl HttpLog {
         };
 
         if config.is_trace_id(key) {
-            if let Some(id) = Self::decode_id(val, key, Self::TRACE_ID) {
+            if let Some(id) = Self::decode_trace_context(config, val, key, Self::TRACE_ID) {
                 info.trace_id = id;
             }
         }
         if config.is_span_id(key) {
-            if let Some(id) = Self::decode_id(val, key, Self::SPAN_ID) {
+            if let Some(id) = Self::decode_trace_context(config, val, key, Self::SPAN_ID) {
                 info.span_id = id;
             }
         }
@@ -1080,12 +1555,121 @@ impl HttpLog {
                 info.x_request_id_1 = val.to_owned();
             }
         }
-        if direction == PacketDirection::ClientToServer && key == &config.proxy_client {
-            info.client_ip = Some(val.to_owned());
+        let Some(parse_config) = parse_config else {
+            if direction == PacketDirection::ClientToServer && key == &config.proxy_client {
+                info.client_ip = Some(val.to_owned());
+            }
+            return Ok(());
+        };
+        if direction == PacketDirection::ClientToServer {
+            let proxy = &parse_config.http_proxy_header_extraction;
+            if key == &config.proxy_client && !proxy.first_hop_as_client {
+                info.client_ip = Some(val.to_owned());
+            }
+            if !proxy.disabled {
+                Self::on_proxy_header(proxy, key, val, info);
+            }
+        }
+        let rules = match direction {
+            PacketDirection::ClientToServer => &parse_config.http_header_extraction.request_headers,
+            PacketDirection::ServerToClient => {
+                &parse_config.http_header_extraction.response_headers
+            }
+        };
+        for rule in rules
+            .iter()
+            .filter(|r| r.header_name.eq_ignore_ascii_case(key))
+        {
+            let mut val = val;
+            if rule.max_length > 0 && val.len() > rule.max_length {
+                let mut end = rule.max_length;
+                while !val.is_char_boundary(end) {
+                    end -= 1;
+                }
+                val = &val[..end];
+            }
+            info.attributes.push(KeyVal {
+                key: if rule.rename.is_empty() {
+                    key.to_owned()
+                } else {
+                    rule.rename.clone()
+                },
+                val: val.to_owned(),
+            });
+        }
+        let identity = &parse_config.http_identity_extraction;
+        if info.user_id.is_none() {
+            info.user_id = Self::extract_identity(&identity.user_id, key, val);
+        }
+        if info.session_id.is_none() {
+            info.session_id = Self::extract_identity(&identity.session_id, key, val);
         }
         Ok(())
     }
 
+    fn on_proxy_header(
+        config: &HttpProxyHeaderExtraction,
+        key: &str,
+        val: &str,
+        info: &mut HttpInfo,
+    ) {
+        let hops = match key {
+            "x-forwarded-for" => proxy::parse_x_forwarded_for(val).collect::<Vec<_>>(),
+            "forwarded" => proxy::parse_forwarded(val).collect(),
+            "x-real-ip" => {
+                info.real_ip = Some(val.trim().to_owned());
+                vec![]
+            }
+            "via" => {
+                // multiple via headers are combined
+                match info.via.as_mut() {
+                    Some(via) => {
+                        via.push_str(", ");
+                        via.push_str(val);
+                    }
+                    None => info.via = Some(val.to_owned()),
+                }
+                return;
+            }
+            _ => return,
+        };
+        let remaining = proxy::MAX_HOPS.saturating_sub(info.forwarded_for.len());
+        info.forwarded_for
+            .extend(hops.into_iter().take(remaining).map(|h| h.to_owned()));
+        if config.first_hop_as_client {
+            if let Some(ip) = proxy::first_ip(&info.forwarded_for).or(info.real_ip.as_ref()) {
+                info.client_ip = Some(ip.clone());
+            }
+        }
+    }
+
+    fn extract_identity(rule: &HttpIdentityRule, key: &str, val: &str) -> Option<String> {
+        let value = if rule
+            .header_names
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(key))
+        {
+            val
+        } else if !rule.cookie_names.is_empty() && (key == "cookie" || key == "set-cookie") {
+            // cookie: a=1; b=2
+            // set-cookie: a=1; Path=/; HttpOnly
+            val.split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(name, _)| rule.cookie_names.iter().any(|c| c == name.trim()))
+                .map(|(_, value)| value.trim().trim_matches('"'))?
+        } else {
+            return None;
+        };
+        if value.is_empty() {
+            return None;
+        }
+        if rule.hashed {
+            Some(hex::encode(Md5::digest(value.as_bytes())))
+        } else {
+            Some(value.to_owned())
+        }
+    }
+
     // uber-trace-id: TRACEID:SPANID:PARENTSPANID:FLAGS
     // 使用':'分隔，第一个字段为TRACEID，第三个字段为SPANID
     fn decode_uber_id(value: &str, id_type: u8) -> Option<String> {
@@ -1165,6 +1749,20 @@ impl HttpLog {
         tingyun::decode_trace_id(value)
     }
 
+    // decode id with custom trace context format if configured, otherwise by trace type
+    pub fn decode_trace_context(
+        config: &L7LogDynamicConfig,
+        payload: &str,
+        trace_key: &str,
+        id_type: u8,
+    ) -> Option<String> {
+        match config.custom_trace_context(trace_key) {
+            Some(c) if id_type == Self::TRACE_ID => c.decode_trace_id(payload),
+            Some(c) => c.decode_span_id(payload),
+            None => Self::decode_id(payload, trace_key, id_type),
+        }
+    }
+
     pub fn decode_id(payload: &str, trace_key: &str, id_type: u8) -> Option<String> {
         let trace_type = TraceType::from(trace_key);
         match trace_type {
@@ -1408,12 +2006,41 @@ pub fn parse_v1_headers(payload: &[u8]) -> V1HeaderIterator<'_> {
     V1HeaderIterator(payload)
 }
 
+// the body starts after the empty line that terminates the headers
+pub fn get_http_v1_body(payload: &[u8]) -> Option<&[u8]> {
+    const HEADER_END: &[u8] = b"\r\n\r\n";
+    payload
+        .windows(HEADER_END.len())
+        .position(|w| w == HEADER_END)
+        .map(|i| &payload[i + HEADER_END.len()..])
+}
+
+// The body is decompressed according to Content-Encoding, returns None if the body
+// is compressed but decompression is disabled
+fn get_decoded_body<'a>(
+    config: &HttpBodyDecompression,
+    encoding: Option<ContentEncoding>,
+    payload: &'a [u8],
+) -> Option<Cow<'a, [u8]>> {
+    let body = get_http_v1_body(payload)?;
+    match encoding {
+        None => Some(Cow::Borrowed(body)),
+        Some(_) if config.max_length == 0 => None,
+        Some(e) => Some(Cow::Owned(compression::decompress(
+            e,
+            body,
+            config.max_length,
+        ))),
+    }
+}
+
 pub fn handle_endpoint(config: &LogParserConfig, path: &String) -> String {
     let keep_segments = config.http_endpoint_trie.find_matching_rule(path);
     if keep_segments <= 0 {
         return "".to_string();
     }
     let output = path.split('?').next().unwrap();
+    let output = config.http_path_normalizer.normalize(output);
     let cleaned_output = output.split('/').collect::<Vec<&str>>();
     let mut start = 0;
     let mut end = 0;
@@ -1441,7 +2068,8 @@ pub fn handle_endpoint(config: &LogParserConfig, path: &String) -> String {
 #[cfg(test)]
 mod tests {
     use crate::config::{
-        handler::LogParserConfig, HttpEndpointExtraction, HttpEndpointTrie, MatchRule,
+        handler::LogParserConfig, HttpEndpointExtraction, HttpEndpointTrie, HttpHeaderRule,
+        HttpPathNormalization, HttpPathNormalizer, MatchRule,
     };
     use crate::flow_generator::L7_RRT_CACHE_CAPACITY;
     use crate::utils::test::Capture;
@@ -1482,6 +2110,7 @@ mod tests {
             vec![],
             vec![TraceType::Sw8],
             vec![TraceType::Sw8],
+            vec![],
         );
         let parse_config = &LogParserConfig {
             l7_log_collect_nps_threshold: 10,
@@ -1705,6 +2334,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_response() {
+        let capture = Capture::load_pcap(Path::new(FILE_DIR).join("httpv1.pcap"), Some(1500));
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let packets = capture.as_meta_packets();
+        let mut http1 = HttpLog::new_v1();
+        let mut checked = vec![];
+        for packet in packets.iter() {
+            let Some(payload) = packet.get_l4_payload() else {
+                continue;
+            };
+            let param = ParseParam::new(packet, log_cache.clone(), true, true);
+            checked.push((
+                http1.check_payload(payload, &param),
+                http1.check_response(payload, &param),
+            ));
+        }
+        // the request is not a response and vice versa
+        assert_eq!(checked, vec![(true, false), (false, true)]);
+    }
+
     #[test]
     fn get_http_v1_header_from_payload() {
         let testcases = vec![
@@ -1731,6 +2381,261 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_capture_error_body() {
+        let body = b"{\"error\":\"db timeout\"}";
+        let mut capture = HttpErrorBodyCapture::default();
+        assert!(!HttpLog::is_error_body_captured(&capture, 500));
+
+        capture.max_length = 8;
+        assert!(HttpLog::is_error_body_captured(&capture, 500));
+        assert!(HttpLog::is_error_body_captured(&capture, 404));
+        assert!(!HttpLog::is_error_body_captured(&capture, 200));
+        assert_eq!(
+            HttpLog::capture_error_body(&capture, body),
+            Some("{\"error\"".to_string())
+        );
+
+        capture.server_error_only = true;
+        assert!(!HttpLog::is_error_body_captured(&capture, 404));
+
+        capture.max_length = 1024;
+        assert_eq!(
+            HttpLog::capture_error_body(&capture, body),
+            Some("{\"error\":\"db timeout\"}".to_string())
+        );
+        assert_eq!(HttpLog::capture_error_body(&capture, b""), None);
+    }
+
+    #[test]
+    fn test_decoded_body() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let body = b"{\"errors\":[{\"message\":\"unknown field\"}]}";
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(body).unwrap();
+        let mut payload = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\n\r\n".to_vec();
+        payload.extend_from_slice(&e.finish().unwrap());
+
+        let mut config = HttpBodyDecompression::default();
+        assert_eq!(
+            get_decoded_body(&config, Some(ContentEncoding::Gzip), &payload).as_deref(),
+            Some(&body[..])
+        );
+        config.max_length = 0;
+        assert_eq!(
+            get_decoded_body(&config, Some(ContentEncoding::Gzip), &payload),
+            None
+        );
+        assert_eq!(
+            get_decoded_body(&config, None, b"HTTP/1.1 200 OK\r\n\r\nabc").as_deref(),
+            Some(&b"abc"[..])
+        );
+    }
+
+    #[test]
+    fn test_sse_stream() {
+        let mut stream = SseStream {
+            start_time: 1000,
+            ..Default::default()
+        };
+        stream.on_data(b"", 1500);
+        assert_eq!(stream.first_event_time, None);
+        stream.on_data(b"event: ping\ndata: 1\n\ndata: 2\n", 2000);
+        assert_eq!(stream.event_count, 1);
+        stream.on_data(b"\ndata: 3\r\n\r\n", 3000);
+        assert_eq!(stream.event_count, 3);
+        assert_eq!(stream.bytes, 41);
+
+        let info = stream.report(4000);
+        assert_eq!(info.msg_type, LogMessageType::Session);
+        assert_eq!(
+            info.metrics,
+            vec![
+                MetricKeyVal {
+                    key: "sse_event_count".to_string(),
+                    val: 3.0,
+                },
+                MetricKeyVal {
+                    key: "sse_bytes".to_string(),
+                    val: 41.0,
+                },
+                MetricKeyVal {
+                    key: "sse_time_to_first_event".to_string(),
+                    val: 1000.0,
+                },
+            ]
+        );
+        assert_eq!(stream.event_count, 0);
+        assert_eq!(stream.last_report_time, 4000);
+
+        // the last interval is reported when the stream ends
+        stream.on_data(b"data: 4\n\n", 5000);
+        let info = stream.close(6000).unwrap();
+        assert_eq!(info.metrics[0].val, 1.0);
+        assert_eq!(info.metrics[1].val, 9.0);
+        assert!(SseStream::default().close(7000).is_none());
+    }
+
+    #[test]
+    fn test_extract_identity() {
+        let mut rule = HttpIdentityRule {
+            header_names: vec!["X-User-Id".to_string()],
+            cookie_names: vec!["JSESSIONID".to_string()],
+            hashed: false,
+        };
+        assert_eq!(
+            HttpLog::extract_identity(&rule, "x-user-id", "10086"),
+            Some("10086".to_string())
+        );
+        assert_eq!(
+            HttpLog::extract_identity(&rule, "cookie", "theme=dark; JSESSIONID=\"abc123\""),
+            Some("abc123".to_string())
+        );
+        assert_eq!(
+            HttpLog::extract_identity(&rule, "set-cookie", "JSESSIONID=xyz; Path=/; HttpOnly"),
+            Some("xyz".to_string())
+        );
+        assert_eq!(
+            HttpLog::extract_identity(&rule, "cookie", "jsessionid=abc"),
+            None
+        );
+        assert_eq!(HttpLog::extract_identity(&rule, "x-user-id", ""), None);
+        assert_eq!(HttpLog::extract_identity(&rule, "user-agent", "curl"), None);
+
+        rule.hashed = true;
+        assert_eq!(
+            HttpLog::extract_identity(&rule, "x-user-id", "10086"),
+            Some("6412121cbb2dc2cb9e460cfee7046be2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_header_extraction() {
+        let mut parse_config = LogParserConfig::default();
+        parse_config.http_header_extraction.request_headers = vec![
+            HttpHeaderRule {
+                header_name: "X-Tenant".to_string(),
+                rename: "tenant".to_string(),
+                max_length: 3,
+            },
+            HttpHeaderRule {
+                header_name: "x-region".to_string(),
+                ..Default::default()
+            },
+        ];
+        let dynamic_config = L7LogDynamicConfig::default();
+        let mut http = HttpLog::new_v1();
+        let mut info = HttpInfo::default();
+        for (key, val, direction) in [
+            ("x-tenant", "abcdef", PacketDirection::ClientToServer),
+            ("x-region", "cn-north", PacketDirection::ClientToServer),
+            ("x-region", "cn-south", PacketDirection::ServerToClient),
+            ("x-zone", "a", PacketDirection::ClientToServer),
+        ] {
+            http.on_header(
+                &dynamic_config,
+                Some(&parse_config),
+                key.as_bytes(),
+                val.as_bytes(),
+                direction,
+                &mut info,
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            info.attributes,
+            vec![
+                KeyVal {
+                    key: "tenant".to_string(),
+                    val: "abc".to_string(),
+                },
+                KeyVal {
+                    key: "x-region".to_string(),
+                    val: "cn-north".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_proxy_headers() {
+        let mut config = HttpProxyHeaderExtraction::default();
+        let mut info = HttpInfo::default();
+        HttpLog::on_proxy_header(&config, "x-real-ip", " 198.51.100.2", &mut info);
+        HttpLog::on_proxy_header(&config, "x-forwarded-for", "unknown, 10.0.0.1", &mut info);
+        HttpLog::on_proxy_header(&config, "via", "1.1 cdn", &mut info);
+        HttpLog::on_proxy_header(&config, "via", "1.0 lb", &mut info);
+        assert_eq!(info.forwarded_for, vec!["unknown", "10.0.0.1"]);
+        assert_eq!(info.real_ip.as_deref(), Some("198.51.100.2"));
+        assert_eq!(info.via.as_deref(), Some("1.1 cdn, 1.0 lb"));
+        assert_eq!(info.client_ip, None);
+
+        config.first_hop_as_client = true;
+        let mut info = HttpInfo::default();
+        HttpLog::on_proxy_header(&config, "x-real-ip", "198.51.100.2", &mut info);
+        assert_eq!(info.client_ip.as_deref(), Some("198.51.100.2"));
+        HttpLog::on_proxy_header(
+            &config,
+            "forwarded",
+            "for=\"[2001:db8::17]:4711\";proto=https",
+            &mut info,
+        );
+        assert_eq!(info.client_ip.as_deref(), Some("2001:db8::17"));
+
+        let mut info = HttpInfo::default();
+        let val = vec!["10.0.0.1"; 20].join(",");
+        HttpLog::on_proxy_header(&config, "x-forwarded-for", &val, &mut info);
+        assert_eq!(info.forwarded_for.len(), proxy::MAX_HOPS);
+    }
+
+    #[test]
+    fn test_pipeline_seq() {
+        let mut http = HttpLog::new_v1();
+        let (req, resp) = (LogMessageType::Request, LogMessageType::Response);
+        assert_eq!(http.next_pipeline_seq(req, 0), (0, false));
+        assert_eq!(http.next_pipeline_seq(resp, 0), (0, false));
+        // the response of request 1 is lost, request 2 resynchronizes the sequences
+        assert_eq!(http.next_pipeline_seq(req, 0), (1, false));
+        assert_eq!(http.next_pipeline_seq(req, 0), (2, true));
+        assert_eq!(http.next_pipeline_seq(resp, 0), (2, false));
+        assert_eq!(http.next_pipeline_seq(req, 0), (3, false));
+        assert_eq!(http.next_pipeline_seq(resp, 0), (3, false));
+        // interim responses do not consume a sequence
+        assert!(HttpLog::is_interim_status(100));
+        assert!(HttpLog::is_interim_status(103));
+        assert!(!HttpLog::is_interim_status(101));
+        assert!(!HttpLog::is_interim_status(200));
+        // response without request
+        assert_eq!(http.next_pipeline_seq(resp, 0), (4, true));
+        // the response of the resynchronized request shows up, the client is pipelining
+        assert_eq!(http.next_pipeline_seq(req, 0), (5, false));
+        assert_eq!(http.next_pipeline_seq(req, 0), (6, true));
+        assert_eq!(http.next_pipeline_seq(resp, 0), (6, false));
+        assert_eq!(http.next_pipeline_seq(resp, 0), (7, true));
+        // pipelined requests
+        assert_eq!(http.next_pipeline_seq(req, 0), (8, false));
+        assert_eq!(http.next_pipeline_seq(req, 0), (9, false));
+        assert_eq!(http.next_pipeline_seq(req, 0), (10, false));
+        assert_eq!(http.next_pipeline_seq(resp, 0), (8, false));
+        assert_eq!(http.next_pipeline_seq(resp, 0), (9, false));
+        assert_eq!(http.next_pipeline_seq(resp, 0), (10, false));
+        // no progress in the pipeline for too long
+        let t = 1000;
+        assert_eq!(http.next_pipeline_seq(req, t), (11, false));
+        assert_eq!(http.next_pipeline_seq(req, t), (12, false));
+        let t = t + HttpLog::PIPELINE_TIMEOUT + 1;
+        assert_eq!(http.next_pipeline_seq(req, t), (13, true));
+        assert_eq!(http.next_pipeline_seq(resp, t), (13, false));
+        // too many requests without response
+        for i in 0..HttpLog::MAX_PIPELINED_REQUESTS {
+            assert_eq!(http.next_pipeline_seq(req, t), (14 + i, false));
+        }
+        assert_eq!(http.next_pipeline_seq(req, t), (78, true));
+        assert_eq!(http.next_pipeline_seq(resp, t), (78, false));
+    }
+
     #[test]
     fn check_perf() {
         let expected = vec![
@@ -1827,6 +2732,7 @@ mod tests {
                 prefix: "/api".to_string(),
                 keep_segments: 1,
             }],
+            ..Default::default()
         });
         config.http_endpoint_trie = trie;
         let path = String::from("/api/v1/users/123?query=456");
@@ -1847,6 +2753,7 @@ mod tests {
                     keep_segments: 4,
                 },
             ],
+            ..Default::default()
         });
         config.http_endpoint_trie = trie;
         let path = String::from("/api/v1/users/123?query=456");
@@ -1864,6 +2771,7 @@ mod tests {
                 prefix: "".to_string(),
                 keep_segments: 3,
             }],
+            ..Default::default()
         });
         config.http_endpoint_trie = trie;
         let path = String::from("/api/v1/users/123?query=456");
@@ -1875,10 +2783,18 @@ mod tests {
                 prefix: "/api/v1".to_string(),
                 keep_segments: 0,
             }],
+            ..Default::default()
         });
         config.http_endpoint_trie = trie;
         let path = String::from("/api/v1/users/123?query=456");
         let expected_output = "/api/v1"; // prefixes match, but the keep_segments is 0, use the default value 2 segments
         assert_eq!(handle_endpoint(&config, &path), expected_output.to_string());
+        config.http_path_normalizer = HttpPathNormalizer::from(&HttpPathNormalization {
+            enabled: true,
+            ..Default::default()
+        });
+        let path = String::from("/123/v1/users/123?query=456");
+        let expected_output = "/{id}/v1"; // the path is normalized before intercepting segments
+        assert_eq!(handle_endpoint(&config, &path), expected_output.to_string());
     }
 }
@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Read;
+
+use brotli_decompressor::Decompressor;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContentEncoding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+impl ContentEncoding {
+    // multiple encodings such as `gzip, br` are not supported
+    pub fn from_header(val: &[u8]) -> Option<Self> {
+        let val = val.trim_ascii();
+        if val.eq_ignore_ascii_case(b"gzip") || val.eq_ignore_ascii_case(b"x-gzip") {
+            Some(Self::Gzip)
+        } else if val.eq_ignore_ascii_case(b"deflate") {
+            Some(Self::Deflate)
+        } else if val.eq_ignore_ascii_case(b"br") {
+            Some(Self::Brotli)
+        } else {
+            None
+        }
+    }
+}
+
+// `deflate` should be zlib format, but some servers send raw deflate stream
+fn is_zlib(body: &[u8]) -> bool {
+    body.len() >= 2 && body[0] & 0x0f == 8 && u16::from_be_bytes([body[0], body[1]]) % 31 == 0
+}
+
+// The body is usually truncated by `l7_log_packet_size`, so the data decompressed
+// before the stream is broken is returned, at most `max_length` bytes.
+pub fn decompress(encoding: ContentEncoding, body: &[u8], max_length: usize) -> Vec<u8> {
+    let mut reader: Box<dyn Read + '_> = match encoding {
+        ContentEncoding::Gzip => Box::new(GzDecoder::new(body)),
+        ContentEncoding::Deflate if is_zlib(body) => Box::new(ZlibDecoder::new(body)),
+        ContentEncoding::Deflate => Box::new(DeflateDecoder::new(body)),
+        ContentEncoding::Brotli => Box::new(Decompressor::new(body, BROTLI_BUFFER_SIZE)),
+    };
+    let mut output = vec![0; max_length];
+    let mut n = 0;
+    while n < max_length {
+        match reader.read(&mut output[n..]) {
+            Ok(0) | Err(_) => break,
+            Ok(size) => n += size,
+        }
+    }
+    output.truncate(n);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+
+    use super::*;
+
+    const BODY: &[u8] =
+        br#"{"errors":[{"message":"Cannot query field \"name\" on type \"Query\"."}]}"#;
+
+    #[test]
+    fn test_from_header() {
+        assert_eq!(
+            ContentEncoding::from_header(b"GZIP"),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(
+            ContentEncoding::from_header(b" br"),
+            Some(ContentEncoding::Brotli)
+        );
+        assert_eq!(ContentEncoding::from_header(b"identity"), None);
+        assert_eq!(ContentEncoding::from_header(b"gzip, br"), None);
+    }
+
+    #[test]
+    fn test_decompress() {
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(BODY).unwrap();
+        let gzip = e.finish().unwrap();
+        assert_eq!(decompress(ContentEncoding::Gzip, &gzip, 1024), BODY);
+        assert_eq!(decompress(ContentEncoding::Gzip, &gzip, 10), &BODY[..10]);
+        // truncated stream
+        assert!(BODY.starts_with(&decompress(
+            ContentEncoding::Gzip,
+            &gzip[..gzip.len() - 10],
+            1024
+        )));
+
+        let mut e = ZlibEncoder::new(Vec::new(), Compression::default());
+        e.write_all(BODY).unwrap();
+        let zlib = e.finish().unwrap();
+        assert_eq!(decompress(ContentEncoding::Deflate, &zlib, 1024), BODY);
+
+        // an uncompressed meta-block of "hello" followed by an empty last meta-block
+        let br = [0x40, 0x00, 0x10, b'h', b'e', b'l', b'l', b'o', 0x03];
+        assert_eq!(decompress(ContentEncoding::Brotli, &br, 1024), b"hello");
+        assert_eq!(decompress(ContentEncoding::Brotli, &br, 3), b"hel");
+    }
+}
@@ -0,0 +1,412 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// The request body is usually truncated by `l7_log_packet_size`, so the json
+// and graphql document are scanned leniently instead of being fully decoded.
+
+#[derive(Debug, Default, PartialEq)]
+pub struct GraphqlRequest {
+    pub operation_name: Option<String>,
+    pub operation_type: &'static str,
+    pub fields: Vec<String>,
+}
+
+impl GraphqlRequest {
+    // such as: "query user,posts"
+    pub fn endpoint(&self) -> String {
+        format!("{} {}", self.operation_type, self.fields.join(","))
+    }
+}
+
+// request body: {"operationName": "GetUser", "query": "query GetUser { user(id: 1) { name } }"}
+pub fn parse_request(body: &[u8]) -> Option<GraphqlRequest> {
+    let query = json_string_value(body, "query")?;
+    let (operation_type, fields) = top_level_fields(query.as_bytes())?;
+    Some(GraphqlRequest {
+        operation_name: json_string_value(body, "operationName").filter(|s| !s.is_empty()),
+        operation_type,
+        fields,
+    })
+}
+
+// response body: {"errors": [{"message": "..."}], "data": null}
+// only the top level member counts, "errors" may also be a field name in data
+pub fn response_has_errors(body: &[u8]) -> bool {
+    let Some(offset) = json_member_offset(body, "errors") else {
+        return false;
+    };
+    let mut s = Scanner::new(&body[offset..]);
+    if s.peek() != Some(b'[') {
+        return false;
+    }
+    s.pos += 1;
+    s.skip_whitespace();
+    match s.peek() {
+        Some(b']') | None => false,
+        _ => true,
+    }
+}
+
+// returns the offset of the first non-whitespace byte of the value, only members
+// of the top level object match
+fn json_member_offset(body: &[u8], key: &str) -> Option<usize> {
+    let mut s = Scanner::new(body);
+    s.skip_whitespace();
+    if s.peek()? != b'{' {
+        return None;
+    }
+    s.pos += 1;
+    loop {
+        s.skip_whitespace();
+        let name = s.json_string()?;
+        s.skip_whitespace();
+        if s.peek()? != b':' {
+            return None;
+        }
+        s.pos += 1;
+        s.skip_whitespace();
+        s.peek()?;
+        if name == key.as_bytes() {
+            return Some(s.pos);
+        }
+        s.skip_json_value()?;
+        s.skip_whitespace();
+        if s.peek()? != b',' {
+            return None;
+        }
+        s.pos += 1;
+    }
+}
+
+// a truncated string value is returned as is
+fn json_string_value(body: &[u8], key: &str) -> Option<String> {
+    let offset = json_member_offset(body, key)?;
+    let value = &body[offset..];
+    if value[0] != b'"' {
+        return None;
+    }
+    let mut output = Vec::new();
+    let mut i = 1;
+    while i < value.len() {
+        match value[i] {
+            b'"' => break,
+            b'\\' if i + 1 < value.len() => {
+                i += 1;
+                match value[i] {
+                    b'n' => output.push(b'\n'),
+                    b'r' => output.push(b'\r'),
+                    b't' => output.push(b'\t'),
+                    b'b' => output.push(0x08),
+                    b'f' => output.push(0x0c),
+                    b'u' if i + 4 < value.len() => {
+                        let c = std::str::from_utf8(&value[i + 1..i + 5])
+                            .ok()
+                            .and_then(|h| u32::from_str_radix(h, 16).ok())
+                            .and_then(char::from_u32)
+                            .unwrap_or(char::REPLACEMENT_CHARACTER);
+                        let mut buf = [0u8; 4];
+                        output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                        i += 4;
+                    }
+                    c => output.push(c),
+                }
+            }
+            c => output.push(c),
+        }
+        i += 1;
+    }
+    Some(String::from_utf8_lossy(&output).into_owned())
+}
+
+struct Scanner<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.buf.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    // whitespace, commas and comments are insignificant in graphql
+    fn skip_ignored(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                b'#' => {
+                    while let Some(c) = self.peek() {
+                        if c == b'\n' {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                b',' => self.pos += 1,
+                c if c.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn name(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || c == b'_') {
+                break;
+            }
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return None;
+        }
+        std::str::from_utf8(&self.buf[start..self.pos]).ok()
+    }
+
+    // returns the raw bytes of a json string, escape sequences are not decoded
+    fn json_string(&mut self) -> Option<&'a [u8]> {
+        if self.peek()? != b'"' {
+            return None;
+        }
+        let start = self.pos + 1;
+        self.pos = start;
+        while let Some(c) = self.peek() {
+            match c {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return Some(&self.buf[start..self.pos - 1]);
+                }
+                _ => self.pos += 1,
+            }
+        }
+        None
+    }
+
+    // returns None if the value is truncated
+    fn skip_json_value(&mut self) -> Option<()> {
+        match self.peek()? {
+            b'{' => self.skip_block(b'{', b'}'),
+            b'[' => self.skip_block(b'[', b']'),
+            b'"' => {
+                self.json_string()?;
+            }
+            _ => {
+                while let Some(c) = self.peek() {
+                    if c == b',' || c == b'}' || c == b']' || c.is_ascii_whitespace() {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            }
+        }
+        self.peek().map(|_| ())
+    }
+
+    // skip a bracketed block including nested blocks and strings,
+    // moves to the end of buffer if the block is not closed
+    fn skip_block(&mut self, open: u8, close: u8) {
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                b'"' => {
+                    while let Some(c) = self.peek() {
+                        self.pos += 1;
+                        match c {
+                            b'\\' => self.pos += 1,
+                            b'"' => break,
+                            _ => (),
+                        }
+                    }
+                }
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn selection_set(&mut self) -> Vec<String> {
+        let mut fields: Vec<String> = vec![];
+        // skip '{'
+        self.pos += 1;
+        loop {
+            self.skip_ignored();
+            let Some(c) = self.peek() else {
+                break;
+            };
+            match c {
+                b'}' => break,
+                b'(' => self.skip_block(b'(', b')'),
+                b'{' => self.skip_block(b'{', b'}'),
+                b'@' => {
+                    self.pos += 1;
+                    let _ = self.name();
+                }
+                // fragment spread or inline fragment
+                b'.' => {
+                    while self.peek() == Some(b'.') {
+                        self.pos += 1;
+                    }
+                    self.skip_ignored();
+                    if self.name() == Some("on") {
+                        self.skip_ignored();
+                        let _ = self.name();
+                    }
+                }
+                c if c.is_ascii_alphabetic() || c == b'_' => {
+                    let Some(mut name) = self.name() else {
+                        break;
+                    };
+                    self.skip_ignored();
+                    // alias: field
+                    if self.peek() == Some(b':') {
+                        self.pos += 1;
+                        self.skip_ignored();
+                        let Some(field) = self.name() else {
+                            break;
+                        };
+                        name = field;
+                    }
+                    if !fields.iter().any(|f| f == name) {
+                        fields.push(name.to_owned());
+                    }
+                }
+                _ => self.pos += 1,
+            }
+        }
+        fields
+    }
+}
+
+// returns the operation type and the top level fields of the first operation
+fn top_level_fields(query: &[u8]) -> Option<(&'static str, Vec<String>)> {
+    let mut s = Scanner::new(query);
+    loop {
+        s.skip_ignored();
+        if s.peek()? == b'{' {
+            // query shorthand
+            let fields = s.selection_set();
+            return if fields.is_empty() {
+                None
+            } else {
+                Some(("query", fields))
+            };
+        }
+        let operation_type = match s.name()? {
+            "query" => "query",
+            "mutation" => "mutation",
+            "subscription" => "subscription",
+            "fragment" => {
+                while s.peek()? != b'{' {
+                    s.pos += 1;
+                }
+                s.skip_block(b'{', b'}');
+                continue;
+            }
+            _ => return None,
+        };
+        // skip operation name, variable definitions and directives
+        loop {
+            s.skip_ignored();
+            match s.peek()? {
+                b'{' => break,
+                b'(' => s.skip_block(b'(', b')'),
+                _ => s.pos += 1,
+            }
+        }
+        let fields = s.selection_set();
+        return if fields.is_empty() {
+            None
+        } else {
+            Some((operation_type, fields))
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let body = br#"{"operationName":"GetUser","variables":{"id":"1"},"query":"query GetUser($id: ID!) {\n  user(id: $id) { name friends { name } }\n  me: viewer @include(if: true) { id }\n  ...Extra\n}\nfragment Extra on Query { version }"}"#;
+        let req = parse_request(body).unwrap();
+        assert_eq!(req.operation_name.as_deref(), Some("GetUser"));
+        assert_eq!(req.operation_type, "query");
+        assert_eq!(req.fields, vec!["user", "viewer"]);
+        assert_eq!(req.endpoint(), "query user,viewer");
+
+        let body = br#"{"query":"mutation { createUser(input: {name: \"a}b\"}) { id } deleteUser(id: 2) }"}"#;
+        let req = parse_request(body).unwrap();
+        assert_eq!(req.operation_name, None);
+        assert_eq!(req.endpoint(), "mutation createUser,deleteUser");
+
+        let body = br#"{"query":"{ books { title } authors"#;
+        assert_eq!(
+            parse_request(body).unwrap().endpoint(),
+            "query books,authors"
+        );
+
+        // "query" and "operationName" of nested objects and string values are not top level members
+        let body = br#"{"variables":{"query":"mutation { drop }","note":"\"operationName\": \"X\""},"operationName":"Search","query":"query Search { search(q: $query) { id } }"}"#;
+        let req = parse_request(body).unwrap();
+        assert_eq!(req.operation_name.as_deref(), Some("Search"));
+        assert_eq!(req.endpoint(), "query search");
+
+        assert_eq!(parse_request(br#"{"query": null}"#), None);
+        assert_eq!(parse_request(br#"{"data": 1}"#), None);
+    }
+
+    #[test]
+    fn test_response_has_errors() {
+        assert!(response_has_errors(
+            br#"{"errors":[{"message":"Cannot query field"}],"data":null}"#
+        ));
+        assert!(response_has_errors(br#"{"data":{}, "errors" : [ {"mess"#));
+        assert!(!response_has_errors(br#"{"data":{"user":{"name":"a"}}}"#));
+        assert!(!response_has_errors(br#"{"errors":[],"data":{}}"#));
+        // "errors" of nested objects and string values are not top level members
+        assert!(!response_has_errors(
+            br#"{"data":{"errors":[{"id":1}],"note":"\"errors\": [1]"}}"#
+        ));
+        assert!(!response_has_errors(
+            br#"{"data":{"a":"}"},"x":["errors"]}"#
+        ));
+        assert!(response_has_errors(
+            br#"{"data":{"errors":[]},"extensions":{},"errors":[{"message":"denied"}]}"#
+        ));
+        // truncated before the top level errors
+        assert!(!response_has_errors(br#"{"data":{"user":{"errors":[1]"#));
+    }
+}
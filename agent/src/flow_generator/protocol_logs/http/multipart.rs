@@ -0,0 +1,170 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// multipart/form-data reference: https://www.rfc-editor.org/rfc/rfc7578
+//
+// --boundary\r\n
+// Content-Disposition: form-data; name="file"; filename="a.png"\r\n
+// Content-Type: image/png\r\n
+// \r\n
+// <content>\r\n
+// --boundary--\r\n
+
+#[derive(Debug, PartialEq)]
+pub struct Part {
+    pub filename: Option<String>,
+    pub size: usize,
+    // the part is cut off by the end of payload, size is the observed length
+    pub truncated: bool,
+}
+
+fn find(buf: &[u8], pattern: &[u8]) -> Option<usize> {
+    if pattern.is_empty() || buf.len() < pattern.len() {
+        return None;
+    }
+    buf.windows(pattern.len()).position(|w| w == pattern)
+}
+
+// value of the parameter `name` in a header such as `multipart/form-data; boundary="abc"`
+fn get_param<'a>(header: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let lower = header.to_ascii_lowercase();
+    let mut offset = 0;
+    loop {
+        let start = offset + find(&lower[offset..], name.as_bytes())?;
+        offset = start + name.len();
+        // make sure it is not the suffix of another parameter, such as `name` in `filename`
+        if start > 0 && !matches!(lower[start - 1], b';' | b' ' | b'\t') {
+            continue;
+        }
+        if lower.get(offset) != Some(&b'=') {
+            continue;
+        }
+        let value = &header[offset + 1..];
+        if value.first() == Some(&b'"') {
+            let value = &value[1..];
+            let end = value.iter().position(|c| *c == b'"').unwrap_or(value.len());
+            return Some(&value[..end]);
+        }
+        let end = value
+            .iter()
+            .position(|c| *c == b';' || *c == b'\r' || *c == b'\n')
+            .unwrap_or(value.len());
+        return Some(value[..end].trim_ascii());
+    }
+}
+
+pub fn get_boundary(content_type: &[u8]) -> Option<&[u8]> {
+    get_param(content_type, "boundary").filter(|b| !b.is_empty())
+}
+
+pub fn parse_parts(body: &[u8], boundary: &[u8]) -> Vec<Part> {
+    let mut parts = vec![];
+    let delimiter = [b"--", boundary].concat();
+    let close_delimiter = [b"\r\n--", boundary].concat();
+    let Some(mut offset) = find(body, &delimiter) else {
+        return parts;
+    };
+    loop {
+        offset += delimiter.len();
+        let rest = &body[offset..];
+        // `--boundary--` is the end of body
+        if rest.is_empty() || rest.starts_with(b"--") {
+            break;
+        }
+        let Some(line_end) = find(rest, b"\r\n") else {
+            break;
+        };
+        let part = &rest[line_end + 2..];
+        let Some(header_end) = find(part, b"\r\n\r\n") else {
+            break;
+        };
+        let filename = get_param(&part[..header_end], "filename")
+            .map(|f| String::from_utf8_lossy(f).into_owned());
+        let content = &part[header_end + 4..];
+        match find(content, &close_delimiter) {
+            Some(size) => {
+                parts.push(Part {
+                    filename,
+                    size,
+                    truncated: false,
+                });
+                // skip to the next `--boundary`
+                offset += line_end + 2 + header_end + 4 + size + 2;
+            }
+            None => {
+                parts.push(Part {
+                    filename,
+                    size: content.len(),
+                    truncated: true,
+                });
+                break;
+            }
+        }
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_boundary() {
+        assert_eq!(
+            get_boundary(b"multipart/form-data; boundary=----WebKitFormBoundary7MA4YWxk"),
+            Some(&b"----WebKitFormBoundary7MA4YWxk"[..])
+        );
+        assert_eq!(
+            get_boundary(b"multipart/form-data; Boundary=\"a b\"; charset=utf-8"),
+            Some(&b"a b"[..])
+        );
+        assert_eq!(get_boundary(b"multipart/form-data"), None);
+        assert_eq!(get_boundary(b"multipart/form-data; boundary="), None);
+    }
+
+    #[test]
+    fn test_parse_parts() {
+        let body = b"--xyz\r\nContent-Disposition: form-data; name=\"desc\"\r\n\r\nhello\r\n--xyz\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.png\"\r\nContent-Type: image/png\r\n\r\n0123456789\r\n--xyz--\r\n";
+        assert_eq!(
+            parse_parts(body, b"xyz"),
+            vec![
+                Part {
+                    filename: None,
+                    size: 5,
+                    truncated: false,
+                },
+                Part {
+                    filename: Some("a.png".to_string()),
+                    size: 10,
+                    truncated: false,
+                },
+            ]
+        );
+
+        let body = b"--xyz\r\nContent-Disposition: form-data; name=\"file\"; filename=\"big.zip\"\r\n\r\nPK0123";
+        assert_eq!(
+            parse_parts(body, b"xyz"),
+            vec![Part {
+                filename: Some("big.zip".to_string()),
+                size: 6,
+                truncated: true,
+            }]
+        );
+
+        assert!(parse_parts(b"--xyz\r\nContent-Disp", b"xyz").is_empty());
+        assert!(parse_parts(b"name=value", b"xyz").is_empty());
+    }
+}
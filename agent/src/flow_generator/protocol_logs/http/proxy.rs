@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Headers added by proxies and CDNs, the client is the first hop:
+//
+// X-Forwarded-For: 203.0.113.7, 10.0.0.1
+// Forwarded: for=203.0.113.7;proto=https, for="[2001:db8::17]:4711"
+// X-Real-IP: 203.0.113.7
+// Via: 1.1 cdn.example.com, 1.0 lb
+
+use std::net::IpAddr;
+
+// the chain is limited to avoid a forged header consuming memory
+pub const MAX_HOPS: usize = 16;
+
+// strip quotes, brackets and port of a node, such as `"[2001:db8::17]:4711"` or `203.0.113.7:80`
+fn normalize_node(node: &str) -> &str {
+    let node = node.trim().trim_matches('"');
+    if let Some(node) = node.strip_prefix('[') {
+        return node.split(']').next().unwrap_or_default();
+    }
+    match node.split_once(':') {
+        // more than one colon is ipv6 without port
+        Some((host, port)) if !port.contains(':') => host,
+        _ => node,
+    }
+}
+
+pub fn parse_x_forwarded_for(val: &str) -> impl Iterator<Item = &str> {
+    val.split(',')
+        .map(normalize_node)
+        .filter(|node| !node.is_empty())
+}
+
+// only the `for` parameters are returned
+pub fn parse_forwarded(val: &str) -> impl Iterator<Item = &str> {
+    val.split(',')
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                if key.trim().eq_ignore_ascii_case("for") {
+                    Some(normalize_node(value))
+                } else {
+                    None
+                }
+            })
+        })
+        .filter(|node| !node.is_empty())
+}
+
+// obfuscated identifiers such as `unknown` and `_hidden` are skipped
+pub fn first_ip<'a, I: IntoIterator<Item = &'a String>>(hops: I) -> Option<&'a String> {
+    hops.into_iter().find(|h| h.parse::<IpAddr>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_x_forwarded_for() {
+        assert_eq!(
+            parse_x_forwarded_for("203.0.113.7, 10.0.0.1:8080,,2001:db8::1").collect::<Vec<_>>(),
+            vec!["203.0.113.7", "10.0.0.1", "2001:db8::1"]
+        );
+        assert_eq!(parse_x_forwarded_for(" ").count(), 0);
+    }
+
+    #[test]
+    fn test_parse_forwarded() {
+        assert_eq!(
+            parse_forwarded(
+                "for=203.0.113.7;proto=https;by=10.0.0.1, For=\"[2001:db8::17]:4711\", for=unknown, proto=http"
+            )
+            .collect::<Vec<_>>(),
+            vec!["203.0.113.7", "2001:db8::17", "unknown"]
+        );
+    }
+
+    #[test]
+    fn test_first_ip() {
+        let hops = vec![
+            "unknown".to_string(),
+            "_hidden".to_string(),
+            "2001:db8::17".to_string(),
+            "10.0.0.1".to_string(),
+        ];
+        assert_eq!(first_ip(&hops).map(|s| s.as_str()), Some("2001:db8::17"));
+        assert_eq!(first_ip(&hops[..2]), None);
+    }
+}
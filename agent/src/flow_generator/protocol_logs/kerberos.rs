@@ -0,0 +1,754 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Kerberos V5, reference: https://www.rfc-editor.org/rfc/rfc4120#section-5
+//
+// KDC-REQ   ::= [APPLICATION 10 | 12] SEQUENCE { pvno [1], msg-type [2], padata [3], req-body [4] }
+// req-body  ::= SEQUENCE { kdc-options [0], cname [1], realm [2], sname [3], ..., etype [8] }
+// KDC-REP   ::= [APPLICATION 11 | 13] SEQUENCE { pvno [0], msg-type [1], padata [2], crealm [3],
+//                                               cname [4], ticket [5], enc-part [6] }
+// KRB-ERROR ::= [APPLICATION 30] SEQUENCE { pvno [0], msg-type [1], ..., error-code [6],
+//                                          crealm [7], cname [8], realm [9], sname [10], e-text [11] }
+//
+// Messages over TCP are prefixed by a 4 bytes length. Elements cut off by the end of
+// payload are skipped, e.g. req-body of a TGS-REQ with a large ticket in padata.
+
+use serde::Serialize;
+
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    flow_generator::error::{Error, Result},
+    utils::bytes::read_u32_be,
+};
+use public::l7_protocol::L7Protocol;
+
+const TCP_LENGTH_PREFIX: usize = 4;
+const PVNO: i64 = 5;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_GENERAL_STRING: u8 = 0x1b;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_TICKET: u8 = 0x61;
+const CONSTRUCTED: u8 = 0x20;
+const APPLICATION: u8 = 0x40;
+const CLASS_MASK: u8 = 0xe0;
+const CONTEXT: u8 = 0xa0;
+
+const MSG_AS_REQ: u8 = 10;
+const MSG_AS_REP: u8 = 11;
+const MSG_TGS_REQ: u8 = 12;
+const MSG_TGS_REP: u8 = 13;
+const MSG_KRB_ERROR: u8 = 30;
+
+const KDC_ERR_SVC_UNAVAILABLE: i32 = 29;
+const KDC_ERR_PREAUTH_REQUIRED: i32 = 25;
+const KRB_ERR_RESPONSE_TOO_BIG: i32 = 52;
+const KRB_ERR_GENERIC: i32 = 60;
+
+// etypes requested by client are limited to keep the log short
+const MAX_ETYPES: usize = 16;
+
+fn message_type(t: u8) -> &'static str {
+    match t {
+        MSG_AS_REQ => "AS-REQ",
+        MSG_AS_REP => "AS-REP",
+        MSG_TGS_REQ => "TGS-REQ",
+        MSG_TGS_REP => "TGS-REP",
+        MSG_KRB_ERROR => "KRB-ERROR",
+        _ => "",
+    }
+}
+
+fn error_name(code: i32) -> Option<&'static str> {
+    let name = match code {
+        1 => "KDC_ERR_NAME_EXP",
+        2 => "KDC_ERR_SERVICE_EXP",
+        3 => "KDC_ERR_BAD_PVNO",
+        6 => "KDC_ERR_C_PRINCIPAL_UNKNOWN",
+        7 => "KDC_ERR_S_PRINCIPAL_UNKNOWN",
+        8 => "KDC_ERR_PRINCIPAL_NOT_UNIQUE",
+        9 => "KDC_ERR_NULL_KEY",
+        12 => "KDC_ERR_POLICY",
+        13 => "KDC_ERR_BADOPTION",
+        14 => "KDC_ERR_ETYPE_NOSUPP",
+        15 => "KDC_ERR_SUMTYPE_NOSUPP",
+        18 => "KDC_ERR_CLIENT_REVOKED",
+        19 => "KDC_ERR_SERVICE_REVOKED",
+        20 => "KDC_ERR_TGT_REVOKED",
+        21 => "KDC_ERR_CLIENT_NOTYET",
+        23 => "KDC_ERR_KEY_EXPIRED",
+        24 => "KDC_ERR_PREAUTH_FAILED",
+        25 => "KDC_ERR_PREAUTH_REQUIRED",
+        29 => "KDC_ERR_SVC_UNAVAILABLE",
+        31 => "KRB_AP_ERR_BAD_INTEGRITY",
+        32 => "KRB_AP_ERR_TKT_EXPIRED",
+        34 => "KRB_AP_ERR_REPEAT",
+        37 => "KRB_AP_ERR_SKEW",
+        41 => "KRB_AP_ERR_MODIFIED",
+        52 => "KRB_ERR_RESPONSE_TOO_BIG",
+        60 => "KRB_ERR_GENERIC",
+        68 => "KDC_ERR_WRONG_REALM",
+        _ => return None,
+    };
+    Some(name)
+}
+
+struct Der<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Der<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    // Returns tag and content. Content of a constructed element is clamped to the
+    // end of buffer, while a primitive element must be complete.
+    fn next(&mut self) -> Option<(u8, &'a [u8], bool)> {
+        let tag = *self.buf.get(self.pos)?;
+        let first = *self.buf.get(self.pos + 1)? as usize;
+        let mut pos = self.pos + 2;
+        let len = if first & 0x80 == 0 {
+            first
+        } else {
+            let n = first & 0x7f;
+            if n == 0 || n > 4 {
+                return None;
+            }
+            let mut len = 0;
+            for b in self.buf.get(pos..pos + n)? {
+                len = len << 8 | *b as usize;
+            }
+            pos += n;
+            len
+        };
+        let end = pos.checked_add(len)?;
+        let complete = end <= self.buf.len();
+        if !complete && tag & CONSTRUCTED == 0 {
+            return None;
+        }
+        let end = end.min(self.buf.len());
+        self.pos = end;
+        Some((tag, &self.buf[pos..end], complete))
+    }
+
+    fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+        match self.next()? {
+            (t, content, _) if t == tag => Some(content),
+            _ => None,
+        }
+    }
+}
+
+fn decode_integer(value: &[u8]) -> Option<i64> {
+    if value.is_empty() || value.len() > 8 {
+        return None;
+    }
+    // sign extended
+    let init = if value[0] & 0x80 != 0 { -1 } else { 0 };
+    Some(value.iter().fold(init, |v, b| v << 8 | *b as i64))
+}
+
+fn integer(content: &[u8]) -> Option<i64> {
+    Der::new(content)
+        .expect(TAG_INTEGER)
+        .and_then(decode_integer)
+}
+
+fn string(content: &[u8]) -> Option<String> {
+    Der::new(content)
+        .expect(TAG_GENERAL_STRING)
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+}
+
+// PrincipalName ::= SEQUENCE { name-type [0] Int32, name-string [1] SEQUENCE OF KerberosString }
+fn principal_name(content: &[u8]) -> Option<String> {
+    let mut fields = Der::new(Der::new(content).expect(TAG_SEQUENCE)?);
+    while let Some((tag, field, _)) = fields.next() {
+        if tag != CONTEXT | 1 {
+            continue;
+        }
+        let mut names = Der::new(Der::new(field).expect(TAG_SEQUENCE)?);
+        let mut components = vec![];
+        while let Some(name) = names.expect(TAG_GENERAL_STRING) {
+            components.push(String::from_utf8_lossy(name));
+        }
+        return Some(components.join("/"));
+    }
+    None
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct KerberosInfo {
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    pub request_type: &'static str,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub response_type: &'static str,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    pub realm: String,
+    // client principal name
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub cname: String,
+    // service principal name
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    pub sname: String,
+    // encryption types requested by client
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub etypes: Vec<i32>,
+    // encryption type of the ticket issued
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_etype: Option<i32>,
+
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<i32>,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub exception: String,
+
+    msg_type: LogMessageType,
+    rrt: u64,
+}
+
+impl L7ProtocolInfoInterface for KerberosInfo {
+    fn session_id(&self) -> Option<u32> {
+        None
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::KerberosInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::Kerberos,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+}
+
+impl KerberosInfo {
+    pub fn merge(&mut self, other: &mut Self) {
+        self.response_type = other.response_type;
+        if self.realm.is_empty() {
+            std::mem::swap(&mut self.realm, &mut other.realm);
+        }
+        if self.cname.is_empty() {
+            std::mem::swap(&mut self.cname, &mut other.cname);
+        }
+        if self.sname.is_empty() {
+            std::mem::swap(&mut self.sname, &mut other.sname);
+        }
+        self.ticket_etype = other.ticket_etype;
+        self.status = other.status;
+        self.error_code = other.error_code;
+        std::mem::swap(&mut self.exception, &mut other.exception);
+    }
+}
+
+impl From<KerberosInfo> for L7ProtocolSendLog {
+    fn from(f: KerberosInfo) -> Self {
+        let mut attributes = vec![];
+        if !f.response_type.is_empty() {
+            attributes.push(KeyVal {
+                key: "kerberos_response_type".to_string(),
+                val: f.response_type.to_string(),
+            });
+        }
+        if !f.cname.is_empty() {
+            attributes.push(KeyVal {
+                key: "kerberos_cname".to_string(),
+                val: f.cname,
+            });
+        }
+        if !f.etypes.is_empty() {
+            attributes.push(KeyVal {
+                key: "kerberos_etypes".to_string(),
+                val: f
+                    .etypes
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            });
+        }
+        if let Some(etype) = f.ticket_etype {
+            attributes.push(KeyVal {
+                key: "kerberos_ticket_etype".to_string(),
+                val: etype.to_string(),
+            });
+        }
+        L7ProtocolSendLog {
+            req: L7Request {
+                req_type: f.request_type.to_string(),
+                domain: f.realm,
+                resource: f.sname,
+                ..Default::default()
+            },
+            resp: L7Response {
+                result: f.response_type.to_string(),
+                code: f.error_code,
+                status: f.status,
+                exception: f.exception,
+            },
+            version: Some(PVNO.to_string()),
+            ext_info: Some(ExtendedInfo {
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct KerberosLog {
+    perf_stats: Option<L7PerfStats>,
+}
+
+impl L7ProtocolParserInterface for KerberosLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() {
+            return false;
+        }
+        match Self::parse(payload, param.l4_protocol) {
+            Ok(info) => info.msg_type == LogMessageType::Request,
+            Err(_) => false,
+        }
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+        let mut info = Self::parse(payload, param.l4_protocol)?;
+        if let Some(perf_stats) = self.perf_stats.as_mut() {
+            match info.msg_type {
+                LogMessageType::Request => perf_stats.inc_req(),
+                _ => perf_stats.inc_resp(),
+            }
+            match info.status {
+                L7ResponseStatus::ClientError => perf_stats.inc_req_err(),
+                L7ResponseStatus::ServerError => perf_stats.inc_resp_err(),
+                _ => {}
+            }
+        }
+        info.cal_rrt(param, None).map(|rrt| {
+            info.rrt = rrt;
+            self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+        });
+        if param.parse_log {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::KerberosInfo(info)))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::Kerberos
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl KerberosLog {
+    fn parse(payload: &[u8], l4_protocol: IpProtocol) -> Result<KerberosInfo> {
+        let payload = if l4_protocol == IpProtocol::TCP {
+            if payload.len() <= TCP_LENGTH_PREFIX {
+                return Err(Error::KerberosLogParseFailed(
+                    "kerberos payload too short".to_string(),
+                ));
+            }
+            // the highest bit is reserved
+            let length = read_u32_be(payload) as usize;
+            if length & 0x80000000 != 0 || length < payload.len() - TCP_LENGTH_PREFIX {
+                let err_msg = format!("kerberos record length {} invalid", length);
+                return Err(Error::KerberosLogParseFailed(err_msg));
+            }
+            &payload[TCP_LENGTH_PREFIX..]
+        } else {
+            payload
+        };
+
+        let mut message = Der::new(payload);
+        let Some((tag, content, _)) = message.next() else {
+            return Err(Error::KerberosLogParseFailed(
+                "kerberos message invalid".to_string(),
+            ));
+        };
+        let msg_type = tag & !CLASS_MASK;
+        let name = message_type(msg_type);
+        if tag & CLASS_MASK != APPLICATION | CONSTRUCTED || name.is_empty() {
+            let err_msg = format!("kerberos message tag {:#x} unsupported", tag);
+            return Err(Error::KerberosLogParseFailed(err_msg));
+        }
+        let Some(fields) = Der::new(content).expect(TAG_SEQUENCE) else {
+            return Err(Error::KerberosLogParseFailed(
+                "kerberos message invalid".to_string(),
+            ));
+        };
+
+        let mut info = KerberosInfo::default();
+        let is_request = msg_type == MSG_AS_REQ || msg_type == MSG_TGS_REQ;
+        // pvno and msg-type are [1] and [2] in KDC-REQ, otherwise [0] and [1]
+        let (pvno_tag, msg_type_tag) = if is_request {
+            (CONTEXT | 1, CONTEXT | 2)
+        } else {
+            (CONTEXT, CONTEXT | 1)
+        };
+        let mut fields = Der::new(fields);
+        let mut header = (None, None);
+        while let Some((tag, field, complete)) = fields.next() {
+            if tag == pvno_tag {
+                header.0 = integer(field);
+            } else if tag == msg_type_tag {
+                header.1 = integer(field);
+            } else if !complete {
+                // cut off fields are skipped
+                break;
+            } else if is_request {
+                Self::on_request_field(tag, field, &mut info);
+            } else if msg_type == MSG_KRB_ERROR {
+                Self::on_error_field(tag, field, &mut info);
+            } else {
+                Self::on_reply_field(tag, field, &mut info);
+            }
+        }
+        if header != (Some(PVNO), Some(msg_type as i64)) {
+            let err_msg = format!("kerberos message header {:?} invalid", header);
+            return Err(Error::KerberosLogParseFailed(err_msg));
+        }
+
+        if is_request {
+            info.msg_type = LogMessageType::Request;
+            info.request_type = name;
+        } else {
+            info.msg_type = LogMessageType::Response;
+            info.response_type = name;
+        }
+        if let Some(code) = info.error_code {
+            info.status = match code {
+                // steps of a normal exchange, the client retries with pre-authentication or over tcp
+                KDC_ERR_PREAUTH_REQUIRED | KRB_ERR_RESPONSE_TOO_BIG => L7ResponseStatus::Ok,
+                KDC_ERR_SVC_UNAVAILABLE | KRB_ERR_GENERIC => L7ResponseStatus::ServerError,
+                _ => L7ResponseStatus::ClientError,
+            };
+            let name = error_name(code)
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| format!("error code {}", code));
+            info.exception = if info.exception.is_empty() {
+                name
+            } else {
+                format!("{}: {}", name, info.exception)
+            };
+        }
+        Ok(info)
+    }
+
+    fn on_request_field(tag: u8, field: &[u8], info: &mut KerberosInfo) {
+        const REQ_BODY: u8 = CONTEXT | 4;
+        const CNAME: u8 = CONTEXT | 1;
+        const REALM: u8 = CONTEXT | 2;
+        const SNAME: u8 = CONTEXT | 3;
+        const ETYPE: u8 = CONTEXT | 8;
+
+        if tag != REQ_BODY {
+            return;
+        }
+        let Some(body) = Der::new(field).expect(TAG_SEQUENCE) else {
+            return;
+        };
+        let mut body = Der::new(body);
+        while let Some((tag, field, complete)) = body.next() {
+            if !complete {
+                break;
+            }
+            match tag {
+                CNAME => info.cname = principal_name(field).unwrap_or_default(),
+                REALM => info.realm = string(field).unwrap_or_default(),
+                SNAME => info.sname = principal_name(field).unwrap_or_default(),
+                ETYPE => {
+                    let Some(etypes) = Der::new(field).expect(TAG_SEQUENCE) else {
+                        continue;
+                    };
+                    let mut etypes = Der::new(etypes);
+                    while let Some(etype) = etypes.expect(TAG_INTEGER) {
+                        if info.etypes.len() >= MAX_ETYPES {
+                            break;
+                        }
+                        if let Some(e) = decode_integer(etype) {
+                            info.etypes.push(e as i32);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn on_reply_field(tag: u8, field: &[u8], info: &mut KerberosInfo) {
+        const CREALM: u8 = CONTEXT | 3;
+        const CNAME: u8 = CONTEXT | 4;
+        const TICKET: u8 = CONTEXT | 5;
+        const TICKET_REALM: u8 = CONTEXT | 1;
+        const TICKET_SNAME: u8 = CONTEXT | 2;
+        const TICKET_ENC_PART: u8 = CONTEXT | 3;
+
+        match tag {
+            CREALM => info.realm = string(field).unwrap_or_default(),
+            CNAME => info.cname = principal_name(field).unwrap_or_default(),
+            TICKET => {
+                let Some(ticket) = Der::new(field)
+                    .expect(TAG_TICKET)
+                    .and_then(|t| Der::new(t).expect(TAG_SEQUENCE))
+                else {
+                    return;
+                };
+                let mut ticket = Der::new(ticket);
+                while let Some((tag, field, _)) = ticket.next() {
+                    match tag {
+                        TICKET_REALM if info.realm.is_empty() => {
+                            info.realm = string(field).unwrap_or_default()
+                        }
+                        TICKET_SNAME => info.sname = principal_name(field).unwrap_or_default(),
+                        // EncryptedData ::= SEQUENCE { etype [0] Int32, kvno [1], cipher [2] }
+                        TICKET_ENC_PART => {
+                            info.ticket_etype = Der::new(field)
+                                .expect(TAG_SEQUENCE)
+                                .and_then(|e| Der::new(e).expect(CONTEXT))
+                                .and_then(integer)
+                                .map(|e| e as i32);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_error_field(tag: u8, field: &[u8], info: &mut KerberosInfo) {
+        const ERROR_CODE: u8 = CONTEXT | 6;
+        const CREALM: u8 = CONTEXT | 7;
+        const CNAME: u8 = CONTEXT | 8;
+        const REALM: u8 = CONTEXT | 9;
+        const SNAME: u8 = CONTEXT | 10;
+        const E_TEXT: u8 = CONTEXT | 11;
+
+        match tag {
+            ERROR_CODE => info.error_code = integer(field).map(|c| c as i32),
+            CREALM => info.realm = string(field).unwrap_or_default(),
+            CNAME => info.cname = principal_name(field).unwrap_or_default(),
+            REALM if info.realm.is_empty() => info.realm = string(field).unwrap_or_default(),
+            SNAME => info.sname = principal_name(field).unwrap_or_default(),
+            // joined to the exception after all fields are decoded
+            E_TEXT => info.exception = string(field).unwrap_or_default(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // definite length encoding of DER
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut e = vec![tag];
+        match content.len() {
+            l if l < 0x80 => e.push(l as u8),
+            l if l < 0x100 => e.extend_from_slice(&[0x81, l as u8]),
+            l => e.extend_from_slice(&[0x82, (l >> 8) as u8, l as u8]),
+        }
+        e.extend_from_slice(content);
+        e
+    }
+
+    fn int(tag: u8, v: u8) -> Vec<u8> {
+        der(tag, &der(TAG_INTEGER, &[v]))
+    }
+
+    fn gstring(tag: u8, s: &str) -> Vec<u8> {
+        der(tag, &der(TAG_GENERAL_STRING, s.as_bytes()))
+    }
+
+    fn principal(tag: u8, name_type: u8, names: &[&str]) -> Vec<u8> {
+        let names = names
+            .iter()
+            .flat_map(|n| der(TAG_GENERAL_STRING, n.as_bytes()))
+            .collect::<Vec<_>>();
+        let fields = [
+            int(CONTEXT, name_type),
+            der(CONTEXT | 1, &der(TAG_SEQUENCE, &names)),
+        ];
+        der(tag, &der(TAG_SEQUENCE, &fields.concat()))
+    }
+
+    fn as_req() -> Vec<u8> {
+        let etypes = [
+            der(TAG_INTEGER, &[18]),
+            der(TAG_INTEGER, &[23]),
+            der(TAG_INTEGER, &[0xff, 0x79]),
+        ]
+        .concat();
+        let body = [
+            der(CONTEXT, &[0x03, 0x05, 0x00, 0x40, 0x81, 0x00, 0x10]),
+            principal(CONTEXT | 1, 1, &["alice"]),
+            gstring(CONTEXT | 2, "EXAMPLE.COM"),
+            principal(CONTEXT | 3, 2, &["krbtgt", "EXAMPLE.COM"]),
+            int(CONTEXT | 7, 42),
+            der(CONTEXT | 8, &der(TAG_SEQUENCE, &etypes)),
+        ]
+        .concat();
+        let fields = [
+            int(CONTEXT | 1, 5),
+            int(CONTEXT | 2, MSG_AS_REQ),
+            der(CONTEXT | 4, &der(TAG_SEQUENCE, &body)),
+        ]
+        .concat();
+        der(
+            APPLICATION | CONSTRUCTED | MSG_AS_REQ,
+            &der(TAG_SEQUENCE, &fields),
+        )
+    }
+
+    #[test]
+    fn test_request() {
+        let info = KerberosLog::parse(&as_req(), IpProtocol::UDP).unwrap();
+        assert_eq!(info.msg_type, LogMessageType::Request);
+        assert_eq!(info.request_type, "AS-REQ");
+        assert_eq!(info.realm, "EXAMPLE.COM");
+        assert_eq!(info.cname, "alice");
+        assert_eq!(info.sname, "krbtgt/EXAMPLE.COM");
+        assert_eq!(info.etypes, vec![18, 23, -135]);
+
+        // with record length over tcp
+        let req = as_req();
+        let mut payload = (req.len() as u32).to_be_bytes().to_vec();
+        payload.extend_from_slice(&req);
+        let info = KerberosLog::parse(&payload, IpProtocol::TCP).unwrap();
+        assert_eq!(info.request_type, "AS-REQ");
+
+        // the body is cut off
+        let cut = &req[..req.len() - 10];
+        let info = KerberosLog::parse(cut, IpProtocol::UDP).unwrap();
+        assert_eq!(info.request_type, "AS-REQ");
+        assert_eq!(info.realm, "");
+
+        assert!(KerberosLog::parse(b"\x16\x03\x01\x02\x00\x01\x00", IpProtocol::UDP).is_err());
+    }
+
+    #[test]
+    fn test_reply() {
+        let enc_part = der(
+            TAG_SEQUENCE,
+            &[int(CONTEXT, 18), der(CONTEXT | 2, &der(0x04, &[0xaa; 8]))].concat(),
+        );
+        let ticket = [
+            int(CONTEXT, 5),
+            gstring(CONTEXT | 1, "EXAMPLE.COM"),
+            principal(CONTEXT | 2, 2, &["HTTP", "web.example.com"]),
+            der(CONTEXT | 3, &enc_part),
+        ]
+        .concat();
+        let fields = [
+            int(CONTEXT, 5),
+            int(CONTEXT | 1, MSG_TGS_REP),
+            gstring(CONTEXT | 3, "EXAMPLE.COM"),
+            principal(CONTEXT | 4, 1, &["alice"]),
+            der(CONTEXT | 5, &der(TAG_TICKET, &der(TAG_SEQUENCE, &ticket))),
+            der(CONTEXT | 6, &enc_part),
+        ]
+        .concat();
+        let rep = der(
+            APPLICATION | CONSTRUCTED | MSG_TGS_REP,
+            &der(TAG_SEQUENCE, &fields),
+        );
+        let info = KerberosLog::parse(&rep, IpProtocol::UDP).unwrap();
+        assert_eq!(info.msg_type, LogMessageType::Response);
+        assert_eq!(info.response_type, "TGS-REP");
+        assert_eq!(info.realm, "EXAMPLE.COM");
+        assert_eq!(info.cname, "alice");
+        assert_eq!(info.sname, "HTTP/web.example.com");
+        assert_eq!(info.ticket_etype, Some(18));
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+    }
+
+    #[test]
+    fn test_error() {
+        let error = |code: u8, e_text: Option<&str>| {
+            let mut fields = [
+                int(CONTEXT, 5),
+                int(CONTEXT | 1, MSG_KRB_ERROR),
+                int(CONTEXT | 6, code),
+                gstring(CONTEXT | 9, "EXAMPLE.COM"),
+                principal(CONTEXT | 10, 2, &["krbtgt", "EXAMPLE.COM"]),
+            ]
+            .concat();
+            if let Some(t) = e_text {
+                fields.extend_from_slice(&gstring(CONTEXT | 11, t));
+            }
+            der(
+                APPLICATION | CONSTRUCTED | MSG_KRB_ERROR,
+                &der(TAG_SEQUENCE, &fields),
+            )
+        };
+
+        let info = KerberosLog::parse(&error(24, Some("bad password")), IpProtocol::UDP).unwrap();
+        assert_eq!(info.response_type, "KRB-ERROR");
+        assert_eq!(info.realm, "EXAMPLE.COM");
+        assert_eq!(info.sname, "krbtgt/EXAMPLE.COM");
+        assert_eq!(info.error_code, Some(24));
+        assert_eq!(info.status, L7ResponseStatus::ClientError);
+        assert_eq!(info.exception, "KDC_ERR_PREAUTH_FAILED: bad password");
+
+        let info = KerberosLog::parse(&error(25, None), IpProtocol::UDP).unwrap();
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+        assert_eq!(info.exception, "KDC_ERR_PREAUTH_REQUIRED");
+
+        let info = KerberosLog::parse(&error(60, None), IpProtocol::UDP).unwrap();
+        assert_eq!(info.status, L7ResponseStatus::ServerError);
+    }
+}
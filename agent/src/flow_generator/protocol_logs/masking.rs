@@ -0,0 +1,242 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+
+use log::warn;
+use regex::{Captures, Regex};
+
+use super::pb_adapter::L7ProtocolSendLog;
+use crate::config::L7LogMasking;
+
+const EMAIL: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}";
+// 13 to 19 digits, optionally grouped by spaces or dashes
+const CARD_NUMBER: &str = r"\b\d(?:[ -]?\d){12,18}\b";
+// optional country code and area code, then 10 or 11 digits like 415-555-0132 or 13812345678
+const PHONE_NUMBER: &str =
+    r"(?:\+\d{1,3}[ -]?)?(?:\(\d{2,4}\)[ -]?)?\b\d{3,4}[ -]?\d{3,4}[ -]?\d{4}\b";
+
+const ATTRIBUTE_PREFIX: &str = "attribute.";
+
+enum Detector {
+    // card numbers must pass the luhn check
+    CardNumber(Regex),
+    Regex(Regex),
+}
+
+fn luhn_check(s: &str) -> bool {
+    let mut sum = 0;
+    let mut digits = 0;
+    for (i, d) in s
+        .bytes()
+        .rev()
+        .filter(|b| b.is_ascii_digit())
+        .map(|b| (b - b'0') as u32)
+        .enumerate()
+    {
+        digits += 1;
+        sum += if i % 2 == 1 {
+            if d * 2 > 9 {
+                d * 2 - 9
+            } else {
+                d * 2
+            }
+        } else {
+            d
+        };
+    }
+    digits > 0 && sum % 10 == 0
+}
+
+impl Detector {
+    fn mask<'a>(&self, s: &'a str, replacement: &str) -> Cow<'a, str> {
+        match self {
+            Detector::CardNumber(re) => re.replace_all(s, |caps: &Captures| {
+                if luhn_check(&caps[0]) {
+                    replacement.to_owned()
+                } else {
+                    caps[0].to_owned()
+                }
+            }),
+            Detector::Regex(re) => re.replace_all(s, regex::NoExpand(replacement)),
+        }
+    }
+}
+
+// Masks sensitive data in fields of l7 logs before they are sent
+#[derive(Default)]
+pub struct Masker {
+    config: L7LogMasking,
+    detectors: Vec<Detector>,
+    resource: bool,
+    endpoint: bool,
+    all_attributes: bool,
+    attributes: Vec<String>,
+}
+
+impl Masker {
+    pub fn new(config: &L7LogMasking) -> Self {
+        let mut detectors = vec![];
+        for d in config.detectors.iter() {
+            let detector = match d.as_str() {
+                "email" => Detector::Regex(Regex::new(EMAIL).unwrap()),
+                "card-number" => Detector::CardNumber(Regex::new(CARD_NUMBER).unwrap()),
+                "phone-number" => Detector::Regex(Regex::new(PHONE_NUMBER).unwrap()),
+                _ => {
+                    warn!("unknown l7 log masking detector {} ignored", d);
+                    continue;
+                }
+            };
+            detectors.push(detector);
+        }
+        for r in config.regexes.iter() {
+            match Regex::new(r) {
+                Ok(re) => detectors.push(Detector::Regex(re)),
+                Err(e) => warn!("invalid l7 log masking regex {}: {}", r, e),
+            }
+        }
+        // card numbers are checked before phone numbers which may match part of them
+        detectors.sort_by_key(|d| !matches!(d, Detector::CardNumber(_)));
+
+        let mut masker = Self {
+            config: config.clone(),
+            detectors,
+            ..Default::default()
+        };
+        for f in config.fields.iter() {
+            match f.as_str() {
+                "resource" => masker.resource = true,
+                "endpoint" => masker.endpoint = true,
+                "attributes" => masker.all_attributes = true,
+                _ => match f.strip_prefix(ATTRIBUTE_PREFIX) {
+                    Some(name) if !name.is_empty() => masker.attributes.push(name.to_owned()),
+                    _ => warn!("unknown l7 log masking field {} ignored", f),
+                },
+            }
+        }
+        masker
+    }
+
+    pub fn update(&mut self, config: &L7LogMasking) {
+        if &self.config != config {
+            *self = Self::new(config);
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.detectors.is_empty()
+            && (self.resource
+                || self.endpoint
+                || self.all_attributes
+                || !self.attributes.is_empty())
+    }
+
+    fn mask_str(&self, s: &mut String) -> bool {
+        let mut masked = false;
+        for d in self.detectors.iter() {
+            // card numbers failed the luhn check are replaced by themselves
+            let m = match d.mask(s, &self.config.replacement) {
+                Cow::Owned(m) if m != *s => m,
+                _ => continue,
+            };
+            *s = m;
+            masked = true;
+        }
+        masked
+    }
+
+    // returns true if anything is masked
+    pub fn mask(&self, log: &mut L7ProtocolSendLog) -> bool {
+        let mut masked = false;
+        if self.resource {
+            masked |= self.mask_str(&mut log.req.resource);
+        }
+        if self.endpoint {
+            masked |= self.mask_str(&mut log.req.endpoint);
+        }
+        if !self.all_attributes && self.attributes.is_empty() {
+            return masked;
+        }
+        let Some(attributes) = log.ext_info.as_mut().and_then(|e| e.attributes.as_mut()) else {
+            return masked;
+        };
+        for kv in attributes.iter_mut() {
+            if self.all_attributes || self.attributes.iter().any(|a| a == &kv.key) {
+                masked |= self.mask_str(&mut kv.val);
+            }
+        }
+        masked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::flow_generator::protocol_logs::pb_adapter::{ExtendedInfo, KeyVal, L7Request};
+
+    #[test]
+    fn test_mask() {
+        let masker = Masker::new(&L7LogMasking {
+            detectors: vec![
+                "email".to_owned(),
+                "phone-number".to_owned(),
+                "card-number".to_owned(),
+            ],
+            regexes: vec![r"token=[^&]+".to_owned()],
+            fields: vec!["resource".to_owned(), "attribute.user".to_owned()],
+            ..Default::default()
+        });
+        assert!(masker.enabled());
+
+        let mut log = L7ProtocolSendLog {
+            req: L7Request {
+                resource:
+                    "/pay?card=4111 1111 1111 1111&order=1234567890123&token=abc&to=a.b@example.com"
+                        .to_owned(),
+                endpoint: "/pay/13812345678".to_owned(),
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                attributes: Some(vec![
+                    KeyVal {
+                        key: "user".to_owned(),
+                        val: "tel 415-555-0132".to_owned(),
+                    },
+                    KeyVal {
+                        key: "other".to_owned(),
+                        val: "a.b@example.com".to_owned(),
+                    },
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(masker.mask(&mut log));
+        // 1234567890123 fails the luhn check and is not a card number
+        assert_eq!(
+            log.req.resource,
+            "/pay?card=***&order=1234567890123&***&to=***"
+        );
+        assert_eq!(log.req.endpoint, "/pay/13812345678");
+        let attributes = log.ext_info.unwrap().attributes.unwrap();
+        assert_eq!(attributes[0].val, "tel ***");
+        assert_eq!(attributes[1].val, "a.b@example.com");
+
+        let masker = Masker::new(&L7LogMasking::default());
+        assert!(!masker.enabled());
+    }
+}
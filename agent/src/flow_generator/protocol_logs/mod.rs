@@ -15,15 +15,26 @@
  */
 
 pub mod consts;
+mod dedup;
+pub(crate) mod dhcp;
 pub(crate) mod dns;
 pub(crate) mod fastcgi;
+mod field_extraction;
+mod field_limit;
 pub(crate) mod http;
+pub(crate) mod kerberos;
+mod masking;
 pub(crate) mod mq;
+pub(crate) mod ntp;
 mod parser;
 pub mod pb_adapter;
 pub(crate) mod plugin;
+pub(crate) mod radius;
 pub(crate) mod rpc;
+pub(crate) mod rtp;
+pub(crate) mod sip;
 pub(crate) mod sql;
+pub(crate) mod ssh;
 pub(crate) mod tls;
 pub use self::http::{
     check_http_method, get_http_request_info, get_http_request_version, get_http_resp_info,
@@ -32,18 +43,27 @@ pub use self::http::{
 use self::pb_adapter::L7ProtocolSendLog;
 pub use self::plugin::custom_wrap::CustomWrapLog;
 pub use self::plugin::wasm::{get_wasm_parser, WasmLog};
+use super::pool::recycle_string;
+pub use dedup::{connection_key, L7LogDeduplicator};
+pub use dhcp::{DhcpInfo, DhcpLog};
 pub use dns::{DnsInfo, DnsLog};
+pub use kerberos::{KerberosInfo, KerberosLog};
 pub use mq::{mqtt, KafkaInfo, KafkaLog, MqttInfo, MqttLog};
+pub use ntp::{NtpInfo, NtpLog};
 use num_enum::TryFromPrimitive;
 pub use parser::{MetaAppProto, SessionAggregator, SLOT_WIDTH};
+pub use radius::{RadiusInfo, RadiusLog};
 pub use rpc::{
     decode_new_rpc_trace_context, decode_new_rpc_trace_context_with_type, DubboHeader, DubboInfo,
     DubboLog, SofaRpcInfo, SofaRpcLog, SOFA_NEW_RPC_TRACE_CTX_KEY,
 };
+pub use rtp::RtpLog;
+pub use sip::{SipInfo, SipLog};
 pub use sql::{
     decode, MongoDBInfo, MongoDBLog, MysqlHeader, MysqlInfo, MysqlLog, OracleInfo, OracleLog,
     PostgreInfo, PostgresqlLog, RedisInfo, RedisLog,
 };
+pub use ssh::{SshInfo, SshLog};
 pub use tls::{TlsInfo, TlsLog};
 
 use std::{
@@ -219,6 +239,9 @@ pub struct AppProtoLogsBaseInfo {
     pub syscall_cap_seq_0: u32,
     #[serde(skip_serializing_if = "value_is_default")]
     pub syscall_cap_seq_1: u32,
+    // AF_UNIX socket path, sent as the attribute 'unix_socket_path'
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub unix_socket_path: String,
 
     pub protocol: IpProtocol,
     #[serde(skip)]
@@ -353,6 +376,9 @@ impl AppProtoLogsBaseInfo {
         if log.syscall_trace_id_request > 0 {
             self.syscall_trace_id_request = log.syscall_trace_id_request;
         }
+        if self.unix_socket_path.is_empty() {
+            std::mem::swap(&mut self.unix_socket_path, &mut log.unix_socket_path);
+        }
         if log.syscall_trace_id_response > 0 {
             self.syscall_trace_id_response = log.syscall_trace_id_response;
         }
@@ -385,19 +411,52 @@ impl AppProtoLogsBaseInfo {
 #[derive(Debug)]
 pub struct BoxAppProtoLogsData(pub Box<MetaAppProto>);
 
+#[derive(Serialize)]
+struct ProcessedAppProtoLog<'a> {
+    #[serde(flatten)]
+    base_info: &'a AppProtoLogsBaseInfo,
+    direction_score: u8,
+    #[serde(flatten)]
+    send_log: &'a L7ProtocolSendLog,
+}
+
 impl Sendable for BoxAppProtoLogsData {
-    fn encode(self, buf: &mut Vec<u8>) -> Result<usize, prost::EncodeError> {
+    fn encode(mut self, buf: &mut Vec<u8>) -> Result<usize, prost::EncodeError> {
+        let unix_socket_path = std::mem::take(&mut self.0.base_info.unix_socket_path);
         let mut pb_proto_logs_data = flow_log::AppProtoLogsData {
             base: Some(self.0.base_info.into()),
             direction_score: self.0.direction_score as u32,
             ..Default::default()
         };
 
-        let log: L7ProtocolSendLog = self.0.l7_info.into();
+        let log: L7ProtocolSendLog = match self.0.send_log {
+            Some(log) => log,
+            None => self.0.l7_info.into(),
+        };
         log.fill_app_proto_log(&mut pb_proto_logs_data);
-        pb_proto_logs_data
+        if !unix_socket_path.is_empty() {
+            let ext_info = pb_proto_logs_data
+                .ext_info
+                .get_or_insert_with(Default::default);
+            ext_info
+                .attribute_names
+                .push("unix_socket_path".to_string());
+            ext_info.attribute_values.push(unix_socket_path);
+        }
+        let result = pb_proto_logs_data
             .encode(buf)
-            .map(|_| pb_proto_logs_data.encoded_len())
+            .map(|_| pb_proto_logs_data.encoded_len());
+
+        // return buffers allocated by parsers to the pool
+        if let Some(req) = pb_proto_logs_data.req {
+            recycle_string(req.domain);
+            recycle_string(req.resource);
+            recycle_string(req.endpoint);
+        }
+        if let Some(resp) = pb_proto_logs_data.resp {
+            recycle_string(resp.result);
+        }
+        result
     }
 
     fn file_name(&self) -> &str {
@@ -409,7 +468,16 @@ impl Sendable for BoxAppProtoLogsData {
     }
 
     fn to_kv_string(&self, kv_string: &mut String) {
-        let json = serde_json::to_string(&(*self.0)).unwrap();
+        // logs processed by field extraction, scripts and masking are written as they are sent
+        let json = match self.0.send_log.as_ref() {
+            Some(send_log) => serde_json::to_string(&ProcessedAppProtoLog {
+                base_info: &self.0.base_info,
+                direction_score: self.0.direction_score,
+                send_log,
+            }),
+            None => serde_json::to_string(&(*self.0)),
+        }
+        .unwrap();
         kv_string.push_str(&json);
         kv_string.push('\n');
     }
@@ -23,9 +23,10 @@ use crate::{
         l7_protocol_log::{KafkaInfoCache, L7ParseResult, L7ProtocolParserInterface, ParseParam},
         meta_packet::EbpfFlags,
     },
-    config::handler::TraceType,
+    config::handler::{L7LogDynamicConfig, TraceType},
     flow_generator::{
         error::{Error, Result},
+        pool::pooled_string_lossy,
         protocol_logs::{
             consts::{
                 KAFKA_REQ_HEADER_LEN, KAFKA_RESP_HEADER_LEN, KAFKA_STATUS_CODE_CHECKER,
@@ -33,13 +34,16 @@ use crate::{
             },
             decode_base64_to_string,
             pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response, TraceInfo},
-            value_is_default, value_is_negative, AppProtoHead, L7ResponseStatus, LogMessageType,
+            value_is_default, value_is_negative, AppProtoHead, HttpLog, L7ResponseStatus,
+            LogMessageType,
         },
     },
-    utils::bytes::{read_i16_be, read_u16_be, read_u32_be},
+    utils::bytes::{read_i16_be, read_u32_be, PayloadReader, ReadError},
 };
 
 const KAFKA_PRODUCE: u16 = 0;
+// responses are recognized only from these ports as they have no signature but the length
+const KAFKA_SERVER_PORTS: [u16; 2] = [9092, 9093];
 const KAFKA_FETCH: u16 = 1;
 
 #[derive(Serialize, Debug, Default, Clone)]
@@ -52,6 +56,8 @@ pub struct KafkaInfo {
     pub correlation_id: u32,
     #[serde(skip_serializing_if = "value_is_default")]
     pub trace_id: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub span_id: String,
 
     // request
     #[serde(rename = "request_length", skip_serializing_if = "value_is_negative")]
@@ -103,6 +109,22 @@ impl L7ProtocolInfoInterface for KafkaInfo {
     fn is_tls(&self) -> bool {
         self.is_tls
     }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+
+    fn request_len(&self) -> Option<u32> {
+        self.req_msg_size
+    }
+
+    fn response_len(&self) -> Option<u32> {
+        self.resp_msg_size
+    }
+
+    fn response_code(&self) -> Option<String> {
+        self.status_code.map(|c| c.to_string())
+    }
 }
 
 impl KafkaInfo {
@@ -234,6 +256,11 @@ impl From<KafkaInfo> for L7ProtocolSendLog {
                 } else {
                     Some(f.trace_id)
                 },
+                span_id: if f.span_id.is_empty() {
+                    None
+                } else {
+                    Some(f.span_id)
+                },
                 ..Default::default()
             }),
             flags,
@@ -262,6 +289,18 @@ impl L7ProtocolParserInterface for KafkaLog {
         ok
     }
 
+    // Response Header => message_size correlation_id
+    fn check_response(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol()
+            || param.l4_protocol != IpProtocol::TCP
+            || payload.len() < KAFKA_RESP_HEADER_LEN
+            || !KAFKA_SERVER_PORTS.contains(&param.port_src)
+        {
+            return false;
+        }
+        read_u32_be(payload) as usize + Self::MSG_LEN_SIZE == payload.len()
+    }
+
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
         if self.perf_stats.is_none() && param.parse_perf {
             self.perf_stats = Some(L7PerfStats::default())
@@ -269,11 +308,22 @@ impl L7ProtocolParserInterface for KafkaLog {
         let mut info = KafkaInfo::default();
         Self::parse(self, payload, param.l4_protocol, param.direction, &mut info)?;
         info.is_tls = param.is_tls();
+        if info.msg_type == LogMessageType::Request && info.api_key == KAFKA_PRODUCE {
+            let headers = Self::produce_record_headers(payload, info.api_version);
+            Self::decode_sw8_record_header(&headers, &mut info);
+            if let Some(config) = param.parse_config {
+                Self::decode_custom_trace_context(&headers, &config.l7_log_dynamic, &mut info);
+            }
+        }
 
         // handle kafka status code
         {
-            let mut log_cache = param.l7_perf_cache.borrow_mut();
-            if let Some(previous) = log_cache.rrt_cache.get(&info.cal_cache_key(param)) {
+            let log_cache = param.l7_perf_cache.borrow();
+            if let Some(previous) = log_cache
+                .pending_requests
+                .peek(&param.flow_id)
+                .and_then(|p| p.get(info.correlation_id))
+            {
                 match (previous.msg_type, info.msg_type) {
                     (LogMessageType::Request, LogMessageType::Response)
                         if param.time < previous.time + param.rrt_timeout as u64 =>
@@ -308,7 +358,7 @@ impl L7ProtocolParserInterface for KafkaLog {
             }
         }
 
-        info.cal_rrt(
+        info.cal_rrt_by_session_id(
             param,
             Some(KafkaInfoCache {
                 api_key: info.api_key,
@@ -342,40 +392,21 @@ impl L7ProtocolParserInterface for KafkaLog {
     fn perf_stats(&mut self) -> Option<L7PerfStats> {
         self.perf_stats.take()
     }
+
+    fn message_len(&self, payload: &[u8], _: PacketDirection) -> Option<usize> {
+        if payload.len() < Self::MSG_LEN_SIZE {
+            return None;
+        }
+        Some(read_u32_be(payload) as usize + Self::MSG_LEN_SIZE)
+    }
 }
 
 impl KafkaLog {
     const MSG_LEN_SIZE: usize = 4;
     const MAX_TRACE_ID: usize = 255;
-
-    fn decode_varint(buf: &[u8]) -> (usize, usize) {
-        let mut shift = 0;
-        let mut n = 0;
-        let mut x = 0;
-        while shift < 64 {
-            if n >= buf.len() {
-                return (0, 0);
-            }
-            let b = buf[n] as usize;
-            n += 1;
-            // The following is divided into three steps:
-            // 1: b&0x7F: Get 7 bits of valid data
-            // 2: (b & 0x7F) << shift: Since shift is small-endian, it is necessary to move 7bits
-            //    to the high position for each Byte of data processed
-            // 3: Perform or operations on x with the current byte
-            // =======================================================================================
-            // 下面这个分成三步走:
-            // 1: b & 0x7F: 获取下7bits有效数据
-            // 2: (b & 0x7F) << shift: 由于是小端序, 所以每次处理一个Byte数据, 都需要向高位移动7bits
-            // 3: 将数据x和当前的这个字节数据 | 在一起
-            x |= (b & 0x7F) << shift;
-            if (b & 0x80) == 0 {
-                return (x, n);
-            }
-            shift += 7;
-        }
-        return (0, 0);
-    }
+    const RECORD_BATCH_HEADER_LEN: usize = 61;
+    const RECORD_BATCH_MAGIC: u8 = 2;
+    const COMPRESSION_CODEC_MASK: i16 = 0x7;
 
     fn get_topics_name_offset(api_key: u16, api_version: u16) -> Option<usize> {
         match api_key {
@@ -435,41 +466,24 @@ impl KafkaLog {
     }
 
     fn decode_topics_name(payload: &[u8], client_id_len: usize, info: &mut KafkaInfo) {
-        let Some(mut topic_offset) = Self::get_topics_name_offset(info.api_key, info.api_version)
+        let Some(topic_offset) = Self::get_topics_name_offset(info.api_key, info.api_version)
         else {
             return;
         };
-        topic_offset += client_id_len;
-        match (info.api_key, info.api_version) {
-            (KAFKA_PRODUCE, 9) if topic_offset + 1 < payload.len() => {
-                let (topic_count, offset) = Self::decode_varint(&payload[topic_offset..]);
-                if offset == 0 || topic_count <= 1 {
-                    return;
-                }
-                topic_offset += offset;
-                let (mut topic_name_len, offset) = Self::decode_varint(&payload[topic_offset..]);
-                if offset == 0 {
-                    return;
-                }
-                topic_offset += offset;
-                topic_name_len -= 1;
-                if topic_name_len <= payload[topic_offset..].len() {
-                    info.topic_name = String::from_utf8_lossy(
-                        &payload[topic_offset..topic_offset + topic_name_len],
-                    )
-                    .into_owned();
-                }
-            }
-            _ if topic_offset + 2 < payload.len() => {
-                let topic_name_len = read_u16_be(&payload[topic_offset..]) as usize;
-                if topic_name_len <= payload[topic_offset + 2..].len() {
-                    info.topic_name = String::from_utf8_lossy(
-                        &payload[topic_offset + 2..topic_offset + 2 + topic_name_len],
-                    )
-                    .into_owned();
-                }
-            }
-            _ => return,
+        let mut reader = PayloadReader::new(payload);
+        if reader.seek(topic_offset + client_id_len).is_err() {
+            return;
+        }
+        let topic_name = match (info.api_key, info.api_version) {
+            // topic_data is a COMPACT_ARRAY whose length is encoded as N + 1
+            (KAFKA_PRODUCE, 9) => match reader.read_unsigned_varint() {
+                Ok(topic_count) if topic_count > 1 => reader.read_compact_string(),
+                _ => return,
+            },
+            _ => reader.read_string(),
+        };
+        if let Ok(topic_name) = topic_name {
+            info.topic_name = pooled_string_lossy(topic_name);
         }
     }
 
@@ -505,6 +519,181 @@ impl KafkaLog {
         }
     }
 
+    // Produce Request (Version: 3-9) => transactional_id acks timeout_ms [topic_data]
+    //     topic_data => name [partition_data]
+    //         partition_data => index records
+    // Only headers of the first record in the first record batch are returned, which is
+    // enough for trace context since a producer injects the same carrier into every record.
+    // Message sets of produce v0-2 have no headers and compressed batches are skipped.
+    fn produce_record_headers(payload: &[u8], api_version: u16) -> Vec<(&[u8], &[u8])> {
+        let mut headers = vec![];
+        if (3..=9).contains(&api_version) {
+            let _ = Self::decode_record_headers(payload, api_version >= 9, &mut headers);
+        }
+        headers
+    }
+
+    fn decode_record_headers<'a>(
+        payload: &'a [u8],
+        flexible: bool,
+        headers: &mut Vec<(&'a [u8], &'a [u8])>,
+    ) -> Result<(), ReadError> {
+        let mut reader = PayloadReader::new(payload);
+        // message_size api_key api_version correlation_id
+        reader.skip(12)?;
+        // client_id is a NULLABLE_STRING in all header versions
+        reader.read_string()?;
+        let records_len = if flexible {
+            // request header TAG_BUFFER
+            for _ in 0..reader.read_unsigned_varint()? {
+                reader.read_unsigned_varint()?;
+                let size = reader.read_unsigned_varint()?;
+                reader.skip(size as usize)?;
+            }
+            // transactional_id
+            reader.read_compact_string()?;
+            // acks timeout_ms
+            reader.skip(6)?;
+            // topic_data and partition_data are COMPACT_ARRAY with length N + 1
+            if reader.read_unsigned_varint()? < 2 {
+                return Ok(());
+            }
+            reader.read_compact_string()?;
+            if reader.read_unsigned_varint()? < 2 {
+                return Ok(());
+            }
+            // index
+            reader.skip(4)?;
+            // records => COMPACT_RECORDS
+            match reader.read_unsigned_varint()?.checked_sub(1) {
+                Some(len) => len as usize,
+                None => return Ok(()),
+            }
+        } else {
+            reader.read_string()?;
+            reader.skip(6)?;
+            if reader.read_i32_be()? < 1 {
+                return Ok(());
+            }
+            reader.read_string()?;
+            if reader.read_i32_be()? < 1 {
+                return Ok(());
+            }
+            reader.skip(4)?;
+            match reader.read_i32_be()? {
+                len if len >= 0 => len as usize,
+                _ => return Ok(()),
+            }
+        };
+        if records_len < Self::RECORD_BATCH_HEADER_LEN {
+            return Ok(());
+        }
+
+        // RecordBatch => baseOffset batchLength partitionLeaderEpoch magic crc attributes
+        //     lastOffsetDelta baseTimestamp maxTimestamp producerId producerEpoch baseSequence
+        //     [records]
+        reader.skip(16)?;
+        if reader.read_u8()? != Self::RECORD_BATCH_MAGIC {
+            return Ok(());
+        }
+        reader.skip(4)?;
+        if reader.read_i16_be()? & Self::COMPRESSION_CODEC_MASK != 0 {
+            return Ok(());
+        }
+        reader.skip(34)?;
+        if reader.read_i32_be()? < 1 {
+            return Ok(());
+        }
+
+        // Record => length attributes timestampDelta offsetDelta key value [headers]
+        //     Header => headerKey headerValue
+        // lengths are zigzag varint and -1 for null
+        reader.read_varint()?;
+        reader.skip(1)?;
+        reader.read_varint()?;
+        reader.read_varint()?;
+        for _ in 0..2 {
+            let len = reader.read_varint()?;
+            reader.skip(len.max(0) as usize)?;
+        }
+        for _ in 0..reader.read_varint()? {
+            let len = reader.read_varint()?;
+            let key = reader.read_bytes(len.max(0) as usize)?;
+            let len = reader.read_varint()?;
+            let value = reader.read_bytes(len.max(0) as usize)?;
+            headers.push((key, value));
+        }
+        Ok(())
+    }
+
+    fn find_record_header<'a>(headers: &[(&'a [u8], &'a [u8])], key: &str) -> Option<&'a [u8]> {
+        headers
+            .iter()
+            .find(|(k, v)| *k == key.as_bytes() && !v.is_empty())
+            .map(|(_, v)| &v[..v.len().min(Self::MAX_TRACE_ID)])
+    }
+
+    // sw8 header of produce records, injected by skywalking kafka plugin
+    // Example: '1-{trace-id}-{segment-id}-{span-id}-{other}'
+    fn decode_sw8_record_header(headers: &[(&[u8], &[u8])], info: &mut KafkaInfo) {
+        let tag = TraceType::Sw8.to_string();
+        let Some(value) = Self::find_record_header(headers, &tag) else {
+            return;
+        };
+        let value = String::from_utf8_lossy(value);
+        if info.trace_id.is_empty() {
+            if let Some(id) = HttpLog::decode_id(&value, &tag, HttpLog::TRACE_ID) {
+                info.trace_id = id;
+            }
+        }
+        if info.span_id.is_empty() {
+            if let Some(id) = HttpLog::decode_id(&value, &tag, HttpLog::SPAN_ID) {
+                info.span_id = id;
+            }
+        }
+    }
+
+    fn decode_custom_trace_context(
+        headers: &[(&[u8], &[u8])],
+        config: &L7LogDynamicConfig,
+        info: &mut KafkaInfo,
+    ) {
+        for t in config.trace_types.iter() {
+            if !info.trace_id.is_empty() {
+                break;
+            }
+            let TraceType::Customize(name) = t else {
+                continue;
+            };
+            let (Some(c), Some(value)) = (
+                config.custom_trace_context(name),
+                Self::find_record_header(headers, name),
+            ) else {
+                continue;
+            };
+            if let Some(id) = c.decode_trace_id(&String::from_utf8_lossy(value)) {
+                info.trace_id = id;
+            }
+        }
+        for t in config.span_types.iter() {
+            if !info.span_id.is_empty() {
+                break;
+            }
+            let TraceType::Customize(name) = t else {
+                continue;
+            };
+            let (Some(c), Some(value)) = (
+                config.custom_trace_context(name),
+                Self::find_record_header(headers, name),
+            ) else {
+                continue;
+            };
+            if let Some(id) = c.decode_span_id(&String::from_utf8_lossy(value)) {
+                info.span_id = id;
+            }
+        }
+    }
+
     // Example: 'sw8  1-{trace-id}-{other}'
     fn decode_sw8_trace_id(payload: &str, info: &mut KafkaInfo) {
         let tag = TraceType::Sw8.to_string();
@@ -542,37 +731,40 @@ impl KafkaLog {
     // The protocol identification is strictly checked to avoid misidentification.
     // The log analysis is not strictly checked because there may be length truncation
     fn request(&mut self, payload: &[u8], strict: bool, info: &mut KafkaInfo) -> Result<()> {
-        let req_len = read_u32_be(payload);
+        // Request Header => message_size api_key api_version correlation_id client_id
+        let mut reader = PayloadReader::new(payload);
+        let req_len = reader.read_u32_be()?;
         info.req_msg_size = Some(req_len);
-        let client_id_len = read_u16_be(&payload[12..]) as usize;
-        if payload.len() < KAFKA_REQ_HEADER_LEN + client_id_len {
+        if strict && req_len as usize != payload.len() - Self::MSG_LEN_SIZE {
             return Err(Error::KafkaLogParseFailed);
         }
-
-        if strict && req_len as usize != payload.len() - Self::MSG_LEN_SIZE {
+        let api_key = reader.read_u16_be()?;
+        let api_version = reader.read_u16_be()?;
+        let correlation_id = reader.read_u32_be()?;
+        let client_id = reader.read_string()?;
+        if !client_id.is_ascii() {
             return Err(Error::KafkaLogParseFailed);
         }
 
         info.msg_type = LogMessageType::Request;
-        info.api_key = read_u16_be(&payload[4..]);
-        info.api_version = read_u16_be(&payload[6..]);
-        info.correlation_id = read_u32_be(&payload[8..]);
-        info.client_id = String::from_utf8_lossy(&payload[14..14 + client_id_len]).into_owned();
-        if !info.client_id.is_ascii() {
-            return Err(Error::KafkaLogParseFailed);
-        }
+        info.api_key = api_key;
+        info.api_version = api_version;
+        info.correlation_id = correlation_id;
         // topic
-        Self::decode_topics_name(payload, client_id_len, info);
+        Self::decode_topics_name(payload, client_id.len(), info);
         // sw8
-        let payload = String::from_utf8_lossy(&payload[14..14 + client_id_len]);
-        Self::decode_sw8_trace_id(&payload, info);
-        Self::decode_traceparent_trace_id(&payload, info);
+        let client_id = String::from_utf8_lossy(client_id);
+        Self::decode_sw8_trace_id(&client_id, info);
+        Self::decode_traceparent_trace_id(&client_id, info);
+        info.client_id = client_id.into_owned();
         Ok(())
     }
 
     fn response(&mut self, payload: &[u8], info: &mut KafkaInfo) -> Result<()> {
-        info.resp_msg_size = Some(read_u32_be(payload));
-        info.correlation_id = read_u32_be(&payload[4..]);
+        // Response Header => message_size correlation_id
+        let mut reader = PayloadReader::new(payload);
+        info.resp_msg_size = Some(reader.read_u32_be()?);
+        info.correlation_id = reader.read_u32_be()?;
         info.msg_type = LogMessageType::Response;
         Ok(())
     }
@@ -646,6 +838,7 @@ mod tests {
 
     use crate::{
         common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        config::TraceContextFormat,
         flow_generator::L7_RRT_CACHE_CAPACITY,
         utils::test::Capture,
     };
@@ -806,4 +999,131 @@ mod tests {
             info.trace_id
         );
     }
+
+    #[test]
+    fn custom_trace_context() {
+        let config = L7LogDynamicConfig::new(
+            "".to_owned(),
+            vec![],
+            vec![],
+            vec![],
+            vec![TraceContextFormat {
+                name: "x-app-trace".to_owned(),
+                delimiter: ":".to_owned(),
+                trace_id_index: Some(0),
+                span_id_index: Some(1),
+                hex_length: 16,
+            }],
+        );
+        let payload = produce_request(3, &[(b"x-app-trace", b"abc123:0def4567")]);
+        let headers = KafkaLog::produce_record_headers(&payload, 3);
+        assert_eq!(
+            KafkaLog::find_record_header(&headers, "x-app-trace"),
+            Some(&b"abc123:0def4567"[..])
+        );
+
+        let mut info = KafkaInfo::default();
+        KafkaLog::decode_custom_trace_context(&headers, &config, &mut info);
+        assert_eq!(info.trace_id, "0000000000abc123");
+        assert_eq!(info.span_id, "0000000000def456");
+    }
+
+    fn push_unsigned_varint(buf: &mut Vec<u8>, mut v: u64) {
+        while v >= 0x80 {
+            buf.push((v & 0x7f) as u8 | 0x80);
+            v >>= 7;
+        }
+        buf.push(v as u8);
+    }
+
+    fn push_varint(buf: &mut Vec<u8>, v: i64) {
+        push_unsigned_varint(buf, ((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    // produce request with a single uncompressed record
+    fn produce_request(api_version: u16, headers: &[(&[u8], &[u8])]) -> Vec<u8> {
+        let mut record = vec![0];
+        // timestampDelta offsetDelta, null key and value "v"
+        push_varint(&mut record, 0);
+        push_varint(&mut record, 0);
+        push_varint(&mut record, -1);
+        push_varint(&mut record, 1);
+        record.push(b'v');
+        push_varint(&mut record, headers.len() as i64);
+        for (k, v) in headers {
+            push_varint(&mut record, k.len() as i64);
+            record.extend_from_slice(k);
+            push_varint(&mut record, v.len() as i64);
+            record.extend_from_slice(v);
+        }
+        let mut batch = vec![0; 16];
+        batch.push(2);
+        batch.extend_from_slice(&[0; 40]);
+        batch.extend_from_slice(&1u32.to_be_bytes());
+        push_varint(&mut batch, record.len() as i64);
+        batch.extend_from_slice(&record);
+
+        let mut payload = vec![0, 0, 0, 0, 0, 0];
+        payload.extend_from_slice(&api_version.to_be_bytes());
+        payload.extend_from_slice(&[0, 0, 0, 1, 0, 3]);
+        payload.extend_from_slice(b"app");
+        if api_version >= 9 {
+            // empty TAG_BUFFER, null transactional_id, acks and timeout_ms
+            payload.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0x75, 0x30]);
+            // one topic "t" with one partition
+            payload.extend_from_slice(&[2, 2, b't', 2, 0, 0, 0, 0]);
+            push_unsigned_varint(&mut payload, batch.len() as u64 + 1);
+        } else {
+            payload.extend_from_slice(&[0xff, 0xff, 0, 1, 0, 0, 0x75, 0x30]);
+            payload.extend_from_slice(&[0, 0, 0, 1, 0, 1, b't', 0, 0, 0, 1, 0, 0, 0, 0]);
+            payload.extend_from_slice(&(batch.len() as u32).to_be_bytes());
+        }
+        payload.extend_from_slice(&batch);
+        let size = payload.len() as u32 - 4;
+        payload[..4].copy_from_slice(&size.to_be_bytes());
+        payload
+    }
+
+    #[test]
+    fn sw8_record_header() {
+        // sw8: 1-{base64 trace id}-{base64 segment id}-{span id}-...
+        let sw8 = b"1-dHJhY2UtMQ==-c2VnbWVudC0x-3-c2VydmljZQ==-aW5zdGFuY2U=-ZW5kcG9pbnQ=-cGVlcg==";
+        for api_version in [3, 8, 9] {
+            let payload = produce_request(api_version, &[(b"x-other", b"1"), (b"sw8", sw8)]);
+            let headers = KafkaLog::produce_record_headers(&payload, api_version);
+            assert_eq!(headers.len(), 2, "api version {}", api_version);
+
+            let mut info = KafkaInfo::default();
+            KafkaLog::decode_sw8_record_header(&headers, &mut info);
+            assert_eq!(info.trace_id, "trace-1");
+            assert_eq!(info.span_id, "segment-1-3");
+        }
+
+        // the header value is cut off by packet truncation
+        let mut payload = produce_request(3, &[(b"sw8", sw8)]);
+        payload.truncate(payload.len() - 10);
+        assert!(KafkaLog::produce_record_headers(&payload, 3).is_empty());
+        // message sets of produce v2 have no headers
+        assert!(KafkaLog::produce_record_headers(&payload, 2).is_empty());
+    }
+
+    #[test]
+    fn truncated_request() {
+        let mut parser = KafkaLog::default();
+        // produce v3 request with client id "app" and a topic name truncated
+        let mut payload = vec![0, 0, 0, 0x40, 0, 0, 0, 3, 0, 0, 0, 1, 0, 3];
+        payload.extend_from_slice(b"app");
+        payload.extend_from_slice(&[0xff; 12]);
+        payload.extend_from_slice(&[0, 0x20, b't']);
+        let mut info = KafkaInfo::default();
+        assert!(parser.request(&payload, false, &mut info).is_ok());
+        assert_eq!(info.client_id, "app");
+        assert_eq!(info.correlation_id, 1);
+        assert!(info.topic_name.is_empty());
+
+        // client id longer than the payload
+        payload.truncate(15);
+        let mut info = KafkaInfo::default();
+        assert!(parser.request(&payload, false, &mut info).is_err());
+    }
 }
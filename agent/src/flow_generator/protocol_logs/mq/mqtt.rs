@@ -98,6 +98,18 @@ impl L7ProtocolInfoInterface for MqttInfo {
     fn is_tls(&self) -> bool {
         self.is_tls
     }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+
+    fn request_len(&self) -> Option<u32> {
+        self.req_msg_size
+    }
+
+    fn response_len(&self) -> Option<u32> {
+        self.res_msg_size
+    }
 }
 
 pub fn topics_format<S>(t: &Option<Vec<MqttTopic>>, serializer: S) -> Result<S::Ok, S::Error>
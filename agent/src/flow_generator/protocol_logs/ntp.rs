@@ -0,0 +1,562 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// NTP, reference: https://www.rfc-editor.org/rfc/rfc5905
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |LI | VN  |Mode |    Stratum     |     Poll      |  Precision   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Root Delay                            |
+// |                         Root Dispersion                       |
+// |                          Reference ID                         |
+// |                     Reference Timestamp (64)                  |
+// |                      Origin Timestamp (64)                    |
+// |                      Receive Timestamp (64)                   |
+// |                      Transmit Timestamp (64)                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The origin timestamp of a response is the transmit timestamp of its request, which is
+// used as the session id. Clock offset and network delay of the client are estimated with
+// the origin, receive and transmit timestamps and the rrt measured by agent.
+
+use serde::Serialize;
+
+use super::pb_adapter::{
+    ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, MetricKeyVal,
+};
+use super::{value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    flow_generator::error::{Error, Result},
+    utils::bytes::{read_u32_be, read_u64_be},
+};
+use public::l7_protocol::L7Protocol;
+
+pub const NTP_PORT: u16 = 123;
+
+const HEADER_SIZE: usize = 48;
+const STRATUM_OFFSET: usize = 1;
+const POLL_OFFSET: usize = 2;
+const PRECISION_OFFSET: usize = 3;
+const ROOT_DELAY_OFFSET: usize = 4;
+const ROOT_DISPERSION_OFFSET: usize = 8;
+const REFERENCE_ID_OFFSET: usize = 12;
+const ORIGIN_TIMESTAMP_OFFSET: usize = 24;
+const RECEIVE_TIMESTAMP_OFFSET: usize = 32;
+const TRANSMIT_TIMESTAMP_OFFSET: usize = 40;
+
+const MODE_SYMMETRIC_ACTIVE: u8 = 1;
+const MODE_SYMMETRIC_PASSIVE: u8 = 2;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const MODE_BROADCAST: u8 = 5;
+
+const LEAP_ALARM: u8 = 3;
+// stratum 0 of server is kiss-o'-death, 16 is unsynchronized
+const STRATUM_KISS_OF_DEATH: u8 = 0;
+const STRATUM_PRIMARY: u8 = 1;
+const STRATUM_UNSYNCHRONIZED: u8 = 16;
+
+// seconds from 1900-01-01 (ntp era 0) to 1970-01-01
+const UNIX_EPOCH_OFFSET: u64 = 2_208_988_800;
+
+fn mode_str(mode: u8) -> &'static str {
+    match mode {
+        MODE_SYMMETRIC_ACTIVE => "symmetric active",
+        MODE_SYMMETRIC_PASSIVE => "symmetric passive",
+        MODE_CLIENT => "client",
+        MODE_SERVER => "server",
+        MODE_BROADCAST => "broadcast",
+        _ => "",
+    }
+}
+
+// converts ntp timestamp to unix time in microseconds
+fn to_unix_micros(timestamp: u64) -> Option<u64> {
+    if timestamp == 0 {
+        return None;
+    }
+    let mut seconds = timestamp >> 32;
+    // timestamps before 1970 are in era 1, which begins at 2036-02-07
+    if seconds < UNIX_EPOCH_OFFSET {
+        seconds += 1 << 32;
+    }
+    let micros = ((timestamp & 0xffffffff) * 1_000_000) >> 32;
+    Some((seconds - UNIX_EPOCH_OFFSET) * 1_000_000 + micros)
+}
+
+// returns (offset, delay) of client clock in microseconds:
+//   t1 = origin, t2 = receive, t3 = transmit, t4 = t1 + rrt
+//   offset = ((t2 - t1) + (t3 - t4)) / 2
+//   delay = (t4 - t1) - (t3 - t2)
+fn clock_offset_and_delay(
+    origin: u64,
+    receive: u64,
+    transmit: u64,
+    rrt: u64,
+) -> Option<(i64, i64)> {
+    let t1 = to_unix_micros(origin)? as i64;
+    let t2 = to_unix_micros(receive)? as i64;
+    let t3 = to_unix_micros(transmit)? as i64;
+    let t4 = t1 + rrt as i64;
+    Some((((t2 - t1) + (t3 - t4)) / 2, ((t4 - t1) - (t3 - t2)).max(0)))
+}
+
+// root delay and root dispersion are in ntp short format, seconds of 16.16 fixed point
+fn short_to_micros(value: u32) -> u32 {
+    ((value as u64 * 1_000_000) >> 16) as u32
+}
+
+fn format_timestamp(timestamp: u64) -> Option<String> {
+    to_unix_micros(timestamp).map(|t| format!("{}.{:06}", t / 1_000_000, t % 1_000_000))
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct NtpInfo {
+    #[serde(skip)]
+    session_id: u32,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub version: u8,
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    pub request_mode: &'static str,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub response_mode: &'static str,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub leap: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stratum: Option<u8>,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub poll: i8,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub precision: i8,
+    // microseconds
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub root_delay: u32,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub root_dispersion: u32,
+    // kiss code or reference clock of stratum 0 and 1, otherwise address of upstream server
+    #[serde(rename = "response_result", skip_serializing_if = "value_is_default")]
+    pub reference_id: String,
+
+    // ntp timestamps of the response
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub origin_timestamp: u64,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub receive_timestamp: u64,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub transmit_timestamp: u64,
+    // microseconds, estimated with rrt of the session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<i64>,
+
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub exception: String,
+
+    msg_type: LogMessageType,
+    rrt: u64,
+}
+
+impl L7ProtocolInfoInterface for NtpInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.session_id)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::NtpInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::NTP,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+}
+
+impl NtpInfo {
+    pub fn merge(&mut self, other: &mut Self) {
+        self.response_mode = other.response_mode;
+        self.leap = other.leap;
+        self.stratum = other.stratum;
+        self.poll = other.poll;
+        self.precision = other.precision;
+        self.root_delay = other.root_delay;
+        self.root_dispersion = other.root_dispersion;
+        std::mem::swap(&mut self.reference_id, &mut other.reference_id);
+        self.origin_timestamp = other.origin_timestamp;
+        self.receive_timestamp = other.receive_timestamp;
+        self.transmit_timestamp = other.transmit_timestamp;
+        self.offset = other.offset;
+        self.delay = other.delay;
+        self.status = other.status;
+        std::mem::swap(&mut self.exception, &mut other.exception);
+    }
+}
+
+impl From<NtpInfo> for L7ProtocolSendLog {
+    fn from(f: NtpInfo) -> Self {
+        let mut attributes = vec![];
+        if !f.response_mode.is_empty() {
+            attributes.push(KeyVal {
+                key: "ntp_response_mode".to_string(),
+                val: f.response_mode.to_string(),
+            });
+        }
+        if let Some(stratum) = f.stratum {
+            for (key, val) in [
+                ("ntp_stratum", stratum.to_string()),
+                ("ntp_leap", f.leap.to_string()),
+                ("ntp_poll", f.poll.to_string()),
+                ("ntp_precision", f.precision.to_string()),
+                ("ntp_root_delay", f.root_delay.to_string()),
+                ("ntp_root_dispersion", f.root_dispersion.to_string()),
+            ] {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        for (key, timestamp) in [
+            ("ntp_origin_timestamp", f.origin_timestamp),
+            ("ntp_receive_timestamp", f.receive_timestamp),
+            ("ntp_transmit_timestamp", f.transmit_timestamp),
+        ] {
+            if let Some(val) = format_timestamp(timestamp) {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        let mut metrics = vec![];
+        if let Some(offset) = f.offset {
+            metrics.push(MetricKeyVal {
+                key: "ntp_offset".to_string(),
+                val: offset as f32,
+            });
+        }
+        if let Some(delay) = f.delay {
+            metrics.push(MetricKeyVal {
+                key: "ntp_delay".to_string(),
+                val: delay as f32,
+            });
+        }
+        L7ProtocolSendLog {
+            req: L7Request {
+                req_type: f.request_mode.to_string(),
+                ..Default::default()
+            },
+            resp: L7Response {
+                result: f.reference_id,
+                status: f.status,
+                exception: f.exception,
+                ..Default::default()
+            },
+            version: Some(f.version.to_string()),
+            ext_info: Some(ExtendedInfo {
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                metrics: if metrics.is_empty() {
+                    None
+                } else {
+                    Some(metrics)
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct NtpLog {
+    perf_stats: Option<L7PerfStats>,
+}
+
+impl L7ProtocolParserInterface for NtpLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::UDP {
+            return false;
+        }
+        match Self::parse(payload) {
+            // the transmit timestamp of request is always set
+            Ok(info) => info.msg_type == LogMessageType::Request && info.session_id != 0,
+            Err(_) => false,
+        }
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+        let mut info = Self::parse(payload)?;
+        if let Some(perf_stats) = self.perf_stats.as_mut() {
+            match info.msg_type {
+                LogMessageType::Request => perf_stats.inc_req(),
+                _ => perf_stats.inc_resp(),
+            }
+            match info.status {
+                L7ResponseStatus::ClientError => perf_stats.inc_req_err(),
+                L7ResponseStatus::ServerError => perf_stats.inc_resp_err(),
+                _ => {}
+            }
+        }
+        info.cal_rrt(param, None).map(|rrt| {
+            info.rrt = rrt;
+            self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+        });
+        if info.msg_type == LogMessageType::Response && info.rrt > 0 {
+            if let Some((offset, delay)) = clock_offset_and_delay(
+                info.origin_timestamp,
+                info.receive_timestamp,
+                info.transmit_timestamp,
+                info.rrt,
+            ) {
+                info.offset = Some(offset);
+                info.delay = Some(delay);
+            }
+        }
+        if param.parse_log {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::NtpInfo(info)))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::NTP
+    }
+
+    fn parsable_on_tcp(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl NtpLog {
+    fn parse(payload: &[u8]) -> Result<NtpInfo> {
+        // extension fields and mac are word aligned
+        if payload.len() < HEADER_SIZE || payload.len() % 4 != 0 {
+            let err_msg = format!("ntp payload length invalid:{}", payload.len());
+            return Err(Error::NtpLogParseFailed(err_msg));
+        }
+        let (leap, version, mode) = (payload[0] >> 6, (payload[0] >> 3) & 0x7, payload[0] & 0x7);
+        if !(1..=4).contains(&version) {
+            let err_msg = format!("ntp version {} invalid", version);
+            return Err(Error::NtpLogParseFailed(err_msg));
+        }
+        // control and private messages are in other formats
+        let mode_name = mode_str(mode);
+        if mode_name.is_empty() {
+            let err_msg = format!("ntp mode {} unsupported", mode);
+            return Err(Error::NtpLogParseFailed(err_msg));
+        }
+        let transmit_timestamp = read_u64_be(&payload[TRANSMIT_TIMESTAMP_OFFSET..]);
+        let mut info = NtpInfo {
+            version,
+            ..Default::default()
+        };
+        if mode == MODE_CLIENT || mode == MODE_SYMMETRIC_ACTIVE {
+            info.msg_type = LogMessageType::Request;
+            info.request_mode = mode_name;
+            info.session_id = transmit_timestamp as u32;
+            return Ok(info);
+        }
+
+        let origin_timestamp = read_u64_be(&payload[ORIGIN_TIMESTAMP_OFFSET..]);
+        let stratum = payload[STRATUM_OFFSET];
+        info.msg_type = LogMessageType::Response;
+        info.response_mode = mode_name;
+        info.session_id = origin_timestamp as u32;
+        info.leap = leap;
+        info.stratum = Some(stratum);
+        info.poll = payload[POLL_OFFSET] as i8;
+        info.precision = payload[PRECISION_OFFSET] as i8;
+        info.root_delay = short_to_micros(read_u32_be(&payload[ROOT_DELAY_OFFSET..]));
+        info.root_dispersion = short_to_micros(read_u32_be(&payload[ROOT_DISPERSION_OFFSET..]));
+        let reference_id = &payload[REFERENCE_ID_OFFSET..REFERENCE_ID_OFFSET + 4];
+        info.reference_id = if stratum <= STRATUM_PRIMARY {
+            String::from_utf8_lossy(reference_id)
+                .trim_end_matches('\0')
+                .to_string()
+        } else {
+            format!(
+                "{}.{}.{}.{}",
+                reference_id[0], reference_id[1], reference_id[2], reference_id[3]
+            )
+        };
+        info.origin_timestamp = origin_timestamp;
+        info.receive_timestamp = read_u64_be(&payload[RECEIVE_TIMESTAMP_OFFSET..]);
+        info.transmit_timestamp = transmit_timestamp;
+
+        if stratum == STRATUM_KISS_OF_DEATH {
+            // the server asks client to slow down or stop, e.g. RATE and DENY
+            info.status = L7ResponseStatus::ClientError;
+            info.exception = format!("kiss code {}", info.reference_id);
+        } else if leap == LEAP_ALARM || stratum >= STRATUM_UNSYNCHRONIZED {
+            info.status = L7ResponseStatus::ServerError;
+            info.exception = "clock unsynchronized".to_string();
+        }
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::*;
+
+    use crate::{
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    // 2023-11-14 22:13:20 UTC
+    const T1: u64 = (1_700_000_000 + UNIX_EPOCH_OFFSET) << 32;
+
+    fn ntp(first: u8, stratum: u8, reference_id: &[u8; 4], timestamps: [u64; 3]) -> Vec<u8> {
+        let mut m = vec![first, stratum, 6, 0xe9];
+        // root delay 0.5s and root dispersion 0.25s
+        m.extend_from_slice(&[0, 0, 0x80, 0, 0, 0, 0x40, 0]);
+        m.extend_from_slice(reference_id);
+        m.extend_from_slice(&[0; 8]);
+        for t in timestamps {
+            m.extend_from_slice(&t.to_be_bytes());
+        }
+        m
+    }
+
+    #[test]
+    fn test_ntp() {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        packet.lookup_key.src_port = 40000;
+        packet.lookup_key.dst_port = NTP_PORT;
+        packet.lookup_key.direction = PacketDirection::ClientToServer;
+        packet.lookup_key.timestamp = Duration::from_secs(T1 >> 32).into();
+
+        let transmit = T1 | 0x12345678;
+        // version 4, client
+        let request = ntp(0x23, 0, &[0; 4], [0, 0, transmit]);
+        let mut ntp_log = NtpLog::default();
+        let param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        assert!(ntp_log.check_payload(&request, &param));
+        let req = match ntp_log
+            .parse_payload(&request, &param)
+            .unwrap()
+            .unwrap_single()
+        {
+            L7ProtocolInfo::NtpInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(req.msg_type, LogMessageType::Request);
+        assert_eq!(req.request_mode, "client");
+        assert_eq!(req.session_id(), Some(0x12345678));
+
+        // server clock is 1s ahead, received and transmitted in 10ms
+        let receive = (T1 + (1 << 32)) | 0x12345678;
+        let response = ntp(
+            0x24,
+            2,
+            &[10, 0, 0, 1],
+            [transmit, receive, receive + (1 << 32) / 100],
+        );
+        packet.lookup_key.direction = PacketDirection::ServerToClient;
+        packet.lookup_key.timestamp = Duration::from_secs((T1 >> 32) + 1).into();
+        let param = ParseParam::new(&packet, log_cache, true, true);
+        assert!(!ntp_log.check_payload(&response, &param));
+        let resp = match ntp_log
+            .parse_payload(&response, &param)
+            .unwrap()
+            .unwrap_single()
+        {
+            L7ProtocolInfo::NtpInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(resp.msg_type, LogMessageType::Response);
+        assert_eq!(resp.session_id(), Some(0x12345678));
+        assert_eq!(resp.stratum, Some(2));
+        assert_eq!(resp.reference_id, "10.0.0.1");
+        assert_eq!(resp.root_delay, 500000);
+        assert_eq!(resp.root_dispersion, 250000);
+        assert_eq!(resp.poll, 6);
+        assert_eq!(resp.precision, -23);
+        assert_eq!(resp.status, L7ResponseStatus::Ok);
+        assert_eq!(
+            format_timestamp(resp.receive_timestamp).as_deref(),
+            Some("1700000001.071111")
+        );
+
+        // kiss-o'-death
+        let kod = ntp(0xe4, 0, b"RATE", [transmit, 0, 0]);
+        let resp = NtpLog::parse(&kod).unwrap();
+        assert_eq!(resp.status, L7ResponseStatus::ClientError);
+        assert_eq!(resp.exception, "kiss code RATE");
+
+        assert!(NtpLog::parse(&request[..40]).is_err());
+        assert!(NtpLog::parse(&ntp(0x07, 0, &[0; 4], [0, 0, transmit])).is_err());
+    }
+
+    #[test]
+    fn test_clock_offset_and_delay() {
+        // request is sent at t1 by client, response is received 100ms later, the server
+        // clock is 1s ahead and it takes 20ms to process the request
+        let receive = T1 + (1 << 32) + (1 << 32) * 40 / 1000;
+        let transmit = receive + (1 << 32) * 20 / 1000;
+        let (offset, delay) = clock_offset_and_delay(T1, receive, transmit, 100_000).unwrap();
+        assert!((offset - 1_000_000).abs() <= 1);
+        assert!((delay - 80_000).abs() <= 1);
+
+        assert_eq!(clock_offset_and_delay(0, receive, transmit, 100_000), None);
+    }
+}
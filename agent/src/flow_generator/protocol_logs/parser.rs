@@ -28,11 +28,18 @@ use std::{
 };
 
 use arc_swap::access::Access;
-use log::{info, warn};
+use log::{debug, info, warn};
 use rand::prelude::{Rng, SeedableRng, SmallRng};
 use serde::Serialize;
 
-use super::{AppProtoHead, AppProtoLogsBaseInfo, BoxAppProtoLogsData, LogMessageType};
+use super::{
+    dedup::{DedupResult, L7LogDeduplicator},
+    field_extraction::FieldExtractor,
+    field_limit::FieldLimiter,
+    masking::Masker,
+    pb_adapter::L7ProtocolSendLog,
+    AppProtoHead, AppProtoLogsBaseInfo, BoxAppProtoLogsData, LogMessageType,
+};
 
 use crate::{
     common::{
@@ -44,6 +51,7 @@ use crate::{
     config::handler::LogParserAccess,
     flow_generator::{error::Result, FLOW_METRICS_PEER_DST, FLOW_METRICS_PEER_SRC},
     metric::document::TapSide,
+    plugin::script::ScriptHook,
     rpc::get_timestamp,
     utils::stats::{Counter, CounterType, CounterValue, RefCountable},
 };
@@ -72,6 +80,9 @@ pub struct MetaAppProto {
     pub direction_score: u8,
     #[serde(flatten)]
     pub l7_info: L7ProtocolInfo,
+    // l7_info transformed by field extraction rules and the l7 log script, sent instead of l7_info if present
+    #[serde(skip)]
+    pub send_log: Option<L7ProtocolSendLog>,
 }
 
 impl fmt::Display for MetaAppProto {
@@ -131,6 +142,7 @@ impl MetaAppProto {
             syscall_coroutine_1: 0,
             syscall_cap_seq_0: 0,
             syscall_cap_seq_1: 0,
+            unix_socket_path: meta_packet.unix_socket_path.clone(),
             ebpf_type: meta_packet.ebpf_type,
             pod_id_0: 0,
             pod_id_1: 0,
@@ -194,6 +206,7 @@ impl MetaAppProto {
             direction: meta_packet.lookup_key.direction,
             direction_score: flow.flow.direction_score,
             l7_info,
+            send_log: None,
         })
     }
 
@@ -258,6 +271,11 @@ pub struct SessionAggrCounter {
     merge: AtomicU64,
     cached: AtomicU64,
     throttle_drop: AtomicU64,
+    script_drop: AtomicU64,
+    script_error: AtomicU64,
+    masked: AtomicU64,
+    truncated: AtomicU64,
+    deduplicated: AtomicU64,
 }
 
 impl RefCountable for SessionAggrCounter {
@@ -288,6 +306,31 @@ impl RefCountable for SessionAggrCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.throttle_drop.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "script-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.script_drop.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "script-error",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.script_error.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "masked",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.masked.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "truncated",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.truncated.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "deduplicated",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.deduplicated.swap(0, Ordering::Relaxed)),
+            ),
         ]
     }
 }
@@ -350,9 +393,17 @@ struct SessionQueue {
     time_window: Option<Vec<HashMap<u64, Box<MetaAppProto>>>>,
 
     throttle: Throttle,
+    field_extractor: FieldExtractor,
+    script_hook: ScriptHook,
+    masker: Masker,
+    field_limiter: FieldLimiter,
+    deduplicator: Arc<L7LogDeduplicator>,
 
     counter: Arc<SessionAggrCounter>,
     output_queue: DebugSender<BoxAppProtoLogsData>,
+    otlp_output: Option<DebugSender<BoxAppProtoLogsData>>,
+    kafka_output: Option<DebugSender<BoxAppProtoLogsData>>,
+    clickhouse_output: Option<DebugSender<BoxAppProtoLogsData>>,
     config: LogParserAccess,
     ntp_diff: Arc<AtomicI64>,
 }
@@ -361,14 +412,22 @@ impl SessionQueue {
     fn new(
         counter: Arc<SessionAggrCounter>,
         output_queue: DebugSender<BoxAppProtoLogsData>,
+        otlp_output: Option<DebugSender<BoxAppProtoLogsData>>,
+        kafka_output: Option<DebugSender<BoxAppProtoLogsData>>,
+        clickhouse_output: Option<DebugSender<BoxAppProtoLogsData>>,
         config: LogParserAccess,
         ntp_diff: Arc<AtomicI64>,
+        deduplicator: Arc<L7LogDeduplicator>,
     ) -> Self {
         //l7_log_session_timeout 20s-300s ，window_size = 4-60，所以 SessionQueue.time_window 预分配内存
         let window_size =
             (config.load().l7_log_session_aggr_timeout.as_secs() / SLOT_WIDTH) as usize;
         let time_window = vec![HashMap::new(); window_size];
         let throttle = Throttle::new(config.clone(), SLOT_WIDTH);
+        let field_extractor = FieldExtractor::new(&config.load().field_extraction_rules);
+        let script_hook = ScriptHook::new(&config.load().l7_log_scripts);
+        let masker = Masker::new(&config.load().l7_log_masking);
+        let field_limiter = FieldLimiter::new(&config.load().l7_log_field_limits);
         Self {
             aggregate_start_time: Duration::ZERO,
             last_flush_time: Duration::ZERO,
@@ -378,9 +437,17 @@ impl SessionQueue {
             window_size,
 
             throttle,
+            field_extractor,
+            script_hook,
+            masker,
+            field_limiter,
+            deduplicator,
 
             counter,
             output_queue,
+            otlp_output,
+            kafka_output,
+            clickhouse_output,
         }
     }
 
@@ -565,7 +632,11 @@ impl SessionQueue {
             self.counter
                 .cached
                 .fetch_sub(map.len() as u64, Ordering::Relaxed);
-            for item in map.into_values() {
+            for mut item in map.into_values() {
+                if !self.post_process(&mut item) {
+                    continue;
+                }
+                self.output_exporters(&item);
                 if batch.len() >= QUEUE_BATCH_SIZE {
                     if let Err(queue::Error::Terminated(..)) =
                         self.output_queue.send_all(&mut batch)
@@ -584,6 +655,9 @@ impl SessionQueue {
             }
         }
         self.time_window.replace(time_window);
+        // logs held for deduplication are sent without waiting for their counterparts
+        let now = get_timestamp(self.ntp_diff.load(Ordering::Relaxed));
+        self.flush_deduplicated(now + self.config.load().l7_log_deduplication.window);
     }
 
     fn calc_key(item: &MetaAppProto) -> u64 {
@@ -617,22 +691,141 @@ impl SessionQueue {
             Duration::from_secs(self.aggregate_start_time.as_secs() + n as u64 * SLOT_WIDTH);
     }
 
+    // apply field extraction rules, the l7 log script, masking and field limits
+    // returns false if the item is dropped by the script
+    fn post_process(&mut self, item: &mut MetaAppProto) -> bool {
+        let protocol = item.base_info.head.proto;
+        let has_rules = self.field_extractor.contains(protocol);
+        let has_script = self.script_hook.contains(protocol);
+        let has_masking = self.masker.enabled();
+        let has_limits = self.field_limiter.contains(protocol);
+        if !has_rules && !has_script && !has_masking && !has_limits {
+            return true;
+        }
+        let mut log: L7ProtocolSendLog = item.l7_info.clone().into();
+        if has_rules {
+            self.field_extractor.extract(protocol, &mut log);
+        }
+        if has_script {
+            let mut scripted = log.clone();
+            match self.script_hook.run(protocol, &mut scripted) {
+                Ok(true) => log = scripted,
+                Ok(false) => {
+                    self.counter.script_drop.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                Err(e) => {
+                    self.counter.script_error.fetch_add(1, Ordering::Relaxed);
+                    debug!("l7 log script of {:?} failed: {}", protocol, e);
+                }
+            }
+        }
+        // masking is the last stage so that nothing added by rules or scripts is leaked
+        if has_masking && self.masker.mask(&mut log) {
+            self.counter.masked.fetch_add(1, Ordering::Relaxed);
+        }
+        // truncate after masking so that a sensitive value cut by the limit is still masked
+        if has_limits && self.field_limiter.truncate(protocol, &mut log) {
+            self.counter.truncated.fetch_add(1, Ordering::Relaxed);
+        }
+        item.send_log = Some(log);
+        true
+    }
+
+    fn update_post_process(&mut self) {
+        let config = self.config.load();
+        self.field_extractor.update(&config.field_extraction_rules);
+        self.script_hook.update(&config.l7_log_scripts);
+        self.masker.update(&config.l7_log_masking);
+        self.field_limiter.update(&config.l7_log_field_limits);
+    }
+
     fn send(&mut self, item: Box<MetaAppProto>) {
         if item.l7_info.skip_send() {
             return;
         }
 
+        let config = self.config.load();
+        if !config.l7_log_deduplication.enabled {
+            self.output(item);
+            return;
+        }
+        let now = get_timestamp(self.ntp_diff.load(Ordering::Relaxed));
+        match self
+            .deduplicator
+            .submit(item, config.l7_log_deduplication.window, now)
+        {
+            DedupResult::Held => (),
+            DedupResult::Send(item) => self.output(item),
+            DedupResult::Merged(item) => {
+                self.counter.deduplicated.fetch_add(1, Ordering::Relaxed);
+                self.output(item);
+            }
+        }
+    }
+
+    // send logs from cbpf or ebpf without counterparts in the deduplication window
+    fn flush_deduplicated(&mut self, now: Duration) {
+        for item in self.deduplicator.expire(now) {
+            self.output(item);
+        }
+    }
+
+    fn output(&mut self, mut item: Box<MetaAppProto>) {
+        if !self.post_process(&mut item) {
+            return;
+        }
+
         if !self.throttle.acquire(item.base_info.start_time.into()) {
             self.counter.throttle_drop.fetch_add(1, Ordering::Relaxed);
             return;
         }
 
+        self.output_exporters(&item);
         if let Err(queue::Error::Terminated(..)) = self.output_queue.send(BoxAppProtoLogsData(item))
         {
             warn!("output queue terminated");
         }
     }
 
+    // logs are copied to the exporters after post processing and throttling
+    fn output_exporters(&self, item: &MetaAppProto) {
+        let config = self.config.load();
+        if let Some(otlp_output) = self.otlp_output.as_ref() {
+            if config.otlp_exporter.enabled {
+                if otlp_output
+                    .send(BoxAppProtoLogsData(Box::new(item.clone())))
+                    .is_err()
+                {
+                    debug!("l7 session aggr push log to otlp sender queue failed, maybe queue have terminated");
+                }
+            }
+        }
+        if let Some(kafka_output) = self.kafka_output.as_ref() {
+            if config.kafka_exporter.enabled && !config.kafka_exporter.l7_flow_log_topic.is_empty()
+            {
+                if kafka_output
+                    .send(BoxAppProtoLogsData(Box::new(item.clone())))
+                    .is_err()
+                {
+                    debug!("l7 session aggr push log to kafka sender queue failed, maybe queue have terminated");
+                }
+            }
+        }
+        if let Some(clickhouse_output) = self.clickhouse_output.as_ref() {
+            if config.clickhouse_exporter.enabled
+                && !config.clickhouse_exporter.l7_flow_log_table.is_empty()
+            {
+                if clickhouse_output
+                    .send(BoxAppProtoLogsData(Box::new(item.clone())))
+                    .is_err()
+                {
+                    debug!("l7 session aggr push log to clickhouse sender queue failed, maybe queue have terminated");
+                }
+            }
+        }
+    }
+
     fn send_all(&mut self, items: Vec<Box<MetaAppProto>>) {
         for item in items {
             self.send(item);
@@ -643,33 +836,45 @@ impl SessionQueue {
 pub struct SessionAggregator {
     input_queue: Arc<Receiver<Box<MetaAppProto>>>,
     output_queue: DebugSender<BoxAppProtoLogsData>,
+    otlp_output: Option<DebugSender<BoxAppProtoLogsData>>,
+    kafka_output: Option<DebugSender<BoxAppProtoLogsData>>,
+    clickhouse_output: Option<DebugSender<BoxAppProtoLogsData>>,
     id: u32,
     running: Arc<AtomicBool>,
     thread: Mutex<Option<JoinHandle<()>>>,
     counter: Arc<SessionAggrCounter>,
     config: LogParserAccess,
     ntp_diff: Arc<AtomicI64>,
+    deduplicator: Arc<L7LogDeduplicator>,
 }
 
 impl SessionAggregator {
     pub fn new(
         input_queue: Receiver<Box<MetaAppProto>>,
         output_queue: DebugSender<BoxAppProtoLogsData>,
+        otlp_output: Option<DebugSender<BoxAppProtoLogsData>>,
+        kafka_output: Option<DebugSender<BoxAppProtoLogsData>>,
+        clickhouse_output: Option<DebugSender<BoxAppProtoLogsData>>,
         id: u32,
         config: LogParserAccess,
         ntp_diff: Arc<AtomicI64>,
+        deduplicator: Arc<L7LogDeduplicator>,
     ) -> (Self, Arc<SessionAggrCounter>) {
         let counter: Arc<SessionAggrCounter> = Default::default();
         (
             Self {
                 input_queue: Arc::new(input_queue),
                 output_queue,
+                otlp_output,
+                kafka_output,
+                clickhouse_output,
                 id,
                 running: Default::default(),
                 thread: Mutex::new(None),
                 counter: counter.clone(),
                 config,
                 ntp_diff,
+                deduplicator,
             },
             counter,
         )
@@ -684,21 +889,34 @@ impl SessionAggregator {
         let counter = self.counter.clone();
         let input_queue = self.input_queue.clone();
         let output_queue = self.output_queue.clone();
+        let otlp_output = self.otlp_output.clone();
+        let kafka_output = self.kafka_output.clone();
+        let clickhouse_output = self.clickhouse_output.clone();
 
         let config = self.config.clone();
         let ntp_diff = self.ntp_diff.clone();
+        let deduplicator = self.deduplicator.clone();
 
         let thread = thread::Builder::new()
             .name("protocol-logs-parser".to_owned())
             .spawn(move || {
-                let mut session_queue =
-                    SessionQueue::new(counter, output_queue, config.clone(), ntp_diff);
+                let mut session_queue = SessionQueue::new(
+                    counter,
+                    output_queue,
+                    otlp_output,
+                    kafka_output,
+                    clickhouse_output,
+                    config.clone(),
+                    ntp_diff.clone(),
+                    deduplicator,
+                );
 
                 let mut batch_buffer = Vec::with_capacity(QUEUE_BATCH_SIZE);
 
                 while running.load(Ordering::Relaxed) {
                     match input_queue.recv_all(&mut batch_buffer, Some(RCV_TIMEOUT)) {
                         Ok(_) => {
+                            session_queue.update_post_process();
                             let config = config.load();
                             for app_proto in batch_buffer.drain(..) {
                                 if config.l7_log_ignore_tap_sides
@@ -708,9 +926,15 @@ impl SessionAggregator {
                                 }
                                 session_queue.aggregate_session_and_send(app_proto);
                             }
+                            session_queue.flush_deduplicated(get_timestamp(
+                                ntp_diff.load(Ordering::Relaxed),
+                            ));
                         }
                         Err(queue::Error::Timeout) => {
                             session_queue.flush_one_slot();
+                            session_queue.flush_deduplicated(get_timestamp(
+                                ntp_diff.load(Ordering::Relaxed),
+                            ));
                             continue;
                         }
                         Err(queue::Error::Terminated(..)) => break,
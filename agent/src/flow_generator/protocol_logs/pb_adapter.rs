@@ -14,11 +14,13 @@
  * limitations under the License.
  */
 
+use serde::Serialize;
+
 use super::L7ResponseStatus;
 
 use public::proto::flow_log;
 
-#[derive(Default, Debug)]
+#[derive(Serialize, Default, Debug, Clone)]
 pub struct L7Request {
     pub req_type: String,
     pub domain: String,
@@ -26,7 +28,7 @@ pub struct L7Request {
     pub endpoint: String,
 }
 
-#[derive(Default, Debug)]
+#[derive(Serialize, Default, Debug, Clone)]
 pub struct L7Response {
     pub status: L7ResponseStatus,
     pub code: Option<i32>,
@@ -34,20 +36,20 @@ pub struct L7Response {
     pub result: String,
 }
 
-#[derive(Default, Debug)]
+#[derive(Serialize, Default, Debug, Clone)]
 pub struct TraceInfo {
     pub trace_id: Option<String>,
     pub span_id: Option<String>,
     pub parent_span_id: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct KeyVal {
     pub key: String,
     pub val: String,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct MetricKeyVal {
     pub key: String,
     pub val: f32,
@@ -55,7 +57,7 @@ pub struct MetricKeyVal {
 
 impl Eq for MetricKeyVal {}
 
-#[derive(Default, Debug)]
+#[derive(Serialize, Default, Debug, Clone)]
 pub struct ExtendedInfo {
     pub service_name: Option<String>,
     pub rpc_service: Option<String>,
@@ -77,7 +79,7 @@ pub struct ExtendedInfo {
  *
  * 在 server 中，req_len、resp_len = -1 时会认为没有值； resp.code = -32768 会认为没有值
  */
-#[derive(Default, Debug)]
+#[derive(Serialize, Default, Debug, Clone)]
 pub struct L7ProtocolSendLog {
     pub req_len: Option<u32>,
     pub resp_len: Option<u32>,
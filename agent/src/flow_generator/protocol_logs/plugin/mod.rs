@@ -28,11 +28,16 @@ use crate::{
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use self::shared_obj::{get_so_parser, SoLog};
-use self::{custom_wrap::CustomWrapLog, wasm::get_wasm_parser};
+use self::{
+    custom_wrap::CustomWrapLog,
+    simple::{get_simple_parser, SimpleLog},
+    wasm::get_wasm_parser,
+};
 
 pub mod custom_wrap;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub mod shared_obj;
+pub mod simple;
 pub mod wasm;
 
 #[enum_dispatch(L7ProtocolParserInterface)]
@@ -40,6 +45,7 @@ pub enum CustomLog {
     WasmLog(WasmLog),
     #[cfg(any(target_os = "linux", target_os = "android"))]
     SoLog(SoLog),
+    SimpleLog(SimpleLog),
 }
 
 pub fn get_custom_log_parser(proto: CustomProtocol) -> L7ProtocolParser {
@@ -50,21 +56,26 @@ pub fn get_custom_log_parser(proto: CustomProtocol) -> L7ProtocolParser {
             CustomProtocol::So(p, s) => CustomLog::SoLog(get_so_parser(p, s)),
             #[cfg(target_os = "windows")]
             CustomProtocol::So(_, _) => todo!(),
+            CustomProtocol::Simple(p, s) => CustomLog::SimpleLog(get_simple_parser(p, s)),
         }),
     })
 }
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 #[inline(always)]
-fn all_plugin_log_parser() -> [CustomLog; 2] {
+fn all_plugin_log_parser() -> [CustomLog; 3] {
     [
         CustomLog::WasmLog(WasmLog::default()),
         CustomLog::SoLog(SoLog::default()),
+        CustomLog::SimpleLog(SimpleLog::default()),
     ]
 }
 
 #[cfg(target_os = "windows")]
 #[inline(always)]
-fn all_plugin_log_parser() -> [CustomLog; 1] {
-    [CustomLog::WasmLog(WasmLog::default())]
+fn all_plugin_log_parser() -> [CustomLog; 2] {
+    [
+        CustomLog::WasmLog(WasmLog::default()),
+        CustomLog::SimpleLog(SimpleLog::default()),
+    ]
 }
@@ -34,8 +34,8 @@ use crate::{
     },
     plugin::{
         c_ffi::{
-            c_str_to_string, ParseCtx, ParseInfo, ACTION_CONTINUE, ACTION_ERROR, ACTION_OK,
-            CHECK_PAYLOAD_FUNC_SYM, PARSE_PAYLOAD_FUNC_SYM,
+            c_str_to_string, ParseCtx, ParseInfo, SoPluginFunc, ACTION_CONTINUE, ACTION_ERROR,
+            ACTION_OK, CHECK_PAYLOAD_FUNC_SYM, PARSE_PAYLOAD_FUNC_SYM, SO_PLUGIN_MAX_VIOLATION,
         },
         shared_obj::get_so_plug_metric_counter_map_key,
         CustomInfo,
@@ -44,6 +44,15 @@ use crate::{
 
 const RESULT_LEN: i32 = 8;
 
+fn on_violation(c: &SoPluginFunc) {
+    if c.health.on_violation() {
+        error!(
+            "so plugin {} is disabled after {} malformed results in a row",
+            c.name, SO_PLUGIN_MAX_VIOLATION
+        );
+    }
+}
+
 #[derive(Default)]
 pub struct SoLog {
     proto_num: Option<u8>,
@@ -59,6 +68,9 @@ impl L7ProtocolParserInterface for SoLog {
         let ctx = &ParseCtx::from((param, payload));
 
         for c in c_funcs.as_ref() {
+            if c.health.is_disabled() {
+                continue;
+            }
             let counter = param.so_plugin_counter_map.as_ref().and_then(|h| {
                 h.so_mertic
                     .get(&get_so_plug_metric_counter_map_key(
@@ -104,9 +116,11 @@ impl L7ProtocolParserInterface for SoLog {
                     None => {
                         error!("read proto str from so plugin fail");
                         counter.map(|c| c.fail_cnt.fetch_add(1, Ordering::Relaxed));
+                        on_violation(c);
                         return false;
                     }
                 }
+                c.health.on_success();
                 return true;
             }
         }
@@ -127,6 +141,9 @@ impl L7ProtocolParserInterface for SoLog {
         }
 
         for c in c_funcs.as_ref() {
+            if c.health.is_disabled() {
+                continue;
+            }
             let counter = param.so_plugin_counter_map.as_ref().and_then(|h| {
                 h.so_mertic
                     .get(&get_so_plug_metric_counter_map_key(
@@ -175,12 +192,13 @@ impl L7ProtocolParserInterface for SoLog {
                     if res.len == 0 {
                         return Ok(L7ParseResult::None);
                     }
-                    if res.len > RESULT_LEN {
+                    if res.len < 0 || res.len > RESULT_LEN {
                         error!(
-                            "so plugin {} return large result length {}",
+                            "so plugin {} return invalid result length {}",
                             c.name, res.len
                         );
                         counter.map(|c| c.fail_cnt.fetch_add(1, Ordering::Relaxed));
+                        on_violation(c);
                         return Err(Error::SoReturnUnexpectVal);
                     }
                     let mut v = vec![];
@@ -215,6 +233,7 @@ impl L7ProtocolParserInterface for SoLog {
                                     self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
                                 });
                                 if res.len == 1 {
+                                    c.health.on_success();
                                     return Ok(L7ParseResult::Single(L7ProtocolInfo::CustomInfo(
                                         info,
                                     )));
@@ -224,9 +243,14 @@ impl L7ProtocolParserInterface for SoLog {
                             Err(e) => {
                                 counter.map(|c| c.fail_cnt.fetch_add(1, Ordering::Relaxed));
                                 error!("so plugin {} convert l7 info fail: {}", c.name, e);
+                                on_violation(c);
                             }
                         }
                     }
+                    // the plugin is healthy only if all of the results are converted
+                    if v.len() == res.len as usize {
+                        c.health.on_success();
+                    }
                     return Ok(L7ParseResult::Multi(v));
                 }
                 ACTION_CONTINUE => continue,
@@ -238,6 +262,7 @@ impl L7ProtocolParserInterface for SoLog {
                 _ => {
                     error!("so plugin {} return unknown action {}", c.name, res.action);
                     counter.map(|c| c.fail_cnt.fetch_add(1, Ordering::Relaxed));
+                    on_violation(c);
                     return Err(Error::SoReturnUnexpectVal);
                 }
             }
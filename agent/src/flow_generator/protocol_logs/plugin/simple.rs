@@ -0,0 +1,362 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use log::warn;
+use public::l7_protocol::{CustomProtocol, L7Protocol};
+
+use crate::{
+    common::{
+        flow::{L7PerfStats, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    config::SimpleProtocolDefinition,
+    flow_generator::{Error, Result},
+    plugin::CustomInfo,
+};
+
+// (offset, size) of an unsigned integer field
+type Field = (usize, usize);
+
+fn read_uint(payload: &[u8], field: Field, little_endian: bool) -> Option<u64> {
+    let (offset, size) = field;
+    let bs = payload.get(offset..offset.checked_add(size)?)?;
+    let mut buf = [0u8; 8];
+    if little_endian {
+        buf[..size].copy_from_slice(bs);
+        Some(u64::from_le_bytes(buf))
+    } else {
+        buf[8 - size..].copy_from_slice(bs);
+        Some(u64::from_be_bytes(buf))
+    }
+}
+
+// Table driven parser compiled from SimpleProtocolDefinition
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimpleProtocol {
+    name: String,
+    magic: Vec<u8>,
+    magic_offset: usize,
+    length: Option<Field>,
+    length_adjustment: i64,
+    request_type: Option<Field>,
+    little_endian: bool,
+    resource_offset: Option<usize>,
+    resource_length: usize,
+    resource_delimiter: Vec<u8>,
+}
+
+impl SimpleProtocol {
+    pub fn new(d: &SimpleProtocolDefinition) -> Option<Self> {
+        if d.name.is_empty() {
+            warn!("simple protocol without name ignored");
+            return None;
+        }
+        let magic = match hex::decode(&d.magic) {
+            Ok(m) => m,
+            Err(e) => {
+                warn!(
+                    "simple protocol {} with invalid magic {} ignored: {}",
+                    d.name, d.magic, e
+                );
+                return None;
+            }
+        };
+        let valid_size = |s: usize| [1, 2, 4, 8].contains(&s);
+        if d.length_offset.is_some() && !valid_size(d.length_size) {
+            warn!(
+                "simple protocol {} with invalid length size {} ignored",
+                d.name, d.length_size
+            );
+            return None;
+        }
+        if d.request_type_offset.is_some() && !valid_size(d.request_type_size) {
+            warn!(
+                "simple protocol {} with invalid request type size {} ignored",
+                d.name, d.request_type_size
+            );
+            return None;
+        }
+        if magic.is_empty() && d.length_offset.is_none() {
+            warn!(
+                "simple protocol {} without magic or length field can not be identified, ignored",
+                d.name
+            );
+            return None;
+        }
+        Some(Self {
+            name: d.name.clone(),
+            magic,
+            magic_offset: d.magic_offset,
+            length: d.length_offset.map(|o| (o, d.length_size)),
+            length_adjustment: d.length_adjustment,
+            request_type: d.request_type_offset.map(|o| (o, d.request_type_size)),
+            little_endian: d.little_endian,
+            resource_offset: d.resource_offset,
+            resource_length: d.resource_length,
+            resource_delimiter: d.resource_delimiter.as_bytes().to_vec(),
+        })
+    }
+
+    fn magic_matches(&self, payload: &[u8]) -> bool {
+        payload.get(self.magic_offset..self.magic_offset + self.magic.len())
+            == Some(&self.magic[..])
+    }
+
+    // message length in header, or payload length if there is no length field
+    fn message_len(&self, payload: &[u8]) -> Option<usize> {
+        let Some(field) = self.length else {
+            return Some(payload.len());
+        };
+        // lengths overflowing with the adjustment are invalid
+        let len = i64::try_from(read_uint(payload, field, self.little_endian)?)
+            .ok()?
+            .checked_add(self.length_adjustment)?;
+        if len <= 0 {
+            return None;
+        }
+        usize::try_from(len).ok()
+    }
+
+    // the first message must be complete in one packet to avoid false positives
+    pub fn check(&self, payload: &[u8]) -> bool {
+        self.magic_matches(payload) && self.message_len(payload) == Some(payload.len())
+    }
+
+    fn resource<'a>(&self, payload: &'a [u8], end: usize) -> Option<&'a [u8]> {
+        let data = payload.get(self.resource_offset?..end)?;
+        if self.resource_length > 0 {
+            return Some(&data[..self.resource_length.min(data.len())]);
+        }
+        if self.resource_delimiter.is_empty() {
+            return Some(data);
+        }
+        match data
+            .windows(self.resource_delimiter.len())
+            .position(|w| w == &self.resource_delimiter[..])
+        {
+            Some(i) => Some(&data[..i]),
+            None => Some(data),
+        }
+    }
+
+    fn parse(&self, payload: &[u8], direction: PacketDirection, info: &mut CustomInfo) -> bool {
+        if !self.magic_matches(payload) {
+            return false;
+        }
+        let Some(len) = self.message_len(payload) else {
+            return false;
+        };
+        match direction {
+            PacketDirection::ClientToServer => {
+                info.req_len = Some(len as u32);
+                if let Some(t) = self
+                    .request_type
+                    .and_then(|f| read_uint(payload, f, self.little_endian))
+                {
+                    info.req.req_type = t.to_string();
+                }
+                if let Some(r) = self.resource(payload, len.min(payload.len())) {
+                    info.req.resource = String::from_utf8_lossy(r).into_owned();
+                }
+            }
+            PacketDirection::ServerToClient => info.resp_len = Some(len as u32),
+        }
+        true
+    }
+}
+
+pub fn compile_simple_protocols(defs: &[SimpleProtocolDefinition]) -> Vec<SimpleProtocol> {
+    let mut protocols: Vec<SimpleProtocol> = vec![];
+    for d in defs.iter() {
+        if protocols.iter().any(|p| p.name == d.name) {
+            warn!("duplicate simple protocol {} ignored", d.name);
+            continue;
+        }
+        if protocols.len() > u8::MAX as usize {
+            warn!("too many simple protocols, {} ignored", d.name);
+            break;
+        }
+        if let Some(p) = SimpleProtocol::new(d) {
+            protocols.push(p);
+        }
+    }
+    protocols
+}
+
+#[derive(Default)]
+pub struct SimpleLog {
+    proto_num: Option<u8>,
+    proto_str: String,
+    perf_stats: Option<L7PerfStats>,
+}
+
+impl L7ProtocolParserInterface for SimpleLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        let Some(config) = param.parse_config else {
+            return false;
+        };
+        for (i, p) in config.simple_protocols.iter().enumerate() {
+            if p.check(payload) {
+                self.proto_num = Some(i as u8);
+                self.proto_str = p.name.clone();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        let Some(config) = param.parse_config else {
+            return Err(Error::NoParseConfig);
+        };
+        // protocols may be reordered after config update, look up by name
+        let Some(protocol) = config
+            .simple_protocols
+            .iter()
+            .find(|p| p.name == self.proto_str)
+        else {
+            return Err(Error::SimpleProtocolParseFailed);
+        };
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default());
+        }
+
+        let mut info = CustomInfo {
+            proto: self.proto_num.unwrap_or_default(),
+            proto_str: self.proto_str.clone(),
+            msg_type: param.direction.into(),
+            ..Default::default()
+        };
+        if !protocol.parse(payload, param.direction, &mut info) {
+            return Err(Error::SimpleProtocolParseFailed);
+        }
+        match param.direction {
+            PacketDirection::ClientToServer => {
+                self.perf_stats.as_mut().map(|p| p.inc_req());
+            }
+            PacketDirection::ServerToClient => {
+                self.perf_stats.as_mut().map(|p| p.inc_resp());
+            }
+        }
+        info.cal_rrt(param, None).map(|rrt| {
+            info.rrt = rrt;
+            self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+        });
+        Ok(L7ParseResult::Single(L7ProtocolInfo::CustomInfo(info)))
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::Custom
+    }
+
+    fn custom_protocol(&self) -> Option<CustomProtocol> {
+        Some(CustomProtocol::Simple(
+            self.proto_num.unwrap(),
+            self.proto_str.clone(),
+        ))
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+pub fn get_simple_parser(p: u8, s: String) -> SimpleLog {
+    SimpleLog {
+        proto_num: Some(p),
+        proto_str: s,
+        perf_stats: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_protocol() {
+        // | magic 0xcafe | length u32 le, excluding header | command u16 le | path ending with \0 |
+        let def = SimpleProtocolDefinition {
+            name: "inhouse".to_owned(),
+            magic: "cafe".to_owned(),
+            length_offset: Some(2),
+            length_size: 4,
+            length_adjustment: 8,
+            request_type_offset: Some(6),
+            request_type_size: 2,
+            little_endian: true,
+            resource_offset: Some(8),
+            resource_delimiter: "\0".to_owned(),
+            ..Default::default()
+        };
+        let protocols = compile_simple_protocols(&[def.clone(), def.clone()]);
+        assert_eq!(protocols.len(), 1);
+        let p = &protocols[0];
+
+        let mut payload = vec![0xca, 0xfe, 12, 0, 0, 0, 3, 0];
+        payload.extend_from_slice(b"/user\0data\0");
+        payload.push(0);
+        assert!(p.check(&payload));
+        assert!(!p.check(&payload[..payload.len() - 1]));
+        assert!(!p.check(b"GET / HTTP/1.1\r\n\r\n"));
+
+        let mut info = CustomInfo::default();
+        assert!(p.parse(&payload, PacketDirection::ClientToServer, &mut info));
+        assert_eq!(info.req_len, Some(20));
+        assert_eq!(info.req.req_type, "3");
+        assert_eq!(info.req.resource, "/user");
+
+        // truncated message
+        let mut info = CustomInfo::default();
+        assert!(p.parse(&payload[..10], PacketDirection::ClientToServer, &mut info));
+        assert_eq!(info.req.resource, "/u");
+
+        // packets with overflowing lengths are rejected
+        let wide = SimpleProtocol::new(&SimpleProtocolDefinition {
+            length_size: 8,
+            length_adjustment: i64::MAX,
+            ..def.clone()
+        })
+        .unwrap();
+        let mut payload = vec![0xca, 0xfe, 1, 0, 0, 0, 0, 0, 0, 0];
+        assert!(!wide.check(&payload));
+        assert!(!wide.parse(
+            &payload,
+            PacketDirection::ClientToServer,
+            &mut CustomInfo::default()
+        ));
+        payload[2..10].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(!wide.check(&payload));
+        assert!(!wide.parse(
+            &payload,
+            PacketDirection::ClientToServer,
+            &mut CustomInfo::default()
+        ));
+
+        let invalid = SimpleProtocolDefinition {
+            name: "invalid".to_owned(),
+            magic: "xyz".to_owned(),
+            ..Default::default()
+        };
+        assert!(SimpleProtocol::new(&invalid).is_none());
+        let unidentifiable = SimpleProtocolDefinition {
+            name: "unidentifiable".to_owned(),
+            ..Default::default()
+        };
+        assert!(SimpleProtocol::new(&unidentifiable).is_none());
+    }
+}
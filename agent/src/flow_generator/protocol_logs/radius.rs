@@ -0,0 +1,509 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// RADIUS, reference: https://www.rfc-editor.org/rfc/rfc2865
+// and https://www.rfc-editor.org/rfc/rfc2866 (accounting)
+// and https://www.rfc-editor.org/rfc/rfc5176 (dynamic authorization)
+//
+// code(1) | identifier(1) | length(2) | authenticator(16) | attributes { type(1) | length(1) | value }
+//
+// Request and response are correlated by the identifier.
+
+use serde::Serialize;
+
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    flow_generator::error::{Error, Result},
+    utils::bytes::{read_u16_be, read_u32_be},
+};
+use public::{l7_protocol::L7Protocol, utils::net::parse_ip_slice};
+
+const HEADER_SIZE: usize = 20;
+const MAX_PACKET_SIZE: usize = 4096;
+
+const ACCESS_REQUEST: u8 = 1;
+const ACCESS_ACCEPT: u8 = 2;
+const ACCESS_REJECT: u8 = 3;
+const ACCOUNTING_REQUEST: u8 = 4;
+const ACCOUNTING_RESPONSE: u8 = 5;
+const ACCESS_CHALLENGE: u8 = 11;
+const STATUS_SERVER: u8 = 12;
+const STATUS_CLIENT: u8 = 13;
+const DISCONNECT_REQUEST: u8 = 40;
+const DISCONNECT_ACK: u8 = 41;
+const DISCONNECT_NAK: u8 = 42;
+const COA_REQUEST: u8 = 43;
+const COA_ACK: u8 = 44;
+const COA_NAK: u8 = 45;
+
+const ATTR_USER_NAME: u8 = 1;
+const ATTR_NAS_IP_ADDRESS: u8 = 4;
+const ATTR_NAS_PORT: u8 = 5;
+const ATTR_REPLY_MESSAGE: u8 = 18;
+const ATTR_CALLED_STATION_ID: u8 = 30;
+const ATTR_CALLING_STATION_ID: u8 = 31;
+const ATTR_NAS_IDENTIFIER: u8 = 32;
+const ATTR_ACCT_STATUS_TYPE: u8 = 40;
+const ATTR_ACCT_SESSION_ID: u8 = 44;
+const ATTR_NAS_IPV6_ADDRESS: u8 = 95;
+const ATTR_ERROR_CAUSE: u8 = 101;
+
+fn code_name(code: u8) -> &'static str {
+    match code {
+        ACCESS_REQUEST => "Access-Request",
+        ACCESS_ACCEPT => "Access-Accept",
+        ACCESS_REJECT => "Access-Reject",
+        ACCOUNTING_REQUEST => "Accounting-Request",
+        ACCOUNTING_RESPONSE => "Accounting-Response",
+        ACCESS_CHALLENGE => "Access-Challenge",
+        STATUS_SERVER => "Status-Server",
+        STATUS_CLIENT => "Status-Client",
+        DISCONNECT_REQUEST => "Disconnect-Request",
+        DISCONNECT_ACK => "Disconnect-ACK",
+        DISCONNECT_NAK => "Disconnect-NAK",
+        COA_REQUEST => "CoA-Request",
+        COA_ACK => "CoA-ACK",
+        COA_NAK => "CoA-NAK",
+        _ => "",
+    }
+}
+
+fn acct_status_type(t: u32) -> String {
+    match t {
+        1 => "Start".to_string(),
+        2 => "Stop".to_string(),
+        3 => "Interim-Update".to_string(),
+        7 => "Accounting-On".to_string(),
+        8 => "Accounting-Off".to_string(),
+        _ => t.to_string(),
+    }
+}
+
+fn attributes(mut data: &[u8]) -> impl Iterator<Item = Result<(u8, &[u8])>> {
+    std::iter::from_fn(move || {
+        let (t, len) = match data {
+            [] => return None,
+            [t, len, ..] if *len as usize >= 2 && *len as usize <= data.len() => {
+                (*t, *len as usize)
+            }
+            _ => {
+                data = &[];
+                return Some(Err(Error::RadiusLogParseFailed(
+                    "radius attribute invalid".to_string(),
+                )));
+            }
+        };
+        let value = &data[2..len];
+        data = &data[len..];
+        Some(Ok((t, value)))
+    })
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct RadiusInfo {
+    #[serde(rename = "request_id")]
+    pub identifier: u8,
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    pub request_type: &'static str,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub response_type: &'static str,
+
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    pub user_name: String,
+    // NAS-Identifier, or NAS-IP-Address if there is no identifier
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    pub nas: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nas_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nas_port: Option<u32>,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub calling_station_id: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub called_station_id: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub acct_status_type: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub acct_session_id: String,
+
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub error_cause: Option<i32>,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub exception: String,
+
+    msg_type: LogMessageType,
+    rrt: u64,
+}
+
+impl L7ProtocolInfoInterface for RadiusInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.identifier as u32)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::RadiusInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::RADIUS,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+}
+
+impl RadiusInfo {
+    pub fn merge(&mut self, other: &mut Self) {
+        self.response_type = other.response_type;
+        if self.user_name.is_empty() {
+            std::mem::swap(&mut self.user_name, &mut other.user_name);
+        }
+        self.status = other.status;
+        self.error_cause = other.error_cause;
+        std::mem::swap(&mut self.exception, &mut other.exception);
+    }
+}
+
+impl From<RadiusInfo> for L7ProtocolSendLog {
+    fn from(f: RadiusInfo) -> Self {
+        let mut attributes = vec![];
+        for (key, val) in [
+            ("radius_nas_ip", f.nas_ip.unwrap_or_default()),
+            (
+                "radius_nas_port",
+                f.nas_port.map(|p| p.to_string()).unwrap_or_default(),
+            ),
+            ("radius_calling_station_id", f.calling_station_id),
+            ("radius_called_station_id", f.called_station_id),
+            ("radius_acct_status_type", f.acct_status_type),
+            ("radius_acct_session_id", f.acct_session_id),
+        ] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        L7ProtocolSendLog {
+            req: L7Request {
+                req_type: f.request_type.to_string(),
+                domain: f.nas,
+                resource: f.user_name,
+                ..Default::default()
+            },
+            resp: L7Response {
+                result: f.response_type.to_string(),
+                code: f.error_cause,
+                status: f.status,
+                exception: f.exception,
+            },
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(f.identifier as u32),
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RadiusLog {
+    perf_stats: Option<L7PerfStats>,
+}
+
+impl L7ProtocolParserInterface for RadiusLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::UDP {
+            return false;
+        }
+        match Self::parse(payload) {
+            Ok(info) => info.msg_type == LogMessageType::Request,
+            Err(_) => false,
+        }
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+        let mut info = Self::parse(payload)?;
+        if let Some(perf_stats) = self.perf_stats.as_mut() {
+            match info.msg_type {
+                LogMessageType::Request => perf_stats.inc_req(),
+                _ => perf_stats.inc_resp(),
+            }
+            match info.status {
+                L7ResponseStatus::ClientError => perf_stats.inc_req_err(),
+                L7ResponseStatus::ServerError => perf_stats.inc_resp_err(),
+                _ => {}
+            }
+        }
+        info.cal_rrt(param, None).map(|rrt| {
+            info.rrt = rrt;
+            self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+        });
+        if param.parse_log {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::RadiusInfo(info)))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::RADIUS
+    }
+
+    fn parsable_on_tcp(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl RadiusLog {
+    fn parse(payload: &[u8]) -> Result<RadiusInfo> {
+        if payload.len() < HEADER_SIZE {
+            let err_msg = format!("radius payload length too short:{}", payload.len());
+            return Err(Error::RadiusLogParseFailed(err_msg));
+        }
+        // octets outside the length are padding
+        let length = read_u16_be(&payload[2..]) as usize;
+        if length < HEADER_SIZE || length > MAX_PACKET_SIZE || length > payload.len() {
+            let err_msg = format!("radius length {} invalid", length);
+            return Err(Error::RadiusLogParseFailed(err_msg));
+        }
+        let code = payload[0];
+        let name = code_name(code);
+        if name.is_empty() {
+            let err_msg = format!("radius code {} unsupported", code);
+            return Err(Error::RadiusLogParseFailed(err_msg));
+        }
+
+        let mut info = RadiusInfo {
+            identifier: payload[1],
+            ..Default::default()
+        };
+        match code {
+            ACCESS_REQUEST | ACCOUNTING_REQUEST | STATUS_SERVER | STATUS_CLIENT
+            | DISCONNECT_REQUEST | COA_REQUEST => {
+                info.msg_type = LogMessageType::Request;
+                info.request_type = name;
+            }
+            _ => {
+                info.msg_type = LogMessageType::Response;
+                info.response_type = name;
+            }
+        }
+
+        let mut reply_message = String::new();
+        for attribute in attributes(&payload[HEADER_SIZE..length]) {
+            let (t, value) = attribute?;
+            let text = || String::from_utf8_lossy(value).into_owned();
+            match t {
+                ATTR_USER_NAME => info.user_name = text(),
+                ATTR_NAS_IP_ADDRESS | ATTR_NAS_IPV6_ADDRESS => {
+                    info.nas_ip = parse_ip_slice(value).map(|ip| ip.to_string())
+                }
+                ATTR_NAS_PORT if value.len() == 4 => info.nas_port = Some(read_u32_be(value)),
+                ATTR_NAS_IDENTIFIER => info.nas = text(),
+                ATTR_CALLED_STATION_ID => info.called_station_id = text(),
+                ATTR_CALLING_STATION_ID => info.calling_station_id = text(),
+                ATTR_ACCT_STATUS_TYPE if value.len() == 4 => {
+                    info.acct_status_type = acct_status_type(read_u32_be(value))
+                }
+                ATTR_ACCT_SESSION_ID => info.acct_session_id = text(),
+                // may be repeated, the texts are concatenated
+                ATTR_REPLY_MESSAGE => reply_message.push_str(&String::from_utf8_lossy(value)),
+                ATTR_ERROR_CAUSE if value.len() == 4 => {
+                    info.error_cause = Some(read_u32_be(value) as i32)
+                }
+                _ => {}
+            }
+        }
+        if info.nas.is_empty() {
+            info.nas = info.nas_ip.clone().unwrap_or_default();
+        }
+
+        match code {
+            ACCESS_REJECT | DISCONNECT_NAK | COA_NAK => {
+                info.status = L7ResponseStatus::ClientError;
+                info.exception = if !reply_message.is_empty() {
+                    reply_message
+                } else if let Some(cause) = info.error_cause {
+                    format!("error cause {}", cause)
+                } else {
+                    name.to_string()
+                };
+            }
+            _ => {}
+        }
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    use crate::{
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn radius(code: u8, identifier: u8, attributes: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut p = vec![code, identifier, 0, 0];
+        p.extend_from_slice(&[0x11; 16]);
+        for (t, value) in attributes {
+            p.push(*t);
+            p.push(value.len() as u8 + 2);
+            p.extend_from_slice(value);
+        }
+        let length = (p.len() as u16).to_be_bytes();
+        p[2..4].copy_from_slice(&length);
+        p
+    }
+
+    #[test]
+    fn test_access() {
+        let request = radius(
+            ACCESS_REQUEST,
+            0x2a,
+            &[
+                (ATTR_USER_NAME, b"alice"),
+                (2, &[0x55; 16]),
+                (ATTR_NAS_IP_ADDRESS, &[10, 0, 0, 1]),
+                (ATTR_NAS_PORT, &[0, 0, 0, 3]),
+                (ATTR_CALLING_STATION_ID, b"02-00-00-00-00-01"),
+            ],
+        );
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        packet.lookup_key.direction = PacketDirection::ClientToServer;
+        let param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        let mut radius_log = RadiusLog::default();
+        assert!(radius_log.check_payload(&request, &param));
+        let info = match radius_log
+            .parse_payload(&request, &param)
+            .unwrap()
+            .unwrap_single()
+        {
+            L7ProtocolInfo::RadiusInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(info.session_id(), Some(0x2a));
+        assert_eq!(info.request_type, "Access-Request");
+        assert_eq!(info.user_name, "alice");
+        assert_eq!(info.nas, "10.0.0.1");
+        assert_eq!(info.nas_port, Some(3));
+        assert_eq!(info.calling_station_id, "02-00-00-00-00-01");
+
+        let reject = radius(
+            ACCESS_REJECT,
+            0x2a,
+            &[
+                (ATTR_REPLY_MESSAGE, b"Invalid "),
+                (ATTR_REPLY_MESSAGE, b"credentials"),
+            ],
+        );
+        packet.lookup_key.direction = PacketDirection::ServerToClient;
+        let param = ParseParam::new(&packet, log_cache, true, true);
+        assert!(!radius_log.check_payload(&reject, &param));
+        let info = match radius_log
+            .parse_payload(&reject, &param)
+            .unwrap()
+            .unwrap_single()
+        {
+            L7ProtocolInfo::RadiusInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(info.response_type, "Access-Reject");
+        assert_eq!(info.status, L7ResponseStatus::ClientError);
+        assert_eq!(info.exception, "Invalid credentials");
+        let perf_stats = radius_log.perf_stats().unwrap();
+        assert_eq!(perf_stats.request_count, 1);
+        assert_eq!(perf_stats.response_count, 1);
+        assert_eq!(perf_stats.err_client_count, 1);
+    }
+
+    #[test]
+    fn test_accounting() {
+        let request = radius(
+            ACCOUNTING_REQUEST,
+            7,
+            &[
+                (ATTR_USER_NAME, b"bob"),
+                (ATTR_NAS_IDENTIFIER, b"ap-1"),
+                (ATTR_ACCT_STATUS_TYPE, &[0, 0, 0, 1]),
+                (ATTR_ACCT_SESSION_ID, b"5f3c0a01"),
+            ],
+        );
+        // with padding
+        let mut payload = request.clone();
+        payload.extend_from_slice(&[0; 4]);
+        let info = RadiusLog::parse(&payload).unwrap();
+        assert_eq!(info.request_type, "Accounting-Request");
+        assert_eq!(info.nas, "ap-1");
+        assert_eq!(info.acct_status_type, "Start");
+        assert_eq!(info.acct_session_id, "5f3c0a01");
+
+        let response = radius(ACCOUNTING_RESPONSE, 7, &[]);
+        let info = RadiusLog::parse(&response).unwrap();
+        assert_eq!(info.msg_type, LogMessageType::Response);
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+
+        // attribute exceeds the length
+        let mut invalid = request.clone();
+        invalid[HEADER_SIZE + 1] = 0xff;
+        assert!(RadiusLog::parse(&invalid).is_err());
+        assert!(RadiusLog::parse(&request[..HEADER_SIZE - 1]).is_err());
+    }
+}
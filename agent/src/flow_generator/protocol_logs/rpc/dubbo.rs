@@ -143,6 +143,18 @@ impl L7ProtocolInfoInterface for DubboInfo {
         self.is_tls
     }
 
+    fn response_status(&self) -> L7ResponseStatus {
+        self.resp_status
+    }
+
+    fn request_len(&self) -> Option<u32> {
+        self.req_msg_size
+    }
+
+    fn response_len(&self) -> Option<u32> {
+        self.resp_msg_size
+    }
+
     fn get_endpoint(&self) -> Option<String> {
         if !self.service_name.is_empty() || !self.method_name.is_empty() {
             Some(format!("{}/{}", self.service_name, self.method_name))
@@ -258,7 +270,7 @@ impl L7ProtocolParserInterface for DubboLog {
         let mut info = DubboInfo::default();
         self.parse(&config.l7_log_dynamic, payload, &mut info, param)?;
         info.is_tls = param.is_tls();
-        info.cal_rrt(param, None).map(|rrt| {
+        info.cal_rrt_by_session_id(param, None).map(|rrt| {
             info.rrt = rrt;
             self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
         });
@@ -526,6 +538,11 @@ impl DubboLog {
             }
 
             Self::decode_trace_id(&payload_str, &trace_type, info);
+            if let Some(c) = config.custom_trace_context(&trace_type.to_string()) {
+                if !info.trace_id.is_empty() {
+                    info.trace_id = c.decode_trace_id(&info.trace_id).unwrap_or_default();
+                }
+            }
             if info.trace_id.len() != 0 {
                 break;
             }
@@ -536,6 +553,11 @@ impl DubboLog {
             }
 
             Self::decode_span_id(&payload_str, &span_type, info);
+            if let Some(c) = config.custom_trace_context(&span_type.to_string()) {
+                if !info.span_id.is_empty() {
+                    info.span_id = c.decode_span_id(&info.span_id).unwrap_or_default();
+                }
+            }
             if info.span_id.len() != 0 {
                 break;
             }
@@ -729,6 +751,7 @@ mod tests {
                         TraceType::Customize("EagleEye-SpanID".to_string()),
                         TraceType::Sw8,
                     ],
+                    vec![],
                 ),
                 ..Default::default()
             };
@@ -818,6 +841,7 @@ mod tests {
                     TraceType::Customize("EagleEye-SpanID".to_string()),
                     TraceType::Sw8,
                 ],
+                vec![],
             ),
             ..Default::default()
         };
@@ -837,4 +861,71 @@ mod tests {
         }
         dubbo.perf_stats.unwrap()
     }
+
+    #[test]
+    fn check_out_of_order_rrt() {
+        let rrt_cache = Rc::new(RefCell::new(L7PerfCache::new(100)));
+        let packet = MetaPacket::default();
+        let mut param = ParseParam::new(&packet, rrt_cache.clone(), true, true);
+        let mut rrt = |request_id: i64, direction: PacketDirection, time: u64| {
+            let info = DubboInfo {
+                request_id,
+                ..Default::default()
+            };
+            param.direction = direction;
+            param.time = time;
+            info.cal_rrt_by_session_id(&param, None)
+        };
+
+        // responses overtake each other
+        assert_eq!(rrt(1, PacketDirection::ClientToServer, 100), None);
+        assert_eq!(rrt(2, PacketDirection::ClientToServer, 200), None);
+        assert_eq!(rrt(3, PacketDirection::ClientToServer, 300), None);
+        assert_eq!(rrt(3, PacketDirection::ServerToClient, 400), Some(100));
+        assert_eq!(rrt(1, PacketDirection::ServerToClient, 500), Some(400));
+        assert_eq!(rrt(2, PacketDirection::ServerToClient, 600), Some(400));
+
+        // request 4 times out and is evicted before its response arrives
+        assert_eq!(rrt(4, PacketDirection::ClientToServer, 1_000), None);
+        assert_eq!(rrt(5, PacketDirection::ClientToServer, 20_000_000), None);
+        assert_eq!(rrt(4, PacketDirection::ServerToClient, 20_000_100), None);
+        assert_eq!(
+            rrt(5, PacketDirection::ServerToClient, 20_000_200),
+            Some(200)
+        );
+        assert_eq!(rrt_cache.borrow_mut().pop_timeout_count(&0, false), 1);
+    }
+
+    #[test]
+    fn check_pending_requests_limit() {
+        let rrt_cache = Rc::new(RefCell::new(L7PerfCache::new(4)));
+        let packet = MetaPacket::default();
+        let mut param = ParseParam::new(&packet, rrt_cache.clone(), true, true);
+        let mut rrt = |flow_id: u64, request_id: i64, direction: PacketDirection, time: u64| {
+            let info = DubboInfo {
+                request_id,
+                ..Default::default()
+            };
+            param.flow_id = flow_id;
+            param.direction = direction;
+            param.time = time;
+            info.cal_rrt_by_session_id(&param, None)
+        };
+
+        assert_eq!(rrt(1, 1, PacketDirection::ClientToServer, 100), None);
+        assert_eq!(rrt(1, 2, PacketDirection::ClientToServer, 200), None);
+        assert_eq!(rrt(2, 1, PacketDirection::ClientToServer, 300), None);
+        assert_eq!(rrt(2, 2, PacketDirection::ClientToServer, 400), None);
+        assert_eq!(rrt_cache.borrow().pending_count, 4);
+        // the oldest request of the least recently used flow is evicted
+        assert_eq!(rrt(3, 1, PacketDirection::ClientToServer, 500), None);
+        assert_eq!(rrt_cache.borrow().pending_count, 4);
+        assert_eq!(rrt(1, 2, PacketDirection::ServerToClient, 600), Some(400));
+        assert_eq!(rrt(2, 1, PacketDirection::ServerToClient, 700), Some(400));
+        assert_eq!(rrt(3, 1, PacketDirection::ServerToClient, 800), Some(300));
+        assert_eq!(rrt_cache.borrow().pending_count, 1);
+        assert_eq!(rrt_cache.borrow_mut().pop_timeout_count(&1, false), 1);
+        assert_eq!(rrt_cache.borrow_mut().pop_timeout_count(&2, true), 1);
+        assert_eq!(rrt_cache.borrow().pending_count, 0);
+    }
 }
@@ -227,6 +227,18 @@ impl L7ProtocolInfoInterface for SofaRpcInfo {
         self.is_tls
     }
 
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+
+    fn request_len(&self) -> Option<u32> {
+        (self.req_len > 0).then_some(self.req_len)
+    }
+
+    fn response_len(&self) -> Option<u32> {
+        (self.resp_len > 0).then_some(self.resp_len)
+    }
+
     fn get_endpoint(&self) -> Option<String> {
         if !self.target_serv.is_empty() || !self.method.is_empty() {
             Some(format!("{}/{}", self.target_serv, self.method))
@@ -477,7 +489,7 @@ impl SofaRpcLog {
             _ => {}
         }
 
-        info.cal_rrt(param, None).map(|rrt| {
+        info.cal_rrt_by_session_id(param, None).map(|rrt| {
             info.rrt = rrt;
             self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
         });
@@ -0,0 +1,541 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// RTP and RTCP, reference: https://www.rfc-editor.org/rfc/rfc3550
+// and https://www.rfc-editor.org/rfc/rfc3551 (static payload types)
+//
+// RTP:  V(2) P(1) X(1) CC(4) | M(1) PT(7) | sequence(2) | timestamp(4) | SSRC(4) | CSRC(4 * CC)
+// RTCP: V(2) P(1) RC(5) | PT(1) | length(2, in 32-bit words minus one) | SSRC(4) | ...
+//
+// Media streams have neither requests nor responses, so no log is sent. Sequence
+// numbers and timestamps of each SSRC are tracked to count loss, reordering and
+// interarrival jitter, which are reported with the flow metrics.
+// RTCP packets in a compound datagram are split by datagram_message_len and parsed
+// one by one.
+
+use std::collections::HashMap;
+
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::{L7PerfStats, MediaPerfStats},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    flow_generator::error::{Error, Result},
+    utils::bytes::{read_u16_be, read_u32_be},
+};
+use public::l7_protocol::L7Protocol;
+
+const HEADER_SIZE: usize = 12;
+const VERSION: u8 = 2;
+
+const RTCP_SR: u8 = 200;
+const RTCP_RR: u8 = 201;
+const RTCP_BYE: u8 = 203;
+const RTCP_APP: u8 = 204;
+const SENDER_INFO_SIZE: usize = 20;
+const REPORT_BLOCK_SIZE: usize = 24;
+
+// reference: https://www.rfc-editor.org/rfc/rfc3550#appendix-A.1
+const MAX_DROPOUT: u16 = 3000;
+const MAX_MISORDER: u16 = 100;
+
+// the clock rate of dynamic payload types is estimated from the timestamps of
+// this duration, and snapped to the closest common rate
+const CLOCK_RATE_ESTIMATE_DURATION: u64 = 1_000_000; // us
+const CLOCK_RATES: [u32; 7] = [8000, 11025, 16000, 22050, 44100, 48000, 90000];
+const CLOCK_RATE_TOLERANCE: f64 = 0.1;
+
+const MAX_SSRC_COUNT: usize = 16;
+
+fn static_clock_rate(payload_type: u8) -> Option<u32> {
+    match payload_type {
+        0 | 3 | 4 | 5 | 7 | 8 | 9 | 12 | 13 | 15 | 18 => Some(8000),
+        6 => Some(16000),
+        10 | 11 => Some(44100),
+        16 => Some(11025),
+        17 => Some(22050),
+        14 | 25 | 26 | 28 | 31 | 32 | 33 | 34 => Some(90000),
+        _ => None,
+    }
+}
+
+fn is_dynamic(payload_type: u8) -> bool {
+    (96..=127).contains(&payload_type)
+}
+
+// packet types 72-76 of rtp are reserved as they conflict with rtcp
+fn is_rtcp(payload: &[u8]) -> bool {
+    payload.len() > 1 && (RTCP_SR..=RTCP_APP).contains(&payload[1])
+}
+
+struct RtpHeader {
+    payload_type: u8,
+    sequence: u16,
+    timestamp: u32,
+    ssrc: u32,
+}
+
+fn parse_rtp(payload: &[u8]) -> Option<RtpHeader> {
+    if payload.len() < HEADER_SIZE || payload[0] >> 6 != VERSION {
+        return None;
+    }
+    let payload_type = payload[1] & 0x7f;
+    if static_clock_rate(payload_type).is_none() && !is_dynamic(payload_type) {
+        return None;
+    }
+    let mut offset = HEADER_SIZE + 4 * (payload[0] & 0xf) as usize;
+    if payload[0] & 0x10 != 0 {
+        // profile(2) | length(2, in 32-bit words) | extension
+        if offset + 4 > payload.len() {
+            return None;
+        }
+        offset += 4 + 4 * read_u16_be(&payload[offset + 2..]) as usize;
+    }
+    if offset > payload.len() {
+        return None;
+    }
+    if payload[0] & 0x20 != 0 {
+        let padding = payload[payload.len() - 1] as usize;
+        if padding == 0 || offset + padding > payload.len() {
+            return None;
+        }
+    }
+    Some(RtpHeader {
+        payload_type,
+        sequence: read_u16_be(&payload[2..]),
+        timestamp: read_u32_be(&payload[4..]),
+        ssrc: read_u32_be(&payload[8..]),
+    })
+}
+
+#[derive(Default)]
+struct RtcpReport {
+    // number of SR and RR packets
+    report_count: u32,
+    fraction_lost_max: u8,
+    bye_ssrcs: Vec<u32>,
+}
+
+// length of the first rtcp packet in payload
+fn rtcp_packet_len(payload: &[u8]) -> Option<usize> {
+    if payload.len() < 4 || payload[0] >> 6 != VERSION {
+        return None;
+    }
+    if !(RTCP_SR..=RTCP_APP).contains(&payload[1]) {
+        return None;
+    }
+    let length = 4 * (read_u16_be(&payload[2..]) as usize + 1);
+    if length > payload.len() {
+        return None;
+    }
+    Some(length)
+}
+
+// parses rtcp packets in payload into the report, a compound packet starts with
+// a SR or RR, and the lengths of all packets add up to the payload length
+fn parse_rtcp_packets(payload: &[u8], compound: bool) -> Option<RtcpReport> {
+    let mut report = RtcpReport::default();
+    let mut offset = 0;
+    while offset < payload.len() {
+        let length = rtcp_packet_len(&payload[offset..])?;
+        let packet = &payload[offset..offset + length];
+        let packet_type = packet[1];
+        if compound && offset == 0 && packet_type != RTCP_SR && packet_type != RTCP_RR {
+            return None;
+        }
+        let count = (packet[0] & 0x1f) as usize;
+        match packet_type {
+            RTCP_SR | RTCP_RR => {
+                // header(4) | SSRC(4) | sender info | report blocks
+                let mut blocks = 8;
+                if packet_type == RTCP_SR {
+                    blocks += SENDER_INFO_SIZE;
+                }
+                if blocks + count * REPORT_BLOCK_SIZE > length {
+                    return None;
+                }
+                report.report_count += 1;
+                // SSRC(4) | fraction lost(1) | cumulative lost(3) | ...
+                for block in packet[blocks..].chunks_exact(REPORT_BLOCK_SIZE).take(count) {
+                    report.fraction_lost_max = report.fraction_lost_max.max(block[4]);
+                }
+            }
+            RTCP_BYE => {
+                if 4 + count * 4 > length {
+                    return None;
+                }
+                for i in 0..count {
+                    report.bye_ssrcs.push(read_u32_be(&packet[4 + i * 4..]));
+                }
+            }
+            _ => (),
+        }
+        offset += length;
+    }
+    Some(report)
+}
+
+fn parse_rtcp(payload: &[u8]) -> Option<RtcpReport> {
+    parse_rtcp_packets(payload, true)
+}
+
+struct Stream {
+    payload_type: u8,
+    clock_rate: Option<u32>,
+    max_sequence: u16,
+    // arrival time in us and timestamp of the first packet for clock rate estimation
+    first: (u64, u32),
+    // arrival time in us and timestamp of the last packet in order
+    last: (u64, u32),
+    // in timestamp units
+    jitter: f64,
+    active: bool,
+}
+
+impl Stream {
+    fn new(header: &RtpHeader, time: u64) -> Self {
+        Self {
+            payload_type: header.payload_type,
+            clock_rate: static_clock_rate(header.payload_type),
+            max_sequence: header.sequence,
+            first: (time, header.timestamp),
+            last: (time, header.timestamp),
+            jitter: 0.0,
+            active: true,
+        }
+    }
+
+    fn estimate_clock_rate(&mut self, time: u64, timestamp: u32) {
+        let elapsed = time.saturating_sub(self.first.0);
+        if elapsed < CLOCK_RATE_ESTIMATE_DURATION {
+            return;
+        }
+        let rate = timestamp.wrapping_sub(self.first.1) as f64 * 1_000_000.0 / elapsed as f64;
+        let closest = CLOCK_RATES
+            .iter()
+            .min_by(|a, b| {
+                (**a as f64 - rate)
+                    .abs()
+                    .total_cmp(&(**b as f64 - rate).abs())
+            })
+            .unwrap();
+        if (*closest as f64 - rate).abs() <= *closest as f64 * CLOCK_RATE_TOLERANCE {
+            self.clock_rate = Some(*closest);
+        } else {
+            self.first = (time, timestamp);
+        }
+    }
+
+    // returns jitter in us
+    fn update_jitter(&mut self, time: u64, timestamp: u32) -> Option<u32> {
+        if self.clock_rate.is_none() {
+            self.estimate_clock_rate(time, timestamp);
+        }
+        let last = std::mem::replace(&mut self.last, (time, timestamp));
+        let clock_rate = self.clock_rate? as f64;
+        // D(i, j) = (Rj - Ri) - (Sj - Si), in timestamp units
+        let d = time.saturating_sub(last.0) as f64 * clock_rate / 1_000_000.0
+            - timestamp.wrapping_sub(last.1) as i32 as f64;
+        self.jitter += (d.abs() - self.jitter) / 16.0;
+        Some((self.jitter * 1_000_000.0 / clock_rate) as u32)
+    }
+}
+
+#[derive(Default)]
+pub struct RtpLog {
+    streams: HashMap<u32, Stream>,
+    stats: MediaPerfStats,
+}
+
+impl L7ProtocolParserInterface for RtpLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::UDP {
+            return false;
+        }
+        if is_rtcp(payload) {
+            parse_rtcp(payload).is_some()
+        } else {
+            parse_rtp(payload).is_some()
+        }
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if is_rtcp(payload) {
+            // payload may be a single packet split from a compound packet
+            let Some(report) = parse_rtcp_packets(payload, false) else {
+                return Err(Error::RtpLogParseFailed("invalid rtcp packet".to_string()));
+            };
+            if param.parse_perf {
+                self.on_rtcp(report);
+            }
+        } else {
+            let Some(header) = parse_rtp(payload) else {
+                return Err(Error::RtpLogParseFailed("invalid rtp packet".to_string()));
+            };
+            if param.parse_perf {
+                self.on_rtp(header, param.time);
+            }
+        }
+        Ok(L7ParseResult::None)
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::RTP
+    }
+
+    fn parsable_on_tcp(&self) -> bool {
+        false
+    }
+
+    // media streams use dynamic ports, and the heuristics may take other udp
+    // traffic as rtp, so it is enabled only on demand
+    fn parse_default(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        None
+    }
+
+    fn datagram_message_len(&self, payload: &[u8]) -> Option<usize> {
+        if is_rtcp(payload) {
+            rtcp_packet_len(payload)
+        } else {
+            None
+        }
+    }
+
+    fn media_stats(&mut self) -> Option<MediaPerfStats> {
+        for stream in self.streams.values_mut() {
+            if stream.active {
+                self.stats.ssrc_count += 1;
+                stream.active = false;
+            }
+        }
+        if self.stats == MediaPerfStats::default() {
+            return None;
+        }
+        let mut stats = std::mem::take(&mut self.stats);
+        // late packets of the previous period may make received more than expected
+        stats.lost_count = stats.expected_count.saturating_sub(stats.packet_count);
+        Some(stats)
+    }
+}
+
+impl RtpLog {
+    fn on_rtp(&mut self, header: RtpHeader, time: u64) {
+        let stats = &mut self.stats;
+        let Some(stream) = self.streams.get_mut(&header.ssrc) else {
+            if self.streams.len() < MAX_SSRC_COUNT {
+                stats.packet_count += 1;
+                stats.expected_count += 1;
+                self.streams.insert(header.ssrc, Stream::new(&header, time));
+            }
+            return;
+        };
+        stream.active = true;
+        let delta = header.sequence.wrapping_sub(stream.max_sequence);
+        if delta == 0 {
+            // duplicated
+            return;
+        }
+        stats.packet_count += 1;
+        if delta < MAX_DROPOUT {
+            stream.max_sequence = header.sequence;
+            stats.expected_count += delta as u32;
+        } else if delta > u16::MAX - MAX_MISORDER {
+            // late packet, already counted in expected
+            stats.out_of_order_count += 1;
+            return;
+        } else {
+            // sequence or payload type changed, such as the source restarted
+            *stream = Stream::new(&header, time);
+            stats.expected_count += 1;
+            return;
+        }
+        if stream.payload_type != header.payload_type {
+            *stream = Stream::new(&header, time);
+            return;
+        }
+        if let Some(jitter) = stream.update_jitter(time, header.timestamp) {
+            stats.jitter_sum += jitter as u64;
+            stats.jitter_count += 1;
+            stats.jitter_max = stats.jitter_max.max(jitter);
+        }
+    }
+
+    fn on_rtcp(&mut self, report: RtcpReport) {
+        self.stats.rtcp_count += report.report_count;
+        self.stats.rtcp_fraction_lost_max = self
+            .stats
+            .rtcp_fraction_lost_max
+            .max(report.fraction_lost_max as u32);
+        for ssrc in report.bye_ssrcs {
+            if let Some(stream) = self.streams.remove(&ssrc) {
+                if stream.active {
+                    self.stats.ssrc_count += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    use crate::{
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn rtp(payload_type: u8, sequence: u16, timestamp: u32, ssrc: u32) -> Vec<u8> {
+        let mut p = vec![0x80, payload_type];
+        p.extend_from_slice(&sequence.to_be_bytes());
+        p.extend_from_slice(&timestamp.to_be_bytes());
+        p.extend_from_slice(&ssrc.to_be_bytes());
+        p.extend_from_slice(&[0xd5; 160]);
+        p
+    }
+
+    fn param(time: u64) -> ParseParam<'static> {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        packet.lookup_key.direction = PacketDirection::ClientToServer;
+        packet.lookup_key.timestamp = std::time::Duration::from_micros(time).into();
+        ParseParam::new(&packet, log_cache, true, true)
+    }
+
+    fn parse(log: &mut RtpLog, payload: &[u8], time: u64) {
+        let param = param(time);
+        assert!(log.check_payload(payload, &param));
+        assert!(log.parse_payload(payload, &param).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_loss_and_reorder() {
+        let mut log = RtpLog::default();
+        // PCMU, 20ms per packet, packet 3 arrives late and packet 5 is lost
+        for (i, sequence) in [1u16, 2, 4, 3, 6, 7].into_iter().enumerate() {
+            let time = 1_000_000 + i as u64 * 20_000;
+            parse(
+                &mut log,
+                &rtp(0, sequence, sequence as u32 * 160, 0x1234),
+                time,
+            );
+        }
+        let stats = log.media_stats().unwrap();
+        assert_eq!(stats.ssrc_count, 1);
+        assert_eq!(stats.packet_count, 6);
+        assert_eq!(stats.expected_count, 7);
+        assert_eq!(stats.lost_count, 1);
+        assert_eq!(stats.out_of_order_count, 1);
+        assert_eq!(stats.jitter_count, 4);
+        assert!(log.media_stats().is_none());
+
+        // sequence wraps around
+        parse(&mut log, &rtp(0, 0xffff, 0, 0x5678), 0);
+        parse(&mut log, &rtp(0, 1, 320, 0x5678), 40_000);
+        let stats = log.media_stats().unwrap();
+        assert_eq!(stats.expected_count, 3);
+        assert_eq!(stats.lost_count, 1);
+        assert_eq!(stats.jitter_count, 1);
+        assert_eq!(stats.jitter_max, 0);
+    }
+
+    #[test]
+    fn test_jitter() {
+        let mut log = RtpLog::default();
+        parse(&mut log, &rtp(8, 100, 0, 1), 0);
+        // 10ms later than expected, D = 80 in timestamp units, J = 80 / 16 = 5
+        parse(&mut log, &rtp(8, 101, 160, 1), 30_000);
+        let stats = log.media_stats().unwrap();
+        assert_eq!(stats.jitter_max, 625);
+
+        // dynamic payload type, clock rate estimated as 48000 after 1 second
+        let mut log = RtpLog::default();
+        for i in 0..=50u32 {
+            parse(&mut log, &rtp(111, i as u16, i * 960, 2), i as u64 * 20_000);
+        }
+        assert_eq!(log.streams[&2].clock_rate, Some(48000));
+        assert_eq!(log.media_stats().unwrap().jitter_count, 1);
+    }
+
+    #[test]
+    fn test_rtcp_compound() {
+        let mut log = RtpLog::default();
+        parse(&mut log, &rtp(0, 1, 0, 0xaabb), 0);
+        // SR without report block, RR with one report block, SDES and BYE
+        let mut compound = vec![0x80, RTCP_SR, 0, 6, 0, 0, 0, 1];
+        compound.extend_from_slice(&[0; SENDER_INFO_SIZE]);
+        compound.extend_from_slice(&[0x81, RTCP_RR, 0, 7, 0, 0, 0, 1]);
+        compound.extend_from_slice(&[0, 0, 0xaa, 0xbb, 50, 0, 0, 3]);
+        compound.extend_from_slice(&[0; 16]);
+        compound.extend_from_slice(&[0x81, 202, 0, 1, 0, 0, 0, 1]);
+        compound.extend_from_slice(&[0x81, RTCP_BYE, 0, 1, 0, 0, 0xaa, 0xbb]);
+        let param = param(1000);
+        assert!(log.check_payload(&compound, &param));
+
+        // packets after the first one are not valid compound packets, but are parsed
+        let mut lens = vec![];
+        let mut rest = &compound[..];
+        while let Some(len) = log.datagram_message_len(rest) {
+            lens.push(len);
+            assert!(log.parse_payload(&rest[..len], &param).unwrap().is_none());
+            rest = &rest[len..];
+        }
+        assert_eq!(lens, vec![28, 32, 8, 8]);
+        assert!(rest.is_empty());
+        assert!(log.streams.is_empty());
+        let stats = log.media_stats().unwrap();
+        assert_eq!(stats.ssrc_count, 1);
+        assert_eq!(stats.rtcp_count, 2);
+        assert_eq!(stats.rtcp_fraction_lost_max, 50);
+
+        // rtp packets are not split
+        assert_eq!(log.datagram_message_len(&rtp(0, 2, 160, 0xaabb)), None);
+    }
+
+    #[test]
+    fn test_rtcp() {
+        let mut log = RtpLog::default();
+        parse(&mut log, &rtp(0, 1, 0, 0xaabb), 0);
+        // RR with one report block of fraction lost 25/256, followed by BYE
+        let mut rr = vec![0x81, RTCP_RR, 0, 7, 0, 0, 0, 1];
+        rr.extend_from_slice(&[0, 0, 0xaa, 0xbb, 25, 0, 0, 3]);
+        rr.extend_from_slice(&[0; 16]);
+        rr.extend_from_slice(&[0x81, RTCP_BYE, 0, 1, 0, 0, 0xaa, 0xbb]);
+        parse(&mut log, &rr, 1000);
+        assert!(log.streams.is_empty());
+        let stats = log.media_stats().unwrap();
+        assert_eq!(stats.ssrc_count, 1);
+        assert_eq!(stats.rtcp_count, 1);
+        assert_eq!(stats.rtcp_fraction_lost_max, 25);
+
+        // BYE alone is not a valid compound packet
+        assert!(parse_rtcp(&[0x81, RTCP_BYE, 0, 1, 0, 0, 0xaa, 0xbb]).is_none());
+        // truncated compound packet
+        assert!(parse_rtcp(&rr[..rr.len() - 4]).is_none());
+        // reserved payload type and bad version
+        assert!(parse_rtp(&rtp(72, 1, 0, 1)).is_none());
+        assert!(parse_rtp(&[0x40; 20]).is_none());
+    }
+}
@@ -0,0 +1,491 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// SIP, reference: https://www.rfc-editor.org/rfc/rfc3261
+//
+// INVITE sip:bob@biloxi.com SIP/2.0        SIP/2.0 200 OK
+// Call-ID: a84b4c76e66710@pc33.atlanta.com  Call-ID: a84b4c76e66710@pc33.atlanta.com
+// CSeq: 314159 INVITE                      CSeq: 314159 INVITE
+//
+// A transaction is identified by Call-ID and CSeq. Provisional responses (1xx) are
+// skipped so that the rrt of INVITE is the call setup latency to the final response,
+// and ACK, which has no response, is reported as a session.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use serde::Serialize;
+
+use super::http::parse_v1_headers;
+use super::pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response};
+use super::{value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        flow::L7PerfStats,
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    flow_generator::error::{Error, Result},
+};
+use public::l7_protocol::L7Protocol;
+
+const SIP_VERSION: &str = "SIP/2.0";
+const METHODS: [&str; 14] = [
+    "INVITE",
+    "ACK",
+    "BYE",
+    "CANCEL",
+    "REGISTER",
+    "OPTIONS",
+    "PRACK",
+    "SUBSCRIBE",
+    "NOTIFY",
+    "PUBLISH",
+    "INFO",
+    "REFER",
+    "MESSAGE",
+    "UPDATE",
+];
+const METHOD_ACK: &str = "ACK";
+
+// full and compact form of header names
+const HEADER_CALL_ID: [&str; 2] = ["call-id", "i"];
+const HEADER_CSEQ: [&str; 2] = ["cseq", ""];
+const HEADER_FROM: [&str; 2] = ["from", "f"];
+const HEADER_TO: [&str; 2] = ["to", "t"];
+const HEADER_USER_AGENT: [&str; 2] = ["user-agent", ""];
+const HEADER_SERVER: [&str; 2] = ["server", ""];
+
+// the address in `"Alice" <sip:alice@atlanta.com>;tag=1928301774`
+fn name_addr(value: &str) -> &str {
+    match (value.find('<'), value.find('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value.split(';').next().unwrap_or_default().trim(),
+    }
+}
+
+// the host in `sip:bob@biloxi.com:5060;transport=udp`
+fn uri_host(uri: &str) -> &str {
+    let uri = uri.split_once(':').map(|(_, rest)| rest).unwrap_or(uri);
+    let host_port = uri.rsplit_once('@').map(|(_, h)| h).unwrap_or(uri);
+    let host_port = host_port.split([';', '?']).next().unwrap_or_default();
+    // ipv6 reference or host with port
+    match host_port.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => host_port.split(':').next().unwrap_or_default(),
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SipInfo {
+    #[serde(skip)]
+    session_id: u32,
+    #[serde(rename = "request_type", skip_serializing_if = "value_is_default")]
+    pub method: String,
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    pub request_uri: String,
+    #[serde(rename = "request_domain", skip_serializing_if = "value_is_default")]
+    pub host: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub call_id: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub cseq: u32,
+    // method in CSeq, which is the method of the request for responses
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub cseq_method: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub from: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub to: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub user_agent: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub server: String,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub exception: String,
+
+    msg_type: LogMessageType,
+    rrt: u64,
+}
+
+impl L7ProtocolInfoInterface for SipInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.session_id)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::SipInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::SIP,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+}
+
+impl SipInfo {
+    pub fn merge(&mut self, other: &mut Self) {
+        if self.method.is_empty() {
+            std::mem::swap(&mut self.method, &mut other.method);
+        }
+        if self.to.is_empty() {
+            std::mem::swap(&mut self.to, &mut other.to);
+        }
+        std::mem::swap(&mut self.server, &mut other.server);
+        self.status_code = other.status_code;
+        self.status = other.status;
+        std::mem::swap(&mut self.exception, &mut other.exception);
+    }
+
+    fn transaction_id(call_id: &str, cseq: u32, method: &str) -> u32 {
+        let mut hasher = DefaultHasher::new();
+        call_id.hash(&mut hasher);
+        cseq.hash(&mut hasher);
+        method.hash(&mut hasher);
+        hasher.finish() as u32
+    }
+}
+
+impl From<SipInfo> for L7ProtocolSendLog {
+    fn from(f: SipInfo) -> Self {
+        let mut attributes = vec![];
+        for (key, val) in [
+            ("sip_call_id", f.call_id),
+            ("sip_from", f.from),
+            ("sip_to", f.to),
+            ("sip_user_agent", f.user_agent),
+            ("sip_server", f.server),
+        ] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        L7ProtocolSendLog {
+            req: L7Request {
+                req_type: f.method,
+                domain: f.host,
+                resource: f.request_uri,
+                ..Default::default()
+            },
+            resp: L7Response {
+                code: f.status_code.map(|c| c as i32),
+                status: f.status,
+                exception: f.exception,
+                ..Default::default()
+            },
+            version: Some("2.0".to_string()),
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(f.cseq),
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SipLog {
+    perf_stats: Option<L7PerfStats>,
+}
+
+impl L7ProtocolParserInterface for SipLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() {
+            return false;
+        }
+        match Self::parse(payload) {
+            Ok(info) => info.msg_type != LogMessageType::Response,
+            Err(_) => false,
+        }
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+        let mut info = Self::parse(payload)?;
+        if info.msg_type == LogMessageType::Response
+            && info.status_code.map(|c| c < 200).unwrap_or_default()
+        {
+            return Ok(L7ParseResult::None);
+        }
+        if let Some(perf_stats) = self.perf_stats.as_mut() {
+            match info.msg_type {
+                LogMessageType::Request => perf_stats.inc_req(),
+                LogMessageType::Response => perf_stats.inc_resp(),
+                _ => {}
+            }
+            match info.status {
+                L7ResponseStatus::ClientError => perf_stats.inc_req_err(),
+                L7ResponseStatus::ServerError => perf_stats.inc_resp_err(),
+                _ => {}
+            }
+        }
+        if info.msg_type != LogMessageType::Session {
+            info.cal_rrt(param, None).map(|rrt| {
+                info.rrt = rrt;
+                self.perf_stats.as_mut().map(|p| p.update_rrt(rrt));
+            });
+        }
+        if param.parse_log {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::SipInfo(info)))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::SIP
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl SipLog {
+    // only the first message is parsed if there are several in a tcp segment
+    fn parse(payload: &[u8]) -> Result<SipInfo> {
+        let mut headers = parse_v1_headers(payload);
+        let Some(first_line) = headers.next() else {
+            return Err(Error::SipLogParseFailed(
+                "sip start line not found".to_string(),
+            ));
+        };
+        let mut info = SipInfo::default();
+        if let Some(status) = first_line.strip_prefix(SIP_VERSION) {
+            // SIP/2.0 486 Busy Here
+            let mut parts = status.trim_start().splitn(2, ' ');
+            let Some(code) = parts.next().and_then(|c| c.parse::<u16>().ok()) else {
+                let err_msg = format!("sip status line {} invalid", first_line);
+                return Err(Error::SipLogParseFailed(err_msg));
+            };
+            if !(100..700).contains(&code) {
+                let err_msg = format!("sip status code {} invalid", code);
+                return Err(Error::SipLogParseFailed(err_msg));
+            }
+            info.msg_type = LogMessageType::Response;
+            info.status_code = Some(code);
+            info.status = match code {
+                400..=499 | 600..=699 => L7ResponseStatus::ClientError,
+                500..=599 => L7ResponseStatus::ServerError,
+                _ => L7ResponseStatus::Ok,
+            };
+            if info.status != L7ResponseStatus::Ok {
+                info.exception = parts.next().unwrap_or_default().to_string();
+            }
+        } else {
+            // INVITE sip:bob@biloxi.com SIP/2.0
+            let mut parts = first_line.split(' ');
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(method), Some(uri), Some(SIP_VERSION), None) if METHODS.contains(&method) => {
+                    info.msg_type = if method == METHOD_ACK {
+                        LogMessageType::Session
+                    } else {
+                        LogMessageType::Request
+                    };
+                    info.method = method.to_string();
+                    info.host = uri_host(uri).to_string();
+                    info.request_uri = uri.to_string();
+                }
+                _ => {
+                    let err_msg = format!("sip request line {} invalid", first_line);
+                    return Err(Error::SipLogParseFailed(err_msg));
+                }
+            }
+        }
+
+        for header in headers {
+            let Some((key, value)) = header.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+            let is = |names: [&str; 2]| names.iter().any(|n| !n.is_empty() && key == *n);
+            if is(HEADER_CALL_ID) {
+                info.call_id = value.to_string();
+            } else if is(HEADER_CSEQ) {
+                // 314159 INVITE
+                let mut parts = value.split_ascii_whitespace();
+                info.cseq = parts
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_default();
+                info.cseq_method = parts.next().unwrap_or_default().to_string();
+            } else if is(HEADER_FROM) {
+                info.from = name_addr(value).to_string();
+            } else if is(HEADER_TO) {
+                info.to = name_addr(value).to_string();
+            } else if is(HEADER_USER_AGENT) {
+                info.user_agent = value.to_string();
+            } else if is(HEADER_SERVER) {
+                info.server = value.to_string();
+            }
+        }
+        if info.call_id.is_empty() || info.cseq_method.is_empty() {
+            return Err(Error::SipLogParseFailed(
+                "sip call-id or cseq not found".to_string(),
+            ));
+        }
+        info.session_id = SipInfo::transaction_id(&info.call_id, info.cseq, &info.cseq_method);
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    use crate::{
+        common::{
+            enums::IpProtocol, flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket,
+        },
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    const INVITE: &[u8] = b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+        Via: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+        Max-Forwards: 70\r\n\
+        To: Bob <sip:bob@biloxi.com>\r\n\
+        From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+        Call-ID: a84b4c76e66710@pc33.atlanta.com\r\n\
+        CSeq: 314159 INVITE\r\n\
+        User-Agent: Linphone/5.2.0\r\n\
+        Content-Length: 0\r\n\r\n";
+
+    fn response(status_line: &str, cseq: &str) -> Vec<u8> {
+        format!(
+            "{}\r\nVia: SIP/2.0/UDP pc33.atlanta.com;branch=z9hG4bK776asdhds\r\n\
+            To: Bob <sip:bob@biloxi.com>;tag=a6c85cf\r\n\
+            From: Alice <sip:alice@atlanta.com>;tag=1928301774\r\n\
+            i: a84b4c76e66710@pc33.atlanta.com\r\n\
+            CSeq: {}\r\nServer: Asterisk PBX 18.0\r\nContent-Length: 0\r\n\r\n",
+            status_line, cseq
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_invite() {
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packet = MetaPacket::default();
+        packet.lookup_key.proto = IpProtocol::UDP;
+        packet.lookup_key.direction = PacketDirection::ClientToServer;
+        let param = ParseParam::new(&packet, log_cache.clone(), true, true);
+        let mut sip = SipLog::default();
+        assert!(sip.check_payload(INVITE, &param));
+        let req = match sip.parse_payload(INVITE, &param).unwrap().unwrap_single() {
+            L7ProtocolInfo::SipInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(req.msg_type, LogMessageType::Request);
+        assert_eq!(req.method, "INVITE");
+        assert_eq!(req.request_uri, "sip:bob@biloxi.com");
+        assert_eq!(req.host, "biloxi.com");
+        assert_eq!(req.call_id, "a84b4c76e66710@pc33.atlanta.com");
+        assert_eq!(req.cseq, 314159);
+        assert_eq!(req.from, "sip:alice@atlanta.com");
+        assert_eq!(req.to, "sip:bob@biloxi.com");
+        assert_eq!(req.user_agent, "Linphone/5.2.0");
+
+        packet.lookup_key.direction = PacketDirection::ServerToClient;
+        let param = ParseParam::new(&packet, log_cache, true, true);
+        // provisional responses are skipped
+        let ringing = response("SIP/2.0 180 Ringing", "314159 INVITE");
+        assert!(!sip.check_payload(&ringing, &param));
+        assert!(matches!(
+            sip.parse_payload(&ringing, &param).unwrap(),
+            L7ParseResult::None
+        ));
+        let busy = response("SIP/2.0 486 Busy Here", "314159 INVITE");
+        let resp = match sip.parse_payload(&busy, &param).unwrap().unwrap_single() {
+            L7ProtocolInfo::SipInfo(i) => i,
+            _ => unreachable!(),
+        };
+        assert_eq!(resp.msg_type, LogMessageType::Response);
+        assert_eq!(resp.session_id(), req.session_id());
+        assert_eq!(resp.status_code, Some(486));
+        assert_eq!(resp.status, L7ResponseStatus::ClientError);
+        assert_eq!(resp.exception, "Busy Here");
+        assert_eq!(resp.server, "Asterisk PBX 18.0");
+
+        let perf_stats = sip.perf_stats().unwrap();
+        assert_eq!(perf_stats.request_count, 1);
+        assert_eq!(perf_stats.response_count, 1);
+        assert_eq!(perf_stats.err_client_count, 1);
+    }
+
+    #[test]
+    fn test_transactions() {
+        let cancel = response("SIP/2.0 200 OK", "314159 CANCEL");
+        let invite = SipLog::parse(INVITE).unwrap();
+        let cancel = SipLog::parse(&cancel).unwrap();
+        assert_ne!(invite.session_id(), cancel.session_id());
+        assert_eq!(cancel.status, L7ResponseStatus::Ok);
+
+        let ack = b"ACK sip:bob@192.0.2.4:5060;transport=udp SIP/2.0\r\nCall-ID: a84b4c76e66710@pc33.atlanta.com\r\nCSeq: 314159 ACK\r\n\r\n";
+        let ack = SipLog::parse(ack).unwrap();
+        assert_eq!(ack.msg_type, LogMessageType::Session);
+        assert_eq!(ack.host, "192.0.2.4");
+
+        assert!(SipLog::parse(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n").is_err());
+        assert!(SipLog::parse(b"INVITE sip:bob@biloxi.com SIP/2.0\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_uri_host() {
+        assert_eq!(uri_host("sip:bob@biloxi.com"), "biloxi.com");
+        assert_eq!(uri_host("sips:[2001:db8::1]:5061"), "2001:db8::1");
+        assert_eq!(
+            uri_host("sip:registrar.biloxi.com;lr"),
+            "registrar.biloxi.com"
+        );
+        assert_eq!(uri_host("tel:+1-201-555-0123"), "+1-201-555-0123");
+    }
+}
@@ -98,6 +98,18 @@ impl L7ProtocolInfoInterface for MongoDBInfo {
     fn is_tls(&self) -> bool {
         self.is_tls
     }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+
+    fn request_len(&self) -> Option<u32> {
+        (self.req_len > 0).then_some(self.req_len)
+    }
+
+    fn response_len(&self) -> Option<u32> {
+        (self.resp_len > 0).then_some(self.resp_len)
+    }
 }
 
 // 协议文档: https://www.mongodb.com/docs/manual/reference/mongodb-wire-protocol/
@@ -211,6 +223,17 @@ impl L7ProtocolParserInterface for MongoDBLog {
     fn perf_stats(&mut self) -> Option<L7PerfStats> {
         self.perf_stats.take()
     }
+
+    fn message_len(&self, payload: &[u8], _: PacketDirection) -> Option<usize> {
+        if payload.len() < _HEADER_SIZE {
+            return None;
+        }
+        let len = bytes::read_u32_le(payload) as usize;
+        if len < _HEADER_SIZE {
+            return None;
+        }
+        Some(len)
+    }
 }
 
 const _OP_REPLY: u32 = 1;
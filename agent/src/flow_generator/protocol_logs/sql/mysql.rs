@@ -37,6 +37,7 @@ use crate::{
     },
     flow_generator::{
         error::{Error, Result},
+        pool::pooled_string_lossy,
         protocol_logs::pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response},
     },
     utils::bytes,
@@ -107,6 +108,14 @@ impl L7ProtocolInfoInterface for MysqlInfo {
     fn is_tls(&self) -> bool {
         self.is_tls
     }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+
+    fn response_code(&self) -> Option<String> {
+        self.error_code.map(|c| c.to_string())
+    }
 }
 
 impl MysqlInfo {
@@ -186,9 +195,7 @@ impl MysqlInfo {
     ) {
         let payload = mysql_string(payload);
         self.context = attempt_obfuscation(obfuscate_cache, payload)
-            .map_or(String::from_utf8_lossy(payload).to_string(), |m| {
-                String::from_utf8_lossy(&m).to_string()
-            });
+            .map_or_else(|| pooled_string_lossy(payload), |m| pooled_string_lossy(&m));
 
         if let Some(t) = trace_id {
             self.trace_id = extra_sql_trace_id(self.context.as_str(), t);
@@ -322,6 +329,14 @@ impl L7ProtocolParserInterface for MysqlLog {
         self.perf_stats.take()
     }
 
+    // only the first packet of a message is reassembled
+    fn message_len(&self, payload: &[u8], _: PacketDirection) -> Option<usize> {
+        if payload.len() < HEADER_LEN {
+            return None;
+        }
+        Some((read_u32_le(payload) & 0xffffff) as usize + HEADER_LEN)
+    }
+
     fn set_obfuscate_cache(&mut self, obfuscate_cache: Option<ObfuscateCache>) {
         self.obfuscate_cache = obfuscate_cache;
     }
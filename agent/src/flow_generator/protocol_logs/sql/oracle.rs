@@ -110,6 +110,10 @@ impl L7ProtocolInfoInterface for OracleInfo {
     fn is_tls(&self) -> bool {
         self.is_tls
     }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
 }
 
 impl From<OracleInfo> for L7ProtocolSendLog {
@@ -130,6 +130,18 @@ impl L7ProtocolInfoInterface for PostgreInfo {
     fn is_tls(&self) -> bool {
         self.is_tls
     }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+
+    // the first two characters of sqlstate are the error class, such as 42 for syntax errors
+    fn response_code(&self) -> Option<String> {
+        if self.resp_type != 'E' {
+            return None;
+        }
+        self.result.get(..2).map(|c| c.to_owned())
+    }
 }
 
 impl From<PostgreInfo> for L7ProtocolSendLog {
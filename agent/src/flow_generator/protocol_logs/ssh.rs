@@ -0,0 +1,735 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// SSH transport layer, reference: https://www.rfc-editor.org/rfc/rfc4253
+// HASSH fingerprint, reference: https://github.com/salesforce/hassh
+//
+// Only the version banner and the binary packets before NEWKEYS are plaintext:
+//
+//   SSH-protoversion-softwareversion SP comments CR LF
+//   uint32 packet_length | byte padding_length | byte[n1] payload | byte[n2] padding | mac
+//
+// Two session logs are reported for each connection:
+// - handshake: banners, HASSH/HASSHServer and negotiated algorithms of both KEXINIT
+// - auth: duration from NEWKEYS to the end of user authentication. It is inferred from
+//   the size of encrypted packets, USERAUTH_SUCCESS is the smallest packet sent by
+//   the server, while SERVICE_ACCEPT or EXT_INFO leading the auth phase and
+//   USERAUTH_FAILURE with a name-list are larger.
+
+use std::borrow::Cow;
+
+use md5::{Digest, Md5};
+use serde::Serialize;
+
+use super::pb_adapter::{
+    ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, MetricKeyVal,
+};
+use super::{value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::{L7PerfStats, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    flow_generator::error::{Error, Result},
+    utils::bytes::read_u32_be,
+};
+use public::l7_protocol::L7Protocol;
+
+const BANNER_PREFIX: &[u8] = b"SSH-";
+const MAX_BANNER_LEN: usize = 255;
+// packet_length and padding_length
+const PACKET_HEADER_LEN: usize = 5;
+const MAX_PACKET_LEN: usize = 35000;
+const KEXINIT_COOKIE_LEN: usize = 16;
+// kex, host key, encryption, mac and compression of both directions, languages are ignored
+const KEXINIT_NAME_LISTS: usize = 8;
+
+const MSG_DISCONNECT: u8 = 1;
+const MSG_KEXINIT: u8 = 20;
+const MSG_NEWKEYS: u8 = 21;
+
+// the auth phase is regarded as not completed if no success is found in these packets
+const AUTH_PHASE_MAX_SERVER_PACKETS: u32 = 8;
+
+const PHASE_HANDSHAKE: &str = "handshake";
+const PHASE_AUTH: &str = "auth";
+const PHASE_DISCONNECT: &str = "disconnect";
+
+fn parse_banner(line: &[u8]) -> Option<(String, String)> {
+    let line = line.strip_prefix(BANNER_PREFIX)?;
+    let line = std::str::from_utf8(line).ok()?.trim_end();
+    let (proto_version, software_version) = line.split_once('-')?;
+    if proto_version.is_empty()
+        || !proto_version
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.')
+    {
+        return None;
+    }
+    Some((proto_version.to_string(), software_version.to_string()))
+}
+
+fn read_string(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = read_u32_be(data.get(..4)?) as usize;
+    let value = data.get(4..4 + len)?;
+    Some((value, &data[4 + len..]))
+}
+
+// the first algorithm of client which is also supported by server
+fn negotiate(client: &str, server: &str) -> String {
+    client
+        .split(',')
+        .find(|c| server.split(',').any(|s| s == *c))
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct KexInit {
+    name_lists: Vec<String>,
+}
+
+impl KexInit {
+    const KEX: usize = 0;
+    const HOST_KEY: usize = 1;
+    const ENCRYPTION_C2S: usize = 2;
+    const ENCRYPTION_S2C: usize = 3;
+    const MAC_C2S: usize = 4;
+    const MAC_S2C: usize = 5;
+    const COMPRESSION_C2S: usize = 6;
+    const COMPRESSION_S2C: usize = 7;
+
+    // payload after the message code
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let mut data = payload.get(KEXINIT_COOKIE_LEN..)?;
+        let mut name_lists = Vec::with_capacity(KEXINIT_NAME_LISTS);
+        for _ in 0..KEXINIT_NAME_LISTS {
+            let (value, rest) = read_string(data)?;
+            name_lists.push(String::from_utf8_lossy(value).into_owned());
+            data = rest;
+        }
+        Some(Self { name_lists })
+    }
+
+    // hassh of client and hasshServer of server
+    fn hassh(&self, from_client: bool) -> String {
+        let (encryption, mac, compression) = if from_client {
+            (Self::ENCRYPTION_C2S, Self::MAC_C2S, Self::COMPRESSION_C2S)
+        } else {
+            (Self::ENCRYPTION_S2C, Self::MAC_S2C, Self::COMPRESSION_S2C)
+        };
+        let s = format!(
+            "{};{};{};{}",
+            self.name_lists[Self::KEX],
+            self.name_lists[encryption],
+            self.name_lists[mac],
+            self.name_lists[compression]
+        );
+        hex::encode(Md5::digest(s.as_bytes()))
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SshInfo {
+    #[serde(rename = "request_type")]
+    pub phase: &'static str,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub version: String,
+    #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
+    pub client_software: String,
+    #[serde(rename = "response_result", skip_serializing_if = "value_is_default")]
+    pub server_software: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hassh: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hassh_server: Option<String>,
+    // negotiated algorithms, cipher, mac and compression are of client to server
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub kex: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub host_key: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub cipher: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub mac: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub compression: String,
+
+    // microseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_duration: Option<u64>,
+    // encrypted packets sent by client in the auth phase
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub auth_packets: u32,
+
+    #[serde(rename = "response_status")]
+    pub status: L7ResponseStatus,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "value_is_default"
+    )]
+    pub exception: String,
+
+    rrt: u64,
+}
+
+impl L7ProtocolInfoInterface for SshInfo {
+    fn session_id(&self) -> Option<u32> {
+        None
+    }
+
+    fn merge_log(&mut self, _: &mut L7ProtocolInfo) -> Result<()> {
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::SSH,
+            msg_type: LogMessageType::Session,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
+}
+
+impl From<SshInfo> for L7ProtocolSendLog {
+    fn from(f: SshInfo) -> Self {
+        let mut attributes = vec![];
+        for (key, val) in [
+            ("ssh_hassh", f.hassh.unwrap_or_default()),
+            ("ssh_hassh_server", f.hassh_server.unwrap_or_default()),
+            ("ssh_kex", f.kex),
+            ("ssh_host_key", f.host_key),
+            ("ssh_cipher", f.cipher),
+            ("ssh_mac", f.mac),
+            ("ssh_compression", f.compression),
+        ] {
+            if !val.is_empty() {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
+        let mut metrics = vec![];
+        if let Some(duration) = f.auth_duration {
+            metrics.push(MetricKeyVal {
+                key: "ssh_auth_duration".to_string(),
+                val: duration as f32,
+            });
+            metrics.push(MetricKeyVal {
+                key: "ssh_auth_packets".to_string(),
+                val: f.auth_packets as f32,
+            });
+        }
+        L7ProtocolSendLog {
+            req: L7Request {
+                req_type: f.phase.to_string(),
+                resource: f.client_software,
+                ..Default::default()
+            },
+            resp: L7Response {
+                result: f.server_software,
+                status: f.status,
+                exception: f.exception,
+                ..Default::default()
+            },
+            version: Some(f.version),
+            ext_info: Some(ExtendedInfo {
+                attributes: if attributes.is_empty() {
+                    None
+                } else {
+                    Some(attributes)
+                },
+                metrics: if metrics.is_empty() {
+                    None
+                } else {
+                    Some(metrics)
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default)]
+struct Peer {
+    // protoversion and softwareversion
+    banner: Option<(String, String)>,
+    kexinit: Option<KexInit>,
+    // KEXINIT may be split into several segments
+    pending: Vec<u8>,
+    encrypted: bool,
+}
+
+// events of the plaintext of one direction
+#[derive(Default)]
+struct Plaintext {
+    kexinit: bool,
+    // bytes following NEWKEYS in the same segment
+    newkeys: Option<usize>,
+    disconnect: Option<(u32, String)>,
+}
+
+impl Peer {
+    fn read(&mut self, payload: &[u8]) -> Result<Plaintext> {
+        let mut events = Plaintext::default();
+        let buffer = if self.pending.is_empty() {
+            Cow::Borrowed(payload)
+        } else {
+            let mut pending = std::mem::take(&mut self.pending);
+            pending.extend_from_slice(payload);
+            Cow::Owned(pending)
+        };
+        let mut data = &buffer[..];
+        if data.starts_with(BANNER_PREFIX) {
+            let Some(end) = data.iter().take(MAX_BANNER_LEN).position(|b| *b == b'\n') else {
+                return Err(Error::SshLogParseFailed("ssh banner invalid".to_string()));
+            };
+            self.banner = parse_banner(&data[..end]);
+            data = &data[end + 1..];
+        }
+        while data.len() > PACKET_HEADER_LEN {
+            let packet_len = read_u32_be(data) as usize;
+            let padding_len = data[4] as usize;
+            if packet_len > MAX_PACKET_LEN || padding_len + 2 > packet_len {
+                let err_msg = format!("ssh packet length {} invalid", packet_len);
+                return Err(Error::SshLogParseFailed(err_msg));
+            }
+            if data.len() < 4 + packet_len {
+                if data[PACKET_HEADER_LEN] == MSG_KEXINIT {
+                    self.pending = data.to_vec();
+                }
+                break;
+            }
+            let message = &data[PACKET_HEADER_LEN..4 + packet_len - padding_len];
+            data = &data[4 + packet_len..];
+            match message[0] {
+                MSG_KEXINIT => {
+                    self.kexinit = KexInit::parse(&message[1..]);
+                    events.kexinit = self.kexinit.is_some();
+                }
+                MSG_NEWKEYS => {
+                    self.encrypted = true;
+                    events.newkeys = Some(data.len());
+                    break;
+                }
+                MSG_DISCONNECT => {
+                    let reason = message.get(1..5).map(read_u32_be).unwrap_or_default();
+                    let description = message
+                        .get(5..)
+                        .and_then(read_string)
+                        .map(|(d, _)| String::from_utf8_lossy(d).into_owned())
+                        .unwrap_or_default();
+                    events.disconnect = Some((reason, description));
+                }
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+}
+
+struct AuthPhase {
+    start_time: u64,
+    client_packets: u32,
+    server_packets: u32,
+    // size of SERVICE_ACCEPT or EXT_INFO
+    first_server_size: usize,
+}
+
+#[derive(Default)]
+pub struct SshLog {
+    perf_stats: Option<L7PerfStats>,
+    client: Peer,
+    server: Peer,
+    handshake_logged: bool,
+    auth: Option<AuthPhase>,
+}
+
+impl L7ProtocolParserInterface for SshLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> bool {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::TCP {
+            return false;
+        }
+        // the server usually sends its banner first
+        let end = payload
+            .iter()
+            .take(MAX_BANNER_LEN)
+            .position(|b| *b == b'\n')
+            .unwrap_or(payload.len());
+        parse_banner(&payload[..end]).is_some()
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        if self.perf_stats.is_none() && param.parse_perf {
+            self.perf_stats = Some(L7PerfStats::default())
+        };
+        let from_client = param.direction == PacketDirection::ClientToServer;
+        let mut infos = vec![];
+        let peer = if from_client {
+            &mut self.client
+        } else {
+            &mut self.server
+        };
+        if peer.encrypted {
+            infos.extend(self.on_encrypted(payload.len(), from_client, param.time));
+        } else {
+            let events = peer.read(payload)?;
+            if let Some((reason, description)) = events.disconnect {
+                let mut info = self.new_info(PHASE_DISCONNECT);
+                info.status = if from_client {
+                    L7ResponseStatus::ClientError
+                } else {
+                    L7ResponseStatus::ServerError
+                };
+                info.exception = if description.is_empty() {
+                    format!("disconnect reason {}", reason)
+                } else {
+                    description
+                };
+                infos.push(info);
+            }
+            if events.kexinit && !self.handshake_logged {
+                if let Some(info) = self.handshake_info() {
+                    self.handshake_logged = true;
+                    infos.push(info);
+                }
+            }
+            if let Some(remaining) = events.newkeys {
+                // the auth phase begins at the first NEWKEYS of either side
+                if self.auth.is_none() && !(self.client.encrypted && self.server.encrypted) {
+                    self.auth = Some(AuthPhase {
+                        start_time: param.time,
+                        client_packets: 0,
+                        server_packets: 0,
+                        first_server_size: 0,
+                    });
+                }
+                if remaining > 0 {
+                    infos.extend(self.on_encrypted(remaining, from_client, param.time));
+                }
+            }
+        }
+
+        if let Some(perf_stats) = self.perf_stats.as_mut() {
+            for info in infos.iter() {
+                perf_stats.inc_req();
+                perf_stats.inc_resp();
+                match info.status {
+                    L7ResponseStatus::ClientError => perf_stats.inc_req_err(),
+                    L7ResponseStatus::ServerError => perf_stats.inc_resp_err(),
+                    _ => {}
+                }
+                if info.rrt > 0 {
+                    perf_stats.update_rrt(info.rrt);
+                }
+            }
+        }
+        if !param.parse_log || infos.is_empty() {
+            return Ok(L7ParseResult::None);
+        }
+        let mut infos = infos
+            .into_iter()
+            .map(L7ProtocolInfo::SshInfo)
+            .collect::<Vec<_>>();
+        if infos.len() == 1 {
+            Ok(L7ParseResult::Single(infos.pop().unwrap()))
+        } else {
+            Ok(L7ParseResult::Multi(infos))
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::SSH
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Option<L7PerfStats> {
+        self.perf_stats.take()
+    }
+}
+
+impl SshLog {
+    fn new_info(&self, phase: &'static str) -> SshInfo {
+        let mut info = SshInfo {
+            phase,
+            ..Default::default()
+        };
+        if let Some((version, software)) = self.client.banner.as_ref() {
+            info.version = version.clone();
+            info.client_software = software.clone();
+        }
+        if let Some((version, software)) = self.server.banner.as_ref() {
+            info.version = version.clone();
+            info.server_software = software.clone();
+        }
+        info
+    }
+
+    fn handshake_info(&self) -> Option<SshInfo> {
+        let (client, server) = (self.client.kexinit.as_ref()?, self.server.kexinit.as_ref()?);
+        let mut info = self.new_info(PHASE_HANDSHAKE);
+        info.hassh = Some(client.hassh(true));
+        info.hassh_server = Some(server.hassh(false));
+        let negotiated = |i: usize| negotiate(&client.name_lists[i], &server.name_lists[i]);
+        info.kex = negotiated(KexInit::KEX);
+        info.host_key = negotiated(KexInit::HOST_KEY);
+        info.cipher = negotiated(KexInit::ENCRYPTION_C2S);
+        info.mac = negotiated(KexInit::MAC_C2S);
+        info.compression = negotiated(KexInit::COMPRESSION_C2S);
+        if info.kex.is_empty() || info.cipher.is_empty() {
+            info.status = L7ResponseStatus::ClientError;
+            info.exception = "no matching algorithm".to_string();
+        }
+        Some(info)
+    }
+
+    fn on_encrypted(&mut self, size: usize, from_client: bool, time: u64) -> Option<SshInfo> {
+        let auth = self.auth.as_mut()?;
+        if size == 0 {
+            return None;
+        }
+        if from_client {
+            auth.client_packets += 1;
+            return None;
+        }
+        auth.server_packets += 1;
+        if auth.first_server_size == 0 {
+            auth.first_server_size = size;
+            return None;
+        }
+        let success = size < auth.first_server_size;
+        if !success && auth.server_packets < AUTH_PHASE_MAX_SERVER_PACKETS {
+            return None;
+        }
+
+        let auth = self.auth.take().unwrap();
+        let mut info = self.new_info(PHASE_AUTH);
+        info.rrt = time.saturating_sub(auth.start_time);
+        info.auth_duration = Some(info.rrt);
+        info.auth_packets = auth.client_packets;
+        if !success {
+            info.status = L7ResponseStatus::ClientError;
+            info.exception = "authentication not completed".to_string();
+        }
+        Some(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    use crate::{
+        common::{l7_protocol_log::L7PerfCache, MetaPacket, Timestamp},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    use PacketDirection::{ClientToServer as C2S, ServerToClient as S2C};
+
+    fn packet(message: &[u8]) -> Vec<u8> {
+        let padding_len = 8 - (PACKET_HEADER_LEN + message.len()) % 8 + 4;
+        let mut p = ((1 + message.len() + padding_len) as u32)
+            .to_be_bytes()
+            .to_vec();
+        p.push(padding_len as u8);
+        p.extend_from_slice(message);
+        p.resize(p.len() + padding_len, 0);
+        p
+    }
+
+    fn kexinit(name_lists: [&str; KEXINIT_NAME_LISTS]) -> Vec<u8> {
+        let mut m = vec![MSG_KEXINIT];
+        m.extend_from_slice(&[0x5a; KEXINIT_COOKIE_LEN]);
+        for list in name_lists.iter().chain(["", ""].iter()) {
+            m.extend_from_slice(&(list.len() as u32).to_be_bytes());
+            m.extend_from_slice(list.as_bytes());
+        }
+        m.extend_from_slice(&[0; 5]);
+        packet(&m)
+    }
+
+    struct Session {
+        ssh: SshLog,
+        log_cache: Rc<RefCell<L7PerfCache>>,
+        time: u64,
+    }
+
+    impl Session {
+        fn new() -> Self {
+            Self {
+                ssh: SshLog::default(),
+                log_cache: Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY))),
+                time: 1_000_000,
+            }
+        }
+
+        fn send(&mut self, payload: &[u8], direction: PacketDirection) -> Vec<SshInfo> {
+            let mut packet = MetaPacket::default();
+            packet.lookup_key.proto = IpProtocol::TCP;
+            packet.lookup_key.direction = direction;
+            packet.lookup_key.timestamp = Timestamp::from_micros(self.time);
+            self.time += 10_000;
+            let param = ParseParam::new(&packet, self.log_cache.clone(), true, true);
+            match self.ssh.parse_payload(payload, &param).unwrap() {
+                L7ParseResult::Single(L7ProtocolInfo::SshInfo(i)) => vec![i],
+                L7ParseResult::Multi(m) => m
+                    .into_iter()
+                    .map(|i| match i {
+                        L7ProtocolInfo::SshInfo(i) => i,
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+                _ => vec![],
+            }
+        }
+    }
+
+    #[test]
+    fn test_banner() {
+        assert_eq!(
+            parse_banner(b"SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.1\r"),
+            Some((
+                "2.0".to_string(),
+                "OpenSSH_8.9p1 Ubuntu-3ubuntu0.1".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_banner(b"SSH-1.99-Cisco-1.25"),
+            Some(("1.99".to_string(), "Cisco-1.25".to_string()))
+        );
+        assert_eq!(parse_banner(b"SSH-x-OpenSSH"), None);
+        assert_eq!(parse_banner(b"HTTP/1.1 200 OK"), None);
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut session = Session::new();
+        assert!(session.send(b"SSH-2.0-OpenSSH_9.6\r\n", S2C).is_empty());
+        let client_kexinit = kexinit([
+            "curve25519-sha256,ext-info-c",
+            "ssh-ed25519,rsa-sha2-256",
+            "chacha20-poly1305@openssh.com,aes128-ctr",
+            "chacha20-poly1305@openssh.com,aes128-ctr",
+            "hmac-sha2-256-etm@openssh.com,hmac-sha2-256",
+            "hmac-sha2-256-etm@openssh.com,hmac-sha2-256",
+            "none,zlib@openssh.com",
+            "none,zlib@openssh.com",
+        ]);
+        let mut payload = b"SSH-2.0-paramiko_2.4.2\r\n".to_vec();
+        payload.extend_from_slice(&client_kexinit);
+        // KEXINIT is split into two segments
+        assert!(session.send(&payload[..100], C2S).is_empty());
+        assert!(session.send(&payload[100..], C2S).is_empty());
+
+        let server_kexinit = kexinit([
+            "curve25519-sha256,kex-strict-s-v00@openssh.com",
+            "rsa-sha2-256,ssh-ed25519",
+            "aes128-ctr,chacha20-poly1305@openssh.com",
+            "aes128-ctr,chacha20-poly1305@openssh.com",
+            "hmac-sha2-256",
+            "hmac-sha2-256",
+            "none",
+            "none",
+        ]);
+        let infos = session.send(&server_kexinit, S2C);
+        assert_eq!(infos.len(), 1);
+        let info = &infos[0];
+        assert_eq!(info.phase, PHASE_HANDSHAKE);
+        assert_eq!(info.version, "2.0");
+        assert_eq!(info.client_software, "paramiko_2.4.2");
+        assert_eq!(info.server_software, "OpenSSH_9.6");
+        assert_eq!(
+            info.hassh.as_deref(),
+            Some("39ca45bd92ba1d507d4107e13038f46d")
+        );
+        assert_eq!(
+            info.hassh_server.as_deref(),
+            Some("a1c6daf0c162a5f275e61b13ccb5ba98")
+        );
+        assert_eq!(info.kex, "curve25519-sha256");
+        assert_eq!(info.host_key, "ssh-ed25519");
+        assert_eq!(info.cipher, "chacha20-poly1305@openssh.com");
+        assert_eq!(info.mac, "hmac-sha2-256");
+        assert_eq!(info.compression, "none");
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+
+        // the first encrypted packet follows NEWKEYS of server in the same segment
+        let mut payload = packet(&[MSG_NEWKEYS]);
+        payload.extend_from_slice(&[0xcc; 44]);
+        assert!(session.send(&payload, S2C).is_empty());
+        assert!(session.send(&packet(&[MSG_NEWKEYS]), C2S).is_empty());
+        // service request, userauth request none, failure, userauth request password
+        assert!(session.send(&[0xcc; 44], C2S).is_empty());
+        assert!(session.send(&[0xcc; 60], C2S).is_empty());
+        assert!(session.send(&[0xcc; 52], S2C).is_empty());
+        assert!(session.send(&[0xcc; 92], C2S).is_empty());
+        // success
+        let infos = session.send(&[0xcc; 28], S2C);
+        assert_eq!(infos.len(), 1);
+        let info = &infos[0];
+        assert_eq!(info.phase, PHASE_AUTH);
+        assert_eq!(info.auth_packets, 3);
+        assert_eq!(info.auth_duration, Some(60_000));
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+        // no more logs after the auth phase
+        assert!(session.send(&[0xcc; 28], S2C).is_empty());
+    }
+
+    #[test]
+    fn test_auth_failures() {
+        let mut session = Session::new();
+        session.send(&packet(&[MSG_NEWKEYS]), C2S);
+        session.send(&packet(&[MSG_NEWKEYS]), S2C);
+        for _ in 0..AUTH_PHASE_MAX_SERVER_PACKETS - 1 {
+            assert!(session.send(&[0xcc; 60], C2S).is_empty());
+            assert!(session.send(&[0xcc; 52], S2C).is_empty());
+        }
+        let infos = session.send(&[0xcc; 52], S2C);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].auth_packets, 7);
+        assert_eq!(infos[0].status, L7ResponseStatus::ClientError);
+
+        let mut session = Session::new();
+        let description = b"Protocol major versions differ.";
+        let mut disconnect = vec![MSG_DISCONNECT, 0, 0, 0, 8];
+        disconnect.extend_from_slice(&(description.len() as u32).to_be_bytes());
+        disconnect.extend_from_slice(description);
+        let infos = session.send(&packet(&disconnect), S2C);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].phase, PHASE_DISCONNECT);
+        assert_eq!(infos[0].status, L7ResponseStatus::ServerError);
+        assert_eq!(infos[0].exception, "Protocol major versions differ.");
+    }
+}
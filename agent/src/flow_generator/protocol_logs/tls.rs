@@ -14,6 +14,10 @@
  * limitations under the License.
  */
 
+mod certificate;
+mod fingerprint;
+mod handshake;
+
 use std::fmt::Display;
 
 use chrono::{prelude::NaiveDateTime, Utc};
@@ -22,7 +26,10 @@ use serde::Serialize;
 use super::pb_adapter::{
     ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, MetricKeyVal,
 };
-use super::{value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType};
+use super::{
+    consts::{DNS_OVER_TLS_ALPN, DNS_OVER_TLS_PORT, ENCRYPTED_DNS_DOT},
+    value_is_default, AppProtoHead, L7ResponseStatus, LogMessageType,
+};
 use crate::{
     common::{
         enums::IpProtocol,
@@ -32,6 +39,7 @@ use crate::{
         meta_packet::EbpfFlags,
         Timestamp,
     },
+    config::TlsCertificateExpiry,
     flow_generator::error::{Error, Result},
 };
 use l7::tls::TlsHeader;
@@ -251,6 +259,31 @@ pub struct TlsInfo {
     pub client_cert_not_before: Timestamp,
     #[serde(skip)]
     pub client_cert_not_after: Timestamp,
+    // subject common name, subject alternative names and issuer of the server certificate
+    #[serde(skip)]
+    pub server_cert_subject: Option<String>,
+    #[serde(skip)]
+    pub server_cert_sans: Vec<String>,
+    #[serde(skip)]
+    pub server_cert_issuer: Option<String>,
+    // alpn and versions in ClientHello
+    #[serde(skip)]
+    pub offered_alpn: Vec<String>,
+    #[serde(skip)]
+    pub offered_versions: Vec<Version>,
+    // alpn selected by ServerHello
+    #[serde(skip)]
+    pub alpn: Option<String>,
+    // fingerprints of ClientHello and ServerHello
+    #[serde(skip)]
+    pub ja3: Option<String>,
+    #[serde(skip)]
+    pub ja4: Option<String>,
+    #[serde(skip)]
+    pub ja3s: Option<String>,
+    // DoT if the handshake looks like dns over tls
+    #[serde(skip)]
+    pub encrypted_dns: Option<&'static str>,
 
     msg_type: LogMessageType,
     rrt: u64,
@@ -284,6 +317,10 @@ impl L7ProtocolInfoInterface for TlsInfo {
     fn is_tls(&self) -> bool {
         true
     }
+
+    fn response_status(&self) -> L7ResponseStatus {
+        self.status
+    }
 }
 
 impl TlsInfo {
@@ -302,6 +339,11 @@ impl TlsInfo {
                     &mut self.client_cert_not_before,
                     &mut other.client_cert_not_before,
                 );
+                std::mem::swap(&mut self.offered_alpn, &mut other.offered_alpn);
+                std::mem::swap(&mut self.offered_versions, &mut other.offered_versions);
+                std::mem::swap(&mut self.ja3, &mut other.ja3);
+                std::mem::swap(&mut self.ja4, &mut other.ja4);
+                std::mem::swap(&mut self.encrypted_dns, &mut other.encrypted_dns);
             }
             LogMessageType::Response => {
                 self.status = other.status;
@@ -310,6 +352,14 @@ impl TlsInfo {
                 std::mem::swap(&mut self.rrt, &mut other.rrt);
                 std::mem::swap(&mut self.tls_rtt, &mut other.tls_rtt);
                 std::mem::swap(&mut self.version, &mut other.version);
+                std::mem::swap(&mut self.alpn, &mut other.alpn);
+                std::mem::swap(&mut self.ja3s, &mut other.ja3s);
+                std::mem::swap(
+                    &mut self.server_cert_subject,
+                    &mut other.server_cert_subject,
+                );
+                std::mem::swap(&mut self.server_cert_sans, &mut other.server_cert_sans);
+                std::mem::swap(&mut self.server_cert_issuer, &mut other.server_cert_issuer);
                 std::mem::swap(
                     &mut self.server_cert_not_after,
                     &mut other.server_cert_not_after,
@@ -335,6 +385,55 @@ impl From<TlsInfo> for L7ProtocolSendLog {
                 val: cipher_suite.to_string(),
             });
         }
+        if !f.offered_versions.is_empty() {
+            attributes.push(KeyVal {
+                key: "offered_versions".to_string(),
+                val: f
+                    .offered_versions
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            });
+        }
+        if !f.offered_alpn.is_empty() {
+            attributes.push(KeyVal {
+                key: "offered_alpn".to_string(),
+                val: f.offered_alpn.join(","),
+            });
+        }
+        if let Some(alpn) = f.alpn {
+            attributes.push(KeyVal {
+                key: "alpn".to_string(),
+                val: alpn,
+            });
+        }
+        if let Some(encrypted_dns) = f.encrypted_dns {
+            attributes.push(KeyVal {
+                key: "encrypted_dns".to_string(),
+                val: encrypted_dns.to_string(),
+            });
+        }
+        if !f.server_cert_sans.is_empty() {
+            attributes.push(KeyVal {
+                key: "server_cert_sans".to_string(),
+                val: f.server_cert_sans.join(","),
+            });
+        }
+        for (key, val) in [
+            ("ja3", f.ja3),
+            ("ja4", f.ja4),
+            ("ja3s", f.ja3s),
+            ("server_cert_subject", f.server_cert_subject),
+            ("server_cert_issuer", f.server_cert_issuer),
+        ] {
+            if let Some(val) = val {
+                attributes.push(KeyVal {
+                    key: key.to_string(),
+                    val,
+                });
+            }
+        }
         if !f.client_cert_not_before.is_zero() {
             attributes.push(KeyVal {
                 key: "client_cert_not_before".to_string(),
@@ -448,8 +547,8 @@ impl L7ProtocolParserInterface for TlsLog {
             return false;
         }
 
-        let tls_header = TlsHeader::new(payload);
-        tls_header.is_handshake() && tls_header.is_client_hello()
+        // the check applies to any port, so the ClientHello is checked strictly
+        handshake::is_client_hello(payload)
     }
 
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
@@ -485,6 +584,127 @@ impl L7ProtocolParserInterface for TlsLog {
 
 impl TlsLog {
     const CHNAGE_CIPHER_SPEC_LIMIT: u8 = 2;
+    const SECONDS_PER_DAY: u64 = 86400;
+
+    // well known public resolvers serving dns over tls
+    const DNS_OVER_TLS_SERVERS: [&'static str; 6] = [
+        "dns.google",
+        "cloudflare-dns.com",
+        "one.one.one.one",
+        "dns.quad9.net",
+        "dns.adguard-dns.com",
+        "dns.alidns.com",
+    ];
+
+    // ALPN `dot` is dns over tls on any port, and the tls on port 853 is dns over tls
+    // unless another protocol is negotiated by ALPN and the server is not a known resolver
+    fn is_dns_over_tls(port: u16, server_name: Option<&str>, alpn: &[String]) -> bool {
+        if alpn.iter().any(|a| a == DNS_OVER_TLS_ALPN) {
+            return true;
+        }
+        if port != DNS_OVER_TLS_PORT {
+            return false;
+        }
+        alpn.is_empty()
+            || server_name
+                .map(|n| {
+                    Self::DNS_OVER_TLS_SERVERS
+                        .iter()
+                        .any(|s| n == *s || n.ends_with(&format!(".{}", s)))
+                })
+                .unwrap_or(false)
+    }
+
+    // expired certificates are counted as well
+    fn is_cert_expiring(not_after: Timestamp, now: Timestamp, warning_days: u32) -> bool {
+        warning_days > 0
+            && not_after.as_secs() < now.as_secs() + warning_days as u64 * Self::SECONDS_PER_DAY
+    }
+
+    // Fields of ClientHello and ServerHello are filled from the plaintext handshake,
+    // and the records are summarized if they are not parsed by the plugin.
+    fn on_records(
+        &mut self,
+        records: &handshake::Records,
+        info: &mut TlsInfo,
+        direction: PacketDirection,
+        unsupported: bool,
+    ) {
+        if let Some(hello) = records.client_hello.as_ref() {
+            info.session_id = Some(0xff);
+            if info.request_domain.is_empty() {
+                info.request_domain = hello.server_name.clone().unwrap_or_default();
+            }
+            info.offered_alpn = hello.alpn.clone();
+            info.offered_versions = hello
+                .supported_versions
+                .iter()
+                .filter(|v| !handshake::is_grease(**v))
+                .map(|v| Version::from(*v))
+                .collect();
+            info.version = Version::from(hello.max_version());
+            info.ja3 = Some(fingerprint::ja3(hello));
+            info.ja4 = Some(fingerprint::ja4(hello));
+        }
+        if let Some(hello) = records.server_hello.as_ref() {
+            info.version = Version::from(hello.negotiated_version());
+            if info.cipher_suite.is_none() {
+                info.cipher_suite = Some(CipherSuite::from(hello.cipher_suite));
+            }
+            info.alpn = hello.alpn.clone();
+            info.ja3s = Some(fingerprint::ja3s(hello));
+        }
+        if let Some(cert) = records.certificate.as_ref() {
+            let (not_before, not_after) = if direction == PacketDirection::ServerToClient {
+                info.server_cert_subject = cert.subject.clone();
+                info.server_cert_sans = cert.sans.clone();
+                info.server_cert_issuer = cert.issuer.clone();
+                (
+                    &mut info.server_cert_not_before,
+                    &mut info.server_cert_not_after,
+                )
+            } else {
+                (
+                    &mut info.client_cert_not_before,
+                    &mut info.client_cert_not_after,
+                )
+            };
+            if not_after.is_zero() {
+                *not_before = Timestamp::from_secs(cert.not_before.unwrap_or_default());
+                *not_after = Timestamp::from_secs(cert.not_after.unwrap_or_default());
+            }
+        }
+        if !unsupported {
+            return;
+        }
+
+        if let Some(t) = records.handshakes.first() {
+            info.handshake_protocol = handshake::handshake_name(*t).to_string();
+        }
+        let names = records.names();
+        if direction == PacketDirection::ClientToServer {
+            info.request_type = names.first().copied().unwrap_or_default().to_string();
+            info.request_resource = names.join("|");
+        } else {
+            info.response_result = names.join("|");
+        }
+        for t in records.content_types.iter() {
+            match *t {
+                handshake::CONTENT_TYPE_ALERT if direction == PacketDirection::ServerToClient => {
+                    self.perf_stats.as_mut().map(|p| p.inc_resp_err());
+                    info.status = L7ResponseStatus::ServerError;
+                }
+                handshake::CONTENT_TYPE_CHANGE_CIPHER_SPEC => {
+                    self.change_cipher_spec_count += 1;
+                    if self.change_cipher_spec_count >= Self::CHNAGE_CIPHER_SPEC_LIMIT {
+                        self.change_cipher_spec_count = 0;
+                        info.session_id = Some(0xff);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
 
     fn parse(&mut self, payload: &[u8], info: &mut TlsInfo, param: &ParseParam) -> Result<()> {
         if self.perf_stats.is_none() && param.parse_perf {
@@ -513,12 +733,18 @@ impl TlsLog {
             return Err(Error::TlsLogParseFailed(format!("Invalid payload")));
         }
 
+        let records = handshake::parse(payload);
+        // record headers are not parsed by the plugin
+        let unsupported = tls_headers.iter().all(|h| h.is_unsupport_content_type());
+
         match param.direction {
             PacketDirection::ClientToServer => {
                 if tls_headers.len() > 0 {
                     tls_headers.iter().for_each(|h| {
                         if h.is_client_hello() {
                             info.session_id = Some(0xff);
+                            // handshake time is measured to the last ChangeCipherSpec of this handshake
+                            self.change_cipher_spec_count = 0;
                         }
 
                         if h.is_change_cipher_spec() {
@@ -555,6 +781,18 @@ impl TlsLog {
                         .collect::<Vec<String>>()
                         .join("|")
                         .to_string();
+                    if let Some(records) = records.as_ref() {
+                        self.on_records(records, info, param.direction, unsupported);
+                        if let Some(hello) = records.client_hello.as_ref() {
+                            if Self::is_dns_over_tls(
+                                param.port_dst,
+                                hello.server_name.as_deref(),
+                                &hello.alpn,
+                            ) {
+                                info.encrypted_dns = Some(ENCRYPTED_DNS_DOT);
+                            }
+                        }
+                    }
                     info.msg_type = LogMessageType::Request;
 
                     self.perf_stats.as_mut().map(|p| p.inc_req());
@@ -602,6 +840,22 @@ impl TlsLog {
                         .collect::<Vec<String>>()
                         .join("|")
                         .to_string();
+                    if let Some(records) = records.as_ref() {
+                        self.on_records(records, info, param.direction, unsupported);
+                    }
+                    if !info.server_cert_not_after.is_zero() {
+                        let warning_days = param
+                            .parse_config
+                            .map(|c| c.tls_certificate_expiry.warning_days)
+                            .unwrap_or(TlsCertificateExpiry::default().warning_days);
+                        if Self::is_cert_expiring(
+                            info.server_cert_not_after,
+                            Timestamp::from_micros(param.time),
+                            warning_days,
+                        ) {
+                            self.perf_stats.as_mut().map(|p| p.inc_tls_cert_expiring());
+                        }
+                    }
                     info.msg_type = LogMessageType::Response;
 
                     self.perf_stats.as_mut().map(|p| p.inc_resp());
@@ -708,6 +962,8 @@ mod tests {
                 rrt_sum: 102011,
                 rrt_max: 55453,
                 tls_rtt: 103343,
+                tls_rtt_count: 1,
+                tls_rtt_max: 103343,
                 ..Default::default()
             },
         )];
@@ -717,6 +973,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_is_dns_over_tls() {
+        let h2 = vec!["h2".to_string()];
+        assert!(TlsLog::is_dns_over_tls(853, None, &[]));
+        assert!(TlsLog::is_dns_over_tls(443, None, &["dot".to_string()]));
+        assert!(TlsLog::is_dns_over_tls(853, Some("dns.google"), &h2));
+        assert!(!TlsLog::is_dns_over_tls(853, Some("www.example.com"), &h2));
+        assert!(!TlsLog::is_dns_over_tls(443, Some("dns.google"), &[]));
+    }
+
+    #[test]
+    fn test_is_cert_expiring() {
+        let now = Timestamp::from_secs(1704067200);
+        let day = TlsLog::SECONDS_PER_DAY;
+        assert!(TlsLog::is_cert_expiring(
+            Timestamp::from_secs(now.as_secs() + 29 * day),
+            now,
+            30
+        ));
+        assert!(TlsLog::is_cert_expiring(
+            Timestamp::from_secs(now.as_secs() - day),
+            now,
+            30
+        ));
+        assert!(!TlsLog::is_cert_expiring(
+            Timestamp::from_secs(now.as_secs() + 31 * day),
+            now,
+            30
+        ));
+        assert!(!TlsLog::is_cert_expiring(
+            Timestamp::from_secs(now.as_secs() - day),
+            now,
+            0
+        ));
+    }
+
     fn run_perf(pcap: &str) -> L7PerfStats {
         let rrt_cache = Rc::new(RefCell::new(L7PerfCache::new(100)));
         let mut tls = TlsLog::default();
@@ -0,0 +1,355 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Leaf certificate of the Certificate handshake message, reference:
+// https://www.rfc-editor.org/rfc/rfc5280#section-4.1
+//
+// TLS 1.2: certificate_list<u24> { cert_data<u24> }
+// TLS 1.3: certificate_request_context<u8> | certificate_list<u24> { cert_data<u24> | extensions<u16> }
+//
+// Only the fields used in logs are decoded, a certificate cut off by the end of
+// payload still returns the fields before the cut.
+
+use chrono::NaiveDate;
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_VERSION: u8 = 0xa0;
+const TAG_EXTENSIONS: u8 = 0xa3;
+const TAG_DNS_NAME: u8 = 0x82;
+const TAG_IP_ADDRESS: u8 = 0x87;
+
+const CONSTRUCTED: u8 = 0x20;
+
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+// SANs are limited to avoid a certificate for many domains consuming memory
+pub const MAX_SANS: usize = 32;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Certificate {
+    // common name of subject
+    pub subject: Option<String>,
+    // common name of issuer, or organization if there is no common name
+    pub issuer: Option<String>,
+    // dNSName and iPAddress of subjectAltName
+    pub sans: Vec<String>,
+    // seconds since epoch
+    pub not_before: Option<u64>,
+    pub not_after: Option<u64>,
+}
+
+struct Der<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Der<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    // Returns tag and content. Content of a constructed element is clamped to the
+    // end of buffer, while a primitive element must be complete.
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        let tag = *self.buf.get(self.pos)?;
+        let first = *self.buf.get(self.pos + 1)? as usize;
+        let mut pos = self.pos + 2;
+        let len = if first & 0x80 == 0 {
+            first
+        } else {
+            let n = first & 0x7f;
+            if n == 0 || n > 4 {
+                return None;
+            }
+            let mut len = 0;
+            for b in self.buf.get(pos..pos + n)? {
+                len = len << 8 | *b as usize;
+            }
+            pos += n;
+            len
+        };
+        let end = pos.checked_add(len)?;
+        if end > self.buf.len() && tag & CONSTRUCTED == 0 {
+            return None;
+        }
+        let end = end.min(self.buf.len());
+        self.pos = end;
+        Some((tag, &self.buf[pos..end]))
+    }
+
+    fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+        match self.next()? {
+            (t, content) if t == tag => Some(content),
+            _ => None,
+        }
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.buf.get(self.pos).copied()
+    }
+}
+
+// UTCTime "YYMMDDHHMMSSZ" or GeneralizedTime "YYYYMMDDHHMMSSZ"
+fn parse_time(tag: u8, content: &[u8]) -> Option<u64> {
+    let s = std::str::from_utf8(content).ok()?;
+    let (year, rest) = match tag {
+        TAG_UTC_TIME => {
+            let year: i32 = s.get(..2)?.parse().ok()?;
+            (if year >= 50 { 1900 + year } else { 2000 + year }, &s[2..])
+        }
+        TAG_GENERALIZED_TIME => (s.get(..4)?.parse().ok()?, &s[4..]),
+        _ => return None,
+    };
+    let field = |i: usize| -> Option<u32> { rest.get(i..i + 2)?.parse().ok() };
+    let time = NaiveDate::from_ymd_opt(year, field(0)?, field(2)?)?.and_hms_opt(
+        field(4)?,
+        field(6)?,
+        field(8)?,
+    )?;
+    u64::try_from(time.timestamp()).ok()
+}
+
+// returns the value of the first attribute with oid in Name
+fn name_attribute(name: &[u8], oid: &[u8]) -> Option<String> {
+    let mut rdns = Der::new(name);
+    while let Some((_, rdn)) = rdns.next() {
+        let mut attributes = Der::new(rdn);
+        while let Some(attribute) = attributes.expect(TAG_SEQUENCE) {
+            let mut attribute = Der::new(attribute);
+            if attribute.expect(TAG_OID) != Some(oid) {
+                continue;
+            }
+            let (_, value) = attribute.next()?;
+            return Some(String::from_utf8_lossy(value).into_owned());
+        }
+    }
+    None
+}
+
+fn parse_sans(value: &[u8]) -> Vec<String> {
+    let mut sans = vec![];
+    let Some(names) = Der::new(value).expect(TAG_SEQUENCE) else {
+        return sans;
+    };
+    let mut names = Der::new(names);
+    while let Some((tag, name)) = names.next() {
+        if sans.len() >= MAX_SANS {
+            break;
+        }
+        match (tag, name.len()) {
+            (TAG_DNS_NAME, _) => sans.push(String::from_utf8_lossy(name).into_owned()),
+            (TAG_IP_ADDRESS, 4) => {
+                sans.push(std::net::Ipv4Addr::from(<[u8; 4]>::try_from(name).unwrap()).to_string())
+            }
+            (TAG_IP_ADDRESS, 16) => {
+                sans.push(std::net::Ipv6Addr::from(<[u8; 16]>::try_from(name).unwrap()).to_string())
+            }
+            _ => (),
+        }
+    }
+    sans
+}
+
+fn parse_extensions(extensions: &[u8], cert: &mut Certificate) {
+    let Some(extensions) = Der::new(extensions).expect(TAG_SEQUENCE) else {
+        return;
+    };
+    let mut extensions = Der::new(extensions);
+    while let Some(extension) = extensions.expect(TAG_SEQUENCE) {
+        let mut extension = Der::new(extension);
+        if extension.expect(TAG_OID) != Some(OID_SUBJECT_ALT_NAME) {
+            continue;
+        }
+        if extension.peek_tag() == Some(TAG_BOOLEAN) {
+            extension.next();
+        }
+        if let Some(value) = extension.expect(TAG_OCTET_STRING) {
+            cert.sans = parse_sans(value);
+        }
+        return;
+    }
+}
+
+pub fn parse_certificate(der: &[u8]) -> Option<Certificate> {
+    let mut cert = Certificate::default();
+    let tbs = Der::new(Der::new(der).expect(TAG_SEQUENCE)?).expect(TAG_SEQUENCE)?;
+    let mut tbs = Der::new(tbs);
+    if tbs.peek_tag() == Some(TAG_VERSION) {
+        tbs.next();
+    }
+    tbs.expect(TAG_INTEGER)?;
+    // signature algorithm
+    tbs.expect(TAG_SEQUENCE)?;
+    let issuer = tbs.expect(TAG_SEQUENCE)?;
+    cert.issuer = name_attribute(issuer, OID_COMMON_NAME)
+        .or_else(|| name_attribute(issuer, OID_ORGANIZATION));
+    let Some(validity) = tbs.expect(TAG_SEQUENCE) else {
+        return Some(cert);
+    };
+    let mut validity = Der::new(validity);
+    if let Some((tag, content)) = validity.next() {
+        cert.not_before = parse_time(tag, content);
+    }
+    if let Some((tag, content)) = validity.next() {
+        cert.not_after = parse_time(tag, content);
+    }
+    let Some(subject) = tbs.expect(TAG_SEQUENCE) else {
+        return Some(cert);
+    };
+    cert.subject = name_attribute(subject, OID_COMMON_NAME);
+    // subject public key info, issuer and subject unique ids are skipped
+    while let Some((tag, content)) = tbs.next() {
+        if tag == TAG_EXTENSIONS {
+            parse_extensions(content, &mut cert);
+            break;
+        }
+    }
+    Some(cert)
+}
+
+fn u24(buf: &[u8]) -> Option<usize> {
+    let b = buf.get(..3)?;
+    Some(u32::from_be_bytes([0, b[0], b[1], b[2]]) as usize)
+}
+
+// The framing differs in TLS 1.3, which is told apart by where the leaf
+// certificate starts since it is a DER SEQUENCE.
+pub fn parse(body: &[u8]) -> Option<Certificate> {
+    let offset = if body.get(6) == Some(&TAG_SEQUENCE) && u24(body)? >= u24(&body[3..])? + 3 {
+        3
+    } else if body.first() == Some(&0) && body.get(7) == Some(&TAG_SEQUENCE) {
+        4
+    } else {
+        return None;
+    };
+    let len = u24(&body[offset..])?;
+    let start = offset + 3;
+    parse_certificate(&body[start..body.len().min(start + len)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut v = vec![tag];
+        if content.len() < 0x80 {
+            v.push(content.len() as u8);
+        } else {
+            v.push(0x82);
+            v.extend_from_slice(&(content.len() as u16).to_be_bytes());
+        }
+        v.extend_from_slice(content);
+        v
+    }
+
+    fn name(oid: &[u8], value: &str) -> Vec<u8> {
+        let mut attribute = tlv(TAG_OID, oid);
+        attribute.extend(tlv(0x0c, value.as_bytes()));
+        tlv(TAG_SEQUENCE, &tlv(0x31, &tlv(TAG_SEQUENCE, &attribute)))
+    }
+
+    fn certificate() -> Vec<u8> {
+        let mut tbs = tlv(TAG_VERSION, &tlv(TAG_INTEGER, &[2]));
+        tbs.extend(tlv(TAG_INTEGER, &[0x12, 0x34]));
+        tbs.extend(tlv(TAG_SEQUENCE, &tlv(TAG_OID, &[0x2a, 0x86, 0x48])));
+        tbs.extend(name(OID_ORGANIZATION, "Example CA"));
+        let mut validity = tlv(TAG_UTC_TIME, b"240101000000Z");
+        validity.extend(tlv(TAG_GENERALIZED_TIME, b"20250101120000Z"));
+        tbs.extend(tlv(TAG_SEQUENCE, &validity));
+        tbs.extend(name(OID_COMMON_NAME, "example.com"));
+        tbs.extend(tlv(TAG_SEQUENCE, &[0; 160]));
+        let mut sans = tlv(TAG_DNS_NAME, b"example.com");
+        sans.extend(tlv(TAG_DNS_NAME, b"*.example.com"));
+        sans.extend(tlv(TAG_IP_ADDRESS, &[10, 0, 0, 1]));
+        let mut extension = tlv(TAG_OID, OID_SUBJECT_ALT_NAME);
+        extension.extend(tlv(TAG_OCTET_STRING, &tlv(TAG_SEQUENCE, &sans)));
+        let mut extensions = tlv(
+            TAG_SEQUENCE,
+            &[
+                tlv(TAG_OID, &[0x55, 0x1d, 0x13]),
+                tlv(TAG_OCTET_STRING, &[0x30, 0]),
+            ]
+            .concat(),
+        );
+        extensions.extend(tlv(TAG_SEQUENCE, &extension));
+        tbs.extend(tlv(TAG_EXTENSIONS, &tlv(TAG_SEQUENCE, &extensions)));
+        let mut cert = tlv(TAG_SEQUENCE, &tbs);
+        cert.extend(tlv(TAG_SEQUENCE, &tlv(TAG_OID, &[0x2a, 0x86, 0x48])));
+        cert.extend(tlv(0x03, &[0; 64]));
+        tlv(TAG_SEQUENCE, &cert)
+    }
+
+    fn certificate_list(cert: &[u8]) -> Vec<u8> {
+        let mut list = (cert.len() as u32).to_be_bytes()[1..].to_vec();
+        list.extend_from_slice(cert);
+        let mut body = (list.len() as u32).to_be_bytes()[1..].to_vec();
+        body.extend(list);
+        body
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time(TAG_UTC_TIME, b"240101000000Z"), Some(1704067200));
+        assert_eq!(parse_time(TAG_UTC_TIME, b"700101000000Z"), Some(0));
+        assert_eq!(
+            parse_time(TAG_GENERALIZED_TIME, b"20250101120000Z"),
+            Some(1735732800)
+        );
+        assert_eq!(parse_time(TAG_UTC_TIME, b"241301000000Z"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let expected = Certificate {
+            subject: Some("example.com".to_string()),
+            issuer: Some("Example CA".to_string()),
+            sans: vec![
+                "example.com".to_string(),
+                "*.example.com".to_string(),
+                "10.0.0.1".to_string(),
+            ],
+            not_before: Some(1704067200),
+            not_after: Some(1735732800),
+        };
+        let body = certificate_list(&certificate());
+        assert_eq!(parse(&body), Some(expected));
+
+        // tls 1.3 with empty certificate_request_context
+        let mut body13 = vec![0];
+        body13.extend(certificate_list(&certificate()));
+        assert_eq!(
+            parse(&body13).unwrap().subject.as_deref(),
+            Some("example.com")
+        );
+
+        // truncated in subject public key info
+        let cert = parse(&body[..140]).unwrap();
+        assert_eq!(cert.subject.as_deref(), Some("example.com"));
+        assert_eq!(cert.not_after, Some(1735732800));
+        assert!(cert.sans.is_empty());
+
+        assert_eq!(parse(&[0, 0, 0]), None);
+    }
+}
@@ -0,0 +1,196 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// TLS client and server fingerprints, GREASE values are ignored in all of them.
+//
+// JA3:  md5 of "SSLVersion,Ciphers,Extensions,EllipticCurves,EllipticCurvePointFormats"
+// JA3S: md5 of "SSLVersion,Cipher,Extensions"
+// JA4:  "t13d1516h2_8daaf6152771_e5627efa2ab1", reference: https://github.com/FoxIO-LLC/ja4
+
+use md5::{Digest, Md5};
+use ring::digest::{digest, SHA256};
+
+use super::handshake::{is_grease, ClientHello, ServerHello};
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_ALPN: u16 = 16;
+const JA4_HASH_LEN: usize = 12;
+
+fn join<T: ToString>(values: impl Iterator<Item = T>, sep: &str) -> String {
+    values.map(|v| v.to_string()).collect::<Vec<_>>().join(sep)
+}
+
+fn not_grease(v: &&u16) -> bool {
+    !is_grease(**v)
+}
+
+pub fn ja3(hello: &ClientHello) -> String {
+    let s = format!(
+        "{},{},{},{},{}",
+        hello.version,
+        join(hello.cipher_suites.iter().filter(not_grease), "-"),
+        join(hello.extensions.iter().filter(not_grease), "-"),
+        join(hello.supported_groups.iter().filter(not_grease), "-"),
+        join(hello.ec_point_formats.iter(), "-"),
+    );
+    hex::encode(Md5::digest(s.as_bytes()))
+}
+
+pub fn ja3s(hello: &ServerHello) -> String {
+    let s = format!(
+        "{},{},{}",
+        hello.version,
+        hello.cipher_suite,
+        join(hello.extensions.iter().filter(not_grease), "-"),
+    );
+    hex::encode(Md5::digest(s.as_bytes()))
+}
+
+fn ja4_version(version: u16) -> &'static str {
+    match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        0x0002 => "s2",
+        _ => "00",
+    }
+}
+
+// first and last characters of the first alpn, hex is used if they are not alphanumeric
+fn ja4_alpn(alpn: Option<&String>) -> String {
+    let Some(alpn) = alpn.map(|a| a.as_bytes()).filter(|a| !a.is_empty()) else {
+        return "00".to_string();
+    };
+    let (first, last) = (alpn[0], alpn[alpn.len() - 1]);
+    if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+        return format!("{}{}", first as char, last as char);
+    }
+    let h = hex::encode(alpn);
+    format!("{}{}", &h[..1], &h[h.len() - 1..])
+}
+
+fn ja4_hash(s: &str) -> String {
+    if s.is_empty() {
+        return "0".repeat(JA4_HASH_LEN);
+    }
+    let mut h = hex::encode(digest(&SHA256, s.as_bytes()));
+    h.truncate(JA4_HASH_LEN);
+    h
+}
+
+// only tcp is supported, quic is not parsed
+pub fn ja4(hello: &ClientHello) -> String {
+    let mut ciphers = hello
+        .cipher_suites
+        .iter()
+        .filter(not_grease)
+        .copied()
+        .collect::<Vec<_>>();
+    let mut extensions = hello
+        .extensions
+        .iter()
+        .filter(not_grease)
+        .copied()
+        .collect::<Vec<_>>();
+    let part_a = format!(
+        "t{}{}{:02}{:02}{}",
+        ja4_version(hello.max_version()),
+        if hello.server_name.is_some() {
+            "d"
+        } else {
+            "i"
+        },
+        ciphers.len().min(99),
+        extensions.len().min(99),
+        ja4_alpn(hello.alpn.first()),
+    );
+
+    ciphers.sort_unstable();
+    let part_b = ja4_hash(&join(ciphers.iter().map(|c| format!("{:04x}", c)), ","));
+
+    // sni and alpn are excluded since they are counted in part a
+    extensions.retain(|e| *e != EXT_SERVER_NAME && *e != EXT_ALPN);
+    extensions.sort_unstable();
+    let mut s = join(extensions.iter().map(|e| format!("{:04x}", e)), ",");
+    if !hello.signature_algorithms.is_empty() {
+        s.push('_');
+        s.push_str(&join(
+            hello
+                .signature_algorithms
+                .iter()
+                .map(|a| format!("{:04x}", a)),
+            ",",
+        ));
+    }
+    let part_c = ja4_hash(&s);
+
+    format!("{}_{}_{}", part_a, part_b, part_c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_hello() -> ClientHello {
+        ClientHello {
+            version: 0x0303,
+            cipher_suites: vec![0x0a0a, 0xc02f, 0x1301],
+            extensions: vec![0x1a1a, 0, 10, 11, 16, 43],
+            server_name: Some("example.com".to_string()),
+            alpn: vec!["h2".to_string(), "http/1.1".to_string()],
+            supported_versions: vec![0x2a2a, 0x0304, 0x0303],
+            supported_groups: vec![0x3a3a, 29, 23],
+            ec_point_formats: vec![0],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_ja3() {
+        let mut hello = client_hello();
+        hello.cipher_suites = vec![0x0a0a, 0x1301, 0xc02f];
+        // 771,4865-49199,0-10-11-16-43,29-23,0
+        assert_eq!(ja3(&hello), "ba56e367277299892e1a86aefd53de70");
+    }
+
+    #[test]
+    fn test_ja3s() {
+        let hello = ServerHello {
+            version: 0x0303,
+            cipher_suite: 0x1301,
+            extensions: vec![43, 16],
+            ..Default::default()
+        };
+        // 771,4865,43-16
+        assert_eq!(ja3s(&hello), "2b83a23dea22815f9c4ffaaeaebdc796");
+    }
+
+    #[test]
+    fn test_ja4() {
+        let mut hello = client_hello();
+        assert_eq!(ja4(&hello), "t13d0205h2_c1929292aa6b_675b29d69375");
+
+        hello.extensions = vec![0x1a1a, 0, 10, 11, 13, 16, 43];
+        hello.signature_algorithms = vec![0x0403, 0x0804];
+        hello.server_name = None;
+        hello.alpn = vec![];
+        assert_eq!(ja4(&hello), "t13i020600_c1929292aa6b_fb71836bce29");
+
+        assert_eq!(ja4_alpn(Some(&"\u{1}x\u{2}".to_string())), "02");
+    }
+}
@@ -0,0 +1,591 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Plaintext records of TLS handshake, reference: https://www.rfc-editor.org/rfc/rfc8446
+//
+// record:    content_type(1) | legacy_version(2) | length(2) | fragment
+// handshake: msg_type(1) | length(3) | body
+//
+// A handshake message may be cut off by the end of payload, the fields before
+// the cut are still returned.
+
+use super::certificate::{self, Certificate};
+
+pub const CONTENT_TYPE_CHANGE_CIPHER_SPEC: u8 = 20;
+pub const CONTENT_TYPE_ALERT: u8 = 21;
+pub const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+pub const CONTENT_TYPE_APPLICATION_DATA: u8 = 23;
+
+pub const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+pub const HANDSHAKE_SERVER_HELLO: u8 = 2;
+pub const HANDSHAKE_CERTIFICATE: u8 = 11;
+
+const RECORD_HEADER_LEN: usize = 5;
+const HANDSHAKE_HEADER_LEN: usize = 4;
+// 2^14 plus the expansion allowed for ciphertext
+const MAX_RECORD_LEN: usize = (1 << 14) + 2048;
+// plaintext records are not longer than 2^14
+const MAX_PLAINTEXT_LEN: usize = 1 << 14;
+const RANDOM_LEN: usize = 32;
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 13;
+const EXT_ALPN: u16 = 16;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+
+const SERVER_NAME_TYPE_HOST_NAME: u8 = 0;
+
+// GREASE values are reserved to be ignored, such as 0x0a0a, 0x1a1a ... 0xfafa
+// reference: https://www.rfc-editor.org/rfc/rfc8701
+pub fn is_grease(v: u16) -> bool {
+    v & 0x0f0f == 0x0a0a && v >> 8 == v & 0xff
+}
+
+pub fn content_type_name(content_type: u8) -> &'static str {
+    match content_type {
+        CONTENT_TYPE_CHANGE_CIPHER_SPEC => "ChangeCipherSpec",
+        CONTENT_TYPE_ALERT => "Alert",
+        CONTENT_TYPE_HANDSHAKE => "Handshake",
+        CONTENT_TYPE_APPLICATION_DATA => "ApplicationData",
+        _ => "Unknown",
+    }
+}
+
+pub fn handshake_name(msg_type: u8) -> &'static str {
+    match msg_type {
+        0 => "HelloRequest",
+        HANDSHAKE_CLIENT_HELLO => "ClientHello",
+        HANDSHAKE_SERVER_HELLO => "ServerHello",
+        4 => "NewSessionTicket",
+        8 => "EncryptedExtensions",
+        HANDSHAKE_CERTIFICATE => "Certificate",
+        12 => "ServerKeyExchange",
+        13 => "CertificateRequest",
+        14 => "ServerHelloDone",
+        15 => "CertificateVerify",
+        16 => "ClientKeyExchange",
+        20 => "Finished",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientHello {
+    pub version: u16,
+    pub cipher_suites: Vec<u16>,
+    // extension types in order
+    pub extensions: Vec<u16>,
+    pub server_name: Option<String>,
+    pub alpn: Vec<String>,
+    pub supported_versions: Vec<u16>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+}
+
+impl ClientHello {
+    // supported_versions extension takes precedence over legacy_version since TLS 1.3
+    pub fn max_version(&self) -> u16 {
+        self.supported_versions
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .max()
+            .unwrap_or(self.version)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ServerHello {
+    pub version: u16,
+    pub cipher_suite: u16,
+    pub extensions: Vec<u16>,
+    pub alpn: Option<String>,
+    pub supported_version: Option<u16>,
+}
+
+impl ServerHello {
+    pub fn negotiated_version(&self) -> u16 {
+        self.supported_version.unwrap_or(self.version)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Records {
+    // content types of records in order
+    pub content_types: Vec<u8>,
+    // plaintext handshake message types in order
+    pub handshakes: Vec<u8>,
+    pub client_hello: Option<ClientHello>,
+    pub server_hello: Option<ServerHello>,
+    // leaf certificate of the first Certificate message
+    pub certificate: Option<Certificate>,
+}
+
+impl Records {
+    // such as "ClientHello" or "ServerHello|Certificate|ServerHelloDone|ChangeCipherSpec"
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut handshakes = self.handshakes.iter();
+        let mut names = vec![];
+        let mut last_is_handshake = false;
+        for t in self.content_types.iter() {
+            if *t != CONTENT_TYPE_HANDSHAKE {
+                names.push(content_type_name(*t));
+                last_is_handshake = false;
+                continue;
+            }
+            // handshake messages may be coalesced into one record or fragmented into many
+            if !last_is_handshake {
+                names.extend(handshakes.by_ref().map(|h| handshake_name(*h)));
+            }
+            last_is_handshake = true;
+        }
+        names
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        let v = *self.buf.get(self.pos)?;
+        self.pos += 1;
+        Some(v)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let b = self.bytes(2)?;
+        Some(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let b = self.buf.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(b)
+    }
+
+    // vector with u8 length prefix
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let n = self.u8()? as usize;
+        self.bytes(n)
+    }
+
+    // vector with u16 length prefix
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let n = self.u16()? as usize;
+        self.bytes(n)
+    }
+}
+
+fn u16_list(buf: &[u8]) -> Vec<u16> {
+    buf.chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect()
+}
+
+fn string_list(buf: &[u8]) -> Vec<String> {
+    let mut r = Reader::new(buf);
+    let mut list = vec![];
+    while let Some(s) = r.vec8() {
+        list.push(String::from_utf8_lossy(s).into_owned());
+    }
+    list
+}
+
+fn parse_server_name(buf: &[u8]) -> Option<String> {
+    let mut r = Reader::new(Reader::new(buf).vec16()?);
+    while let Some(name_type) = r.u8() {
+        let name = r.vec16()?;
+        if name_type == SERVER_NAME_TYPE_HOST_NAME {
+            return Some(String::from_utf8_lossy(name).into_owned());
+        }
+    }
+    None
+}
+
+// calls f with each complete extension, returns extension types including the truncated one
+fn for_each_extension<F: FnMut(u16, &[u8])>(r: &mut Reader, mut f: F) -> Vec<u16> {
+    let mut types = vec![];
+    let Some(len) = r.u16() else {
+        return types;
+    };
+    let end = r.buf.len().min(r.pos + len as usize);
+    let mut r = Reader::new(&r.buf[r.pos..end]);
+    while let Some(t) = r.u16() {
+        types.push(t);
+        match r.vec16() {
+            Some(data) => f(t, data),
+            None => break,
+        }
+    }
+    types
+}
+
+fn parse_client_hello(body: &[u8]) -> Option<ClientHello> {
+    let mut r = Reader::new(body);
+    let mut hello = ClientHello {
+        version: r.u16()?,
+        ..Default::default()
+    };
+    r.bytes(RANDOM_LEN)?;
+    // legacy_session_id
+    r.vec8()?;
+    hello.cipher_suites = u16_list(r.vec16()?);
+    // legacy_compression_methods
+    r.vec8()?;
+    hello.extensions = for_each_extension(&mut r, |t, data| match t {
+        EXT_SERVER_NAME => hello.server_name = parse_server_name(data),
+        EXT_SUPPORTED_GROUPS => {
+            if let Some(list) = Reader::new(data).vec16() {
+                hello.supported_groups = u16_list(list);
+            }
+        }
+        EXT_EC_POINT_FORMATS => {
+            if let Some(list) = Reader::new(data).vec8() {
+                hello.ec_point_formats = list.to_vec();
+            }
+        }
+        EXT_SIGNATURE_ALGORITHMS => {
+            if let Some(list) = Reader::new(data).vec16() {
+                hello.signature_algorithms = u16_list(list);
+            }
+        }
+        EXT_ALPN => {
+            if let Some(list) = Reader::new(data).vec16() {
+                hello.alpn = string_list(list);
+            }
+        }
+        EXT_SUPPORTED_VERSIONS => {
+            if let Some(list) = Reader::new(data).vec8() {
+                hello.supported_versions = u16_list(list);
+            }
+        }
+        _ => (),
+    });
+    Some(hello)
+}
+
+fn parse_server_hello(body: &[u8]) -> Option<ServerHello> {
+    let mut r = Reader::new(body);
+    let mut hello = ServerHello {
+        version: r.u16()?,
+        ..Default::default()
+    };
+    r.bytes(RANDOM_LEN)?;
+    // legacy_session_id_echo
+    r.vec8()?;
+    hello.cipher_suite = r.u16()?;
+    // legacy_compression_method
+    r.u8()?;
+    hello.extensions = for_each_extension(&mut r, |t, data| match t {
+        EXT_ALPN => {
+            hello.alpn = Reader::new(data)
+                .vec16()
+                .and_then(|list| string_list(list).into_iter().next());
+        }
+        EXT_SUPPORTED_VERSIONS => {
+            hello.supported_version = Reader::new(data).u16();
+        }
+        _ => (),
+    });
+    Some(hello)
+}
+
+fn is_record_header(header: &[u8]) -> bool {
+    matches!(
+        header[0],
+        CONTENT_TYPE_CHANGE_CIPHER_SPEC
+            | CONTENT_TYPE_ALERT
+            | CONTENT_TYPE_HANDSHAKE
+            | CONTENT_TYPE_APPLICATION_DATA
+    ) && header[1] == 3
+        && header[2] <= 4
+        && (u16::from_be_bytes([header[3], header[4]]) as usize) <= MAX_RECORD_LEN
+}
+
+fn is_version(major: u8, minor: u8) -> bool {
+    major == 3 && minor <= 4
+}
+
+// Strict check of the ClientHello at the start of payload, so that it is safe to
+// apply on any port. The record and handshake lengths must be consistent with each
+// other, and the fields up to cipher suites must be valid if they are captured.
+pub fn is_client_hello(payload: &[u8]) -> bool {
+    const MIN_BODY_LEN: usize = 2 + RANDOM_LEN + 1 + 2 + 2 + 1 + 1;
+    const MAX_SESSION_ID_LEN: usize = 32;
+
+    if payload.len() < RECORD_HEADER_LEN + HANDSHAKE_HEADER_LEN + 2 {
+        return false;
+    }
+    if payload[0] != CONTENT_TYPE_HANDSHAKE || !is_version(payload[1], payload[2]) {
+        return false;
+    }
+    let record_len = u16::from_be_bytes([payload[3], payload[4]]) as usize;
+    if record_len < HANDSHAKE_HEADER_LEN + MIN_BODY_LEN || record_len > MAX_PLAINTEXT_LEN {
+        return false;
+    }
+    let h = &payload[RECORD_HEADER_LEN..];
+    if h[0] != HANDSHAKE_CLIENT_HELLO {
+        return false;
+    }
+    let len = u32::from_be_bytes([0, h[1], h[2], h[3]]) as usize;
+    // the ClientHello fills the record exactly, or is fragmented into full records
+    if len < MIN_BODY_LEN
+        || (len + HANDSHAKE_HEADER_LEN != record_len
+            && (len + HANDSHAKE_HEADER_LEN < record_len || record_len != MAX_PLAINTEXT_LEN))
+    {
+        return false;
+    }
+    let mut r = Reader::new(&h[HANDSHAKE_HEADER_LEN..]);
+    match r.u16() {
+        Some(v) if is_version((v >> 8) as u8, v as u8) => (),
+        _ => return false,
+    }
+    if r.bytes(RANDOM_LEN).is_none() {
+        return true;
+    }
+    let Some(session_id_len) = r.u8() else {
+        return true;
+    };
+    if session_id_len as usize > MAX_SESSION_ID_LEN {
+        return false;
+    }
+    if r.bytes(session_id_len as usize).is_none() {
+        return true;
+    }
+    match r.u16() {
+        Some(n) => n > 0 && n % 2 == 0 && (n as usize) < len,
+        None => true,
+    }
+}
+
+// returns None if the payload does not start with a tls record
+pub fn parse(payload: &[u8]) -> Option<Records> {
+    if payload.len() < RECORD_HEADER_LEN || !is_record_header(payload) {
+        return None;
+    }
+    let mut records = Records::default();
+    let mut data = vec![];
+    // handshake records after ChangeCipherSpec are encrypted
+    let mut encrypted = false;
+    let mut offset = 0;
+    while offset + RECORD_HEADER_LEN <= payload.len() {
+        let header = &payload[offset..offset + RECORD_HEADER_LEN];
+        if !is_record_header(header) {
+            break;
+        }
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let start = offset + RECORD_HEADER_LEN;
+        let end = payload.len().min(start + len);
+        records.content_types.push(header[0]);
+        match header[0] {
+            CONTENT_TYPE_HANDSHAKE if !encrypted => data.extend_from_slice(&payload[start..end]),
+            CONTENT_TYPE_CHANGE_CIPHER_SPEC => encrypted = true,
+            _ => (),
+        }
+        offset = start + len;
+    }
+
+    let mut offset = 0;
+    while offset + HANDSHAKE_HEADER_LEN <= data.len() {
+        let msg_type = data[offset];
+        let len =
+            u32::from_be_bytes([0, data[offset + 1], data[offset + 2], data[offset + 3]]) as usize;
+        let start = offset + HANDSHAKE_HEADER_LEN;
+        let body = &data[start..data.len().min(start + len)];
+        records.handshakes.push(msg_type);
+        match msg_type {
+            HANDSHAKE_CLIENT_HELLO if records.client_hello.is_none() => {
+                records.client_hello = parse_client_hello(body)
+            }
+            HANDSHAKE_SERVER_HELLO if records.server_hello.is_none() => {
+                records.server_hello = parse_server_hello(body)
+            }
+            HANDSHAKE_CERTIFICATE if records.certificate.is_none() => {
+                records.certificate = certificate::parse(body)
+            }
+            _ => (),
+        }
+        offset = start + len;
+    }
+    Some(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(content_type: u8, fragment: &[u8]) -> Vec<u8> {
+        let mut r = vec![content_type, 3, 1];
+        r.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+        r.extend_from_slice(fragment);
+        r
+    }
+
+    fn handshake(msg_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut h = vec![msg_type];
+        h.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        h.extend_from_slice(body);
+        h
+    }
+
+    fn extension(t: u16, data: &[u8]) -> Vec<u8> {
+        let mut e = t.to_be_bytes().to_vec();
+        e.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        e.extend_from_slice(data);
+        e
+    }
+
+    fn client_hello() -> Vec<u8> {
+        let mut body = vec![3, 3];
+        body.extend_from_slice(&[0; RANDOM_LEN]);
+        body.push(0);
+        body.extend_from_slice(&[0, 6, 0x0a, 0x0a, 0x13, 0x01, 0xc0, 0x2f]);
+        body.extend_from_slice(&[1, 0]);
+        let mut extensions = vec![];
+        extensions.extend(extension(0x1a1a, &[]));
+        extensions.extend(extension(
+            EXT_SERVER_NAME,
+            &[
+                0, 14, 0, 0, 11, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm',
+            ],
+        ));
+        extensions.extend(extension(EXT_SUPPORTED_GROUPS, &[0, 4, 0, 0x1d, 0, 0x17]));
+        extensions.extend(extension(EXT_EC_POINT_FORMATS, &[1, 0]));
+        extensions.extend(extension(
+            EXT_ALPN,
+            &[
+                0, 12, 2, b'h', b'2', 8, b'h', b't', b't', b'p', b'/', b'1', b'.', b'1',
+            ],
+        ));
+        extensions.extend(extension(
+            EXT_SUPPORTED_VERSIONS,
+            &[6, 0x2a, 0x2a, 3, 4, 3, 3],
+        ));
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend(extensions);
+        record(
+            CONTENT_TYPE_HANDSHAKE,
+            &handshake(HANDSHAKE_CLIENT_HELLO, &body),
+        )
+    }
+
+    #[test]
+    fn test_is_grease() {
+        assert!(is_grease(0x0a0a));
+        assert!(is_grease(0xfafa));
+        assert!(!is_grease(0x0a1a));
+        assert!(!is_grease(0x1301));
+    }
+
+    #[test]
+    fn test_parse_client_hello() {
+        let payload = client_hello();
+        let records = parse(&payload).unwrap();
+        assert_eq!(records.handshakes, vec![HANDSHAKE_CLIENT_HELLO]);
+        assert_eq!(records.names(), vec!["ClientHello"]);
+        let hello = records.client_hello.unwrap();
+        assert_eq!(hello.version, 0x303);
+        assert_eq!(hello.cipher_suites, vec![0x0a0a, 0x1301, 0xc02f]);
+        assert_eq!(hello.extensions, vec![0x1a1a, 0, 10, 11, 16, 43]);
+        assert_eq!(hello.server_name.as_deref(), Some("example.com"));
+        assert_eq!(hello.alpn, vec!["h2", "http/1.1"]);
+        assert_eq!(hello.supported_groups, vec![0x1d, 0x17]);
+        assert_eq!(hello.ec_point_formats, vec![0]);
+        assert_eq!(hello.max_version(), 0x304);
+
+        // truncated in the alpn extension
+        let records = parse(&payload[..payload.len() - 20]).unwrap();
+        let hello = records.client_hello.unwrap();
+        assert_eq!(hello.server_name.as_deref(), Some("example.com"));
+        assert!(hello.alpn.is_empty());
+        assert_eq!(hello.max_version(), 0x303);
+
+        assert_eq!(parse(b"GET / HTTP/1.1\r\n"), None);
+    }
+
+    #[test]
+    fn test_is_client_hello() {
+        let payload = client_hello();
+        assert!(is_client_hello(&payload));
+        // truncated by the capture
+        assert!(is_client_hello(&payload[..20]));
+        assert!(!is_client_hello(&payload[..8]));
+        assert!(!is_client_hello(b"GET / HTTP/1.1\r\nHost: a\r\n"));
+
+        // bad record version
+        let mut p = payload.clone();
+        p[2] = 5;
+        assert!(!is_client_hello(&p));
+        // handshake length does not match the record length
+        let mut p = payload.clone();
+        p[8] -= 1;
+        assert!(!is_client_hello(&p));
+        // bad client version
+        let mut p = payload.clone();
+        p[9] = 2;
+        assert!(!is_client_hello(&p));
+        // session id too long
+        let mut p = payload.clone();
+        p[11 + RANDOM_LEN] = 33;
+        assert!(!is_client_hello(&p));
+        // odd cipher suites length
+        let mut p = payload.clone();
+        p[13 + RANDOM_LEN] = 5;
+        assert!(!is_client_hello(&p));
+        // not a ClientHello
+        let mut p = payload.clone();
+        p[5] = HANDSHAKE_SERVER_HELLO;
+        assert!(!is_client_hello(&p));
+    }
+
+    #[test]
+    fn test_parse_server_hello() {
+        let mut body = vec![3, 3];
+        body.extend_from_slice(&[0; RANDOM_LEN]);
+        body.extend_from_slice(&[0, 0x13, 0x01, 0]);
+        let mut extensions = extension(EXT_SUPPORTED_VERSIONS, &[3, 4]);
+        extensions.extend(extension(EXT_ALPN, &[0, 3, 2, b'h', b'2']));
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend(extensions);
+        let mut payload = record(
+            CONTENT_TYPE_HANDSHAKE,
+            &handshake(HANDSHAKE_SERVER_HELLO, &body),
+        );
+        payload.extend(record(CONTENT_TYPE_CHANGE_CIPHER_SPEC, &[1]));
+        payload.extend(record(CONTENT_TYPE_APPLICATION_DATA, &[0; 16]));
+
+        let records = parse(&payload).unwrap();
+        assert_eq!(
+            records.names(),
+            vec!["ServerHello", "ChangeCipherSpec", "ApplicationData"]
+        );
+        let hello = records.server_hello.unwrap();
+        assert_eq!(hello.cipher_suite, 0x1301);
+        assert_eq!(hello.alpn.as_deref(), Some("h2"));
+        assert_eq!(hello.negotiated_version(), 0x304);
+        assert_eq!(hello.extensions, vec![43, 16]);
+    }
+}
@@ -97,27 +97,36 @@ impl ServiceTable {
         }
     }
 
-    // When the direction of the flow is incorrect, the L7 parser will obtain the correct direction
-    // and synchronize it here, and after calling reset_score, adjust_score cannot modify direction
-    pub fn reset_score(&mut self, flow_src_key: ServiceKey, flow_dst_key: ServiceKey) {
+    // The L7 parser obtains the correct direction of the flow and synchronizes it here, so that the
+    // server port is learned for flows seen mid-life. After calling reset_score, adjust_score cannot
+    // modify direction. Returns the score of flow_dst_key.
+    pub fn reset_score(&mut self, flow_src_key: ServiceKey, flow_dst_key: ServiceKey) -> u8 {
         // adjust_score
+        let mut flow_dst_score = Self::MIN_SCORE + Self::SCORE_DIFF_THRESHOLD + 1;
         match (flow_src_key, flow_dst_key) {
             (ServiceKey::V4(flow_src_key), ServiceKey::V4(flow_dst_key)) => {
                 self.ipv4.put(flow_src_key, Self::MIN_SCORE);
-                self.ipv4.put(
-                    flow_dst_key,
-                    Self::MIN_SCORE + Self::SCORE_DIFF_THRESHOLD + 1,
-                );
+                if let Some(score) = self.ipv4.get(&flow_dst_key) {
+                    // keep the score learned from SYN|ACK
+                    flow_dst_score = flow_dst_score.max(*score);
+                }
+                self.ipv4.put(flow_dst_key, flow_dst_score);
             }
             (ServiceKey::V6(flow_src_key), ServiceKey::V6(flow_dst_key)) => {
                 self.ipv6.put(flow_src_key, Self::MIN_SCORE);
-                self.ipv6.put(
-                    flow_dst_key,
-                    Self::MIN_SCORE + Self::SCORE_DIFF_THRESHOLD + 1,
-                );
+                if let Some(score) = self.ipv6.get(&flow_dst_key) {
+                    flow_dst_score = flow_dst_score.max(*score);
+                }
+                self.ipv6.put(flow_dst_key, flow_dst_score);
             }
             _ => unimplemented!(),
         }
+        flow_dst_score
+    }
+
+    // Whether the direction is not yet distinguished from the port heuristics of adjust_score
+    pub fn is_weak_score(flow_dst_score: u8) -> bool {
+        flow_dst_score <= Self::MIN_SCORE + Self::SCORE_DIFF_THRESHOLD
     }
 
     // At present, the function is called in update_l4_direction and update_flow_direction respectively,
@@ -700,6 +709,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn reset_score() {
+        let client = ServiceKey::new(
+            Ipv4Addr::new(192, 168, 1, 1).into(),
+            EPC_FROM_DEEPFLOW as i16,
+            1234,
+        );
+        let server = ServiceKey::new(
+            Ipv4Addr::new(192, 168, 1, 10).into(),
+            EPC_FROM_DEEPFLOW as i16,
+            3306,
+        );
+
+        let mut table = ServiceTable::new(10, 10);
+        // the server port learned from l7 parser corrects the flow seen mid-life
+        let dst_score = table.reset_score(client, server);
+        assert!(!ServiceTable::is_weak_score(dst_score));
+        let (src_score, dst_score) = table.get_tcp_score(
+            true,
+            false,
+            PacketDirection::ClientToServer,
+            TcpFlags::ACK,
+            false,
+            false,
+            server,
+            client,
+        );
+        assert!(!ServiceTable::is_client_to_server(src_score, dst_score));
+
+        // the score learned from SYN|ACK is kept
+        table.get_tcp_score(
+            true,
+            false,
+            PacketDirection::ServerToClient,
+            TcpFlags::SYN_ACK,
+            false,
+            false,
+            server,
+            client,
+        );
+        assert_eq!(table.reset_score(client, server), ServiceTable::MAX_SCORE);
+    }
+
     #[test]
     fn get_udp_score() {
         let key_pairs = vec![
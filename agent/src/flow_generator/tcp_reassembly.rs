@@ -0,0 +1,645 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    mem,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use super::flow_map::FlowMapCounter;
+use crate::common::flow::PacketDirection;
+
+// out of order segments cached while waiting for the gap before them
+const MAX_PENDING_SEGMENTS: usize = 8;
+// messages not completed in time are given up
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, PartialEq)]
+pub enum Reassembled {
+    // the segment is not part of a message being reassembled, parse it as it is
+    Bypass,
+    // the segment is buffered, wait for the rest of the message
+    Pending,
+    Complete(Vec<u8>),
+}
+
+#[derive(Default)]
+struct Stream {
+    buffer: Vec<u8>,
+    message_len: usize,
+    next_seq: u32,
+    start_time: Duration,
+    pending: Vec<(u32, Vec<u8>)>,
+    // bytes after the message in the last segment, which begin the next pipelined message
+    leftover: Vec<u8>,
+    // bytes accounted in the memory budget
+    reserved: usize,
+}
+
+impl Stream {
+    fn is_active(&self) -> bool {
+        self.message_len > 0
+    }
+
+    // append the segment if it is adjacent to or overlaps the tail of the buffer
+    // returns false if there is a gap before the segment
+    fn append(&mut self, seq: u32, payload: &[u8]) -> bool {
+        let offset = seq.wrapping_sub(self.next_seq) as i32;
+        if offset > 0 {
+            return false;
+        }
+        let skip = offset.unsigned_abs() as usize;
+        if skip < payload.len() {
+            let payload = &payload[skip..];
+            let remain = self.message_len - self.buffer.len();
+            let (message, next) = payload.split_at(payload.len().min(remain));
+            self.buffer.extend_from_slice(message);
+            self.leftover.extend_from_slice(next);
+            self.next_seq = self.next_seq.wrapping_add(payload.len() as u32);
+        }
+        true
+    }
+
+    fn drain_pending(&mut self) {
+        while !self.pending.is_empty() && self.buffer.len() < self.message_len {
+            let next_seq = self.next_seq;
+            let Some(index) = self
+                .pending
+                .iter()
+                .position(|(seq, _)| seq.wrapping_sub(next_seq) as i32 <= 0)
+            else {
+                return;
+            };
+            let (seq, payload) = self.pending.swap_remove(index);
+            self.append(seq, &payload);
+        }
+    }
+}
+
+// Reassembles length prefixed messages crossing tcp segments for L7 parsers,
+// the memory of all flows in a FlowMap is limited by a shared budget
+pub struct TcpReassembly {
+    streams: [Stream; 2],
+    counter: Arc<FlowMapCounter>,
+}
+
+impl TcpReassembly {
+    pub fn new(counter: Arc<FlowMapCounter>) -> Self {
+        Self {
+            streams: Default::default(),
+            counter,
+        }
+    }
+
+    fn reserve(&mut self, index: usize, size: usize, memory_limit: usize) -> bool {
+        let used = self
+            .counter
+            .tcp_reassembly_memory
+            .fetch_add(size as u64, Ordering::Relaxed);
+        if used + size as u64 > memory_limit as u64 {
+            self.counter
+                .tcp_reassembly_memory
+                .fetch_sub(size as u64, Ordering::Relaxed);
+            return false;
+        }
+        self.streams[index].reserved += size;
+        true
+    }
+
+    fn release(&mut self, index: usize) {
+        let stream = mem::take(&mut self.streams[index]);
+        self.counter
+            .tcp_reassembly_memory
+            .fetch_sub(stream.reserved as u64, Ordering::Relaxed);
+    }
+
+    fn evict(&mut self, index: usize) {
+        self.release(index);
+        self.counter
+            .tcp_reassembly_evicted
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn start(
+        &mut self,
+        index: usize,
+        seq: u32,
+        payload: &[u8],
+        message_len: Option<usize>,
+        now: Duration,
+        max_message_size: usize,
+        memory_limit: usize,
+    ) -> bool {
+        let len = match message_len {
+            Some(len) if len > payload.len() && len <= max_message_size => len,
+            _ => return false,
+        };
+        if !self.reserve(index, len, memory_limit) {
+            self.counter
+                .tcp_reassembly_evicted
+                .fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        let stream = &mut self.streams[index];
+        stream.buffer = Vec::with_capacity(len);
+        stream.buffer.extend_from_slice(payload);
+        stream.message_len = len;
+        stream.next_seq = seq.wrapping_add(payload.len() as u32);
+        stream.start_time = now;
+        true
+    }
+
+    // message_len returns the total length of the message starting at the given bytes,
+    // it is provided by the parser
+    pub fn reassemble<F: Fn(&[u8]) -> Option<usize>>(
+        &mut self,
+        direction: PacketDirection,
+        seq: u32,
+        payload: &[u8],
+        message_len: F,
+        now: Duration,
+        max_message_size: usize,
+        memory_limit: usize,
+    ) -> Reassembled {
+        let index = direction as usize;
+        if payload.is_empty() {
+            return if self.streams[index].is_active() {
+                Reassembled::Pending
+            } else {
+                Reassembled::Bypass
+            };
+        }
+        if self.streams[index].is_active()
+            && now > self.streams[index].start_time + REASSEMBLY_TIMEOUT
+        {
+            self.evict(index);
+        }
+
+        if !self.streams[index].is_active() {
+            let len = message_len(payload);
+            return if self.start(
+                index,
+                seq,
+                payload,
+                len,
+                now,
+                max_message_size,
+                memory_limit,
+            ) {
+                Reassembled::Pending
+            } else {
+                Reassembled::Bypass
+            };
+        }
+
+        let stream = &mut self.streams[index];
+        if !stream.append(seq, payload) {
+            let offset = seq.wrapping_sub(stream.next_seq) as usize;
+            let remain = stream.message_len - stream.buffer.len();
+            if offset >= remain || stream.pending.len() >= MAX_PENDING_SEGMENTS {
+                // lost segments or the segment belongs to the next message
+                self.evict(index);
+                return Reassembled::Bypass;
+            }
+            if !self.reserve(index, payload.len(), memory_limit) {
+                self.evict(index);
+                return Reassembled::Bypass;
+            }
+            self.streams[index].pending.push((seq, payload.to_vec()));
+            return Reassembled::Pending;
+        }
+        stream.drain_pending();
+
+        if stream.buffer.len() < stream.message_len {
+            return Reassembled::Pending;
+        }
+        let mut buffer = mem::take(&mut stream.buffer);
+        let leftover = mem::take(&mut stream.leftover);
+        let next_seq = stream.next_seq;
+        self.release(index);
+        self.counter
+            .tcp_reassembly_complete
+            .fetch_add(1, Ordering::Relaxed);
+        if !leftover.is_empty() {
+            // continue with the next message if it is cut by the segment boundary,
+            // otherwise it is parsed along with this message like other pipelined payloads
+            let len = message_len(&leftover);
+            let seq = next_seq.wrapping_sub(leftover.len() as u32);
+            if !self.start(
+                index,
+                seq,
+                &leftover,
+                len,
+                now,
+                max_message_size,
+                memory_limit,
+            ) {
+                buffer.extend_from_slice(&leftover);
+            }
+        }
+        Reassembled::Complete(buffer)
+    }
+}
+
+impl Drop for TcpReassembly {
+    fn drop(&mut self) {
+        self.release(0);
+        self.release(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_MESSAGE_SIZE: usize = 1024;
+    const MEMORY_LIMIT: usize = 4096;
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn test_in_order() {
+        let counter = Arc::new(FlowMapCounter::default());
+        let mut r = TcpReassembly::new(counter.clone());
+        let msg = message(300);
+        let dir = PacketDirection::ClientToServer;
+        let now = Duration::from_secs(1);
+
+        assert_eq!(
+            r.reassemble(
+                dir,
+                1000,
+                &msg[..100],
+                |_| None,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Bypass
+        );
+        assert_eq!(
+            r.reassemble(
+                dir,
+                1000,
+                &msg[..100],
+                |_| Some(300),
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        assert_eq!(counter.tcp_reassembly_memory.load(Ordering::Relaxed), 300);
+        // retransmission is ignored
+        assert_eq!(
+            r.reassemble(
+                dir,
+                1000,
+                &msg[..100],
+                |_| Some(300),
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        assert_eq!(
+            r.reassemble(
+                dir,
+                1100,
+                &msg[100..200],
+                |_| None,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        // the other direction is independent
+        assert_eq!(
+            r.reassemble(
+                PacketDirection::ServerToClient,
+                5000,
+                &msg[..10],
+                |_| None,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Bypass
+        );
+        // overlapping with the buffered tail
+        assert_eq!(
+            r.reassemble(
+                dir,
+                1150,
+                &msg[150..],
+                |_| None,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Complete(msg.clone())
+        );
+        assert_eq!(counter.tcp_reassembly_memory.load(Ordering::Relaxed), 0);
+        assert_eq!(counter.tcp_reassembly_complete.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let counter = Arc::new(FlowMapCounter::default());
+        let mut r = TcpReassembly::new(counter.clone());
+        let msg = message(300);
+        let dir = PacketDirection::ServerToClient;
+        let now = Duration::from_secs(1);
+        // sequence wraps
+        let seq = u32::MAX - 150;
+
+        assert_eq!(
+            r.reassemble(
+                dir,
+                seq,
+                &msg[..100],
+                |_| Some(300),
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        assert_eq!(
+            r.reassemble(
+                dir,
+                seq.wrapping_add(200),
+                &msg[200..],
+                |_| None,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        assert_eq!(
+            r.reassemble(
+                dir,
+                seq.wrapping_add(100),
+                &msg[100..200],
+                |_| None,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Complete(msg.clone())
+        );
+        assert_eq!(counter.tcp_reassembly_memory.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_pipelining() {
+        let counter = Arc::new(FlowMapCounter::default());
+        let mut r = TcpReassembly::new(counter.clone());
+        let dir = PacketDirection::ClientToServer;
+        let now = Duration::from_secs(1);
+        // messages prefixed with their length
+        let message_len =
+            |p: &[u8]| (p.len() >= 2).then(|| u16::from_be_bytes([p[0], p[1]]) as usize);
+        let prefixed = |len: usize| {
+            let mut m = message(len);
+            m[..2].copy_from_slice(&(len as u16).to_be_bytes());
+            m
+        };
+        let (first, second, third) = (prefixed(300), prefixed(200), prefixed(20));
+
+        assert_eq!(
+            r.reassemble(
+                dir,
+                0,
+                &first[..100],
+                message_len,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        // the segment ends in the middle of the next message
+        let mut segment = first[100..].to_vec();
+        segment.extend_from_slice(&second[..50]);
+        assert_eq!(
+            r.reassemble(
+                dir,
+                100,
+                &segment,
+                message_len,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Complete(first.clone())
+        );
+        assert_eq!(counter.tcp_reassembly_memory.load(Ordering::Relaxed), 200);
+        // the next message is complete in the segment
+        let mut segment = second[50..].to_vec();
+        segment.extend_from_slice(&third);
+        let mut expected = second.clone();
+        expected.extend_from_slice(&third);
+        assert_eq!(
+            r.reassemble(
+                dir,
+                350,
+                &segment,
+                message_len,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Complete(expected)
+        );
+        assert_eq!(counter.tcp_reassembly_memory.load(Ordering::Relaxed), 0);
+        assert_eq!(counter.tcp_reassembly_complete.load(Ordering::Relaxed), 2);
+
+        // pipelined bytes in an out of order segment are kept as well
+        assert_eq!(
+            r.reassemble(
+                dir,
+                520,
+                &first[..100],
+                message_len,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        let mut segment = first[200..].to_vec();
+        segment.extend_from_slice(&second[..100]);
+        assert_eq!(
+            r.reassemble(
+                dir,
+                720,
+                &segment,
+                message_len,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        assert_eq!(
+            r.reassemble(
+                dir,
+                620,
+                &first[100..200],
+                message_len,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Complete(first.clone())
+        );
+        assert_eq!(
+            r.reassemble(
+                dir,
+                920,
+                &second[100..],
+                message_len,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Complete(second.clone())
+        );
+        drop(r);
+        assert_eq!(counter.tcp_reassembly_memory.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let counter = Arc::new(FlowMapCounter::default());
+        let msg = message(1000);
+        let dir = PacketDirection::ClientToServer;
+        let now = Duration::from_secs(1);
+
+        // too large
+        let mut r = TcpReassembly::new(counter.clone());
+        assert_eq!(
+            r.reassemble(
+                dir,
+                0,
+                &msg[..100],
+                |_| Some(2000),
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Bypass
+        );
+
+        // memory budget shared by flows
+        let mut flows = (0..4)
+            .map(|_| TcpReassembly::new(counter.clone()))
+            .collect::<Vec<_>>();
+        for f in flows.iter_mut() {
+            assert_eq!(
+                f.reassemble(
+                    dir,
+                    0,
+                    &msg[..100],
+                    |_| Some(1000),
+                    now,
+                    MAX_MESSAGE_SIZE,
+                    MEMORY_LIMIT
+                ),
+                Reassembled::Pending
+            );
+        }
+        assert_eq!(
+            r.reassemble(
+                dir,
+                0,
+                &msg[..100],
+                |_| Some(1000),
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Bypass
+        );
+        assert_eq!(counter.tcp_reassembly_evicted.load(Ordering::Relaxed), 1);
+        flows.pop();
+        assert_eq!(counter.tcp_reassembly_memory.load(Ordering::Relaxed), 3000);
+
+        // lost segment
+        assert_eq!(
+            r.reassemble(
+                dir,
+                0,
+                &msg[..100],
+                |_| Some(1000),
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        assert_eq!(
+            r.reassemble(
+                dir,
+                1000,
+                &msg[..100],
+                |_| None,
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Bypass
+        );
+        assert_eq!(counter.tcp_reassembly_evicted.load(Ordering::Relaxed), 2);
+
+        // timeout
+        assert_eq!(
+            r.reassemble(
+                dir,
+                0,
+                &msg[..100],
+                |_| Some(1000),
+                now,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Pending
+        );
+        assert_eq!(
+            r.reassemble(
+                dir,
+                100,
+                &msg[100..200],
+                |_| None,
+                now + REASSEMBLY_TIMEOUT * 2,
+                MAX_MESSAGE_SIZE,
+                MEMORY_LIMIT
+            ),
+            Reassembled::Bypass
+        );
+        assert_eq!(counter.tcp_reassembly_evicted.load(Ordering::Relaxed), 3);
+
+        drop(flows);
+        assert_eq!(counter.tcp_reassembly_memory.load(Ordering::Relaxed), 0);
+    }
+}
@@ -14,6 +14,12 @@
  * limitations under the License.
  */
 
+mod datadog;
+mod otlp_json;
+mod statsd;
+
+pub use statsd::StatsdServer;
+
 use std::collections::HashMap;
 use std::fmt::{self, Debug, Formatter};
 use std::io::{Read, Write};
@@ -57,6 +63,7 @@ use crate::{
     flow_generator::protocol_logs::{http::handle_endpoint, L7ResponseStatus},
     metric::document::{Direction, TapSide},
     policy::PolicyGetter,
+    sender::remote_write_sender::{TimeSeries, WriteRequest},
 };
 
 use public::{
@@ -82,6 +89,10 @@ type GenericError = Box<dyn std::error::Error + Send + Sync>;
 
 const NOT_FOUND: &[u8] = b"Not Found";
 const GZIP: &str = "gzip";
+const JSON_CONTENT_TYPE: &str = "application/json";
+const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+// time series in a remote write request generated by agent
+const MAX_SERIES_PER_REQUEST: usize = 1000;
 const OPEN_TELEMETRY: u32 = 20220607;
 const OPEN_TELEMETRY_COMPRESSED: u32 = 20221024;
 const PROMETHEUS: u32 = 20220613;
@@ -135,6 +146,17 @@ pub struct PrometheusExtra {
     extra_label_values: Vec<String>,
 }
 
+impl PrometheusExtra {
+    // metrics is a snappy compressed remote write request without extra labels
+    pub fn new(metrics: Vec<u8>) -> Self {
+        Self {
+            metrics,
+            extra_label_names: vec![],
+            extra_label_values: vec![],
+        }
+    }
+}
+
 impl Debug for PrometheusExtra {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.write_fmt(format_args!(
@@ -147,6 +169,33 @@ impl Debug for PrometheusExtra {
 #[derive(Debug)]
 pub struct BoxedPrometheusExtra(pub Box<PrometheusExtra>);
 
+// sends time series generated by agent as snappy compressed remote write requests
+pub(crate) fn send_prometheus_series(
+    sender: &DebugSender<BoxedPrometheusExtra>,
+    series: Vec<TimeSeries>,
+) {
+    let mut encoder = snap::raw::Encoder::new();
+    let mut iter = series.into_iter().peekable();
+    while iter.peek().is_some() {
+        let request = WriteRequest {
+            timeseries: iter.by_ref().take(MAX_SERIES_PER_REQUEST).collect(),
+        };
+        let metrics = match encoder.compress_vec(&request.encode_to_vec()) {
+            Ok(m) => m,
+            Err(e) => {
+                warn!("compress prometheus write request failed: {}", e);
+                continue;
+            }
+        };
+        if let Err(Error::Terminated(..)) = sender.send(BoxedPrometheusExtra(Box::new(
+            PrometheusExtra::new(metrics),
+        ))) {
+            warn!("sender queue has terminated");
+            return;
+        }
+    }
+}
+
 impl Sendable for BoxedPrometheusExtra {
     fn encode(self, buf: &mut Vec<u8>) -> Result<usize, prost::EncodeError> {
         let pb_prometheus_metric = metric::PrometheusMetric {
@@ -243,7 +292,7 @@ async fn aggregate_with_catch_exception(
 
 fn decode_otel_trace_data(
     peer_addr: SocketAddr,
-    data: Vec<u8>,
+    mut d: TracesData,
     local_epc_id: u32,
     policy_getter: Arc<PolicyGetter>,
     time_diff: i64,
@@ -251,7 +300,6 @@ fn decode_otel_trace_data(
     log_parser_config: Arc<LogParserConfig>,
 ) -> Result<(Vec<u8>, Vec<BatchedBox<L7Stats>>), GenericError> {
     let mut l7_stats: Vec<BatchedBox<L7Stats>> = vec![];
-    let mut d = TracesData::decode(data.as_slice())?;
     // 因为collector传过来traceData的全部resource都有"app.host.ip"的属性，所以只检查第一个resource有没有“app.host.ip”即可，
     // sdk传过来的traceData因没有该属性则要补上(key: “app.host.ip”, value: 对端IP)属性值
     // =======================================================================
@@ -584,6 +632,58 @@ fn compress_data(input: Vec<u8>) -> std::io::Result<Vec<u8>> {
     e.finish()
 }
 
+// converts traces into the span integration format and sends them to Sender
+fn send_traces_data(
+    peer_addr: SocketAddr,
+    traces_data: TracesData,
+    otel_sender: &DebugSender<OpenTelemetry>,
+    compressed_otel_sender: &DebugSender<OpenTelemetryCompressed>,
+    otel_l7_stats_sender: &DebugSender<BatchedBox<L7Stats>>,
+    compressed: bool,
+    counter: &CompressedMetric,
+    local_epc_id: u32,
+    policy_getter: Arc<PolicyGetter>,
+    time_diff: i64,
+    flow_id: Arc<AtomicU64>,
+    log_parser_config: Arc<LogParserConfig>,
+) -> Result<(), GenericError> {
+    let mut decode_data = decode_otel_trace_data(
+        peer_addr,
+        traces_data,
+        local_epc_id,
+        policy_getter,
+        time_diff,
+        flow_id,
+        log_parser_config,
+    )
+    .map_err(|e| {
+        debug!("decode otel trace data error: {}", e);
+        e
+    })?;
+    if !decode_data.1.is_empty() {
+        if let Err(Error::Terminated(..)) = otel_l7_stats_sender.send_all(&mut decode_data.1) {
+            warn!("sender queue has terminated");
+        }
+    }
+    if compressed {
+        counter
+            .uncompressed
+            .fetch_add(decode_data.0.len() as u64, Ordering::Relaxed);
+        let compressed_data = compress_data(decode_data.0)?;
+        counter
+            .compressed
+            .fetch_add(compressed_data.len() as u64, Ordering::Relaxed);
+        if let Err(Error::Terminated(..)) =
+            compressed_otel_sender.send(OpenTelemetryCompressed(compressed_data))
+        {
+            warn!("sender queue has terminated");
+        }
+    } else if let Err(Error::Terminated(..)) = otel_sender.send(OpenTelemetry(decode_data.0)) {
+        warn!("sender queue has terminated");
+    }
+    Ok(())
+}
+
 /// 接收metric server发送的请求，根据路由处理分发
 async fn handler(
     peer_addr: SocketAddr,
@@ -615,10 +715,28 @@ async fn handler(
                 .body(doc_bytes.as_slice().into())
                 .unwrap())
         }
-        // OpenTelemetry trace integration
-        (&Method::POST, "/api/v1/otel/trace") => {
+        // OpenTelemetry trace integration, "/v1/traces" is the default path of OTLP/HTTP
+        (&Method::POST, "/api/v1/otel/trace") | (&Method::POST, "/v1/traces") => {
+            // OTLP/HTTP responds in the encoding of the request
+            let is_json = req
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| v.starts_with(JSON_CONTENT_TYPE))
+                .is_some();
+            let response = || {
+                let (content_type, body) = if is_json {
+                    (JSON_CONTENT_TYPE, Body::from("{}"))
+                } else {
+                    (PROTOBUF_CONTENT_TYPE, Body::empty())
+                };
+                Response::builder()
+                    .header(CONTENT_TYPE, content_type)
+                    .body(body)
+                    .unwrap()
+            };
             if external_trace_integration_disabled {
-                return Ok(Response::builder().body(Body::empty()).unwrap());
+                return Ok(response());
             }
             let (part, body) = req.into_parts();
             let whole_body = match aggregate_with_catch_exception(body, &exception_handler).await {
@@ -628,47 +746,89 @@ async fn handler(
                 }
             };
             let tracing_data = decode_metric(whole_body, &part.headers)?;
-            let time_diff = time_diff.load(Ordering::Relaxed);
-            let mut decode_data = decode_otel_trace_data(
+            let traces_data = if is_json {
+                otlp_json::decode_traces_data(tracing_data.as_slice())
+            } else {
+                TracesData::decode(tracing_data.as_slice()).map_err(|e| e.into())
+            };
+            let traces_data = match traces_data {
+                Ok(d) => d,
+                Err(e) => {
+                    debug!("decode otel trace data error: {}", e);
+                    return Ok(Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(e.to_string().into())
+                        .unwrap());
+                }
+            };
+            send_traces_data(
                 peer_addr,
-                tracing_data,
+                traces_data,
+                &otel_sender,
+                &compressed_otel_sender,
+                &otel_l7_stats_sender,
+                compressed,
+                &counter,
                 local_epc_id,
                 policy_getter,
-                time_diff,
-                flow_id.clone(),
-                log_parser_config.clone(),
-            )
-            .map_err(|e| {
-                debug!("decode otel trace data error: {}", e);
-                e
-            })?;
-            if !decode_data.1.is_empty() {
-                if let Err(Error::Terminated(..)) =
-                    otel_l7_stats_sender.send_all(&mut decode_data.1)
-                {
-                    warn!("sender queue has terminated");
-                }
+                time_diff.load(Ordering::Relaxed),
+                flow_id,
+                log_parser_config,
+            )?;
+
+            Ok(response())
+        }
+        // Datadog trace integration, compatible with the trace intake API of datadog agent
+        (&Method::POST | &Method::PUT, "/v0.3/traces" | "/v0.4/traces") => {
+            let response = || {
+                Response::builder()
+                    .header(CONTENT_TYPE, JSON_CONTENT_TYPE)
+                    .body(Body::from(datadog::TRACES_RESPONSE))
+                    .unwrap()
+            };
+            if external_trace_integration_disabled {
+                return Ok(response());
             }
-            if compressed {
-                counter
-                    .uncompressed
-                    .fetch_add(decode_data.0.len() as u64, Ordering::Relaxed);
-                let compressed_data = compress_data(decode_data.0)?;
-                counter
-                    .compressed
-                    .fetch_add(compressed_data.len() as u64, Ordering::Relaxed);
-                if let Err(Error::Terminated(..)) =
-                    compressed_otel_sender.send(OpenTelemetryCompressed(compressed_data))
-                {
-                    warn!("sender queue has terminated");
+            let is_json = req
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| v.starts_with(JSON_CONTENT_TYPE))
+                .is_some();
+            let (part, body) = req.into_parts();
+            let whole_body = match aggregate_with_catch_exception(body, &exception_handler).await {
+                Ok(b) => b,
+                Err(e) => {
+                    return Ok(e);
                 }
-            } else {
-                if let Err(Error::Terminated(..)) = otel_sender.send(OpenTelemetry(decode_data.0)) {
-                    warn!("sender queue has terminated");
+            };
+            let tracing_data = decode_metric(whole_body, &part.headers)?;
+            let traces_data = match datadog::decode_traces_data(tracing_data.as_slice(), is_json) {
+                Ok(d) => d,
+                Err(e) => {
+                    debug!("decode datadog trace data error: {}", e);
+                    return Ok(Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(e.to_string().into())
+                        .unwrap());
                 }
-            }
+            };
+            send_traces_data(
+                peer_addr,
+                traces_data,
+                &otel_sender,
+                &compressed_otel_sender,
+                &otel_l7_stats_sender,
+                compressed,
+                &counter,
+                local_epc_id,
+                policy_getter,
+                time_diff.load(Ordering::Relaxed),
+                flow_id,
+                log_parser_config,
+            )?;
 
-            Ok(Response::builder().body(Body::empty()).unwrap())
+            Ok(response())
         }
         // Prometheus integration
         (&Method::POST, "/api/v1/prometheus") => {
@@ -0,0 +1,339 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Decoder of traces posted to the trace intake API v0.3/v0.4 of datadog agent,
+// spans are translated into opentelemetry spans following the semantic conventions
+// used by decode_otel_trace_data, see
+// https://github.com/DataDog/datadog-agent/blob/main/pkg/trace/api/version.go
+
+// fields of the messages differ between opentelemetry proto versions
+#![allow(clippy::needless_update)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+use serde::Deserialize;
+
+use super::GenericError;
+
+use public::proto::integration::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue},
+    resource::v1::Resource,
+    trace::v1::{span::SpanKind, ResourceSpans, ScopeSpans, Span, Status, TracesData},
+};
+
+// sampling rates returned to tracers, all traces are kept
+pub const TRACES_RESPONSE: &str = r#"{"rate_by_service":{"service:,env:":1}}"#;
+
+const SCOPE_NAME: &str = "datadog";
+// higher 64 bits of 128 bits trace id in hex
+const TRACE_ID_HIGH_TAG: &str = "_dd.p.tid";
+const INTERNAL_TAG_PREFIX: &str = "_dd.";
+
+// error status of opentelemetry
+const STATUS_CODE_ERROR: i32 = 2;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DatadogSpan {
+    service: String,
+    // operation name, e.g. http.request
+    name: String,
+    // e.g. GET /users/?
+    resource: String,
+    trace_id: u64,
+    span_id: u64,
+    parent_id: u64,
+    // nanoseconds
+    start: i64,
+    duration: i64,
+    error: i32,
+    meta: HashMap<String, String>,
+    metrics: HashMap<String, f64>,
+    #[serde(rename = "type")]
+    span_type: String,
+}
+
+// payloads are msgpack encoded by default, JSON is accepted as well
+pub fn decode_traces_data(data: &[u8], is_json: bool) -> Result<TracesData, GenericError> {
+    let traces: Vec<Vec<DatadogSpan>> = if is_json {
+        serde_json::from_slice(data)?
+    } else {
+        rmp_serde::from_slice(data)?
+    };
+
+    // spans of a service are grouped into a resource
+    let mut services: BTreeMap<String, Vec<Span>> = BTreeMap::new();
+    for span in traces.into_iter().flatten() {
+        services
+            .entry(span.service.clone())
+            .or_default()
+            .push(translate_span(span));
+    }
+
+    Ok(TracesData {
+        resource_spans: services
+            .into_iter()
+            .map(|(service, spans)| ResourceSpans {
+                resource: Some(Resource {
+                    attributes: vec![string_attribute("service.name", service)],
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    scope: Some(InstrumentationScope {
+                        name: SCOPE_NAME.to_owned(),
+                        ..Default::default()
+                    }),
+                    spans,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+            .collect(),
+    })
+}
+
+fn translate_span(mut span: DatadogSpan) -> Span {
+    let mut trace_id = vec![0u8; 8];
+    if let Some(high) = span
+        .meta
+        .get(TRACE_ID_HIGH_TAG)
+        .and_then(|h| u64::from_str_radix(h, 16).ok())
+    {
+        trace_id.copy_from_slice(&high.to_be_bytes());
+    }
+    trace_id.extend_from_slice(&span.trace_id.to_be_bytes());
+
+    let kind = match span.meta.remove("span.kind").as_deref() {
+        Some("server") => SpanKind::Server,
+        Some("client") => SpanKind::Client,
+        Some("producer") => SpanKind::Producer,
+        Some("consumer") => SpanKind::Consumer,
+        Some("internal") => SpanKind::Internal,
+        _ => match span.span_type.as_str() {
+            "web" => SpanKind::Server,
+            "http" | "grpc" | "sql" | "db" | "cassandra" | "elasticsearch" | "mongodb"
+            | "redis" | "memcached" | "cache" => SpanKind::Client,
+            _ => SpanKind::Internal,
+        },
+    };
+
+    let status = if span.error != 0 {
+        Some(Status {
+            message: span
+                .meta
+                .get("error.message")
+                .or_else(|| span.meta.get("error.msg"))
+                .cloned()
+                .unwrap_or_default(),
+            code: STATUS_CODE_ERROR,
+            ..Default::default()
+        })
+    } else {
+        None
+    };
+
+    let mut attributes = vec![string_attribute("operation.name", span.name.clone())];
+    if !span.span_type.is_empty() {
+        attributes.push(string_attribute("span.type", span.span_type.clone()));
+    }
+    // fill attributes recognized by decode_otel_trace_data
+    if !span.meta.contains_key("http.scheme") && span.meta.contains_key("http.method") {
+        let scheme = match span.meta.get("http.url") {
+            Some(url) if url.starts_with("https://") => "https",
+            _ => "http",
+        };
+        attributes.push(string_attribute("http.scheme", scheme.to_owned()));
+    }
+    if !span.meta.contains_key("db.system") {
+        if let Some(db) = span.meta.get("db.type") {
+            attributes.push(string_attribute("db.system", db.clone()));
+        }
+    }
+    if !span.meta.contains_key("net.peer.ip") {
+        if let Some(ip) = span
+            .meta
+            .get("network.destination.ip")
+            .or_else(|| span.meta.get("out.host"))
+            .filter(|h| h.parse::<IpAddr>().is_ok())
+        {
+            attributes.push(string_attribute("net.peer.ip", ip.clone()));
+        }
+    }
+
+    let mut meta = span.meta.into_iter().collect::<Vec<_>>();
+    meta.sort();
+    for (key, value) in meta {
+        if key.starts_with(INTERNAL_TAG_PREFIX) {
+            continue;
+        }
+        let value = match value.parse::<i64>() {
+            Ok(code) if key == "http.status_code" => any_value::Value::IntValue(code),
+            _ => any_value::Value::StringValue(value),
+        };
+        attributes.push(KeyValue {
+            key,
+            value: Some(AnyValue { value: Some(value) }),
+        });
+    }
+    let mut metrics = span.metrics.into_iter().collect::<Vec<_>>();
+    metrics.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, value) in metrics {
+        if key.starts_with(INTERNAL_TAG_PREFIX) {
+            continue;
+        }
+        attributes.push(KeyValue {
+            key,
+            value: Some(AnyValue {
+                value: Some(any_value::Value::DoubleValue(value)),
+            }),
+        });
+    }
+
+    let start = span.start.max(0) as u64;
+    Span {
+        trace_id,
+        span_id: span.span_id.to_be_bytes().to_vec(),
+        parent_span_id: if span.parent_id == 0 {
+            vec![]
+        } else {
+            span.parent_id.to_be_bytes().to_vec()
+        },
+        name: if span.resource.is_empty() {
+            span.name
+        } else {
+            span.resource
+        },
+        kind: kind as i32,
+        start_time_unix_nano: start,
+        end_time_unix_nano: start + span.duration.max(0) as u64,
+        attributes,
+        status,
+        ..Default::default()
+    }
+}
+
+fn string_attribute(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn decode_datadog_traces() {
+        let traces = json!([[
+            {
+                "service": "web-frontend",
+                "name": "http.request",
+                "resource": "GET /users",
+                "trace_id": 0x1122334455667788u64,
+                "span_id": 1,
+                "parent_id": 0,
+                "start": 1700000000000000000i64,
+                "duration": 2000000,
+                "error": 1,
+                "type": "web",
+                "meta": {
+                    "_dd.p.tid": "6553f10a00000000",
+                    "http.method": "GET",
+                    "http.url": "https://example.com/users",
+                    "http.status_code": "500",
+                    "error.message": "internal error"
+                },
+                "metrics": {"_sampling_priority_v1": 1, "process_id": 42}
+            },
+            {
+                "service": "users-db",
+                "name": "postgres.query",
+                "resource": "SELECT * FROM users",
+                "trace_id": 0x1122334455667788u64,
+                "span_id": 2,
+                "parent_id": 1,
+                "start": 1700000000000500000i64,
+                "duration": 1000000,
+                "type": "sql",
+                "meta": {"db.type": "postgresql", "out.host": "10.0.0.2"}
+            }
+        ]]);
+        let payload = rmp_serde::to_vec_named(&traces).unwrap();
+        for (data, is_json) in [
+            (payload, false),
+            (serde_json::to_vec(&traces).unwrap(), true),
+        ] {
+            let traces_data = decode_traces_data(&data, is_json).unwrap();
+            assert_eq!(traces_data.resource_spans.len(), 2);
+
+            let attribute = |span: &Span, key: &str| {
+                span.attributes
+                    .iter()
+                    .find(|kv| kv.key == key)
+                    .and_then(|kv| kv.value.clone())
+                    .and_then(|v| v.value)
+            };
+
+            let web = &traces_data.resource_spans[1].scope_spans[0].spans[0];
+            assert_eq!(
+                web.trace_id,
+                [
+                    0x65, 0x53, 0xf1, 0x0a, 0, 0, 0, 0, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77,
+                    0x88
+                ]
+            );
+            assert_eq!(web.span_id, 1u64.to_be_bytes());
+            assert!(web.parent_span_id.is_empty());
+            assert_eq!(web.name, "GET /users");
+            assert_eq!(web.kind, SpanKind::Server as i32);
+            assert_eq!(web.end_time_unix_nano - web.start_time_unix_nano, 2000000);
+            assert_eq!(web.status.as_ref().unwrap().code, STATUS_CODE_ERROR);
+            assert_eq!(web.status.as_ref().unwrap().message, "internal error");
+            assert_eq!(
+                attribute(web, "http.scheme"),
+                Some(any_value::Value::StringValue("https".to_owned()))
+            );
+            assert_eq!(
+                attribute(web, "http.status_code"),
+                Some(any_value::Value::IntValue(500))
+            );
+            assert_eq!(
+                attribute(web, "process_id"),
+                Some(any_value::Value::DoubleValue(42.0))
+            );
+            assert!(attribute(web, "_dd.p.tid").is_none());
+
+            let db = &traces_data.resource_spans[0].scope_spans[0].spans[0];
+            assert_eq!(db.parent_span_id, 1u64.to_be_bytes());
+            assert_eq!(db.kind, SpanKind::Client as i32);
+            assert!(db.status.is_none());
+            assert_eq!(
+                attribute(db, "db.system"),
+                Some(any_value::Value::StringValue("postgresql".to_owned()))
+            );
+            assert_eq!(
+                attribute(db, "net.peer.ip"),
+                Some(any_value::Value::StringValue("10.0.0.2".to_owned()))
+            );
+        }
+    }
+}
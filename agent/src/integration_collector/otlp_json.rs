@@ -0,0 +1,373 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Decoder of the OTLP/JSON encoding, see
+// https://opentelemetry.io/docs/specs/otlp/#json-protobuf-encoding
+//
+// Differences from the standard protobuf JSON mapping:
+//   - trace_id, span_id and parent_span_id are hex strings instead of base64
+//   - enums are integers, names are accepted as well
+// Both lowerCamelCase and original field names are accepted.
+
+// fields of the messages differ between opentelemetry proto versions
+#![allow(clippy::needless_update)]
+
+use serde_json::{Map, Value};
+
+use super::GenericError;
+
+use public::proto::integration::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, ArrayValue, InstrumentationScope, KeyValue, KeyValueList},
+    resource::v1::Resource,
+    trace::v1::{span, ResourceSpans, ScopeSpans, Span, Status, TracesData},
+};
+
+type Object = Map<String, Value>;
+
+const SPAN_KINDS: [&str; 6] = [
+    "SPAN_KIND_UNSPECIFIED",
+    "SPAN_KIND_INTERNAL",
+    "SPAN_KIND_SERVER",
+    "SPAN_KIND_CLIENT",
+    "SPAN_KIND_PRODUCER",
+    "SPAN_KIND_CONSUMER",
+];
+const STATUS_CODES: [&str; 3] = ["STATUS_CODE_UNSET", "STATUS_CODE_OK", "STATUS_CODE_ERROR"];
+
+// ExportTraceServiceRequest has the same layout as TracesData
+pub fn decode_traces_data(data: &[u8]) -> Result<TracesData, GenericError> {
+    let value: Value = serde_json::from_slice(data)?;
+    let Value::Object(obj) = value else {
+        return Err("otlp json is not an object".into());
+    };
+    Ok(TracesData {
+        resource_spans: objects(&obj, "resourceSpans", "resource_spans")?
+            .map(resource_spans)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn resource_spans(obj: &Object) -> Result<ResourceSpans, GenericError> {
+    Ok(ResourceSpans {
+        resource: object(obj, "resource", "resource")?
+            .map(|r| -> Result<_, GenericError> {
+                Ok(Resource {
+                    attributes: attributes(r)?,
+                    dropped_attributes_count: get_u32(
+                        r,
+                        "droppedAttributesCount",
+                        "dropped_attributes_count",
+                    )?,
+                    ..Default::default()
+                })
+            })
+            .transpose()?,
+        scope_spans: objects(obj, "scopeSpans", "scope_spans")?
+            .map(scope_spans)
+            .collect::<Result<_, _>>()?,
+        schema_url: get_string(obj, "schemaUrl", "schema_url")?,
+        ..Default::default()
+    })
+}
+
+fn scope_spans(obj: &Object) -> Result<ScopeSpans, GenericError> {
+    Ok(ScopeSpans {
+        scope: object(obj, "scope", "scope")?
+            .map(|s| -> Result<_, GenericError> {
+                Ok(InstrumentationScope {
+                    name: get_string(s, "name", "name")?,
+                    version: get_string(s, "version", "version")?,
+                    ..Default::default()
+                })
+            })
+            .transpose()?,
+        spans: objects(obj, "spans", "spans")?
+            .map(span)
+            .collect::<Result<_, _>>()?,
+        schema_url: get_string(obj, "schemaUrl", "schema_url")?,
+        ..Default::default()
+    })
+}
+
+fn span(obj: &Object) -> Result<Span, GenericError> {
+    Ok(Span {
+        trace_id: get_hex(obj, "traceId", "trace_id")?,
+        span_id: get_hex(obj, "spanId", "span_id")?,
+        trace_state: get_string(obj, "traceState", "trace_state")?,
+        parent_span_id: get_hex(obj, "parentSpanId", "parent_span_id")?,
+        name: get_string(obj, "name", "name")?,
+        kind: get_enum(obj, "kind", "kind", &SPAN_KINDS)?,
+        start_time_unix_nano: get_u64(obj, "startTimeUnixNano", "start_time_unix_nano")?,
+        end_time_unix_nano: get_u64(obj, "endTimeUnixNano", "end_time_unix_nano")?,
+        attributes: attributes(obj)?,
+        dropped_attributes_count: get_u32(
+            obj,
+            "droppedAttributesCount",
+            "dropped_attributes_count",
+        )?,
+        events: objects(obj, "events", "events")?
+            .map(|e| -> Result<_, GenericError> {
+                Ok(span::Event {
+                    time_unix_nano: get_u64(e, "timeUnixNano", "time_unix_nano")?,
+                    name: get_string(e, "name", "name")?,
+                    attributes: attributes(e)?,
+                    dropped_attributes_count: get_u32(
+                        e,
+                        "droppedAttributesCount",
+                        "dropped_attributes_count",
+                    )?,
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        dropped_events_count: get_u32(obj, "droppedEventsCount", "dropped_events_count")?,
+        links: objects(obj, "links", "links")?
+            .map(|l| -> Result<_, GenericError> {
+                Ok(span::Link {
+                    trace_id: get_hex(l, "traceId", "trace_id")?,
+                    span_id: get_hex(l, "spanId", "span_id")?,
+                    trace_state: get_string(l, "traceState", "trace_state")?,
+                    attributes: attributes(l)?,
+                    dropped_attributes_count: get_u32(
+                        l,
+                        "droppedAttributesCount",
+                        "dropped_attributes_count",
+                    )?,
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        dropped_links_count: get_u32(obj, "droppedLinksCount", "dropped_links_count")?,
+        status: object(obj, "status", "status")?
+            .map(|s| -> Result<_, GenericError> {
+                Ok(Status {
+                    message: get_string(s, "message", "message")?,
+                    code: get_enum(s, "code", "code", &STATUS_CODES)?,
+                    ..Default::default()
+                })
+            })
+            .transpose()?,
+        ..Default::default()
+    })
+}
+
+fn attributes(obj: &Object) -> Result<Vec<KeyValue>, GenericError> {
+    objects(obj, "attributes", "attributes")?
+        .map(key_value)
+        .collect()
+}
+
+fn key_value(obj: &Object) -> Result<KeyValue, GenericError> {
+    Ok(KeyValue {
+        key: get_string(obj, "key", "key")?,
+        value: object(obj, "value", "value")?.map(any_value).transpose()?,
+    })
+}
+
+fn any_value(obj: &Object) -> Result<AnyValue, GenericError> {
+    let value = if let Some(v) = field(obj, "stringValue", "string_value") {
+        any_value::Value::StringValue(as_string(v)?)
+    } else if let Some(v) = field(obj, "boolValue", "bool_value") {
+        any_value::Value::BoolValue(v.as_bool().ok_or("boolValue is not a bool")?)
+    } else if let Some(v) = field(obj, "intValue", "int_value") {
+        any_value::Value::IntValue(as_i64(v)?)
+    } else if let Some(v) = field(obj, "doubleValue", "double_value") {
+        any_value::Value::DoubleValue(as_f64(v)?)
+    } else if let Some(v) = field(obj, "bytesValue", "bytes_value") {
+        any_value::Value::BytesValue(base64::decode(as_string(v)?)?)
+    } else if let Some(a) = object(obj, "arrayValue", "array_value")? {
+        any_value::Value::ArrayValue(ArrayValue {
+            values: objects(a, "values", "values")?
+                .map(any_value)
+                .collect::<Result<_, _>>()?,
+        })
+    } else if let Some(kv) = object(obj, "kvlistValue", "kvlist_value")? {
+        any_value::Value::KvlistValue(KeyValueList {
+            values: objects(kv, "values", "values")?
+                .map(key_value)
+                .collect::<Result<_, _>>()?,
+        })
+    } else {
+        return Ok(AnyValue { value: None });
+    };
+    Ok(AnyValue { value: Some(value) })
+}
+
+fn field<'a>(obj: &'a Object, camel: &str, name: &str) -> Option<&'a Value> {
+    obj.get(camel)
+        .or_else(|| obj.get(name))
+        .filter(|v| !v.is_null())
+}
+
+fn object<'a>(
+    obj: &'a Object,
+    camel: &str,
+    name: &str,
+) -> Result<Option<&'a Object>, GenericError> {
+    match field(obj, camel, name) {
+        Some(Value::Object(o)) => Ok(Some(o)),
+        Some(_) => Err(format!("{} is not an object", camel).into()),
+        None => Ok(None),
+    }
+}
+
+fn objects<'a>(
+    obj: &'a Object,
+    camel: &str,
+    name: &str,
+) -> Result<impl Iterator<Item = &'a Object>, GenericError> {
+    let values = match field(obj, camel, name) {
+        Some(Value::Array(a)) => a.as_slice(),
+        Some(_) => return Err(format!("{} is not an array", camel).into()),
+        None => &[],
+    };
+    if values.iter().any(|v| !v.is_object()) {
+        return Err(format!("{} is not an array of objects", camel).into());
+    }
+    Ok(values.iter().filter_map(|v| v.as_object()))
+}
+
+fn as_string(v: &Value) -> Result<String, GenericError> {
+    v.as_str()
+        .map(|s| s.to_owned())
+        .ok_or_else(|| format!("{} is not a string", v).into())
+}
+
+// 64 bit integers are strings in the protobuf JSON mapping, numbers are accepted as well
+fn as_u64(v: &Value) -> Result<u64, GenericError> {
+    match v {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("{} is not an unsigned integer", v).into())
+}
+
+fn as_i64(v: &Value) -> Result<i64, GenericError> {
+    match v {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("{} is not an integer", v).into())
+}
+
+fn as_f64(v: &Value) -> Result<f64, GenericError> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            s => s.parse().ok(),
+        },
+        _ => None,
+    }
+    .ok_or_else(|| format!("{} is not a number", v).into())
+}
+
+fn get_string(obj: &Object, camel: &str, name: &str) -> Result<String, GenericError> {
+    field(obj, camel, name)
+        .map(as_string)
+        .unwrap_or_else(|| Ok(String::new()))
+}
+
+fn get_u64(obj: &Object, camel: &str, name: &str) -> Result<u64, GenericError> {
+    field(obj, camel, name).map(as_u64).unwrap_or(Ok(0))
+}
+
+fn get_u32(obj: &Object, camel: &str, name: &str) -> Result<u32, GenericError> {
+    Ok(get_u64(obj, camel, name)?.try_into()?)
+}
+
+fn get_hex(obj: &Object, camel: &str, name: &str) -> Result<Vec<u8>, GenericError> {
+    Ok(hex::decode(get_string(obj, camel, name)?)?)
+}
+
+fn get_enum(obj: &Object, camel: &str, name: &str, names: &[&str]) -> Result<i32, GenericError> {
+    match field(obj, camel, name) {
+        Some(Value::String(s)) => names
+            .iter()
+            .position(|n| n == s)
+            .map(|p| p as i32)
+            .ok_or_else(|| format!("unknown {} {}", camel, s).into()),
+        Some(v) => Ok(as_i64(v)?.try_into()?),
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use public::proto::integration::opentelemetry::proto::common::v1::any_value::Value::{
+        IntValue, StringValue,
+    };
+
+    #[test]
+    fn decode_otlp_json() {
+        let data = r#"{
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [{"key": "service.name", "value": {"stringValue": "svc"}}]
+                },
+                "scopeSpans": [{
+                    "scope": {"name": "lib", "version": "1.0"},
+                    "spans": [{
+                        "traceId": "5b8efff798038103d269b633813fc60c",
+                        "spanId": "eee19b7ec3c1b174",
+                        "parentSpanId": "",
+                        "name": "GET /api",
+                        "kind": 2,
+                        "startTimeUnixNano": "1544712660000000000",
+                        "endTimeUnixNano": 1544712661000000000,
+                        "attributes": [{"key": "http.status_code", "value": {"intValue": "200"}}],
+                        "status": {"code": "STATUS_CODE_ERROR"}
+                    }]
+                }]
+            }]
+        }"#;
+        let traces = decode_traces_data(data.as_bytes()).unwrap();
+        let resource_spans = &traces.resource_spans[0];
+        assert_eq!(
+            resource_spans.resource.as_ref().unwrap().attributes[0]
+                .value
+                .as_ref()
+                .unwrap()
+                .value,
+            Some(StringValue("svc".to_string()))
+        );
+        let scope_spans = &resource_spans.scope_spans[0];
+        assert_eq!(scope_spans.scope.as_ref().unwrap().name, "lib");
+        let span = &scope_spans.spans[0];
+        assert_eq!(
+            span.trace_id,
+            hex::decode("5b8efff798038103d269b633813fc60c").unwrap()
+        );
+        assert_eq!(span.span_id, hex::decode("eee19b7ec3c1b174").unwrap());
+        assert!(span.parent_span_id.is_empty());
+        assert_eq!(span.kind, 2);
+        assert_eq!(span.start_time_unix_nano, 1544712660000000000);
+        assert_eq!(span.end_time_unix_nano, 1544712661000000000);
+        assert_eq!(
+            span.attributes[0].value.as_ref().unwrap().value,
+            Some(IntValue(200))
+        );
+        assert_eq!(span.status.as_ref().unwrap().code, 2);
+
+        assert!(decode_traces_data(br#"{"resourceSpans": {}}"#).is_err());
+        assert!(decode_traces_data(b"[]").is_err());
+    }
+}
@@ -0,0 +1,619 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::{debug, info, warn};
+
+use super::{send_prometheus_series, BoxedPrometheusExtra};
+use crate::{
+    config::StatsdConfig,
+    sender::remote_write_sender::{Label, Sample, TimeSeries},
+};
+
+use public::{
+    counter::{Counter, CounterType, CounterValue, OwnedCountable},
+    queue::DebugSender,
+    utils::net::ipv6_enabled,
+};
+
+const RECV_BUFFER_SIZE: usize = 65535;
+const RECV_TIMEOUT: Duration = Duration::from_millis(500);
+const BIND_MAX_TRIES: usize = 3;
+// values kept for quantiles of a timer in a flush interval
+const MAX_TIMER_VALUES: usize = 10000;
+const QUANTILES: [(f64, &str); 3] = [(0.5, "0.5"), (0.9, "0.9"), (0.99, "0.99")];
+
+#[derive(Default)]
+struct StatsdStats {
+    packets: AtomicU64,
+    metrics: AtomicU64,
+    invalid_metrics: AtomicU64,
+    dropped_metrics: AtomicU64,
+    series: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct StatsdCounter {
+    stats: Arc<StatsdStats>,
+}
+
+impl OwnedCountable for StatsdCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "packets",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.stats.packets.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "metrics",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.stats.metrics.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "invalid-metrics",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.stats.invalid_metrics.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped-metrics",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.stats.dropped_metrics.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "series",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.stats.series.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MetricKind {
+    Counter,
+    Gauge,
+    // ms, h and d are all aggregated as timers
+    Timer,
+    Set,
+}
+
+#[derive(Debug, PartialEq)]
+struct StatsdMetric<'a> {
+    name: String,
+    // a dogstatsd packet can carry multiple values, e.g. name:1:2:3|h
+    values: Vec<&'a str>,
+    kind: MetricKind,
+    sample_rate: f64,
+    // sorted by name
+    tags: Vec<(String, String)>,
+}
+
+// replaces characters not allowed in prometheus names with '_'
+fn sanitize_name(name: &str) -> String {
+    let mut s: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if s.starts_with(|c: char| c.is_ascii_digit()) {
+        s.insert(0, '_');
+    }
+    s
+}
+
+// Parses a line in statsd or dogstatsd format:
+//     <name>:<value>[:<value>...]|<type>[|@<sample_rate>][|#<tag>:<value>,...][|T<timestamp>][|c:<container>]
+// Events (_e) and service checks (_sc) are not supported.
+// Tags without value are ignored as prometheus labels have to be key value pairs.
+fn parse_line(line: &str) -> Option<StatsdMetric<'_>> {
+    if line.starts_with("_e{") || line.starts_with("_sc|") {
+        return None;
+    }
+    let mut fields = line.split('|');
+    let (name, values) = fields.next()?.split_once(':')?;
+    if name.is_empty() {
+        return None;
+    }
+    let values = values.split(':').collect::<Vec<_>>();
+    let kind = match fields.next()? {
+        "c" => MetricKind::Counter,
+        "g" => MetricKind::Gauge,
+        "ms" | "h" | "d" => MetricKind::Timer,
+        "s" => MetricKind::Set,
+        _ => return None,
+    };
+    if values.iter().any(|v| v.is_empty()) {
+        return None;
+    }
+    if kind != MetricKind::Set && values.iter().any(|v| v.parse::<f64>().is_err()) {
+        return None;
+    }
+
+    let mut sample_rate = 1.0;
+    let mut tags = vec![];
+    for field in fields {
+        if let Some(rate) = field.strip_prefix('@') {
+            sample_rate = rate.parse::<f64>().ok()?;
+            if sample_rate <= 0.0 || sample_rate > 1.0 {
+                return None;
+            }
+        } else if let Some(t) = field.strip_prefix('#') {
+            for tag in t.split(',') {
+                match tag.split_once(':') {
+                    Some((k, v)) if !k.is_empty() && !v.is_empty() => {
+                        tags.push((sanitize_name(k), v.to_owned()))
+                    }
+                    _ => continue,
+                }
+            }
+        }
+        // timestamp and container id are ignored
+    }
+    tags.sort();
+    tags.dedup_by(|a, b| a.0 == b.0);
+
+    Some(StatsdMetric {
+        name: sanitize_name(name),
+        values,
+        kind,
+        sample_rate,
+        tags,
+    })
+}
+
+#[derive(Debug)]
+enum Aggregate {
+    Counter(f64),
+    Gauge(f64),
+    Timer {
+        count: f64,
+        sum: f64,
+        values: Vec<f64>,
+    },
+    Set(HashSet<String>),
+}
+
+impl Aggregate {
+    fn new(kind: MetricKind) -> Self {
+        match kind {
+            MetricKind::Counter => Aggregate::Counter(0.0),
+            MetricKind::Gauge => Aggregate::Gauge(0.0),
+            MetricKind::Timer => Aggregate::Timer {
+                count: 0.0,
+                sum: 0.0,
+                values: vec![],
+            },
+            MetricKind::Set => Aggregate::Set(HashSet::new()),
+        }
+    }
+
+    fn kind(&self) -> MetricKind {
+        match self {
+            Aggregate::Counter(_) => MetricKind::Counter,
+            Aggregate::Gauge(_) => MetricKind::Gauge,
+            Aggregate::Timer { .. } => MetricKind::Timer,
+            Aggregate::Set(_) => MetricKind::Set,
+        }
+    }
+
+    fn add(&mut self, value: &str, sample_rate: f64) {
+        match self {
+            Aggregate::Counter(c) => *c += value.parse::<f64>().unwrap() / sample_rate,
+            // values with sign are relative to the current value, as statsd does
+            Aggregate::Gauge(g) if value.starts_with(['+', '-']) => {
+                *g += value.parse::<f64>().unwrap()
+            }
+            Aggregate::Gauge(g) => *g = value.parse::<f64>().unwrap(),
+            Aggregate::Timer { count, sum, values } => {
+                let v = value.parse::<f64>().unwrap();
+                *count += 1.0 / sample_rate;
+                *sum += v / sample_rate;
+                if values.len() < MAX_TIMER_VALUES {
+                    values.push(v);
+                }
+            }
+            Aggregate::Set(s) => {
+                if !s.contains(value) {
+                    s.insert(value.to_owned());
+                }
+            }
+        }
+    }
+}
+
+// Aggregates metrics by name and tags in a flush interval
+struct Aggregator {
+    series: HashMap<(String, Vec<(String, String)>), Aggregate>,
+    max_series: usize,
+}
+
+impl Aggregator {
+    fn new(max_series: usize) -> Self {
+        Self {
+            series: HashMap::new(),
+            max_series,
+        }
+    }
+
+    fn add(&mut self, metric: StatsdMetric, stats: &StatsdStats) {
+        let len = self.series.len();
+        let aggr = match self.series.entry((metric.name, metric.tags)) {
+            Entry::Occupied(o) if o.get().kind() != metric.kind => {
+                debug!(
+                    "statsd metric {} type changed from {:?} to {:?}",
+                    o.key().0,
+                    o.get().kind(),
+                    metric.kind
+                );
+                stats.invalid_metrics.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(_) if len >= self.max_series => {
+                stats.dropped_metrics.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            Entry::Vacant(v) => v.insert(Aggregate::new(metric.kind)),
+        };
+        for value in metric.values {
+            aggr.add(value, metric.sample_rate);
+        }
+        stats.metrics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Counters are reported as the increase in the interval, timers as summaries
+    // and sets as the number of unique values.
+    fn flush(&mut self, timestamp: i64) -> Vec<TimeSeries> {
+        let mut series = vec![];
+        for ((name, tags), aggr) in self.series.drain() {
+            match aggr {
+                Aggregate::Counter(v) | Aggregate::Gauge(v) => {
+                    series.push(new_series(&name, &tags, None, v, timestamp))
+                }
+                Aggregate::Set(s) => {
+                    series.push(new_series(&name, &tags, None, s.len() as f64, timestamp))
+                }
+                Aggregate::Timer {
+                    count,
+                    sum,
+                    mut values,
+                } => {
+                    values.sort_by(|a, b| a.total_cmp(b));
+                    for (q, label) in QUANTILES {
+                        let index = ((values.len() as f64 * q).ceil() as usize).max(1) - 1;
+                        series.push(new_series(
+                            &name,
+                            &tags,
+                            Some(label),
+                            values[index],
+                            timestamp,
+                        ));
+                    }
+                    series.push(new_series(
+                        &format!("{}_sum", name),
+                        &tags,
+                        None,
+                        sum,
+                        timestamp,
+                    ));
+                    series.push(new_series(
+                        &format!("{}_count", name),
+                        &tags,
+                        None,
+                        count,
+                        timestamp,
+                    ));
+                }
+            }
+        }
+        series
+    }
+}
+
+fn new_series(
+    name: &str,
+    tags: &[(String, String)],
+    quantile: Option<&str>,
+    value: f64,
+    timestamp: i64,
+) -> TimeSeries {
+    let mut labels = vec![Label {
+        name: "__name__".to_owned(),
+        value: name.to_owned(),
+    }];
+    if let Some(q) = quantile {
+        labels.push(Label {
+            name: "quantile".to_owned(),
+            value: q.to_owned(),
+        });
+    }
+    for (k, v) in tags {
+        if k == "quantile" && quantile.is_some() {
+            continue;
+        }
+        labels.push(Label {
+            name: k.clone(),
+            value: v.clone(),
+        });
+    }
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+    TimeSeries {
+        labels,
+        samples: vec![Sample { value, timestamp }],
+    }
+}
+
+/// Listens on a UDP port for StatsD/DogStatsD metrics, aggregates them in flush intervals
+/// and sends them to Sender as prometheus remote write data
+pub struct StatsdServer {
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    config: StatsdConfig,
+    sender: DebugSender<BoxedPrometheusExtra>,
+    stats: Arc<StatsdStats>,
+    external_metric_integration_disabled: bool,
+}
+
+impl StatsdServer {
+    pub fn new(
+        config: StatsdConfig,
+        sender: DebugSender<BoxedPrometheusExtra>,
+        external_metric_integration_disabled: bool,
+    ) -> (Self, StatsdCounter) {
+        let counter = StatsdCounter::default();
+        (
+            Self {
+                running: Default::default(),
+                thread: Mutex::new(None),
+                config,
+                sender,
+                stats: counter.stats.clone(),
+                external_metric_integration_disabled,
+            },
+            counter,
+        )
+    }
+
+    pub fn start(&self) {
+        if !self.config.enabled || self.external_metric_integration_disabled {
+            return;
+        }
+        if self.running.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let running = self.running.clone();
+        let config = self.config.clone();
+        let sender = self.sender.clone();
+        let stats = self.stats.clone();
+        let handle = thread::Builder::new()
+            .name("statsd-server".to_owned())
+            .spawn(move || {
+                let Some(socket) = Self::bind(config.listen_port, &running) else {
+                    running.store(false, Ordering::Relaxed);
+                    return;
+                };
+                info!("statsd server listening on udp port {}", config.listen_port);
+                Self::run(socket, &config, &running, &sender, &stats);
+            })
+            .unwrap();
+        self.thread.lock().unwrap().replace(handle);
+    }
+
+    pub fn stop(&self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if let Some(handle) = self.thread.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+        info!("statsd server stopped");
+    }
+
+    fn bind(port: u16, running: &AtomicBool) -> Option<UdpSocket> {
+        let addr: SocketAddr = if ipv6_enabled() {
+            (Ipv6Addr::UNSPECIFIED, port).into()
+        } else {
+            (Ipv4Addr::UNSPECIFIED, port).into()
+        };
+        for tries in 1..=BIND_MAX_TRIES {
+            if !running.load(Ordering::Relaxed) {
+                return None;
+            }
+            match UdpSocket::bind(addr) {
+                Ok(socket) => {
+                    if let Err(e) = socket.set_read_timeout(Some(RECV_TIMEOUT)) {
+                        warn!("set statsd socket read timeout failed: {}", e);
+                        return None;
+                    }
+                    return Some(socket);
+                }
+                // the port may not be released yet if the server restarted just now
+                Err(e) if tries < BIND_MAX_TRIES => {
+                    debug!("bind statsd server on {} failed: {}", addr, e)
+                }
+                Err(e) => warn!("bind statsd server on {} failed: {}", addr, e),
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+        None
+    }
+
+    fn run(
+        socket: UdpSocket,
+        config: &StatsdConfig,
+        running: &AtomicBool,
+        sender: &DebugSender<BoxedPrometheusExtra>,
+        stats: &StatsdStats,
+    ) {
+        let mut aggregator = Aggregator::new(config.max_series);
+        let mut buffer = vec![0u8; RECV_BUFFER_SIZE];
+        let mut next_flush = Instant::now() + config.flush_interval;
+        loop {
+            let running = running.load(Ordering::Relaxed);
+            match socket.recv(&mut buffer) {
+                Ok(n) => {
+                    stats.packets.fetch_add(1, Ordering::Relaxed);
+                    for line in String::from_utf8_lossy(&buffer[..n]).lines() {
+                        let line = line.trim();
+                        if line.is_empty() {
+                            continue;
+                        }
+                        match parse_line(line) {
+                            Some(metric) => aggregator.add(metric, stats),
+                            None => {
+                                debug!("invalid statsd metric: {}", line);
+                                stats.invalid_metrics.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(e) => warn!("statsd server receive failed: {}", e),
+            }
+
+            // flush what is left before exiting
+            if !running || Instant::now() >= next_flush {
+                next_flush += config.flush_interval;
+                let timestamp = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as i64;
+                let series = aggregator.flush(timestamp);
+                if !series.is_empty() {
+                    stats
+                        .series
+                        .fetch_add(series.len() as u64, Ordering::Relaxed);
+                    send_prometheus_series(sender, series);
+                }
+            }
+            if !running {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label<'a>(s: &'a TimeSeries, name: &str) -> Option<&'a str> {
+        s.labels
+            .iter()
+            .find(|l| l.name == name)
+            .map(|l| l.value.as_str())
+    }
+
+    #[test]
+    fn parse_statsd_line() {
+        assert_eq!(
+            parse_line("page.views:1|c|@0.5|#env:prod,region:us-east-1,standalone"),
+            Some(StatsdMetric {
+                name: "page_views".to_owned(),
+                values: vec!["1"],
+                kind: MetricKind::Counter,
+                sample_rate: 0.5,
+                tags: vec![
+                    ("env".to_owned(), "prod".to_owned()),
+                    ("region".to_owned(), "us-east-1".to_owned()),
+                ],
+            })
+        );
+        assert_eq!(
+            parse_line("latency:3:5:7|h|T1656581400|c:83c0a99c0a54").map(|m| m.values),
+            Some(vec!["3", "5", "7"])
+        );
+        assert_eq!(
+            parse_line("users.online:-5|g").map(|m| m.kind),
+            Some(MetricKind::Gauge)
+        );
+        assert!(parse_line("no_type:1").is_none());
+        assert!(parse_line("bad_value:abc|c").is_none());
+        assert!(parse_line("bad_rate:1|c|@2").is_none());
+        assert!(parse_line("_e{5,4}:title|text").is_none());
+        assert!(parse_line("_sc|service.check|0").is_none());
+    }
+
+    #[test]
+    fn aggregate_statsd_metrics() {
+        let stats = StatsdStats::default();
+        let mut aggregator = Aggregator::new(4);
+        for line in [
+            "requests:1|c|@0.1|#code:200",
+            "requests:2|c|#code:200",
+            "requests:1|c|#code:500",
+            "temperature:20|g",
+            "temperature:+5|g",
+            "users:alice|s",
+            "users:bob|s",
+            "users:alice|s",
+            "dropped:1|c",
+            "requests:1|g|#code:200",
+        ] {
+            aggregator.add(parse_line(line).unwrap(), &stats);
+        }
+        assert_eq!(stats.metrics.load(Ordering::Relaxed), 8);
+        assert_eq!(stats.dropped_metrics.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.invalid_metrics.load(Ordering::Relaxed), 1);
+
+        let series = aggregator.flush(1000);
+        assert_eq!(series.len(), 4);
+        let find = |name: &str, code: Option<&str>| {
+            series
+                .iter()
+                .find(|s| label(s, "__name__") == Some(name) && label(s, "code") == code)
+                .map(|s| s.samples[0].value)
+        };
+        assert_eq!(find("requests", Some("200")), Some(12.0));
+        assert_eq!(find("requests", Some("500")), Some(1.0));
+        assert_eq!(find("temperature", None), Some(25.0));
+        assert_eq!(find("users", None), Some(2.0));
+        assert!(series.iter().all(|s| s.samples[0].timestamp == 1000));
+        assert!(aggregator.flush(2000).is_empty());
+
+        let mut aggregator = Aggregator::new(100);
+        for i in 1..=100 {
+            aggregator.add(
+                parse_line(&format!("latency:{}|ms|#quantile:x", i)).unwrap(),
+                &stats,
+            );
+        }
+        let series = aggregator.flush(1000);
+        assert_eq!(series.len(), QUANTILES.len() + 2);
+        let find = |name: &str, quantile: Option<&str>| {
+            series
+                .iter()
+                .find(|s| label(s, "__name__") == Some(name) && label(s, "quantile") == quantile)
+                .map(|s| s.samples[0].value)
+        };
+        assert_eq!(find("latency", Some("0.5")), Some(50.0));
+        assert_eq!(find("latency", Some("0.99")), Some(99.0));
+        assert_eq!(find("latency_sum", Some("x")), Some(5050.0));
+        assert_eq!(find("latency_count", Some("x")), Some(100.0));
+    }
+}
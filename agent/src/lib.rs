@@ -72,6 +72,10 @@ pub use {
         },
         FlowPerfCounter as _FlowPerfCounter, L7FlowPerf as _L7FlowPerf,
     },
+    flow_generator::pool::{
+        pooled_string as _pooled_string, recycle_string as _recycle_string,
+        string_pool_counter as _string_pool_counter,
+    },
     flow_generator::protocol_logs::LogMessageType as _LogMessageType,
     flow_generator::HttpLog,
     npb_pcap_policy::{
@@ -35,7 +35,7 @@ use public::{
 };
 
 const METRICS_VERSION: u32 = 20220117;
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Document {
     pub timestamp: u32,
     pub tagger: Tagger,
@@ -18,6 +18,8 @@ use std::mem::swap;
 
 use public::proto::metric;
 
+use crate::common::flow::{ApdexCounts, RrtHistogram};
+
 const FLOW_ID: u32 = 1;
 const USAGE_ID: u32 = 4;
 const APP_ID: u32 = 5;
@@ -55,6 +57,64 @@ impl Meter {
             Meter::Usage(m) => m.reverse(),
         }
     }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Meter::Flow(_) => "flow",
+            Meter::App(_) => "app",
+            Meter::Usage(_) => "usage",
+        }
+    }
+
+    // main values of the meter for exporters, latencies in microseconds
+    pub fn values(&self) -> Vec<(&'static str, u64)> {
+        match self {
+            Meter::Flow(m) => vec![
+                ("packet_tx", m.traffic.packet_tx),
+                ("packet_rx", m.traffic.packet_rx),
+                ("byte_tx", m.traffic.byte_tx),
+                ("byte_rx", m.traffic.byte_rx),
+                ("new_flow", m.traffic.new_flow),
+                ("closed_flow", m.traffic.closed_flow),
+                ("retrans_tx", m.performance.retrans_tx),
+                ("retrans_rx", m.performance.retrans_rx),
+                ("zero_win_tx", m.performance.zero_win_tx),
+                ("zero_win_rx", m.performance.zero_win_rx),
+                ("client_rst_flow", m.anomaly.client_rst_flow),
+                ("server_rst_flow", m.anomaly.server_rst_flow),
+                ("tcp_timeout", m.anomaly.tcp_timeout),
+                ("rtt_sum_us", m.latency.rtt_sum),
+                ("rtt_count", m.latency.rtt_count as u64),
+                ("rtt_max_us", m.latency.rtt_max as u64),
+                ("srt_sum_us", m.latency.srt_sum),
+                ("srt_count", m.latency.srt_count as u64),
+                ("srt_max_us", m.latency.srt_max as u64),
+                ("art_sum_us", m.latency.art_sum),
+                ("art_count", m.latency.art_count as u64),
+                ("art_max_us", m.latency.art_max as u64),
+            ],
+            Meter::App(m) => vec![
+                ("request", m.traffic.request as u64),
+                ("response", m.traffic.response as u64),
+                ("client_error", m.anomaly.client_error as u64),
+                ("server_error", m.anomaly.server_error as u64),
+                ("timeout", m.anomaly.timeout as u64),
+                ("rrt_sum_us", m.latency.rrt_sum),
+                ("rrt_count", m.latency.rrt_count as u64),
+                ("rrt_max_us", m.latency.rrt_max as u64),
+            ],
+            Meter::Usage(m) => vec![
+                ("packet_tx", m.packet_tx),
+                ("packet_rx", m.packet_rx),
+                ("byte_tx", m.byte_tx),
+                ("byte_rx", m.byte_rx),
+                ("l3_byte_tx", m.l3_byte_tx),
+                ("l3_byte_rx", m.l3_byte_rx),
+                ("l4_byte_tx", m.l4_byte_tx),
+                ("l4_byte_rx", m.l4_byte_rx),
+            ],
+        }
+    }
 }
 
 impl From<Meter> for metric::Meter {
@@ -206,6 +266,8 @@ pub struct Latency {
     pub rrt_max: u32,
     pub cit_max: u32, // us, the max time between the client request and the last server response (Payload > 1)
     pub tls_rtt_max: u32,
+    pub ack_rtt_client_max: u32,
+    pub ack_rtt_server_max: u32,
 
     pub rtt_sum: u64,
     pub rtt_client_sum: u64,
@@ -215,6 +277,8 @@ pub struct Latency {
     pub rrt_sum: u64,
     pub cit_sum: u64,
     pub tls_rtt_sum: u64,
+    pub ack_rtt_client_sum: u64,
+    pub ack_rtt_server_sum: u64,
 
     pub rtt_count: u32,
     pub rtt_client_count: u32,
@@ -224,6 +288,8 @@ pub struct Latency {
     pub rrt_count: u32,
     pub cit_count: u32,
     pub tls_rtt_count: u32,
+    pub ack_rtt_client_count: u32,
+    pub ack_rtt_server_count: u32,
 }
 
 impl Latency {
@@ -252,6 +318,12 @@ impl Latency {
         if self.tls_rtt_max < other.tls_rtt_max {
             self.tls_rtt_max = other.tls_rtt_max;
         }
+        if self.ack_rtt_client_max < other.ack_rtt_client_max {
+            self.ack_rtt_client_max = other.ack_rtt_client_max;
+        }
+        if self.ack_rtt_server_max < other.ack_rtt_server_max {
+            self.ack_rtt_server_max = other.ack_rtt_server_max;
+        }
 
         self.rtt_sum += other.rtt_sum;
         self.rtt_client_sum += other.rtt_client_sum;
@@ -261,6 +333,8 @@ impl Latency {
         self.rrt_sum += other.rrt_sum;
         self.cit_sum += other.cit_sum;
         self.tls_rtt_sum += other.tls_rtt_sum;
+        self.ack_rtt_client_sum += other.ack_rtt_client_sum;
+        self.ack_rtt_server_sum += other.ack_rtt_server_sum;
 
         self.rtt_count += other.rtt_count;
         self.rtt_client_count += other.rtt_client_count;
@@ -270,6 +344,8 @@ impl Latency {
         self.rrt_count += other.rrt_count;
         self.cit_count += other.cit_count;
         self.tls_rtt_count += other.tls_rtt_count;
+        self.ack_rtt_client_count += other.ack_rtt_client_count;
+        self.ack_rtt_server_count += other.ack_rtt_server_count;
     }
 }
 
@@ -299,6 +375,17 @@ impl From<Latency> for metric::Latency {
             art_count: m.art_count,
             rrt_count: m.rrt_count,
             cit_count: m.cit_count,
+
+            tls_rtt_max: m.tls_rtt_max,
+            tls_rtt_sum: m.tls_rtt_sum,
+            tls_rtt_count: m.tls_rtt_count,
+
+            ack_rtt_client_max: m.ack_rtt_client_max,
+            ack_rtt_server_max: m.ack_rtt_server_max,
+            ack_rtt_client_sum: m.ack_rtt_client_sum,
+            ack_rtt_server_sum: m.ack_rtt_server_sum,
+            ack_rtt_client_count: m.ack_rtt_client_count,
+            ack_rtt_server_count: m.ack_rtt_server_count,
         }
     }
 }
@@ -311,6 +398,8 @@ pub struct Performance {
     pub zero_win_rx: u64,
     pub retrans_syn: u32,
     pub retrans_synack: u32,
+    pub dup_ack_tx: u64,
+    pub dup_ack_rx: u64,
 }
 
 impl Performance {
@@ -321,6 +410,8 @@ impl Performance {
         self.zero_win_rx += other.zero_win_rx;
         self.retrans_syn += other.retrans_syn;
         self.retrans_synack += other.retrans_synack;
+        self.dup_ack_tx += other.dup_ack_tx;
+        self.dup_ack_rx += other.dup_ack_rx;
     }
 }
 
@@ -333,6 +424,8 @@ impl From<Performance> for metric::Performance {
             zero_win_rx: m.zero_win_rx,
             retrans_syn: m.retrans_syn,
             retrans_synack: m.retrans_synack,
+            dup_ack_tx: m.dup_ack_tx,
+            dup_ack_rx: m.dup_ack_rx,
         }
     }
 }
@@ -460,16 +553,22 @@ pub struct AppTraffic {
     pub request: u32,
     pub response: u32,
     pub direction_score: u8,
+    // bytes of flows recognized as the l7 protocol, sent by the client and the server
+    pub byte_tx: u64,
+    pub byte_rx: u64,
 }
 
 impl AppTraffic {
     pub fn sequential_merge(&mut self, other: &AppTraffic) {
         self.request += other.request;
         self.response += other.response;
-        self.direction_score = self.direction_score.max(other.direction_score)
+        self.direction_score = self.direction_score.max(other.direction_score);
+        self.byte_tx += other.byte_tx;
+        self.byte_rx += other.byte_rx;
     }
     pub fn reverse(&mut self) {
         swap(&mut self.request, &mut self.response);
+        swap(&mut self.byte_tx, &mut self.byte_rx);
         self.direction_score = 0;
     }
 }
@@ -480,6 +579,8 @@ impl From<AppTraffic> for metric::AppTraffic {
             request: m.request,
             response: m.response,
             direction_score: m.direction_score as u32,
+            byte_tx: m.byte_tx,
+            byte_rx: m.byte_rx,
         }
     }
 }
@@ -489,6 +590,8 @@ pub struct AppLatency {
     pub rrt_max: u32,
     pub rrt_sum: u64,
     pub rrt_count: u32,
+    pub rrt_histogram: RrtHistogram,
+    pub apdex: ApdexCounts,
 }
 
 impl AppLatency {
@@ -498,6 +601,8 @@ impl AppLatency {
         }
         self.rrt_sum += other.rrt_sum;
         self.rrt_count += other.rrt_count;
+        self.rrt_histogram.merge(&other.rrt_histogram);
+        self.apdex.merge(&other.apdex);
     }
 }
 
@@ -507,6 +612,12 @@ impl From<AppLatency> for metric::AppLatency {
             rrt_max: m.rrt_max,
             rrt_sum: m.rrt_sum,
             rrt_count: m.rrt_count,
+            rrt_histogram_start: m.rrt_histogram.start,
+            rrt_histogram_factor: m.rrt_histogram.factor,
+            rrt_histogram: m.rrt_histogram.counts().to_vec(),
+            apdex_satisfied: m.apdex.satisfied,
+            apdex_tolerating: m.apdex.tolerating,
+            apdex_frustrated: m.apdex.frustrated,
         }
     }
 }
@@ -761,6 +761,7 @@ impl ApiWatcher {
             list_limit: config.kubernetes_api_list_limit,
             list_interval: config.kubernetes_api_list_interval,
             max_memory: config.max_memory,
+            field_selector: None,
         };
 
         let (resource_watchers, task_handles) = loop {
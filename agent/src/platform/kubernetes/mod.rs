@@ -33,6 +33,9 @@ pub use passive_poller::PassivePoller;
 pub use sidecar_poller::SidecarPoller;
 
 mod resource_watcher;
+pub use resource_watcher::{
+    default_resources, GenericResourceWatcher, ResourceWatcherFactory, Watcher, WatcherConfig,
+};
 
 use public::netns::{self, InterfaceInfo, NsFile};
 
@@ -438,6 +438,17 @@ pub struct WatcherConfig {
     pub list_limit: u32,
     pub list_interval: Duration,
     pub max_memory: u64,
+    // e.g. spec.nodeName=node1, watches all resources if None
+    pub field_selector: Option<String>,
+}
+
+impl WatcherConfig {
+    fn list_params(&self) -> ListParams {
+        match self.field_selector.as_ref() {
+            Some(selector) => ListParams::default().fields(selector),
+            None => ListParams::default(),
+        }
+    }
 }
 
 // 发生错误，需要重新构造实例
@@ -549,7 +560,7 @@ where
             let mut stream = match ctx
                 .api
                 .watch(
-                    &ListParams::default(),
+                    &ctx.config.list_params(),
                     ctx.resource_version
                         .as_ref()
                         .map(|s| s as &str)
@@ -671,7 +682,7 @@ where
         let mut all_entries = HashMap::new();
         let mut total_count = 0;
         let mut total_bytes = 0;
-        let mut params = ListParams::default().limit(ctx.config.list_limit);
+        let mut params = ctx.config.list_params().limit(ctx.config.list_limit);
         loop {
             trace!("{} list with {:?}", ctx.kind, params);
             match ctx.api.list(&params).await {
@@ -27,9 +27,9 @@ pub use kubernetes::{ActivePoller, ApiWatcher, GenericPoller, Poller};
 #[cfg(target_os = "linux")]
 pub use libvirt_xml_extractor::LibvirtXmlExtractor;
 pub use platform_synchronizer::ProcRegRewrite;
-#[cfg(any(target_os = "linux", target_os = "android"))]
-pub use platform_synchronizer::SocketSynchronizer;
 pub use platform_synchronizer::{process_info_enabled, PlatformSynchronizer};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use platform_synchronizer::{ConntrackSynchronizer, SocketSynchronizer};
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct InterfaceEntry {
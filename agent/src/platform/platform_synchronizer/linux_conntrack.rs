@@ -0,0 +1,144 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use log::{debug, info, warn};
+
+use crate::{
+    common::policy::ConntrackEntry, config::handler::PlatformAccess, policy::PolicySetter,
+};
+
+// Reads NATed connections in the conntrack table of the node, which are used by policy to
+// fill the real client and server address of flows before and after NAT
+pub struct ConntrackSynchronizer {
+    config: PlatformAccess,
+    running: Arc<Mutex<bool>>,
+    stop_notify: Arc<Condvar>,
+    policy_setter: PolicySetter,
+}
+
+impl ConntrackSynchronizer {
+    pub fn new(config: PlatformAccess, policy_setter: PolicySetter) -> Self {
+        Self {
+            config,
+            running: Arc::new(Mutex::new(false)),
+            stop_notify: Arc::new(Condvar::new()),
+            policy_setter,
+        }
+    }
+
+    pub fn start(&self) {
+        let mut running_guard = self.running.lock().unwrap();
+        if *running_guard {
+            warn!("conntrack sync is running");
+            return;
+        }
+
+        let (running, config, stop_notify, policy_setter) = (
+            self.running.clone(),
+            self.config.clone(),
+            self.stop_notify.clone(),
+            self.policy_setter,
+        );
+        thread::Builder::new()
+            .name("conntrack-synchronizer".to_string())
+            .spawn(move || Self::run(running, config, stop_notify, policy_setter))
+            .unwrap();
+        *running_guard = true;
+
+        info!("conntrack sync start");
+    }
+
+    fn run(
+        running: Arc<Mutex<bool>>,
+        config: PlatformAccess,
+        stop_notify: Arc<Condvar>,
+        policy_setter: PolicySetter,
+    ) {
+        let mut last_entries: Vec<ConntrackEntry> = vec![];
+
+        loop {
+            let running_guard = running.lock().unwrap();
+            let (enabled, sync_interval, proc_root) = {
+                let conf_guard = config.load();
+                (
+                    conf_guard.conntrack_nat_enabled,
+                    conf_guard.conntrack_sync_interval,
+                    conf_guard.os_proc_scan_conf.os_proc_root.clone(),
+                )
+            };
+
+            let current_entries = if enabled {
+                let path = Path::new(&proc_root).join("net/nf_conntrack");
+                match get_nat_conntrack_entries(&path) {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        warn!("read conntrack table {} failed: {}", path.display(), e);
+                        vec![]
+                    }
+                }
+            } else {
+                vec![]
+            };
+            if current_entries != last_entries {
+                debug!("update {} conntrack entries", current_entries.len());
+                policy_setter.update_conntrack(&current_entries);
+                last_entries = current_entries;
+            }
+
+            if !Self::wait_timeout(running_guard, stop_notify.clone(), sync_interval) {
+                return;
+            }
+        }
+    }
+
+    pub fn stop(&self) {
+        let mut running_guard = self.running.lock().unwrap();
+        if !*running_guard {
+            warn!("conntrack sync not running");
+            return;
+        }
+        *running_guard = false;
+        self.stop_notify.notify_one();
+        info!("conntrack sync stop");
+    }
+
+    fn wait_timeout(guard: MutexGuard<bool>, stop_notify: Arc<Condvar>, timeout: Duration) -> bool {
+        *(stop_notify.wait_timeout(guard, timeout).unwrap().0)
+    }
+}
+
+// Lines of protocols other than tcp and udp or without NAT are skipped
+fn get_nat_conntrack_entries(path: &Path) -> io::Result<Vec<ConntrackEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = vec![];
+    for line in reader.lines() {
+        if let Ok(entry) = line?.parse::<ConntrackEntry>() {
+            if entry.is_nat() {
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
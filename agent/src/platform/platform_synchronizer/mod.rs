@@ -17,6 +17,8 @@
 #[cfg(any(target_os = "linux", target_os = "android"))]
 mod linux;
 #[cfg(any(target_os = "linux", target_os = "android"))]
+mod linux_conntrack;
+#[cfg(any(target_os = "linux", target_os = "android"))]
 mod linux_process;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 mod linux_socket;
@@ -41,6 +43,8 @@ pub use self::windows::*;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use linux::*;
 #[cfg(any(target_os = "linux", target_os = "android"))]
+pub use linux_conntrack::ConntrackSynchronizer;
+#[cfg(any(target_os = "linux", target_os = "android"))]
 pub use linux_process::*;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use linux_socket::*;
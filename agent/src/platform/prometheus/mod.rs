@@ -1 +1,2 @@
+pub mod scraper;
 pub mod targets;
@@ -0,0 +1,602 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    env,
+    io::Read,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::access::Access;
+use flate2::read::ZlibDecoder;
+use futures::future::join_all;
+use k8s_openapi::api::core::v1::Pod;
+use kube::{Client, Config};
+use log::{debug, info, warn};
+use tokio::{runtime::Runtime, task::JoinHandle};
+
+use crate::{
+    config::handler::PlatformAccess,
+    integration_collector::{send_prometheus_series, BoxedPrometheusExtra},
+    platform::kubernetes::{
+        default_resources, GenericResourceWatcher, ResourceWatcherFactory, Watcher, WatcherConfig,
+    },
+    sender::remote_write_sender::{Label, Sample, TimeSeries},
+    utils::{
+        environment::{get_k8s_local_node_ip, running_in_container},
+        stats::{self, Countable, Counter, CounterType, CounterValue, RefCountable, StatsOption},
+    },
+};
+
+use public::queue::DebugSender;
+
+const K8S_NODE_NAME_FOR_DEEPFLOW: &str = "K8S_NODE_NAME_FOR_DEEPFLOW";
+
+const ANNOTATION_SCRAPE: &str = "prometheus.io/scrape";
+const ANNOTATION_PORT: &str = "prometheus.io/port";
+const ANNOTATION_PATH: &str = "prometheus.io/path";
+const ANNOTATION_SCHEME: &str = "prometheus.io/scheme";
+
+const DEFAULT_PORT: u16 = 80;
+const DEFAULT_PATH: &str = "/metrics";
+const JOB_NAME: &str = "kubernetes-pods";
+const ACCEPT_HEADER: &str = "text/plain;version=0.0.4;q=1,*/*;q=0.1";
+
+#[derive(Default)]
+pub struct ScraperCounter {
+    targets: AtomicU64,
+    scrapes: AtomicU64,
+    scrape_errors: AtomicU64,
+    samples: AtomicU64,
+    samples_dropped: AtomicU64,
+}
+
+impl RefCountable for ScraperCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "targets",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.targets.load(Ordering::Relaxed)),
+            ),
+            (
+                "scrapes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.scrapes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "scrape-errors",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.scrape_errors.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "samples",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.samples.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "samples-dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.samples_dropped.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    url: String,
+    // job, namespace, pod and instance
+    labels: Vec<(String, String)>,
+}
+
+impl Target {
+    fn from_pod(pod: &Pod) -> Option<Self> {
+        let annotations = pod.metadata.annotations.as_ref()?;
+        if annotations.get(ANNOTATION_SCRAPE).map(|s| s.as_str()) != Some("true") {
+            return None;
+        }
+        let pod_ip = pod.status.as_ref()?.pod_ip.as_ref()?;
+        let port = match annotations.get(ANNOTATION_PORT) {
+            Some(p) => match p.parse::<u16>() {
+                Ok(p) => p,
+                Err(_) => {
+                    debug!(
+                        "pod {:?} has invalid {} annotation: {}",
+                        pod.metadata.name, ANNOTATION_PORT, p
+                    );
+                    return None;
+                }
+            },
+            None => DEFAULT_PORT,
+        };
+        let path = annotations
+            .get(ANNOTATION_PATH)
+            .map(|p| p.as_str())
+            .unwrap_or(DEFAULT_PATH);
+        let scheme = match annotations.get(ANNOTATION_SCHEME).map(|s| s.as_str()) {
+            Some("https") => "https",
+            _ => "http",
+        };
+        let instance = if pod_ip.contains(':') {
+            format!("[{}]:{}", pod_ip, port)
+        } else {
+            format!("{}:{}", pod_ip, port)
+        };
+        Some(Self {
+            url: format!(
+                "{}://{}{}{}",
+                scheme,
+                instance,
+                if path.starts_with('/') { "" } else { "/" },
+                path
+            ),
+            labels: vec![
+                ("instance".to_owned(), instance),
+                ("job".to_owned(), JOB_NAME.to_owned()),
+                (
+                    "namespace".to_owned(),
+                    pod.metadata.namespace.clone().unwrap_or_default(),
+                ),
+                (
+                    "pod".to_owned(),
+                    pod.metadata.name.clone().unwrap_or_default(),
+                ),
+            ],
+        })
+    }
+}
+
+struct Context {
+    config: PlatformAccess,
+    runtime: Arc<Runtime>,
+    sender: DebugSender<BoxedPrometheusExtra>,
+    counter: Arc<ScraperCounter>,
+    running: Arc<AtomicBool>,
+}
+
+// Scrapes prometheus metrics from pods on the local node according to their
+// prometheus.io/* annotations, and sends them as remote write requests
+pub struct PrometheusScraper {
+    context: Arc<Context>,
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+    stats_collector: Arc<stats::Collector>,
+}
+
+impl PrometheusScraper {
+    pub fn new(
+        runtime: Arc<Runtime>,
+        config: PlatformAccess,
+        sender: DebugSender<BoxedPrometheusExtra>,
+        stats_collector: Arc<stats::Collector>,
+    ) -> Self {
+        let counter = Arc::new(ScraperCounter::default());
+        stats_collector.register_countable(
+            "prometheus_scraper",
+            Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+            vec![StatsOption::Tag("kind", "pod_annotation".to_string())],
+        );
+        Self {
+            context: Arc::new(Context {
+                config,
+                runtime,
+                sender,
+                counter,
+                running: Arc::new(AtomicBool::new(false)),
+            }),
+            thread: Mutex::new(None),
+            stats_collector,
+        }
+    }
+
+    pub fn start(&self) {
+        let config_guard = self.context.config.load();
+        if !config_guard.prometheus_scrape_config.enabled {
+            return;
+        }
+        if !running_in_container() {
+            info!("prometheus scraper only works in kubernetes");
+            return;
+        }
+        if self.context.running.swap(true, Ordering::Relaxed) {
+            info!("prometheus scraper has already running");
+            return;
+        }
+
+        let context = self.context.clone();
+        let stats_collector = self.stats_collector.clone();
+        let handle = thread::Builder::new()
+            .name("prometheus-scraper".to_owned())
+            .spawn(move || Self::run(context, stats_collector))
+            .unwrap();
+        self.thread.lock().unwrap().replace(handle);
+        info!("prometheus scraper is running");
+    }
+
+    pub fn stop(&self) {
+        if !self.context.running.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if let Some(handle) = self.thread.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+        info!("prometheus scraper is stopped");
+    }
+
+    fn new_pod_watcher(
+        context: &Context,
+        stats_collector: &stats::Collector,
+    ) -> Option<(GenericResourceWatcher, Option<JoinHandle<()>>)> {
+        let client = context.runtime.block_on(async {
+            let mut config = match Config::infer().await {
+                Ok(c) => c,
+                Err(e) => {
+                    warn!("failed to infer kubernetes config: {}", e);
+                    return None;
+                }
+            };
+            config.accept_invalid_certs = true;
+            match Client::try_from(config) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    warn!("failed to create kubernetes client: {}", e);
+                    None
+                }
+            }
+        })?;
+
+        let config_guard = context.config.load();
+        let mut resource = default_resources()
+            .into_iter()
+            .find(|r| r.name == "pods")
+            .unwrap();
+        resource.selected_gv = resource.group_versions.first().copied();
+        let watcher_config = WatcherConfig {
+            list_limit: config_guard.kubernetes_api_list_limit,
+            list_interval: config_guard.kubernetes_api_list_interval,
+            max_memory: config_guard.max_memory,
+            // pods on other nodes are filtered by host ip if node name is unknown
+            field_selector: env::var(K8S_NODE_NAME_FOR_DEEPFLOW)
+                .ok()
+                .filter(|n| !n.is_empty())
+                .map(|n| format!("spec.nodeName={}", n)),
+        };
+        let factory = ResourceWatcherFactory::new(client, context.runtime.handle().clone());
+        let watcher = factory.new_watcher(resource, None, stats_collector, &watcher_config)?;
+        let handle = watcher.start();
+        Some((watcher, handle))
+    }
+
+    fn run(context: Arc<Context>, stats_collector: Arc<stats::Collector>) {
+        let Some((watcher, handle)) = Self::new_pod_watcher(&context, &stats_collector) else {
+            context.running.store(false, Ordering::Relaxed);
+            return;
+        };
+        let node_ip = get_k8s_local_node_ip().map(|ip| ip.to_string());
+        let filter_by_host_ip = env::var(K8S_NODE_NAME_FOR_DEEPFLOW)
+            .map(|n| n.is_empty())
+            .unwrap_or(true);
+
+        let mut version = 0;
+        let mut targets = vec![];
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        while context.running.load(Ordering::Relaxed) {
+            let config = context.config.load().prometheus_scrape_config.clone();
+
+            if watcher.ready() && watcher.version() != version {
+                version = watcher.version();
+                targets = Self::targets(
+                    &watcher.entries(),
+                    node_ip.as_deref().filter(|_| filter_by_host_ip),
+                );
+                context
+                    .counter
+                    .targets
+                    .store(targets.len() as u64, Ordering::Relaxed);
+                debug!("prometheus scraper targets updated: {:?}", targets);
+            }
+
+            if !targets.is_empty() {
+                let timestamp = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as i64;
+                let results = context.runtime.block_on(join_all(
+                    targets
+                        .iter()
+                        .map(|t| Self::scrape(&client, &t.url, config.timeout)),
+                ));
+                let mut series = vec![];
+                for (target, result) in targets.iter().zip(results) {
+                    context.counter.scrapes.fetch_add(1, Ordering::Relaxed);
+                    let up = match result {
+                        Ok(text) => {
+                            let mut scraped = parse_exposition(&text, &target.labels, timestamp);
+                            if scraped.len() > config.sample_limit {
+                                // the whole scrape fails when sample limit exceeded, as prometheus does
+                                warn!(
+                                    "prometheus target {} exceeded sample limit {} with {} samples",
+                                    target.url,
+                                    config.sample_limit,
+                                    scraped.len()
+                                );
+                                context
+                                    .counter
+                                    .samples_dropped
+                                    .fetch_add(scraped.len() as u64, Ordering::Relaxed);
+                                context
+                                    .counter
+                                    .scrape_errors
+                                    .fetch_add(1, Ordering::Relaxed);
+                                0.0
+                            } else {
+                                context
+                                    .counter
+                                    .samples
+                                    .fetch_add(scraped.len() as u64, Ordering::Relaxed);
+                                series.append(&mut scraped);
+                                1.0
+                            }
+                        }
+                        Err(e) => {
+                            debug!("scrape prometheus target {} failed: {}", target.url, e);
+                            context
+                                .counter
+                                .scrape_errors
+                                .fetch_add(1, Ordering::Relaxed);
+                            0.0
+                        }
+                    };
+                    series.push(new_series("up", &target.labels, vec![], up, timestamp));
+                }
+                send_prometheus_series(&context.sender, series);
+            }
+
+            // sleep in small steps so that stop() returns in time
+            let mut remaining = config.interval;
+            while !remaining.is_zero() && context.running.load(Ordering::Relaxed) {
+                let step = remaining.min(Duration::from_secs(1));
+                thread::sleep(step);
+                remaining -= step;
+            }
+        }
+
+        if let Some(handle) = handle {
+            handle.abort();
+        }
+    }
+
+    fn targets(entries: &[Vec<u8>], host_ip: Option<&str>) -> Vec<Target> {
+        let mut targets = vec![];
+        for entry in entries {
+            let mut content = vec![];
+            if let Err(e) = ZlibDecoder::new(entry.as_slice()).read_to_end(&mut content) {
+                warn!("decompress pod entry failed: {}", e);
+                continue;
+            }
+            let pod: Pod = match serde_json::from_slice(&content) {
+                Ok(p) => p,
+                Err(e) => {
+                    warn!("deserialize pod entry failed: {}", e);
+                    continue;
+                }
+            };
+            if let Some(host_ip) = host_ip {
+                if pod.status.as_ref().and_then(|s| s.host_ip.as_deref()) != Some(host_ip) {
+                    continue;
+                }
+            }
+            if let Some(target) = Target::from_pod(&pod) {
+                targets.push(target);
+            }
+        }
+        targets.sort_by(|a, b| a.url.cmp(&b.url));
+        targets.dedup_by(|a, b| a.url == b.url);
+        targets
+    }
+
+    async fn scrape(
+        client: &reqwest::Client,
+        url: &str,
+        timeout: Duration,
+    ) -> Result<String, reqwest::Error> {
+        client
+            .get(url)
+            .header(reqwest::header::ACCEPT, ACCEPT_HEADER)
+            .timeout(timeout)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+    }
+}
+
+fn new_series(
+    name: &str,
+    target_labels: &[(String, String)],
+    mut labels: Vec<Label>,
+    value: f64,
+    timestamp: i64,
+) -> TimeSeries {
+    for (name, value) in target_labels {
+        labels.push(Label {
+            name: name.clone(),
+            value: value.clone(),
+        });
+    }
+    labels.push(Label {
+        name: "__name__".to_owned(),
+        value: name.to_owned(),
+    });
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+    TimeSeries {
+        labels,
+        samples: vec![Sample { value, timestamp }],
+    }
+}
+
+// Parses prometheus text exposition format, lines that can not be parsed are skipped.
+// Scraped labels conflicting with target labels are renamed to exported_<name>.
+fn parse_exposition(
+    text: &str,
+    target_labels: &[(String, String)],
+    default_timestamp: i64,
+) -> Vec<TimeSeries> {
+    let mut series = vec![];
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line) {
+            Some((name, mut labels, value, timestamp)) => {
+                for label in labels.iter_mut() {
+                    if label.name == "__name__"
+                        || target_labels.iter().any(|(n, _)| n == &label.name)
+                    {
+                        label.name = format!("exported_{}", label.name);
+                    }
+                }
+                series.push(new_series(
+                    name,
+                    target_labels,
+                    labels,
+                    value,
+                    timestamp.unwrap_or(default_timestamp),
+                ));
+            }
+            None => debug!("invalid prometheus exposition line: {}", line),
+        }
+    }
+    series
+}
+
+fn parse_line(line: &str) -> Option<(&str, Vec<Label>, f64, Option<i64>)> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_ascii_whitespace())
+        .unwrap_or(line.len());
+    let name = &line[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let mut rest = &line[name_end..];
+
+    let mut labels = vec![];
+    if let Some(s) = rest.strip_prefix('{') {
+        rest = s;
+        loop {
+            rest = rest.trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+            if let Some(s) = rest.strip_prefix('}') {
+                rest = s;
+                break;
+            }
+            let eq = rest.find('=')?;
+            let label_name = rest[..eq].trim();
+            rest = rest[eq + 1..].trim_start().strip_prefix('"')?;
+
+            let mut value = String::new();
+            let mut chars = rest.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (i, '"') => break i,
+                    (_, '\\') => match chars.next()?.1 {
+                        'n' => value.push('\n'),
+                        c => value.push(c),
+                    },
+                    (_, c) => value.push(c),
+                }
+            };
+            rest = &rest[end + 1..];
+            labels.push(Label {
+                name: label_name.to_owned(),
+                value,
+            });
+        }
+    }
+
+    let mut fields = rest.split_ascii_whitespace();
+    // rust parses NaN, +Inf and -Inf case insensitively
+    let value = fields.next()?.parse::<f64>().ok()?;
+    let timestamp = match fields.next() {
+        Some(ts) => Some(ts.parse::<i64>().ok()?),
+        None => None,
+    };
+    Some((name, labels, value, timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_prometheus_exposition() {
+        let text = r#"
+# HELP http_requests_total The total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+http_requests_total{method="post",code="400",} 3 1395066363000
+msdos_file_access_time_seconds{path="C:\\DIR\\FILE.TXT",error="Cannot find file:\n\"FILE.TXT\""} 1.458255915e9
+metric_without_labels +Inf
+conflicting{job="other"} NaN
+invalid{label="unterminated} 1
+"#;
+        let target_labels = vec![
+            ("instance".to_owned(), "10.0.0.1:8080".to_owned()),
+            ("job".to_owned(), JOB_NAME.to_owned()),
+        ];
+        let series = parse_exposition(text, &target_labels, 42);
+        assert_eq!(series.len(), 5);
+
+        let label = |s: &TimeSeries, name: &str| {
+            s.labels
+                .iter()
+                .find(|l| l.name == name)
+                .map(|l| l.value.clone())
+        };
+        assert_eq!(
+            label(&series[0], "__name__").as_deref(),
+            Some("http_requests_total")
+        );
+        assert_eq!(label(&series[1], "code").as_deref(), Some("400"));
+        assert_eq!(series[1].samples[0].value, 3.0);
+        assert_eq!(series[1].samples[0].timestamp, 1395066363000);
+        assert_eq!(
+            label(&series[2], "path").as_deref(),
+            Some(r"C:\DIR\FILE.TXT")
+        );
+        assert_eq!(
+            label(&series[2], "error").as_deref(),
+            Some("Cannot find file:\n\"FILE.TXT\"")
+        );
+        assert_eq!(series[2].samples[0].timestamp, 42);
+        assert_eq!(series[3].samples[0].value, f64::INFINITY);
+        assert!(series[4].samples[0].value.is_nan());
+        assert_eq!(label(&series[4], "job").as_deref(), Some(JOB_NAME));
+        assert_eq!(label(&series[4], "exported_job").as_deref(), Some("other"));
+        assert!(series
+            .iter()
+            .all(|s| s.labels.windows(2).all(|w| w[0].name < w[1].name)));
+    }
+}
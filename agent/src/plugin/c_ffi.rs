@@ -15,6 +15,10 @@
  */
 
 use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc,
+};
 
 use public::enums::IpProtocol;
 
@@ -340,7 +344,7 @@ pub type CheckPayloadCFunc = unsafe extern "C" fn(*const ParseCtx) -> CheckResul
 pub type ParsePayloadCFunc =
     unsafe extern "C" fn(*const ParseCtx, *mut ParseInfo, info_max_len: i32) -> ParseResult;
 
-#[derive(Clone, Eq)]
+#[derive(Clone)]
 pub struct SoPluginFunc {
     pub hash: String,
     pub name: String,
@@ -348,6 +352,8 @@ pub struct SoPluginFunc {
     // due to C can not return variable length data, use the consistent length `result_max_len` as length of ParseResult array
     // return < 0 indicate fail, >=0 assume success
     pub parse_payload: ParsePayloadCFunc,
+    // shared by all flow maps, reset when the plugin is loaded again
+    pub health: Arc<SoPluginHealth>,
 }
 
 impl PartialEq for SoPluginFunc {
@@ -356,6 +362,38 @@ impl PartialEq for SoPluginFunc {
     }
 }
 
+impl Eq for SoPluginFunc {}
+
+// a plugin returning malformed results this many times in a row is considered broken,
+// and skipped until it is loaded again
+pub const SO_PLUGIN_MAX_VIOLATION: u32 = 100;
+
+/*
+    the agent is built with `panic = 'abort'`, and a panic or a crash in plugin can not be recovered,
+    so only the results violating the abi are checked, which are the errors the agent can survive.
+*/
+#[derive(Debug, Default)]
+pub struct SoPluginHealth {
+    violation: AtomicU32,
+    disabled: AtomicBool,
+}
+
+impl SoPluginHealth {
+    pub fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    // return true if the plugin is disabled by this violation
+    pub fn on_violation(&self) -> bool {
+        let count = self.violation.fetch_add(1, Ordering::Relaxed) + 1;
+        count >= SO_PLUGIN_MAX_VIOLATION && !self.disabled.swap(true, Ordering::Relaxed)
+    }
+
+    pub fn on_success(&self) {
+        self.violation.store(0, Ordering::Relaxed);
+    }
+}
+
 pub fn c_str_to_string(s: &[u8]) -> Option<String> {
     s.iter()
         .position(|b| *b == 0)
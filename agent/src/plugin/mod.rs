@@ -15,6 +15,7 @@
  */
 
 pub mod c_ffi;
+pub mod script;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub mod shared_obj;
 pub mod wasm;
@@ -428,6 +429,18 @@ impl L7ProtocolInfoInterface for CustomInfo {
         false
     }
 
+    fn response_status(&self) -> L7ResponseStatus {
+        self.resp.status
+    }
+
+    fn request_len(&self) -> Option<u32> {
+        self.req_len
+    }
+
+    fn response_len(&self) -> Option<u32> {
+        self.resp_len
+    }
+
     fn need_merge(&self) -> bool {
         self.need_protocol_merge
     }
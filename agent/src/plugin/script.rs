@@ -0,0 +1,366 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// rhai scripts configured per protocol, run on the l7 log right before it is sent.
+// the log is exposed to the script as the object map `log`:
+//
+//   log.req_type, log.domain, log.resource, log.endpoint     string
+//   log.status, log.code                                     int, code may be ()
+//   log.exception, log.result, log.version                   string, version may be ()
+//   log.service_name                                         string or ()
+//   log.request_id                                           int or ()
+//   log.attributes                                           map of string
+//
+// modifications of `log` are written back, and the log is dropped if the script returns false.
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use public::l7_protocol::L7Protocol;
+use rhai::{module_resolvers::DummyModuleResolver, Dynamic, Engine, Map, Scope, AST};
+
+use crate::{
+    config::L7LogScripts,
+    flow_generator::protocol_logs::{
+        pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog},
+        L7ResponseStatus,
+    },
+};
+
+// Instant::now() is too expensive to be called on every operation
+const TIMEOUT_CHECK_INTERVAL: u64 = 64;
+
+pub struct ScriptHook {
+    config: L7LogScripts,
+    engine: Engine,
+    scripts: HashMap<L7Protocol, AST>,
+    timeout: Duration,
+    deadline: Rc<Cell<Instant>>,
+}
+
+impl ScriptHook {
+    pub fn new(config: &L7LogScripts) -> Self {
+        let deadline = Rc::new(Cell::new(Instant::now()));
+        let mut engine = Engine::new();
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.set_max_operations(config.max_operations);
+        let d = deadline.clone();
+        engine.on_progress(move |ops| {
+            if ops % TIMEOUT_CHECK_INTERVAL == 0 && Instant::now() > d.get() {
+                Some(Dynamic::UNIT)
+            } else {
+                None
+            }
+        });
+
+        let mut scripts = HashMap::new();
+        for (name, source) in config.scripts.iter() {
+            let protocol = L7Protocol::from(name.clone());
+            if protocol == L7Protocol::Unknown {
+                warn!("l7 log script of unknown protocol {} ignored", name);
+                continue;
+            }
+            match engine.compile(source) {
+                Ok(ast) => {
+                    scripts.insert(protocol, ast);
+                }
+                Err(e) => warn!("compile l7 log script of {} failed: {}", name, e),
+            }
+        }
+
+        Self {
+            config: config.clone(),
+            engine,
+            scripts,
+            timeout: config.timeout,
+            deadline,
+        }
+    }
+
+    pub fn update(&mut self, config: &L7LogScripts) {
+        if self.config != *config {
+            *self = Self::new(config);
+        }
+    }
+
+    pub fn contains(&self, protocol: L7Protocol) -> bool {
+        self.scripts.contains_key(&protocol)
+    }
+
+    // returns Ok(false) if the log should be dropped
+    // the log may be partially modified on error and should not be sent
+    pub fn run(&self, protocol: L7Protocol, log: &mut L7ProtocolSendLog) -> Result<bool, String> {
+        let Some(ast) = self.scripts.get(&protocol) else {
+            return Ok(true);
+        };
+        let mut scope = Scope::new();
+        scope.push("log", to_map(log));
+        self.deadline.set(Instant::now() + self.timeout);
+        let ret = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, ast)
+            .map_err(|e| e.to_string())?;
+        if let Ok(false) = ret.as_bool() {
+            return Ok(false);
+        }
+        let map = scope
+            .get_value::<Map>("log")
+            .ok_or_else(|| "log is not an object map".to_owned())?;
+        from_map(map, log)?;
+        Ok(true)
+    }
+}
+
+fn string_or_unit(s: Option<&String>) -> Dynamic {
+    s.map(|s| Dynamic::from(s.clone())).unwrap_or(Dynamic::UNIT)
+}
+
+fn to_map(log: &L7ProtocolSendLog) -> Map {
+    let mut m = Map::new();
+    m.insert("req_type".into(), log.req.req_type.clone().into());
+    m.insert("domain".into(), log.req.domain.clone().into());
+    m.insert("resource".into(), log.req.resource.clone().into());
+    m.insert("endpoint".into(), log.req.endpoint.clone().into());
+    m.insert("status".into(), (log.resp.status as u8 as i64).into());
+    m.insert(
+        "code".into(),
+        log.resp
+            .code
+            .map(|c| Dynamic::from(c as i64))
+            .unwrap_or(Dynamic::UNIT),
+    );
+    m.insert("exception".into(), log.resp.exception.clone().into());
+    m.insert("result".into(), log.resp.result.clone().into());
+    m.insert("version".into(), string_or_unit(log.version.as_ref()));
+
+    let ext = log.ext_info.as_ref();
+    m.insert(
+        "service_name".into(),
+        string_or_unit(ext.and_then(|e| e.service_name.as_ref())),
+    );
+    m.insert(
+        "request_id".into(),
+        ext.and_then(|e| e.request_id)
+            .map(|id| Dynamic::from(id as i64))
+            .unwrap_or(Dynamic::UNIT),
+    );
+    let mut attributes = Map::new();
+    if let Some(attrs) = ext.and_then(|e| e.attributes.as_ref()) {
+        for kv in attrs {
+            attributes.insert(kv.key.as_str().into(), kv.val.clone().into());
+        }
+    }
+    m.insert("attributes".into(), attributes.into());
+    m
+}
+
+fn take_string(m: &mut Map, key: &str) -> Result<Option<String>, String> {
+    match m.remove(key) {
+        Some(v) if !v.is_unit() => v
+            .into_string()
+            .map(Some)
+            .map_err(|t| format!("log.{} is {}, expected string", key, t)),
+        _ => Ok(None),
+    }
+}
+
+fn take_int(m: &mut Map, key: &str) -> Result<Option<i64>, String> {
+    match m.remove(key) {
+        Some(v) if !v.is_unit() => v
+            .as_int()
+            .map(Some)
+            .map_err(|t| format!("log.{} is {}, expected int", key, t)),
+        _ => Ok(None),
+    }
+}
+
+fn from_map(mut m: Map, log: &mut L7ProtocolSendLog) -> Result<(), String> {
+    log.req.req_type = take_string(&mut m, "req_type")?.unwrap_or_default();
+    log.req.domain = take_string(&mut m, "domain")?.unwrap_or_default();
+    log.req.resource = take_string(&mut m, "resource")?.unwrap_or_default();
+    log.req.endpoint = take_string(&mut m, "endpoint")?.unwrap_or_default();
+    if let Some(status) = take_int(&mut m, "status")? {
+        log.resp.status = u8::try_from(status)
+            .ok()
+            .and_then(|s| L7ResponseStatus::try_from(s).ok())
+            .ok_or_else(|| format!("invalid log.status {}", status))?;
+    }
+    log.resp.code = take_int(&mut m, "code")?.map(|c| c as i32);
+    log.resp.exception = take_string(&mut m, "exception")?.unwrap_or_default();
+    log.resp.result = take_string(&mut m, "result")?.unwrap_or_default();
+    log.version = take_string(&mut m, "version")?;
+
+    let service_name = take_string(&mut m, "service_name")?;
+    let request_id = take_int(&mut m, "request_id")?.map(|id| id as u32);
+    let attributes = match m.remove("attributes") {
+        Some(v) if !v.is_unit() => {
+            let type_name = v.type_name();
+            v.try_cast::<Map>()
+                .ok_or_else(|| format!("log.attributes is {}, expected map", type_name))?
+        }
+        _ => Map::new(),
+    };
+    if log.ext_info.is_none()
+        && service_name.is_none()
+        && request_id.is_none()
+        && attributes.is_empty()
+    {
+        return Ok(());
+    }
+    let ext = log.ext_info.get_or_insert_with(ExtendedInfo::default);
+    ext.service_name = service_name;
+    ext.request_id = request_id;
+
+    // keep the original attributes (order and duplicated keys) if untouched by the script
+    let origin = ext.attributes.take().unwrap_or_default();
+    let unchanged = origin.len() == attributes.len()
+        && origin.iter().all(|kv| {
+            attributes
+                .get(kv.key.as_str())
+                .map(|v| v.to_string() == kv.val)
+                .unwrap_or(false)
+        });
+    if unchanged {
+        ext.attributes = Some(origin).filter(|a| !a.is_empty());
+    } else if !attributes.is_empty() {
+        ext.attributes = Some(
+            attributes
+                .into_iter()
+                .map(|(k, v)| KeyVal {
+                    key: k.to_string(),
+                    val: v.to_string(),
+                })
+                .collect(),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::flow_generator::protocol_logs::pb_adapter::{L7Request, L7Response};
+
+    fn kafka_log(topic: &str) -> L7ProtocolSendLog {
+        L7ProtocolSendLog {
+            req: L7Request {
+                req_type: "Produce".to_owned(),
+                resource: topic.to_owned(),
+                ..Default::default()
+            },
+            resp: L7Response {
+                code: Some(0),
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(42),
+                attributes: Some(vec![KeyVal {
+                    key: "partition".to_owned(),
+                    val: "3".to_owned(),
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn new_hook(script: &str) -> ScriptHook {
+        let mut config = L7LogScripts::default();
+        config.scripts.insert("Kafka".to_owned(), script.to_owned());
+        ScriptHook::new(&config)
+    }
+
+    #[test]
+    fn test_transform() {
+        let hook = new_hook(
+            r#"
+            let parts = log.resource.split(".");
+            if parts.len() > 1 {
+                log.attributes.tenant = parts[0];
+            }
+            log.endpoint = "topic";
+            "#,
+        );
+        assert!(hook.contains(L7Protocol::Kafka));
+        assert!(!hook.contains(L7Protocol::Http1));
+
+        let mut log = kafka_log("acme.orders");
+        assert_eq!(hook.run(L7Protocol::Kafka, &mut log), Ok(true));
+        assert_eq!(log.req.req_type, "Produce");
+        assert_eq!(log.req.endpoint, "topic");
+        assert_eq!(log.resp.code, Some(0));
+        let ext = log.ext_info.unwrap();
+        assert_eq!(ext.request_id, Some(42));
+        let mut attrs = ext.attributes.unwrap();
+        attrs.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(
+            attrs,
+            vec![
+                KeyVal {
+                    key: "partition".to_owned(),
+                    val: "3".to_owned(),
+                },
+                KeyVal {
+                    key: "tenant".to_owned(),
+                    val: "acme".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drop() {
+        let hook = new_hook(r#"!log.resource.starts_with("__")"#);
+        let mut log = kafka_log("__consumer_offsets");
+        assert_eq!(hook.run(L7Protocol::Kafka, &mut log), Ok(false));
+        let mut log = kafka_log("orders");
+        assert_eq!(hook.run(L7Protocol::Kafka, &mut log), Ok(true));
+        assert_eq!(log.req.resource, "orders");
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut log = kafka_log("orders");
+        let hook = new_hook("loop {}");
+        assert!(hook.run(L7Protocol::Kafka, &mut log).is_err());
+
+        let mut config = L7LogScripts::default();
+        config.max_operations = u64::MAX;
+        config.timeout = Duration::from_millis(10);
+        config
+            .scripts
+            .insert("Kafka".to_owned(), "loop {}".to_owned());
+        let hook = ScriptHook::new(&config);
+        let start = Instant::now();
+        assert!(hook.run(L7Protocol::Kafka, &mut log).is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let hook = new_hook("log.status = 100;");
+        let mut log = kafka_log("orders");
+        assert!(hook.run(L7Protocol::Kafka, &mut log).is_err());
+        let hook = new_hook("this is not rhai");
+        assert!(!hook.contains(L7Protocol::Kafka));
+    }
+}
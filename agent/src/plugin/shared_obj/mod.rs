@@ -92,6 +92,7 @@ pub fn load_plugin(plugin: &[u8], name: &String) -> Result<SoPluginFunc, String>
         name: name.clone(),
         check_payload: check_func,
         parse_payload: parse_func,
+        health: Default::default(),
     })
 }
 
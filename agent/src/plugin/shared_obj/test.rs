@@ -43,6 +43,7 @@ use crate::{
 };
 
 use super::{load_plugin, SoPluginFunc};
+use crate::plugin::c_ffi::SO_PLUGIN_MAX_VIOLATION;
 
 fn get_plugin() -> SoPluginFunc {
     // the so source code lcoate in resources/test/plugins/so_plugin_test.c
@@ -143,6 +144,25 @@ fn test_check() {
     assert!(p.check_payload(&REQ_PAYLOAD, &param));
 }
 
+#[test]
+fn test_disabled() {
+    let plugin = get_plugin();
+    for _ in 1..SO_PLUGIN_MAX_VIOLATION {
+        assert!(!plugin.health.on_violation());
+    }
+    plugin.health.on_success();
+    for _ in 1..SO_PLUGIN_MAX_VIOLATION {
+        assert!(!plugin.health.on_violation());
+    }
+    assert!(plugin.health.on_violation());
+    assert!(plugin.health.is_disabled());
+
+    let rrt_cache = Rc::new(RefCell::new(L7PerfCache::new(100)));
+    let param = get_req_param(rrt_cache, Rc::new(vec![plugin]));
+    let mut p = SoLog::default();
+    assert!(!p.check_payload(&REQ_PAYLOAD, &param));
+}
+
 #[test]
 fn test_parse() {
     let attr = vec![
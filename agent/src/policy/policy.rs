@@ -14,7 +14,7 @@
  * limitations under the License.
  */
 
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, RwLock,
@@ -37,7 +37,8 @@ use crate::common::flow::{PacketDirection, SignalSource};
 use crate::common::lookup_key::LookupKey;
 use crate::common::platform_data::PlatformData;
 use crate::common::policy::{
-    gpid_key, Acl, Cidr, Container, GpidEntry, GpidProtocol, IpGroupData, PeerConnection,
+    gpid_key, Acl, Cidr, ConntrackEntry, Container, GpidEntry, GpidProtocol, IpGroupData,
+    PeerConnection,
 };
 use crate::common::MetaPacket;
 use crate::common::TapPort;
@@ -93,6 +94,8 @@ pub struct Policy {
     forward: Forward,
 
     nat: RwLock<Vec<AHashMap<u128, GpidEntry>>>,
+    // (protocol, src, dst) => (real src, real dst) of NATed connections in the conntrack table
+    conntrack: RwLock<AHashMap<(u8, SocketAddr, SocketAddr), (SocketAddr, SocketAddr)>>,
 
     queue_count: usize,
     first_hit: usize,
@@ -116,6 +119,7 @@ impl Policy {
             table: FirstPath::new(queue_count, level, map_size, fast_disable),
             forward: Forward::new(queue_count, forward_capacity),
             nat: RwLock::new(vec![AHashMap::new(), AHashMap::new()]),
+            conntrack: RwLock::new(AHashMap::new()),
             queue_count,
             first_hit: 0,
             fast_hit: 0,
@@ -279,6 +283,32 @@ impl Policy {
             packet.endpoint_data = Some(EndpointDataPov::new(endpoints));
             Self::fill_gpid_entry(packet, &gpid_entries);
         }
+        self.fill_conntrack_entry(packet);
+    }
+
+    // Packets before and after NAT get the same real client and server address from the
+    // conntrack table, so that flows of the same connection can be joined
+    fn fill_conntrack_entry(&self, packet: &mut MetaPacket) {
+        let conntrack = self.conntrack.read().unwrap();
+        if conntrack.is_empty() {
+            return;
+        }
+        let key = &mut packet.lookup_key;
+        let src = SocketAddr::new(key.src_ip, key.src_port);
+        let dst = SocketAddr::new(key.dst_ip, key.dst_port);
+        let Some((real_src, real_dst)) = conntrack.get(&(u8::from(key.proto), src, dst)) else {
+            return;
+        };
+        if *real_src != src && TapPort::NAT_SOURCE_CONNTRACK > key.src_nat_source {
+            key.src_nat_source = TapPort::NAT_SOURCE_CONNTRACK;
+            key.src_nat_ip = real_src.ip();
+            key.src_nat_port = real_src.port();
+        }
+        if *real_dst != dst && TapPort::NAT_SOURCE_CONNTRACK > key.dst_nat_source {
+            key.dst_nat_source = TapPort::NAT_SOURCE_CONNTRACK;
+            key.dst_nat_ip = real_dst.ip();
+            key.dst_nat_port = real_dst.port();
+        }
     }
 
     fn send(
@@ -492,6 +522,21 @@ impl Policy {
         *self.nat.write().unwrap() = table;
     }
 
+    pub fn update_conntrack(&mut self, entries: &Vec<ConntrackEntry>) {
+        let mut table = AHashMap::with_capacity(entries.len() << 2);
+        for entry in entries.iter().filter(|e| e.is_nat()) {
+            let protocol = u8::from(entry.protocol);
+            let (client, server) = entry.real_addrs();
+            let (original, reply) = (entry.original, entry.reply);
+            // packets of both directions before and after NAT
+            table.insert((protocol, original.0, original.1), (client, server));
+            table.insert((protocol, original.1, original.0), (server, client));
+            table.insert((protocol, reply.0, reply.1), (server, client));
+            table.insert((protocol, reply.1, reply.0), (client, server));
+        }
+        *self.conntrack.write().unwrap() = table;
+    }
+
     pub fn get_acls(&self) -> &Vec<Arc<Acl>> {
         return &self.acls;
     }
@@ -677,6 +722,10 @@ impl PolicySetter {
         self.policy().update_gpids(entrys);
     }
 
+    pub fn update_conntrack(&self, entries: &Vec<ConntrackEntry>) {
+        self.policy().update_conntrack(entries);
+    }
+
     pub fn set_memory_limit(&self, limit: u64) {
         self.policy().set_memory_limit(limit)
     }
@@ -742,4 +791,69 @@ mod test {
             assert_eq!(10, e.dst_info.l3_epc_id);
         }
     }
+
+    #[test]
+    fn test_policy_conntrack() {
+        let (setter, mut getter) = Policy::new(1, 0, 1024, 1024, false);
+        // client 10.1.0.2 visits service 10.96.0.10:53 with SNAT to node 192.168.0.1,
+        // and the real server is 10.1.0.9:53
+        let entry: ConntrackEntry = "ipv4 2 udp 17 29 src=10.1.0.2 dst=10.96.0.10 sport=43210 dport=53 src=10.1.0.9 dst=192.168.0.1 sport=53 dport=50000 mark=0 zone=0 use=2"
+            .parse()
+            .unwrap();
+        setter.update_conntrack(&vec![entry]);
+
+        let new_packet = |src: &str, dst: &str| {
+            let (src, dst) = (
+                src.parse::<SocketAddr>().unwrap(),
+                dst.parse::<SocketAddr>().unwrap(),
+            );
+            let mut packet = MetaPacket::default();
+            packet.lookup_key = LookupKey {
+                src_ip: src.ip(),
+                dst_ip: dst.ip(),
+                src_port: src.port(),
+                dst_port: dst.port(),
+                proto: IpProtocol::UDP,
+                ..Default::default()
+            };
+            packet
+        };
+        // addresses without NAT are not filled, same as FlowMap::init_nat_info()
+        let real_addrs = |packet: &MetaPacket| {
+            let key = &packet.lookup_key;
+            (
+                if key.src_nat_source == TapPort::NAT_SOURCE_CONNTRACK {
+                    SocketAddr::new(key.src_nat_ip, key.src_nat_port)
+                } else {
+                    SocketAddr::new(key.src_ip, key.src_port)
+                },
+                if key.dst_nat_source == TapPort::NAT_SOURCE_CONNTRACK {
+                    SocketAddr::new(key.dst_nat_ip, key.dst_nat_port)
+                } else {
+                    SocketAddr::new(key.dst_ip, key.dst_port)
+                },
+            )
+        };
+
+        // before NAT
+        let mut packet = new_packet("10.1.0.2:43210", "10.96.0.10:53");
+        getter.lookup(&mut packet, 0, 0);
+        assert_eq!(
+            real_addrs(&packet),
+            (
+                "10.1.0.2:43210".parse().unwrap(),
+                "10.1.0.9:53".parse().unwrap()
+            )
+        );
+        // reply after NAT
+        let mut packet = new_packet("10.1.0.9:53", "192.168.0.1:50000");
+        getter.lookup(&mut packet, 0, 0);
+        assert_eq!(
+            real_addrs(&packet),
+            (
+                "10.1.0.9:53".parse().unwrap(),
+                "10.1.0.2:43210".parse().unwrap()
+            )
+        );
+    }
 }
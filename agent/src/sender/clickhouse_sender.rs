@@ -0,0 +1,529 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Weak,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use arc_swap::access::Access;
+use chrono_tz::Tz;
+use clickhouse_rs::{types::Value, Block, Options, Pool};
+use log::{info, warn};
+use tokio::runtime::Runtime;
+
+use super::{remote_write_sender::document_labels, QUEUE_BATCH_SIZE};
+
+use crate::config::{handler::CollectorAccess, ClickhouseExporter};
+use crate::flow_generator::protocol_logs::{pb_adapter::L7ProtocolSendLog, BoxAppProtoLogsData};
+use crate::metric::document::BoxedDocument;
+use crate::utils::stats::{
+    Collector, Countable, Counter, CounterType, CounterValue, RefCountable, StatsOption,
+};
+use public::queue::{Error, Receiver};
+
+const METER_PLACEHOLDER: &str = "{meter}";
+// tags of all metrics tables, missing tags of a document are empty
+const METRICS_TAGS: [&str; 12] = [
+    "agent_id",
+    "signal_source",
+    "client_ip",
+    "server_ip",
+    "ip",
+    "protocol",
+    "server_port",
+    "tap_side",
+    "l7_protocol",
+    "endpoint",
+    "app_service",
+    "app_instance",
+];
+
+type Row = Vec<(&'static str, Value)>;
+
+// Data inserted by the clickhouse sender, rows of a table have the same columns
+pub trait ClickhouseRow: Debug + Send + 'static {
+    // tag of the sender counter
+    const TYPE: &'static str;
+
+    fn table(&self, exporter: &ClickhouseExporter) -> String;
+
+    fn to_row(self) -> Row;
+}
+
+fn time_value(seconds: u64) -> Value {
+    Value::DateTime(seconds as u32, Tz::UTC)
+}
+
+impl ClickhouseRow for BoxAppProtoLogsData {
+    const TYPE: &'static str = "l7_flow_log";
+
+    fn table(&self, exporter: &ClickhouseExporter) -> String {
+        exporter.l7_flow_log_table.clone()
+    }
+
+    fn to_row(self) -> Row {
+        let log = *self.0;
+        let base = log.base_info;
+        let send_log: L7ProtocolSendLog = match log.send_log {
+            Some(log) => log,
+            None => log.l7_info.into(),
+        };
+        let req = send_log.req;
+        let resp = send_log.resp;
+        let trace_info = send_log.trace_info.unwrap_or_default();
+        vec![
+            ("time", time_value(base.start_time.as_secs())),
+            ("start_time", Value::from(base.start_time.as_micros())),
+            ("end_time", Value::from(base.end_time.as_micros())),
+            ("agent_id", Value::from(base.vtap_id)),
+            ("flow_id", Value::from(base.flow_id)),
+            (
+                "signal_source",
+                Value::from(format!("{:?}", base.signal_source)),
+            ),
+            ("tap_side", Value::from(format!("{:?}", base.tap_side))),
+            ("protocol", Value::from(format!("{:?}", base.protocol))),
+            ("l7_protocol", Value::from(format!("{:?}", base.head.proto))),
+            ("client_ip", Value::from(base.ip_src.to_string())),
+            ("server_ip", Value::from(base.ip_dst.to_string())),
+            ("client_port", Value::from(base.port_src)),
+            ("server_port", Value::from(base.port_dst)),
+            ("process_id_0", Value::from(base.process_id_0)),
+            ("process_id_1", Value::from(base.process_id_1)),
+            ("process_kname_0", Value::from(base.process_kname_0)),
+            ("process_kname_1", Value::from(base.process_kname_1)),
+            ("request_type", Value::from(req.req_type)),
+            ("request_domain", Value::from(req.domain)),
+            ("request_resource", Value::from(req.resource)),
+            ("endpoint", Value::from(req.endpoint)),
+            ("response_status", Value::from(format!("{:?}", resp.status))),
+            ("response_code", Value::from(resp.code.unwrap_or_default())),
+            ("response_exception", Value::from(resp.exception)),
+            ("response_result", Value::from(resp.result)),
+            // microseconds
+            ("response_duration", Value::from(base.head.rrt)),
+            (
+                "trace_id",
+                Value::from(trace_info.trace_id.unwrap_or_default()),
+            ),
+            (
+                "span_id",
+                Value::from(trace_info.span_id.unwrap_or_default()),
+            ),
+            (
+                "parent_span_id",
+                Value::from(trace_info.parent_span_id.unwrap_or_default()),
+            ),
+        ]
+    }
+}
+
+impl ClickhouseRow for BoxedDocument {
+    const TYPE: &'static str = "metrics";
+
+    fn table(&self, exporter: &ClickhouseExporter) -> String {
+        exporter
+            .metrics_table
+            .replace(METER_PLACEHOLDER, self.0.meter.kind())
+    }
+
+    fn to_row(self) -> Row {
+        let doc = &self.0;
+        let mut tags: HashMap<&'static str, String> = document_labels(doc).into_iter().collect();
+        let mut row = vec![("time", time_value(doc.timestamp as u64))];
+        for name in METRICS_TAGS {
+            row.push((name, Value::from(tags.remove(name).unwrap_or_default())));
+        }
+        for (name, value) in doc.meter.values() {
+            row.push((name, Value::from(value)));
+        }
+        row
+    }
+}
+
+fn column_type(value: &Value) -> &'static str {
+    match value {
+        Value::UInt16(_) => "UInt16",
+        Value::UInt32(_) => "UInt32",
+        Value::UInt64(_) => "UInt64",
+        Value::Int32(_) => "Int32",
+        Value::DateTime(..) => "DateTime('UTC')",
+        _ => "String",
+    }
+}
+
+fn create_table_query(exporter: &ClickhouseExporter, table: &str, row: &Row) -> String {
+    let columns = row
+        .iter()
+        .map(|(name, value)| format!("{} {}", name, column_type(value)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    exporter
+        .create_table_template
+        .replace("{database}", &exporter.database)
+        .replace("{table}", table)
+        .replace("{columns}", &columns)
+        .replace(
+            "{ttl_hours}",
+            &(exporter.ttl.as_secs() / 3600).max(1).to_string(),
+        )
+}
+
+#[derive(Debug, Default)]
+pub struct ClickhouseSenderCounter {
+    pub rx: AtomicU64,
+    pub tx_rows: AtomicU64,
+    pub tx_inserts: AtomicU64,
+    pub dropped: AtomicU64,
+}
+
+impl RefCountable for ClickhouseSenderCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "rx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-rows",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_rows.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-inserts",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_inserts.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+pub struct ClickhouseSenderThread<T: ClickhouseRow> {
+    input: Arc<Receiver<T>>,
+    config: CollectorAccess,
+    stats: Arc<Collector>,
+    runtime: Arc<Runtime>,
+
+    thread_handle: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+}
+
+impl<T: ClickhouseRow> ClickhouseSenderThread<T> {
+    pub fn new(
+        input: Receiver<T>,
+        config: CollectorAccess,
+        stats: Arc<Collector>,
+        runtime: Arc<Runtime>,
+    ) -> Self {
+        Self {
+            input: Arc::new(input),
+            config,
+            stats,
+            runtime,
+            thread_handle: None,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("clickhouse {} sender already started, do nothing.", T::TYPE);
+            return;
+        }
+
+        let mut sender = ClickhouseSender::new(
+            self.input.clone(),
+            self.config.clone(),
+            self.running.clone(),
+            self.runtime.clone(),
+        );
+        self.stats.register_countable(
+            "clickhouse_sender",
+            Countable::Ref(Arc::downgrade(&sender.counter) as Weak<dyn RefCountable>),
+            vec![StatsOption::Tag("type", T::TYPE.to_string())],
+        );
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("clickhouse-sender".to_owned())
+                .spawn(move || sender.process())
+                .unwrap(),
+        );
+        info!("clickhouse {} sender started", T::TYPE);
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("clickhouse {} sender already stopped, do nothing.", T::TYPE);
+            return None;
+        }
+        info!("notified stopping clickhouse {} sender", T::TYPE);
+        self.thread_handle.take()
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("clickhouse {} sender already stopped, do nothing.", T::TYPE);
+            return;
+        }
+        info!("stopping clickhouse {} sender", T::TYPE);
+        let _ = self.thread_handle.take().unwrap().join();
+        info!("stopped clickhouse {} sender", T::TYPE);
+    }
+}
+
+struct ClickhouseSender<T: ClickhouseRow> {
+    input: Arc<Receiver<T>>,
+    config: CollectorAccess,
+    running: Arc<AtomicBool>,
+    counter: Arc<ClickhouseSenderCounter>,
+    runtime: Arc<Runtime>,
+
+    exporter: ClickhouseExporter,
+    pool: Option<Pool>,
+    // tables created or checked by the create table query
+    created_tables: HashSet<String>,
+    // rows and the create table query of tables
+    pending: HashMap<String, (Block, String)>,
+    pending_rows: usize,
+    last_flush_time: Instant,
+}
+
+impl<T: ClickhouseRow> ClickhouseSender<T> {
+    const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn new(
+        input: Arc<Receiver<T>>,
+        config: CollectorAccess,
+        running: Arc<AtomicBool>,
+        runtime: Arc<Runtime>,
+    ) -> Self {
+        let mut sender = Self {
+            input,
+            config,
+            running,
+            counter: Arc::new(ClickhouseSenderCounter::default()),
+            runtime,
+            exporter: ClickhouseExporter::default(),
+            pool: None,
+            created_tables: HashSet::new(),
+            pending: HashMap::new(),
+            pending_rows: 0,
+            last_flush_time: Instant::now(),
+        };
+        let exporter = sender.config.load().clickhouse_exporter.clone();
+        sender.update_exporter(exporter);
+        sender
+    }
+
+    fn update_exporter(&mut self, exporter: ClickhouseExporter) {
+        self.pool = None;
+        self.created_tables.clear();
+        if !exporter.endpoint.is_empty() {
+            match Options::from_str(&format!("tcp://{}", exporter.endpoint)) {
+                Ok(options) => {
+                    let options = options
+                        .username(exporter.username.clone())
+                        .password(exporter.password.clone())
+                        .database(exporter.database.clone())
+                        .connection_timeout(exporter.timeout)
+                        .query_timeout(exporter.timeout);
+                    // connects on the first insert and reconnects after failures
+                    let _guard = self.runtime.enter();
+                    self.pool = Some(Pool::new(options));
+                }
+                Err(e) => warn!("invalid clickhouse endpoint {}: {}", exporter.endpoint, e),
+            }
+        }
+        self.exporter = exporter;
+    }
+
+    fn append(&mut self, batch: &mut Vec<T>) {
+        for item in batch.drain(..) {
+            let table = item.table(&self.exporter);
+            if table.is_empty() {
+                continue;
+            }
+            let row = item.to_row();
+            let (block, _) = self.pending.entry(table).or_insert_with_key(|table| {
+                (
+                    Block::new(),
+                    create_table_query(&self.exporter, table, &row),
+                )
+            });
+            let row = row
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect::<Vec<_>>();
+            if let Err(e) = block.push(row) {
+                warn!("clickhouse {} sender append row failed: {}", T::TYPE, e);
+                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            self.pending_rows += 1;
+        }
+    }
+
+    fn flush(&mut self) {
+        self.last_flush_time = Instant::now();
+        if self.pending.is_empty() {
+            return;
+        }
+        self.pending_rows = 0;
+        let pending = std::mem::take(&mut self.pending);
+        let Some(pool) = self.pool.as_ref() else {
+            for (block, _) in pending.values() {
+                self.counter
+                    .dropped
+                    .fetch_add(block.row_count() as u64, Ordering::Relaxed);
+            }
+            return;
+        };
+        for (table, (block, query)) in pending {
+            let n = block.row_count() as u64;
+            let create_table = !self.exporter.create_table_template.is_empty()
+                && !self.created_tables.contains(&table);
+            let result = self.runtime.block_on(async {
+                let mut handle = pool.get_handle().await?;
+                if create_table {
+                    handle.execute(query).await?;
+                }
+                handle.insert(&table, block).await
+            });
+            match result {
+                Ok(_) => {
+                    self.counter.tx_inserts.fetch_add(1, Ordering::Relaxed);
+                    self.counter.tx_rows.fetch_add(n, Ordering::Relaxed);
+                    if create_table {
+                        self.created_tables.insert(table);
+                    }
+                }
+                Err(e) => {
+                    if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+                        warn!(
+                            "clickhouse {} sender insert into {} failed: {}",
+                            T::TYPE,
+                            table,
+                            e
+                        );
+                    }
+                    self.counter.dropped.fetch_add(n, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    fn process(&mut self) {
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        while self.running.load(Ordering::Relaxed) {
+            if self.config.load().clickhouse_exporter != self.exporter {
+                let exporter = self.config.load().clickhouse_exporter.clone();
+                info!(
+                    "clickhouse exporter config change from {:?} to {:?}",
+                    self.exporter, exporter
+                );
+                self.flush();
+                self.update_exporter(exporter);
+            }
+            match self
+                .input
+                .recv_all(&mut batch, Some(Self::QUEUE_READ_TIMEOUT))
+            {
+                Ok(_) => {
+                    self.counter
+                        .rx
+                        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    if !self.exporter.enabled {
+                        batch.clear();
+                        continue;
+                    }
+                    self.append(&mut batch);
+                    if self.pending_rows >= self.exporter.batch_size
+                        || self.last_flush_time.elapsed() >= self.exporter.flush_interval
+                    {
+                        self.flush();
+                    }
+                }
+                Err(Error::Timeout) => {
+                    if self.last_flush_time.elapsed() >= self.exporter.flush_interval {
+                        self.flush();
+                    }
+                }
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+        }
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    use crate::metric::{
+        document::{Code, Document, Tagger},
+        meter::{AppMeter, Meter},
+    };
+
+    #[test]
+    fn document_to_row() {
+        let mut app_meter = AppMeter::default();
+        app_meter.traffic.request = 10;
+        let mut doc = Document::new(Meter::App(app_meter));
+        doc.timestamp = 1700000000;
+        doc.tagger = Tagger {
+            code: Code::IP_PATH | Code::SERVER_PORT | Code::VTAP_ID,
+            ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+            ip1: Ipv4Addr::new(10, 0, 0, 2).into(),
+            server_port: 8080,
+            vtap_id: 3,
+            ..Default::default()
+        };
+        let doc = BoxedDocument(Box::new(doc));
+
+        let exporter = ClickhouseExporter::default();
+        let table = doc.table(&exporter);
+        assert_eq!(table, "app_metrics");
+        let row = doc.to_row();
+        assert_eq!(row.len(), 1 + METRICS_TAGS.len() + 8);
+        assert_eq!(row[0], ("time", Value::DateTime(1700000000, Tz::UTC)));
+        let value = |name: &str| row.iter().find(|(n, _)| *n == name).unwrap().1.clone();
+        assert_eq!(value("server_ip"), Value::from("10.0.0.2".to_owned()));
+        assert_eq!(value("ip"), Value::from(String::new()));
+        assert_eq!(value("request"), Value::from(10u64));
+
+        let query = create_table_query(&exporter, &table, &row);
+        assert!(query.starts_with(
+            "CREATE TABLE IF NOT EXISTS deepflow.app_metrics (time DateTime('UTC'), agent_id String,"
+        ));
+        assert!(query.contains("rrt_max_us UInt64)"));
+        assert!(query.ends_with("TTL time + toIntervalHour(168)"));
+    }
+}
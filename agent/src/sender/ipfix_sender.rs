@@ -0,0 +1,754 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Weak,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use arc_swap::access::Access;
+use log::{info, warn};
+
+use super::QUEUE_BATCH_SIZE;
+
+use crate::common::{
+    flow::{CloseType, Flow, L7PerfStats},
+    tagged_flow::BoxedTaggedFlow,
+};
+use crate::config::{handler::CollectorAccess, FlowExportProtocol, IpfixExporter};
+use crate::utils::stats::{
+    Collector, Countable, Counter, CounterType, CounterValue, RefCountable, StatsOption,
+};
+use public::queue::{Error, Receiver};
+
+const IPFIX_VERSION: u16 = 10;
+const NETFLOW_V9_VERSION: u16 = 9;
+const TEMPLATE_SET_ID: u16 = 2;
+const NETFLOW_V9_TEMPLATE_SET_ID: u16 = 0;
+const TEMPLATE_ID_IPV4: u16 = 256;
+const TEMPLATE_ID_IPV6: u16 = 257;
+const MESSAGE_HEADER_LEN: usize = 16;
+const NETFLOW_V9_HEADER_LEN: usize = 20;
+const SET_HEADER_LEN: usize = 4;
+// netflow v9 flowsets are padded to 4 bytes
+const NETFLOW_V9_SET_ALIGNMENT: usize = 4;
+// keep messages in a single udp packet without fragmentation
+const MAX_MESSAGE_LEN: usize = 1400;
+// reverse information elements of biflow records (RFC 5103)
+const REVERSE_ENTERPRISE_NUMBER: u32 = 29305;
+const ENTERPRISE_BIT: u16 = 0x8000;
+
+// flowEndReason
+const END_REASON_IDLE_TIMEOUT: u8 = 0x01;
+const END_REASON_ACTIVE_TIMEOUT: u8 = 0x02;
+const END_REASON_END_OF_FLOW: u8 = 0x03;
+
+// information element id, length and enterprise number (0 for IANA elements)
+type FieldSpecifier = (u16, u16, u32);
+
+const IPV4_ADDRESS_FIELDS: [FieldSpecifier; 2] = [
+    (8, 4, 0),  // sourceIPv4Address
+    (12, 4, 0), // destinationIPv4Address
+];
+const IPV6_ADDRESS_FIELDS: [FieldSpecifier; 2] = [
+    (27, 16, 0), // sourceIPv6Address
+    (28, 16, 0), // destinationIPv6Address
+];
+const COMMON_FIELDS: [FieldSpecifier; 12] = [
+    (7, 2, 0),                         // sourceTransportPort
+    (11, 2, 0),                        // destinationTransportPort
+    (4, 1, 0),                         // protocolIdentifier
+    (6, 2, 0),                         // tcpControlBits
+    (1, 8, 0),                         // octetDeltaCount
+    (2, 8, 0),                         // packetDeltaCount
+    (6, 2, REVERSE_ENTERPRISE_NUMBER), // reverseTcpControlBits
+    (1, 8, REVERSE_ENTERPRISE_NUMBER), // reverseOctetDeltaCount
+    (2, 8, REVERSE_ENTERPRISE_NUMBER), // reversePacketDeltaCount
+    (152, 8, 0),                       // flowStartMilliseconds
+    (153, 8, 0),                       // flowEndMilliseconds
+    (136, 1, 0),                       // flowEndReason
+];
+// netflow v9 has neither reverse nor enterprise-specific elements
+const NETFLOW_V9_COMMON_FIELDS: [FieldSpecifier; 10] = [
+    (7, 2, 0),  // L4_SRC_PORT
+    (11, 2, 0), // L4_DST_PORT
+    (4, 1, 0),  // PROTOCOL
+    (6, 1, 0),  // TCP_FLAGS
+    (1, 8, 0),  // IN_BYTES
+    (2, 8, 0),  // IN_PKTS
+    (23, 8, 0), // OUT_BYTES
+    (24, 8, 0), // OUT_PKTS
+    (22, 4, 0), // FIRST_SWITCHED in milliseconds of system uptime
+    (21, 4, 0), // LAST_SWITCHED in milliseconds of system uptime
+];
+// enterprise-specific elements with the configured enterprise number
+const L7_FIELDS: [(u16, u16); 7] = [
+    (1, 1), // l7Protocol
+    (2, 4), // l7RequestCount
+    (3, 4), // l7ResponseCount
+    (4, 4), // l7ErrorCount, client and server errors
+    (5, 8), // rrtSum in microseconds
+    (6, 4), // rrtCount
+    (7, 4), // rrtMax in microseconds
+];
+
+#[derive(Debug, Default)]
+pub struct IpfixSenderCounter {
+    pub rx: AtomicU64,
+    pub tx_records: AtomicU64,
+    pub tx_messages: AtomicU64,
+    pub tx_bytes: AtomicU64,
+    pub dropped: AtomicU64,
+}
+
+impl RefCountable for IpfixSenderCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "rx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-records",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_records.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-messages",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_messages.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+// Builds IPFIX messages (RFC 7011) or NetFlow v9 export packets (RFC 3954) of flows,
+// records of the same template are put into one set
+struct IpfixEncoder {
+    protocol: FlowExportProtocol,
+    observation_domain_id: u32,
+    enterprise_number: u32,
+    // ipfix: the number of data records sent before current message
+    // netflow v9: the number of export packets sent before current packet
+    sequence: u32,
+    // netflow v9 timestamps are relative to the system uptime
+    boot_time: Duration,
+
+    buffer: Vec<u8>,
+    records: u32,
+    template_records: u16,
+    // template id and offset of the current set
+    set: Option<(u16, usize)>,
+}
+
+impl IpfixEncoder {
+    fn new(
+        protocol: FlowExportProtocol,
+        observation_domain_id: u32,
+        enterprise_number: u32,
+        boot_time: Duration,
+    ) -> Self {
+        Self {
+            protocol,
+            observation_domain_id,
+            enterprise_number,
+            sequence: 0,
+            boot_time,
+            buffer: Vec::with_capacity(MAX_MESSAGE_LEN),
+            records: 0,
+            template_records: 0,
+            set: None,
+        }
+    }
+
+    fn header_len(&self) -> usize {
+        match self.protocol {
+            FlowExportProtocol::Ipfix => MESSAGE_HEADER_LEN,
+            FlowExportProtocol::NetflowV9 => NETFLOW_V9_HEADER_LEN,
+        }
+    }
+
+    fn fields(&self, template_id: u16) -> Vec<FieldSpecifier> {
+        let mut fields = if template_id == TEMPLATE_ID_IPV6 {
+            IPV6_ADDRESS_FIELDS.to_vec()
+        } else {
+            IPV4_ADDRESS_FIELDS.to_vec()
+        };
+        if self.protocol == FlowExportProtocol::NetflowV9 {
+            fields.extend_from_slice(&NETFLOW_V9_COMMON_FIELDS);
+            return fields;
+        }
+        fields.extend_from_slice(&COMMON_FIELDS);
+        if self.enterprise_number != 0 {
+            fields.extend(
+                L7_FIELDS
+                    .iter()
+                    .map(|(id, len)| (*id, *len, self.enterprise_number)),
+            );
+        }
+        fields
+    }
+
+    fn record_len(&self, template_id: u16) -> usize {
+        self.fields(template_id)
+            .iter()
+            .map(|(_, len, _)| *len as usize)
+            .sum()
+    }
+
+    fn begin_message(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize(self.header_len(), 0);
+        self.records = 0;
+        self.template_records = 0;
+        self.set = None;
+    }
+
+    fn begin_set(&mut self, set_id: u16) {
+        self.end_set();
+        self.set = Some((set_id, self.buffer.len()));
+        self.buffer.extend_from_slice(&set_id.to_be_bytes());
+        self.buffer.extend_from_slice(&[0, 0]);
+    }
+
+    fn end_set(&mut self) {
+        if let Some((_, offset)) = self.set.take() {
+            if self.protocol == FlowExportProtocol::NetflowV9 {
+                let padding = (NETFLOW_V9_SET_ALIGNMENT
+                    - (self.buffer.len() - offset) % NETFLOW_V9_SET_ALIGNMENT)
+                    % NETFLOW_V9_SET_ALIGNMENT;
+                self.buffer.resize(self.buffer.len() + padding, 0);
+            }
+            let len = (self.buffer.len() - offset) as u16;
+            self.buffer[offset + 2..offset + 4].copy_from_slice(&len.to_be_bytes());
+        }
+    }
+
+    // returns the finished message, which is kept until the next begin_message()
+    fn end_message(&mut self, now: Duration) -> Option<&[u8]> {
+        self.end_set();
+        let header_len = self.header_len();
+        if self.buffer.len() <= header_len {
+            return None;
+        }
+        let export_time = now.as_secs() as u32;
+        let len = self.buffer.len() as u16;
+        let count = self.template_records + self.records as u16;
+        let uptime = self.uptime_millis(now);
+        let header = &mut self.buffer[..header_len];
+        match self.protocol {
+            FlowExportProtocol::Ipfix => {
+                header[0..2].copy_from_slice(&IPFIX_VERSION.to_be_bytes());
+                header[2..4].copy_from_slice(&len.to_be_bytes());
+                header[4..8].copy_from_slice(&export_time.to_be_bytes());
+                header[8..12].copy_from_slice(&self.sequence.to_be_bytes());
+                header[12..16].copy_from_slice(&self.observation_domain_id.to_be_bytes());
+                self.sequence = self.sequence.wrapping_add(self.records);
+            }
+            FlowExportProtocol::NetflowV9 => {
+                header[0..2].copy_from_slice(&NETFLOW_V9_VERSION.to_be_bytes());
+                header[2..4].copy_from_slice(&count.to_be_bytes());
+                header[4..8].copy_from_slice(&uptime.to_be_bytes());
+                header[8..12].copy_from_slice(&export_time.to_be_bytes());
+                header[12..16].copy_from_slice(&self.sequence.to_be_bytes());
+                header[16..20].copy_from_slice(&self.observation_domain_id.to_be_bytes());
+                self.sequence = self.sequence.wrapping_add(1);
+            }
+        }
+        self.records = 0;
+        self.template_records = 0;
+        Some(&self.buffer)
+    }
+
+    // wraps around after about 49.7 days as netflow v9 exporters do
+    fn uptime_millis(&self, time: Duration) -> u32 {
+        time.saturating_sub(self.boot_time).as_millis() as u32
+    }
+
+    fn encode_templates(&mut self) {
+        self.begin_message();
+        self.begin_set(match self.protocol {
+            FlowExportProtocol::Ipfix => TEMPLATE_SET_ID,
+            FlowExportProtocol::NetflowV9 => NETFLOW_V9_TEMPLATE_SET_ID,
+        });
+        for template_id in [TEMPLATE_ID_IPV4, TEMPLATE_ID_IPV6] {
+            self.template_records += 1;
+            let fields = self.fields(template_id);
+            self.buffer.extend_from_slice(&template_id.to_be_bytes());
+            self.buffer
+                .extend_from_slice(&(fields.len() as u16).to_be_bytes());
+            for (id, len, enterprise_number) in fields {
+                if enterprise_number == 0 {
+                    self.buffer.extend_from_slice(&id.to_be_bytes());
+                    self.buffer.extend_from_slice(&len.to_be_bytes());
+                } else {
+                    self.buffer
+                        .extend_from_slice(&(id | ENTERPRISE_BIT).to_be_bytes());
+                    self.buffer.extend_from_slice(&len.to_be_bytes());
+                    self.buffer
+                        .extend_from_slice(&enterprise_number.to_be_bytes());
+                }
+            }
+        }
+    }
+
+    // returns false if the message has no room for the record
+    fn encode_flow(&mut self, flow: &Flow) -> bool {
+        let template_id = match (flow.flow_key.ip_src, flow.flow_key.ip_dst) {
+            (IpAddr::V4(_), IpAddr::V4(_)) => TEMPLATE_ID_IPV4,
+            _ => TEMPLATE_ID_IPV6,
+        };
+        let new_set = !matches!(self.set, Some((id, _)) if id == template_id);
+        let mut len = self.record_len(template_id) + if new_set { SET_HEADER_LEN } else { 0 };
+        if self.protocol == FlowExportProtocol::NetflowV9 {
+            // room for padding of the current set and the new set
+            len += if new_set { 2 } else { 1 } * (NETFLOW_V9_SET_ALIGNMENT - 1);
+        }
+        if self.buffer.len() + len > MAX_MESSAGE_LEN {
+            return false;
+        }
+        if new_set {
+            self.begin_set(template_id);
+        }
+        let first_switched = self.uptime_millis(flow.start_time.into());
+        let last_switched = self.uptime_millis(flow.end_time.into());
+
+        let buf = &mut self.buffer;
+        match (flow.flow_key.ip_src, flow.flow_key.ip_dst) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                buf.extend_from_slice(&src.octets());
+                buf.extend_from_slice(&dst.octets());
+            }
+            (src, dst) => {
+                buf.extend_from_slice(&to_ipv6(src).octets());
+                buf.extend_from_slice(&to_ipv6(dst).octets());
+            }
+        }
+        let (src, dst) = (&flow.flow_metrics_peers[0], &flow.flow_metrics_peers[1]);
+        if self.protocol == FlowExportProtocol::NetflowV9 {
+            buf.extend_from_slice(&flow.flow_key.port_src.to_be_bytes());
+            buf.extend_from_slice(&flow.flow_key.port_dst.to_be_bytes());
+            buf.push(u8::from(flow.flow_key.proto));
+            buf.push((src.tcp_flags | dst.tcp_flags).bits());
+            buf.extend_from_slice(&src.byte_count.to_be_bytes());
+            buf.extend_from_slice(&src.packet_count.to_be_bytes());
+            buf.extend_from_slice(&dst.byte_count.to_be_bytes());
+            buf.extend_from_slice(&dst.packet_count.to_be_bytes());
+            buf.extend_from_slice(&first_switched.to_be_bytes());
+            buf.extend_from_slice(&last_switched.to_be_bytes());
+            self.records += 1;
+            return true;
+        }
+        buf.extend_from_slice(&flow.flow_key.port_src.to_be_bytes());
+        buf.extend_from_slice(&flow.flow_key.port_dst.to_be_bytes());
+        buf.push(u8::from(flow.flow_key.proto));
+        buf.extend_from_slice(&(src.tcp_flags.bits() as u16).to_be_bytes());
+        buf.extend_from_slice(&src.byte_count.to_be_bytes());
+        buf.extend_from_slice(&src.packet_count.to_be_bytes());
+        buf.extend_from_slice(&(dst.tcp_flags.bits() as u16).to_be_bytes());
+        buf.extend_from_slice(&dst.byte_count.to_be_bytes());
+        buf.extend_from_slice(&dst.packet_count.to_be_bytes());
+        buf.extend_from_slice(&(Duration::from(flow.start_time).as_millis() as u64).to_be_bytes());
+        buf.extend_from_slice(&(Duration::from(flow.end_time).as_millis() as u64).to_be_bytes());
+        buf.push(match flow.close_type {
+            CloseType::Timeout => END_REASON_IDLE_TIMEOUT,
+            CloseType::ForcedReport => END_REASON_ACTIVE_TIMEOUT,
+            _ => END_REASON_END_OF_FLOW,
+        });
+        if self.enterprise_number != 0 {
+            let default_stats = L7PerfStats::default();
+            let (l7_protocol, l7) = flow
+                .flow_perf_stats
+                .as_ref()
+                .map(|s| (s.l7_protocol as u8, &s.l7))
+                .unwrap_or((0, &default_stats));
+            buf.push(l7_protocol);
+            buf.extend_from_slice(&l7.request_count.to_be_bytes());
+            buf.extend_from_slice(&l7.response_count.to_be_bytes());
+            buf.extend_from_slice(&(l7.err_client_count + l7.err_server_count).to_be_bytes());
+            buf.extend_from_slice(&l7.rrt_sum.to_be_bytes());
+            buf.extend_from_slice(&l7.rrt_count.to_be_bytes());
+            buf.extend_from_slice(&l7.rrt_max.to_be_bytes());
+        }
+        self.records += 1;
+        true
+    }
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+pub struct IpfixSenderThread {
+    input: Arc<Receiver<BoxedTaggedFlow>>,
+    config: CollectorAccess,
+    stats: Arc<Collector>,
+
+    thread_handle: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+}
+
+impl IpfixSenderThread {
+    pub fn new(
+        input: Receiver<BoxedTaggedFlow>,
+        config: CollectorAccess,
+        stats: Arc<Collector>,
+    ) -> Self {
+        Self {
+            input: Arc::new(input),
+            config,
+            stats,
+            thread_handle: None,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("ipfix sender already started, do nothing.");
+            return;
+        }
+
+        let mut sender = IpfixSender::new(
+            self.input.clone(),
+            self.config.clone(),
+            self.running.clone(),
+        );
+        self.stats.register_countable(
+            "ipfix_sender",
+            Countable::Ref(Arc::downgrade(&sender.counter) as Weak<dyn RefCountable>),
+            vec![StatsOption::Tag("type", "l4_flow_log".to_string())],
+        );
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("ipfix-sender".to_owned())
+                .spawn(move || sender.process())
+                .unwrap(),
+        );
+        info!("ipfix sender started");
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("ipfix sender already stopped, do nothing.");
+            return None;
+        }
+        info!("notified stopping ipfix sender");
+        self.thread_handle.take()
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("ipfix sender already stopped, do nothing.");
+            return;
+        }
+        info!("stopping ipfix sender");
+        let _ = self.thread_handle.take().unwrap().join();
+        info!("stopped ipfix sender");
+    }
+}
+
+struct IpfixSender {
+    input: Arc<Receiver<BoxedTaggedFlow>>,
+    config: CollectorAccess,
+    running: Arc<AtomicBool>,
+    counter: Arc<IpfixSenderCounter>,
+
+    exporter: IpfixExporter,
+    encoder: IpfixEncoder,
+    boot_time: Duration,
+    collectors: Vec<(UdpSocket, SocketAddr)>,
+    last_template_time: Duration,
+}
+
+impl IpfixSender {
+    const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn new(
+        input: Arc<Receiver<BoxedTaggedFlow>>,
+        config: CollectorAccess,
+        running: Arc<AtomicBool>,
+    ) -> Self {
+        let boot_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut sender = Self {
+            input,
+            config,
+            running,
+            counter: Arc::new(IpfixSenderCounter::default()),
+            exporter: IpfixExporter::default(),
+            encoder: IpfixEncoder::new(FlowExportProtocol::Ipfix, 0, 0, boot_time),
+            boot_time,
+            collectors: vec![],
+            last_template_time: Duration::ZERO,
+        };
+        let exporter = sender.config.load().ipfix_exporter.clone();
+        sender.update_exporter(exporter);
+        sender
+    }
+
+    fn update_exporter(&mut self, exporter: IpfixExporter) {
+        self.collectors.clear();
+        for collector in exporter.collectors.iter() {
+            let addr = match collector.parse::<SocketAddr>() {
+                Ok(addr) => addr,
+                Err(e) => {
+                    warn!("invalid ipfix collector {}: {}", collector, e);
+                    continue;
+                }
+            };
+            let local = match addr {
+                SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+                SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+            };
+            match UdpSocket::bind(local) {
+                Ok(socket) => self.collectors.push((socket, addr)),
+                Err(e) => warn!("bind socket for ipfix collector {} failed: {}", addr, e),
+            }
+        }
+        self.encoder = IpfixEncoder::new(
+            exporter.protocol,
+            exporter.observation_domain_id,
+            exporter.enterprise_number,
+            self.boot_time,
+        );
+        // templates are sent with the next flows
+        self.last_template_time = Duration::ZERO;
+        self.exporter = exporter;
+    }
+
+    fn send_message(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let Some(message) = self.encoder.end_message(now) else {
+            return;
+        };
+        for (socket, addr) in self.collectors.iter() {
+            match socket.send_to(message, addr) {
+                Ok(n) => {
+                    self.counter.tx_messages.fetch_add(1, Ordering::Relaxed);
+                    self.counter.tx_bytes.fetch_add(n as u64, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+                        warn!("send ipfix message to {} failed: {}", addr, e);
+                    }
+                    self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    // templates are sent periodically since udp collectors may restart or lose packets
+    fn send_templates(&mut self, now: Duration) {
+        if self.last_template_time.is_zero()
+            || now >= self.last_template_time + self.exporter.template_refresh_interval
+        {
+            self.encoder.encode_templates();
+            self.send_message();
+            self.last_template_time = now;
+        }
+    }
+
+    fn send_flows(&mut self, flows: &mut Vec<BoxedTaggedFlow>) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.send_templates(now);
+        self.encoder.begin_message();
+        for f in flows.drain(..) {
+            if !self.encoder.encode_flow(&f.0.flow) {
+                self.send_message();
+                self.encoder.begin_message();
+                self.encoder.encode_flow(&f.0.flow);
+            }
+            self.counter.tx_records.fetch_add(1, Ordering::Relaxed);
+        }
+        self.send_message();
+    }
+
+    fn process(&mut self) {
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        while self.running.load(Ordering::Relaxed) {
+            if self.config.load().ipfix_exporter != self.exporter {
+                let exporter = self.config.load().ipfix_exporter.clone();
+                info!(
+                    "ipfix exporter config change from {:?} to {:?}",
+                    self.exporter, exporter
+                );
+                self.update_exporter(exporter);
+            }
+            match self
+                .input
+                .recv_all(&mut batch, Some(Self::QUEUE_READ_TIMEOUT))
+            {
+                Ok(_) => {
+                    self.counter
+                        .rx
+                        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    if !self.exporter.enabled || self.collectors.is_empty() {
+                        batch.clear();
+                        continue;
+                    }
+                    self.send_flows(&mut batch);
+                }
+                Err(Error::Timeout) => continue,
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::flow::FlowPerfStats;
+    use public::{enums::IpProtocol, l7_protocol::L7Protocol};
+
+    #[test]
+    fn encode_templates_and_flows() {
+        let mut encoder = IpfixEncoder::new(FlowExportProtocol::Ipfix, 7, 12345, Duration::ZERO);
+        encoder.encode_templates();
+        let message = encoder
+            .end_message(Duration::from_secs(1))
+            .unwrap()
+            .to_vec();
+        assert_eq!(u16::from_be_bytes([message[0], message[1]]), IPFIX_VERSION);
+        assert_eq!(
+            u16::from_be_bytes([message[2], message[3]]) as usize,
+            message.len()
+        );
+        assert_eq!(
+            u16::from_be_bytes([message[16], message[17]]),
+            TEMPLATE_SET_ID
+        );
+        assert_eq!(
+            u16::from_be_bytes([message[18], message[19]]) as usize,
+            message.len() - MESSAGE_HEADER_LEN
+        );
+        // ipv4 template with 21 fields
+        assert_eq!(u16::from_be_bytes([message[20], message[21]]), 256);
+        assert_eq!(u16::from_be_bytes([message[22], message[23]]), 21);
+
+        let mut flow = Flow::default();
+        flow.flow_key.ip_src = Ipv4Addr::new(10, 0, 0, 1).into();
+        flow.flow_key.ip_dst = Ipv4Addr::new(10, 0, 0, 2).into();
+        flow.flow_key.proto = IpProtocol::TCP;
+        flow.flow_perf_stats = Some(FlowPerfStats {
+            l7_protocol: L7Protocol::Http1,
+            ..Default::default()
+        });
+        encoder.begin_message();
+        let mut records = 0;
+        while encoder.encode_flow(&flow) {
+            records += 1;
+        }
+        let record_len = encoder.record_len(TEMPLATE_ID_IPV4);
+        assert_eq!(
+            records,
+            (MAX_MESSAGE_LEN - MESSAGE_HEADER_LEN - SET_HEADER_LEN) / record_len
+        );
+        let message = encoder.end_message(Duration::from_secs(1)).unwrap();
+        assert_eq!(
+            message.len(),
+            MESSAGE_HEADER_LEN + SET_HEADER_LEN + records * record_len
+        );
+        assert_eq!(u16::from_be_bytes([message[16], message[17]]), 256);
+        // sequence counts data records of previous messages
+        assert_eq!(encoder.sequence, records as u32);
+    }
+
+    #[test]
+    fn encode_netflow_v9() {
+        let boot_time = Duration::from_secs(1000);
+        let mut encoder = IpfixEncoder::new(FlowExportProtocol::NetflowV9, 7, 12345, boot_time);
+        encoder.encode_templates();
+        let message = encoder
+            .end_message(boot_time + Duration::from_secs(1))
+            .unwrap()
+            .to_vec();
+        assert_eq!(
+            u16::from_be_bytes([message[0], message[1]]),
+            NETFLOW_V9_VERSION
+        );
+        // two template records
+        assert_eq!(u16::from_be_bytes([message[2], message[3]]), 2);
+        assert_eq!(
+            u32::from_be_bytes([message[4], message[5], message[6], message[7]]),
+            1000
+        );
+        assert_eq!(
+            u16::from_be_bytes([message[20], message[21]]),
+            NETFLOW_V9_TEMPLATE_SET_ID
+        );
+        // ipv4 template without reverse and enterprise-specific fields
+        assert_eq!(u16::from_be_bytes([message[24], message[25]]), 256);
+        assert_eq!(u16::from_be_bytes([message[26], message[27]]), 12);
+
+        let mut flow = Flow::default();
+        flow.flow_key.ip_src = Ipv4Addr::new(10, 0, 0, 1).into();
+        flow.flow_key.ip_dst = Ipv4Addr::new(10, 0, 0, 2).into();
+        flow.flow_key.proto = IpProtocol::TCP;
+        flow.start_time = (boot_time + Duration::from_millis(10)).into();
+        flow.end_time = (boot_time + Duration::from_millis(20)).into();
+        encoder.begin_message();
+        assert!(encoder.encode_flow(&flow));
+        assert!(encoder.encode_flow(&flow));
+        let message = encoder.end_message(boot_time).unwrap();
+        let record_len = encoder.record_len(TEMPLATE_ID_IPV4);
+        let set_len = SET_HEADER_LEN + 2 * record_len;
+        let padded_set_len = (set_len + 3) / 4 * 4;
+        assert_eq!(message.len(), NETFLOW_V9_HEADER_LEN + padded_set_len);
+        assert_eq!(u16::from_be_bytes([message[2], message[3]]), 2);
+        assert_eq!(
+            u16::from_be_bytes([message[22], message[23]]) as usize,
+            padded_set_len
+        );
+        // FIRST_SWITCHED and LAST_SWITCHED of the first record
+        let offset = NETFLOW_V9_HEADER_LEN + SET_HEADER_LEN + record_len - 8;
+        assert_eq!(
+            u32::from_be_bytes(message[offset..offset + 4].try_into().unwrap()),
+            10
+        );
+        assert_eq!(
+            u32::from_be_bytes(message[offset + 4..offset + 8].try_into().unwrap()),
+            20
+        );
+        // sequence counts export packets
+        assert_eq!(encoder.sequence, 2);
+    }
+}
@@ -0,0 +1,509 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Weak,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use arc_swap::access::Access;
+use log::{info, warn};
+use rdkafka::{
+    client::ClientContext,
+    config::ClientConfig,
+    error::{KafkaError, RDKafkaErrorCode},
+    message::Message,
+    producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext},
+};
+use serde_json::{Map, Value};
+
+use super::{remote_write_sender::document_labels, QUEUE_BATCH_SIZE};
+
+use crate::config::{
+    handler::CollectorAccess, KafkaExporter, KafkaMessageFormat, KafkaPartitionKey,
+};
+use crate::flow_generator::protocol_logs::BoxAppProtoLogsData;
+use crate::metric::document::{BoxedDocument, Code};
+use crate::utils::stats::{
+    Collector, Countable, Counter, CounterType, CounterValue, RefCountable, StatsOption,
+};
+use public::{
+    queue::{Error, Receiver},
+    sender::Sendable,
+};
+
+// Messages published by the kafka sender
+pub trait KafkaMessage: Sendable {
+    // tag of the sender counter
+    const TYPE: &'static str;
+
+    fn topic(exporter: &KafkaExporter) -> &str;
+
+    fn to_json(&self, buf: &mut String);
+
+    // records with an empty key are sent without a key and partitioned by the producer
+    fn partition_key(&self, key: KafkaPartitionKey) -> Vec<u8>;
+}
+
+fn ip_key(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+impl KafkaMessage for BoxAppProtoLogsData {
+    const TYPE: &'static str = "l7_flow_log";
+
+    fn topic(exporter: &KafkaExporter) -> &str {
+        &exporter.l7_flow_log_topic
+    }
+
+    fn to_json(&self, buf: &mut String) {
+        self.to_kv_string(buf);
+        if buf.ends_with('\n') {
+            buf.pop();
+        }
+    }
+
+    fn partition_key(&self, key: KafkaPartitionKey) -> Vec<u8> {
+        match key {
+            KafkaPartitionKey::None => vec![],
+            KafkaPartitionKey::FlowId => self.0.base_info.flow_id.to_be_bytes().to_vec(),
+            KafkaPartitionKey::ServerIp => ip_key(self.0.base_info.ip_dst),
+        }
+    }
+}
+
+impl KafkaMessage for BoxedDocument {
+    const TYPE: &'static str = "metrics";
+
+    fn topic(exporter: &KafkaExporter) -> &str {
+        &exporter.metrics_topic
+    }
+
+    fn to_json(&self, buf: &mut String) {
+        let doc = &self.0;
+        let mut tags = Map::new();
+        for (name, value) in document_labels(doc) {
+            tags.insert(name.to_owned(), Value::String(value));
+        }
+        let mut values = Map::new();
+        for (name, value) in doc.meter.values() {
+            values.insert(name.to_owned(), Value::from(value));
+        }
+        let mut json = Map::new();
+        json.insert("timestamp".to_owned(), Value::from(doc.timestamp));
+        json.insert("meter".to_owned(), Value::from(doc.meter.kind()));
+        json.insert("tags".to_owned(), Value::Object(tags));
+        json.insert("values".to_owned(), Value::Object(values));
+        buf.push_str(&Value::Object(json).to_string());
+    }
+
+    // metrics are aggregated from many flows and have no flow id
+    fn partition_key(&self, key: KafkaPartitionKey) -> Vec<u8> {
+        let tagger = &self.0.tagger;
+        match key {
+            KafkaPartitionKey::ServerIp if tagger.code.contains(Code::IP_PATH) => {
+                ip_key(tagger.ip1)
+            }
+            KafkaPartitionKey::ServerIp if tagger.code.contains(Code::IP) => ip_key(tagger.ip),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct KafkaSenderCounter {
+    pub rx: AtomicU64,
+    pub tx_records: AtomicU64,
+    pub tx_bytes: AtomicU64,
+    pub dropped: AtomicU64,
+}
+
+impl RefCountable for KafkaSenderCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "rx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-records",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_records.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+pub struct KafkaSenderThread<T: KafkaMessage> {
+    input: Arc<Receiver<T>>,
+    config: CollectorAccess,
+    stats: Arc<Collector>,
+
+    thread_handle: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+}
+
+impl<T: KafkaMessage> KafkaSenderThread<T> {
+    pub fn new(input: Receiver<T>, config: CollectorAccess, stats: Arc<Collector>) -> Self {
+        Self {
+            input: Arc::new(input),
+            config,
+            stats,
+            thread_handle: None,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("kafka {} sender already started, do nothing.", T::TYPE);
+            return;
+        }
+
+        let mut sender = KafkaSender::new(
+            self.input.clone(),
+            self.config.clone(),
+            self.running.clone(),
+        );
+        self.stats.register_countable(
+            "kafka_sender",
+            Countable::Ref(Arc::downgrade(&sender.counter) as Weak<dyn RefCountable>),
+            vec![StatsOption::Tag("type", T::TYPE.to_string())],
+        );
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("kafka-sender".to_owned())
+                .spawn(move || sender.process())
+                .unwrap(),
+        );
+        info!("kafka {} sender started", T::TYPE);
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("kafka {} sender already stopped, do nothing.", T::TYPE);
+            return None;
+        }
+        info!("notified stopping kafka {} sender", T::TYPE);
+        self.thread_handle.take()
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("kafka {} sender already stopped, do nothing.", T::TYPE);
+            return;
+        }
+        info!("stopping kafka {} sender", T::TYPE);
+        let _ = self.thread_handle.take().unwrap().join();
+        info!("stopped kafka {} sender", T::TYPE);
+    }
+}
+
+// counts records by the delivery reports of librdkafka, which are served in poll()
+struct DeliveryCounter(Arc<KafkaSenderCounter>);
+
+impl ClientContext for DeliveryCounter {}
+
+impl ProducerContext for DeliveryCounter {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: Self::DeliveryOpaque) {
+        match result {
+            Ok(message) => {
+                self.0.tx_records.fetch_add(1, Ordering::Relaxed);
+                self.0
+                    .tx_bytes
+                    .fetch_add(message.payload_len() as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.0.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+struct KafkaSender<T: KafkaMessage> {
+    input: Arc<Receiver<T>>,
+    config: CollectorAccess,
+    running: Arc<AtomicBool>,
+    counter: Arc<KafkaSenderCounter>,
+
+    exporter: KafkaExporter,
+    producer: Option<BaseProducer<DeliveryCounter>>,
+    last_connect_time: Option<Instant>,
+}
+
+impl<T: KafkaMessage> KafkaSender<T> {
+    const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1);
+    const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+    fn new(input: Arc<Receiver<T>>, config: CollectorAccess, running: Arc<AtomicBool>) -> Self {
+        let exporter = config.load().kafka_exporter.clone();
+        Self {
+            input,
+            config,
+            running,
+            counter: Arc::new(KafkaSenderCounter::default()),
+            exporter,
+            producer: None,
+            last_connect_time: None,
+        }
+    }
+
+    // brokers are connected in the background by librdkafka, creating a producer only
+    // fails on invalid configurations and is retried at intervals
+    fn connect(&mut self) {
+        if self.producer.is_some() || self.exporter.brokers.is_empty() {
+            return;
+        }
+        if let Some(t) = self.last_connect_time {
+            if t.elapsed() < Self::RECONNECT_INTERVAL {
+                return;
+            }
+        }
+        self.last_connect_time = Some(Instant::now());
+        let timeout = self.exporter.timeout.as_millis().to_string();
+        match ClientConfig::new()
+            .set("bootstrap.servers", self.exporter.brokers.join(","))
+            .set("message.timeout.ms", &timeout)
+            .set("request.timeout.ms", &timeout)
+            .set("acks", "1")
+            .create_with_context(DeliveryCounter(self.counter.clone()))
+        {
+            Ok(producer) => {
+                info!(
+                    "kafka {} sender created producer for {:?}",
+                    T::TYPE,
+                    self.exporter.brokers
+                );
+                self.producer = Some(producer);
+            }
+            Err(e) => warn!(
+                "kafka {} sender create producer for {:?} failed: {}",
+                T::TYPE,
+                self.exporter.brokers,
+                e
+            ),
+        }
+    }
+
+    // records still queued in librdkafka are sent before the producer is dropped
+    fn close(&mut self) {
+        if let Some(producer) = self.producer.take() {
+            if let Err(e) = producer.flush(self.exporter.timeout) {
+                warn!("kafka {} sender flush failed: {}", T::TYPE, e);
+            }
+        }
+    }
+
+    fn send(&mut self, batch: &mut Vec<T>) {
+        self.connect();
+        let Some(producer) = self.producer.as_ref() else {
+            self.counter
+                .dropped
+                .fetch_add(batch.len() as u64, Ordering::Relaxed);
+            batch.clear();
+            return;
+        };
+        let topic = T::topic(&self.exporter);
+        let mut json = String::new();
+        let mut buf = vec![];
+        for item in batch.drain(..) {
+            let key = item.partition_key(self.exporter.partition_key);
+            let value = match self.exporter.format {
+                KafkaMessageFormat::Json => {
+                    json.clear();
+                    item.to_json(&mut json);
+                    json.as_bytes()
+                }
+                KafkaMessageFormat::Protobuf => {
+                    buf.clear();
+                    if let Err(e) = item.encode(&mut buf) {
+                        warn!("kafka {} sender encode failed: {}", T::TYPE, e);
+                        self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    &buf[..]
+                }
+            };
+            let mut record = BaseRecord::to(topic).payload(value);
+            if !key.is_empty() {
+                record = record.key(&key[..]);
+            }
+            if let Err((e, _)) = producer.send(record) {
+                // the local queue is full when brokers are unreachable or too slow
+                if self.counter.dropped.load(Ordering::Relaxed) == 0
+                    || !matches!(
+                        e,
+                        KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull)
+                    )
+                {
+                    warn!(
+                        "kafka {} sender publish to topic {} failed: {}",
+                        T::TYPE,
+                        topic,
+                        e
+                    );
+                }
+                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        producer.poll(Duration::ZERO);
+    }
+
+    fn process(&mut self) {
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        while self.running.load(Ordering::Relaxed) {
+            if self.config.load().kafka_exporter != self.exporter {
+                let exporter = self.config.load().kafka_exporter.clone();
+                info!(
+                    "kafka exporter config change from {:?} to {:?}",
+                    self.exporter, exporter
+                );
+                self.close();
+                self.exporter = exporter;
+                self.last_connect_time = None;
+            }
+            match self
+                .input
+                .recv_all(&mut batch, Some(Self::QUEUE_READ_TIMEOUT))
+            {
+                Ok(_) => {
+                    self.counter
+                        .rx
+                        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    if !self.exporter.enabled || T::topic(&self.exporter).is_empty() {
+                        batch.clear();
+                        continue;
+                    }
+                    self.send(&mut batch);
+                }
+                Err(Error::Timeout) => {
+                    if let Some(producer) = self.producer.as_ref() {
+                        producer.poll(Duration::ZERO);
+                    }
+                }
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+        }
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    use crate::common::{l7_protocol_info::L7ProtocolInfo, MetaPacket, TaggedFlow};
+    use crate::flow_generator::protocol_logs::{
+        pb_adapter::{L7ProtocolSendLog, L7Request},
+        AppProtoHead, DnsInfo, MetaAppProto,
+    };
+    use crate::metric::{
+        document::{Document, Tagger},
+        meter::{AppMeter, Meter},
+    };
+
+    #[test]
+    fn l7_log_to_kafka_message() {
+        let mut log = MetaAppProto::new(
+            &TaggedFlow::default(),
+            &MetaPacket::default(),
+            L7ProtocolInfo::DnsInfo(DnsInfo {
+                query_name: "db.internal.example.com".to_owned(),
+                ..Default::default()
+            }),
+            AppProtoHead::default(),
+        )
+        .unwrap();
+        log.base_info.flow_id = 42;
+        // masked by the log parser before it is sent
+        log.send_log = Some(L7ProtocolSendLog {
+            req: L7Request {
+                resource: "db.***.example.com".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let log = BoxAppProtoLogsData(Box::new(log));
+
+        let mut json = String::new();
+        log.to_json(&mut json);
+        assert!(!json.ends_with('\n'));
+        let json: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["req"]["resource"], "db.***.example.com");
+        assert!(json.get("request_resource").is_none());
+        assert_eq!(json["flow_id"], 42);
+
+        assert_eq!(
+            log.partition_key(KafkaPartitionKey::FlowId),
+            42u64.to_be_bytes().to_vec()
+        );
+    }
+
+    #[test]
+    fn document_to_kafka_message() {
+        let mut app_meter = AppMeter::default();
+        app_meter.traffic.request = 10;
+        let mut doc = Document::new(Meter::App(app_meter));
+        doc.timestamp = 1700000000;
+        doc.tagger = Tagger {
+            code: Code::IP_PATH | Code::SERVER_PORT | Code::VTAP_ID,
+            ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+            ip1: Ipv4Addr::new(10, 0, 0, 2).into(),
+            server_port: 8080,
+            vtap_id: 3,
+            ..Default::default()
+        };
+        let doc = BoxedDocument(Box::new(doc));
+
+        let mut json = String::new();
+        doc.to_json(&mut json);
+        let json: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["timestamp"], 1700000000);
+        assert_eq!(json["meter"], "app");
+        assert_eq!(json["tags"]["server_ip"], "10.0.0.2");
+        assert_eq!(json["tags"]["agent_id"], "3");
+        assert_eq!(json["values"]["request"], 10);
+
+        assert_eq!(
+            doc.partition_key(KafkaPartitionKey::ServerIp),
+            vec![10, 0, 0, 2]
+        );
+        assert!(doc.partition_key(KafkaPartitionKey::FlowId).is_empty());
+        assert!(doc.partition_key(KafkaPartitionKey::None).is_empty());
+    }
+}
@@ -17,7 +17,13 @@
 use std::sync::atomic::{AtomicU8, Ordering};
 
 // NpbBandwidthWatcher NewFragmenterBuilder NewCompressorBuilder NewPCapBuilder NewUniformCollectSender
+pub mod clickhouse_sender;
+pub mod ipfix_sender;
+pub mod kafka_sender;
 pub mod npb_sender;
+pub mod otlp_sender;
+pub mod remote_write_sender;
+mod spool;
 mod tcp_packet;
 pub(crate) mod uniform_sender;
 
@@ -0,0 +1,651 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::Hasher;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Weak,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use arc_swap::access::Access;
+use log::{info, warn};
+use tokio::runtime::Runtime;
+use tonic::transport::{Channel, Endpoint};
+
+use super::QUEUE_BATCH_SIZE;
+
+use crate::config::{handler::LogParserAccess, OtlpExporter};
+use crate::flow_generator::protocol_logs::{
+    pb_adapter::L7ProtocolSendLog, BoxAppProtoLogsData, L7ResponseStatus, MetaAppProto,
+};
+use crate::metric::document::TapSide;
+use crate::utils::stats::{
+    Collector, Countable, Counter, CounterType, CounterValue, RefCountable, StatsOption,
+};
+use public::{
+    proto::integration::opentelemetry::proto::{
+        collector::trace::v1::{
+            trace_service_client::TraceServiceClient, ExportTraceServiceRequest,
+        },
+        common::v1::{
+            any_value::Value::{IntValue, StringValue},
+            AnyValue, InstrumentationScope, KeyValue,
+        },
+        resource::v1::Resource,
+        trace::v1::{span::SpanKind, status::StatusCode, ResourceSpans, ScopeSpans, Span, Status},
+    },
+    queue::{Error, Receiver},
+};
+
+const SCOPE_NAME: &str = "deepflow-agent";
+const TRACE_ID_LEN: usize = 16;
+const SPAN_ID_LEN: usize = 8;
+
+#[derive(Debug, Default)]
+pub struct OtlpSenderCounter {
+    pub rx: AtomicU64,
+    pub tx_spans: AtomicU64,
+    pub tx_requests: AtomicU64,
+    pub dropped: AtomicU64,
+}
+
+impl RefCountable for OtlpSenderCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "rx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-spans",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_spans.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_requests.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+fn string_attribute(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(StringValue(value)),
+        }),
+    }
+}
+
+fn int_attribute(key: &str, value: i64) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(IntValue(value)),
+        }),
+    }
+}
+
+fn hash_id(s: &str, seed: u64) -> [u8; 8] {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u64(seed);
+    hasher.write(s.as_bytes());
+    hasher.finish().to_be_bytes()
+}
+
+// W3C and B3 ids are hex strings, shorter ids are left padded with zeros and other
+// formats such as sw8 segment ids are hashed to keep spans of a trace together
+fn to_trace_id(s: &str) -> Vec<u8> {
+    match hex::decode(s) {
+        Ok(id) if !id.is_empty() && id.len() <= TRACE_ID_LEN => {
+            let mut trace_id = vec![0; TRACE_ID_LEN - id.len()];
+            trace_id.extend_from_slice(&id);
+            trace_id
+        }
+        _ => [hash_id(s, 0), hash_id(s, 1)].concat(),
+    }
+}
+
+fn to_span_id(s: &str) -> Vec<u8> {
+    match hex::decode(s) {
+        Ok(id) if !id.is_empty() && id.len() <= SPAN_ID_LEN => {
+            let mut span_id = vec![0; SPAN_ID_LEN - id.len()];
+            span_id.extend_from_slice(&id);
+            span_id
+        }
+        _ => hash_id(s, 0).to_vec(),
+    }
+}
+
+// logs of the same service, process and agent share one resource
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct ResourceKey {
+    service_name: String,
+    process_id: u32,
+    agent_id: u16,
+}
+
+// Converts an l7 flow log into a span, the side of the tap decides the span kind and
+// the service, span ids carried in the request become the parent of the span
+fn to_span(log: MetaAppProto) -> (ResourceKey, Span) {
+    let base = log.base_info;
+    let send_log: L7ProtocolSendLog = match log.send_log {
+        Some(log) => log,
+        None => log.l7_info.into(),
+    };
+
+    let side = base.tap_side as u8;
+    let (kind, process_id, process_kname, ip) = if side & TapSide::Client as u8 != 0 {
+        (
+            SpanKind::Client,
+            base.process_id_0,
+            base.process_kname_0,
+            base.ip_src,
+        )
+    } else if side & TapSide::Server as u8 != 0 {
+        (
+            SpanKind::Server,
+            base.process_id_1,
+            base.process_kname_1,
+            base.ip_dst,
+        )
+    } else {
+        (
+            SpanKind::Internal,
+            base.process_id_1,
+            base.process_kname_1,
+            base.ip_dst,
+        )
+    };
+    let key = ResourceKey {
+        service_name: if process_kname.is_empty() {
+            ip.to_string()
+        } else {
+            process_kname
+        },
+        process_id,
+        agent_id: base.vtap_id,
+    };
+
+    let trace_info = send_log.trace_info.unwrap_or_default();
+    let trace_id = match trace_info.trace_id.as_ref() {
+        Some(id) if !id.is_empty() => to_trace_id(id),
+        _ => rand::random::<[u8; TRACE_ID_LEN]>().to_vec(),
+    };
+    let parent_span_id = trace_info
+        .span_id
+        .as_ref()
+        .or(trace_info.parent_span_id.as_ref())
+        .filter(|id| !id.is_empty())
+        .map(|id| to_span_id(id))
+        .unwrap_or_default();
+
+    let req = send_log.req;
+    let resp = send_log.resp;
+    let name = if !req.endpoint.is_empty() {
+        req.endpoint.clone()
+    } else if !req.resource.is_empty() {
+        req.resource.clone()
+    } else {
+        format!("{:?}", base.head.proto)
+    };
+
+    let mut attributes = vec![
+        string_attribute("l7_protocol", format!("{:?}", base.head.proto)),
+        string_attribute("tap_side", format!("{:?}", base.tap_side)),
+        int_attribute("flow_id", base.flow_id as i64),
+        string_attribute("client.address", base.ip_src.to_string()),
+        int_attribute("client.port", base.port_src as i64),
+        string_attribute("server.address", base.ip_dst.to_string()),
+        int_attribute("server.port", base.port_dst as i64),
+    ];
+    for (key, value) in [
+        ("request_type", req.req_type),
+        ("request_domain", req.domain),
+        ("request_resource", req.resource),
+        ("endpoint", req.endpoint),
+        ("response_exception", resp.exception.clone()),
+        ("response_result", resp.result),
+    ] {
+        if !value.is_empty() {
+            attributes.push(string_attribute(key, value));
+        }
+    }
+    if let Some(code) = resp.code {
+        attributes.push(int_attribute("response_code", code as i64));
+    }
+    attributes.push(string_attribute(
+        "response_status",
+        format!("{:?}", resp.status),
+    ));
+    if let Some(version) = send_log.version.filter(|v| !v.is_empty()) {
+        attributes.push(string_attribute("version", version));
+    }
+    if let Some(ext_info) = send_log.ext_info {
+        for (key, value) in [
+            ("x_request_id_0", ext_info.x_request_id_0),
+            ("x_request_id_1", ext_info.x_request_id_1),
+            ("user_agent", ext_info.user_agent),
+            ("rpc_service", ext_info.rpc_service),
+        ] {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                attributes.push(string_attribute(key, value));
+            }
+        }
+        for kv in ext_info.attributes.unwrap_or_default() {
+            attributes.push(string_attribute(&kv.key, kv.val));
+        }
+    }
+
+    // client errors are failures of the caller only
+    let error = match resp.status {
+        L7ResponseStatus::ServerError => true,
+        L7ResponseStatus::ClientError => kind == SpanKind::Client,
+        _ => false,
+    };
+    let status = if error {
+        Some(Status {
+            message: resp.exception,
+            code: StatusCode::Error as i32,
+            ..Default::default()
+        })
+    } else {
+        None
+    };
+
+    let span = Span {
+        trace_id,
+        span_id: rand::random::<[u8; SPAN_ID_LEN]>().to_vec(),
+        parent_span_id,
+        name,
+        kind: kind as i32,
+        start_time_unix_nano: base.start_time.as_nanos(),
+        end_time_unix_nano: base.end_time.as_nanos().max(base.start_time.as_nanos()),
+        attributes,
+        status,
+        ..Default::default()
+    };
+    (key, span)
+}
+
+fn export_request(
+    logs: impl Iterator<Item = BoxAppProtoLogsData>,
+    resource_attributes: &HashMap<String, String>,
+) -> ExportTraceServiceRequest {
+    let mut resources: HashMap<ResourceKey, Vec<Span>> = HashMap::new();
+    for log in logs {
+        let (key, span) = to_span(*log.0);
+        resources.entry(key).or_default().push(span);
+    }
+    let resource_spans = resources
+        .into_iter()
+        .map(|(key, spans)| {
+            let mut attributes = vec![
+                string_attribute("service.name", key.service_name),
+                int_attribute("deepflow.agent_id", key.agent_id as i64),
+            ];
+            if key.process_id != 0 {
+                attributes.push(int_attribute("process.pid", key.process_id as i64));
+            }
+            for (key, value) in resource_attributes.iter() {
+                attributes.push(string_attribute(key, value.clone()));
+            }
+            ResourceSpans {
+                resource: Some(Resource {
+                    attributes,
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    scope: Some(InstrumentationScope {
+                        name: SCOPE_NAME.to_owned(),
+                        version: env!("CARGO_PKG_VERSION").to_owned(),
+                        ..Default::default()
+                    }),
+                    spans,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }
+        })
+        .collect();
+    ExportTraceServiceRequest { resource_spans }
+}
+
+pub struct OtlpSenderThread {
+    input: Arc<Receiver<BoxAppProtoLogsData>>,
+    config: LogParserAccess,
+    stats: Arc<Collector>,
+    runtime: Arc<Runtime>,
+
+    thread_handle: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+}
+
+impl OtlpSenderThread {
+    pub fn new(
+        input: Receiver<BoxAppProtoLogsData>,
+        config: LogParserAccess,
+        stats: Arc<Collector>,
+        runtime: Arc<Runtime>,
+    ) -> Self {
+        Self {
+            input: Arc::new(input),
+            config,
+            stats,
+            runtime,
+            thread_handle: None,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("otlp sender already started, do nothing.");
+            return;
+        }
+
+        let mut sender = OtlpSender::new(
+            self.input.clone(),
+            self.config.clone(),
+            self.running.clone(),
+            self.runtime.clone(),
+        );
+        self.stats.register_countable(
+            "otlp_sender",
+            Countable::Ref(Arc::downgrade(&sender.counter) as Weak<dyn RefCountable>),
+            vec![StatsOption::Tag("type", "l7_flow_log".to_string())],
+        );
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("otlp-sender".to_owned())
+                .spawn(move || sender.process())
+                .unwrap(),
+        );
+        info!("otlp sender started");
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("otlp sender already stopped, do nothing.");
+            return None;
+        }
+        info!("notified stopping otlp sender");
+        self.thread_handle.take()
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("otlp sender already stopped, do nothing.");
+            return;
+        }
+        info!("stopping otlp sender");
+        let _ = self.thread_handle.take().unwrap().join();
+        info!("stopped otlp sender");
+    }
+}
+
+struct OtlpSender {
+    input: Arc<Receiver<BoxAppProtoLogsData>>,
+    config: LogParserAccess,
+    running: Arc<AtomicBool>,
+    counter: Arc<OtlpSenderCounter>,
+    runtime: Arc<Runtime>,
+
+    exporter: OtlpExporter,
+    client: Option<TraceServiceClient<Channel>>,
+    pending: Vec<BoxAppProtoLogsData>,
+    last_flush_time: Instant,
+}
+
+impl OtlpSender {
+    const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn new(
+        input: Arc<Receiver<BoxAppProtoLogsData>>,
+        config: LogParserAccess,
+        running: Arc<AtomicBool>,
+        runtime: Arc<Runtime>,
+    ) -> Self {
+        let mut sender = Self {
+            input,
+            config,
+            running,
+            counter: Arc::new(OtlpSenderCounter::default()),
+            runtime,
+            exporter: OtlpExporter::default(),
+            client: None,
+            pending: vec![],
+            last_flush_time: Instant::now(),
+        };
+        let exporter = sender.config.load().otlp_exporter.clone();
+        sender.update_exporter(exporter);
+        sender
+    }
+
+    fn update_exporter(&mut self, exporter: OtlpExporter) {
+        self.client = None;
+        if !exporter.endpoint.is_empty() {
+            match Endpoint::from_shared(exporter.endpoint.clone()) {
+                Ok(endpoint) => {
+                    // connects on the first export and reconnects after failures
+                    let _guard = self.runtime.enter();
+                    let channel = endpoint
+                        .connect_timeout(exporter.timeout)
+                        .timeout(exporter.timeout)
+                        .connect_lazy();
+                    self.client = Some(TraceServiceClient::new(channel));
+                }
+                Err(e) => warn!("invalid otlp endpoint {}: {}", exporter.endpoint, e),
+            }
+        }
+        self.exporter = exporter;
+    }
+
+    fn flush(&mut self) {
+        self.last_flush_time = Instant::now();
+        if self.pending.is_empty() {
+            return;
+        }
+        let Some(client) = self.client.as_mut() else {
+            self.counter
+                .dropped
+                .fetch_add(self.pending.len() as u64, Ordering::Relaxed);
+            self.pending.clear();
+            return;
+        };
+        while !self.pending.is_empty() {
+            let n = self.pending.len().min(self.exporter.batch_size);
+            let request =
+                export_request(self.pending.drain(..n), &self.exporter.resource_attributes);
+            match self.runtime.block_on(client.export(request)) {
+                Ok(_) => {
+                    self.counter.tx_requests.fetch_add(1, Ordering::Relaxed);
+                    self.counter.tx_spans.fetch_add(n as u64, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+                        warn!(
+                            "export spans to otlp endpoint {} failed: {}",
+                            self.exporter.endpoint, e
+                        );
+                    }
+                    self.counter.dropped.fetch_add(n as u64, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    fn process(&mut self) {
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        while self.running.load(Ordering::Relaxed) {
+            if self.config.load().otlp_exporter != self.exporter {
+                let exporter = self.config.load().otlp_exporter.clone();
+                info!(
+                    "otlp exporter config change from {:?} to {:?}",
+                    self.exporter, exporter
+                );
+                self.flush();
+                self.update_exporter(exporter);
+            }
+            match self
+                .input
+                .recv_all(&mut batch, Some(Self::QUEUE_READ_TIMEOUT))
+            {
+                Ok(_) => {
+                    self.counter
+                        .rx
+                        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    if !self.exporter.enabled {
+                        batch.clear();
+                        continue;
+                    }
+                    self.pending.append(&mut batch);
+                    if self.pending.len() >= self.exporter.batch_size
+                        || self.last_flush_time.elapsed() >= Self::QUEUE_READ_TIMEOUT
+                    {
+                        self.flush();
+                    }
+                }
+                Err(Error::Timeout) => self.flush(),
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+        }
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    use crate::common::{l7_protocol_info::L7ProtocolInfo, MetaPacket, TaggedFlow, Timestamp};
+    use crate::flow_generator::protocol_logs::{
+        pb_adapter::{L7Request, L7Response, TraceInfo},
+        AppProtoHead, DnsInfo,
+    };
+    use public::l7_protocol::L7Protocol;
+
+    #[test]
+    fn trace_context_ids() {
+        assert_eq!(
+            to_trace_id("0af7651916cd43dd8448eb211c80319c"),
+            hex::decode("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+        // 64 bit b3 trace ids
+        assert_eq!(
+            to_trace_id("8448eb211c80319c"),
+            hex::decode("00000000000000008448eb211c80319c").unwrap()
+        );
+        let id = to_trace_id("a1b2.c3d4.1000");
+        assert_eq!(id.len(), TRACE_ID_LEN);
+        assert_eq!(id, to_trace_id("a1b2.c3d4.1000"));
+        assert_eq!(
+            to_span_id("b7ad6b7169203331"),
+            hex::decode("b7ad6b7169203331").unwrap()
+        );
+        assert_eq!(to_span_id("segment-1").len(), SPAN_ID_LEN);
+    }
+
+    #[test]
+    fn convert_l7_log() {
+        let mut log = MetaAppProto::new(
+            &TaggedFlow::default(),
+            &MetaPacket::default(),
+            L7ProtocolInfo::DnsInfo(DnsInfo::default()),
+            AppProtoHead {
+                proto: L7Protocol::Http1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        log.send_log = Some(L7ProtocolSendLog {
+            req: L7Request {
+                req_type: "GET".to_owned(),
+                resource: "/api/v1/users/1".to_owned(),
+                endpoint: "/api/v1/users/{id}".to_owned(),
+                ..Default::default()
+            },
+            resp: L7Response {
+                status: L7ResponseStatus::ServerError,
+                code: Some(503),
+                ..Default::default()
+            },
+            trace_info: Some(TraceInfo {
+                trace_id: Some("0af7651916cd43dd8448eb211c80319c".to_owned()),
+                span_id: Some("b7ad6b7169203331".to_owned()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        log.base_info.tap_side = TapSide::ServerProcess;
+        log.base_info.ip_dst = Ipv4Addr::new(10, 0, 0, 2).into();
+        log.base_info.process_id_1 = 100;
+        log.base_info.process_kname_1 = "nginx".to_owned();
+        log.base_info.start_time = Timestamp::from_millis(1000);
+        log.base_info.end_time = Timestamp::from_millis(1010);
+
+        let (key, span) = to_span(log.clone());
+        assert_eq!(key.service_name, "nginx");
+        assert_eq!(key.process_id, 100);
+        assert_eq!(span.kind, SpanKind::Server as i32);
+        assert_eq!(span.name, "/api/v1/users/{id}");
+        assert_eq!(
+            span.trace_id,
+            to_trace_id("0af7651916cd43dd8448eb211c80319c")
+        );
+        assert_eq!(span.parent_span_id, to_span_id("b7ad6b7169203331"));
+        assert_eq!(span.span_id.len(), SPAN_ID_LEN);
+        assert_eq!(span.start_time_unix_nano, 1_000_000_000);
+        assert_eq!(span.end_time_unix_nano, 1_010_000_000);
+        assert_eq!(span.status.unwrap().code, StatusCode::Error as i32);
+        assert!(span
+            .attributes
+            .contains(&int_attribute("response_code", 503)));
+
+        let mut attributes = HashMap::new();
+        attributes.insert("deployment.environment".to_owned(), "test".to_owned());
+        let logs = vec![
+            BoxAppProtoLogsData(Box::new(log.clone())),
+            BoxAppProtoLogsData(Box::new(log)),
+        ];
+        let request = export_request(logs.into_iter(), &attributes);
+        assert_eq!(request.resource_spans.len(), 1);
+        let resource_spans = &request.resource_spans[0];
+        assert_eq!(resource_spans.scope_spans[0].spans.len(), 2);
+        let resource = resource_spans.resource.as_ref().unwrap();
+        assert!(resource
+            .attributes
+            .contains(&string_attribute("service.name", "nginx".to_owned())));
+        assert!(resource.attributes.contains(&string_attribute(
+            "deployment.environment",
+            "test".to_owned()
+        )));
+    }
+}
@@ -0,0 +1,497 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Weak,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use arc_swap::access::Access;
+use log::{info, warn};
+use prost::Message;
+use reqwest::{
+    blocking::Client,
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE},
+};
+
+use super::QUEUE_BATCH_SIZE;
+
+use crate::config::{handler::CollectorAccess, PrometheusRemoteWrite};
+use crate::metric::{
+    document::{BoxedDocument, Code, Document},
+    meter::Meter,
+};
+use crate::utils::stats::{
+    Collector, Countable, Counter, CounterType, CounterValue, RefCountable, StatsOption,
+};
+use public::queue::{Error, Receiver};
+
+const METRIC_NAME_LABEL: &str = "__name__";
+const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+// messages of prometheus remote write protocol 1.0, see prompb/remote.proto and
+// prompb/types.proto of prometheus
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    pub labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    pub samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct Label {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct Sample {
+    #[prost(double, tag = "1")]
+    pub value: f64,
+    // milliseconds
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct RemoteWriteSenderCounter {
+    pub rx: AtomicU64,
+    pub tx_series: AtomicU64,
+    pub tx_requests: AtomicU64,
+    pub tx_bytes: AtomicU64,
+    pub dropped: AtomicU64,
+}
+
+impl RefCountable for RemoteWriteSenderCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "rx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-series",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_series.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_requests.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+// Only flow and application metrics are exported, with values in the minute of the
+// document, e.g. deepflow_app_request
+fn meter_values(meter: &Meter) -> Vec<(String, u64)> {
+    match meter {
+        Meter::Flow(_) | Meter::App(_) => meter
+            .values()
+            .into_iter()
+            .map(|(name, value)| (format!("deepflow_{}_{}", meter.kind(), name), value))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+pub(super) fn document_labels(doc: &Document) -> Vec<(&'static str, String)> {
+    let tagger = &doc.tagger;
+    let mut labels = vec![
+        ("agent_id", tagger.vtap_id.to_string()),
+        ("signal_source", format!("{:?}", tagger.signal_source)),
+    ];
+    if tagger.code.contains(Code::IP_PATH) {
+        labels.push(("client_ip", tagger.ip.to_string()));
+        labels.push(("server_ip", tagger.ip1.to_string()));
+    } else if tagger.code.contains(Code::IP) {
+        labels.push(("ip", tagger.ip.to_string()));
+    }
+    if tagger.code.contains(Code::PROTOCOL) {
+        labels.push(("protocol", format!("{:?}", tagger.protocol)));
+    }
+    if tagger.code.contains(Code::SERVER_PORT) {
+        labels.push(("server_port", tagger.server_port.to_string()));
+    }
+    if tagger.code.contains(Code::TAP_SIDE) {
+        labels.push(("tap_side", format!("{:?}", tagger.tap_side)));
+    }
+    if tagger.code.contains(Code::L7_PROTOCOL) {
+        labels.push(("l7_protocol", format!("{:?}", tagger.l7_protocol)));
+    }
+    for (name, value) in [
+        ("endpoint", tagger.endpoint.as_ref()),
+        ("app_service", tagger.otel_service.as_ref()),
+        ("app_instance", tagger.otel_instance.as_ref()),
+    ] {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            labels.push((name, value.clone()));
+        }
+    }
+    labels
+}
+
+// Converts documents into time series with one sample each, labels are sorted by
+// name as required by the protocol
+fn to_time_series(
+    docs: &[BoxedDocument],
+    external_labels: &HashMap<String, String>,
+) -> Vec<TimeSeries> {
+    let mut series = vec![];
+    for doc in docs.iter() {
+        let doc = &doc.0;
+        let mut labels: Vec<Label> = document_labels(doc)
+            .into_iter()
+            .map(|(name, value)| Label {
+                name: name.to_owned(),
+                value,
+            })
+            .collect();
+        for (name, value) in external_labels.iter() {
+            if !labels.iter().any(|l| &l.name == name) {
+                labels.push(Label {
+                    name: name.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        let timestamp = doc.timestamp as i64 * 1000;
+        for (name, value) in meter_values(&doc.meter) {
+            let mut labels = labels.clone();
+            labels.push(Label {
+                name: METRIC_NAME_LABEL.to_owned(),
+                value: name,
+            });
+            labels.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+            series.push(TimeSeries {
+                labels,
+                samples: vec![Sample {
+                    value: value as f64,
+                    timestamp,
+                }],
+            });
+        }
+    }
+    series
+}
+
+pub struct RemoteWriteSenderThread {
+    input: Arc<Receiver<BoxedDocument>>,
+    config: CollectorAccess,
+    stats: Arc<Collector>,
+
+    thread_handle: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+}
+
+impl RemoteWriteSenderThread {
+    pub fn new(
+        input: Receiver<BoxedDocument>,
+        config: CollectorAccess,
+        stats: Arc<Collector>,
+    ) -> Self {
+        Self {
+            input: Arc::new(input),
+            config,
+            stats,
+            thread_handle: None,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("remote write sender already started, do nothing.");
+            return;
+        }
+
+        let mut sender = RemoteWriteSender::new(
+            self.input.clone(),
+            self.config.clone(),
+            self.running.clone(),
+        );
+        self.stats.register_countable(
+            "remote_write_sender",
+            Countable::Ref(Arc::downgrade(&sender.counter) as Weak<dyn RefCountable>),
+            vec![StatsOption::Tag("type", "metrics".to_string())],
+        );
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("remote-write-sender".to_owned())
+                .spawn(move || sender.process())
+                .unwrap(),
+        );
+        info!("remote write sender started");
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("remote write sender already stopped, do nothing.");
+            return None;
+        }
+        info!("notified stopping remote write sender");
+        self.thread_handle.take()
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("remote write sender already stopped, do nothing.");
+            return;
+        }
+        info!("stopping remote write sender");
+        let _ = self.thread_handle.take().unwrap().join();
+        info!("stopped remote write sender");
+    }
+}
+
+struct RemoteWriteSender {
+    input: Arc<Receiver<BoxedDocument>>,
+    config: CollectorAccess,
+    running: Arc<AtomicBool>,
+    counter: Arc<RemoteWriteSenderCounter>,
+
+    exporter: PrometheusRemoteWrite,
+    client: Option<Client>,
+    headers: HeaderMap,
+}
+
+impl RemoteWriteSender {
+    const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn new(
+        input: Arc<Receiver<BoxedDocument>>,
+        config: CollectorAccess,
+        running: Arc<AtomicBool>,
+    ) -> Self {
+        let mut sender = Self {
+            input,
+            config,
+            running,
+            counter: Arc::new(RemoteWriteSenderCounter::default()),
+            exporter: PrometheusRemoteWrite::default(),
+            client: None,
+            headers: HeaderMap::new(),
+        };
+        let exporter = sender.config.load().prometheus_remote_write.clone();
+        sender.update_exporter(exporter);
+        sender
+    }
+
+    fn update_exporter(&mut self, exporter: PrometheusRemoteWrite) {
+        self.client = None;
+        self.headers = HeaderMap::new();
+        self.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-protobuf"),
+        );
+        self.headers
+            .insert(CONTENT_ENCODING, HeaderValue::from_static("snappy"));
+        self.headers.insert(
+            "x-prometheus-remote-write-version",
+            HeaderValue::from_static(REMOTE_WRITE_VERSION),
+        );
+        for (name, value) in exporter.headers.iter() {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    self.headers.insert(name, value);
+                }
+                _ => warn!("invalid remote write header {}: {}", name, value),
+            }
+        }
+        if !exporter.endpoint.is_empty() {
+            match Client::builder().timeout(exporter.timeout).build() {
+                Ok(client) => self.client = Some(client),
+                Err(e) => warn!("build remote write client failed: {}", e),
+            }
+        }
+        self.exporter = exporter;
+    }
+
+    fn send_series(&mut self, series: Vec<TimeSeries>) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        let n = series.len() as u64;
+        let request = WriteRequest { timeseries: series };
+        let body = match snap::raw::Encoder::new().compress_vec(&request.encode_to_vec()) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("compress remote write request failed: {}", e);
+                self.counter.dropped.fetch_add(n, Ordering::Relaxed);
+                return;
+            }
+        };
+        let len = body.len() as u64;
+        let result = client
+            .post(&self.exporter.endpoint)
+            .headers(self.headers.clone())
+            .body(body)
+            .send()
+            .and_then(|r| r.error_for_status());
+        match result {
+            Ok(_) => {
+                self.counter.tx_requests.fetch_add(1, Ordering::Relaxed);
+                self.counter.tx_series.fetch_add(n, Ordering::Relaxed);
+                self.counter.tx_bytes.fetch_add(len, Ordering::Relaxed);
+            }
+            Err(e) => {
+                if self.counter.dropped.load(Ordering::Relaxed) == 0 {
+                    warn!("remote write to {} failed: {}", self.exporter.endpoint, e);
+                }
+                self.counter.dropped.fetch_add(n, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn send_documents(&mut self, docs: &mut Vec<BoxedDocument>) {
+        let mut series = to_time_series(docs, &self.exporter.external_labels);
+        docs.clear();
+        while !series.is_empty() {
+            let rest = series.split_off(series.len().min(self.exporter.batch_size));
+            self.send_series(series);
+            series = rest;
+        }
+    }
+
+    fn process(&mut self) {
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        while self.running.load(Ordering::Relaxed) {
+            if self.config.load().prometheus_remote_write != self.exporter {
+                let exporter = self.config.load().prometheus_remote_write.clone();
+                info!(
+                    "prometheus remote write config change from {:?} to {:?}",
+                    self.exporter, exporter
+                );
+                self.update_exporter(exporter);
+            }
+            match self
+                .input
+                .recv_all(&mut batch, Some(Self::QUEUE_READ_TIMEOUT))
+            {
+                Ok(_) => {
+                    self.counter
+                        .rx
+                        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    if !self.exporter.enabled || self.client.is_none() {
+                        batch.clear();
+                        continue;
+                    }
+                    self.send_documents(&mut batch);
+                }
+                Err(Error::Timeout) => continue,
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    use crate::metric::{
+        document::Tagger,
+        meter::{AppMeter, FlowMeter},
+    };
+    use public::l7_protocol::L7Protocol;
+
+    fn label<'a>(series: &'a TimeSeries, name: &str) -> Option<&'a str> {
+        series
+            .labels
+            .iter()
+            .find(|l| l.name == name)
+            .map(|l| l.value.as_str())
+    }
+
+    #[test]
+    fn documents_to_time_series() {
+        let mut app_meter = AppMeter::default();
+        app_meter.traffic.request = 10;
+        app_meter.anomaly.server_error = 2;
+        let mut doc = Document::new(Meter::App(app_meter));
+        doc.timestamp = 1700000000;
+        doc.tagger = Tagger {
+            code: Code::IP_PATH | Code::SERVER_PORT | Code::L7_PROTOCOL | Code::VTAP_ID,
+            ip: Ipv4Addr::new(10, 0, 0, 1).into(),
+            ip1: Ipv4Addr::new(10, 0, 0, 2).into(),
+            server_port: 8080,
+            vtap_id: 3,
+            l7_protocol: L7Protocol::Http1,
+            endpoint: Some("/api/v1/users".to_owned()),
+            ..Default::default()
+        };
+        let docs = vec![
+            BoxedDocument(Box::new(doc)),
+            BoxedDocument(Box::new(Document::new(Meter::Flow(FlowMeter::default())))),
+        ];
+        let mut external_labels = HashMap::new();
+        external_labels.insert("cluster".to_owned(), "test".to_owned());
+        let series = to_time_series(&docs, &external_labels);
+        assert_eq!(series.len(), 8 + 22);
+
+        let request = series
+            .iter()
+            .find(|s| label(s, METRIC_NAME_LABEL) == Some("deepflow_app_request"))
+            .unwrap();
+        assert_eq!(request.labels[0].name, METRIC_NAME_LABEL);
+        assert!(request.labels.windows(2).all(|w| w[0].name < w[1].name));
+        assert_eq!(label(request, "client_ip"), Some("10.0.0.1"));
+        assert_eq!(label(request, "server_ip"), Some("10.0.0.2"));
+        assert_eq!(label(request, "server_port"), Some("8080"));
+        assert_eq!(label(request, "endpoint"), Some("/api/v1/users"));
+        assert_eq!(label(request, "agent_id"), Some("3"));
+        assert_eq!(label(request, "cluster"), Some("test"));
+        assert_eq!(request.samples[0].value, 10.0);
+        assert_eq!(request.samples[0].timestamp, 1700000000000);
+
+        let request = WriteRequest { timeseries: series };
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .unwrap();
+        let decoded = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+        assert_eq!(WriteRequest::decode(decoded.as_slice()).unwrap(), request);
+    }
+}
@@ -0,0 +1,288 @@
+/*
+ * Copyright (c) 2023 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use log::{debug, warn};
+
+const SEGMENT_SUFFIX: &str = ".spool";
+// frame_size + msg_type
+const MIN_FRAME_SIZE: usize = 5;
+
+struct Segment {
+    path: PathBuf,
+    created: Duration,
+    size: u64,
+}
+
+// Bounded on-disk queue of serialized frames, frames are appended to segment files
+// and read back oldest first. Each frame starts with its big endian frame size as
+// encoded by the uniform sender, so no extra framing is needed.
+pub struct DiskSpool {
+    dir: PathBuf,
+    max_size: u64,
+    max_age: Duration,
+    segment_size: u64,
+
+    segments: VecDeque<Segment>,
+    total_size: u64,
+    // writes go to the last segment
+    writer: Option<File>,
+    // content of the first segment and offset of the next frame
+    reader: Option<(Vec<u8>, usize)>,
+}
+
+impl DiskSpool {
+    const MAX_SEGMENT_SIZE: u64 = 4 << 20;
+
+    pub fn new<P: AsRef<Path>>(dir: P, max_size: u64, max_age: Duration) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        // segments left by last run are drained as well
+        let mut segments = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let Some(created) = name
+                .strip_suffix(SEGMENT_SUFFIX)
+                .and_then(|n| n.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            let size = fs::metadata(&path)?.len();
+            segments.push(Segment {
+                path,
+                created: Duration::from_nanos(created),
+                size,
+            });
+        }
+        segments.sort_by_key(|s| s.created);
+
+        Ok(Self {
+            dir,
+            max_size,
+            max_age,
+            segment_size: (max_size / 8).clamp(1, Self::MAX_SEGMENT_SIZE),
+            total_size: segments.iter().map(|s| s.size).sum(),
+            segments: segments.into(),
+            writer: None,
+            reader: None,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn size(&self) -> u64 {
+        self.total_size
+    }
+
+    // returns bytes evicted to keep the spool in its limits
+    pub fn push(&mut self, frame: &[u8]) -> Result<u64> {
+        let mut evicted = self.expire();
+
+        let rotate = match (self.writer.as_ref(), self.segments.back()) {
+            (Some(_), Some(last)) => last.size + frame.len() as u64 > self.segment_size,
+            _ => true,
+        };
+        if rotate {
+            self.writer.take();
+            let created = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap();
+            // keep segment names unique and ordered
+            let created = match self.segments.back() {
+                Some(last) if last.created >= created => last.created + Duration::from_nanos(1),
+                _ => created,
+            };
+            let path = self
+                .dir
+                .join(format!("{:020}{}", created.as_nanos(), SEGMENT_SUFFIX));
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            self.writer = Some(file);
+            self.segments.push_back(Segment {
+                path,
+                created,
+                size: 0,
+            });
+        }
+
+        self.writer.as_mut().unwrap().write_all(frame)?;
+        self.segments.back_mut().unwrap().size += frame.len() as u64;
+        self.total_size += frame.len() as u64;
+
+        while self.total_size > self.max_size && self.segments.len() > 1 {
+            evicted += self.remove_first();
+        }
+        Ok(evicted)
+    }
+
+    // returns the oldest frame without removing it
+    pub fn peek(&mut self) -> Option<&[u8]> {
+        loop {
+            if self.segments.is_empty() {
+                return None;
+            }
+            if self.reader.is_none() {
+                if self.segments.len() == 1 {
+                    // stop writing the segment being read
+                    self.writer.take();
+                }
+                match fs::read(&self.segments[0].path) {
+                    Ok(content) => self.reader = Some((content, 0)),
+                    Err(e) => {
+                        warn!(
+                            "read spool segment {} failed: {}",
+                            self.segments[0].path.display(),
+                            e
+                        );
+                        self.remove_first();
+                        continue;
+                    }
+                }
+            }
+
+            let (content, offset) = self.reader.as_ref().unwrap();
+            let remaining = &content[*offset..];
+            if remaining.len() < MIN_FRAME_SIZE {
+                if !remaining.is_empty() {
+                    debug!(
+                        "spool segment {} has {} trailing bytes",
+                        self.segments[0].path.display(),
+                        remaining.len()
+                    );
+                }
+                self.remove_first();
+                continue;
+            }
+            let frame_size = u32::from_be_bytes(remaining[..4].try_into().unwrap()) as usize;
+            if frame_size < MIN_FRAME_SIZE || frame_size > remaining.len() {
+                warn!(
+                    "spool segment {} corrupted at offset {}",
+                    self.segments[0].path.display(),
+                    offset
+                );
+                self.remove_first();
+                continue;
+            }
+            let (content, offset) = self.reader.as_ref().unwrap();
+            return Some(&content[*offset..*offset + frame_size]);
+        }
+    }
+
+    // removes the frame returned by last peek
+    pub fn pop(&mut self) {
+        let Some((content, offset)) = self.reader.as_mut() else {
+            return;
+        };
+        let frame_size = u32::from_be_bytes(content[*offset..*offset + 4].try_into().unwrap());
+        *offset += frame_size as usize;
+        if *offset >= content.len() {
+            self.remove_first();
+        }
+    }
+
+    fn expire(&mut self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let mut evicted = 0;
+        while let Some(first) = self.segments.front() {
+            if first.created + self.max_age > now {
+                break;
+            }
+            evicted += self.remove_first();
+        }
+        evicted
+    }
+
+    fn remove_first(&mut self) -> u64 {
+        let Some(segment) = self.segments.pop_front() else {
+            return 0;
+        };
+        if self.segments.is_empty() {
+            self.writer.take();
+        }
+        self.reader.take();
+        if let Err(e) = fs::remove_file(&segment.path) {
+            warn!(
+                "remove spool segment {} failed: {}",
+                segment.path.display(),
+                e
+            );
+        }
+        self.total_size -= segment.size;
+        segment.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(len: usize, fill: u8) -> Vec<u8> {
+        let mut f = vec![fill; len];
+        f[..4].copy_from_slice((len as u32).to_be_bytes().as_slice());
+        f
+    }
+
+    #[test]
+    fn spool_push_and_drain() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = DiskSpool::new(dir.path(), 1 << 20, Duration::from_secs(3600)).unwrap();
+        for i in 0..10 {
+            spool.push(&frame(100 + i, i as u8)).unwrap();
+        }
+        assert_eq!(spool.size(), (100..110).sum::<usize>() as u64);
+
+        // frames are kept across restarts
+        drop(spool);
+        let mut spool = DiskSpool::new(dir.path(), 1 << 20, Duration::from_secs(3600)).unwrap();
+        for i in 0..10 {
+            assert_eq!(spool.peek().unwrap(), frame(100 + i, i as u8).as_slice());
+            spool.pop();
+        }
+        assert!(spool.peek().is_none());
+        assert_eq!(spool.size(), 0);
+    }
+
+    #[test]
+    fn spool_evicts_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = DiskSpool::new(dir.path(), 8 << 10, Duration::from_secs(3600)).unwrap();
+        let mut evicted = 0;
+        for i in 0..64 {
+            evicted += spool.push(&frame(1000, i as u8)).unwrap();
+        }
+        assert!(evicted > 0);
+        assert!(spool.size() <= 8 << 10);
+        // the newest frame is always kept
+        let mut last = None;
+        while let Some(f) = spool.peek() {
+            last = Some(f[4]);
+            spool.pop();
+        }
+        assert_eq!(last, Some(63));
+    }
+}
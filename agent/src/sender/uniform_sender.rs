@@ -31,9 +31,9 @@ use log::{debug, error, info, warn};
 use public::sender::{SendMessageType, Sendable};
 use rand::{thread_rng, RngCore};
 
-use super::{get_sender_id, QUEUE_BATCH_SIZE};
+use super::{get_sender_id, spool::DiskSpool, QUEUE_BATCH_SIZE};
 
-use crate::config::handler::SenderAccess;
+use crate::config::{handler::SenderAccess, SenderSpool};
 use crate::exception::ExceptionHandler;
 use crate::utils::stats::{
     Collector, Countable, Counter, CounterType, CounterValue, RefCountable, StatsOption,
@@ -49,6 +49,10 @@ pub struct SenderCounter {
     pub tx: AtomicU64,
     pub tx_bytes: AtomicU64,
     pub dropped: AtomicU64,
+    pub spooled: AtomicU64,
+    pub unspooled: AtomicU64,
+    pub spool_evicted_bytes: AtomicU64,
+    pub spool_failed: AtomicU64,
 }
 
 impl RefCountable for SenderCounter {
@@ -74,6 +78,26 @@ impl RefCountable for SenderCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "spooled",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.spooled.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "unspooled",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.unspooled.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "spool-evicted-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.spool_evicted_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "spool-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.spool_failed.swap(0, Ordering::Relaxed)),
+            ),
         ]
     }
 }
@@ -89,6 +113,11 @@ struct Header {
 }
 
 impl Header {
+    // frame_size + msg_type + version + sequence + vtap_id
+    const LEN: usize = 4 + 1 + 4 + 8 + 2;
+    // set on msg_type when data after the header is compressed by zstd
+    const MSG_TYPE_FLAG_ZSTD: u8 = 0x80;
+
     fn encode(&self, buffer: &mut Vec<u8>) {
         buffer.extend_from_slice(self.frame_size.to_be_bytes().as_slice());
         buffer.push(self.msg_type.into());
@@ -161,6 +190,25 @@ impl<T: Sendable> Encoder<T> {
         self.buffer[0..4].copy_from_slice(frame_size.to_be_bytes().as_slice());
     }
 
+    // compress data after the header, the server is informed by the flag in msg_type
+    pub fn compress(&mut self, level: i32) {
+        if matches!(
+            self.header.msg_type,
+            SendMessageType::Compress | SendMessageType::Syslog | SendMessageType::Statsd
+        ) || self.buffer.len() <= Header::LEN
+        {
+            return;
+        }
+        match zstd::bulk::compress(&self.buffer[Header::LEN..], level) {
+            Ok(compressed) => {
+                self.buffer.truncate(Header::LEN);
+                self.buffer.extend_from_slice(compressed.as_slice());
+                self.buffer[4] |= Header::MSG_TYPE_FLAG_ZSTD;
+            }
+            Err(e) => debug!("{} zstd compress failed {}", self.id, e),
+        }
+    }
+
     pub fn update_header_vtap_id(&mut self, vtap_id: u16) {
         self.header.vtap_id = vtap_id;
     }
@@ -295,6 +343,10 @@ pub struct UniformSender<T> {
     pre_file_path: String,
     written_size: u64,
 
+    // buffers frames on disk when the server is unreachable
+    spool: Option<DiskSpool>,
+    spool_config: SenderSpool,
+
     cached: bool,
 }
 
@@ -302,6 +354,7 @@ impl<T: Sendable> UniformSender<T> {
     const TCP_WRITE_TIMEOUT: u64 = 3; // s
     const QUEUE_READ_TIMEOUT: u64 = 3; // s
     const DEFAULT_RECONNECT_INTERVAL: u8 = 10; // s
+    const SPOOL_DRAIN_BATCH: usize = 64;
 
     pub fn new(
         id: usize,
@@ -335,6 +388,8 @@ impl<T: Sendable> UniformSender<T> {
             file_path: String::new(),
             pre_file_path: String::new(),
             written_size: 0,
+            spool: None,
+            spool_config: SenderSpool::default(),
             cached,
         }
     }
@@ -365,15 +420,105 @@ impl<T: Sendable> UniformSender<T> {
         }
     }
 
+    fn update_spool(&mut self) {
+        let config = self.config.load();
+        if self.spool_config == config.sender_spool {
+            return;
+        }
+        self.spool_config = config.sender_spool.clone();
+        self.spool.take();
+        if !self.spool_config.enabled {
+            info!("{} sender disk spool disabled", self.name);
+            return;
+        }
+
+        let dir = Path::new(&self.spool_config.dir).join(format!("{}-{}", self.name, self.id));
+        match DiskSpool::new(
+            &dir,
+            (self.spool_config.max_size as u64) << 20,
+            self.spool_config.max_age,
+        ) {
+            Ok(spool) => {
+                info!(
+                    "{} sender disk spool enabled in {}, {} bytes spooled",
+                    self.name,
+                    dir.display(),
+                    spool.size()
+                );
+                self.spool = Some(spool);
+            }
+            Err(e) => warn!(
+                "{} sender create disk spool in {} failed: {}",
+                self.name,
+                dir.display(),
+                e
+            ),
+        }
+    }
+
+    fn spool_buffer(&mut self, buffer: &[u8]) {
+        let Some(spool) = self.spool.as_mut() else {
+            self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        match spool.push(buffer) {
+            Ok(evicted) => {
+                self.counter.spooled.fetch_add(1, Ordering::Relaxed);
+                self.counter
+                    .spool_evicted_bytes
+                    .fetch_add(evicted, Ordering::Relaxed);
+            }
+            Err(e) => {
+                if self.counter.spool_failed.load(Ordering::Relaxed) == 0 {
+                    warn!(
+                        "{} sender write disk spool in {} failed: {}",
+                        self.name,
+                        spool.dir().display(),
+                        e
+                    );
+                }
+                self.counter.spool_failed.fetch_add(1, Ordering::Relaxed);
+                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // send spooled frames after the connection is recovered
+    fn drain_spool(&mut self) {
+        let Some(mut spool) = self.spool.take() else {
+            return;
+        };
+        for _ in 0..Self::SPOOL_DRAIN_BATCH {
+            let Some(frame) = spool.peek() else {
+                break;
+            };
+            if !self.send_buffer(frame) {
+                break;
+            }
+            spool.pop();
+            self.counter.unspooled.fetch_add(1, Ordering::Relaxed);
+        }
+        self.spool = Some(spool);
+    }
+
     fn flush_encoder(&mut self) {
+        self.update_spool();
         if self.encoder.buffer_len() > 0 {
+            if let Some(level) = self.config.load().zstd_level {
+                self.encoder.compress(level);
+            }
             self.encoder.set_header_frame_size();
             let buffer = self.encoder.get_buffer();
-            self.send_buffer(buffer.as_slice());
+            if !self.send_buffer(buffer.as_slice()) {
+                self.spool_buffer(buffer.as_slice());
+                return;
+            }
         }
+        self.drain_spool();
     }
 
-    fn send_buffer(&mut self, buffer: &[u8]) {
+    // returns false if the buffer is not sent
+    fn send_buffer(&mut self, buffer: &[u8]) -> bool {
         if self.reconnect || self.tcp_stream.is_none() {
             if let Some(t) = self.tcp_stream.take() {
                 if let Err(e) = t.shutdown(Shutdown::Both) {
@@ -388,7 +533,7 @@ impl<T: Sendable> UniformSender<T> {
                 self.last_reconnect = now;
             }
             if self.last_reconnect + Duration::from_secs(self.reconnect_interval as u64) > now {
-                return;
+                return false;
             }
 
             self.last_reconnect = now;
@@ -402,7 +547,7 @@ impl<T: Sendable> UniformSender<T> {
                         self.name, e
                     );
                     self.tcp_stream.take();
-                    return;
+                    return false;
                 }
                 info!(
                     "{} sender tcp connection to {}:{} succeed.",
@@ -422,11 +567,10 @@ impl<T: Sendable> UniformSender<T> {
                         );
                     }
                 }
-                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
                 // reconnect after waiting 10 seconds + random 5 seconds to prevent frequent reconnection
                 self.reconnect_interval =
                     Self::DEFAULT_RECONNECT_INTERVAL + (thread_rng().next_u64() % 5) as u8;
-                return;
+                return false;
             }
         }
 
@@ -443,7 +587,7 @@ impl<T: Sendable> UniformSender<T> {
                         self.counter
                             .tx_bytes
                             .fetch_add(buffer.len() as u64, Ordering::Relaxed);
-                        break;
+                        return true;
                     }
                 }
                 Err(e) if e.kind() == ErrorKind::WouldBlock => {
@@ -458,9 +602,8 @@ impl<T: Sendable> UniformSender<T> {
                             self.name, self.dst_ip, self.dst_port, e
                         );
                     }
-                    self.counter.dropped.fetch_add(1, Ordering::Relaxed);
                     self.tcp_stream.take();
-                    break;
+                    return false;
                 }
             };
         }
@@ -40,7 +40,7 @@ use tokio::sync::broadcast;
 #[cfg(target_os = "linux")]
 use crate::platform::{
     kubernetes::{GenericPoller, Poller, SidecarPoller},
-    prometheus::targets::TargetsWatcher,
+    prometheus::{scraper::PrometheusScraper, targets::TargetsWatcher},
     ApiWatcher, LibvirtXmlExtractor,
 };
 use crate::{
@@ -71,19 +71,27 @@ use crate::{
     },
     exception::ExceptionHandler,
     flow_generator::{
-        protocol_logs::BoxAppProtoLogsData, protocol_logs::SessionAggregator, PacketSequenceParser,
+        pool::{set_string_pool_limit, StringPoolCountable},
+        protocol_logs::BoxAppProtoLogsData,
+        protocol_logs::L7LogDeduplicator,
+        protocol_logs::SessionAggregator,
+        PacketSequenceParser,
     },
     handler::{NpbBuilder, PacketHandlerBuilder},
     integration_collector::{
         BoxedPrometheusExtra, MetricServer, OpenTelemetry, OpenTelemetryCompressed, Profile,
-        TelegrafMetric,
+        StatsdServer, TelegrafMetric,
     },
     metric::document::BoxedDocument,
     monitor::Monitor,
     platform::PlatformSynchronizer,
     policy::{Policy, PolicySetter},
     rpc::{Session, Synchronizer, DEFAULT_TIMEOUT},
-    sender::{npb_sender::NpbArpTable, uniform_sender::UniformSenderThread},
+    sender::{
+        clickhouse_sender::ClickhouseSenderThread, ipfix_sender::IpfixSenderThread,
+        kafka_sender::KafkaSenderThread, npb_sender::NpbArpTable, otlp_sender::OtlpSenderThread,
+        remote_write_sender::RemoteWriteSenderThread, uniform_sender::UniformSenderThread,
+    },
     utils::{
         cgroups::{is_kernel_available_for_cgroups, Cgroups},
         command::get_hostname,
@@ -101,7 +109,7 @@ use crate::{
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use crate::{
     ebpf_dispatcher::EbpfCollector,
-    platform::SocketSynchronizer,
+    platform::{ConntrackSynchronizer, SocketSynchronizer},
     utils::{environment::core_file_check, lru::Lru},
 };
 
@@ -1196,6 +1204,13 @@ pub struct AgentComponents {
     pub collectors: Vec<CollectorThread>,
     pub l7_collectors: Vec<L7CollectorThread>,
     pub l4_flow_uniform_sender: UniformSenderThread<BoxedTaggedFlow>,
+    pub ipfix_sender: IpfixSenderThread,
+    pub otlp_sender: OtlpSenderThread,
+    pub remote_write_sender: RemoteWriteSenderThread,
+    pub kafka_log_sender: KafkaSenderThread<BoxAppProtoLogsData>,
+    pub kafka_metrics_sender: KafkaSenderThread<BoxedDocument>,
+    pub clickhouse_log_sender: ClickhouseSenderThread<BoxAppProtoLogsData>,
+    pub clickhouse_metrics_sender: ClickhouseSenderThread<BoxedDocument>,
     pub metrics_uniform_sender: UniformSenderThread<BoxedDocument>,
     pub l7_flow_uniform_sender: UniformSenderThread<BoxAppProtoLogsData>,
     pub stats_sender: UniformSenderThread<ArcBatch>,
@@ -1204,14 +1219,19 @@ pub struct AgentComponents {
     pub kubernetes_poller: Arc<GenericPoller>,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub socket_synchronizer: SocketSynchronizer,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub conntrack_synchronizer: ConntrackSynchronizer,
     #[cfg(target_os = "linux")]
     pub prometheus_targets_watcher: Arc<TargetsWatcher>,
+    #[cfg(target_os = "linux")]
+    pub prometheus_scraper: PrometheusScraper,
     pub debugger: Debugger,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub ebpf_collector: Option<Box<EbpfCollector>>,
     pub running: AtomicBool,
     pub stats_collector: Arc<stats::Collector>,
     pub external_metrics_server: MetricServer,
+    pub statsd_server: StatsdServer,
     pub otel_uniform_sender: UniformSenderThread<OpenTelemetry>,
     pub prometheus_uniform_sender: UniformSenderThread<BoxedPrometheusExtra>,
     pub telegraf_uniform_sender: UniformSenderThread<TelegrafMetric>,
@@ -1243,7 +1263,11 @@ impl AgentComponents {
         flow_receiver: queue::Receiver<Arc<BatchedBox<TaggedFlow>>>,
         toa_info_sender: DebugSender<Box<(SocketAddr, SocketAddr)>>,
         l4_flow_aggr_sender: Option<DebugSender<BoxedTaggedFlow>>,
+        ipfix_sender: Option<DebugSender<BoxedTaggedFlow>>,
         metrics_sender: DebugSender<BoxedDocument>,
+        remote_write_sender: Option<DebugSender<BoxedDocument>>,
+        kafka_sender: Option<DebugSender<BoxedDocument>>,
+        clickhouse_sender: Option<DebugSender<BoxedDocument>>,
         metrics_type: MetricsType,
         config_handler: &ConfigHandler,
         queue_debugger: &QueueDebugger,
@@ -1272,6 +1296,7 @@ impl AgentComponents {
                 id,                                   // id
                 l4_log_receiver,                      // input
                 l4_flow_aggr_sender.unwrap().clone(), // output
+                ipfix_sender,
                 config_handler.collector(),
                 synchronizer.ntp_diff(),
             );
@@ -1346,6 +1371,7 @@ impl AgentComponents {
             config_handler.collector(),
             synchronizer.ntp_diff(),
             stats_collector.clone(),
+            l7_log_deduplicator,
         );
 
         let (mut second_collector, mut minute_collector) = (None, None);
@@ -1354,6 +1380,9 @@ impl AgentComponents {
                 id as u32,
                 second_receiver,
                 metrics_sender.clone(),
+                None,
+                None,
+                None,
                 MetricsType::SECOND,
                 second_quadruple_tolerable_delay as u32 + COMMON_DELAY, // qg processing is delayed and requires the collector component to increase the window size
                 &stats_collector,
@@ -1366,6 +1395,9 @@ impl AgentComponents {
                 id as u32,
                 minute_receiver,
                 metrics_sender,
+                remote_write_sender,
+                kafka_sender,
+                clickhouse_sender,
                 MetricsType::MINUTE,
                 minute_quadruple_tolerable_delay as u32 + COMMON_DELAY, // qg processing is delayed and requires the collector component to increase the window size
                 &stats_collector,
@@ -1387,10 +1419,14 @@ impl AgentComponents {
         stats_collector: Arc<stats::Collector>,
         l7_stats_receiver: queue::Receiver<BatchedBox<L7Stats>>,
         metrics_sender: DebugSender<BoxedDocument>,
+        remote_write_sender: Option<DebugSender<BoxedDocument>>,
+        kafka_sender: Option<DebugSender<BoxedDocument>>,
+        clickhouse_sender: Option<DebugSender<BoxedDocument>>,
         metrics_type: MetricsType,
         config_handler: &ConfigHandler,
         queue_debugger: &QueueDebugger,
         synchronizer: &Arc<Synchronizer>,
+        l7_log_deduplicator: Arc<L7LogDeduplicator>,
     ) -> L7CollectorThread {
         let yaml_config = &config_handler.candidate_config.yaml_config;
 
@@ -1462,6 +1498,9 @@ impl AgentComponents {
                 id as u32,
                 l7_second_receiver,
                 metrics_sender.clone(),
+                None,
+                None,
+                None,
                 MetricsType::SECOND,
                 second_quadruple_tolerable_delay as u32 + COMMON_DELAY, // qg processing is delayed and requires the collector component to increase the window size
                 &stats_collector,
@@ -1474,6 +1513,9 @@ impl AgentComponents {
                 id as u32,
                 l7_minute_receiver,
                 metrics_sender,
+                remote_write_sender,
+                kafka_sender,
+                clickhouse_sender,
                 MetricsType::MINUTE,
                 minute_quadruple_tolerable_delay as u32 + COMMON_DELAY, // qg processing is delayed and requires the collector component to increase the window size
                 &stats_collector,
@@ -1584,6 +1626,15 @@ impl AgentComponents {
         );
         synchronizer.add_flow_acl_listener(Box::new(policy_setter));
         policy_setter.set_memory_limit(max_memory);
+        // recycled strings of l7 logs take at most 1/64 of max memory
+        if max_memory > 0 {
+            set_string_pool_limit(max_memory as usize / 64);
+        }
+        stats_collector.register_countable(
+            "string_pool",
+            Countable::Owned(Box::new(StringPoolCountable)),
+            Default::default(),
+        );
 
         // TODO: collector enabled
         // TODO: packet handler builders
@@ -1655,6 +1706,9 @@ impl AgentComponents {
                 yaml_config.toa_lru_cache_size,
             ))),
         );
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let conntrack_synchronizer =
+            ConntrackSynchronizer::new(config_handler.platform(), policy_setter);
 
         let rx_leaky_bucket = Arc::new(LeakyBucket::new(match candidate_config.tap_mode {
             TapMode::Analyzer => None,
@@ -1704,6 +1758,141 @@ impl AgentComponents {
             true,
         );
 
+        let ipfix_queue_name = "3-flowlog-to-ipfix-sender";
+        let (ipfix_sender, ipfix_receiver, counter) = queue::bounded_with_debug(
+            yaml_config.flow_sender_queue_size as usize,
+            ipfix_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            "queue",
+            Countable::Owned(Box::new(counter)),
+            vec![StatsOption::Tag("module", ipfix_queue_name.to_string())],
+        );
+        let ipfix_sender_thread = IpfixSenderThread::new(
+            ipfix_receiver,
+            config_handler.collector(),
+            stats_collector.clone(),
+        );
+
+        let otlp_queue_name = "3-protolog-to-otlp-sender";
+        let (otlp_sender, otlp_receiver, counter) = queue::bounded_with_debug(
+            yaml_config.flow_sender_queue_size as usize,
+            otlp_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            "queue",
+            Countable::Owned(Box::new(counter)),
+            vec![StatsOption::Tag("module", otlp_queue_name.to_string())],
+        );
+        let otlp_sender_thread = OtlpSenderThread::new(
+            otlp_receiver,
+            config_handler.log_parser(),
+            stats_collector.clone(),
+            runtime.clone(),
+        );
+
+        let remote_write_queue_name = "3-doc-to-remote-write-sender";
+        let (remote_write_sender, remote_write_receiver, counter) = queue::bounded_with_debug(
+            yaml_config.collector_sender_queue_size,
+            remote_write_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            "queue",
+            Countable::Owned(Box::new(counter)),
+            vec![StatsOption::Tag(
+                "module",
+                remote_write_queue_name.to_string(),
+            )],
+        );
+        let remote_write_sender_thread = RemoteWriteSenderThread::new(
+            remote_write_receiver,
+            config_handler.collector(),
+            stats_collector.clone(),
+        );
+
+        let kafka_log_queue_name = "3-protolog-to-kafka-sender";
+        let (kafka_log_sender, kafka_log_receiver, counter) = queue::bounded_with_debug(
+            yaml_config.flow_sender_queue_size as usize,
+            kafka_log_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            "queue",
+            Countable::Owned(Box::new(counter)),
+            vec![StatsOption::Tag("module", kafka_log_queue_name.to_string())],
+        );
+        let kafka_log_sender_thread = KafkaSenderThread::new(
+            kafka_log_receiver,
+            config_handler.collector(),
+            stats_collector.clone(),
+        );
+
+        let kafka_metrics_queue_name = "3-doc-to-kafka-sender";
+        let (kafka_metrics_sender, kafka_metrics_receiver, counter) = queue::bounded_with_debug(
+            yaml_config.collector_sender_queue_size,
+            kafka_metrics_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            "queue",
+            Countable::Owned(Box::new(counter)),
+            vec![StatsOption::Tag(
+                "module",
+                kafka_metrics_queue_name.to_string(),
+            )],
+        );
+        let kafka_metrics_sender_thread = KafkaSenderThread::new(
+            kafka_metrics_receiver,
+            config_handler.collector(),
+            stats_collector.clone(),
+        );
+
+        let clickhouse_log_queue_name = "3-protolog-to-clickhouse-sender";
+        let (clickhouse_log_sender, clickhouse_log_receiver, counter) = queue::bounded_with_debug(
+            yaml_config.flow_sender_queue_size as usize,
+            clickhouse_log_queue_name,
+            &queue_debugger,
+        );
+        stats_collector.register_countable(
+            "queue",
+            Countable::Owned(Box::new(counter)),
+            vec![StatsOption::Tag(
+                "module",
+                clickhouse_log_queue_name.to_string(),
+            )],
+        );
+        let clickhouse_log_sender_thread = ClickhouseSenderThread::new(
+            clickhouse_log_receiver,
+            config_handler.collector(),
+            stats_collector.clone(),
+            runtime.clone(),
+        );
+
+        let clickhouse_metrics_queue_name = "3-doc-to-clickhouse-sender";
+        let (clickhouse_metrics_sender, clickhouse_metrics_receiver, counter) =
+            queue::bounded_with_debug(
+                yaml_config.collector_sender_queue_size,
+                clickhouse_metrics_queue_name,
+                &queue_debugger,
+            );
+        stats_collector.register_countable(
+            "queue",
+            Countable::Owned(Box::new(counter)),
+            vec![StatsOption::Tag(
+                "module",
+                clickhouse_metrics_queue_name.to_string(),
+            )],
+        );
+        let clickhouse_metrics_sender_thread = ClickhouseSenderThread::new(
+            clickhouse_metrics_receiver,
+            config_handler.collector(),
+            stats_collector.clone(),
+            runtime.clone(),
+        );
+
         let metrics_queue_name = "3-doc-to-collector-sender";
         let (metrics_sender, metrics_receiver, counter) = queue::bounded_with_debug(
             yaml_config.collector_sender_queue_size,
@@ -1724,6 +1913,8 @@ impl AgentComponents {
             true,
         );
 
+        // shared by session aggregators and l7 collectors of all dispatchers
+        let l7_log_deduplicator = Arc::new(L7LogDeduplicator::default());
         let proto_log_queue_name = "2-protolog-to-collector-sender";
         let (proto_log_sender, proto_log_receiver, counter) = queue::bounded_with_debug(
             yaml_config.flow_sender_queue_size,
@@ -1940,9 +2131,13 @@ impl AgentComponents {
             let (session_aggr, counter) = SessionAggregator::new(
                 log_receiver,
                 proto_log_sender.clone(),
+                Some(otlp_sender.clone()),
+                Some(kafka_log_sender.clone()),
+                Some(clickhouse_log_sender.clone()),
                 i as u32,
                 config_handler.log_parser(),
                 synchronizer.ntp_diff(),
+                l7_log_deduplicator.clone(),
             );
             stats_collector.register_countable(
                 "l7_session_aggr",
@@ -2035,6 +2230,16 @@ impl AgentComponents {
                     #[cfg(any(target_os = "linux", target_os = "android"))]
                     af_packet_version: config_handler.candidate_config.dispatcher.af_packet_version,
                     packet_blocks: config_handler.candidate_config.dispatcher.af_packet_blocks,
+                    packet_max_blocks: match config_handler
+                        .candidate_config
+                        .dispatcher
+                        .af_packet_max_blocks
+                    {
+                        0 => 0,
+                        // memory limit is shared by dispatchers
+                        max_blocks => (max_blocks / local_dispatcher_count)
+                            .max(config_handler.candidate_config.dispatcher.af_packet_blocks),
+                    },
                     tap_mode: candidate_config.tap_mode,
                     tap_mac_script: yaml_config.tap_mac_script.clone(),
                     is_ipv6: ctrl_ip.is_ipv6(),
@@ -2043,6 +2248,44 @@ impl AgentComponents {
                     controller_port: static_config.controller_port,
                     controller_tls_port: static_config.controller_tls_port,
                     libpcap_enabled: yaml_config.libpcap_enabled,
+                    #[cfg(any(target_os = "linux", target_os = "android"))]
+                    pf_ring_enabled: yaml_config.pf_ring_enabled,
+                    #[cfg(any(target_os = "linux", target_os = "android"))]
+                    pf_ring_zc_enabled: yaml_config.pf_ring_zc_enabled,
+                    dpdk_conf: dispatcher::DpdkRingPortConf {
+                        enabled: yaml_config.ovs_dpdk_enabled,
+                        core_id: yaml_config.dpdk_pmd_core_id,
+                        ring_names: yaml_config.dispatcher_dpdk_rings(i, local_dispatcher_count),
+                        file_prefix: yaml_config.dpdk_file_prefix.clone(),
+                    },
+                    erspan_timestamp_enabled: yaml_config.erspan_timestamp_enabled,
+                    hardware_timestamp_enabled: yaml_config.afpacket_hardware_timestamp,
+                    sflow_ports: yaml_config.xflow_collector.sflow_ingestion_ports(),
+                    #[cfg(any(target_os = "linux", target_os = "android"))]
+                    pcap_replay: if yaml_config.pcap_replay.files.is_empty() {
+                        None
+                    } else {
+                        Some(dispatcher::PcapReplayOptions {
+                            files: yaml_config
+                                .pcap_replay
+                                .dispatcher_files(i, local_dispatcher_count),
+                            speed: yaml_config.pcap_replay.speed,
+                            preserve_timestamps: yaml_config.pcap_replay.preserve_timestamps,
+                        })
+                    },
+                    capture_bpf: yaml_config
+                        .dispatcher_capture_bpf
+                        .get(i)
+                        .cloned()
+                        .unwrap_or_default(),
+                    packet_dedup: if yaml_config.packet_dedup.enabled {
+                        Some(dispatcher::PacketDedupOptions {
+                            window: yaml_config.packet_dedup.window,
+                            capacity: yaml_config.packet_dedup.capacity,
+                        })
+                    } else {
+                        None
+                    },
                     snap_len: config_handler
                         .candidate_config
                         .dispatcher
@@ -2115,7 +2358,11 @@ impl AgentComponents {
                 flow_receiver,
                 toa_sender.clone(),
                 Some(l4_flow_aggr_sender.clone()),
+                Some(ipfix_sender.clone()),
                 metrics_sender.clone(),
+                Some(remote_write_sender.clone()),
+                Some(kafka_metrics_sender.clone()),
+                Some(clickhouse_metrics_sender.clone()),
                 MetricsType::SECOND | MetricsType::MINUTE,
                 config_handler,
                 &queue_debugger,
@@ -2127,10 +2374,14 @@ impl AgentComponents {
                 stats_collector.clone(),
                 l7_stats_receiver,
                 metrics_sender.clone(),
+                Some(remote_write_sender.clone()),
+                Some(kafka_metrics_sender.clone()),
+                Some(clickhouse_metrics_sender.clone()),
                 MetricsType::SECOND | MetricsType::MINUTE,
                 config_handler,
                 &queue_debugger,
                 &synchronizer,
+                l7_log_deduplicator.clone(),
             );
             l7_collectors.push(l7_collector);
         }
@@ -2218,7 +2469,11 @@ impl AgentComponents {
                 flow_receiver,
                 toa_sender.clone(),
                 None,
+                None,
                 metrics_sender.clone(),
+                Some(remote_write_sender.clone()),
+                Some(kafka_metrics_sender.clone()),
+                Some(clickhouse_metrics_sender.clone()),
                 MetricsType::SECOND | MetricsType::MINUTE,
                 config_handler,
                 &queue_debugger,
@@ -2240,9 +2495,13 @@ impl AgentComponents {
             let (session_aggr, counter) = SessionAggregator::new(
                 log_receiver,
                 proto_log_sender.clone(),
+                Some(otlp_sender.clone()),
+                Some(kafka_log_sender.clone()),
+                Some(clickhouse_log_sender.clone()),
                 ebpf_dispatcher_id as u32,
                 config_handler.log_parser(),
                 synchronizer.ntp_diff(),
+                l7_log_deduplicator.clone(),
             );
             stats_collector.register_countable(
                 "l7_session_aggr",
@@ -2256,10 +2515,14 @@ impl AgentComponents {
                 stats_collector.clone(),
                 l7_stats_receiver,
                 metrics_sender.clone(),
+                Some(remote_write_sender.clone()),
+                Some(kafka_metrics_sender.clone()),
+                Some(clickhouse_metrics_sender.clone()),
                 MetricsType::SECOND | MetricsType::MINUTE,
                 config_handler,
                 &queue_debugger,
                 &synchronizer,
+                l7_log_deduplicator.clone(),
             );
             l7_collectors.push(l7_collector);
             match EbpfCollector::new(
@@ -2333,10 +2596,14 @@ impl AgentComponents {
             stats_collector.clone(),
             l7_stats_receiver,
             metrics_sender.clone(),
+            Some(remote_write_sender.clone()),
+            Some(kafka_metrics_sender.clone()),
+            Some(clickhouse_metrics_sender.clone()),
             MetricsType::SECOND | MetricsType::MINUTE,
             config_handler,
             &queue_debugger,
             &synchronizer,
+            l7_log_deduplicator.clone(),
         );
         l7_collectors.push(l7_collector);
 
@@ -2406,6 +2673,27 @@ impl AgentComponents {
             true,
         );
 
+        #[cfg(target_os = "linux")]
+        let prometheus_scraper = PrometheusScraper::new(
+            runtime.clone(),
+            config_handler.platform(),
+            prometheus_sender.clone(),
+            stats_collector.clone(),
+        );
+
+        let (statsd_server, statsd_counter) = StatsdServer::new(
+            candidate_config.yaml_config.statsd_config.clone(),
+            prometheus_sender.clone(),
+            candidate_config
+                .yaml_config
+                .external_metric_integration_disabled,
+        );
+        stats_collector.register_countable(
+            "statsd_server",
+            Countable::Owned(Box::new(statsd_counter)),
+            Default::default(),
+        );
+
         let (external_metrics_server, external_metrics_counter) = MetricServer::new(
             runtime.clone(),
             otel_sender,
@@ -2464,6 +2752,13 @@ impl AgentComponents {
             collectors,
             l7_collectors,
             l4_flow_uniform_sender,
+            ipfix_sender: ipfix_sender_thread,
+            otlp_sender: otlp_sender_thread,
+            remote_write_sender: remote_write_sender_thread,
+            kafka_log_sender: kafka_log_sender_thread,
+            kafka_metrics_sender: kafka_metrics_sender_thread,
+            clickhouse_log_sender: clickhouse_log_sender_thread,
+            clickhouse_metrics_sender: clickhouse_metrics_sender_thread,
             metrics_uniform_sender,
             l7_flow_uniform_sender,
             stats_sender,
@@ -2472,8 +2767,12 @@ impl AgentComponents {
             kubernetes_poller,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             socket_synchronizer,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            conntrack_synchronizer,
             #[cfg(target_os = "linux")]
             prometheus_targets_watcher,
+            #[cfg(target_os = "linux")]
+            prometheus_scraper,
             debugger,
             session_aggrs,
             #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -2481,6 +2780,7 @@ impl AgentComponents {
             stats_collector,
             running: AtomicBool::new(false),
             external_metrics_server,
+            statsd_server,
             exception_handler,
             max_memory,
             otel_uniform_sender,
@@ -2513,6 +2813,8 @@ impl AgentComponents {
 
         #[cfg(any(target_os = "linux", target_os = "android"))]
         self.socket_synchronizer.start();
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        self.conntrack_synchronizer.start();
         #[cfg(target_os = "linux")]
         {
             if crate::utils::environment::is_tt_pod(self.config.trident_type) {
@@ -2526,6 +2828,13 @@ impl AgentComponents {
         self.metrics_uniform_sender.start();
         self.l7_flow_uniform_sender.start();
         self.l4_flow_uniform_sender.start();
+        self.ipfix_sender.start();
+        self.otlp_sender.start();
+        self.remote_write_sender.start();
+        self.kafka_log_sender.start();
+        self.kafka_metrics_sender.start();
+        self.clickhouse_log_sender.start();
+        self.clickhouse_metrics_sender.start();
 
         // Enterprise Edition Feature: packet-sequence
         self.packet_sequence_uniform_sender.start();
@@ -2581,6 +2890,9 @@ impl AgentComponents {
             if self.config.metric_server.enabled {
                 self.external_metrics_server.start();
             }
+            self.statsd_server.start();
+            #[cfg(target_os = "linux")]
+            self.prometheus_scraper.start();
             self.pcap_batch_uniform_sender.start();
         }
         self.handler_builders.iter().for_each(|x| {
@@ -2609,10 +2921,13 @@ impl AgentComponents {
 
         #[cfg(any(target_os = "linux", target_os = "android"))]
         self.socket_synchronizer.stop();
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        self.conntrack_synchronizer.stop();
         #[cfg(target_os = "linux")]
         {
             self.kubernetes_poller.stop();
             self.prometheus_targets_watcher.stop();
+            self.prometheus_scraper.stop();
         }
 
         for q in self.collectors.iter_mut() {
@@ -2632,6 +2947,27 @@ impl AgentComponents {
         if let Some(h) = self.l4_flow_uniform_sender.notify_stop() {
             join_handles.push(h);
         }
+        if let Some(h) = self.ipfix_sender.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.otlp_sender.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.remote_write_sender.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.kafka_log_sender.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.kafka_metrics_sender.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.clickhouse_log_sender.notify_stop() {
+            join_handles.push(h);
+        }
+        if let Some(h) = self.clickhouse_metrics_sender.notify_stop() {
+            join_handles.push(h);
+        }
         if let Some(h) = self.metrics_uniform_sender.notify_stop() {
             join_handles.push(h);
         }
@@ -2646,6 +2982,7 @@ impl AgentComponents {
         }
 
         self.external_metrics_server.stop();
+        self.statsd_server.stop();
         if let Some(h) = self.otel_uniform_sender.notify_stop() {
             join_handles.push(h);
         }
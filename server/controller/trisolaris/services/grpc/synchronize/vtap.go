@@ -91,6 +91,7 @@ func (e *VTapEvent) generateConfigInfo(c *vtap.VTapCache, clusterID string) *api
 	configure := &api.Config{
 		CollectorEnabled:              proto.Bool(Int2Bool(vtapConfig.CollectorEnabled)),
 		CollectorSocketType:           &collectorSocketType,
+		CollectorZstdSupported:        proto.Bool(true),
 		PlatformEnabled:               proto.Bool(Int2Bool(vtapConfig.PlatformEnabled)),
 		MaxCpus:                       proto.Uint32(uint32(vtapConfig.MaxCPUs)),
 		MaxMemory:                     proto.Uint32(uint32(vtapConfig.MaxMemory)),
@@ -448,6 +449,7 @@ func (e *VTapEvent) generateNoVTapCacheConfig(groupID string) *api.Config {
 	configure := &api.Config{
 		CollectorEnabled:              proto.Bool(Int2Bool(vtapConfig.CollectorEnabled)),
 		CollectorSocketType:           &collectorSocketType,
+		CollectorZstdSupported:        proto.Bool(true),
 		PlatformEnabled:               proto.Bool(Int2Bool(vtapConfig.PlatformEnabled)),
 		MaxCpus:                       proto.Uint32(uint32(vtapConfig.MaxCPUs)),
 		MaxMemory:                     proto.Uint32(uint32(vtapConfig.MaxMemory)),
@@ -998,6 +998,159 @@ var ColumnAdd64 = []*ColumnAdds{
 	},
 }
 
+var l4FlowLogTables = []string{"l4_flow_log", "l4_flow_log_local"}
+var u32MediaColumnNameAdd645 = []string{
+	"media_ssrc_count", "media_packet_count", "media_expected_count", "media_lost_count", "media_out_of_order_count",
+	"media_jitter_count", "media_jitter_max", "media_rtcp_count", "media_rtcp_fraction_lost_max",
+}
+var u32ColumnNameAdd645 = []string{
+	"rrt_histogram_start", "rrt_histogram_factor", "rrt_sketch_alpha", "response_code_others",
+	"apdex_satisfied", "apdex_tolerating", "apdex_frustrated",
+	"req_size_count", "req_size_min", "req_size_max", "resp_size_count", "resp_size_min", "resp_size_max",
+	"concurrency_max", "concurrency_samples", "tls_rtt_count", "tls_rtt_max",
+	"dup_ack_tx", "dup_ack_rx", "ack_rtt_client_max", "ack_rtt_server_max",
+	"icmp_error_count", "icmp_error_mtu", "sctp_ppid", "tunnel_gwlb_flow_cookie",
+	"tunnel_nsh_spi",
+}
+var u8ColumnNameAdd645 = []string{"icmp_type", "icmp_code", "icmp_error_type", "icmp_error_code", "tunnel_nsh_si"}
+var u64ColumnNameAdd645 = []string{
+	"req_size_sum", "resp_size_sum", "concurrency_sum", "ack_rtt_client_count", "ack_rtt_server_count",
+	"tunnel_gwlb_endpoint_id", "tunnel_gwlb_attachment_id",
+}
+var f64ColumnNameAdd645 = []string{"ack_rtt_client_sum", "ack_rtt_server_sum"}
+var arrayU32ColumnNameAdd645 = []string{
+	"rrt_histogram", "rrt_sketch_bins", "response_code_counts", "req_size_histogram", "resp_size_histogram",
+}
+var u32FlowColumnNameAdd645 = []string{"tls_rtt_max", "ack_rtt_client_max", "ack_rtt_server_max"}
+var u64FlowColumnNameAdd645 = []string{
+	"tls_rtt_count", "dup_ack_tx", "dup_ack_rx", "ack_rtt_client_count", "ack_rtt_server_count",
+}
+var f64FlowColumnNameAdd645 = []string{"tls_rtt_sum", "ack_rtt_client_sum", "ack_rtt_server_sum"}
+var u32AppColumnNameAdd645 = []string{
+	"rrt_histogram_start", "rrt_histogram_factor",
+	"apdex_satisfied", "apdex_tolerating", "apdex_frustrated",
+}
+var u64AppColumnNameAdd645 = []string{"byte_tx", "byte_rx"}
+var arrayU32AppColumnNameAdd645 = []string{"rrt_histogram"}
+var vtapAppAllPortTables = append(append([]string{}, vtapAppPortTables...), vtapAppEdgePortTables...)
+
+var ColumnAdd645 = []*ColumnAdds{
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: []string{"tls_cert_expiring", "dns_nxdomain", "dns_servfail", "dns_refused", "dns_timeout"},
+		ColumnType:  ckdb.UInt32,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: u32MediaColumnNameAdd645,
+		ColumnType:  ckdb.UInt32,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: []string{"media_jitter_sum"},
+		ColumnType:  ckdb.UInt64,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: u32ColumnNameAdd645,
+		ColumnType:  ckdb.UInt32,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: arrayU32ColumnNameAdd645,
+		ColumnType:  ckdb.ArrayUInt32,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_metrics"},
+		Tables:      vtapAppAllPortTables,
+		ColumnNames: u32AppColumnNameAdd645,
+		ColumnType:  ckdb.UInt32,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_metrics"},
+		Tables:      vtapAppAllPortTables,
+		ColumnNames: arrayU32AppColumnNameAdd645,
+		ColumnType:  ckdb.ArrayUInt32,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: []string{"rrt_sketch_offset"},
+		ColumnType:  ckdb.Int32,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: []string{"response_codes"},
+		ColumnType:  ckdb.ArrayString,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: u64ColumnNameAdd645,
+		ColumnType:  ckdb.UInt64,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_metrics"},
+		Tables:      vtapAppAllPortTables,
+		ColumnNames: u64AppColumnNameAdd645,
+		ColumnType:  ckdb.UInt64,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_metrics"},
+		Tables:      vtapFlowTables,
+		ColumnNames: u32FlowColumnNameAdd645,
+		ColumnType:  ckdb.UInt32,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_metrics"},
+		Tables:      vtapFlowTables,
+		ColumnNames: u64FlowColumnNameAdd645,
+		ColumnType:  ckdb.UInt64,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_metrics"},
+		Tables:      vtapFlowTables,
+		ColumnNames: f64FlowColumnNameAdd645,
+		ColumnType:  ckdb.Float64,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: f64ColumnNameAdd645,
+		ColumnType:  ckdb.Float64,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: u8ColumnNameAdd645,
+		ColumnType:  ckdb.UInt8,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: []string{"sctp_stream_count", "tunnel_erspan_sgt", "outer_vlan"},
+		ColumnType:  ckdb.UInt16,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: []string{"srv6_segments"},
+		ColumnType:  ckdb.ArrayString,
+	},
+	&ColumnAdds{
+		Dbs:         []string{"flow_log"},
+		Tables:      l4FlowLogTables,
+		ColumnNames: []string{"srv6_active_segment"},
+		ColumnType:  ckdb.IPv6,
+	},
+}
+
 var IndexAdd64 = []*IndexAdds{
 	&IndexAdds{
 		Dbs:         []string{"flow_log"},
@@ -1314,7 +1467,52 @@ func (i *Issu) addColumnDatasource(connect *sql.DB, d *DatasourceInfo, isEdgeTab
 		}
 	}
 
-	for _, version := range [][]*ColumnAdds{columnAddss612, columnAddss620, columnAddss623, columnAddss625, columnAddss633} {
+	columnAddss645 := []*ColumnAdds{}
+	if !isAppTable {
+		columnAddss645 = append(columnAddss645, []*ColumnAdds{
+			&ColumnAdds{
+				Dbs:         []string{d.db},
+				Tables:      []string{d.name, d.name + "_agg"},
+				ColumnNames: u32FlowColumnNameAdd645,
+				ColumnType:  ckdb.UInt32,
+			},
+			&ColumnAdds{
+				Dbs:         []string{d.db},
+				Tables:      []string{d.name, d.name + "_agg"},
+				ColumnNames: u64FlowColumnNameAdd645,
+				ColumnType:  ckdb.UInt64,
+			},
+			&ColumnAdds{
+				Dbs:         []string{d.db},
+				Tables:      []string{d.name, d.name + "_agg"},
+				ColumnNames: f64FlowColumnNameAdd645,
+				ColumnType:  ckdb.Float64,
+			},
+		}...)
+	} else {
+		columnAddss645 = append(columnAddss645, []*ColumnAdds{
+			&ColumnAdds{
+				Dbs:         []string{d.db},
+				Tables:      []string{d.name, d.name + "_agg"},
+				ColumnNames: u32AppColumnNameAdd645,
+				ColumnType:  ckdb.UInt32,
+			},
+			&ColumnAdds{
+				Dbs:         []string{d.db},
+				Tables:      []string{d.name, d.name + "_agg"},
+				ColumnNames: arrayU32AppColumnNameAdd645,
+				ColumnType:  ckdb.ArrayUInt32,
+			},
+			&ColumnAdds{
+				Dbs:         []string{d.db},
+				Tables:      []string{d.name, d.name + "_agg"},
+				ColumnNames: u64AppColumnNameAdd645,
+				ColumnType:  ckdb.UInt64,
+			},
+		}...)
+	}
+
+	for _, version := range [][]*ColumnAdds{columnAddss612, columnAddss620, columnAddss623, columnAddss625, columnAddss633, columnAddss645} {
 		for _, addrs := range version {
 			columnAdds = append(columnAdds, getColumnAdds(addrs)...)
 		}
@@ -1393,7 +1591,7 @@ func NewCKIssu(cfg *config.Config) (*Issu, error) {
 		datasourceInfo: make(map[string]*DatasourceInfo),
 	}
 
-	allVersionAdds := [][]*ColumnAdds{ColumnAdd610, ColumnAdd611, ColumnAdd612, ColumnAdd613, ColumnAdd615, ColumnAdd618, ColumnAdd620, ColumnAdd623, ColumnAdd625, ColumnAdd626, ColumnAdd633, ColumnAdd635, ColumnAdd64}
+	allVersionAdds := [][]*ColumnAdds{ColumnAdd610, ColumnAdd611, ColumnAdd612, ColumnAdd613, ColumnAdd615, ColumnAdd618, ColumnAdd620, ColumnAdd623, ColumnAdd625, ColumnAdd626, ColumnAdd633, ColumnAdd635, ColumnAdd64, ColumnAdd645}
 	i.columnAdds = []*ColumnAdd{}
 	for _, versionAdd := range allVersionAdds {
 		for _, adds := range versionAdd {
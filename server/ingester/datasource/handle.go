@@ -109,29 +109,41 @@ func getMetricsSubTableIDs(tableGroup, baseTable string) ([]zerodoc.MetricsTable
 
 // zerodoc 的 Latency 结构中的非累加聚合字段
 var unsummableMaxFieldsMap = map[string]struct{}{
-	"rtt_max":        {},
-	"rtt_client_max": {},
-	"rtt_server_max": {},
-	"srt_max":        {},
-	"art_max":        {},
-	"rrt_max":        {},
+	"rtt_max":            {},
+	"rtt_client_max":     {},
+	"rtt_server_max":     {},
+	"srt_max":            {},
+	"art_max":            {},
+	"rrt_max":            {},
+	"tls_rtt_max":        {},
+	"ack_rtt_client_max": {},
+	"ack_rtt_server_max": {},
+
+	"rrt_histogram_start":  {},
+	"rrt_histogram_factor": {},
 }
 
 // 对于unsumable的sum列使用max,min聚合时, count列取相应的max,min列的值
 var unsummableFieldsMap = map[string]struct{}{
-	"rtt_sum":        {},
-	"rtt_client_sum": {},
-	"rtt_server_sum": {},
-	"srt_sum":        {},
-	"art_sum":        {},
-	"rrt_sum":        {},
-
-	"rtt_count":        {},
-	"rtt_client_count": {},
-	"rtt_server_count": {},
-	"srt_count":        {},
-	"art_count":        {},
-	"rrt_count":        {},
+	"rtt_sum":            {},
+	"rtt_client_sum":     {},
+	"rtt_server_sum":     {},
+	"srt_sum":            {},
+	"art_sum":            {},
+	"rrt_sum":            {},
+	"tls_rtt_sum":        {},
+	"ack_rtt_client_sum": {},
+	"ack_rtt_server_sum": {},
+
+	"rtt_count":            {},
+	"rtt_client_count":     {},
+	"rtt_server_count":     {},
+	"srt_count":            {},
+	"art_count":            {},
+	"rrt_count":            {},
+	"tls_rtt_count":        {},
+	"ack_rtt_client_count": {},
+	"ack_rtt_server_count": {},
 }
 
 func getColumnString(column *ckdb.Column, aggrSummable, aggrUnsummable string, t TableType) string {
@@ -166,6 +178,10 @@ func getColumnString(column *ckdb.Column, aggrSummable, aggrUnsummable string, t
 		if isUnsummableMax || isUnsummable {
 			aggr = aggrUnsummable
 		}
+		// 数组字段(如直方图)按下标逐个聚合, 例如: sumForEachState(rrt_histogram)
+		if column.Type == ckdb.ArrayUInt32 {
+			aggr += "ForEach"
+		}
 		switch t {
 		case AGG:
 			return fmt.Sprintf("%s__%s AggregateFunction(%s, %s)", column.Name, t.String(), aggr, column.Type.String())
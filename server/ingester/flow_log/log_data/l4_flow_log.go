@@ -55,10 +55,11 @@ type L4FlowLog struct {
 }
 
 type DataLinkLayer struct {
-	MAC0    uint64 `json:"mac_0"`
-	MAC1    uint64 `json:"mac_1"`
-	EthType uint16 `json:"eth_type"`
-	VLAN    uint16 `json:"vlan,omitempty"`
+	MAC0      uint64 `json:"mac_0"`
+	MAC1      uint64 `json:"mac_1"`
+	EthType   uint16 `json:"eth_type"`
+	VLAN      uint16 `json:"vlan,omitempty"`
+	OuterVLAN uint16 `json:"outer_vlan,omitempty"`
 }
 
 var DataLinkLayerColumns = []*ckdb.Column{
@@ -66,6 +67,7 @@ var DataLinkLayerColumns = []*ckdb.Column{
 	ckdb.NewColumn("mac_1", ckdb.UInt64),
 	ckdb.NewColumn("eth_type", ckdb.UInt16).SetIndex(ckdb.IndexSet),
 	ckdb.NewColumn("vlan", ckdb.UInt16).SetIndex(ckdb.IndexSet),
+	ckdb.NewColumn("outer_vlan", ckdb.UInt16).SetComment("QinQ的外层VLAN"),
 }
 
 func (f *DataLinkLayer) WriteBlock(block *ckdb.Block) {
@@ -73,7 +75,8 @@ func (f *DataLinkLayer) WriteBlock(block *ckdb.Block) {
 		f.MAC0,
 		f.MAC1,
 		f.EthType,
-		f.VLAN)
+		f.VLAN,
+		f.OuterVLAN)
 }
 
 type NetworkLayer struct {
@@ -100,6 +103,19 @@ type NetworkLayer struct {
 	TunnelTxMac1 uint32 `json:"tunnel_tx_mac_1,omitempty"`
 	TunnelRxMac0 uint32 `json:"tunnel_rx_mac_0,omitempty"`
 	TunnelRxMac1 uint32 `json:"tunnel_rx_mac_1,omitempty"`
+
+	TunnelErspanSGT uint16 `json:"tunnel_erspan_sgt,omitempty"`
+
+	TunnelGwlbEndpointID   uint64 `json:"tunnel_gwlb_endpoint_id,omitempty"`
+	TunnelGwlbAttachmentID uint64 `json:"tunnel_gwlb_attachment_id,omitempty"`
+	TunnelGwlbFlowCookie   uint32 `json:"tunnel_gwlb_flow_cookie,omitempty"`
+
+	TunnelNshSPI uint32 `json:"tunnel_nsh_spi,omitempty"`
+	TunnelNshSI  uint8  `json:"tunnel_nsh_si,omitempty"`
+
+	// segment list of the SRv6 header and the active segment (the outer destination)
+	Srv6Segments      []string `json:"srv6_segments,omitempty"`
+	Srv6ActiveSegment net.IP   `json:"srv6_active_segment,omitempty"`
 }
 
 var NetworkLayerColumns = []*ckdb.Column{
@@ -126,6 +142,18 @@ var NetworkLayerColumns = []*ckdb.Column{
 	ckdb.NewColumn("tunnel_tx_mac_1", ckdb.UInt32),
 	ckdb.NewColumn("tunnel_rx_mac_0", ckdb.UInt32),
 	ckdb.NewColumn("tunnel_rx_mac_1", ckdb.UInt32),
+
+	ckdb.NewColumn("tunnel_erspan_sgt", ckdb.UInt16).SetComment("ERSPAN III的安全组标签"),
+
+	ckdb.NewColumn("tunnel_gwlb_endpoint_id", ckdb.UInt64).SetComment("AWS GWLB Geneve选项中的VPC endpoint ID"),
+	ckdb.NewColumn("tunnel_gwlb_attachment_id", ckdb.UInt64),
+	ckdb.NewColumn("tunnel_gwlb_flow_cookie", ckdb.UInt32),
+
+	ckdb.NewColumn("tunnel_nsh_spi", ckdb.UInt32).SetComment("NSH的服务路径标识"),
+	ckdb.NewColumn("tunnel_nsh_si", ckdb.UInt8).SetComment("NSH的服务索引"),
+
+	ckdb.NewColumn("srv6_segments", ckdb.ArrayString),
+	ckdb.NewColumn("srv6_active_segment", ckdb.IPv6),
 }
 
 func (n *NetworkLayer) WriteBlock(block *ckdb.Block) {
@@ -157,7 +185,19 @@ func (n *NetworkLayer) WriteBlock(block *ckdb.Block) {
 		n.TunnelTxMac0,
 		n.TunnelTxMac1,
 		n.TunnelRxMac0,
-		n.TunnelRxMac1)
+		n.TunnelRxMac1,
+
+		n.TunnelErspanSGT,
+
+		n.TunnelGwlbEndpointID,
+		n.TunnelGwlbAttachmentID,
+		n.TunnelGwlbFlowCookie,
+
+		n.TunnelNshSPI,
+		n.TunnelNshSI,
+
+		n.Srv6Segments)
+	block.WriteIPv6(n.Srv6ActiveSegment)
 }
 
 type TransportLayer struct {
@@ -169,6 +209,16 @@ type TransportLayer struct {
 	SynAckSeq        uint32 `json:"syn_ack_seq"`
 	LastKeepaliveSeq uint32 `json:"last_keepalive_seq"`
 	LastKeepaliveAck uint32 `json:"last_keepalive_ack"`
+
+	ICMPType       uint8  `json:"icmp_type,omitempty"`
+	ICMPCode       uint8  `json:"icmp_code,omitempty"`
+	ICMPErrorType  uint8  `json:"icmp_error_type,omitempty"`
+	ICMPErrorCode  uint8  `json:"icmp_error_code,omitempty"`
+	ICMPErrorCount uint32 `json:"icmp_error_count,omitempty"`
+	ICMPErrorMTU   uint32 `json:"icmp_error_mtu,omitempty"`
+
+	SCTPPPID        uint32 `json:"sctp_ppid,omitempty"`
+	SCTPStreamCount uint16 `json:"sctp_stream_count,omitempty"`
 }
 
 var TransportLayerColumns = []*ckdb.Column{
@@ -181,6 +231,16 @@ var TransportLayerColumns = []*ckdb.Column{
 	ckdb.NewColumn("syn_ack_seq", ckdb.UInt32).SetComment("握手回应包的TCP SEQ序列号"),
 	ckdb.NewColumn("last_keepalive_seq", ckdb.UInt32),
 	ckdb.NewColumn("last_keepalive_ack", ckdb.UInt32),
+
+	ckdb.NewColumn("icmp_type", ckdb.UInt8),
+	ckdb.NewColumn("icmp_code", ckdb.UInt8),
+	ckdb.NewColumn("icmp_error_type", ckdb.UInt8).SetComment("流收到的icmp差错报文类型"),
+	ckdb.NewColumn("icmp_error_code", ckdb.UInt8),
+	ckdb.NewColumn("icmp_error_count", ckdb.UInt32),
+	ckdb.NewColumn("icmp_error_mtu", ckdb.UInt32).SetComment("icmp需要分片差错报文中的下一跳MTU"),
+
+	ckdb.NewColumn("sctp_ppid", ckdb.UInt32).SetComment("SCTP DATA块的载荷协议标识"),
+	ckdb.NewColumn("sctp_stream_count", ckdb.UInt16),
 }
 
 func (t *TransportLayer) WriteBlock(block *ckdb.Block) {
@@ -192,7 +252,17 @@ func (t *TransportLayer) WriteBlock(block *ckdb.Block) {
 		t.SynSeq,
 		t.SynAckSeq,
 		t.LastKeepaliveSeq,
-		t.LastKeepaliveAck)
+		t.LastKeepaliveAck,
+
+		t.ICMPType,
+		t.ICMPCode,
+		t.ICMPErrorType,
+		t.ICMPErrorCode,
+		t.ICMPErrorCount,
+		t.ICMPErrorMTU,
+
+		t.SCTPPPID,
+		t.SCTPStreamCount)
 }
 
 type ApplicationLayer struct {
@@ -502,6 +572,74 @@ type Metrics struct {
 	L7ServerError   uint32 `json:"l7_server_error,omitempty"`
 	L7ServerTimeout uint32 `json:"l7_server_timeout,omitempty"`
 	L7Error         uint32 `json:"l7_error,omitempty"`
+	TLSCertExpiring uint32 `json:"tls_cert_expiring,omitempty"`
+	DNSNXDomain     uint32 `json:"dns_nxdomain,omitempty"`
+	DNSServFail     uint32 `json:"dns_servfail,omitempty"`
+	DNSRefused      uint32 `json:"dns_refused,omitempty"`
+	DNSTimeout      uint32 `json:"dns_timeout,omitempty"`
+
+	// RTP/RTCP media streams
+	MediaSSRCCount           uint32 `json:"media_ssrc_count,omitempty"`
+	MediaPacketCount         uint32 `json:"media_packet_count,omitempty"`
+	MediaExpectedCount       uint32 `json:"media_expected_count,omitempty"`
+	MediaLostCount           uint32 `json:"media_lost_count,omitempty"`
+	MediaOutOfOrderCount     uint32 `json:"media_out_of_order_count,omitempty"`
+	MediaJitterSum           uint64 `json:"media_jitter_sum,omitempty"`
+	MediaJitterCount         uint32 `json:"media_jitter_count,omitempty"`
+	MediaJitterMax           uint32 `json:"media_jitter_max,omitempty"`
+	MediaRTCPCount           uint32 `json:"media_rtcp_count,omitempty"`
+	MediaRTCPFractionLostMax uint32 `json:"media_rtcp_fraction_lost_max,omitempty"`
+
+	// rrt histogram, the upper bound of bucket i is rrt_histogram_start * rrt_histogram_factor^i us
+	RRTHistogramStart  uint32   `json:"rrt_histogram_start,omitempty"`
+	RRTHistogramFactor uint32   `json:"rrt_histogram_factor,omitempty"`
+	RRTHistogram       []uint32 `json:"rrt_histogram,omitempty"`
+
+	// DDSketch of rrt in us, gamma = (1 + alpha) / (1 - alpha), alpha = rrt_sketch_alpha / 10000
+	RRTSketchAlpha  uint32   `json:"rrt_sketch_alpha,omitempty"`
+	RRTSketchOffset int32    `json:"rrt_sketch_offset,omitempty"`
+	RRTSketchBins   []uint32 `json:"rrt_sketch_bins,omitempty"`
+
+	// the most frequent response codes and their counts
+	ResponseCodes      []string `json:"response_codes,omitempty"`
+	ResponseCodeCounts []uint32 `json:"response_code_counts,omitempty"`
+	ResponseCodeOthers uint32   `json:"response_code_others,omitempty"`
+
+	// apdex samples, satisfied within t, tolerating within 4t, frustrated beyond 4t or with error responses
+	ApdexSatisfied  uint32 `json:"apdex_satisfied,omitempty"`
+	ApdexTolerating uint32 `json:"apdex_tolerating,omitempty"`
+	ApdexFrustrated uint32 `json:"apdex_frustrated,omitempty"`
+
+	// request and response sizes, the upper bound of histogram bucket i is 64 * 4^i bytes
+	ReqSizeCount      uint32   `json:"req_size_count,omitempty"`
+	ReqSizeSum        uint64   `json:"req_size_sum,omitempty"`
+	ReqSizeMin        uint32   `json:"req_size_min,omitempty"`
+	ReqSizeMax        uint32   `json:"req_size_max,omitempty"`
+	ReqSizeHistogram  []uint32 `json:"req_size_histogram,omitempty"`
+	RespSizeCount     uint32   `json:"resp_size_count,omitempty"`
+	RespSizeSum       uint64   `json:"resp_size_sum,omitempty"`
+	RespSizeMin       uint32   `json:"resp_size_min,omitempty"`
+	RespSizeMax       uint32   `json:"resp_size_max,omitempty"`
+	RespSizeHistogram []uint32 `json:"resp_size_histogram,omitempty"`
+
+	// requests waiting for responses, the average is concurrency_sum / concurrency_samples
+	ConcurrencyMax     uint32 `json:"concurrency_max,omitempty"`
+	ConcurrencySum     uint64 `json:"concurrency_sum,omitempty"`
+	ConcurrencySamples uint32 `json:"concurrency_samples,omitempty"`
+
+	TLSRTTCount uint32 `json:"tls_rtt_count,omitempty"`
+	TLSRTTMax   uint32 `json:"tls_rtt_max,omitempty"`
+
+	DupAckTx uint32 `json:"dup_ack_tx,omitempty"`
+	DupAckRx uint32 `json:"dup_ack_rx,omitempty"`
+
+	// rtt sampled from data segments and their ACKs
+	AckRTTClientSum   uint32 `json:"ack_rtt_client_sum,omitempty"`
+	AckRTTServerSum   uint32 `json:"ack_rtt_server_sum,omitempty"`
+	AckRTTClientCount uint32 `json:"ack_rtt_client_count,omitempty"`
+	AckRTTServerCount uint32 `json:"ack_rtt_server_count,omitempty"`
+	AckRTTClientMax   uint32 `json:"ack_rtt_client_max,omitempty"`
+	AckRTTServerMax   uint32 `json:"ack_rtt_server_max,omitempty"`
 }
 
 var MetricsColumns = []*ckdb.Column{
@@ -554,6 +692,66 @@ var MetricsColumns = []*ckdb.Column{
 	ckdb.NewColumn("l7_server_error", ckdb.UInt32),
 	ckdb.NewColumn("l7_server_timeout", ckdb.UInt32),
 	ckdb.NewColumn("l7_error", ckdb.UInt32),
+	ckdb.NewColumn("tls_cert_expiring", ckdb.UInt32).SetComment("TLS handshakes whose server certificate is about to expire"),
+	ckdb.NewColumn("dns_nxdomain", ckdb.UInt32),
+	ckdb.NewColumn("dns_servfail", ckdb.UInt32),
+	ckdb.NewColumn("dns_refused", ckdb.UInt32),
+	ckdb.NewColumn("dns_timeout", ckdb.UInt32),
+
+	ckdb.NewColumn("media_ssrc_count", ckdb.UInt32),
+	ckdb.NewColumn("media_packet_count", ckdb.UInt32),
+	ckdb.NewColumn("media_expected_count", ckdb.UInt32),
+	ckdb.NewColumn("media_lost_count", ckdb.UInt32),
+	ckdb.NewColumn("media_out_of_order_count", ckdb.UInt32),
+	ckdb.NewColumn("media_jitter_sum", ckdb.UInt64).SetComment("单位: 微秒"),
+	ckdb.NewColumn("media_jitter_count", ckdb.UInt32),
+	ckdb.NewColumn("media_jitter_max", ckdb.UInt32).SetComment("单位: 微秒"),
+	ckdb.NewColumn("media_rtcp_count", ckdb.UInt32),
+	ckdb.NewColumn("media_rtcp_fraction_lost_max", ckdb.UInt32),
+
+	ckdb.NewColumn("rrt_histogram_start", ckdb.UInt32).SetComment("单位: 微秒"),
+	ckdb.NewColumn("rrt_histogram_factor", ckdb.UInt32),
+	ckdb.NewColumn("rrt_histogram", ckdb.ArrayUInt32),
+
+	ckdb.NewColumn("rrt_sketch_alpha", ckdb.UInt32).SetComment("单位: 万分之一"),
+	ckdb.NewColumn("rrt_sketch_offset", ckdb.Int32),
+	ckdb.NewColumn("rrt_sketch_bins", ckdb.ArrayUInt32),
+
+	ckdb.NewColumn("response_codes", ckdb.ArrayString),
+	ckdb.NewColumn("response_code_counts", ckdb.ArrayUInt32),
+	ckdb.NewColumn("response_code_others", ckdb.UInt32).SetComment("不在 response_codes 中的响应次数"),
+
+	ckdb.NewColumn("apdex_satisfied", ckdb.UInt32),
+	ckdb.NewColumn("apdex_tolerating", ckdb.UInt32),
+	ckdb.NewColumn("apdex_frustrated", ckdb.UInt32),
+
+	ckdb.NewColumn("req_size_count", ckdb.UInt32),
+	ckdb.NewColumn("req_size_sum", ckdb.UInt64).SetComment("单位: 字节"),
+	ckdb.NewColumn("req_size_min", ckdb.UInt32).SetComment("单位: 字节"),
+	ckdb.NewColumn("req_size_max", ckdb.UInt32).SetComment("单位: 字节"),
+	ckdb.NewColumn("req_size_histogram", ckdb.ArrayUInt32),
+	ckdb.NewColumn("resp_size_count", ckdb.UInt32),
+	ckdb.NewColumn("resp_size_sum", ckdb.UInt64).SetComment("单位: 字节"),
+	ckdb.NewColumn("resp_size_min", ckdb.UInt32).SetComment("单位: 字节"),
+	ckdb.NewColumn("resp_size_max", ckdb.UInt32).SetComment("单位: 字节"),
+	ckdb.NewColumn("resp_size_histogram", ckdb.ArrayUInt32),
+
+	ckdb.NewColumn("concurrency_max", ckdb.UInt32),
+	ckdb.NewColumn("concurrency_sum", ckdb.UInt64),
+	ckdb.NewColumn("concurrency_samples", ckdb.UInt32),
+
+	ckdb.NewColumn("tls_rtt_count", ckdb.UInt32),
+	ckdb.NewColumn("tls_rtt_max", ckdb.UInt32).SetComment("单位: 微秒"),
+
+	ckdb.NewColumn("dup_ack_tx", ckdb.UInt32),
+	ckdb.NewColumn("dup_ack_rx", ckdb.UInt32),
+
+	ckdb.NewColumn("ack_rtt_client_sum", ckdb.Float64).SetComment("单位: 微秒"),
+	ckdb.NewColumn("ack_rtt_server_sum", ckdb.Float64).SetComment("单位: 微秒"),
+	ckdb.NewColumn("ack_rtt_client_count", ckdb.UInt64),
+	ckdb.NewColumn("ack_rtt_server_count", ckdb.UInt64),
+	ckdb.NewColumn("ack_rtt_client_max", ckdb.UInt32).SetComment("单位: 微秒"),
+	ckdb.NewColumn("ack_rtt_server_max", ckdb.UInt32).SetComment("单位: 微秒"),
 }
 
 func (m *Metrics) WriteBlock(block *ckdb.Block) {
@@ -605,7 +803,67 @@ func (m *Metrics) WriteBlock(block *ckdb.Block) {
 		m.L7ClientError,
 		m.L7ServerError,
 		m.L7ServerTimeout,
-		m.L7Error)
+		m.L7Error,
+		m.TLSCertExpiring,
+		m.DNSNXDomain,
+		m.DNSServFail,
+		m.DNSRefused,
+		m.DNSTimeout,
+
+		m.MediaSSRCCount,
+		m.MediaPacketCount,
+		m.MediaExpectedCount,
+		m.MediaLostCount,
+		m.MediaOutOfOrderCount,
+		m.MediaJitterSum,
+		m.MediaJitterCount,
+		m.MediaJitterMax,
+		m.MediaRTCPCount,
+		m.MediaRTCPFractionLostMax,
+
+		m.RRTHistogramStart,
+		m.RRTHistogramFactor,
+		m.RRTHistogram,
+
+		m.RRTSketchAlpha,
+		m.RRTSketchOffset,
+		m.RRTSketchBins,
+
+		m.ResponseCodes,
+		m.ResponseCodeCounts,
+		m.ResponseCodeOthers,
+
+		m.ApdexSatisfied,
+		m.ApdexTolerating,
+		m.ApdexFrustrated,
+
+		m.ReqSizeCount,
+		m.ReqSizeSum,
+		m.ReqSizeMin,
+		m.ReqSizeMax,
+		m.ReqSizeHistogram,
+		m.RespSizeCount,
+		m.RespSizeSum,
+		m.RespSizeMin,
+		m.RespSizeMax,
+		m.RespSizeHistogram,
+
+		m.ConcurrencyMax,
+		m.ConcurrencySum,
+		m.ConcurrencySamples,
+
+		m.TLSRTTCount,
+		m.TLSRTTMax,
+
+		m.DupAckTx,
+		m.DupAckRx,
+
+		float64(m.AckRTTClientSum),
+		float64(m.AckRTTServerSum),
+		uint64(m.AckRTTClientCount),
+		uint64(m.AckRTTServerCount),
+		m.AckRTTClientMax,
+		m.AckRTTServerMax)
 }
 
 func parseUint32EpcID(v uint32) int32 {
@@ -623,6 +881,7 @@ func (d *DataLinkLayer) Fill(f *pb.Flow) {
 	d.MAC1 = f.FlowKey.MacDst
 	d.EthType = uint16(f.EthType)
 	d.VLAN = uint16(f.Vlan)
+	d.OuterVLAN = uint16(f.OuterVlan)
 }
 
 func cloneIP(src net.IP) net.IP {
@@ -662,7 +921,19 @@ func (n *NetworkLayer) Fill(f *pb.Flow, isIPV6 bool) {
 		n.TunnelTxMac1 = f.Tunnel.TxMac1
 		n.TunnelRxMac0 = f.Tunnel.RxMac0
 		n.TunnelRxMac1 = f.Tunnel.RxMac1
+		n.TunnelErspanSGT = uint16(f.Tunnel.ErspanSgt)
+		n.TunnelGwlbEndpointID = f.Tunnel.GwlbEndpointId
+		n.TunnelGwlbAttachmentID = f.Tunnel.GwlbAttachmentId
+		n.TunnelGwlbFlowCookie = f.Tunnel.GwlbFlowCookie
+		n.TunnelNshSPI = f.Tunnel.NshSpi
+		n.TunnelNshSI = uint8(f.Tunnel.NshSi)
+	}
+
+	n.Srv6Segments = make([]string, 0, len(f.Srv6Segments))
+	for _, segment := range f.Srv6Segments {
+		n.Srv6Segments = append(n.Srv6Segments, net.IP(segment).String())
 	}
+	n.Srv6ActiveSegment = cloneIP(f.Srv6ActiveSegment)
 }
 
 func (t *TransportLayer) Fill(f *pb.Flow) {
@@ -674,6 +945,16 @@ func (t *TransportLayer) Fill(f *pb.Flow) {
 	t.SynAckSeq = f.SynackSeq
 	t.LastKeepaliveSeq = f.LastKeepaliveSeq
 	t.LastKeepaliveAck = f.LastKeepaliveAck
+
+	t.ICMPType = uint8(f.IcmpType)
+	t.ICMPCode = uint8(f.IcmpCode)
+	t.ICMPErrorType = uint8(f.IcmpErrorType)
+	t.ICMPErrorCode = uint8(f.IcmpErrorCode)
+	t.ICMPErrorCount = f.IcmpErrorCount
+	t.ICMPErrorMTU = f.IcmpErrorMtu
+
+	t.SCTPPPID = f.SctpPpid
+	t.SCTPStreamCount = uint16(f.SctpStreamCount)
 }
 
 func (a *ApplicationLayer) Fill(f *pb.Flow) {
@@ -930,6 +1211,23 @@ func (m *Metrics) Fill(f *pb.Flow) {
 		m.RTTClient = p.Tcp.RttClientMax
 		m.RTTServer = p.Tcp.RttServerMax
 		m.TLSRTT = p.L7.TlsRtt
+		m.TLSCertExpiring = p.L7.TlsCertExpiring
+		m.DNSNXDomain = p.L7.DnsNxdomain
+		m.DNSServFail = p.L7.DnsServfail
+		m.DNSRefused = p.L7.DnsRefused
+		m.DNSTimeout = p.L7.DnsTimeout
+		if p.Media != nil {
+			m.MediaSSRCCount = p.Media.SsrcCount
+			m.MediaPacketCount = p.Media.PacketCount
+			m.MediaExpectedCount = p.Media.ExpectedCount
+			m.MediaLostCount = p.Media.LostCount
+			m.MediaOutOfOrderCount = p.Media.OutOfOrderCount
+			m.MediaJitterSum = p.Media.JitterSum
+			m.MediaJitterCount = p.Media.JitterCount
+			m.MediaJitterMax = p.Media.JitterMax
+			m.MediaRTCPCount = p.Media.RtcpCount
+			m.MediaRTCPFractionLostMax = p.Media.RtcpFractionLostMax
+		}
 
 		m.SRTSum = p.Tcp.SrtSum
 		m.SRTCount = p.Tcp.SrtCount
@@ -951,10 +1249,12 @@ func (m *Metrics) Fill(f *pb.Flow) {
 		if p.Tcp.CountsPeerTx != nil {
 			m.RetransTx = p.Tcp.CountsPeerTx.RetransCount
 			m.ZeroWinTx = p.Tcp.CountsPeerTx.ZeroWinCount
+			m.DupAckTx = p.Tcp.CountsPeerTx.DupAckCount
 		}
 		if p.Tcp.CountsPeerRx != nil {
 			m.RetransRx = p.Tcp.CountsPeerRx.RetransCount
 			m.ZeroWinRx = p.Tcp.CountsPeerRx.ZeroWinCount
+			m.DupAckRx = p.Tcp.CountsPeerRx.DupAckCount
 		}
 		m.SynCount = p.Tcp.SynCount
 		m.SynackCount = p.Tcp.SynackCount
@@ -964,6 +1264,55 @@ func (m *Metrics) Fill(f *pb.Flow) {
 		if m.SynackCount > 0 {
 			m.RetransSynack = m.SynackCount - 1
 		}
+
+		m.RRTHistogramStart = p.L7.RrtHistogramStart
+		m.RRTHistogramFactor = p.L7.RrtHistogramFactor
+		m.RRTHistogram = append([]uint32{}, p.L7.RrtHistogram...)
+
+		m.RRTSketchAlpha = p.L7.RrtSketchAlpha
+		m.RRTSketchOffset = p.L7.RrtSketchOffset
+		m.RRTSketchBins = append([]uint32{}, p.L7.RrtSketchBins...)
+
+		m.ResponseCodes = make([]string, 0, len(p.L7.ResponseCodes))
+		m.ResponseCodeCounts = make([]uint32, 0, len(p.L7.ResponseCodes))
+		for _, c := range p.L7.ResponseCodes {
+			m.ResponseCodes = append(m.ResponseCodes, c.Code)
+			m.ResponseCodeCounts = append(m.ResponseCodeCounts, c.Count)
+		}
+		m.ResponseCodeOthers = p.L7.ResponseCodeOthers
+
+		m.ApdexSatisfied = p.L7.ApdexSatisfied
+		m.ApdexTolerating = p.L7.ApdexTolerating
+		m.ApdexFrustrated = p.L7.ApdexFrustrated
+
+		if s := p.L7.ReqSize; s != nil {
+			m.ReqSizeCount = s.Count
+			m.ReqSizeSum = s.Sum
+			m.ReqSizeMin = s.Min
+			m.ReqSizeMax = s.Max
+			m.ReqSizeHistogram = append([]uint32{}, s.Histogram...)
+		}
+		if s := p.L7.RespSize; s != nil {
+			m.RespSizeCount = s.Count
+			m.RespSizeSum = s.Sum
+			m.RespSizeMin = s.Min
+			m.RespSizeMax = s.Max
+			m.RespSizeHistogram = append([]uint32{}, s.Histogram...)
+		}
+
+		m.ConcurrencyMax = p.L7.ConcurrencyMax
+		m.ConcurrencySum = p.L7.ConcurrencySum
+		m.ConcurrencySamples = p.L7.ConcurrencySamples
+
+		m.TLSRTTCount = p.L7.TlsRttCount
+		m.TLSRTTMax = p.L7.TlsRttMax
+
+		m.AckRTTClientSum = p.Tcp.AckRttClientSum
+		m.AckRTTServerSum = p.Tcp.AckRttServerSum
+		m.AckRTTClientCount = p.Tcp.AckRttClientCount
+		m.AckRTTServerCount = p.Tcp.AckRttServerCount
+		m.AckRTTClientMax = p.Tcp.AckRttClientMax
+		m.AckRTTServerMax = p.Tcp.AckRttServerMax
 	}
 }
 
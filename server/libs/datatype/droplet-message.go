@@ -141,19 +141,30 @@ const (
 	FLOW_HEADER_LEN      = FLOW_VTAPID_OFFSET + 2
 )
 
+// 消息类型字节的最高位表示FlowHeader之后的数据经过了zstd压缩
+const MESSAGE_TYPE_FLAG_ZSTD = 0x80
+
 type BaseHeader struct {
 	FrameSize uint32      // tcp发送时，需要按此长度收齐数据后，再decode (FrameSize总长度，包含了 BaseHeader的长度)
 	Type      MessageType // 消息类型
+	Zstd      bool        // FlowHeader之后的数据是否为zstd压缩
 }
 
 func (h *BaseHeader) Encode(chunk []byte) {
 	binary.BigEndian.PutUint32(chunk[MESSAGE_FRAME_SIZE_OFFSET:], h.FrameSize)
 	chunk[MESSAGE_TYPE_OFFSET] = uint8(h.Type)
+	if h.Zstd {
+		chunk[MESSAGE_TYPE_OFFSET] |= MESSAGE_TYPE_FLAG_ZSTD
+	}
 }
 
 func (h *BaseHeader) Decode(buf []byte) error {
 	h.FrameSize = binary.BigEndian.Uint32(buf[MESSAGE_FRAME_SIZE_OFFSET:])
-	h.Type = MessageType(buf[MESSAGE_TYPE_OFFSET])
+	h.Type = MessageType(buf[MESSAGE_TYPE_OFFSET] &^ MESSAGE_TYPE_FLAG_ZSTD)
+	h.Zstd = buf[MESSAGE_TYPE_OFFSET]&MESSAGE_TYPE_FLAG_ZSTD != 0
+	if h.Zstd && h.Type.HeaderType() != HEADER_TYPE_LT_VTAP {
+		return fmt.Errorf("header type is %d, zstd compression is not supported", h.Type)
+	}
 
 	switch h.Type.HeaderType() {
 	case HEADER_TYPE_LT:
@@ -147,22 +147,29 @@ const (
 type L7Protocol uint8
 
 const (
-	L7_PROTOCOL_UNKNOWN L7Protocol = 0
-	L7_PROTOCOL_HTTP_1  L7Protocol = 20
-	L7_PROTOCOL_HTTP_2  L7Protocol = 21
-	L7_PROTOCOL_DUBBO   L7Protocol = 40
-	L7_PROTOCOL_GRPC    L7Protocol = 41
-	L7_PROTOCOL_SOFARPC L7Protocol = 43
-	L7_PROTOCOL_FASTCGI L7Protocol = 44
-	L7_PROTOCOL_MYSQL   L7Protocol = 60
-	L7_PROTOCOL_POSTGRE L7Protocol = 61
-	L7_PROTOCOL_ORACLE  L7Protocol = 62
-	L7_PROTOCOL_REDIS   L7Protocol = 80
-	L7_PROTOCOL_MONGODB L7Protocol = 81
-	L7_PROTOCOL_KAFKA   L7Protocol = 100
-	L7_PROTOCOL_MQTT    L7Protocol = 101
-	L7_PROTOCOL_DNS     L7Protocol = 120
-	L7_PROTOCOL_CUSTOM  L7Protocol = 127
+	L7_PROTOCOL_UNKNOWN  L7Protocol = 0
+	L7_PROTOCOL_HTTP_1   L7Protocol = 20
+	L7_PROTOCOL_HTTP_2   L7Protocol = 21
+	L7_PROTOCOL_DUBBO    L7Protocol = 40
+	L7_PROTOCOL_GRPC     L7Protocol = 41
+	L7_PROTOCOL_SOFARPC  L7Protocol = 43
+	L7_PROTOCOL_FASTCGI  L7Protocol = 44
+	L7_PROTOCOL_MYSQL    L7Protocol = 60
+	L7_PROTOCOL_POSTGRE  L7Protocol = 61
+	L7_PROTOCOL_ORACLE   L7Protocol = 62
+	L7_PROTOCOL_REDIS    L7Protocol = 80
+	L7_PROTOCOL_MONGODB  L7Protocol = 81
+	L7_PROTOCOL_KAFKA    L7Protocol = 100
+	L7_PROTOCOL_MQTT     L7Protocol = 101
+	L7_PROTOCOL_SIP      L7Protocol = 110
+	L7_PROTOCOL_RTP      L7Protocol = 111
+	L7_PROTOCOL_DNS      L7Protocol = 120
+	L7_PROTOCOL_DHCP     L7Protocol = 122
+	L7_PROTOCOL_NTP      L7Protocol = 123
+	L7_PROTOCOL_SSH      L7Protocol = 124
+	L7_PROTOCOL_KERBEROS L7Protocol = 125
+	L7_PROTOCOL_RADIUS   L7Protocol = 126
+	L7_PROTOCOL_CUSTOM   L7Protocol = 127
 )
 
 // size = 9 * 4B = 36B
@@ -689,6 +696,48 @@ func (p L7Protocol) String(isTLS bool) string {
 		} else {
 			return "MQTT"
 		}
+	case L7_PROTOCOL_DHCP:
+		if isTLS {
+			return "DHCP_TLS"
+		} else {
+			return "DHCP"
+		}
+	case L7_PROTOCOL_NTP:
+		if isTLS {
+			return "NTP_TLS"
+		} else {
+			return "NTP"
+		}
+	case L7_PROTOCOL_SSH:
+		if isTLS {
+			return "SSH_TLS"
+		} else {
+			return "SSH"
+		}
+	case L7_PROTOCOL_KERBEROS:
+		if isTLS {
+			return "Kerberos_TLS"
+		} else {
+			return "Kerberos"
+		}
+	case L7_PROTOCOL_RADIUS:
+		if isTLS {
+			return "RADIUS_TLS"
+		} else {
+			return "RADIUS"
+		}
+	case L7_PROTOCOL_SIP:
+		if isTLS {
+			return "SIP_TLS"
+		} else {
+			return "SIP"
+		}
+	case L7_PROTOCOL_RTP:
+		if isTLS {
+			return "RTP_TLS"
+		} else {
+			return "RTP"
+		}
 	case L7_PROTOCOL_CUSTOM:
 		if isTLS {
 			return "Custom_TLS"
@@ -29,6 +29,7 @@ import (
 	"sync/atomic"
 	"time"
 
+	"github.com/klauspost/compress/zstd"
 	logging "github.com/op/go-logging"
 
 	"github.com/deepflowio/deepflow/server/libs/app"
@@ -391,6 +392,8 @@ type Receiver struct {
 	counter *ReceiverCounter
 
 	status *AdapterStatus
+
+	zstdDecoder *zstd.Decoder // 解压agent发送的zstd压缩数据, DecodeAll可并发调用
 }
 
 type ReceiverCounter struct {
@@ -421,6 +424,11 @@ func NewReceiver(
 		status:          &AdapterStatus{},
 	}
 	receiver.status.init()
+	decoder, err := zstd.NewReader(nil, zstd.WithDecoderMaxMemory(RECV_BUFSIZE_MAX))
+	if err != nil {
+		log.Errorf("create zstd decoder failed: %s", err)
+	}
+	receiver.zstdDecoder = decoder
 
 	debug.ServerRegisterSimple(TRIDENT_ADAPTER_STATUS_CMD, receiver)
 	receiver.DropDetection.Init("receiver", DROP_DETECT_WINDOW_SIZE)
@@ -699,6 +707,12 @@ func (r *Receiver) ProcessUDPServer() {
 			r.logReceiveError(size, remoteAddr, fmt.Errorf("unknown message type %d", baseHeader.Type))
 			continue
 		}
+		// zstd压缩的数据只通过tcp发送
+		if baseHeader.Zstd {
+			ReleaseRecvBuffer(recvBuffer)
+			r.logReceiveError(size, remoteAddr, fmt.Errorf("zstd compressed message type %d is not supported by udp", baseHeader.Type))
+			continue
+		}
 
 		headerLen := datatype.MESSAGE_HEADER_LEN
 		metricsTimestamp, vtapID, sequence := uint32(0), uint16(0), uint64(0)
@@ -815,6 +829,20 @@ func ReadN(r *bufio.Reader, buffer []byte) error {
 	return nil
 }
 
+func (r *Receiver) decompressZstd(data []byte) ([]byte, error) {
+	if r.zstdDecoder == nil {
+		return nil, fmt.Errorf("zstd decoder is not initialized")
+	}
+	decompressed, err := r.zstdDecoder.DecodeAll(data, nil)
+	if err != nil {
+		return nil, err
+	}
+	if len(decompressed) > RECV_BUFSIZE_MAX {
+		return nil, fmt.Errorf("decompressed size %d exceeds %d", len(decompressed), RECV_BUFSIZE_MAX)
+	}
+	return decompressed, nil
+}
+
 func (r *Receiver) handleTCPConnection(conn net.Conn) {
 	defer conn.Close()
 	defer r.flushPutTCPQueues()
@@ -897,6 +925,20 @@ func (r *Receiver) handleTCPConnection(conn net.Conn) {
 			log.Warningf("TCP client(%s) connection read error.%s", conn.RemoteAddr().String(), err.Error())
 			return
 		}
+		if baseHeader.Zstd {
+			decompressed, err := r.decompressZstd(recvBuffer.Buffer[:dataLen])
+			ReleaseRecvBuffer(recvBuffer)
+			if err != nil {
+				r.logTCPReceiveInvalidData(fmt.Sprintf("TCP client(%s) zstd decompress failed: %s", conn.RemoteAddr().String(), err))
+				continue
+			}
+			dataLen = len(decompressed)
+			recvBuffer, isNew = AcquireRecvBuffer(dataLen, TCP)
+			if isNew {
+				r.counter.NewBufferCount++
+			}
+			copy(recvBuffer.Buffer, decompressed)
+		}
 
 		if baseHeader.Type == datatype.MESSAGE_TYPE_METRICS {
 			metricsTimestamp = r.getMetricsTimestamp(recvBuffer.Buffer)
@@ -911,7 +953,7 @@ func (r *Receiver) handleTCPConnection(conn net.Conn) {
 			ReleaseRecvBuffer(recvBuffer)
 		} else {
 			recvBuffer.Begin = 0
-			recvBuffer.End = int(baseHeader.FrameSize) - headerLen
+			recvBuffer.End = dataLen
 			recvBuffer.IP = ip
 			recvBuffer.VtapID = vtapID
 			r.putTCPQueue(int(r.counter.RxPackets), r.handlers[baseHeader.Type], recvBuffer)
@@ -135,6 +135,8 @@ type AppTraffic struct {
 	Request        uint32 `db:"request"`
 	Response       uint32 `db:"response"`
 	DirectionScore uint8  `db:"direction_score"`
+	ByteTx         uint64 `db:"byte_tx"`
+	ByteRx         uint64 `db:"byte_rx"`
 }
 
 func (_ *AppTraffic) Reverse() {
@@ -145,12 +147,16 @@ func (t *AppTraffic) WriteToPB(p *pb.AppTraffic) {
 	p.Request = t.Request
 	p.Response = t.Response
 	p.DirectionScore = uint32(t.DirectionScore)
+	p.ByteTx = t.ByteTx
+	p.ByteRx = t.ByteRx
 }
 
 func (t *AppTraffic) ReadFromPB(p *pb.AppTraffic) {
 	t.Request = p.Request
 	t.Response = p.Response
 	t.DirectionScore = uint8(p.DirectionScore)
+	t.ByteTx = p.ByteTx
+	t.ByteRx = p.ByteRx
 }
 
 func (t *AppTraffic) ConcurrentMerge(other *AppTraffic) {
@@ -159,6 +165,8 @@ func (t *AppTraffic) ConcurrentMerge(other *AppTraffic) {
 	if t.DirectionScore < other.DirectionScore {
 		t.DirectionScore = other.DirectionScore
 	}
+	t.ByteTx += other.ByteTx
+	t.ByteRx += other.ByteRx
 }
 
 func (t *AppTraffic) SequentialMerge(other *AppTraffic) {
@@ -166,8 +174,8 @@ func (t *AppTraffic) SequentialMerge(other *AppTraffic) {
 }
 
 func (t *AppTraffic) MarshalTo(b []byte) int {
-	fields := []string{"request=", "response=", "direction_score"}
-	values := []uint64{uint64(t.Request), uint64(t.Response), uint64(t.DirectionScore)}
+	fields := []string{"request=", "response=", "direction_score", "byte_tx=", "byte_rx="}
+	values := []uint64{uint64(t.Request), uint64(t.Response), uint64(t.DirectionScore), t.ByteTx, t.ByteRx}
 	return marshalKeyValues(b, fields, values)
 }
 
@@ -183,18 +191,29 @@ func AppTrafficColumns() []*ckdb.Column {
 	columns = append(columns, ckdb.NewColumn("request", ckdb.UInt32).SetComment("累计请求次数"))
 	columns = append(columns, ckdb.NewColumn("response", ckdb.UInt32).SetComment("累计响应次数"))
 	columns = append(columns, ckdb.NewColumn("direction_score", ckdb.UInt8).SetComment("for correcting direction").SetIndex(ckdb.IndexMinmax))
+	columns = append(columns, ckdb.NewColumn("byte_tx", ckdb.UInt64).SetComment("客户端发送的字节数"))
+	columns = append(columns, ckdb.NewColumn("byte_rx", ckdb.UInt64).SetComment("服务端发送的字节数"))
 	return columns
 }
 
 // WriteBlock和LatencyColumns的列需要按顺序一一对应
 func (t *AppTraffic) WriteBlock(block *ckdb.Block) {
-	block.Write(t.Request, t.Response, t.DirectionScore)
+	block.Write(t.Request, t.Response, t.DirectionScore, t.ByteTx, t.ByteRx)
 }
 
 type AppLatency struct {
 	RRTMax   uint32 `db:"rrt_max"` // us
 	RRTSum   uint64 `db:"rrt_sum"` // us
 	RRTCount uint32 `db:"rrt_count"`
+
+	// the upper bound of bucket i is RRTHistogramStart * RRTHistogramFactor^i us
+	RRTHistogramStart  uint32   `db:"rrt_histogram_start"`
+	RRTHistogramFactor uint32   `db:"rrt_histogram_factor"`
+	RRTHistogram       []uint32 `db:"rrt_histogram"`
+
+	ApdexSatisfied  uint32 `db:"apdex_satisfied"`
+	ApdexTolerating uint32 `db:"apdex_tolerating"`
+	ApdexFrustrated uint32 `db:"apdex_frustrated"`
 }
 
 func (_ *AppLatency) Reverse() {
@@ -205,12 +224,24 @@ func (l *AppLatency) WriteToPB(p *pb.AppLatency) {
 	p.RrtMax = l.RRTMax
 	p.RrtSum = l.RRTSum
 	p.RrtCount = l.RRTCount
+	p.RrtHistogramStart = l.RRTHistogramStart
+	p.RrtHistogramFactor = l.RRTHistogramFactor
+	p.RrtHistogram = append(p.RrtHistogram[:0], l.RRTHistogram...)
+	p.ApdexSatisfied = l.ApdexSatisfied
+	p.ApdexTolerating = l.ApdexTolerating
+	p.ApdexFrustrated = l.ApdexFrustrated
 }
 
 func (l *AppLatency) ReadFromPB(p *pb.AppLatency) {
 	l.RRTMax = p.RrtMax
 	l.RRTSum = p.RrtSum
 	l.RRTCount = p.RrtCount
+	l.RRTHistogramStart = p.RrtHistogramStart
+	l.RRTHistogramFactor = p.RrtHistogramFactor
+	l.RRTHistogram = append(l.RRTHistogram[:0], p.RrtHistogram...)
+	l.ApdexSatisfied = p.ApdexSatisfied
+	l.ApdexTolerating = p.ApdexTolerating
+	l.ApdexFrustrated = p.ApdexFrustrated
 }
 
 func (l *AppLatency) ConcurrentMerge(other *AppLatency) {
@@ -219,6 +250,20 @@ func (l *AppLatency) ConcurrentMerge(other *AppLatency) {
 	}
 	l.RRTSum += other.RRTSum
 	l.RRTCount += other.RRTCount
+	if l.RRTHistogramStart == 0 {
+		l.RRTHistogramStart = other.RRTHistogramStart
+		l.RRTHistogramFactor = other.RRTHistogramFactor
+	}
+	for i, v := range other.RRTHistogram {
+		if i < len(l.RRTHistogram) {
+			l.RRTHistogram[i] += v
+		} else {
+			l.RRTHistogram = append(l.RRTHistogram, v)
+		}
+	}
+	l.ApdexSatisfied += other.ApdexSatisfied
+	l.ApdexTolerating += other.ApdexTolerating
+	l.ApdexFrustrated += other.ApdexFrustrated
 }
 
 func (l *AppLatency) SequentialMerge(other *AppLatency) {
@@ -226,8 +271,10 @@ func (l *AppLatency) SequentialMerge(other *AppLatency) {
 }
 
 func (l *AppLatency) MarshalTo(b []byte) int {
-	fields := []string{"rrt_sum=", "rrt_count=", "rrt_max="}
-	values := []uint64{l.RRTSum, uint64(l.RRTCount), uint64(l.RRTMax)}
+	fields := []string{"rrt_sum=", "rrt_count=", "rrt_max=", "rrt_histogram_start=", "rrt_histogram_factor=",
+		"apdex_satisfied=", "apdex_tolerating=", "apdex_frustrated="}
+	values := []uint64{l.RRTSum, uint64(l.RRTCount), uint64(l.RRTMax), uint64(l.RRTHistogramStart), uint64(l.RRTHistogramFactor),
+		uint64(l.ApdexSatisfied), uint64(l.ApdexTolerating), uint64(l.ApdexFrustrated)}
 	return marshalKeyValues(b, fields, values)
 }
 
@@ -243,12 +290,19 @@ func AppLatencyColumns() []*ckdb.Column {
 	columns = append(columns, ckdb.NewColumn("rrt_max", ckdb.UInt32).SetComment("所有请求响应时延最大值(us)"))
 	columns = append(columns, ckdb.NewColumn("rrt_sum", ckdb.Float64).SetComment("累计所有请求响应时延(us)"))
 	columns = append(columns, ckdb.NewColumn("rrt_count", ckdb.UInt64).SetComment("请求响应时延计算次数"))
+	columns = append(columns, ckdb.NewColumn("rrt_histogram_start", ckdb.UInt32).SetComment("请求响应时延直方图首个桶的上界(us)"))
+	columns = append(columns, ckdb.NewColumn("rrt_histogram_factor", ckdb.UInt32).SetComment("请求响应时延直方图相邻桶上界的倍数"))
+	columns = append(columns, ckdb.NewColumn("rrt_histogram", ckdb.ArrayUInt32).SetComment("请求响应时延直方图各桶的计数"))
+	columns = append(columns, ckdb.NewColumn("apdex_satisfied", ckdb.UInt32).SetComment("响应时延满意的请求次数"))
+	columns = append(columns, ckdb.NewColumn("apdex_tolerating", ckdb.UInt32).SetComment("响应时延可容忍的请求次数"))
+	columns = append(columns, ckdb.NewColumn("apdex_frustrated", ckdb.UInt32).SetComment("响应时延不满意或异常的请求次数"))
 	return columns
 }
 
 // WriteBlock和LatencyColumns的列需要按顺序一一对应
 func (l *AppLatency) WriteBlock(block *ckdb.Block) {
-	block.Write(l.RRTMax, float64(l.RRTSum), uint64(l.RRTCount))
+	block.Write(l.RRTMax, float64(l.RRTSum), uint64(l.RRTCount), l.RRTHistogramStart, l.RRTHistogramFactor, l.RRTHistogram,
+		l.ApdexSatisfied, l.ApdexTolerating, l.ApdexFrustrated)
 }
 
 type AppAnomaly struct {
@@ -221,29 +221,38 @@ func (t *Traffic) WriteBlock(block *ckdb.Block) {
 }
 
 type Latency struct {
-	RTTMax       uint32 `db:"rtt_max"`        // us，Trident保证时延最大值不会超过3600s，能容纳在u32内
-	RTTClientMax uint32 `db:"rtt_client_max"` // us
-	RTTServerMax uint32 `db:"rtt_server_max"` // us
-	SRTMax       uint32 `db:"srt_max"`        // us
-	ARTMax       uint32 `db:"art_max"`        // us
-	RRTMax       uint32 `db:"rrt_max"`        // us
-	CITMax       uint32 `db:"cit_max"`        // client idle time max
-
-	RTTSum       uint64 `db:"rtt_sum"`        // us
-	RTTClientSum uint64 `db:"rtt_client_sum"` // us
-	RTTServerSum uint64 `db:"rtt_server_sum"` // us
-	SRTSum       uint64 `db:"srt_sum"`        // us
-	ARTSum       uint64 `db:"art_sum"`        // us
-	RRTSum       uint64 `db:"rrt_sum"`        // us
-	CITSum       uint64 `db:"cit_sum"`
-
-	RTTCount       uint32 `db:"rtt_count"`
-	RTTClientCount uint32 `db:"rtt_client_count"`
-	RTTServerCount uint32 `db:"rtt_server_count"`
-	SRTCount       uint32 `db:"srt_count"`
-	ARTCount       uint32 `db:"art_count"`
-	RRTCount       uint32 `db:"rrt_count"`
-	CITCount       uint32 `db:"cit_count"`
+	RTTMax          uint32 `db:"rtt_max"`            // us，Trident保证时延最大值不会超过3600s，能容纳在u32内
+	RTTClientMax    uint32 `db:"rtt_client_max"`     // us
+	RTTServerMax    uint32 `db:"rtt_server_max"`     // us
+	SRTMax          uint32 `db:"srt_max"`            // us
+	ARTMax          uint32 `db:"art_max"`            // us
+	RRTMax          uint32 `db:"rrt_max"`            // us
+	CITMax          uint32 `db:"cit_max"`            // client idle time max
+	TLSRTTMax       uint32 `db:"tls_rtt_max"`        // us
+	AckRTTClientMax uint32 `db:"ack_rtt_client_max"` // us
+	AckRTTServerMax uint32 `db:"ack_rtt_server_max"` // us
+
+	RTTSum          uint64 `db:"rtt_sum"`            // us
+	RTTClientSum    uint64 `db:"rtt_client_sum"`     // us
+	RTTServerSum    uint64 `db:"rtt_server_sum"`     // us
+	SRTSum          uint64 `db:"srt_sum"`            // us
+	ARTSum          uint64 `db:"art_sum"`            // us
+	RRTSum          uint64 `db:"rrt_sum"`            // us
+	CITSum          uint64 `db:"cit_sum"`
+	TLSRTTSum       uint64 `db:"tls_rtt_sum"`        // us
+	AckRTTClientSum uint64 `db:"ack_rtt_client_sum"` // us
+	AckRTTServerSum uint64 `db:"ack_rtt_server_sum"` // us
+
+	RTTCount          uint32 `db:"rtt_count"`
+	RTTClientCount    uint32 `db:"rtt_client_count"`
+	RTTServerCount    uint32 `db:"rtt_server_count"`
+	SRTCount          uint32 `db:"srt_count"`
+	ARTCount          uint32 `db:"art_count"`
+	RRTCount          uint32 `db:"rrt_count"`
+	CITCount          uint32 `db:"cit_count"`
+	TLSRTTCount       uint32 `db:"tls_rtt_count"`
+	AckRTTClientCount uint32 `db:"ack_rtt_client_count"`
+	AckRTTServerCount uint32 `db:"ack_rtt_server_count"`
 }
 
 func (_ *Latency) Reverse() {
@@ -258,6 +267,9 @@ func (l *Latency) WriteToPB(p *pb.Latency) {
 	p.ArtMax = l.ARTMax
 	p.RrtMax = l.RRTMax
 	p.CitMax = l.CITMax
+	p.TlsRttMax = l.TLSRTTMax
+	p.AckRttClientMax = l.AckRTTClientMax
+	p.AckRttServerMax = l.AckRTTServerMax
 
 	p.RttSum = l.RTTSum
 	p.RttClientSum = l.RTTClientSum
@@ -266,6 +278,9 @@ func (l *Latency) WriteToPB(p *pb.Latency) {
 	p.ArtSum = l.ARTSum
 	p.RrtSum = l.RRTSum
 	p.CitSum = l.CITSum
+	p.TlsRttSum = l.TLSRTTSum
+	p.AckRttClientSum = l.AckRTTClientSum
+	p.AckRttServerSum = l.AckRTTServerSum
 
 	p.RttCount = l.RTTCount
 	p.RttClientCount = l.RTTClientCount
@@ -274,6 +289,9 @@ func (l *Latency) WriteToPB(p *pb.Latency) {
 	p.ArtCount = l.ARTCount
 	p.RrtCount = l.RRTCount
 	p.CitCount = l.CITCount
+	p.TlsRttCount = l.TLSRTTCount
+	p.AckRttClientCount = l.AckRTTClientCount
+	p.AckRttServerCount = l.AckRTTServerCount
 }
 
 func (l *Latency) ReadFromPB(p *pb.Latency) {
@@ -284,6 +302,9 @@ func (l *Latency) ReadFromPB(p *pb.Latency) {
 	l.ARTMax = p.ArtMax
 	l.RRTMax = p.RrtMax
 	l.CITMax = p.CitMax
+	l.TLSRTTMax = p.TlsRttMax
+	l.AckRTTClientMax = p.AckRttClientMax
+	l.AckRTTServerMax = p.AckRttServerMax
 
 	l.RTTSum = p.RttSum
 	l.RTTClientSum = p.RttClientSum
@@ -292,6 +313,9 @@ func (l *Latency) ReadFromPB(p *pb.Latency) {
 	l.ARTSum = p.ArtSum
 	l.RRTSum = p.RrtSum
 	l.CITSum = p.CitSum
+	l.TLSRTTSum = p.TlsRttSum
+	l.AckRTTClientSum = p.AckRttClientSum
+	l.AckRTTServerSum = p.AckRttServerSum
 
 	l.RTTCount = p.RttCount
 	l.RTTClientCount = p.RttClientCount
@@ -300,6 +324,9 @@ func (l *Latency) ReadFromPB(p *pb.Latency) {
 	l.ARTCount = p.ArtCount
 	l.RRTCount = p.RrtCount
 	l.CITCount = p.CitCount
+	l.TLSRTTCount = p.TlsRttCount
+	l.AckRTTClientCount = p.AckRttClientCount
+	l.AckRTTServerCount = p.AckRttServerCount
 }
 
 func (l *Latency) ConcurrentMerge(other *Latency) {
@@ -324,6 +351,15 @@ func (l *Latency) ConcurrentMerge(other *Latency) {
 	if l.CITMax < other.CITMax {
 		l.CITMax = other.CITMax
 	}
+	if l.TLSRTTMax < other.TLSRTTMax {
+		l.TLSRTTMax = other.TLSRTTMax
+	}
+	if l.AckRTTClientMax < other.AckRTTClientMax {
+		l.AckRTTClientMax = other.AckRTTClientMax
+	}
+	if l.AckRTTServerMax < other.AckRTTServerMax {
+		l.AckRTTServerMax = other.AckRTTServerMax
+	}
 
 	l.RTTSum += other.RTTSum
 	l.RTTClientSum += other.RTTClientSum
@@ -332,6 +368,9 @@ func (l *Latency) ConcurrentMerge(other *Latency) {
 	l.ARTSum += other.ARTSum
 	l.RRTSum += other.RRTSum
 	l.CITSum += other.CITSum
+	l.TLSRTTSum += other.TLSRTTSum
+	l.AckRTTClientSum += other.AckRTTClientSum
+	l.AckRTTServerSum += other.AckRTTServerSum
 
 	l.RTTCount += other.RTTCount
 	l.RTTClientCount += other.RTTClientCount
@@ -340,6 +379,9 @@ func (l *Latency) ConcurrentMerge(other *Latency) {
 	l.ARTCount += other.ARTCount
 	l.RRTCount += other.RRTCount
 	l.CITCount += other.CITCount
+	l.TLSRTTCount += other.TLSRTTCount
+	l.AckRTTClientCount += other.AckRTTClientCount
+	l.AckRTTServerCount += other.AckRTTServerCount
 }
 
 func (l *Latency) SequentialMerge(other *Latency) {
@@ -347,13 +389,13 @@ func (l *Latency) SequentialMerge(other *Latency) {
 }
 
 func (l *Latency) MarshalTo(b []byte) int {
-	fields := []string{"rtt_sum=", "rtt_client_sum=", "rtt_server_sum=", "srt_sum=", "art_sum=", "rrt_sum=", "cit_sum=",
-		"rtt_count=", "rtt_client_count=", "rtt_server_count=", "srt_count=", "art_count=", "rrt_count=", "cit_count",
-		"rtt_max=", "rtt_client_max=", "rtt_server_max=", "srt_max=", "art_max=", "rrt_max=", "cit_max="}
+	fields := []string{"rtt_sum=", "rtt_client_sum=", "rtt_server_sum=", "srt_sum=", "art_sum=", "rrt_sum=", "cit_sum=", "tls_rtt_sum=", "ack_rtt_client_sum=", "ack_rtt_server_sum=",
+		"rtt_count=", "rtt_client_count=", "rtt_server_count=", "srt_count=", "art_count=", "rrt_count=", "cit_count", "tls_rtt_count=", "ack_rtt_client_count=", "ack_rtt_server_count=",
+		"rtt_max=", "rtt_client_max=", "rtt_server_max=", "srt_max=", "art_max=", "rrt_max=", "cit_max=", "tls_rtt_max=", "ack_rtt_client_max=", "ack_rtt_server_max="}
 	values := []uint64{
-		l.RTTSum, l.RTTClientSum, l.RTTServerSum, l.SRTSum, l.ARTSum, l.RRTSum, l.CITSum,
-		uint64(l.RTTCount), uint64(l.RTTClientCount), uint64(l.RTTServerCount), uint64(l.SRTCount), uint64(l.ARTCount), uint64(l.RRTCount), uint64(l.CITCount),
-		uint64(l.RTTMax), uint64(l.RTTClientMax), uint64(l.RTTServerMax), uint64(l.SRTMax), uint64(l.ARTMax), uint64(l.RRTMax), uint64(l.CITMax),
+		l.RTTSum, l.RTTClientSum, l.RTTServerSum, l.SRTSum, l.ARTSum, l.RRTSum, l.CITSum, l.TLSRTTSum, l.AckRTTClientSum, l.AckRTTServerSum,
+		uint64(l.RTTCount), uint64(l.RTTClientCount), uint64(l.RTTServerCount), uint64(l.SRTCount), uint64(l.ARTCount), uint64(l.RRTCount), uint64(l.CITCount), uint64(l.TLSRTTCount), uint64(l.AckRTTClientCount), uint64(l.AckRTTServerCount),
+		uint64(l.RTTMax), uint64(l.RTTClientMax), uint64(l.RTTServerMax), uint64(l.SRTMax), uint64(l.ARTMax), uint64(l.RRTMax), uint64(l.CITMax), uint64(l.TLSRTTMax), uint64(l.AckRTTClientMax), uint64(l.AckRTTServerMax),
 	}
 	return marshalKeyValues(b, fields, values)
 }
@@ -366,41 +408,53 @@ const (
 	LATENCY_ART
 	LATENCY_RRT
 	LATENCY_CIT
+	LATENCY_TLS_RTT
+	LATENCY_ACK_RTT_CLIENT
+	LATENCY_ACK_RTT_SERVER
 )
 
 // Columns列和WriteBlock的列需要按顺序一一对应
 func LatencyColumns() []*ckdb.Column {
 	sumColumns := ckdb.NewColumnsWithComment(
 		[][2]string{
-			LATENCY_RTT:        {"rtt_sum", "累计建立连接RTT(us)"},
-			LATENCY_RTT_CLIENT: {"rtt_client_sum", "客户端累计建立连接RTT(us)"},
-			LATENCY_RTT_SERVER: {"rtt_server_sum", "服务端累计建立连接RTT(us)"},
-			LATENCY_SRT:        {"srt_sum", "累计所有系统响应时延(us)"},
-			LATENCY_ART:        {"art_sum", "累计所有应用响应时延(us)"},
-			LATENCY_RRT:        {"rrt_sum", "累计所有应用请求响应时延(us)"},
-			LATENCY_CIT:        {"cit_sum", "Total client idle time(us)"},
+			LATENCY_RTT:            {"rtt_sum", "累计建立连接RTT(us)"},
+			LATENCY_RTT_CLIENT:     {"rtt_client_sum", "客户端累计建立连接RTT(us)"},
+			LATENCY_RTT_SERVER:     {"rtt_server_sum", "服务端累计建立连接RTT(us)"},
+			LATENCY_SRT:            {"srt_sum", "累计所有系统响应时延(us)"},
+			LATENCY_ART:            {"art_sum", "累计所有应用响应时延(us)"},
+			LATENCY_RRT:            {"rrt_sum", "累计所有应用请求响应时延(us)"},
+			LATENCY_CIT:            {"cit_sum", "Total client idle time(us)"},
+			LATENCY_TLS_RTT:        {"tls_rtt_sum", "Total tls handshake time(us)"},
+			LATENCY_ACK_RTT_CLIENT: {"ack_rtt_client_sum", "Total client rtt sampled from data segments(us)"},
+			LATENCY_ACK_RTT_SERVER: {"ack_rtt_server_sum", "Total server rtt sampled from data segments(us)"},
 		},
 		ckdb.Float64)
 	counterColumns := ckdb.NewColumnsWithComment(
 		[][2]string{
-			LATENCY_RTT:        {"rtt_count", "建立连接时延计算次数"},
-			LATENCY_RTT_CLIENT: {"rtt_client_count", "客户端建立连接时延计算次数"},
-			LATENCY_RTT_SERVER: {"rtt_server_count", "服务端建立连接时延计算次数"},
-			LATENCY_SRT:        {"srt_count", "系统响应时延计算次数"},
-			LATENCY_ART:        {"art_count", "应用响应时延计算次数"},
-			LATENCY_RRT:        {"rrt_count", "应用请求响应时延计算次数"},
-			LATENCY_CIT:        {"cit_count", "Client idle time calculation times"},
+			LATENCY_RTT:            {"rtt_count", "建立连接时延计算次数"},
+			LATENCY_RTT_CLIENT:     {"rtt_client_count", "客户端建立连接时延计算次数"},
+			LATENCY_RTT_SERVER:     {"rtt_server_count", "服务端建立连接时延计算次数"},
+			LATENCY_SRT:            {"srt_count", "系统响应时延计算次数"},
+			LATENCY_ART:            {"art_count", "应用响应时延计算次数"},
+			LATENCY_RRT:            {"rrt_count", "应用请求响应时延计算次数"},
+			LATENCY_CIT:            {"cit_count", "Client idle time calculation times"},
+			LATENCY_TLS_RTT:        {"tls_rtt_count", "Tls handshake time calculation times"},
+			LATENCY_ACK_RTT_CLIENT: {"ack_rtt_client_count", "Client rtt sampled from data segments calculation times"},
+			LATENCY_ACK_RTT_SERVER: {"ack_rtt_server_count", "Server rtt sampled from data segments calculation times"},
 		},
 		ckdb.UInt64)
 	maxColumns := ckdb.NewColumnsWithComment(
 		[][2]string{
-			LATENCY_RTT:        {"rtt_max", "建立连接RTT最大值(us)"},
-			LATENCY_RTT_CLIENT: {"rtt_client_max", "客户端建立连接RTT最大值(us)"},
-			LATENCY_RTT_SERVER: {"rtt_server_max", "服务端建立连接RTT最大值(us)"},
-			LATENCY_SRT:        {"srt_max", "所有系统响应时延最大值(us)"},
-			LATENCY_ART:        {"art_max", "所有应用响应时延最大值(us)"},
-			LATENCY_RRT:        {"rrt_max", "所有应用请求响应时延最大值(us)"},
-			LATENCY_CIT:        {"cit_max", "Max client idle time(us)"},
+			LATENCY_RTT:            {"rtt_max", "建立连接RTT最大值(us)"},
+			LATENCY_RTT_CLIENT:     {"rtt_client_max", "客户端建立连接RTT最大值(us)"},
+			LATENCY_RTT_SERVER:     {"rtt_server_max", "服务端建立连接RTT最大值(us)"},
+			LATENCY_SRT:            {"srt_max", "所有系统响应时延最大值(us)"},
+			LATENCY_ART:            {"art_max", "所有应用响应时延最大值(us)"},
+			LATENCY_RRT:            {"rrt_max", "所有应用请求响应时延最大值(us)"},
+			LATENCY_CIT:            {"cit_max", "Max client idle time(us)"},
+			LATENCY_TLS_RTT:        {"tls_rtt_max", "Max tls handshake time(us)"},
+			LATENCY_ACK_RTT_CLIENT: {"ack_rtt_client_max", "Max client rtt sampled from data segments(us)"},
+			LATENCY_ACK_RTT_SERVER: {"ack_rtt_server_max", "Max server rtt sampled from data segments(us)"},
 		}, ckdb.UInt32)
 	for _, c := range maxColumns {
 		c.SetIndex(ckdb.IndexNone)
@@ -422,6 +476,9 @@ func (l *Latency) WriteBlock(block *ckdb.Block) {
 		float64(l.ARTSum),
 		float64(l.RRTSum),
 		float64(l.CITSum),
+		float64(l.TLSRTTSum),
+		float64(l.AckRTTClientSum),
+		float64(l.AckRTTServerSum),
 
 		uint64(l.RTTCount),
 		uint64(l.RTTClientCount),
@@ -430,6 +487,9 @@ func (l *Latency) WriteBlock(block *ckdb.Block) {
 		uint64(l.ARTCount),
 		uint64(l.RRTCount),
 		uint64(l.CITCount),
+		uint64(l.TLSRTTCount),
+		uint64(l.AckRTTClientCount),
+		uint64(l.AckRTTServerCount),
 
 		l.RTTMax,
 		l.RTTClientMax,
@@ -438,6 +498,9 @@ func (l *Latency) WriteBlock(block *ckdb.Block) {
 		l.ARTMax,
 		l.RRTMax,
 		l.CITMax,
+		l.TLSRTTMax,
+		l.AckRTTClientMax,
+		l.AckRTTServerMax,
 	)
 
 }
@@ -449,6 +512,8 @@ type Performance struct {
 	ZeroWinRx     uint64 `db:"zero_win_rx"`
 	RetransSyn    uint32 `db:"retrans_syn"`
 	RetransSynack uint32 `db:"retrans_synack"`
+	DupAckTx      uint64 `db:"dup_ack_tx"`
+	DupAckRx      uint64 `db:"dup_ack_rx"`
 }
 
 func (a *Performance) Reverse() {
@@ -462,6 +527,8 @@ func (a *Performance) WriteToPB(p *pb.Performance) {
 	p.ZeroWinRx = a.ZeroWinRx
 	p.RetransSyn = a.RetransSyn
 	p.RetransSynack = a.RetransSynack
+	p.DupAckTx = a.DupAckTx
+	p.DupAckRx = a.DupAckRx
 }
 
 func (a *Performance) ReadFromPB(p *pb.Performance) {
@@ -471,6 +538,8 @@ func (a *Performance) ReadFromPB(p *pb.Performance) {
 	a.ZeroWinRx = p.ZeroWinRx
 	a.RetransSyn = p.RetransSyn
 	a.RetransSynack = p.RetransSynack
+	a.DupAckTx = p.DupAckTx
+	a.DupAckRx = p.DupAckRx
 }
 
 func (a *Performance) ConcurrentMerge(other *Performance) {
@@ -480,6 +549,8 @@ func (a *Performance) ConcurrentMerge(other *Performance) {
 	a.ZeroWinRx += other.ZeroWinRx
 	a.RetransSyn += other.RetransSyn
 	a.RetransSynack += other.RetransSynack
+	a.DupAckTx += other.DupAckTx
+	a.DupAckRx += other.DupAckRx
 }
 
 func (a *Performance) SequentialMerge(other *Performance) {
@@ -489,9 +560,11 @@ func (a *Performance) SequentialMerge(other *Performance) {
 func (a *Performance) MarshalTo(b []byte) int {
 	fields := []string{
 		"retrans_tx=", "retrans_rx=", "retrans=", "zero_win_tx=", "zero_win_rx=", "zero_win=", "retrans_syn=", "retrans_synack",
+		"dup_ack_tx=", "dup_ack_rx=",
 	}
 	values := []uint64{
 		a.RetransTx, a.RetransRx, a.RetransTx + a.RetransRx, a.ZeroWinTx, a.ZeroWinRx, a.ZeroWinTx + a.ZeroWinRx, uint64(a.RetransSyn), uint64(a.RetransSynack),
+		a.DupAckTx, a.DupAckRx,
 	}
 	return marshalKeyValues(b, fields, values)
 }
@@ -507,6 +580,9 @@ const (
 
 	PERF_RETRANS_SYN
 	PERF_RETRANS_SYNACK
+
+	PERF_DUP_ACK_TX
+	PERF_DUP_ACK_RX
 )
 
 // Columns列和WriteBlock的列需要按顺序一一对应
@@ -523,6 +599,9 @@ func PerformanceColumns() []*ckdb.Column {
 
 			PERF_RETRANS_SYN:    {"retrans_syn", "Total client retransmit SYN times"},
 			PERF_RETRANS_SYNACK: {"retrans_synack", "Total server retransmit SYNACK times"},
+
+			PERF_DUP_ACK_TX: {"dup_ack_tx", "Total client duplicate ACK times"},
+			PERF_DUP_ACK_RX: {"dup_ack_rx", "Total server duplicate ACK times"},
 		},
 		ckdb.UInt64)
 }
@@ -533,6 +612,7 @@ func (a *Performance) WriteBlock(block *ckdb.Block) {
 		a.RetransTx, a.RetransRx, a.RetransTx+a.RetransRx,
 		a.ZeroWinTx, a.ZeroWinRx, a.ZeroWinTx+a.ZeroWinRx,
 		uint64(a.RetransSyn), uint64(a.RetransSynack),
+		a.DupAckTx, a.DupAckRx,
 	)
 }
 